@@ -147,6 +147,47 @@ pub fn run_basic_dynamic_function(store: &Store, compiler_name: &str, c: &mut Cr
     );
 }
 
+pub fn run_basic_host_function(store: &Store, compiler_name: &str, c: &mut Criterion) {
+    let module = Module::new(&store, BASIC_WAT).unwrap();
+
+    let import_object = imports! {
+        "env" => {
+            "multiply" => Function::new_native(&store, |a: i32, b: i32| a * b),
+        },
+    };
+    let instance = Instance::new(&module, &import_object).unwrap();
+    let f: NativeFunc<(i32, i32), i32> = instance
+        .exports
+        .get_native_function("double_then_add")
+        .unwrap();
+    c.bench_function(&format!("basic static host func {}", compiler_name), |b| {
+        b.iter(|| {
+            let result = black_box(f.call(4, 6).unwrap());
+            assert_eq!(result, 20);
+        })
+    });
+
+    let multiply_signature = FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]);
+    let import_object = imports! {
+        "env" => {
+            "multiply" => Function::new(&store, &multiply_signature, |args| {
+                Ok(vec![Val::I32(args[0].unwrap_i32() * args[1].unwrap_i32())])
+            }),
+        },
+    };
+    let instance = Instance::new(&module, &import_object).unwrap();
+    let f: NativeFunc<(i32, i32), i32> = instance
+        .exports
+        .get_native_function("double_then_add")
+        .unwrap();
+    c.bench_function(&format!("basic dynamic host func {}", compiler_name), |b| {
+        b.iter(|| {
+            let result = black_box(f.call(4, 6).unwrap());
+            assert_eq!(result, 20);
+        })
+    });
+}
+
 fn run_static_benchmarks(c: &mut Criterion) {
     #[cfg(feature = "llvm")]
     {
@@ -187,6 +228,31 @@ fn run_dynamic_benchmarks(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, run_static_benchmarks, run_dynamic_benchmarks);
+fn run_host_benchmarks(c: &mut Criterion) {
+    #[cfg(feature = "llvm")]
+    {
+        let store = Store::new(&JIT::new(&wasmer_compiler_llvm::LLVM::new()).engine());
+        run_basic_host_function(&store, "llvm", c);
+    }
+
+    #[cfg(feature = "cranelift")]
+    {
+        let store = Store::new(&JIT::new(&wasmer_compiler_cranelift::Cranelift::new()).engine());
+        run_basic_host_function(&store, "cranelift", c);
+    }
+
+    #[cfg(feature = "singlepass")]
+    {
+        let store = Store::new(&JIT::new(&wasmer_compiler_singlepass::Singlepass::new()).engine());
+        run_basic_host_function(&store, "singlepass", c);
+    }
+}
+
+criterion_group!(
+    benches,
+    run_static_benchmarks,
+    run_dynamic_benchmarks,
+    run_host_benchmarks
+);
 
 criterion_main!(benches);
@@ -0,0 +1,66 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use wasmer::{CompilerConfig, Differential, DifferentialCall, DifferentialError, Module, Store};
+use wasmer_compiler_cranelift::Cranelift;
+use wasmer_compiler_singlepass::Singlepass;
+use wasmer_engine_jit::JIT;
+
+/// How long the calls of an input can run before they're interrupted.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+fuzz_target!(|wasm_bytes: &[u8]| {
+    // Both sides canonicalize the NaNs, so that the memories they're
+    // stored in compare equal.
+    let mut singlepass = Singlepass::default();
+    singlepass.canonicalize_nans(true);
+    singlepass.enable_epoch_interruption();
+    let mut cranelift = Cranelift::default();
+    cranelift.canonicalize_nans(true);
+    cranelift.enable_epoch_interruption();
+    let left = Store::new(&JIT::new(&singlepass).engine());
+    let right = Store::new(&JIT::new(&cranelift).engine());
+    let module = match Module::new(&left, wasm_bytes) {
+        Ok(module) => module,
+        Err(_) => return,
+    };
+    // Call every exported function without parameters, in order.
+    let calls = module
+        .exports()
+        .functions()
+        .filter(|export| export.ty().params().is_empty())
+        .map(|export| DifferentialCall::new(export.name(), vec![]))
+        .collect::<Vec<_>>();
+
+    // Interrupt the infinite loops once the timeout elapsed.
+    left.set_epoch_deadline(1);
+    right.set_epoch_deadline(1);
+    let (done, timer) = mpsc::channel::<()>();
+    let watchdog = {
+        let (left, right) = (left.clone(), right.clone());
+        thread::spawn(move || {
+            let timed_out = timer.recv_timeout(TIMEOUT) == Err(mpsc::RecvTimeoutError::Timeout);
+            if timed_out {
+                left.increment_epoch();
+                right.increment_epoch();
+            }
+            timed_out
+        })
+    };
+    let result = Differential::new(left, right).run(wasm_bytes, &calls);
+    drop(done);
+    let timed_out = watchdog.join().unwrap();
+
+    match result {
+        Ok(_)
+        | Err(DifferentialError::Compile { .. })
+        | Err(DifferentialError::Instantiation { .. }) => {}
+        // The timeout may elapse while one side is ahead of the other.
+        Err(_) if timed_out => {}
+        Err(error) => panic!("{}", error),
+    }
+});
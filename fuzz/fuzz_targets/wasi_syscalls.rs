@@ -0,0 +1,95 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use std::convert::TryInto;
+use wasmer::{Extern, Function, Memory, MemoryType, Store, Type, Val};
+use wasmer_compiler_cranelift::Cranelift;
+use wasmer_engine_jit::JIT;
+use wasmer_wasi::{generate_import_object_from_env, LogOutput, WasiState, WasiVersion};
+
+/// Reads the fuzzer input as a sequence of syscalls: one byte picks the
+/// syscall, then each of its parameters takes 4 or 8 bytes.
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (first, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(*first)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn value(&mut self, ty: &Type) -> Option<Val> {
+        match ty {
+            Type::I32 => Some(Val::I32(i32::from_le_bytes(
+                self.bytes(4)?.try_into().unwrap(),
+            ))),
+            Type::I64 => Some(Val::I64(i64::from_le_bytes(
+                self.bytes(8)?.try_into().unwrap(),
+            ))),
+            _ => None,
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let store = Store::new(&JIT::new(&Cranelift::default()).engine());
+    // Nothing is read from or written to the host's standard streams, so
+    // no syscall blocks on them.
+    let mut wasi_env = WasiState::new("fuzz")
+        .stdin(Box::new(LogOutput::stdout("fuzz", "stdin")))
+        .stdout(Box::new(LogOutput::stdout("fuzz", "stdout")))
+        .stderr(Box::new(LogOutput::stderr("fuzz", "stderr")))
+        .finalize()
+        .unwrap();
+    let memory = Memory::new(&store, MemoryType::new(1, None, false)).unwrap();
+    wasi_env.set_memory(memory.clone());
+
+    // The memory starts with a copy of the input, so the pointers given to
+    // the syscalls point at arbitrary data as well.
+    for (cell, byte) in memory.view::<u8>().iter().zip(data) {
+        cell.set(*byte);
+    }
+
+    let import_object = generate_import_object_from_env(&store, wasi_env, WasiVersion::Snapshot1);
+    let mut syscalls: Vec<(String, Function)> = import_object
+        .into_iter()
+        // `poll_oneoff` sleeps as long as the guest asks.
+        .filter(|((_, name), _)| name != "poll_oneoff")
+        .filter_map(
+            |((_, name), export)| match Extern::from_export(&store, export) {
+                Extern::Function(function) => Some((name, function)),
+                _ => None,
+            },
+        )
+        .collect();
+    syscalls.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut input = Input { data };
+    while let Some(index) = input.byte() {
+        let (_, syscall) = &syscalls[index as usize % syscalls.len()];
+        let params = syscall
+            .ty()
+            .params()
+            .iter()
+            .map(|ty| input.value(ty))
+            .collect::<Option<Vec<_>>>();
+        let params = match params {
+            Some(params) => params,
+            None => break,
+        };
+        // Errnos and traps (such as `proc_exit`) are expected, panics are not.
+        let _ = syscall.call(&params);
+    }
+});
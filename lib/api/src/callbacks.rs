@@ -0,0 +1,204 @@
+//! Guest functions passed to the host as callbacks.
+
+use crate::externals::{Function, Table};
+use crate::native::NativeFunc;
+use crate::{FunctionType, RuntimeError, Val, WasmTypeList};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The handle of a callback registered in a [`CallbackTable`].
+///
+/// It can be given to the guest as an `i32`, e.g. to unsubscribe later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(u32);
+
+impl CallbackId {
+    /// Returns the handle as an integer.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for CallbackId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Default)]
+struct Callbacks {
+    function_table: Option<Table>,
+    next_id: u32,
+    functions: HashMap<CallbackId, Function>,
+}
+
+/// The guest functions registered as callbacks by host imports, to be
+/// called later, e.g. when a timer fires or an event comes in.
+///
+/// Guests pass their functions either as `funcref` values or, like C
+/// and Rust do for function pointers, as indices in their function
+/// table. Their signature is checked when they're registered and when
+/// they're called.
+///
+/// It's cheap to clone, all the clones sharing the same callbacks, so
+/// it can be put in the environment of host functions.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let callbacks = CallbackTable::new();
+/// let subscribe = Function::new_native_with_env(
+///     &store,
+///     callbacks.clone(),
+///     |callbacks: &mut CallbackTable, function_pointer: u32| -> u32 {
+///         callbacks
+///             .register_index::<i32, ()>(function_pointer)
+///             .map(CallbackId::as_u32)
+///             .unwrap_or(u32::max_value())
+///     },
+/// );
+/// let module = Module::new(&store, r#"(module
+///     (import "env" "subscribe" (func $subscribe (param i32) (result i32)))
+///     (table (export "__indirect_function_table") 1 funcref)
+///     (elem (i32.const 0) $on_event)
+///     (func $on_event (param i32))
+///     (func (export "start") (drop (call $subscribe (i32.const 0)))))"#)?;
+/// let instance = Instance::new(&module, &imports! {
+///     "env" => { "subscribe" => subscribe },
+/// })?;
+/// callbacks.set_function_table(instance.exports.get_table("__indirect_function_table")?.clone());
+/// instance.exports.get_function("start")?.call(&[])?;
+///
+/// // Later, when an event comes in.
+/// for id in callbacks.ids() {
+///     callbacks.get::<i32, ()>(id)?.call(42)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CallbackTable {
+    callbacks: Arc<Mutex<Callbacks>>,
+}
+
+impl CallbackTable {
+    /// Creates an empty `CallbackTable`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the table that the function indices given to
+    /// [`CallbackTable::register_index`] refer to, usually the exported
+    /// `__indirect_function_table` of the instance.
+    pub fn set_function_table(&self, table: Table) {
+        self.callbacks.lock().unwrap().function_table = Some(table);
+    }
+
+    /// Registers the function at `index` in the function table,
+    /// checking it has the `Args -> Rets` signature.
+    pub fn register_index<Args, Rets>(&self, index: u32) -> Result<CallbackId, RuntimeError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        let table = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .function_table
+            .clone()
+            .ok_or_else(|| RuntimeError::new("no function table to look callbacks up"))?;
+        match table.get(index) {
+            Some(Val::FuncRef(function)) => self.register::<Args, Rets>(function),
+            Some(_) => Err(RuntimeError::new(format!(
+                "the callback at index {} is null",
+                index
+            ))),
+            None => Err(RuntimeError::new(format!(
+                "the callback index {} is out of bounds",
+                index
+            ))),
+        }
+    }
+
+    /// Registers `function`, e.g. a `funcref` given by the guest,
+    /// checking it has the `Args -> Rets` signature.
+    pub fn register<Args, Rets>(&self, function: Function) -> Result<CallbackId, RuntimeError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        check_signature::<Args, Rets>(function.ty())?;
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let id = CallbackId(callbacks.next_id);
+        callbacks.next_id = callbacks
+            .next_id
+            .checked_add(1)
+            .ok_or_else(|| RuntimeError::new("too many callbacks"))?;
+        callbacks.functions.insert(id, function);
+        Ok(id)
+    }
+
+    /// Unregisters a callback, returning whether it was registered.
+    pub fn unregister(&self, id: CallbackId) -> bool {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .functions
+            .remove(&id)
+            .is_some()
+    }
+
+    /// Returns the handles of the registered callbacks.
+    pub fn ids(&self) -> Vec<CallbackId> {
+        let mut ids = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .functions
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
+
+    /// Returns a callback, to be called with the `Args -> Rets`
+    /// signature.
+    pub fn get<Args, Rets>(
+        &self,
+        id: CallbackId,
+    ) -> Result<NativeFunc<'_, Args, Rets>, RuntimeError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        let function = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .functions
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| RuntimeError::new(format!("no callback with id {}", id.0)))?;
+        function.native()
+    }
+}
+
+fn check_signature<Args, Rets>(ty: &FunctionType) -> Result<(), RuntimeError>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    if ty.params() != Args::wasm_types() || ty.results() != Rets::wasm_types() {
+        return Err(RuntimeError::new(format!(
+            "the callback has the signature {}, expected {}",
+            ty,
+            FunctionType::new(Args::wasm_types(), Rets::wasm_types())
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Sharing compiled code between isolated stores.
+
+use crate::module::Module;
+use crate::store::Store;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer_compiler::CompileError;
+#[cfg(feature = "wat")]
+use wasmer_compiler::WasmError;
+use wasmer_engine::Artifact;
+use wasmer_vm::ModuleHash;
+
+/// A cache of compiled modules shared by the stores of an engine, so a
+/// module instantiated in many stores is compiled once.
+///
+/// Only the compiled code, which is read-only, is shared: the modules
+/// returned for a store belong to it, and their instances keep all their
+/// state in it. Stores created with [`Store::isolated`] share their
+/// engine, and thus the cache, while having their own policy, limits
+/// and epoch.
+///
+/// Modules are identified by their [`Module::hash`], so the same module
+/// given as text and as binary is compiled once. Modules are compiled
+/// with the tunables of the first store they're requested for. Each
+/// engine has its own entries, since compiled code can only run in the
+/// engine it was compiled with.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let cache = CodeCache::new();
+/// let tenant_a = Store::default();
+/// let tenant_b = tenant_a.isolated();
+///
+/// let wat = r#"(module (global (export "g") (mut i32) (i32.const 0)))"#;
+/// let module_a = cache.module(&tenant_a, wat)?;
+/// let module_b = cache.module(&tenant_b, wat)?;
+/// assert_eq!(cache.len(), 1);
+///
+/// let instance_a = Instance::new(&module_a, &imports! {})?;
+/// let instance_b = Instance::new(&module_b, &imports! {})?;
+/// instance_a.exports.get_global("g")?.set(Value::I32(1))?;
+/// assert_eq!(instance_b.exports.get_global("g")?.get(), Value::I32(0));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct CodeCache {
+    /// The compiled modules by engine, and by [`Module::hash`].
+    modules: Mutex<HashMap<String, HashMap<ModuleHash, Arc<dyn Artifact>>>>,
+}
+
+impl CodeCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the module of `bytes` for `store`, compiling it if no
+    /// store of the same engine did yet.
+    ///
+    /// Like [`Module::new`], the bytes can be in the WebAssembly text
+    /// format if the "wat" feature is enabled for this crate.
+    pub fn module(&self, store: &Store, bytes: impl AsRef<[u8]>) -> Result<Module, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let binary: &[u8] = bytes.as_ref();
+
+        let engine = store.engine().id().id();
+        let hash = ModuleHash::generate(binary);
+        let cached = self
+            .modules
+            .lock()
+            .unwrap()
+            .get(&engine)
+            .and_then(|modules| modules.get(&hash))
+            .cloned();
+        if let Some(artifact) = cached {
+            return Ok(Module::from_shared_artifact(store, artifact));
+        }
+
+        // compiled without the lock, so other modules can be served
+        // meanwhile; a module compiled twice concurrently is cached once
+        let module = Module::from_binary(store, binary)?;
+        let artifact = self
+            .modules
+            .lock()
+            .unwrap()
+            .entry(engine)
+            .or_insert_with(HashMap::new)
+            .entry(hash)
+            .or_insert_with(|| module.artifact().clone())
+            .clone();
+        Ok(Module::from_shared_artifact(store, artifact))
+    }
+
+    /// Returns the number of compiled modules in the cache.
+    pub fn len(&self) -> usize {
+        self.modules
+            .lock()
+            .unwrap()
+            .values()
+            .map(HashMap::len)
+            .sum()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the modules from the cache. The modules already
+    /// returned keep their code.
+    pub fn clear(&self) {
+        self.modules.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for CodeCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CodeCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
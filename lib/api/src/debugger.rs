@@ -0,0 +1,191 @@
+//! Debugging of the guests at the granularity of their functions, with
+//! a hook called on entry of each function.
+
+use crate::exports::ExportError;
+use crate::externals::{Function, Memory};
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::Store;
+use crate::types::Val;
+use crate::utils::is_wasm;
+use crate::{FrameInfo, InstantiationError, RuntimeError};
+use thiserror::Error;
+use wasmer_compiler::{
+    instrument_function_entries, CompileError, WasmError, DEBUG_ATTACHED_EXPORT,
+    DEBUG_HOOK_SLOT_EXPORT, DEBUG_MEMORY_EXPORT, DEBUG_TABLE_EXPORT,
+};
+use wasmer_engine::Resolver;
+
+/// An error while instantiating a [`DebugModule`].
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum DebugError {
+    /// The module couldn't be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    /// An export added by the instrumentation is missing, so the module
+    /// wasn't instrumented by [`DebugModule::instrument`].
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The hook couldn't be installed.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// A module instrumented to call a hook on entry of each of its
+/// functions, where a debugger can stop the guest, inspect its stack
+/// and its memory, and resume it.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # use std::sync::{Arc, Mutex};
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let wat = r#"(module
+///     (func $double (export "double") (param i32) (result i32)
+///         (i32.mul (local.get 0) (i32.const 2)))
+///     (func (export "run") (result i32)
+///         (call $double (i32.const 21))))"#;
+/// let module = DebugModule::new(&store, wat)?;
+/// let entered = Arc::new(Mutex::new(vec![]));
+/// let recorded = entered.clone();
+/// let instance = module.instantiate(&imports! {}, move |entry: &FunctionEntry| {
+///     recorded.lock().unwrap().push(entry.function_index());
+///     Ok(())
+/// })?;
+/// let run = instance.exports.get_native_function::<(), i32>("run")?;
+/// assert_eq!(run.call()?, 42);
+/// assert_eq!(*entered.lock().unwrap(), vec![1, 0]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct DebugModule {
+    module: Module,
+}
+
+impl DebugModule {
+    /// Instruments the given WebAssembly bytes, e.g. to save them and
+    /// load them later with [`DebugModule::from_instrumented`].
+    ///
+    /// The function indices and names are unchanged, but the offsets of
+    /// the code in the module move.
+    ///
+    /// If the bytes are not WebAssembly-like and the "wat" feature is
+    /// enabled for this crate, they are first converted from the
+    /// WebAssembly text format, like in [`Module::new`].
+    pub fn instrument(bytes: impl AsRef<[u8]>) -> Result<Vec<u8>, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let bytes: &[u8] = bytes.as_ref();
+        if !is_wasm(bytes) {
+            return Err(CompileError::Wasm(WasmError::Generic(
+                "The bytes are not a WebAssembly module".to_string(),
+            )));
+        }
+        Ok(instrument_function_entries(bytes)?)
+    }
+
+    /// Instruments the given WebAssembly bytes, see
+    /// [`DebugModule::instrument`], and compiles them.
+    pub fn new(store: &Store, bytes: impl AsRef<[u8]>) -> Result<Self, CompileError> {
+        let instrumented = Self::instrument(bytes)?;
+        Self::from_instrumented(store, &instrumented)
+    }
+
+    /// Compiles a module instrumented by [`DebugModule::instrument`].
+    pub fn from_instrumented(store: &Store, instrumented: &[u8]) -> Result<Self, CompileError> {
+        Ok(Self {
+            module: Module::from_binary(store, instrumented)?,
+        })
+    }
+
+    /// Returns the instrumented module.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Returns the index of the function named `name` in the name
+    /// section, if any.
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        self.module
+            .info()
+            .function_names
+            .iter()
+            .find(|(_, function_name)| function_name.as_str() == name)
+            .map(|(index, _)| index.as_u32())
+    }
+
+    /// Instantiates the module, and installs the `hook` called on entry
+    /// of each function, once the start function has run.
+    ///
+    /// The guest is stopped while the hook runs. An error returned by
+    /// the hook is raised as a trap in the guest, e.g. to abort it when
+    /// the debugger detaches.
+    pub fn instantiate<H>(&self, resolver: &dyn Resolver, hook: H) -> Result<Instance, DebugError>
+    where
+        H: FnMut(&FunctionEntry) -> Result<(), RuntimeError> + 'static,
+    {
+        let instance = Instance::new(&self.module, resolver)?;
+        let table = instance.exports.get_table(DEBUG_TABLE_EXPORT)?;
+        let hook = Function::new_native_with_env(
+            self.module.store(),
+            DebugHook {
+                hook: Box::new(hook),
+            },
+            |env: &mut DebugHook, function_index: i32| {
+                (env.hook)(&FunctionEntry {
+                    function_index: function_index as u32,
+                })
+            },
+        );
+        let hook_slot = instance
+            .exports
+            .get_global(DEBUG_HOOK_SLOT_EXPORT)?
+            .get()
+            .unwrap_i32();
+        table.set(hook_slot as u32, Val::FuncRef(hook))?;
+        instance
+            .exports
+            .get_global(DEBUG_ATTACHED_EXPORT)?
+            .set(Val::I32(1))?;
+        Ok(instance)
+    }
+
+    /// Returns the first memory of an `instance` of the module, exported
+    /// by the instrumentation, if the module has one.
+    pub fn memory(instance: &Instance) -> Option<&Memory> {
+        instance.exports.get_memory(DEBUG_MEMORY_EXPORT).ok()
+    }
+}
+
+/// The environment of the hook of an instrumented module.
+struct DebugHook {
+    hook: Box<dyn FnMut(&FunctionEntry) -> Result<(), RuntimeError>>,
+}
+
+/// The entry of a function of a [`DebugModule`], passed to its hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionEntry {
+    function_index: u32,
+}
+
+impl FunctionEntry {
+    /// Returns the index of the entered function in the function index
+    /// space.
+    pub fn function_index(&self) -> u32 {
+        self.function_index
+    }
+
+    /// Returns the WebAssembly frames of the stack, the entered function
+    /// first.
+    pub fn frames(&self) -> Vec<FrameInfo> {
+        RuntimeError::new("").trace().to_vec()
+    }
+}
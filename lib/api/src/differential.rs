@@ -0,0 +1,280 @@
+//! Differential testing: running the same module and calls with two
+//! stores, e.g. with two compilers or engines, and comparing what they
+//! observe.
+//!
+//! This is meant for the contributors of compilers and engines, and the
+//! implementers of proposals, to validate their changes against an
+//! existing backend, from tests or fuzzers.
+
+use crate::exports::ExportError;
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::Store;
+use crate::types::Val;
+use crate::{CompileError, InstantiationError, RuntimeError, TrapCode};
+use std::fmt;
+use thiserror::Error;
+
+/// A call of an exported function, made by [`Differential::run`].
+#[derive(Debug, Clone)]
+pub struct DifferentialCall {
+    /// The name of the exported function.
+    pub function: String,
+    /// The arguments of the call.
+    pub args: Vec<Val>,
+}
+
+impl DifferentialCall {
+    /// Creates a call of the exported `function` with `args`.
+    pub fn new(function: impl Into<String>, args: Vec<Val>) -> Self {
+        Self {
+            function: function.into(),
+            args,
+        }
+    }
+}
+
+/// The outcome of a call, as compared by [`Differential::run`].
+#[derive(Debug, Clone)]
+pub enum DifferentialOutcome {
+    /// The call returned these values.
+    Returned(Box<[Val]>),
+    /// The call trapped, with a trap code if the trap came from the
+    /// WebAssembly code.
+    Trapped(Option<TrapCode>),
+}
+
+impl DifferentialOutcome {
+    fn from_result(result: Result<Box<[Val]>, RuntimeError>) -> Self {
+        match result {
+            Ok(values) => Self::Returned(values),
+            Err(error) => Self::Trapped(error.trap_code()),
+        }
+    }
+
+    /// Whether both outcomes are the same. NaNs are considered equal
+    /// regardless of their bits, and references regardless of what they
+    /// point to, since they can't be compared across stores.
+    fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Returned(a), Self::Returned(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+            }
+            (Self::Trapped(a), Self::Trapped(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DifferentialOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Returned(values) => write!(f, "returned {:?}", values),
+            Self::Trapped(Some(code)) => write!(f, "trapped with {}", code),
+            Self::Trapped(None) => write!(f, "trapped"),
+        }
+    }
+}
+
+fn same_value(a: &Val, b: &Val) -> bool {
+    match (a, b) {
+        (Val::I32(a), Val::I32(b)) => a == b,
+        (Val::I64(a), Val::I64(b)) => a == b,
+        (Val::F32(a), Val::F32(b)) => a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+        (Val::F64(a), Val::F64(b)) => a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+        (Val::V128(a), Val::V128(b)) => a == b,
+        (Val::ExternRef(_), Val::ExternRef(_)) | (Val::FuncRef(_), Val::FuncRef(_)) => true,
+        _ => false,
+    }
+}
+
+/// A difference found by [`Differential::run`], or an error preventing
+/// the comparison.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum DifferentialError {
+    /// The module couldn't be compiled by one of the stores.
+    #[error("the {side} store failed to compile the module: {error}")]
+    Compile {
+        /// `"left"` or `"right"`.
+        side: &'static str,
+        /// The compilation error.
+        #[source]
+        error: CompileError,
+    },
+    /// The module couldn't be instantiated with one of the stores.
+    #[error("the {side} store failed to instantiate the module: {error}")]
+    Instantiation {
+        /// `"left"` or `"right"`.
+        side: &'static str,
+        /// The instantiation error.
+        #[source]
+        error: InstantiationError,
+    },
+    /// A called function isn't exported.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// A call had different outcomes.
+    #[error("call {index} of `{function}` differs: left {left}, right {right}")]
+    Outcome {
+        /// The index of the call.
+        index: usize,
+        /// The name of the called function.
+        function: String,
+        /// The outcome with the left store.
+        left: DifferentialOutcome,
+        /// The outcome with the right store.
+        right: DifferentialOutcome,
+    },
+    /// An exported memory differs after a call.
+    #[error("memory `{name}` differs after call {index}, at offset {offset:#x}")]
+    Memory {
+        /// The index of the call.
+        index: usize,
+        /// The name of the exported memory.
+        name: String,
+        /// The offset of the first differing byte, or the smallest size
+        /// if their sizes differ.
+        offset: u64,
+    },
+    /// An exported global differs after a call.
+    #[error("global `{name}` differs after call {index}: left {left:?}, right {right:?}")]
+    Global {
+        /// The index of the call.
+        index: usize,
+        /// The name of the exported global.
+        name: String,
+        /// The value with the left store.
+        left: Val,
+        /// The value with the right store.
+        right: Val,
+    },
+}
+
+/// Runs a module with two stores and compares the outcomes of calls to
+/// its exports, and its exported memories and globals after each call.
+///
+/// # Example
+///
+/// ```ignore
+/// # use wasmer::*;
+/// let differential = Differential::new(singlepass_store, cranelift_store);
+/// differential.run(wasm_bytes, &[DifferentialCall::new("add", vec![Val::I32(1), Val::I32(2)])])?;
+/// ```
+pub struct Differential {
+    left: Store,
+    right: Store,
+    imports: Box<dyn Fn(&Store) -> ImportObject>,
+}
+
+impl Differential {
+    /// Creates a harness comparing the `left` and `right` stores, for
+    /// modules without imports.
+    pub fn new(left: Store, right: Store) -> Self {
+        Self {
+            left,
+            right,
+            imports: Box::new(|_| ImportObject::new()),
+        }
+    }
+
+    /// Sets the function creating the imports of the modules, called for
+    /// each store.
+    ///
+    /// The imports should behave the same with both stores, e.g. be
+    /// deterministic.
+    pub fn imports<F>(mut self, imports: F) -> Self
+    where
+        F: Fn(&Store) -> ImportObject + 'static,
+    {
+        self.imports = Box::new(imports);
+        self
+    }
+
+    /// Instantiates `wasm` with both stores, and makes the `calls` in
+    /// order on both instances, returning their outcomes if they're all
+    /// the same.
+    ///
+    /// A trap doesn't stop the run: the following calls are made on the
+    /// same instances.
+    pub fn run(
+        &self,
+        wasm: impl AsRef<[u8]>,
+        calls: &[DifferentialCall],
+    ) -> Result<Vec<DifferentialOutcome>, DifferentialError> {
+        let left = self.instantiate(&self.left, wasm.as_ref(), "left")?;
+        let right = self.instantiate(&self.right, wasm.as_ref(), "right")?;
+        let mut outcomes = Vec::with_capacity(calls.len());
+        for (index, call) in calls.iter().enumerate() {
+            let left_outcome = DifferentialOutcome::from_result(
+                left.exports.get_function(&call.function)?.call(&call.args),
+            );
+            let right_outcome = DifferentialOutcome::from_result(
+                right.exports.get_function(&call.function)?.call(&call.args),
+            );
+            if !left_outcome.same(&right_outcome) {
+                return Err(DifferentialError::Outcome {
+                    index,
+                    function: call.function.clone(),
+                    left: left_outcome,
+                    right: right_outcome,
+                });
+            }
+            compare_state(&left, &right, index)?;
+            outcomes.push(left_outcome);
+        }
+        Ok(outcomes)
+    }
+
+    fn instantiate(
+        &self,
+        store: &Store,
+        wasm: &[u8],
+        side: &'static str,
+    ) -> Result<Instance, DifferentialError> {
+        let module =
+            Module::new(store, wasm).map_err(|error| DifferentialError::Compile { side, error })?;
+        Instance::new(&module, &(self.imports)(store))
+            .map_err(|error| DifferentialError::Instantiation { side, error })
+    }
+}
+
+fn compare_state(left: &Instance, right: &Instance, index: usize) -> Result<(), DifferentialError> {
+    for (name, left_memory) in left.exports.iter().memories() {
+        let right_memory = right.exports.get_memory(name)?;
+        // The instances don't run while their memories are compared.
+        let (left_data, right_data) =
+            unsafe { (left_memory.data_unchecked(), right_memory.data_unchecked()) };
+        let offset = left_data
+            .iter()
+            .zip(right_data)
+            .position(|(a, b)| a != b)
+            .or_else(|| {
+                if left_data.len() != right_data.len() {
+                    Some(left_data.len().min(right_data.len()))
+                } else {
+                    None
+                }
+            });
+        if let Some(offset) = offset {
+            return Err(DifferentialError::Memory {
+                index,
+                name: name.clone(),
+                offset: offset as u64,
+            });
+        }
+    }
+    for (name, left_global) in left.exports.iter().globals() {
+        let (left_value, right_value) = (left_global.get(), right.exports.get_global(name)?.get());
+        if !same_value(&left_value, &right_value) {
+            return Err(DifferentialError::Global {
+                index,
+                name: name.clone(),
+                left: left_value,
+                right: right_value,
+            });
+        }
+    }
+    Ok(())
+}
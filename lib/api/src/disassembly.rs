@@ -0,0 +1,153 @@
+//! Inspection of the machine code generated for the functions of a
+//! module.
+
+#[cfg(feature = "capstone")]
+use std::fmt::Write;
+#[cfg(feature = "capstone")]
+use wasmer_compiler::CompileError;
+use wasmer_compiler::CompiledFunctionFrameInfo;
+
+/// The machine code generated for a function, returned by
+/// [`Module::disassemble`].
+///
+/// [`Module::disassemble`]: crate::Module::disassemble
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    function_index: u32,
+    address: usize,
+    code: Vec<u8>,
+    mappings: Vec<OffsetMapping>,
+}
+
+/// A range of machine code generated for the WebAssembly instruction at
+/// `wasm_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetMapping {
+    /// The offset of the range in the machine code of the function.
+    pub code_offset: usize,
+    /// The length of the range.
+    pub code_len: usize,
+    /// The offset of the instruction in the module.
+    pub wasm_offset: usize,
+}
+
+impl Disassembly {
+    pub(crate) fn new(
+        function_index: u32,
+        code: &[u8],
+        frame_info: &CompiledFunctionFrameInfo,
+    ) -> Self {
+        let mut mappings = frame_info
+            .address_map
+            .instructions
+            .iter()
+            .filter(|instruction| !instruction.srcloc.is_default() && instruction.code_len > 0)
+            .map(|instruction| OffsetMapping {
+                code_offset: instruction.code_offset,
+                code_len: instruction.code_len,
+                wasm_offset: instruction.srcloc.bits() as usize,
+            })
+            .collect::<Vec<_>>();
+        mappings.sort_by_key(|mapping| mapping.code_offset);
+        Self {
+            function_index,
+            address: code.as_ptr() as usize,
+            code: code.to_vec(),
+            mappings,
+        }
+    }
+
+    /// Returns the index of the function in the function index space.
+    pub fn function_index(&self) -> u32 {
+        self.function_index
+    }
+
+    /// Returns the address the machine code is loaded at.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Returns the machine code of the function.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Returns the ranges of machine code generated for each
+    /// WebAssembly instruction, by code offset. The code of the prologue
+    /// and of the epilogue may not be mapped.
+    pub fn mappings(&self) -> &[OffsetMapping] {
+        &self.mappings
+    }
+
+    /// Returns the offset in the module of the WebAssembly instruction
+    /// the machine code at `code_offset` was generated for.
+    pub fn wasm_offset(&self, code_offset: usize) -> Option<usize> {
+        let index = match self
+            .mappings
+            .binary_search_by_key(&code_offset, |mapping| mapping.code_offset)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let mapping = &self.mappings[index];
+        if code_offset < mapping.code_offset + mapping.code_len {
+            Some(mapping.wasm_offset)
+        } else {
+            None
+        }
+    }
+
+    /// Disassembles the machine code into text, one instruction per
+    /// line, preceded by a comment with the offset of the WebAssembly
+    /// instruction it was generated for when it changes.
+    ///
+    /// Only x86-64 and AArch64 code can be disassembled.
+    #[cfg(feature = "capstone")]
+    pub fn to_text(&self) -> Result<String, CompileError> {
+        let instructions = capstone()?
+            .disasm_all(&self.code, 0)
+            .map_err(|e| CompileError::Codegen(e.to_string()))?;
+        let mut text = String::new();
+        let mut last_wasm_offset = None;
+        for instruction in instructions.iter() {
+            let code_offset = instruction.address() as usize;
+            if let Some(wasm_offset) = self.wasm_offset(code_offset) {
+                if last_wasm_offset != Some(wasm_offset) {
+                    writeln!(text, "; wasm 0x{:x}", wasm_offset).unwrap();
+                    last_wasm_offset = Some(wasm_offset);
+                }
+            }
+            writeln!(
+                text,
+                "{:6x}: {} {}",
+                code_offset,
+                instruction.mnemonic().unwrap_or("?"),
+                instruction.op_str().unwrap_or("")
+            )
+            .unwrap();
+        }
+        Ok(text)
+    }
+}
+
+/// Returns a disassembler of the code of the host.
+#[cfg(feature = "capstone")]
+fn capstone() -> Result<capstone::Capstone, CompileError> {
+    use capstone::prelude::*;
+
+    #[cfg(target_arch = "x86_64")]
+    let capstone = Capstone::new()
+        .x86()
+        .mode(arch::x86::ArchMode::Mode64)
+        .build();
+    #[cfg(target_arch = "aarch64")]
+    let capstone = Capstone::new()
+        .arm64()
+        .mode(arch::arm64::ArchMode::Arm)
+        .build();
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let capstone: Result<Capstone, _> =
+        Err("disassembling the code of this architecture isn't supported");
+    capstone.map_err(|e| CompileError::Codegen(e.to_string()))
+}
@@ -0,0 +1,300 @@
+//! Timers and host-posted events, delivered to guest callbacks when the
+//! host polls the event loop.
+
+use crate::callbacks::{CallbackId, CallbackTable};
+use crate::exports::Exports;
+use crate::externals::{Function, Table};
+use crate::store::Store;
+use crate::RuntimeError;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The import namespace of the event loop functions.
+pub const EVENTS_NAMESPACE: &str = "wasmer_events";
+
+struct Timer {
+    deadline: Instant,
+    interval: Option<Duration>,
+    data: i32,
+}
+
+struct Subscription {
+    kind: u32,
+    data: i32,
+}
+
+#[derive(Default)]
+struct EventLoopState {
+    timers: HashMap<CallbackId, Timer>,
+    subscriptions: HashMap<CallbackId, Subscription>,
+    events: VecDeque<(u32, i32)>,
+    /// The one-shot timers expired in the current poll and not called
+    /// or cleared yet.
+    firing: HashSet<CallbackId>,
+}
+
+/// An event loop letting guests register callbacks for timers and for
+/// events posted by the host, instead of busy looping.
+///
+/// The guest imports the functions of the [`EVENTS_NAMESPACE`]
+/// namespace, given by [`EventLoop::exports`]:
+///
+/// * `set_timeout(callback: i32, delay_ms: i32, data: i32) -> i32`
+///   calls `callback` once, after `delay_ms` milliseconds;
+/// * `set_interval(callback: i32, interval_ms: i32, data: i32) -> i32`
+///   calls `callback` every `interval_ms` milliseconds;
+/// * `clear_timer(id: i32)` cancels a timer;
+/// * `subscribe(kind: i32, callback: i32, data: i32) -> i32` calls
+///   `callback` for each event of the given kind posted by the host
+///   with [`EventLoop::post_event`];
+/// * `unsubscribe(id: i32)` cancels a subscription.
+///
+/// The callbacks are indices in the function table of the guest, with
+/// the `(data: i32, payload: i32)` signature, where `payload` is the
+/// timer id for timers, and the event payload for events. Registering
+/// a callback with another signature traps.
+///
+/// Nothing runs on its own: the host calls [`Instance::poll_events`]
+/// when it sees fit, e.g. after sleeping until
+/// [`EventLoop::next_deadline`].
+///
+/// [`Instance::poll_events`]: crate::Instance::poll_events
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let module = Module::new(&store, r#"(module
+///     (import "wasmer_events" "subscribe" (func $subscribe (param i32 i32 i32) (result i32)))
+///     (table (export "__indirect_function_table") 1 funcref)
+///     (elem (i32.const 0) $on_message)
+///     (global $received (export "received") (mut i32) (i32.const 0))
+///     (func $on_message (param i32 i32)
+///         (global.set $received (local.get 1)))
+///     (func (export "start") (drop (call $subscribe (i32.const 1) (i32.const 0) (i32.const 0)))))"#)?;
+/// let event_loop = EventLoop::new();
+/// let events = event_loop.exports(&store);
+/// let mut instance = Instance::new(&module, &imports! { "wasmer_events" => events })?;
+/// instance.set_event_loop(event_loop.clone())?;
+/// instance.exports.get_function("start")?.call(&[])?;
+///
+/// event_loop.post_event(1, 42);
+/// assert_eq!(instance.poll_events()?, 1);
+/// assert_eq!(instance.exports.get_global("received")?.get(), Value::I32(42));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct EventLoop {
+    callbacks: CallbackTable,
+    state: Arc<Mutex<EventLoopState>>,
+}
+
+impl EventLoop {
+    /// Creates an empty `EventLoop`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the functions to be imported by the guest in the
+    /// [`EVENTS_NAMESPACE`] namespace.
+    pub fn exports(&self, store: &Store) -> Exports {
+        let mut exports = Exports::new();
+        exports.insert(
+            "set_timeout",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |event_loop: &mut Self, callback: u32, delay_ms: u32, data: i32| {
+                    event_loop.add_timer(callback, delay_ms, data, false)
+                },
+            ),
+        );
+        exports.insert(
+            "set_interval",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |event_loop: &mut Self, callback: u32, interval_ms: u32, data: i32| {
+                    event_loop.add_timer(callback, interval_ms, data, true)
+                },
+            ),
+        );
+        exports.insert(
+            "clear_timer",
+            Function::new_native_with_env(store, self.clone(), |event_loop: &mut Self, id: u32| {
+                event_loop.cancel(CallbackId::from(id))
+            }),
+        );
+        exports.insert(
+            "subscribe",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |event_loop: &mut Self, kind: u32, callback: u32, data: i32| {
+                    let id = event_loop
+                        .callbacks
+                        .register_index::<(i32, i32), ()>(callback)?;
+                    event_loop
+                        .state
+                        .lock()
+                        .unwrap()
+                        .subscriptions
+                        .insert(id, Subscription { kind, data });
+                    Ok::<_, RuntimeError>(id.as_u32())
+                },
+            ),
+        );
+        exports.insert(
+            "unsubscribe",
+            Function::new_native_with_env(store, self.clone(), |event_loop: &mut Self, id: u32| {
+                event_loop.cancel(CallbackId::from(id))
+            }),
+        );
+        exports
+    }
+
+    /// Sets the function table of the guest, that the callbacks are
+    /// looked up in.
+    pub fn set_function_table(&self, table: Table) {
+        self.callbacks.set_function_table(table);
+    }
+
+    /// Posts an event, delivered to the callbacks subscribed to `kind`
+    /// on the next poll.
+    pub fn post_event(&self, kind: u32, payload: i32) {
+        self.state.lock().unwrap().events.push_back((kind, payload));
+    }
+
+    /// Returns when the next timer expires, if any. The host can sleep
+    /// until then before polling.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.state
+            .lock()
+            .unwrap()
+            .timers
+            .values()
+            .map(|timer| timer.deadline)
+            .min()
+    }
+
+    /// Returns whether there are posted events not delivered yet.
+    pub fn has_pending_events(&self) -> bool {
+        !self.state.lock().unwrap().events.is_empty()
+    }
+
+    /// Calls the callbacks of the expired timers, in deadline order,
+    /// then the callbacks subscribed to the posted events, in posting
+    /// order, and returns the number of callbacks called.
+    ///
+    /// If a callback traps, the error is returned and the callbacks
+    /// left are dropped.
+    pub fn poll(&self) -> Result<usize, RuntimeError> {
+        let now = Instant::now();
+        let mut calls = vec![];
+        let mut one_shots = vec![];
+        {
+            let mut state = self.state.lock().unwrap();
+            let mut expired = state
+                .timers
+                .iter()
+                .filter(|(_, timer)| timer.deadline <= now)
+                .map(|(id, timer)| (timer.deadline, *id))
+                .collect::<Vec<_>>();
+            expired.sort_by_key(|(deadline, id)| (*deadline, id.as_u32()));
+            for (_, id) in expired {
+                let timer = state.timers.get_mut(&id).unwrap();
+                calls.push((id, timer.data, id.as_u32() as i32));
+                match timer.interval {
+                    Some(interval) => {
+                        timer.deadline += interval;
+                        if timer.deadline <= now {
+                            // Don't catch up on the missed ticks.
+                            timer.deadline = now + interval;
+                        }
+                    }
+                    None => {
+                        state.timers.remove(&id);
+                        one_shots.push(id);
+                    }
+                }
+            }
+
+            state.firing = one_shots.iter().copied().collect();
+
+            let events = state.events.drain(..).collect::<Vec<_>>();
+            let mut subscriptions = state
+                .subscriptions
+                .iter()
+                .map(|(id, subscription)| (*id, subscription.kind, subscription.data))
+                .collect::<Vec<_>>();
+            subscriptions.sort_by_key(|(id, _, _)| id.as_u32());
+            for (kind, payload) in events {
+                for (id, _, data) in subscriptions.iter().filter(|(_, k, _)| *k == kind) {
+                    calls.push((*id, *data, payload));
+                }
+            }
+        }
+
+        // The one-shot callbacks are unregistered before any callback is
+        // called, so they aren't left registered when one traps.
+        let mut one_shot_callbacks = HashMap::new();
+        for id in one_shots {
+            if let Ok(callback) = self.callbacks.get::<(i32, i32), ()>(id) {
+                one_shot_callbacks.insert(id, callback);
+            }
+            self.callbacks.unregister(id);
+        }
+
+        let mut called = 0;
+        for (id, data, payload) in calls {
+            // The callback may have been cancelled by a previous one.
+            let callback = match one_shot_callbacks.remove(&id) {
+                Some(callback) => {
+                    if !self.state.lock().unwrap().firing.remove(&id) {
+                        continue;
+                    }
+                    callback
+                }
+                None => match self.callbacks.get::<(i32, i32), ()>(id) {
+                    Ok(callback) => callback,
+                    Err(_) => continue,
+                },
+            };
+            callback.call(data, payload)?;
+            called += 1;
+        }
+        Ok(called)
+    }
+
+    fn add_timer(
+        &self,
+        callback: u32,
+        milliseconds: u32,
+        data: i32,
+        repeat: bool,
+    ) -> Result<u32, RuntimeError> {
+        let id = self.callbacks.register_index::<(i32, i32), ()>(callback)?;
+        let duration = Duration::from_millis(milliseconds as u64);
+        self.state.lock().unwrap().timers.insert(
+            id,
+            Timer {
+                deadline: Instant::now() + duration,
+                interval: if repeat { Some(duration) } else { None },
+                data,
+            },
+        );
+        Ok(id.as_u32())
+    }
+
+    fn cancel(&self, id: CallbackId) {
+        let mut state = self.state.lock().unwrap();
+        state.timers.remove(&id);
+        state.subscriptions.remove(&id);
+        state.firing.remove(&id);
+        self.callbacks.unregister(id);
+    }
+}
@@ -0,0 +1,134 @@
+//! How a guest program ended, as reported by the ABIs running it (WASI
+//! and Emscripten), without scraping its output.
+
+use std::fmt;
+
+/// The number of bytes of stderr kept by a [`StderrCapture`].
+const CAPTURE_SIZE: usize = 4096;
+
+/// How a guest program ended.
+///
+/// The exit code, the reason of an abort and the trap are exclusive; the
+/// panic message is set along with them when the program panicked before
+/// exiting, aborting or trapping.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExitStatus {
+    /// The exit code given to `proc_exit` or `exit`, or 0 if the entry
+    /// point returned.
+    pub code: Option<i32>,
+    /// The reason given to `abort`, if the program aborted.
+    pub abort_reason: Option<String>,
+    /// The message of the trap ending the program, if it trapped.
+    pub trap: Option<String>,
+    /// The message of the last Rust panic the program wrote to stderr.
+    pub panic_message: Option<String>,
+}
+
+impl ExitStatus {
+    /// Creates the status of a program exiting with `code`.
+    pub fn exited(code: i32) -> Self {
+        Self {
+            code: Some(code),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the status of a program aborting for `reason`.
+    pub fn aborted(reason: impl Into<String>) -> Self {
+        Self {
+            abort_reason: Some(reason.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the status of a program ending with the trap `message`.
+    pub fn trapped(message: impl Into<String>) -> Self {
+        Self {
+            trap: Some(message.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the message of the Rust panic the program wrote to stderr,
+    /// as found by `capture`.
+    pub fn with_panic_message(mut self, capture: &StderrCapture) -> Self {
+        self.panic_message = capture.panic_message();
+        self
+    }
+
+    /// Returns whether the program exited with the code 0.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(code) = self.code {
+            write!(f, "exited with code {}", code)?;
+        } else if let Some(reason) = &self.abort_reason {
+            write!(f, "aborted: {}", reason)?;
+        } else if let Some(trap) = &self.trap {
+            write!(f, "trapped: {}", trap)?;
+        } else {
+            write!(f, "ended")?;
+        }
+        if let Some(message) = &self.panic_message {
+            write!(f, " after a panic: {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// The end of what a guest program wrote to stderr, kept to find the
+/// message of a Rust panic.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{ExitStatus, StderrCapture};
+/// let mut capture = StderrCapture::new();
+/// capture.write(b"thread 'main' panicked at 'oops', src/main.rs:2:5\n");
+/// capture.write(b"note: run with `RUST_BACKTRACE=1` for a backtrace\n");
+/// let status = ExitStatus::trapped("unreachable").with_panic_message(&capture);
+/// assert_eq!(
+///     status.panic_message.as_deref(),
+///     Some("thread 'main' panicked at 'oops', src/main.rs:2:5")
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StderrCapture {
+    tail: Vec<u8>,
+}
+
+impl StderrCapture {
+    /// Creates an empty capture.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `bytes` written to stderr, keeping the last 4 KiB.
+    pub fn write(&mut self, bytes: &[u8]) {
+        let bytes = &bytes[bytes.len().saturating_sub(CAPTURE_SIZE)..];
+        self.tail.extend_from_slice(bytes);
+        let excess = self.tail.len().saturating_sub(CAPTURE_SIZE);
+        self.tail.drain(..excess);
+    }
+
+    /// Returns the message of the last Rust panic written, from its
+    /// `thread '...' panicked at` line up to the `note:` following it.
+    pub fn panic_message(&self) -> Option<String> {
+        let text = String::from_utf8_lossy(&self.tail);
+        let lines = text.lines().collect::<Vec<_>>();
+        let start = lines
+            .iter()
+            .rposition(|line| line.starts_with("thread '") && line.contains("' panicked at"))?;
+        let message = lines[start..]
+            .iter()
+            .take_while(|line| !line.starts_with("note: "))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(message.trim_end().to_string())
+    }
+}
@@ -95,6 +95,37 @@ impl Exports {
         }
     }
 
+    /// Get several exports of the same type at once, in the order of
+    /// `names`.
+    ///
+    /// The returned references can be kept around to avoid looking
+    /// up the exports by name in hot loops.
+    pub fn get_many<'a, T: Exportable<'a>>(
+        &'a self,
+        names: &[&str],
+    ) -> Result<Vec<&'a T>, ExportError> {
+        names.iter().map(|name| self.get(name)).collect()
+    }
+
+    /// Get the index of an export given its `name`.
+    ///
+    /// Exports keep the order in which the module declares them, so
+    /// the index can be used with [`Exports::get_by_index`].
+    pub fn get_index_of(&self, name: &str) -> Option<usize> {
+        self.map.get_full(name).map(|(index, _, _)| index)
+    }
+
+    /// Get an export given its `index`, without a name lookup.
+    pub fn get_by_index<'a, T: Exportable<'a>>(
+        &'a self,
+        index: usize,
+    ) -> Result<&'a T, ExportError> {
+        match self.map.get_index(index) {
+            None => Err(ExportError::Missing(format!("#{}", index))),
+            Some((_, extern_)) => T::get_self_from_extern(extern_),
+        }
+    }
+
     /// Get an export as a `Global`.
     pub fn get_global(&self, name: &str) -> Result<&Global, ExportError> {
         self.get(name)
@@ -10,7 +10,7 @@ use std::cell::RefCell;
 use std::cmp::max;
 use std::fmt;
 use wasmer_vm::{
-    raise_user_trap, resume_panic, wasmer_call_trampoline, Export, ExportFunction,
+    raise_user_trap, resume_panic, wasmer_call_trampoline, Export, ExportFunction, InstanceHandle,
     VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext, VMFunctionBody, VMFunctionKind,
     VMTrampoline,
 };
@@ -59,6 +59,11 @@ pub struct Function {
 impl Function {
     /// Creates a new host `Function` (dynamic) with the provided signature.
     ///
+    /// Calls from WebAssembly go through a trampoline that converts the
+    /// arguments and results to `Value`s. For hot imports, prefer
+    /// [`Function::new_native`], which WebAssembly calls directly with
+    /// the native ABI.
+    ///
     /// # Example
     ///
     /// ```
@@ -96,6 +101,7 @@ impl Function {
                 vmctx,
                 signature: ty.clone(),
                 call_trampoline: None,
+                instance_vmctx: None,
             },
         }
     }
@@ -146,6 +152,7 @@ impl Function {
                 vmctx,
                 signature: ty.clone(),
                 call_trampoline: None,
+                instance_vmctx: None,
             },
         }
     }
@@ -188,6 +195,7 @@ impl Function {
                 signature,
                 kind: VMFunctionKind::Static,
                 call_trampoline: None,
+                instance_vmctx: None,
             },
         }
     }
@@ -242,6 +250,7 @@ impl Function {
                 vmctx,
                 signature,
                 call_trampoline: None,
+                instance_vmctx: None,
             },
         }
     }
@@ -303,8 +312,13 @@ impl Function {
 
         // Call the trampoline.
         if let Err(error) = unsafe {
+            let instance = self
+                .exported
+                .instance_vmctx
+                .map(|vmctx| InstanceHandle::from_vmctx(vmctx));
             wasmer_call_trampoline(
                 self.exported.vmctx,
+                instance.as_ref(),
                 func.trampoline,
                 self.exported.address,
                 values_vec.as_mut_ptr() as *mut u8,
@@ -423,6 +437,7 @@ impl Function {
             self.store.clone(),
             self.exported.address,
             self.exported.vmctx,
+            self.exported.instance_vmctx,
             self.exported.kind,
             self.definition.clone(),
         ))
@@ -533,8 +548,15 @@ impl<T: VMDynamicFunction> VMDynamicFunctionCall<T> for VMDynamicFunctionContext
 
             // We need to dynamically check that the returns
             // match the expected types, as well as expected length.
-            let return_types = returns.iter().map(|ret| ret.ty()).collect::<Vec<_>>();
-            if return_types != func_ty.results() {
+            // The types are only collected when they don't match, so
+            // that the hot path doesn't allocate.
+            if returns.len() != func_ty.results().len()
+                || returns
+                    .iter()
+                    .zip(func_ty.results())
+                    .any(|(ret, ty)| ret.ty() != *ty)
+            {
+                let return_types = returns.iter().map(|ret| ret.ty()).collect::<Vec<_>>();
                 return Err(RuntimeError::new(format!(
                     "Dynamic function returned wrong signature. Expected {:?} but got {:?}",
                     func_ty.results(),
@@ -94,6 +94,12 @@ impl Global {
         }
     }
 
+    /// Returns the number of references to the global: the clones of this
+    /// `Global`, and the instance defining it, if any.
+    pub(crate) fn references(&self) -> usize {
+        Arc::strong_count(&self.global)
+    }
+
     /// Returns whether or not these two globals refer to the same data.
     pub fn same(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.global, &other.global)
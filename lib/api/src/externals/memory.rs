@@ -155,6 +155,12 @@ impl Memory {
         }
     }
 
+    /// Returns the number of references to the memory: the clones of this
+    /// `Memory`, and the instance defining it, if any.
+    pub(crate) fn references(&self) -> usize {
+        Arc::strong_count(&self.memory)
+    }
+
     /// Returns whether or not these two globals refer to the same data.
     pub fn same(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.memory, &other.memory)
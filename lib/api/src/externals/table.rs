@@ -146,6 +146,12 @@ impl Table {
         }
     }
 
+    /// Returns the number of references to the table: the clones of this
+    /// `Table`, and the instance defining it, if any.
+    pub(crate) fn references(&self) -> usize {
+        Arc::strong_count(&self.table)
+    }
+
     /// Returns whether or not these two tables refer to the same data.
     pub fn same(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.table, &other.table)
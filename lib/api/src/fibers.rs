@@ -0,0 +1,307 @@
+//! Experimental stack switching: guest fibers suspended and resumed
+//! through host imports.
+
+use crate::callbacks::{CallbackId, CallbackTable};
+use crate::exports::Exports;
+use crate::externals::{Function, Table};
+use crate::store::Store;
+use crate::RuntimeError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The import namespace of the fiber functions.
+pub const FIBERS_NAMESPACE: &str = "wasmer_fibers";
+
+/// The status of a fiber, as returned by `fiber_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum FiberStatus {
+    /// The fiber hasn't started yet, or is suspended.
+    Suspended = 0,
+    /// The fiber is running, or resuming another fiber.
+    Running = 1,
+    /// The entry function of the fiber returned or trapped.
+    Finished = 2,
+}
+
+/// What the resumer of a fiber sends it.
+enum Resume {
+    Value(i32),
+    Cancel,
+}
+
+/// What a fiber sends back to its resumer.
+enum Yield {
+    Suspended(i32),
+    Finished(i32),
+    Trapped(RuntimeError),
+}
+
+struct Fiber {
+    status: FiberStatus,
+    resume: Sender<Resume>,
+    yields: Arc<Mutex<Receiver<Yield>>>,
+}
+
+/// The end of the channels of the fiber running on the current thread.
+struct CurrentFiber {
+    yields: Sender<Yield>,
+    resume: Receiver<Resume>,
+}
+
+thread_local! {
+    static CURRENT_FIBER: RefCell<Option<CurrentFiber>> = RefCell::new(None);
+}
+
+#[derive(Default)]
+struct FibersState {
+    next_id: u32,
+    fibers: HashMap<u32, Fiber>,
+}
+
+/// Experimental fibers letting guests switch between stacks, e.g. to
+/// implement green threads or async functions, before a standard stack
+/// switching proposal lands.
+///
+/// The guest imports the functions of the [`FIBERS_NAMESPACE`]
+/// namespace, given by [`Fibers::exports`]:
+///
+/// * `fiber_new(entry: i32, arg: i32) -> i32` creates a suspended fiber
+///   calling `entry`, an index in the function table of the guest with
+///   the `(arg: i32) -> i32` signature, with `arg` on its first resume;
+/// * `fiber_resume(fiber: i32, value: i32) -> i32` runs a suspended
+///   fiber until it suspends itself or its entry function returns, and
+///   returns the value it was suspended with or the returned value.
+///   `value` is returned by the `fiber_suspend` call the fiber resumes
+///   from, and is ignored on the first resume;
+/// * `fiber_suspend(value: i32) -> i32` suspends the current fiber,
+///   returning `value` to its resumer;
+/// * `fiber_status(fiber: i32) -> i32` returns a [`FiberStatus`];
+/// * `fiber_drop(fiber: i32)` frees a suspended or finished fiber. A
+///   suspended fiber traps in `fiber_suspend` to unwind its stack.
+///
+/// Resuming a fiber which isn't suspended, suspending outside of a
+/// fiber, and a trap in a fiber trap in the caller.
+///
+/// Each fiber runs on its own host thread, parked while the fiber
+/// isn't running, so the guest code of a store only runs on one thread
+/// at a time, like with native stack switching.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let module = Module::new(&store, r#"(module
+///     (import "wasmer_fibers" "fiber_new" (func $new (param i32 i32) (result i32)))
+///     (import "wasmer_fibers" "fiber_resume" (func $resume (param i32 i32) (result i32)))
+///     (import "wasmer_fibers" "fiber_suspend" (func $suspend (param i32) (result i32)))
+///     (table (export "__indirect_function_table") 1 funcref)
+///     (elem (i32.const 0) $counter)
+///     (func $counter (param $next i32) (result i32)
+///         (loop $again
+///             (local.set $next (i32.add (local.get $next) (call $suspend (local.get $next))))
+///             (br $again))
+///         (unreachable))
+///     (func (export "run") (result i32) (local $fiber i32)
+///         (local.set $fiber (call $new (i32.const 0) (i32.const 1)))
+///         (drop (call $resume (local.get $fiber) (i32.const 0)))
+///         (drop (call $resume (local.get $fiber) (i32.const 10)))
+///         (call $resume (local.get $fiber) (i32.const 100))))"#)?;
+/// let fibers = Fibers::new();
+/// let exports = fibers.exports(&store);
+/// let instance = Instance::new(&module, &imports! { "wasmer_fibers" => exports })?;
+/// fibers.set_function_table(instance.exports.get_table("__indirect_function_table")?.clone());
+/// let run = instance.exports.get_native_function::<(), i32>("run")?;
+/// assert_eq!(run.call()?, 111);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Fibers {
+    callbacks: CallbackTable,
+    state: Arc<Mutex<FibersState>>,
+}
+
+impl Fibers {
+    /// Creates a `Fibers` without fibers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the functions to be imported by the guest in the
+    /// [`FIBERS_NAMESPACE`] namespace.
+    pub fn exports(&self, store: &Store) -> Exports {
+        let mut exports = Exports::new();
+        exports.insert(
+            "fiber_new",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |fibers: &mut Self, entry: u32, arg: i32| fibers.spawn(entry, arg),
+            ),
+        );
+        exports.insert(
+            "fiber_resume",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |fibers: &mut Self, fiber: u32, value: i32| fibers.resume(fiber, value),
+            ),
+        );
+        exports.insert(
+            "fiber_suspend",
+            Function::new_native(store, |value: i32| suspend(value)),
+        );
+        exports.insert(
+            "fiber_status",
+            Function::new_native_with_env(store, self.clone(), |fibers: &mut Self, fiber: u32| {
+                fibers.status(fiber).map(|status| status as i32)
+            }),
+        );
+        exports.insert(
+            "fiber_drop",
+            Function::new_native_with_env(store, self.clone(), |fibers: &mut Self, fiber: u32| {
+                fibers.drop_fiber(fiber)
+            }),
+        );
+        exports
+    }
+
+    /// Sets the function table of the guest, that the entry functions
+    /// of the fibers are looked up in.
+    pub fn set_function_table(&self, table: Table) {
+        self.callbacks.set_function_table(table);
+    }
+
+    /// Returns the status of a fiber.
+    pub fn status(&self, fiber: u32) -> Result<FiberStatus, RuntimeError> {
+        let state = self.state.lock().unwrap();
+        Ok(get_fiber(&state, fiber)?.status)
+    }
+
+    fn spawn(&self, entry: u32, arg: i32) -> Result<u32, RuntimeError> {
+        let id = self.callbacks.register_index::<i32, i32>(entry)?;
+        let (resume_sender, resume) = channel();
+        let (yields, yield_receiver) = channel();
+        let callbacks = self.callbacks.clone();
+        let mut state = self.state.lock().unwrap();
+        let fiber = state.next_id;
+        state.next_id = fiber
+            .checked_add(1)
+            .ok_or_else(|| RuntimeError::new("too many fibers"))?;
+        thread::Builder::new()
+            .name(format!("wasmer-fiber-{}", fiber))
+            .spawn(move || run_fiber(callbacks, id, arg, CurrentFiber { yields, resume }))
+            .map_err(|e| RuntimeError::new(format!("can't start a fiber: {}", e)))?;
+        state.fibers.insert(
+            fiber,
+            Fiber {
+                status: FiberStatus::Suspended,
+                resume: resume_sender,
+                yields: Arc::new(Mutex::new(yield_receiver)),
+            },
+        );
+        Ok(fiber)
+    }
+
+    fn resume(&self, fiber: u32, value: i32) -> Result<i32, RuntimeError> {
+        let yields = {
+            let mut state = self.state.lock().unwrap();
+            let fiber = get_fiber_mut(&mut state, fiber)?;
+            if fiber.status != FiberStatus::Suspended {
+                return Err(RuntimeError::new("the fiber isn't suspended"));
+            }
+            fiber.status = FiberStatus::Running;
+            let _ = fiber.resume.send(Resume::Value(value));
+            fiber.yields.clone()
+        };
+        // The state isn't locked while the fiber runs, as it can create
+        // and resume other fibers.
+        let yielded = yields.lock().unwrap().recv();
+        let mut state = self.state.lock().unwrap();
+        let fiber = get_fiber_mut(&mut state, fiber)?;
+        match yielded {
+            Ok(Yield::Suspended(value)) => {
+                fiber.status = FiberStatus::Suspended;
+                Ok(value)
+            }
+            Ok(Yield::Finished(value)) => {
+                fiber.status = FiberStatus::Finished;
+                Ok(value)
+            }
+            Ok(Yield::Trapped(error)) => {
+                fiber.status = FiberStatus::Finished;
+                Err(error)
+            }
+            Err(_) => {
+                fiber.status = FiberStatus::Finished;
+                Err(RuntimeError::new("the fiber exited"))
+            }
+        }
+    }
+
+    fn drop_fiber(&self, fiber: u32) -> Result<(), RuntimeError> {
+        let mut state = self.state.lock().unwrap();
+        if get_fiber(&state, fiber)?.status == FiberStatus::Running {
+            return Err(RuntimeError::new("can't drop a running fiber"));
+        }
+        let fiber = state.fibers.remove(&fiber).unwrap();
+        let _ = fiber.resume.send(Resume::Cancel);
+        Ok(())
+    }
+}
+
+fn get_fiber(state: &FibersState, fiber: u32) -> Result<&Fiber, RuntimeError> {
+    state
+        .fibers
+        .get(&fiber)
+        .ok_or_else(|| RuntimeError::new(format!("no fiber with id {}", fiber)))
+}
+
+fn get_fiber_mut(state: &mut FibersState, fiber: u32) -> Result<&mut Fiber, RuntimeError> {
+    state
+        .fibers
+        .get_mut(&fiber)
+        .ok_or_else(|| RuntimeError::new(format!("no fiber with id {}", fiber)))
+}
+
+/// Runs the entry function of a fiber on its thread, once resumed.
+fn run_fiber(callbacks: CallbackTable, entry: CallbackId, arg: i32, current: CurrentFiber) {
+    match current.resume.recv() {
+        Ok(Resume::Value(_)) => {}
+        // Dropped before its first resume.
+        _ => {
+            callbacks.unregister(entry);
+            return;
+        }
+    }
+    let yields = current.yields.clone();
+    CURRENT_FIBER.with(|fiber| *fiber.borrow_mut() = Some(current));
+    let result = callbacks
+        .get::<i32, i32>(entry)
+        .and_then(|entry| entry.call(arg));
+    callbacks.unregister(entry);
+    let _ = yields.send(match result {
+        Ok(value) => Yield::Finished(value),
+        Err(error) => Yield::Trapped(error),
+    });
+}
+
+fn suspend(value: i32) -> Result<i32, RuntimeError> {
+    CURRENT_FIBER.with(|fiber| {
+        let fiber = fiber.borrow();
+        let fiber = fiber
+            .as_ref()
+            .ok_or_else(|| RuntimeError::new("can't suspend outside of a fiber"))?;
+        let _ = fiber.yields.send(Yield::Suspended(value));
+        match fiber.resume.recv() {
+            Ok(Resume::Value(value)) => Ok(value),
+            _ => Err(RuntimeError::new("the fiber was dropped")),
+        }
+    })
+}
@@ -0,0 +1,221 @@
+//! Allocation of guest memory from the host, through the allocator
+//! exported by the instance.
+
+use crate::externals::{Function, Memory};
+use crate::instance::Instance;
+use crate::ptr::{Array, WasmPtr};
+use crate::{RuntimeError, Val, ValType};
+use thiserror::Error;
+
+/// The pairs of allocation and deallocation functions looked up in the
+/// exports, in order.
+const ALLOCATORS: &[(&str, &str)] = &[
+    ("malloc", "free"),
+    ("__wbindgen_malloc", "__wbindgen_free"),
+    ("allocate", "deallocate"),
+];
+
+/// An error while allocating guest memory.
+#[derive(Error, Debug)]
+pub enum GuestAllocatorError {
+    /// The instance doesn't export an allocator.
+    #[error("the instance exports no `malloc`, `__wbindgen_malloc` or `allocate`")]
+    NoAllocator,
+    /// The instance doesn't export a memory.
+    #[error("the instance exports no memory")]
+    NoMemory,
+    /// The allocator has a signature that isn't supported.
+    #[error("unsupported signature for `{0}`")]
+    UnsupportedSignature(String),
+    /// The guest allocator couldn't allocate the buffer.
+    #[error("the guest allocator couldn't allocate {0} bytes")]
+    OutOfMemory(u32),
+    /// The guest allocator trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// Allocates buffers in the memory of an instance with the allocator
+/// it exports, e.g. `malloc` and `free`.
+///
+/// The buffers are freed when dropped, unless their ownership is given
+/// to the guest with [`GuestBuffer::into_guest`].
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// # let module = Module::new(&store, r#"(module
+/// #     (memory (export "memory") 1)
+/// #     (global $next (mut i32) (i32.const 16))
+/// #     (func (export "malloc") (param i32) (result i32)
+/// #         (global.get $next)
+/// #         (global.set $next (i32.add (global.get $next) (local.get 0))))
+/// #     (func (export "free") (param i32)))"#)?;
+/// # let instance = Instance::new(&module, &imports! {})?;
+/// let allocator = GuestAllocator::new(&instance)?;
+/// let buffer = allocator.alloc_bytes(b"hello")?;
+/// // Pass `buffer.offset()` and `buffer.len()` to the guest...
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GuestAllocator {
+    memory: Memory,
+    alloc: Function,
+    dealloc: Option<Function>,
+}
+
+impl GuestAllocator {
+    /// Looks the allocator and the memory of `instance` up.
+    pub fn new(instance: &Instance) -> Result<Self, GuestAllocatorError> {
+        let memory = instance
+            .exports
+            .get_memory("memory")
+            .ok()
+            .or_else(|| instance.exports.iter().memories().next().map(|(_, m)| m))
+            .ok_or(GuestAllocatorError::NoMemory)?
+            .clone();
+        let (alloc_name, dealloc_name) = ALLOCATORS
+            .iter()
+            .find(|(alloc_name, _)| instance.exports.get_function(alloc_name).is_ok())
+            .ok_or(GuestAllocatorError::NoAllocator)?;
+
+        let alloc = instance.exports.get_function(alloc_name).unwrap().clone();
+        let params = alloc.ty().params();
+        if params.is_empty()
+            || params.len() > 2
+            || params.iter().any(|ty| *ty != ValType::I32)
+            || alloc.ty().results() != [ValType::I32]
+        {
+            return Err(GuestAllocatorError::UnsupportedSignature(
+                alloc_name.to_string(),
+            ));
+        }
+        let dealloc = match instance.exports.get_function(dealloc_name) {
+            Ok(dealloc) => {
+                let params = dealloc.ty().params();
+                if params.is_empty()
+                    || params.len() > 3
+                    || params.iter().any(|ty| *ty != ValType::I32)
+                {
+                    return Err(GuestAllocatorError::UnsupportedSignature(
+                        dealloc_name.to_string(),
+                    ));
+                }
+                Some(dealloc.clone())
+            }
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            memory,
+            alloc,
+            dealloc,
+        })
+    }
+
+    /// Returns the memory the buffers are allocated in.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Allocates a buffer of `size` bytes.
+    pub fn alloc(&self, size: u32) -> Result<GuestBuffer<'_>, GuestAllocatorError> {
+        // Allocators taking an alignment (like `__wbindgen_malloc`) get
+        // the largest one of the primitive types.
+        let args = [Val::I32(size as i32), Val::I32(8)];
+        let params = self.alloc.ty().params().len();
+        let offset = match *self.alloc.call(&args[..params])? {
+            [Val::I32(offset)] => offset as u32,
+            _ => unreachable!("the signature is checked"),
+        };
+        if offset == 0 && size != 0 {
+            return Err(GuestAllocatorError::OutOfMemory(size));
+        }
+        let end = offset as u64 + size as u64;
+        if end > self.memory.size().bytes().0 as u64 {
+            return Err(GuestAllocatorError::OutOfMemory(size));
+        }
+        Ok(GuestBuffer {
+            allocator: self,
+            offset,
+            size,
+        })
+    }
+
+    /// Allocates a buffer holding a copy of `data`.
+    pub fn alloc_bytes(&self, data: &[u8]) -> Result<GuestBuffer<'_>, GuestAllocatorError> {
+        let buffer = self.alloc(data.len() as u32)?;
+        let view = self.memory.view::<u8>();
+        let start = buffer.offset as usize;
+        for (cell, byte) in view[start..start + data.len()].iter().zip(data) {
+            cell.set(*byte);
+        }
+        Ok(buffer)
+    }
+
+    fn dealloc(&self, offset: u32, size: u32) -> Result<(), RuntimeError> {
+        if let Some(dealloc) = &self.dealloc {
+            let args = [Val::I32(offset as i32), Val::I32(size as i32), Val::I32(8)];
+            dealloc.call(&args[..dealloc.ty().params().len()])?;
+        }
+        Ok(())
+    }
+}
+
+/// A buffer allocated in guest memory by a [`GuestAllocator`], freed
+/// when dropped.
+pub struct GuestBuffer<'a> {
+    allocator: &'a GuestAllocator,
+    offset: u32,
+    size: u32,
+}
+
+impl<'a> GuestBuffer<'a> {
+    /// Returns the offset of the buffer in guest memory.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the size of the buffer, in bytes.
+    pub fn len(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns a pointer to the buffer.
+    pub fn ptr(&self) -> WasmPtr<u8, Array> {
+        WasmPtr::new(self.offset)
+    }
+
+    /// Gives the ownership of the buffer to the guest, which becomes
+    /// responsible for freeing it, and returns its offset.
+    pub fn into_guest(self) -> u32 {
+        let offset = self.offset;
+        std::mem::forget(self);
+        offset
+    }
+
+    /// Frees the buffer, returning the error of the guest allocator if
+    /// it traps.
+    pub fn free(self) -> Result<(), RuntimeError> {
+        let (offset, size) = (self.offset, self.size);
+        let allocator = self.allocator;
+        std::mem::forget(self);
+        allocator.dealloc(offset, size)
+    }
+}
+
+impl<'a> Drop for GuestBuffer<'a> {
+    fn drop(&mut self) {
+        // Errors can't be reported from here; use `free` to get them.
+        let _ = self.allocator.dealloc(self.offset, self.size);
+    }
+}
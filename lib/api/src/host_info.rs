@@ -0,0 +1,178 @@
+//! Discovery of the version, features and capabilities of the host by
+//! guests.
+
+use crate::exports::Exports;
+use crate::externals::{Function, Memory};
+use crate::ptr::{Array, WasmPtr};
+use crate::store::Store;
+use crate::RuntimeError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use wasmer_compiler::Features;
+
+/// The import namespace of the host info functions.
+pub const HOST_INFO_NAMESPACE: &str = "host_info";
+
+/// The version, features and capabilities of the host, that portable
+/// guests can query to adapt to the host instead of trapping on missing
+/// imports.
+///
+/// The guest imports the functions of the [`HOST_INFO_NAMESPACE`]
+/// namespace, given by [`HostCapabilities::exports`]:
+///
+/// * `version() -> i32` returns the version of Wasmer, encoded as
+///   `major << 16 | minor << 8 | patch`;
+/// * `has_feature(name: i32, name_len: i32) -> i32` returns 1 if the
+///   WebAssembly feature named by the UTF-8 string at `name` is enabled,
+///   e.g. `simd` or `threads`, and 0 otherwise;
+/// * `capability_version(name: i32, name_len: i32) -> i32` returns the
+///   version of the capability named by the UTF-8 string at `name`
+///   granted to the guest, e.g. the version of a [`HostInterface`], and
+///   0 if it isn't granted.
+///
+/// The names are read from the memory given to
+/// [`HostCapabilities::set_memory`]; reading them without a memory, or
+/// out of its bounds, traps.
+///
+/// [`HostInterface`]: crate::HostInterface
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let host_info = HostCapabilities::new()
+///     .features(&Features::default())
+///     .capability("log", 2);
+///
+/// let module = Module::new(&store, r#"(module
+///     (import "host_info" "capability_version"
+///         (func $capability_version (param i32 i32) (result i32)))
+///     (memory (export "memory") 1)
+///     (data (i32.const 0) "log")
+///     (func (export "log_version") (result i32)
+///         (call $capability_version (i32.const 0) (i32.const 3))))"#)?;
+/// let exports = host_info.exports(&store);
+/// let instance = Instance::new(&module, &imports! { HOST_INFO_NAMESPACE => exports })?;
+/// host_info.set_memory(instance.exports.get_memory("memory")?.clone());
+///
+/// let log_version = instance.exports.get_native_function::<(), i32>("log_version")?;
+/// assert_eq!(log_version.call()?, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HostCapabilities {
+    features: BTreeSet<String>,
+    capabilities: BTreeMap<String, u32>,
+    memory: Arc<Mutex<Option<Memory>>>,
+}
+
+impl HostCapabilities {
+    /// Creates host info with no feature nor capability.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports the WebAssembly features enabled in `features`, under
+    /// the names of their proposals: `threads`, `reference_types`,
+    /// `simd`, `bulk_memory` and `multi_value`.
+    pub fn features(mut self, features: &Features) -> Self {
+        let enabled = [
+            ("threads", features.threads),
+            ("reference_types", features.reference_types),
+            ("simd", features.simd),
+            ("bulk_memory", features.bulk_memory),
+            ("multi_value", features.multi_value),
+        ];
+        for (name, _) in enabled.iter().filter(|(_, enabled)| *enabled) {
+            self.features.insert(name.to_string());
+        }
+        self
+    }
+
+    /// Reports an additional feature, e.g. one of the host.
+    pub fn feature(mut self, name: &str) -> Self {
+        self.features.insert(name.to_string());
+        self
+    }
+
+    /// Grants the capability `name` at `version`, which must be at
+    /// least 1.
+    pub fn capability(mut self, name: &str, version: u32) -> Self {
+        self.capabilities.insert(name.to_string(), version.max(1));
+        self
+    }
+
+    /// Returns whether the feature `name` is reported.
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.contains(name)
+    }
+
+    /// Returns the version of the capability `name`, if granted.
+    pub fn capability_version(&self, name: &str) -> Option<u32> {
+        self.capabilities.get(name).copied()
+    }
+
+    /// Returns the functions to be imported by the guest in the
+    /// [`HOST_INFO_NAMESPACE`] namespace.
+    pub fn exports(&self, store: &Store) -> Exports {
+        let mut exports = Exports::new();
+        exports.insert("version", Function::new_native(store, version));
+        exports.insert(
+            "has_feature",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |host_info: &mut Self, name: u32, name_len: u32| {
+                    let name = host_info.read_name(name, name_len)?;
+                    Ok::<_, RuntimeError>(host_info.has_feature(&name) as i32)
+                },
+            ),
+        );
+        exports.insert(
+            "capability_version",
+            Function::new_native_with_env(
+                store,
+                self.clone(),
+                |host_info: &mut Self, name: u32, name_len: u32| {
+                    let name = host_info.read_name(name, name_len)?;
+                    Ok::<_, RuntimeError>(host_info.capability_version(&name).unwrap_or(0))
+                },
+            ),
+        );
+        exports
+    }
+
+    /// Sets the memory of the guest, that the names are read from.
+    pub fn set_memory(&self, memory: Memory) {
+        *self.memory.lock().unwrap() = Some(memory);
+    }
+
+    fn read_name(&self, name: u32, name_len: u32) -> Result<String, RuntimeError> {
+        let memory = self.memory.lock().unwrap();
+        let memory = memory
+            .as_ref()
+            .ok_or_else(|| RuntimeError::new("host_info: the memory of the guest isn't set"))?;
+        WasmPtr::<u8, Array>::new(name)
+            .get_utf8_string(memory, name_len)
+            .map(str::to_string)
+            .ok_or_else(|| RuntimeError::new("host_info: invalid name"))
+    }
+}
+
+/// Returns the version of this crate, encoded as
+/// `major << 16 | minor << 8 | patch`.
+fn version() -> i32 {
+    let mut parts = crate::VERSION.split('.').map(|part| {
+        part.chars()
+            .take_while(char::is_ascii_digit)
+            .collect::<String>()
+            .parse::<i32>()
+            .unwrap_or(0)
+    });
+    let mut next = || parts.next().unwrap_or(0).min(0xff);
+    let (major, minor, patch) = (next(), next(), next());
+    major << 16 | minor << 8 | patch
+}
@@ -0,0 +1,125 @@
+//! Migration of the state of an instance to an instance of a new
+//! version of its module, for [`Instance::hot_swap`].
+//!
+//! [`Instance::hot_swap`]: crate::Instance::hot_swap
+
+use crate::exports::ExportError;
+use crate::guest_allocator::{GuestAllocator, GuestAllocatorError};
+use crate::instance::Instance;
+use crate::ptr::WasmPtr;
+use crate::types::Mutability;
+use crate::{InstantiationError, RuntimeError};
+use thiserror::Error;
+
+/// An error while hot swapping an instance.
+#[derive(Error, Debug)]
+pub enum HotSwapError {
+    /// The new module couldn't be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    /// An export needed by the migration is missing.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The state couldn't be allocated in the new instance.
+    #[error(transparent)]
+    Allocator(#[from] GuestAllocatorError),
+    /// The migration trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    /// The state of the old instance doesn't fit in the new one.
+    #[error("incompatible state layout: {0}")]
+    IncompatibleLayout(String),
+}
+
+/// Migrates the state by copying the exported memories, and the
+/// exported mutable globals, to the exports of the new instance with
+/// the same names.
+///
+/// It's only correct when both modules lay their state out the same
+/// way, e.g. when only the code of some functions changed.
+pub fn migrate_memory(old: &Instance, new: &Instance) -> Result<(), HotSwapError> {
+    for (name, memory) in old.exports.iter().memories() {
+        let target = new.exports.get_memory(name).map_err(|_| {
+            HotSwapError::IncompatibleLayout(format!("the new module exports no memory `{}`", name))
+        })?;
+        if memory.same(target) {
+            // An imported memory, shared by both instances.
+            continue;
+        }
+        if target.size() < memory.size() {
+            target.grow(memory.size() - target.size()).map_err(|e| {
+                HotSwapError::IncompatibleLayout(format!("can't grow the memory `{}`: {}", name, e))
+            })?;
+        }
+        let len = memory.data_size() as usize;
+        // The instances don't run while their memories are copied.
+        unsafe {
+            target.data_unchecked_mut()[..len].copy_from_slice(memory.data_unchecked());
+        }
+    }
+    for (name, global) in old.exports.iter().globals() {
+        if global.ty().mutability != Mutability::Var {
+            continue;
+        }
+        let target = new.exports.get_global(name).map_err(|_| {
+            HotSwapError::IncompatibleLayout(format!("the new module exports no global `{}`", name))
+        })?;
+        if target.ty() != global.ty() {
+            return Err(HotSwapError::IncompatibleLayout(format!(
+                "the global `{}` has the type {}, expected {}",
+                name,
+                target.ty(),
+                global.ty()
+            )));
+        }
+        target.set(global.get())?;
+    }
+    Ok(())
+}
+
+/// Returns a migration serializing the state with the `save` export of
+/// the old instance, and deserializing it with the `restore` export of
+/// the new one.
+///
+/// `save` has the `() -> i32` signature, and returns a pointer to the
+/// state, prefixed with its length as a little-endian `u32`. The state
+/// is copied to a buffer allocated by the [`GuestAllocator`] of the new
+/// instance, passed to `restore` with the `(ptr: i32, len: i32)`
+/// signature and freed once it returns.
+pub fn migrate_with_exports<'a>(
+    save: &'a str,
+    restore: &'a str,
+) -> impl FnOnce(&Instance, &Instance) -> Result<(), HotSwapError> + 'a {
+    move |old, new| {
+        let save = old.exports.get_native_function::<(), u32>(save)?;
+        let restore = new.exports.get_native_function::<(u32, u32), ()>(restore)?;
+        let memory = old
+            .exports
+            .iter()
+            .memories()
+            .next()
+            .map(|(_, m)| m)
+            .ok_or_else(|| {
+                HotSwapError::IncompatibleLayout("the old module exports no memory".to_string())
+            })?;
+
+        let offset = save.call()?;
+        let len = WasmPtr::<u32>::new(offset)
+            .read(memory)
+            .ok_or_else(|| RuntimeError::new("the saved state is out of bounds"))?;
+        let start = offset as usize + 4;
+        let state = memory
+            .view::<u8>()
+            .get(start..start + len as usize)
+            .ok_or_else(|| RuntimeError::new("the saved state is out of bounds"))?
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<_>>();
+
+        let allocator = GuestAllocator::new(new)?;
+        let buffer = allocator.alloc_bytes(&state)?;
+        restore.call(buffer.offset(), buffer.len())?;
+        buffer.free()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! Recording of the calls a module makes to its function imports, see
+//! [`Store::set_import_call_recording`].
+//!
+//! [`Store::set_import_call_recording`]: crate::Store::set_import_call_recording
+
+use crate::exports::Exportable;
+use crate::externals::Function;
+use crate::store::Store;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use wasmer_engine::{Artifact, Resolver};
+use wasmer_types::ImportIndex;
+use wasmer_vm::{Export, ExportFunction, ModuleInfo, VMFunctionBody, VMFunctionKind};
+
+/// The number of calls made to a function import, by all the
+/// instances of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportCallCount {
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub name: String,
+    /// The number of calls.
+    pub calls: u64,
+}
+
+struct ImportCallCounter {
+    module: String,
+    name: String,
+    calls: Arc<AtomicU64>,
+}
+
+/// The call counters of the function imports of a module, indexed by
+/// function index.
+pub(crate) struct ImportCalls {
+    counters: Vec<ImportCallCounter>,
+}
+
+impl ImportCalls {
+    pub(crate) fn new(module: &ModuleInfo) -> Self {
+        let mut counters = module
+            .imports
+            .iter()
+            .filter_map(|((module, name, _), index)| match index {
+                ImportIndex::Function(index) => Some((*index, module, name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        counters.sort_by_key(|(index, _, _)| *index);
+        Self {
+            counters: counters
+                .into_iter()
+                .map(|(_, module, name)| ImportCallCounter {
+                    module: module.clone(),
+                    name: name.clone(),
+                    calls: Arc::new(AtomicU64::new(0)),
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn counts(&self) -> Vec<ImportCallCount> {
+        self.counters
+            .iter()
+            .map(|counter| ImportCallCount {
+                module: counter.module.clone(),
+                name: counter.name.clone(),
+                calls: counter.calls.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// A resolver wrapping the function imports resolved by another one
+/// into functions counting their calls.
+pub(crate) struct RecordingResolver<'a> {
+    pub(crate) resolver: &'a dyn Resolver,
+    pub(crate) store: &'a Store,
+    pub(crate) artifact: &'a dyn Artifact,
+    pub(crate) calls: &'a ImportCalls,
+}
+
+impl<'a> Resolver for RecordingResolver<'a> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        let function = match self.resolver.resolve(index, module, field)? {
+            Export::Function(function) => function,
+            export => return Some(export),
+        };
+        let module_info = self.artifact.module_ref();
+        let function_index =
+            match module_info
+                .imports
+                .get(&(module.to_string(), field.to_string(), index))
+            {
+                Some(ImportIndex::Function(function_index)) => *function_index,
+                _ => return Some(Export::Function(function)),
+            };
+        let signature_index = module_info.functions[function_index];
+        if function.signature != module_info.signatures[signature_index] {
+            // Let the linking report the mismatch.
+            return Some(Export::Function(function));
+        }
+
+        // Call the import like the instance would, through the dynamic
+        // function trampoline of the module if needed.
+        let address = match function.kind {
+            VMFunctionKind::Dynamic => {
+                self.artifact.finished_dynamic_function_trampolines()[function_index].0
+                    as *const VMFunctionBody
+            }
+            VMFunctionKind::Static => function.address,
+        };
+        let callee = Function::from_export(
+            self.store,
+            ExportFunction {
+                address,
+                vmctx: function.vmctx,
+                signature: function.signature.clone(),
+                kind: VMFunctionKind::Static,
+                call_trampoline: Some(
+                    self.artifact.finished_function_call_trampolines()[signature_index],
+                ),
+                instance_vmctx: None,
+            },
+        );
+        let calls = self.calls.counters[function_index.as_u32() as usize]
+            .calls
+            .clone();
+        let recorder = Function::new_with_env(
+            self.store,
+            &function.signature,
+            (callee, calls),
+            |(callee, calls): &mut (Function, Arc<AtomicU64>), args| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Ok(callee.call(args)?.into_vec())
+            },
+        );
+        Some(recorder.to_export())
+    }
+}
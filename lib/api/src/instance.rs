@@ -1,11 +1,20 @@
-use crate::exports::Exports;
+use crate::events::EventLoop;
+use crate::exports::{ExportError, Exports};
 use crate::externals::Extern;
+use crate::hot_swap::HotSwapError;
+use crate::limits::LimitReservation;
 use crate::module::Module;
 use crate::store::Store;
-use crate::InstantiationError;
+use crate::{InstantiationError, RuntimeError};
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use wasmer_engine::Resolver;
-use wasmer_vm::{InstanceHandle, VMContext};
+use wasmer_types::ExportIndex;
+use wasmer_vm::{InstanceHandle, InstanceMemoryUsage, TrapCode, VMContext};
 
 /// A WebAssembly Instance is a stateful, executable
 /// instance of a WebAssembly [`Module`].
@@ -19,10 +28,33 @@ use wasmer_vm::{InstanceHandle, VMContext};
 pub struct Instance {
     handle: InstanceHandle,
     module: Module,
+    event_loop: Option<EventLoop>,
+    /// The resources reserved in the [`StoreLimits`] of the store, released
+    /// when the instance and its clones are dropped.
+    ///
+    /// [`StoreLimits`]: crate::StoreLimits
+    limits: Option<Arc<LimitReservation>>,
+    /// Shared by the clones of the instance, to count them.
+    clones: Arc<()>,
     /// The exports for an instance.
     pub exports: Exports,
 }
 
+/// The export called by [`Instance::shutdown`].
+pub const SHUTDOWN_EXPORT: &str = "_shutdown";
+
+/// How an [`Instance::shutdown`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// The shutdown export returned before the deadline.
+    Completed,
+    /// The shutdown export didn't return before the deadline, and was
+    /// interrupted.
+    Interrupted,
+    /// The instance doesn't export a shutdown function.
+    Unsupported,
+}
+
 #[cfg(test)]
 mod send_test {
     use super::*;
@@ -70,9 +102,20 @@ impl Instance {
     /// Those are, as defined by the spec:
     ///  * Link errors that happen when plugging the imports into the instance
     ///  * Runtime errors that happen when running the module `start` function.
+    ///
+    /// It can also fail with an [`InstantiationError::Limit`] if the
+    /// instance would exceed the [`StoreLimits`] of the store.
+    ///
+    /// [`StoreLimits`]: crate::StoreLimits
     pub fn new(module: &Module, resolver: &dyn Resolver) -> Result<Self, InstantiationError> {
         let store = module.store();
 
+        let limits = match store.limits() {
+            Some(limits) => Some(Arc::new(
+                limits.reserve(module).map_err(InstantiationError::Limit)?,
+            )),
+            None => None,
+        };
         let handle = module.instantiate(resolver)?;
 
         let exports = module
@@ -88,6 +131,9 @@ impl Instance {
         Ok(Self {
             handle,
             module: module.clone(),
+            event_loop: None,
+            limits,
+            clones: Arc::new(()),
             exports,
         })
     }
@@ -102,7 +148,207 @@ impl Instance {
         self.module.store()
     }
 
-    #[doc(hidden)]
+    /// Returns the memory used by this instance: its linear memories,
+    /// tables, `VMContext` and host-side data.
+    ///
+    /// The code and the metadata of the module, shared by all its
+    /// instances, aren't included.
+    pub fn memory_usage(&self) -> InstanceMemoryUsage {
+        self.handle.memory_usage()
+    }
+
+    /// Returns the CPU time used by the calls into the instance, measured
+    /// with the CPU clock of the calling threads.
+    ///
+    /// The host functions called by the instance count as part of its
+    /// calls; the calls into other instances they make don't.
+    pub fn cpu_time(&self) -> Duration {
+        self.handle.cpu_time()
+    }
+
+    /// Resets the instance to its state right after instantiation, so
+    /// it can serve another request without being instantiated again.
+    ///
+    /// The memories the instance defines are shrunk back to their
+    /// initial size and zeroed by replacing their pages, then the data
+    /// and element segments are applied again, the globals are
+    /// restored, and the start function is run again. Grown tables keep
+    /// their size, with their elements cleared. The memories, tables and
+    /// globals it imports, and the state of the host functions, aren't
+    /// reset.
+    ///
+    /// If it fails, the instance is left in an unspecified state and
+    /// should be dropped.
+    ///
+    /// # Safety
+    ///
+    /// The instance must not be running, e.g. it must not be called by
+    /// a host function called by the instance, and no other thread may
+    /// use the instance, its clones or its exports during the reset.
+    /// The pointers into its memories, such as [`MemoryView`]s, are
+    /// invalidated.
+    ///
+    /// [`MemoryView`]: crate::MemoryView
+    pub unsafe fn reset(&self) -> Result<(), InstantiationError> {
+        self.module.artifact().reset_instance(&self.handle)
+    }
+
+    /// Whether clones of the instance, or of the memories, tables and
+    /// globals it defines and exports, are alive, so resetting it would
+    /// change the state of their users.
+    ///
+    /// The clones of its exported functions aren't counted.
+    pub(crate) fn is_shared(&self) -> bool {
+        if Arc::strong_count(&self.clones) > 1 {
+            return true;
+        }
+        let info = self.module.info();
+        let mut names = HashMap::<&ExportIndex, usize>::new();
+        for index in info.exports.values() {
+            *names.entry(index).or_default() += 1;
+        }
+        info.exports.iter().any(|(name, index)| {
+            let local = match index {
+                ExportIndex::Function(_) => false,
+                ExportIndex::Table(index) => info.local_table_index(*index).is_some(),
+                ExportIndex::Memory(index) => info.local_memory_index(*index).is_some(),
+                ExportIndex::Global(index) => info.local_global_index(*index).is_some(),
+            };
+            let references = match self.exports.get_extern(name) {
+                Some(Extern::Table(table)) if local => table.references(),
+                Some(Extern::Memory(memory)) if local => memory.references(),
+                Some(Extern::Global(global)) if local => global.references(),
+                _ => return false,
+            };
+            // the instance and each of its exports hold a reference
+            references > 1 + names[index]
+        })
+    }
+
+    /// Sets the [`EventLoop`] whose functions are imported by the
+    /// instance, looking its callbacks up in the exported
+    /// `__indirect_function_table`.
+    pub fn set_event_loop(&mut self, event_loop: EventLoop) -> Result<(), ExportError> {
+        let table = self.exports.get_table("__indirect_function_table")?;
+        event_loop.set_function_table(table.clone());
+        self.event_loop = Some(event_loop);
+        Ok(())
+    }
+
+    /// Returns the [`EventLoop`] of the instance, if any.
+    pub fn event_loop(&self) -> Option<&EventLoop> {
+        self.event_loop.as_ref()
+    }
+
+    /// Delivers the expired timers and the posted events of the
+    /// [`EventLoop`] of the instance to the guest callbacks, returning
+    /// the number of callbacks called.
+    ///
+    /// See [`EventLoop::poll`].
+    pub fn poll_events(&self) -> Result<usize, RuntimeError> {
+        match &self.event_loop {
+            Some(event_loop) => event_loop.poll(),
+            None => Ok(0),
+        }
+    }
+
+    /// Replaces the instance with an instance of `module`, a new version
+    /// of its module, and returns the old instance.
+    ///
+    /// `migrate` is called with the old and the new instances to move
+    /// the state of the former to the latter, e.g. with
+    /// [`migrate_memory`] or [`migrate_with_exports`]. If instantiating
+    /// the new module or migrating the state fails, the instance is
+    /// left unchanged, so the callers keep using the old version.
+    ///
+    /// Clones of the instance and its exports taken before the swap
+    /// still refer to the old instance. The [`EventLoop`] isn't carried
+    /// over, since the callbacks refer to the old function table.
+    ///
+    /// [`migrate_memory`]: crate::migrate_memory
+    /// [`migrate_with_exports`]: crate::migrate_with_exports
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let store = Store::default();
+    /// let v1 = Module::new(&store, r#"(module
+    ///     (global $count (export "count") (mut i32) (i32.const 0))
+    ///     (func (export "tick") (global.set $count (i32.add (global.get $count) (i32.const 1)))))"#)?;
+    /// let v2 = Module::new(&store, r#"(module
+    ///     (global $count (export "count") (mut i32) (i32.const 0))
+    ///     (func (export "tick") (global.set $count (i32.add (global.get $count) (i32.const 2)))))"#)?;
+    ///
+    /// let mut instance = Instance::new(&v1, &imports! {})?;
+    /// instance.exports.get_function("tick")?.call(&[])?;
+    /// instance.hot_swap(&v2, &imports! {}, migrate_memory)?;
+    /// instance.exports.get_function("tick")?.call(&[])?;
+    /// assert_eq!(instance.exports.get_global("count")?.get(), Value::I32(3));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hot_swap<F>(
+        &mut self,
+        module: &Module,
+        resolver: &dyn Resolver,
+        migrate: F,
+    ) -> Result<Self, HotSwapError>
+    where
+        F: FnOnce(&Self, &Self) -> Result<(), HotSwapError>,
+    {
+        let new = Self::new(module, resolver)?;
+        migrate(self, &new)?;
+        Ok(std::mem::replace(self, new))
+    }
+
+    /// Asks a reactor-style instance to shut down, by calling its
+    /// [`SHUTDOWN_EXPORT`] export, so it can drain its pending work.
+    ///
+    /// If the export doesn't return within `deadline`, it's interrupted
+    /// through the epoch deadline of the store, which requires code
+    /// compiled with `CompilerConfig::enable_epoch_interruption`. The
+    /// deadline interrupts all the code of the store running at the
+    /// time, and the previous deadline is restored afterwards.
+    ///
+    /// Traps other than the interruption are returned as errors.
+    pub fn shutdown(&self, deadline: Duration) -> Result<ShutdownOutcome, RuntimeError> {
+        let shutdown = match self.exports.get_function(SHUTDOWN_EXPORT) {
+            Ok(shutdown) => shutdown,
+            Err(_) => return Ok(ShutdownOutcome::Unsupported),
+        };
+
+        let previous_deadline = self.store().epoch().deadline();
+        let (done, watchdog_done) = mpsc::channel::<()>();
+        let store = self.store().clone();
+        let watchdog = thread::spawn(move || match watchdog_done.recv_timeout(deadline) {
+            Err(RecvTimeoutError::Timeout) => {
+                store.set_epoch_deadline(0);
+                true
+            }
+            _ => false,
+        });
+        let result = shutdown.call(&[]);
+        drop(done);
+        let interrupted = watchdog.join().unwrap();
+        if interrupted {
+            self.store().epoch().set_deadline_at(previous_deadline);
+        }
+
+        match result {
+            Ok(_) => Ok(ShutdownOutcome::Completed),
+            Err(error) if interrupted && error.trap_code() == Some(TrapCode::Interrupt) => {
+                Ok(ShutdownOutcome::Interrupted)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns a pointer to the `VMContext` of the instance, laid out
+    /// as described by [`Module::vmctx_layout`].
+    ///
+    /// The pointer is valid as long as the instance is alive. The
+    /// memories, tables and globals may be accessed through it while no
+    /// WebAssembly code of the instance runs.
     pub fn vmctx_ptr(&self) -> *mut VMContext {
         self.handle.vmctx_ptr()
     }
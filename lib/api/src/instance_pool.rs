@@ -0,0 +1,157 @@
+//! A pool of ready instances of a module, reset between uses.
+
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::InstantiationError;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+/// A pool of instances of a module, created ahead of time and reset with
+/// [`Instance::reset`] when they're returned, so request handlers skip
+/// instantiation.
+///
+/// The imports are shared by all the instances, so host state kept by
+/// the host functions isn't reset with them.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"(module
+///     (global $count (export "count") (mut i32) (i32.const 0))
+///     (func (export "handle") (result i32)
+///         (global.set $count (i32.add (global.get $count) (i32.const 1)))
+///         (global.get $count)))"#)?;
+/// let pool = InstancePool::new(&module, &imports! {}, 2)?;
+/// assert_eq!(pool.idle(), 2);
+///
+/// for _ in 0..3 {
+///     let instance = pool.get()?;
+///     let handle = instance.exports.get_function("handle")?;
+///     assert_eq!(handle.call(&[])?[0], Value::I32(1));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct InstancePool {
+    module: Module,
+    imports: ImportObject,
+    max_idle: usize,
+    idle: Mutex<Vec<Instance>>,
+}
+
+impl InstancePool {
+    /// Creates a pool of instances of `module` with `imports`, starting
+    /// with `size` instances and keeping at most `size` idle ones.
+    pub fn new(
+        module: &Module,
+        imports: &ImportObject,
+        size: usize,
+    ) -> Result<Self, InstantiationError> {
+        let idle = (0..size)
+            .map(|_| Instance::new(module, imports))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            module: module.clone(),
+            imports: imports.clone(),
+            max_idle: size,
+            idle: Mutex::new(idle),
+        })
+    }
+
+    /// Takes an instance from the pool, or creates one if none is idle.
+    /// The instance is reset and returned to the pool when the
+    /// [`PooledInstance`] is dropped.
+    pub fn get(&self) -> Result<PooledInstance<'_>, InstantiationError> {
+        let instance = self.idle.lock().unwrap().pop();
+        let instance = match instance {
+            Some(instance) => instance,
+            None => Instance::new(&self.module, &self.imports)?,
+        };
+        Ok(PooledInstance {
+            pool: self,
+            instance: Some(instance),
+        })
+    }
+
+    /// Returns the number of idle instances.
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Resets `instance` and keeps it if the pool isn't full. Instances
+    /// failing to reset, or still shared with clones, are dropped.
+    fn put(&self, instance: Instance) {
+        if self.idle() >= self.max_idle || instance.is_shared() {
+            return;
+        }
+        // Safety: the instance isn't running, since it was borrowed by
+        // its `PooledInstance`, and no clone of it, or of the memories,
+        // tables and globals it defines, is left.
+        if unsafe { instance.reset() }.is_err() {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push(instance);
+        }
+    }
+}
+
+impl fmt::Debug for InstancePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InstancePool")
+            .field("module", &self.module)
+            .field("max_idle", &self.max_idle)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// An instance taken from an [`InstancePool`], returned to it when
+/// dropped.
+///
+/// The instance is reset for its next user, unless clones of it, or of
+/// the memories, tables and globals it exports, outlive it: it's dropped
+/// then. The clones of its exported functions should not outlive it,
+/// since they would call into the instance of the next user.
+pub struct PooledInstance<'pool> {
+    pool: &'pool InstancePool,
+    instance: Option<Instance>,
+}
+
+impl<'pool> PooledInstance<'pool> {
+    /// Takes the instance out of the pool for good, e.g. to keep its
+    /// state.
+    pub fn detach(mut self) -> Instance {
+        self.instance.take().unwrap()
+    }
+}
+
+impl<'pool> Deref for PooledInstance<'pool> {
+    type Target = Instance;
+
+    fn deref(&self) -> &Instance {
+        self.instance.as_ref().unwrap()
+    }
+}
+
+impl<'pool> Drop for PooledInstance<'pool> {
+    fn drop(&mut self) {
+        if let Some(instance) = self.instance.take() {
+            self.pool.put(instance);
+        }
+    }
+}
+
+impl<'pool> fmt::Debug for PooledInstance<'pool> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PooledInstance")
+            .field("instance", &self.instance)
+            .finish()
+    }
+}
@@ -24,14 +24,37 @@
     )
 )]
 
+mod callbacks;
+mod code_cache;
+#[cfg(feature = "compiler")]
+mod debugger;
+mod differential;
+mod disassembly;
+mod events;
+mod exit_status;
 mod exports;
 mod externals;
+mod fibers;
+mod guest_allocator;
+mod host_info;
+mod hot_swap;
+mod import_calls;
 mod import_object;
 mod instance;
+mod instance_pool;
+mod limits;
+mod linker;
+mod memory_debug;
 mod module;
 mod native;
+mod plugin;
+mod policy;
 mod ptr;
+mod scheduler;
+#[cfg(feature = "compiler")]
+mod split;
 mod store;
+mod threads;
 mod tunables;
 mod types;
 mod utils;
@@ -45,16 +68,46 @@ pub mod internals {
     pub use crate::externals::{WithEnv, WithoutEnv};
 }
 
+pub use crate::callbacks::{CallbackId, CallbackTable};
+pub use crate::code_cache::CodeCache;
+#[cfg(feature = "compiler")]
+pub use crate::debugger::{DebugError, DebugModule, FunctionEntry};
+pub use crate::differential::{
+    Differential, DifferentialCall, DifferentialError, DifferentialOutcome,
+};
+pub use crate::disassembly::{Disassembly, OffsetMapping};
+pub use crate::events::{EventLoop, EVENTS_NAMESPACE};
+pub use crate::exit_status::{ExitStatus, StderrCapture};
 pub use crate::exports::{ExportError, Exportable, Exports, ExportsIterator};
 pub use crate::externals::{
     Extern, FromToNativeWasmType, Function, Global, HostFunction, Memory, Table, WasmTypeList,
 };
+pub use crate::fibers::{FiberStatus, Fibers, FIBERS_NAMESPACE};
+pub use crate::guest_allocator::{GuestAllocator, GuestAllocatorError, GuestBuffer};
+pub use crate::host_info::{HostCapabilities, HOST_INFO_NAMESPACE};
+pub use crate::hot_swap::{migrate_memory, migrate_with_exports, HotSwapError};
+pub use crate::import_calls::ImportCallCount;
 pub use crate::import_object::{ImportObject, ImportObjectIterator, LikeNamespace};
-pub use crate::instance::Instance;
+pub use crate::instance::{Instance, ShutdownOutcome, SHUTDOWN_EXPORT};
+pub use crate::instance_pool::{InstancePool, PooledInstance};
+pub use crate::limits::{StoreLimits, StoreUsage};
+pub use crate::linker::{Linker, LinkerError};
+pub use crate::memory_debug::{CanaryError, MemoryCanaries, CANARY_SIZE};
 pub use crate::module::Module;
 pub use crate::native::NativeFunc;
-pub use crate::ptr::{Array, Item, WasmPtr};
+pub use crate::plugin::{
+    required_interfaces, HostInterface, InterfaceRequirement, PluginError, PluginHost,
+    INTERFACES_SECTION,
+};
+pub use crate::policy::{PolicyViolation, StorePolicy};
+pub use crate::ptr::{Array, Item, MemoryAccessError, OutOfBoundsPolicy, WasmPtr};
+pub use crate::scheduler::{
+    Scheduler, TaskError, TaskHandle, TaskOptions, DEFAULT_TIME_SLICE, STEP_EXPORT,
+};
+#[cfg(feature = "compiler")]
+pub use crate::split::{SplitError, SplitModule};
 pub use crate::store::{Store, StoreObject};
+pub use crate::threads::{ThreadError, WasmThread, WasmThreads, THREAD_START_EXPORT};
 pub use crate::tunables::Tunables;
 pub use crate::types::{
     ExportType, ExternRef, ExternType, FunctionType, GlobalType, HostInfo, HostRef, ImportType,
@@ -65,19 +118,29 @@ pub use crate::utils::is_wasm;
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{
-    wasmparser, CompilerConfig, FunctionMiddleware, FunctionMiddlewareGenerator,
-    MiddlewareReaderState,
+    wasmparser, CompileLimits, CompilerConfig, FunctionAnalysis, FunctionCache, FunctionMiddleware,
+    FunctionMiddlewareGenerator, MiddlewareReaderState, ModuleAnalysis,
 };
-pub use wasmer_compiler::{CpuFeature, Features, Target};
+pub use wasmer_compiler::{CompiledFunctionStats, CpuFeature, Features, Target};
 pub use wasmer_engine::{
-    ChainableNamedResolver, DeserializeError, Engine, FrameInfo, InstantiationError, LinkError,
-    NamedResolver, NamedResolverChain, Resolver, RuntimeError, SerializeError,
+    detached_signature, public_key, sign_module, ChainableNamedResolver, DeserializeError, Engine,
+    FrameInfo, ImportError, InstantiationError, LinkError, NamedResolver, NamedResolverChain,
+    Resolver, RuntimeError, SerializeError, StoreLimitError, TrustStore, SIGNATURE_LEN,
+    SIGNATURE_SECTION,
 };
 pub use wasmer_types::{
-    Atomically, Bytes, GlobalInit, LocalFunctionIndex, MemoryView, Pages, ValueType,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    little_endian_struct, AtomicValue, Atomically, Bytes, GlobalInit, LittleEndian,
+    LocalFunctionIndex, MemoryView, Pages, ValueType, WaitResult, WASM_MAX_PAGES, WASM_MIN_PAGES,
+    WASM_PAGE_SIZE,
+};
+pub use wasmer_vm::{
+    assert_vmcontext_layout_version, raise_user_trap, set_break_on_trap, set_signal_handler_policy,
+    Export, InstanceAllocator, InstanceMemoryUsage, MemoryError, MemoryStyle, ModuleHash,
+    SignalHandlerPolicy, SignalHandlerPolicyError, SystemInstanceAllocator, TrapCode,
+    VMContextLayout, VMCONTEXT_LAYOUT_VERSION,
 };
-pub use wasmer_vm::{raise_user_trap, Export, MemoryError};
+#[cfg(feature = "wasmprinter")]
+pub use wasmprinter::print_bytes as wasm2wat;
 #[cfg(feature = "wat")]
 pub use wat::parse_bytes as wat2wasm;
 
@@ -0,0 +1,232 @@
+//! Limits on the resources used by the instances of a store.
+
+use crate::module::Module;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use wasmer_engine::StoreLimitError;
+use wasmer_types::{Bytes, MemoryType, Pages};
+use wasmer_vm::{Memory as VMMemory, MemoryError, MemoryStyle, VMMemoryDefinition};
+
+/// Caps on the instances of a store and the resources they define, see
+/// [`Store::set_limits`].
+///
+/// Instantiating a module that would exceed a cap fails with an
+/// [`InstantiationError::Limit`]. An instance counts until it and all
+/// its clones are dropped. The memories count with their initial size
+/// when the instance is created, and growing them fails once they would
+/// exceed the cap, until they're dropped. Imported memories and tables
+/// count for the instance defining them.
+///
+/// The clones of the limits share the usage of the instances, so a
+/// store and its clones share their capacity.
+///
+/// [`Store::set_limits`]: crate::Store::set_limits
+/// [`InstantiationError::Limit`]: crate::InstantiationError::Limit
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// store.set_limits(StoreLimits::new().instances(1));
+///
+/// let module = Module::new(&store, "(module)")?;
+/// let instance = Instance::new(&module, &imports! {})?;
+/// assert!(matches!(
+///     Instance::new(&module, &imports! {}),
+///     Err(InstantiationError::Limit(StoreLimitError::Instances(1)))
+/// ));
+/// drop(instance);
+/// Instance::new(&module, &imports! {})?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StoreLimits {
+    max_instances: Option<usize>,
+    max_memory_bytes: Option<u64>,
+    max_tables: Option<usize>,
+    usage: Arc<Mutex<StoreUsage>>,
+}
+
+/// The resources used by the live instances of a store, see
+/// [`StoreLimits::usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreUsage {
+    /// The number of live instances.
+    pub instances: usize,
+    /// The size of the memories they define, in bytes.
+    pub memory_bytes: u64,
+    /// The number of tables they define.
+    pub tables: usize,
+}
+
+impl StoreLimits {
+    /// Creates limits that don't cap anything yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the number of live instances.
+    pub fn instances(mut self, max: usize) -> Self {
+        self.max_instances = Some(max);
+        self
+    }
+
+    /// Caps the total size of the memories of the live instances, in
+    /// bytes.
+    pub fn memory(mut self, max_bytes: u64) -> Self {
+        self.max_memory_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Caps the number of tables of the live instances.
+    pub fn tables(mut self, max: usize) -> Self {
+        self.max_tables = Some(max);
+        self
+    }
+
+    /// Returns the resources currently used by the live instances.
+    pub fn usage(&self) -> StoreUsage {
+        *self.usage.lock().unwrap()
+    }
+
+    /// Reserves the resources of an instance of `module`, until the
+    /// returned reservation is dropped.
+    pub(crate) fn reserve(&self, module: &Module) -> Result<LimitReservation, StoreLimitError> {
+        let info = module.info();
+        let reserved = StoreUsage {
+            instances: 1,
+            memory_bytes: info
+                .memories
+                .values()
+                .skip(info.num_imported_memories)
+                .map(|memory| memory.minimum.bytes().0 as u64)
+                .sum(),
+            tables: info.tables.len() - info.num_imported_tables,
+        };
+
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(max) = self.max_instances {
+            if usage.instances + reserved.instances > max {
+                return Err(StoreLimitError::Instances(max));
+            }
+        }
+        if let Some(limit) = self.max_memory_bytes {
+            let requested = usage.memory_bytes + reserved.memory_bytes;
+            if requested > limit {
+                return Err(StoreLimitError::Memory { requested, limit });
+            }
+        }
+        if let Some(limit) = self.max_tables {
+            let requested = usage.tables + reserved.tables;
+            if requested > limit {
+                return Err(StoreLimitError::Tables { requested, limit });
+            }
+        }
+        usage.instances += reserved.instances;
+        usage.memory_bytes += reserved.memory_bytes;
+        usage.tables += reserved.tables;
+
+        Ok(LimitReservation {
+            usage: self.usage.clone(),
+            reserved,
+        })
+    }
+
+    /// Wraps a memory defined by an instance, so that its growth counts
+    /// against the memory cap.
+    pub(crate) fn limit_memory(&self, memory: Arc<dyn VMMemory>) -> Arc<dyn VMMemory> {
+        Arc::new(LimitedMemory {
+            inner: memory,
+            limits: self.clone(),
+            grown: Mutex::new(0),
+        })
+    }
+}
+
+/// A memory whose growth counts in the usage of [`StoreLimits`], until
+/// it's dropped or reset.
+#[derive(Debug)]
+struct LimitedMemory {
+    inner: Arc<dyn VMMemory>,
+    limits: StoreLimits,
+    /// the bytes the memory grew by, counted in the usage
+    grown: Mutex<u64>,
+}
+
+impl LimitedMemory {
+    fn release_growth(&self) {
+        let mut grown = self.grown.lock().unwrap();
+        self.limits.usage.lock().unwrap().memory_bytes -= *grown;
+        *grown = 0;
+    }
+}
+
+impl VMMemory for LimitedMemory {
+    fn ty(&self) -> &MemoryType {
+        self.inner.ty()
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        self.inner.style()
+    }
+
+    fn size(&self) -> Pages {
+        self.inner.size()
+    }
+
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        let bytes = Bytes::from(delta).0 as u64;
+        let mut grown = self.grown.lock().unwrap();
+        // the usage stays locked until the memory grew, so that concurrent
+        // growths can't both fit
+        let mut usage = self.limits.usage.lock().unwrap();
+        if let Some(limit) = self.limits.max_memory_bytes {
+            let requested = usage.memory_bytes + bytes;
+            if requested > limit {
+                return Err(MemoryError::Generic(
+                    StoreLimitError::Memory { requested, limit }.to_string(),
+                ));
+            }
+        }
+        let previous = self.inner.grow(delta)?;
+        usage.memory_bytes += bytes;
+        *grown += bytes;
+        Ok(previous)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.inner.vmmemory()
+    }
+
+    fn reset(&self) -> Result<(), MemoryError> {
+        self.inner.reset()?;
+        // the memory is back to its initial size
+        self.release_growth();
+        Ok(())
+    }
+}
+
+impl Drop for LimitedMemory {
+    fn drop(&mut self) {
+        self.release_growth();
+    }
+}
+
+/// The resources of an instance, released when it's dropped.
+#[derive(Debug)]
+pub(crate) struct LimitReservation {
+    usage: Arc<Mutex<StoreUsage>>,
+    reserved: StoreUsage,
+}
+
+impl Drop for LimitReservation {
+    fn drop(&mut self) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.instances -= self.reserved.instances;
+        usage.memory_bytes -= self.reserved.memory_bytes;
+        usage.tables -= self.reserved.tables;
+    }
+}
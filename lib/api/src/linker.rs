@@ -0,0 +1,194 @@
+//! Linking of several modules together, by the names of their
+//! instances.
+
+use crate::exports::{Exportable, Exports};
+use crate::externals::Extern;
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::Store;
+use crate::InstantiationError;
+use std::collections::HashMap;
+use thiserror::Error;
+use wasmer_engine::NamedResolver;
+use wasmer_vm::Export;
+
+/// An error while defining imports in a [`Linker`], or instantiating a
+/// module with them.
+#[derive(Error, Debug)]
+pub enum LinkerError {
+    /// The import is already defined and shadowing isn't allowed.
+    #[error("`{module}`.`{name}` is already defined")]
+    AlreadyDefined {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        name: String,
+    },
+    /// The instantiation of a module failed.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+}
+
+/// Resolves the imports of modules by name, from host definitions and
+/// the exports of the instances registered under a name.
+///
+/// It makes linking several modules together automatic: each module
+/// instantiated with [`Linker::module`] has its exports registered
+/// under its name, to be imported by the modules instantiated next.
+///
+/// By default, defining an import twice is an error. Shadowing can be
+/// allowed with [`Linker::allow_shadowing`], e.g. to override some
+/// imports.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let math = Module::new(&store, r#"(module
+///     (func (export "double") (param i32) (result i32)
+///         (i32.mul (local.get 0) (i32.const 2))))"#)?;
+/// let app = Module::new(&store, r#"(module
+///     (import "math" "double" (func $double (param i32) (result i32)))
+///     (import "env" "offset" (global $offset i32))
+///     (func (export "run") (param i32) (result i32)
+///         (i32.add (call $double (local.get 0)) (global.get $offset))))"#)?;
+///
+/// let mut linker = Linker::new(&store);
+/// linker.define("env", "offset", Global::new(&store, Value::I32(1)))?;
+/// linker.module("math", &math)?;
+/// let app = linker.instantiate(&app)?;
+/// let run = app.exports.get_native_function::<i32, i32>("run")?;
+/// assert_eq!(run.call(20)?, 41);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Linker {
+    store: Store,
+    definitions: HashMap<(String, String), Extern>,
+    allow_shadowing: bool,
+}
+
+impl Linker {
+    /// Creates a `Linker` with no definitions.
+    pub fn new(store: &Store) -> Self {
+        Self {
+            store: store.clone(),
+            definitions: HashMap::new(),
+            allow_shadowing: false,
+        }
+    }
+
+    /// Returns the [`Store`] of the linker.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Sets whether defining an import that is already defined replaces
+    /// it, instead of being an error.
+    pub fn allow_shadowing(&mut self, allow: bool) -> &mut Self {
+        self.allow_shadowing = allow;
+        self
+    }
+
+    /// Defines the import `name` of the module `module`.
+    pub fn define(
+        &mut self,
+        module: &str,
+        name: &str,
+        item: impl Into<Extern>,
+    ) -> Result<&mut Self, LinkerError> {
+        self.check_undefined(module, name)?;
+        self.definitions
+            .insert((module.to_string(), name.to_string()), item.into());
+        Ok(self)
+    }
+
+    /// Defines the imports of the module `module` from `exports`.
+    pub fn define_namespace(
+        &mut self,
+        module: &str,
+        exports: &Exports,
+    ) -> Result<&mut Self, LinkerError> {
+        for (name, _) in exports.iter() {
+            self.check_undefined(module, name)?;
+        }
+        for (name, item) in exports.iter() {
+            self.definitions
+                .insert((module.to_string(), name.clone()), item.clone());
+        }
+        Ok(self)
+    }
+
+    /// Defines all the imports of `import_object`, e.g. the imports
+    /// generated by WASI or Emscripten.
+    pub fn define_import_object(
+        &mut self,
+        import_object: &ImportObject,
+    ) -> Result<&mut Self, LinkerError> {
+        let definitions = import_object
+            .clone()
+            .into_iter()
+            .map(|((module, name), export)| {
+                ((module, name), Extern::from_export(&self.store, export))
+            })
+            .collect::<Vec<_>>();
+        for ((module, name), _) in &definitions {
+            self.check_undefined(module, name)?;
+        }
+        self.definitions.extend(definitions);
+        Ok(self)
+    }
+
+    /// Registers the exports of `instance` under the name `name`, for
+    /// the modules importing from `name`.
+    pub fn instance(&mut self, name: &str, instance: &Instance) -> Result<&mut Self, LinkerError> {
+        self.define_namespace(name, &instance.exports)
+    }
+
+    /// Instantiates `module` and registers its exports under the name
+    /// `name`, returning the instance.
+    pub fn module(&mut self, name: &str, module: &Module) -> Result<Instance, LinkerError> {
+        let instance = self.instantiate(module)?;
+        self.instance(name, &instance)?;
+        Ok(instance)
+    }
+
+    /// Instantiates `module` with the definitions of the linker.
+    pub fn instantiate(&self, module: &Module) -> Result<Instance, LinkerError> {
+        Ok(Instance::new(module, self)?)
+    }
+
+    /// Returns the definition of the import `name` of the module
+    /// `module`, if any.
+    pub fn get(&self, module: &str, name: &str) -> Option<&Extern> {
+        self.definitions
+            .get(&(module.to_string(), name.to_string()))
+    }
+
+    /// Returns the definitions of the linker, as `(module, name, item)`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &Extern)> {
+        self.definitions
+            .iter()
+            .map(|((module, name), item)| (module.as_str(), name.as_str(), item))
+    }
+
+    fn check_undefined(&self, module: &str, name: &str) -> Result<(), LinkerError> {
+        if !self.allow_shadowing && self.get(module, name).is_some() {
+            return Err(LinkerError::AlreadyDefined {
+                module: module.to_string(),
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl NamedResolver for Linker {
+    fn resolve_by_name(&self, module: &str, name: &str) -> Option<Export> {
+        self.get(module, name).map(Extern::to_export)
+    }
+}
@@ -0,0 +1,164 @@
+//! Debugging aids for memory corruption in guests, such as C code
+//! ported to WebAssembly: poisoning grown memory, and canaries around
+//! the buffers written by the host.
+
+use crate::externals::Memory;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer_types::{Bytes, MemoryType, Pages};
+use wasmer_vm::{Memory as VMMemory, MemoryError, MemoryStyle, VMMemoryDefinition};
+
+/// A memory filling the pages it grows by with a byte, set by
+/// [`Store::set_memory_poison`](crate::Store::set_memory_poison).
+#[derive(Debug)]
+pub(crate) struct PoisonedMemory {
+    pub(crate) inner: Arc<dyn VMMemory>,
+    pub(crate) byte: u8,
+}
+
+impl VMMemory for PoisonedMemory {
+    fn ty(&self) -> &MemoryType {
+        self.inner.ty()
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        self.inner.style()
+    }
+
+    fn size(&self) -> Pages {
+        self.inner.size()
+    }
+
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        let previous = self.inner.grow(delta)?;
+        let start = Bytes::from(previous).0;
+        let end = Bytes::from(self.inner.size()).0;
+        // The pages were just added, so nothing else accesses them yet.
+        unsafe {
+            let definition = self.inner.vmmemory().as_ref();
+            std::ptr::write_bytes(definition.base.add(start), self.byte, end - start);
+        }
+        Ok(previous)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.inner.vmmemory()
+    }
+
+    fn reset(&self) -> Result<(), MemoryError> {
+        self.inner.reset()
+    }
+}
+
+/// The size of each of the two canaries around a guarded region.
+pub const CANARY_SIZE: u32 = 8;
+
+const CANARY: [u8; CANARY_SIZE as usize] = [0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef];
+
+/// An error found by [`MemoryCanaries`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CanaryError {
+    /// The canaries of a region don't fit in the memory.
+    #[error("the canaries of the region at {offset:#x} of {len} bytes are out of bounds")]
+    OutOfBounds {
+        /// The offset of the region.
+        offset: u32,
+        /// The length of the region.
+        len: u32,
+    },
+    /// A canary was overwritten, e.g. by the guest writing past the end of
+    /// a buffer given by the host.
+    #[error(
+        "the canary at {canary:#x} of the region at {offset:#x} of {len} bytes was overwritten"
+    )]
+    Overwritten {
+        /// The offset of the region.
+        offset: u32,
+        /// The length of the region.
+        len: u32,
+        /// The offset of the overwritten canary.
+        canary: u32,
+    },
+}
+
+/// Canaries placed around the regions of a memory written by the host,
+/// to detect the guest writing out of them.
+///
+/// The host reserves [`CANARY_SIZE`] bytes before and after each region,
+/// e.g. by allocating them with the buffer, guards the region with
+/// [`MemoryCanaries::guard`] and checks the canaries after calling the
+/// guest with [`MemoryCanaries::check`].
+#[derive(Debug, Clone)]
+pub struct MemoryCanaries {
+    memory: Memory,
+    regions: Vec<(u32, u32)>,
+}
+
+impl MemoryCanaries {
+    /// Creates canaries for `memory`, without regions.
+    pub fn new(memory: &Memory) -> Self {
+        Self {
+            memory: memory.clone(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// Writes the canaries around the region of `len` bytes at `offset`,
+    /// and checks them from now on.
+    pub fn guard(&mut self, offset: u32, len: u32) -> Result<(), CanaryError> {
+        for &canary in self.canaries(offset, len)?.iter() {
+            for (cell, byte) in self.memory.view::<u8>()[canary as usize..]
+                .iter()
+                .zip(&CANARY)
+            {
+                cell.set(*byte);
+            }
+        }
+        self.regions.push((offset, len));
+        Ok(())
+    }
+
+    /// Stops checking the canaries of the region at `offset`, e.g. when
+    /// it's freed.
+    pub fn release(&mut self, offset: u32) {
+        self.regions.retain(|(region, _)| *region != offset);
+    }
+
+    /// Checks that the canaries of all the regions are intact.
+    pub fn check(&self) -> Result<(), CanaryError> {
+        let view = self.memory.view::<u8>();
+        for &(offset, len) in self.regions.iter() {
+            for &canary in self.canaries(offset, len)?.iter() {
+                let intact = view[canary as usize..]
+                    .iter()
+                    .zip(&CANARY)
+                    .all(|(cell, byte)| cell.get() == *byte);
+                if !intact {
+                    return Err(CanaryError::Overwritten {
+                        offset,
+                        len,
+                        canary,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the offsets of the canaries of a region.
+    fn canaries(&self, offset: u32, len: u32) -> Result<[u32; 2], CanaryError> {
+        let out_of_bounds = CanaryError::OutOfBounds { offset, len };
+        let before = offset
+            .checked_sub(CANARY_SIZE)
+            .ok_or_else(|| out_of_bounds.clone())?;
+        let after = offset
+            .checked_add(len)
+            .ok_or_else(|| out_of_bounds.clone())?;
+        match after.checked_add(CANARY_SIZE) {
+            Some(end) if u64::from(end) <= self.memory.data_size() => Ok([before, after]),
+            _ => Err(out_of_bounds),
+        }
+    }
+}
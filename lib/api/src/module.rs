@@ -1,16 +1,20 @@
+use crate::disassembly::Disassembly;
+use crate::import_calls::{ImportCallCount, ImportCalls, RecordingResolver};
 use crate::store::Store;
 use crate::types::{ExportType, ImportType};
+use crate::utils::is_wasm;
 use crate::InstantiationError;
 use std::fmt;
 use std::io;
 use std::path::Path;
 use std::sync::Arc;
 use thiserror::Error;
-use wasmer_compiler::CompileError;
-#[cfg(feature = "wat")]
-use wasmer_compiler::WasmError;
-use wasmer_engine::{Artifact, DeserializeError, Resolver, SerializeError};
-use wasmer_vm::{ExportsIterator, ImportsIterator, InstanceHandle, ModuleInfo};
+#[cfg(feature = "compiler")]
+use wasmer_compiler::{analyze_module, tree_shake, ModuleAnalysis};
+use wasmer_compiler::{CompileError, CompiledFunctionStats, WasmError};
+use wasmer_engine::{Artifact, DeserializeError, ImportError, LinkError, Resolver, SerializeError};
+use wasmer_types::FunctionIndex;
+use wasmer_vm::{ExportsIterator, ImportsIterator, InstanceHandle, ModuleInfo, VMContextLayout};
 
 #[derive(Error, Debug)]
 pub enum IoCompileError {
@@ -34,6 +38,8 @@ pub enum IoCompileError {
 pub struct Module {
     store: Store,
     artifact: Arc<dyn Artifact>,
+    import_calls: Option<Arc<ImportCalls>>,
+    binary: Option<Arc<[u8]>>,
 }
 
 impl Module {
@@ -127,7 +133,56 @@ impl Module {
     /// Opposed to [`Module::new`], this function is not compatible with
     /// the WebAssembly text format (if the "wat" feature is enabled for
     /// this crate).
+    ///
+    /// If the engine of the store has a [`TrustStore`], the binary must
+    /// embed a [`SIGNATURE_SECTION`] signed with one of its keys.
+    ///
+    /// [`TrustStore`]: crate::TrustStore
+    /// [`SIGNATURE_SECTION`]: crate::SIGNATURE_SECTION
     pub fn from_binary(store: &Store, binary: &[u8]) -> Result<Self, CompileError> {
+        if let Some(trust_store) = store.engine().trust_store() {
+            trust_store.verify(binary)?;
+        }
+        Self::validate(store, binary)?;
+        unsafe { Self::from_binary_unchecked(store, binary) }
+    }
+
+    /// Creates a new WebAssembly module from a binary and its detached
+    /// `signature`, as made by [`detached_signature`].
+    ///
+    /// If the engine of the store has a [`TrustStore`], the signature
+    /// must be made with one of its keys; otherwise it isn't checked.
+    ///
+    /// [`detached_signature`]: crate::detached_signature
+    /// [`TrustStore`]: crate::TrustStore
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let secret_key = [7; 32];
+    /// let binary = wat2wasm(b"(module)")?;
+    /// let signature = detached_signature(&binary, &secret_key);
+    ///
+    /// let mut trust_store = TrustStore::new();
+    /// trust_store.trust(&public_key(&secret_key))?;
+    /// let engine = JIT::new(&Cranelift::default()).trust_store(trust_store).engine();
+    /// let store = Store::new(&engine);
+    ///
+    /// assert!(Module::from_binary(&store, &binary).is_err());
+    /// Module::from_binary_with_signature(&store, &binary, &signature)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_binary_with_signature(
+        store: &Store,
+        binary: &[u8],
+        signature: &[u8],
+    ) -> Result<Self, CompileError> {
+        if let Some(trust_store) = store.engine().trust_store() {
+            trust_store.verify_detached(binary, signature)?;
+        }
         Self::validate(store, binary)?;
         unsafe { Self::from_binary_unchecked(store, binary) }
     }
@@ -139,6 +194,7 @@ impl Module {
     /// This can speed up compilation time a bit, but it should be only used
     /// in environments where the WebAssembly modules are trusted and validated
     /// beforehand.
+    /// The signature of the module isn't verified either.
     pub unsafe fn from_binary_unchecked(
         store: &Store,
         binary: &[u8],
@@ -157,9 +213,64 @@ impl Module {
         store.engine().validate(binary)
     }
 
+    /// Estimates the stack and memory requirements of the functions of
+    /// a WebAssembly module, without compiling it.
+    ///
+    /// The estimates are conservative, so they can be used to place
+    /// instances without trial and error. See [`ModuleAnalysis`] and
+    /// [`FunctionAnalysis`] for what is estimated.
+    ///
+    /// Like [`Module::new`], the bytes can be in the WebAssembly text
+    /// format if the "wat" feature is enabled for this crate.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module
+    ///     (memory 1)
+    ///     (func $square (param i32) (result i32)
+    ///         (i32.mul (local.get 0) (local.get 0)))
+    ///     (func (export "run") (result i32)
+    ///         (call $square (i32.const 4))))"#;
+    /// let analysis = Module::analyze(&store, wat)?;
+    /// let run = analysis.export("run").unwrap();
+    /// assert_eq!(run.max_call_depth, Some(2));
+    /// assert_eq!(analysis.initial_memory, Bytes(WASM_PAGE_SIZE));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ModuleAnalysis`]: crate::ModuleAnalysis
+    /// [`FunctionAnalysis`]: crate::FunctionAnalysis
+    #[cfg(feature = "compiler")]
+    pub fn analyze(store: &Store, bytes: impl AsRef<[u8]>) -> Result<ModuleAnalysis, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let binary: &[u8] = bytes.as_ref();
+        Self::validate(store, binary)?;
+        Ok(analyze_module(binary)?)
+    }
+
     fn compile(store: &Store, binary: &[u8]) -> Result<Self, CompileError> {
         let artifact = store.engine().compile(binary, store.tunables())?;
-        Ok(Self::from_artifact(store, artifact))
+        #[allow(unused_mut)]
+        let mut module = Self::from_artifact(store, artifact);
+        // Keep the binary to print it.
+        #[cfg(feature = "wasmprinter")]
+        {
+            if store.keeps_module_binaries() {
+                module.binary = Some(binary.into());
+            }
+        }
+        Ok(module)
     }
 
     /// Serializes a module into a binary representation that the `Engine`
@@ -251,10 +362,23 @@ impl Module {
         Ok(Self::from_artifact(store, artifact))
     }
 
+    /// Creates a module of `store` running the code of `artifact`, shared
+    /// with the modules of other stores of the same engine.
+    pub(crate) fn from_shared_artifact(store: &Store, artifact: Arc<dyn Artifact>) -> Self {
+        Self::from_artifact(store, artifact)
+    }
+
     fn from_artifact(store: &Store, artifact: Arc<dyn Artifact>) -> Self {
+        let import_calls = if store.records_import_calls() {
+            Some(Arc::new(ImportCalls::new(artifact.module_ref())))
+        } else {
+            None
+        };
         Self {
             store: store.clone(),
             artifact,
+            import_calls,
+            binary: None,
         }
     }
 
@@ -262,10 +386,44 @@ impl Module {
         &self,
         resolver: &dyn Resolver,
     ) -> Result<InstanceHandle, InstantiationError> {
+        if let Some(policy) = self.store.policy() {
+            let violations = policy.check(self);
+            if policy.is_dry_run() {
+                policy.record(violations);
+            } else if !violations.is_empty() {
+                return Err(InstantiationError::Link(LinkError::from_imports(
+                    violations
+                        .into_iter()
+                        .map(|violation| {
+                            (
+                                violation.namespace,
+                                violation.name,
+                                ImportError::NotAllowed(violation.ty),
+                            )
+                        })
+                        .collect(),
+                )));
+            }
+        }
+
         unsafe {
+            let recording_resolver;
+            let resolver = match &self.import_calls {
+                Some(calls) => {
+                    recording_resolver = RecordingResolver {
+                        resolver,
+                        store: &self.store,
+                        artifact: self.artifact.as_ref(),
+                        calls,
+                    };
+                    &recording_resolver as &dyn Resolver
+                }
+                None => resolver,
+            };
             let instance_handle =
                 self.artifact
                     .instantiate(self.store.tunables(), resolver, Box::new(()))?;
+            instance_handle.set_epoch(self.store.epoch().clone());
 
             // After the instance handle is created, we need to initialize
             // the data, call the start function and so. However, if any
@@ -278,6 +436,41 @@ impl Module {
         }
     }
 
+    /// Returns the number of calls made to each function import of
+    /// the module by its instances, or `None` if the store the module
+    /// was compiled with doesn't record them.
+    ///
+    /// See [`Store::set_import_call_recording`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// store.set_import_call_recording(true);
+    /// let module = Module::new(&store, r#"(module
+    ///     (import "env" "used" (func $used))
+    ///     (import "env" "legacy" (func))
+    ///     (func (export "run") (call $used) (call $used)))"#)?;
+    /// let instance = Instance::new(&module, &imports! {
+    ///     "env" => {
+    ///         "used" => Function::new_native(&store, || {}),
+    ///         "legacy" => Function::new_native(&store, || {}),
+    ///     },
+    /// })?;
+    /// instance.exports.get_function("run")?.call(&[])?;
+    ///
+    /// let calls = module.import_calls().unwrap();
+    /// assert_eq!((calls[0].name.as_str(), calls[0].calls), ("used", 2));
+    /// assert_eq!((calls[1].name.as_str(), calls[1].calls), ("legacy", 0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_calls(&self) -> Option<Vec<ImportCallCount>> {
+        self.import_calls.as_ref().map(|calls| calls.counts())
+    }
+
     /// Returns the name of the current module.
     ///
     /// This name is normally set in the WebAssembly bytecode by some
@@ -299,6 +492,32 @@ impl Module {
         self.artifact.module_ref().name.as_deref()
     }
 
+    /// Returns the content hash of the module, the hash of the binary it
+    /// was compiled from.
+    ///
+    /// The hash is stable across processes and engines, and kept when the
+    /// module is serialized, so it can be used as a cache key and to
+    /// identify the module in logs and metrics. Modules compiled from the
+    /// same WebAssembly text have the same hash as the binary it
+    /// converts to.
+    ///
+    /// It's only `None` for modules of engines that don't compile them
+    /// from a binary.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = "(module)";
+    /// let module = Module::new(&store, wat)?;
+    /// assert_eq!(module.hash(), Some(ModuleHash::generate(&wat2wasm(wat.as_bytes())?)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hash(&self) -> Option<ModuleHash> {
+        self.artifact.hash()
+    }
+
     /// Sets the name of the current module.
     /// This is normally useful for stacktraces and debugging.
     ///
@@ -396,11 +615,257 @@ impl Module {
         self.artifact.module_ref().custom_sections(name)
     }
 
+    /// Get the name of a function from the name section, given its
+    /// index in the function index space (imported functions first).
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.artifact
+            .module_ref()
+            .function_names
+            .get(&FunctionIndex::from_u32(index))
+            .map(String::as_str)
+    }
+
+    /// Get the name of a local (or parameter) of a function from the
+    /// name section, given their indices.
+    pub fn local_name(&self, function_index: u32, local_index: u32) -> Option<&str> {
+        self.artifact
+            .module_ref()
+            .local_names
+            .get(&FunctionIndex::from_u32(function_index))?
+            .get(&local_index)
+            .map(String::as_str)
+    }
+
+    /// Get all the custom sections of the module, with their names.
+    pub fn all_custom_sections(&self) -> impl Iterator<Item = (&str, Arc<[u8]>)> + '_ {
+        self.artifact.module_ref().all_custom_sections()
+    }
+
+    /// Returns the given WebAssembly bytes with a custom section
+    /// `name` containing `data` appended.
+    ///
+    /// Custom sections don't change the semantics of a module, so this
+    /// can be used to attach metadata (like a license or a source map)
+    /// to a module before compiling or distributing it.
+    ///
+    /// If the bytes are not WebAssembly-like and the "wat" feature is
+    /// enabled for this crate, they are first converted from the
+    /// WebAssembly text format, like in [`Module::new`].
+    pub fn with_custom_section(
+        bytes: impl AsRef<[u8]>,
+        name: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let bytes: &[u8] = bytes.as_ref();
+        if !is_wasm(bytes) {
+            return Err(CompileError::Wasm(WasmError::Generic(
+                "The bytes are not a WebAssembly module".to_string(),
+            )));
+        }
+
+        let mut payload = vec![];
+        write_leb128_u32(&mut payload, name.len() as u32);
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(data);
+
+        let mut module = bytes.to_vec();
+        // The custom section id.
+        module.push(0);
+        write_leb128_u32(&mut module, payload.len() as u32);
+        module.extend(payload);
+        Ok(module)
+    }
+
+    /// Returns the given WebAssembly bytes without the code unreachable
+    /// from the exported functions `entries`, to shrink the compile
+    /// time and the artifact size of modules embedding large libraries.
+    ///
+    /// The exported functions other than the `entries` are removed from
+    /// the exports, and the bodies of the functions unreachable from
+    /// them are replaced by an `unreachable` instruction. The element
+    /// and data segments are removed when the reachable code doesn't
+    /// use the tables or the memories.
+    ///
+    /// If the bytes are not WebAssembly-like and the "wat" feature is
+    /// enabled for this crate, they are first converted from the
+    /// WebAssembly text format, like in [`Module::new`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module
+    ///     (func $helper (result i32) (i32.const 42))
+    ///     (func (export "run") (result i32) (call $helper))
+    ///     (func (export "unused") (result i32) (i32.const 0)))"#;
+    /// let wasm = Module::tree_shake(wat, &["run"])?;
+    /// let module = Module::new(&store, wasm)?;
+    /// assert_eq!(module.exports().count(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "compiler")]
+    pub fn tree_shake(bytes: impl AsRef<[u8]>, entries: &[&str]) -> Result<Vec<u8>, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let bytes: &[u8] = bytes.as_ref();
+        if !is_wasm(bytes) {
+            return Err(CompileError::Wasm(WasmError::Generic(
+                "The bytes are not a WebAssembly module".to_string(),
+            )));
+        }
+        Ok(tree_shake(bytes, entries)?)
+    }
+
+    /// Returns the module in the WebAssembly text format, with the
+    /// names of its name section.
+    ///
+    /// Only the modules compiled from WebAssembly in this process, with
+    /// a store keeping their binary, can be printed: see
+    /// [`Store::set_keep_module_binaries`]. The deserialized modules
+    /// don't keep their binary.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// store.set_keep_module_binaries(true);
+    /// let module = Module::new(&store, "(module (func $answer (result i32) (i32.const 42)))")?;
+    /// assert!(module.to_wat()?.contains("(func $answer"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "wasmprinter")]
+    pub fn to_wat(&self) -> Result<String, CompileError> {
+        let binary = self.binary.as_ref().ok_or_else(|| {
+            CompileError::Wasm(WasmError::Generic(
+                "The binary of a deserialized module can't be printed".to_string(),
+            ))
+        })?;
+        wasmprinter::print_bytes(binary)
+            .map_err(|e| CompileError::Wasm(WasmError::Generic(e.to_string())))
+    }
+
+    /// Returns the compilation statistics of the functions defined in
+    /// the module, with their index in the function index space.
+    ///
+    /// Only engines that keep them (e.g. the JIT engine) report them;
+    /// others return nothing.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module
+    ///     (func $add (param i32 i32) (result i32)
+    ///         local.get 0
+    ///         local.get 1
+    ///         i32.add))"#;
+    /// let module = Module::new(&store, wat)?;
+    /// for (index, stats) in module.compile_info() {
+    ///     println!(
+    ///         "{}: {} bytes in {:?}",
+    ///         module.function_name(index).unwrap_or("<unnamed>"),
+    ///         stats.code_size,
+    ///         stats.compile_time
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compile_info(&self) -> impl Iterator<Item = (u32, &CompiledFunctionStats)> + '_ {
+        let module = self.artifact.module_ref();
+        self.artifact
+            .function_stats()
+            .into_iter()
+            .flat_map(|stats| stats.iter())
+            .map(move |(local_index, stats)| (module.func_index(local_index).as_u32(), stats))
+    }
+
+    /// Returns the machine code generated for a function, given its index
+    /// in the function index space, with the WebAssembly instruction each
+    /// range of code was generated for.
+    ///
+    /// With the "capstone" feature, [`Disassembly::to_text`] disassembles
+    /// the code into text.
+    ///
+    /// Returns `None` for imported functions, or if the engine doesn't
+    /// keep the address maps of the functions (e.g. the native engine).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let module = Module::new(&store, r#"(module
+    ///     (func $add (param i32 i32) (result i32)
+    ///         local.get 0
+    ///         local.get 1
+    ///         i32.add))"#)?;
+    /// let disassembly = module.disassemble(0).unwrap();
+    /// for mapping in disassembly.mappings() {
+    ///     let code = &disassembly.code()[mapping.code_offset..][..mapping.code_len];
+    ///     println!("0x{:x}: {:02x?}", mapping.wasm_offset, code);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn disassemble(&self, function_index: u32) -> Option<Disassembly> {
+        let index = self
+            .artifact
+            .module_ref()
+            .local_func_index(FunctionIndex::from_u32(function_index))?;
+        let body = self.artifact.finished_functions().get(index)?;
+        let frame_info = self.artifact.function_frame_info(index)?;
+        // The body is kept alive by the artifact.
+        let code = unsafe {
+            let body = &***body;
+            std::slice::from_raw_parts(body.as_ptr() as *const u8, body.len())
+        };
+        Some(Disassembly::new(function_index, code, &frame_info))
+    }
+
     /// Returns the [`Store`] where the `Instance` belongs.
     pub fn store(&self) -> &Store {
         &self.store
     }
 
+    /// Returns the bytes of executable code memory allocated for this module.
+    ///
+    /// Only engines that manage code memory themselves (e.g. the JIT engine)
+    /// report it; others return 0.
+    pub fn code_memory_size(&self) -> usize {
+        self.artifact.code_memory_size()
+    }
+
+    /// Describes where the memories, tables and globals of the
+    /// instances of this module are in their `VMContext`, pointed to by
+    /// [`Instance::vmctx_ptr`].
+    ///
+    /// [`Instance::vmctx_ptr`]: crate::Instance::vmctx_ptr
+    pub fn vmctx_layout(&self) -> VMContextLayout {
+        VMContextLayout::for_module(self.info())
+    }
+
     /// The ABI of the ModuleInfo is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
@@ -426,6 +891,20 @@ impl fmt::Debug for Module {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Module")
             .field("name", &self.name())
+            .field("hash", &self.hash().map(|hash| hash.to_string()))
             .finish()
     }
 }
+
+/// Write `value` to `out` as an unsigned LEB128 number.
+fn write_leb128_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -27,6 +27,7 @@ pub struct NativeFunc<'a, Args = (), Rets = ()> {
     store: Store,
     address: *const VMFunctionBody,
     vmctx: *mut VMContext,
+    instance_vmctx: Option<*mut VMContext>,
     arg_kind: VMFunctionKind,
     // exported: ExportFunction,
     _phantom: PhantomData<(&'a (), Args, Rets)>,
@@ -43,6 +44,7 @@ where
         store: Store,
         address: *const VMFunctionBody,
         vmctx: *mut VMContext,
+        instance_vmctx: Option<*mut VMContext>,
         arg_kind: VMFunctionKind,
         definition: FunctionDefinition,
     ) -> Self {
@@ -51,6 +53,7 @@ where
             store,
             address,
             vmctx,
+            instance_vmctx,
             arg_kind,
             _phantom: PhantomData,
         }
@@ -70,6 +73,7 @@ where
             signature,
             kind: other.arg_kind,
             call_trampoline: None,
+            instance_vmctx: other.instance_vmctx,
         }
     }
 }
@@ -90,6 +94,7 @@ where
                 signature,
                 kind: other.arg_kind,
                 call_trampoline: None,
+                instance_vmctx: other.instance_vmctx,
             },
         }
     }
@@ -126,8 +131,12 @@ macro_rules! impl_native_traits {
                             rets_list.as_mut()
                         };
                         unsafe {
+                            let instance = self
+                                .instance_vmctx
+                                .map(|vmctx| wasmer_vm::InstanceHandle::from_vmctx(vmctx));
                             wasmer_vm::wasmer_call_trampoline(
                                 self.vmctx,
+                                instance.as_ref(),
                                 trampoline,
                                 self.address,
                                 args_rets.as_mut_ptr() as *mut u8,
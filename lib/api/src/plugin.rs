@@ -0,0 +1,203 @@
+//! Negotiation of the versioned interfaces a host provides to plugins.
+
+use crate::exports::Exports;
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::Store;
+use crate::InstantiationError;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The custom section where modules list the interfaces they require.
+///
+/// Its content is UTF-8 text, with one `<name> <version>` requirement
+/// per line, e.g. `log 2`. A module can have several such sections.
+pub const INTERFACES_SECTION: &str = "wasmer_interfaces";
+
+/// A versioned interface provided by a host to plugins, as the
+/// functions (and other externs) of the import namespace named after
+/// the interface.
+///
+/// New versions of an interface must be backwards compatible: a host
+/// providing version `n` can run the plugins requiring versions `1` to
+/// `n`. Breaking changes require a new interface name.
+pub trait HostInterface {
+    /// The name of the interface, which is also the name of its import
+    /// namespace.
+    const NAME: &'static str;
+
+    /// The version of the interface, starting at 1.
+    const VERSION: u32;
+
+    /// Returns the externs of the interface.
+    fn exports(&self, store: &Store) -> Exports;
+}
+
+/// An interface required by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceRequirement {
+    /// The name of the interface.
+    pub name: String,
+    /// The minimum version of the interface.
+    pub version: u32,
+}
+
+/// An error while instantiating a plugin.
+#[derive(Error, Debug)]
+pub enum PluginError {
+    /// The plugin requires an interface the host doesn't provide.
+    #[error("plugin requires {name} v{required}, host doesn't provide {name}")]
+    MissingInterface {
+        /// The name of the interface.
+        name: String,
+        /// The version required by the plugin.
+        required: u32,
+    },
+    /// The plugin requires a newer version of an interface than the
+    /// one the host provides.
+    #[error("plugin requires {name} v{required}, host provides v{provided}")]
+    IncompatibleVersion {
+        /// The name of the interface.
+        name: String,
+        /// The version required by the plugin.
+        required: u32,
+        /// The version provided by the host.
+        provided: u32,
+    },
+    /// The `wasmer_interfaces` section of the plugin is malformed.
+    #[error("malformed `wasmer_interfaces` section: {0}")]
+    MalformedRequirements(String),
+    /// The instantiation of the plugin failed.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+}
+
+/// Returns the interfaces required by `module`, listed in its
+/// [`INTERFACES_SECTION`] custom sections.
+pub fn required_interfaces(module: &Module) -> Result<Vec<InterfaceRequirement>, PluginError> {
+    let mut requirements = vec![];
+    for section in module.custom_sections(INTERFACES_SECTION) {
+        let text = std::str::from_utf8(&section)
+            .map_err(|_| PluginError::MalformedRequirements("invalid UTF-8".to_string()))?;
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let mut words = line.split_whitespace();
+            let requirement = match (words.next(), words.next(), words.next()) {
+                (Some(name), Some(version), None) => version
+                    .trim_start_matches('v')
+                    .parse()
+                    .ok()
+                    .filter(|version| *version > 0)
+                    .map(|version| InterfaceRequirement {
+                        name: name.to_string(),
+                        version,
+                    }),
+                _ => None,
+            };
+            requirements.push(requirement.ok_or_else(|| {
+                PluginError::MalformedRequirements(format!("invalid requirement `{}`", line))
+            })?);
+        }
+    }
+    Ok(requirements)
+}
+
+/// The interfaces a host provides to plugins, checked against the
+/// requirements of the plugins before instantiating them.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// struct Log;
+///
+/// impl HostInterface for Log {
+///     const NAME: &'static str = "log";
+///     const VERSION: u32 = 1;
+///
+///     fn exports(&self, store: &Store) -> Exports {
+///         let mut exports = Exports::new();
+///         exports.insert("info", Function::new_native(store, |_: i32, _: i32| {}));
+///         exports
+///     }
+/// }
+///
+/// let store = Store::default();
+/// let mut host = PluginHost::new(&store);
+/// host.provide(&Log);
+///
+/// let wasm = Module::with_custom_section("(module)", INTERFACES_SECTION, b"log 2")?;
+/// let plugin = Module::new(&store, wasm)?;
+/// let error = host.instantiate(&plugin).unwrap_err();
+/// assert_eq!(error.to_string(), "plugin requires log v2, host provides v1");
+/// # Ok(())
+/// # }
+/// ```
+pub struct PluginHost {
+    store: Store,
+    versions: HashMap<String, u32>,
+    imports: ImportObject,
+}
+
+impl PluginHost {
+    /// Creates a host providing no interface.
+    pub fn new(store: &Store) -> Self {
+        Self {
+            store: store.clone(),
+            versions: HashMap::new(),
+            imports: ImportObject::new(),
+        }
+    }
+
+    /// Provides `interface` to the plugins, replacing any interface
+    /// with the same name.
+    pub fn provide<I: HostInterface>(&mut self, interface: &I) -> &mut Self {
+        self.versions.insert(I::NAME.to_string(), I::VERSION);
+        self.imports
+            .register(I::NAME, interface.exports(&self.store));
+        self
+    }
+
+    /// Returns the version of the interface `name` provided by the
+    /// host, if any.
+    pub fn version(&self, name: &str) -> Option<u32> {
+        self.versions.get(name).copied()
+    }
+
+    /// Checks the host provides the interfaces required by `module`.
+    pub fn check(&self, module: &Module) -> Result<(), PluginError> {
+        for requirement in required_interfaces(module)? {
+            match self.version(&requirement.name) {
+                None => {
+                    return Err(PluginError::MissingInterface {
+                        name: requirement.name,
+                        required: requirement.version,
+                    })
+                }
+                Some(provided) if provided < requirement.version => {
+                    return Err(PluginError::IncompatibleVersion {
+                        name: requirement.name,
+                        required: requirement.version,
+                        provided,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the host provides the interfaces required by `module`,
+    /// and instantiates it with them.
+    pub fn instantiate(&self, module: &Module) -> Result<Instance, PluginError> {
+        self.check(module)?;
+        Ok(Instance::new(module, &self.imports)?)
+    }
+
+    /// Returns the imports of the interfaces provided by the host, e.g.
+    /// to chain them with other imports.
+    pub fn imports(&self) -> &ImportObject {
+        &self.imports
+    }
+}
@@ -0,0 +1,125 @@
+//! Restriction of the imports of the modules instantiated in a store.
+
+use crate::module::Module;
+use crate::types::ExternType;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// An import not allowed by a [`StorePolicy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyViolation {
+    /// The name of the importing module, if any.
+    pub module_name: Option<String>,
+    /// The module name of the import.
+    pub namespace: String,
+    /// The field name of the import.
+    pub name: String,
+    /// The type of the import.
+    pub ty: ExternType,
+}
+
+/// A deny-by-default policy on the imports of the modules instantiated
+/// in a store, see [`Store::set_policy`].
+///
+/// Instantiating a module importing anything not explicitly allowed
+/// fails with an [`ImportError::NotAllowed`] link error. In dry-run
+/// mode, instantiation succeeds and the violations are recorded
+/// instead, to be read with [`StorePolicy::violations`], e.g. to
+/// evaluate a policy before enforcing it.
+///
+/// The clones of a policy share the recorded violations.
+///
+/// [`Store::set_policy`]: crate::Store::set_policy
+/// [`ImportError::NotAllowed`]: crate::ImportError::NotAllowed
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let mut policy = StorePolicy::new();
+/// policy.allow("env", "log").allow_namespace("wasi_snapshot_preview1");
+/// store.set_policy(policy);
+///
+/// let module = Module::new(&store, r#"(module (import "env" "exec" (func)))"#)?;
+/// let exec = Function::new_native(&store, || {});
+/// let result = Instance::new(&module, &imports! { "env" => { "exec" => exec } });
+/// assert!(matches!(
+///     result,
+///     Err(InstantiationError::Link(LinkError::Import(_, _, ImportError::NotAllowed(_))))
+/// ));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StorePolicy {
+    allowed: HashSet<(String, String)>,
+    allowed_namespaces: HashSet<String>,
+    dry_run: bool,
+    violations: Arc<Mutex<Vec<PolicyViolation>>>,
+}
+
+impl StorePolicy {
+    /// Creates a policy allowing no import.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the import `name` of the module `namespace`.
+    pub fn allow(&mut self, namespace: &str, name: &str) -> &mut Self {
+        self.allowed
+            .insert((namespace.to_string(), name.to_string()));
+        self
+    }
+
+    /// Allows all the imports of the module `namespace`.
+    pub fn allow_namespace(&mut self, namespace: &str) -> &mut Self {
+        self.allowed_namespaces.insert(namespace.to_string());
+        self
+    }
+
+    /// Sets whether the policy only records the violations, instead of
+    /// failing the instantiation.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Returns whether the policy only records the violations.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns whether the import `name` of the module `namespace` is
+    /// allowed.
+    pub fn is_allowed(&self, namespace: &str, name: &str) -> bool {
+        self.allowed_namespaces.contains(namespace)
+            || self
+                .allowed
+                .contains(&(namespace.to_string(), name.to_string()))
+    }
+
+    /// Returns the imports of `module` not allowed by the policy.
+    pub fn check(&self, module: &Module) -> Vec<PolicyViolation> {
+        module
+            .imports()
+            .filter(|import| !self.is_allowed(import.module(), import.name()))
+            .map(|import| PolicyViolation {
+                module_name: module.name().map(str::to_string),
+                namespace: import.module().to_string(),
+                name: import.name().to_string(),
+                ty: import.ty().clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the violations recorded in dry-run mode.
+    pub fn violations(&self) -> Vec<PolicyViolation> {
+        self.violations.lock().unwrap().clone()
+    }
+
+    pub(crate) fn record(&self, violations: Vec<PolicyViolation>) {
+        self.violations.lock().unwrap().extend(violations);
+    }
+}
@@ -5,10 +5,55 @@
 //!
 //! Therefore, you should use this abstraction whenever possible to avoid memory
 //! related bugs when implementing an ABI.
+//!
+//! Out-of-bounds accesses return `None`, or trap the guest if the store of the
+//! memory has the [`OutOfBoundsPolicy::Trap`] policy.
+
+use crate::{externals::Memory, FromToNativeWasmType, RuntimeError};
+use std::{cell::Cell, fmt, marker::PhantomData, mem, ops::Range};
+use thiserror::Error;
+use wasmer_types::{LittleEndian, ValueType};
 
-use crate::{externals::Memory, FromToNativeWasmType};
-use std::{cell::Cell, fmt, marker::PhantomData, mem};
-use wasmer_types::ValueType;
+/// What the safe memory accessors do on an out-of-bounds access, see
+/// [`Store::set_out_of_bounds_policy`].
+///
+/// [`Store::set_out_of_bounds_policy`]: crate::Store::set_out_of_bounds_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBoundsPolicy {
+    /// Return `None` to the host function, e.g. for a WASI syscall to
+    /// return `EFAULT` like POSIX does.
+    Error,
+    /// Trap the guest with a [`MemoryAccessError`].
+    ///
+    /// It must only be used when the memory is accessed by host
+    /// functions called by the guest, as there's nothing to trap
+    /// otherwise.
+    Trap,
+}
+
+impl Default for OutOfBoundsPolicy {
+    fn default() -> Self {
+        Self::Error
+    }
+}
+
+/// The error of the trap raised by an out-of-bounds access with the
+/// [`OutOfBoundsPolicy::Trap`] policy.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("out of bounds memory access at offset {offset}")]
+pub struct MemoryAccessError {
+    /// The offset of the accessed pointer.
+    pub offset: u32,
+}
+
+/// Handles an out-of-bounds access of `memory` at `offset`, according to
+/// the policy of its store.
+fn out_of_bounds<T>(memory: &Memory, offset: u32) -> Option<T> {
+    match memory.store().out_of_bounds_policy() {
+        OutOfBoundsPolicy::Error => None,
+        OutOfBoundsPolicy::Trap => RuntimeError::raise(Box::new(MemoryAccessError { offset })),
+    }
+}
 
 /// The `Array` marker type. This type can be used like `WasmPtr<T, Array>`
 /// to get access to methods
@@ -75,11 +120,12 @@ impl<T: Copy + ValueType> WasmPtr<T, Item> {
     /// This invariant will be enforced in the future.
     #[inline]
     pub fn deref<'a>(self, memory: &'a Memory) -> Option<&'a Cell<T>> {
-        if (self.offset as usize) + mem::size_of::<T>() > memory.size().bytes().0
-            || mem::size_of::<T>() == 0
-        {
+        if mem::size_of::<T>() == 0 {
             return None;
         }
+        if (self.offset as usize) + mem::size_of::<T>() > memory.size().bytes().0 {
+            return out_of_bounds(memory, self.offset);
+        }
         unsafe {
             let cell_ptr = align_pointer(
                 memory.view::<u8>().as_ptr().add(self.offset as usize) as usize,
@@ -98,11 +144,12 @@ impl<T: Copy + ValueType> WasmPtr<T, Item> {
     ///   exclusive access to Wasm linear memory before calling this method.
     #[inline]
     pub unsafe fn deref_mut<'a>(self, memory: &'a Memory) -> Option<&'a mut Cell<T>> {
-        if (self.offset as usize) + mem::size_of::<T>() > memory.size().bytes().0
-            || mem::size_of::<T>() == 0
-        {
+        if mem::size_of::<T>() == 0 {
             return None;
         }
+        if (self.offset as usize) + mem::size_of::<T>() > memory.size().bytes().0 {
+            return out_of_bounds(memory, self.offset);
+        }
         let cell_ptr = align_pointer(
             memory.view::<u8>().as_ptr().add(self.offset as usize) as usize,
             mem::align_of::<T>(),
@@ -111,6 +158,45 @@ impl<T: Copy + ValueType> WasmPtr<T, Item> {
     }
 }
 
+/// Methods for `WasmPtr`s to data with a [`LittleEndian`] layout.
+///
+/// They copy the value from and to memory byte by byte, so they work
+/// whatever the alignment of the pointer and the byte order of the host.
+impl<T: Copy + LittleEndian> WasmPtr<T, Item> {
+    /// Reads the value pointed to, returning `None` if it's out of
+    /// bounds.
+    pub fn read(self, memory: &Memory) -> Option<T> {
+        let view = memory.view::<u8>();
+        let cells = match self.byte_range().and_then(|range| view.get(range)) {
+            Some(cells) => cells,
+            None => return out_of_bounds(memory, self.offset),
+        };
+        let bytes = cells.iter().map(Cell::get).collect::<Vec<u8>>();
+        Some(T::read_le(&bytes))
+    }
+
+    /// Writes `value` where the pointer points, returning `None` if it's
+    /// out of bounds.
+    pub fn write(self, memory: &Memory, value: &T) -> Option<()> {
+        let view = memory.view::<u8>();
+        let cells = match self.byte_range().and_then(|range| view.get(range)) {
+            Some(cells) => cells,
+            None => return out_of_bounds(memory, self.offset),
+        };
+        let mut bytes = vec![0; T::SIZE];
+        value.write_le(&mut bytes);
+        for (cell, byte) in cells.iter().zip(bytes) {
+            cell.set(byte);
+        }
+        Some(())
+    }
+
+    fn byte_range(self) -> Option<Range<usize>> {
+        let start = self.offset as usize;
+        Some(start..start.checked_add(T::SIZE)?)
+    }
+}
+
 /// Methods for `WasmPtr`s to arrays of data that can be dereferenced, namely to
 /// types that implement [`ValueType`], meaning that they're valid for all
 /// possible bit patterns.
@@ -129,11 +215,13 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
         let slice_full_len = index as usize + length as usize;
         let memory_size = memory.size().bytes().0;
 
+        if mem::size_of::<T>() == 0 {
+            return None;
+        }
         if (self.offset as usize) + (item_size * slice_full_len) > memory_size
             || self.offset as usize >= memory_size
-            || mem::size_of::<T>() == 0
         {
-            return None;
+            return out_of_bounds(memory, self.offset);
         }
 
         unsafe {
@@ -167,11 +255,13 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
         let slice_full_len = index as usize + length as usize;
         let memory_size = memory.size().bytes().0;
 
+        if mem::size_of::<T>() == 0 {
+            return None;
+        }
         if (self.offset as usize) + (item_size * slice_full_len) > memory.size().bytes().0
             || self.offset as usize >= memory_size
-            || mem::size_of::<T>() == 0
         {
-            return None;
+            return out_of_bounds(memory, self.offset);
         }
 
         let cell_ptr = align_pointer(
@@ -194,7 +284,7 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
         if self.offset as usize + str_len as usize > memory.size().bytes().0
             || self.offset as usize >= memory_size
         {
-            return None;
+            return out_of_bounds(memory, self.offset);
         }
         let ptr = unsafe { memory.view::<u8>().as_ptr().add(self.offset as usize) as *const u8 };
         let slice: &[u8] = unsafe { std::slice::from_raw_parts(ptr, str_len as usize) };
@@ -210,7 +300,12 @@ impl<T: Copy + ValueType> WasmPtr<T, Array> {
     /// underlying data can be mutated if the Wasm is allowed to execute or
     /// an aliasing `WasmPtr` is used to mutate memory.
     pub fn get_utf8_string_with_nul(self, memory: &Memory) -> Option<&str> {
-        memory.view::<u8>()[(self.offset as usize)..]
+        let view = memory.view::<u8>();
+        let cells = match view.get((self.offset as usize)..) {
+            Some(cells) => cells,
+            None => return out_of_bounds(memory, self.offset),
+        };
+        cells
             .iter()
             .map(|cell| cell.get())
             .position(|byte| byte == 0)
@@ -0,0 +1,378 @@
+//! A scheduler running many instances on a few threads.
+
+use crate::exports::ExportError;
+use crate::instance::Instance;
+use crate::RuntimeError;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use wasmer_vm::{TrapCode, VMEpoch};
+
+/// The export called by the [`Scheduler`] to run a step of a task, with
+/// the `() -> i32` signature. It returns 0 once the task is complete.
+pub const STEP_EXPORT: &str = "_step";
+
+/// The time slice of the tasks spawned without one.
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// An error ending a task of a [`Scheduler`].
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TaskError {
+    /// The instance doesn't export the step function.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// A step trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    /// A step ran longer than the time slice of the task, and was
+    /// interrupted.
+    #[error("a step ran longer than the time slice of the task")]
+    SliceExceeded,
+    /// The task wasn't complete at its deadline.
+    #[error("the task wasn't complete at its deadline")]
+    DeadlineMissed,
+    /// The scheduler was dropped before the task was complete.
+    #[error("the scheduler was dropped before the task was complete")]
+    Cancelled,
+}
+
+/// The scheduling of a task, given to [`Scheduler::spawn`].
+#[derive(Debug, Clone, Copy)]
+pub struct TaskOptions {
+    slice: Duration,
+    deadline: Option<Instant>,
+}
+
+impl TaskOptions {
+    /// Creates options with the [`DEFAULT_TIME_SLICE`] and no deadline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the longest time a step of the task may run.
+    pub fn slice(mut self, slice: Duration) -> Self {
+        self.slice = slice;
+        self
+    }
+
+    /// Sets the time by which the task must be complete. Tasks with a
+    /// deadline run before the others, the earliest deadline first.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
+
+impl Default for TaskOptions {
+    fn default() -> Self {
+        Self {
+            slice: DEFAULT_TIME_SLICE,
+            deadline: None,
+        }
+    }
+}
+
+struct Task {
+    instance: Instance,
+    options: TaskOptions,
+    done: Sender<Result<Instance, TaskError>>,
+}
+
+/// A step being run, interrupted through the epoch of its store once
+/// `interrupt_at` is reached.
+struct Running {
+    epoch: Arc<VMEpoch>,
+    interrupt_at: Instant,
+    interrupted: bool,
+}
+
+/// The tasks waiting for their next step, by deadline (tasks without one
+/// last), then by order of arrival.
+type QueueKey = (bool, Option<Instant>, u64);
+
+#[derive(Default)]
+struct State {
+    queue: BTreeMap<QueueKey, Task>,
+    running: HashMap<u64, Running>,
+    next_id: u64,
+    closed: bool,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when a task is queued, or the scheduler is closed.
+    ready: Condvar,
+    /// Notified when a step starts, or the scheduler is stopped.
+    timer: Condvar,
+}
+
+/// A scheduler multiplexing many instances over a pool of worker
+/// threads, so a few threads serve many long-running guests.
+///
+/// A task is an instance exporting a [`STEP_EXPORT`] function that does
+/// a bounded amount of work and returns whether there's more to do. The
+/// task is suspended between steps, letting the workers run the steps
+/// of the other tasks. The tasks with a deadline run first, the earliest
+/// deadline first.
+///
+/// A step running longer than the time slice of its task, or past the
+/// deadline of the task, is interrupted through the epoch deadline of
+/// the store, which requires code compiled with
+/// `CompilerConfig::enable_epoch_interruption`. Since the deadline
+/// interrupts all the code of the store, each task should have its own
+/// store, e.g. created with [`Store::isolated`].
+///
+/// Dropping the scheduler waits for the running steps and cancels the
+/// other tasks.
+///
+/// [`Store::isolated`]: crate::Store::isolated
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let wat = r#"(module
+///     (global $left (export "left") (mut i32) (i32.const 3))
+///     (func (export "_step") (result i32)
+///         (global.set $left (i32.sub (global.get $left) (i32.const 1)))
+///         (global.get $left)))"#;
+///
+/// let scheduler = Scheduler::new(2);
+/// let tasks = (0..4)
+///     .map(|_| {
+///         let module = Module::new(&store.isolated(), wat)?;
+///         let instance = Instance::new(&module, &imports! {})?;
+///         Ok(scheduler.spawn(instance, TaskOptions::new()))
+///     })
+///     .collect::<anyhow::Result<Vec<_>>>()?;
+///
+/// for task in tasks {
+///     let instance = task.join()?;
+///     assert_eq!(instance.exports.get_global("left")?.get(), Value::I32(0));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler running the steps of its tasks on `workers`
+    /// threads.
+    pub fn new(workers: usize) -> Self {
+        let shared = Arc::new(Shared::default());
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+        let timer = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.watch())
+        };
+        Self {
+            shared,
+            workers,
+            timer: Some(timer),
+        }
+    }
+
+    /// Queues `instance` to be run step by step until its
+    /// [`STEP_EXPORT`] returns 0.
+    pub fn spawn(&self, instance: Instance, options: TaskOptions) -> TaskHandle {
+        let (done, result) = mpsc::channel();
+        self.shared.push(Task {
+            instance,
+            options,
+            done,
+        });
+        TaskHandle { result }
+    }
+
+    /// Returns the number of tasks waiting for their next step.
+    pub fn queued(&self) -> usize {
+        self.shared.state.lock().unwrap().queue.len()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.closed = true;
+            state.queue.clear();
+        }
+        self.shared.ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+
+        // the timer outlives the workers, to interrupt their last steps
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.timer.notify_all();
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("workers", &self.workers.len())
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+impl Shared {
+    /// Queues `task` for its next step, unless the scheduler is closed.
+    fn push(&self, task: Task) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        let deadline = task.options.deadline;
+        state.queue.insert((deadline.is_none(), deadline, id), task);
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    /// Runs the steps of the queued tasks until the scheduler is closed.
+    fn work(&self) {
+        loop {
+            let (id, task) = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if state.closed {
+                        return;
+                    }
+                    if let Some(key) = state.queue.keys().next().cloned() {
+                        let task = state.queue.remove(&key).unwrap();
+                        break (key.2, task);
+                    }
+                    state = self.ready.wait(state).unwrap();
+                }
+            };
+            if let Some(task) = self.step(id, task) {
+                self.push(task);
+            }
+        }
+    }
+
+    /// Runs a step of `task`, returning it if it isn't complete.
+    fn step(&self, id: u64, task: Task) -> Option<Task> {
+        if task.options.deadline_passed() {
+            let _ = task.done.send(Err(TaskError::DeadlineMissed));
+            return None;
+        }
+        let step = match task
+            .instance
+            .exports
+            .get_native_function::<(), i32>(STEP_EXPORT)
+        {
+            Ok(step) => step,
+            Err(error) => {
+                let _ = task.done.send(Err(error.into()));
+                return None;
+            }
+        };
+
+        let epoch = task.instance.store().epoch().clone();
+        epoch.set_deadline(u64::max_value());
+        let mut interrupt_at = Instant::now() + task.options.slice;
+        if let Some(deadline) = task.options.deadline {
+            interrupt_at = interrupt_at.min(deadline);
+        }
+        self.state.lock().unwrap().running.insert(
+            id,
+            Running {
+                epoch: epoch.clone(),
+                interrupt_at,
+                interrupted: false,
+            },
+        );
+        self.timer.notify_one();
+
+        let result = step.call();
+        self.state.lock().unwrap().running.remove(&id);
+        // the timer may have interrupted the store after the step returned
+        epoch.set_deadline(u64::max_value());
+
+        let error = match result {
+            Ok(0) => {
+                let _ = task.done.send(Ok(task.instance));
+                return None;
+            }
+            Ok(_) => return Some(task),
+            Err(error) if error.trap_code() == Some(TrapCode::Interrupt) => {
+                if task.options.deadline_passed() {
+                    TaskError::DeadlineMissed
+                } else {
+                    TaskError::SliceExceeded
+                }
+            }
+            Err(error) => error.into(),
+        };
+        let _ = task.done.send(Err(error));
+        None
+    }
+
+    /// Interrupts the steps running past their time, until the scheduler
+    /// is stopped.
+    fn watch(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.stopped {
+            let now = Instant::now();
+            for running in state.running.values_mut() {
+                if !running.interrupted && running.interrupt_at <= now {
+                    running.epoch.set_deadline(0);
+                    running.interrupted = true;
+                }
+            }
+            let next = state
+                .running
+                .values()
+                .filter(|running| !running.interrupted)
+                .map(|running| running.interrupt_at)
+                .min();
+            state = match next {
+                Some(next) => self.timer.wait_timeout(state, next - now).unwrap().0,
+                None => self.timer.wait(state).unwrap(),
+            };
+        }
+    }
+}
+
+/// A task spawned on a [`Scheduler`].
+#[derive(Debug)]
+pub struct TaskHandle {
+    result: Receiver<Result<Instance, TaskError>>,
+}
+
+impl TaskHandle {
+    /// Waits for the task to be complete, and returns its instance.
+    pub fn join(self) -> Result<Instance, TaskError> {
+        self.result.recv().unwrap_or(Err(TaskError::Cancelled))
+    }
+}
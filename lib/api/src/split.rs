@@ -0,0 +1,178 @@
+//! Lazy loading of the cold code of a module, split into a primary
+//! module and a secondary module.
+
+use crate::exports::{ExportError, Exports};
+use crate::externals::Function;
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::Store;
+use crate::types::Val;
+use crate::utils::is_wasm;
+use crate::{InstantiationError, RuntimeError};
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer_compiler::{
+    split_module, CompileError, WasmError, SPLIT_LOADED_EXPORT, SPLIT_NAMESPACE, SPLIT_TABLE_EXPORT,
+};
+use wasmer_engine::Resolver;
+
+/// An error while instantiating a [`SplitModule`].
+#[derive(Error, Debug)]
+pub enum SplitError {
+    /// The primary module couldn't be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    /// An export added by the split is missing, so the module wasn't
+    /// split by [`SplitModule::split`].
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The loader couldn't be installed.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// A module split into a primary module, compiled eagerly, and a
+/// secondary module holding its cold code, compiled and instantiated
+/// the first time a cold function is called.
+///
+/// This reduces the initial compile time and memory of massive modules
+/// where only a fraction of the code usually runs.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let wat = r#"(module
+///     (func (export "run") (param i32) (result i32)
+///         (if (result i32) (local.get 0)
+///             (then (call $recover))
+///             (else (i32.const 0))))
+///     (func $recover (result i32) (i32.const 42)))"#;
+/// let split = SplitModule::new(&store, wat, &["run"])?;
+/// let instance = split.instantiate(&imports! {})?;
+/// let run = instance.exports.get_native_function::<i32, i32>("run")?;
+/// assert_eq!(run.call(0)?, 0);
+/// // Loads the secondary module.
+/// assert_eq!(run.call(1)?, 42);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SplitModule {
+    primary: Module,
+    secondary: Arc<[u8]>,
+}
+
+impl SplitModule {
+    /// Splits the given WebAssembly bytes into a primary module and a
+    /// secondary module, returned in this order, e.g. to save them and
+    /// load them later with [`SplitModule::from_parts`].
+    ///
+    /// The functions reachable from the exported functions
+    /// `hot_exports` (and from the start function) stay in the primary
+    /// module, the other ones move to the secondary module.
+    ///
+    /// If the bytes are not WebAssembly-like and the "wat" feature is
+    /// enabled for this crate, they are first converted from the
+    /// WebAssembly text format, like in [`Module::new`].
+    pub fn split(
+        bytes: impl AsRef<[u8]>,
+        hot_exports: &[&str],
+    ) -> Result<(Vec<u8>, Vec<u8>), CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let bytes: &[u8] = bytes.as_ref();
+        if !is_wasm(bytes) {
+            return Err(CompileError::Wasm(WasmError::Generic(
+                "The bytes are not a WebAssembly module".to_string(),
+            )));
+        }
+        Ok(split_module(bytes, hot_exports)?)
+    }
+
+    /// Splits the given WebAssembly bytes, see [`SplitModule::split`],
+    /// and compiles the primary module.
+    pub fn new(
+        store: &Store,
+        bytes: impl AsRef<[u8]>,
+        hot_exports: &[&str],
+    ) -> Result<Self, CompileError> {
+        let (primary, secondary) = Self::split(bytes, hot_exports)?;
+        Self::from_parts(store, &primary, secondary)
+    }
+
+    /// Compiles the primary module of a split. The secondary module is
+    /// only compiled when it's loaded.
+    pub fn from_parts(
+        store: &Store,
+        primary: &[u8],
+        secondary: Vec<u8>,
+    ) -> Result<Self, CompileError> {
+        Ok(Self {
+            primary: Module::from_binary(store, primary)?,
+            secondary: secondary.into(),
+        })
+    }
+
+    /// Returns the primary module.
+    pub fn primary(&self) -> &Module {
+        &self.primary
+    }
+
+    /// Instantiates the primary module, and installs the loader of the
+    /// secondary module called by the stubs of the cold functions.
+    pub fn instantiate(&self, resolver: &dyn Resolver) -> Result<Instance, SplitError> {
+        let instance = Instance::new(&self.primary, resolver)?;
+        let table = instance.exports.get_table(SPLIT_TABLE_EXPORT)?;
+        let loader = Function::new_native_with_env(
+            self.primary.store(),
+            SplitLoader {
+                store: self.primary.store().clone(),
+                secondary: self.secondary.clone(),
+                exports: instance.exports.clone(),
+                instance: None,
+            },
+            |loader: &mut SplitLoader| loader.load(),
+        );
+        table.set(table.size() - 1, Val::FuncRef(loader))?;
+        Ok(instance)
+    }
+}
+
+/// The environment of the loader of a secondary module.
+struct SplitLoader {
+    store: Store,
+    secondary: Arc<[u8]>,
+    /// The exports of the primary instance.
+    exports: Exports,
+    /// The secondary instance, once loaded.
+    instance: Option<Instance>,
+}
+
+impl SplitLoader {
+    fn load(&mut self) -> Result<(), RuntimeError> {
+        if self.instance.is_some() {
+            return Ok(());
+        }
+        let module = Module::from_binary(&self.store, &self.secondary)
+            .map_err(|e| RuntimeError::new(format!("can't compile the secondary module: {}", e)))?;
+        let mut imports = ImportObject::new();
+        imports.register(SPLIT_NAMESPACE, self.exports.clone());
+        let instance = Instance::new(&module, &imports).map_err(|e| {
+            RuntimeError::new(format!("can't instantiate the secondary module: {}", e))
+        })?;
+        self.exports
+            .get_global(SPLIT_LOADED_EXPORT)
+            .map_err(|e| RuntimeError::new(e.to_string()))?
+            .set(Val::I32(1))?;
+        self.instance = Some(instance);
+        Ok(())
+    }
+}
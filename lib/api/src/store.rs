@@ -1,10 +1,15 @@
-use crate::tunables::Tunables;
+use crate::limits::StoreLimits;
+use crate::policy::StorePolicy;
+use crate::ptr::OutOfBoundsPolicy;
+use crate::tunables::{HookedTunables, Tunables};
+use crate::MemoryType;
 use std::fmt;
 use std::sync::Arc;
 #[cfg(all(feature = "compiler", feature = "engine"))]
 use wasmer_compiler::CompilerConfig;
 use wasmer_engine::Engine;
 use wasmer_engine::Tunables as BaseTunables;
+use wasmer_vm::{InstanceAllocator, MemoryStyle, VMEpoch};
 
 /// The store represents all global state that can be manipulated by
 /// WebAssembly programs. It consists of the runtime representation
@@ -20,6 +25,12 @@ use wasmer_engine::Tunables as BaseTunables;
 pub struct Store {
     engine: Arc<dyn Engine + Send + Sync>,
     tunables: Arc<dyn BaseTunables + Send + Sync>,
+    epoch: Arc<VMEpoch>,
+    record_import_calls: bool,
+    keep_binaries: bool,
+    policy: Option<StorePolicy>,
+    limits: Option<StoreLimits>,
+    out_of_bounds: OutOfBoundsPolicy,
 }
 
 impl Store {
@@ -31,6 +42,12 @@ impl Store {
         Self {
             engine: engine.cloned(),
             tunables: Arc::new(Tunables::for_target(engine.target())),
+            epoch: Arc::new(VMEpoch::default()),
+            record_import_calls: false,
+            keep_binaries: false,
+            policy: None,
+            limits: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
         }
     }
 
@@ -45,6 +62,34 @@ impl Store {
         Self {
             engine: engine.cloned(),
             tunables: Arc::new(tunables),
+            epoch: Arc::new(VMEpoch::default()),
+            record_import_calls: false,
+            keep_binaries: false,
+            policy: None,
+            limits: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
+        }
+    }
+
+    /// Creates a store sharing the engine and the tunables of this one,
+    /// but none of its other settings: it has no policy, no limits, and
+    /// its own epoch.
+    ///
+    /// The modules compiled in any of the stores of an engine can be
+    /// shared with the others through a [`CodeCache`], while the
+    /// instances of each store stay separate.
+    ///
+    /// [`CodeCache`]: crate::CodeCache
+    pub fn isolated(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            tunables: self.tunables.clone(),
+            epoch: Arc::new(VMEpoch::default()),
+            record_import_calls: false,
+            keep_binaries: false,
+            policy: None,
+            limits: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
         }
     }
 
@@ -53,11 +98,193 @@ impl Store {
         self.tunables.as_ref()
     }
 
+    /// Sets a hook deciding the [`MemoryStyle`] of the memories of the
+    /// modules compiled with this store from now on.
+    ///
+    /// The hook gets the name of the module (from its name section, if
+    /// any), the memory type and the style the tunables picked, and
+    /// returns the style to use, e.g. to force dynamic memories for a
+    /// plugin that grows a lot. Hooks set on the same store are applied
+    /// in order.
+    ///
+    /// Only this store and the stores cloned from it afterwards are
+    /// affected, so a clone can be used to compile a single module with
+    /// other styles.
+    pub fn set_memory_style_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(Option<&str>, &MemoryType, MemoryStyle) -> MemoryStyle + Send + Sync + 'static,
+    {
+        self.tunables = Arc::new(HookedTunables {
+            inner: self.tunables.clone(),
+            hook: Some(Box::new(hook)),
+            allocator: None,
+            poison: None,
+            limits: None,
+        });
+    }
+
+    /// Sets the [`InstanceAllocator`] allocating the `VMContext` of the
+    /// instances created with this store from now on, which holds their
+    /// imports and the definitions of their tables, memories and globals.
+    ///
+    /// Embedders instantiating frequently can use it to move these
+    /// allocations onto an arena, or to measure them. Only this store and
+    /// the stores cloned from it afterwards are affected.
+    pub fn set_instance_allocator(&mut self, allocator: impl InstanceAllocator + 'static) {
+        self.tunables = Arc::new(HookedTunables {
+            inner: self.tunables.clone(),
+            hook: None,
+            allocator: Some(Arc::new(allocator)),
+            poison: None,
+            limits: None,
+        });
+    }
+
+    /// Sets the byte filling the pages the memories created with this
+    /// store from now on grow by, instead of zeros.
+    ///
+    /// This is a debugging aid for guests reading memory they didn't
+    /// write, e.g. uninitialized heap allocations in ported C code, which
+    /// then read the poison rather than zeros. It breaks the WebAssembly
+    /// semantics: guests relying on grown memory being zeroed, such as
+    /// `calloc` implementations skipping fresh memory, misbehave. Only
+    /// this store and the stores cloned from it afterwards are affected.
+    pub fn set_memory_poison(&mut self, byte: u8) {
+        self.tunables = Arc::new(HookedTunables {
+            inner: self.tunables.clone(),
+            hook: None,
+            allocator: None,
+            poison: Some(byte),
+            limits: None,
+        });
+    }
+
     /// Returns the [`Engine`].
     pub fn engine(&self) -> &Arc<dyn Engine + Send + Sync> {
         &self.engine
     }
 
+    /// Sets whether the modules compiled with this store from now on
+    /// record the calls made to their function imports, to be read with
+    /// [`Module::import_calls`].
+    ///
+    /// It tells which host APIs are actually used, e.g. before
+    /// deprecating some. The function imports are called through an
+    /// additional host function, so calling them is slower.
+    ///
+    /// Like [`Store::set_memory_style_hook`], only this store and the
+    /// stores cloned from it afterwards are affected.
+    ///
+    /// [`Module::import_calls`]: crate::Module::import_calls
+    pub fn set_import_call_recording(&mut self, enabled: bool) {
+        self.record_import_calls = enabled;
+    }
+
+    /// Returns whether the modules compiled with this store record the
+    /// calls to their function imports.
+    pub(crate) fn records_import_calls(&self) -> bool {
+        self.record_import_calls
+    }
+
+    /// Sets whether the modules compiled with this store from now on
+    /// keep their binary, to be printed with [`Module::to_wat`].
+    ///
+    /// The binaries aren't kept by default, as they can be as large as
+    /// the compiled code.
+    ///
+    /// Like [`Store::set_memory_style_hook`], only this store and the
+    /// stores cloned from it afterwards are affected.
+    ///
+    /// [`Module::to_wat`]: crate::Module::to_wat
+    pub fn set_keep_module_binaries(&mut self, enabled: bool) {
+        self.keep_binaries = enabled;
+    }
+
+    /// Returns whether the modules compiled with this store keep their
+    /// binary.
+    pub(crate) fn keeps_module_binaries(&self) -> bool {
+        self.keep_binaries
+    }
+
+    /// Sets the policy restricting the imports of the modules
+    /// instantiated with this store from now on.
+    ///
+    /// Like [`Store::set_memory_style_hook`], only this store and the
+    /// stores cloned from it afterwards are affected.
+    pub fn set_policy(&mut self, policy: StorePolicy) {
+        self.policy = Some(policy);
+    }
+
+    /// Returns the import policy of the store, if any.
+    pub fn policy(&self) -> Option<&StorePolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Sets the caps on the instances created with this store from now
+    /// on, and the resources they define.
+    ///
+    /// Like [`Store::set_memory_style_hook`], only this store and the
+    /// stores cloned from it afterwards are affected; they share the
+    /// usage of the limits.
+    pub fn set_limits(&mut self, limits: StoreLimits) {
+        // the growth of the memories is limited by their tunables
+        self.tunables = Arc::new(HookedTunables {
+            inner: self.tunables.clone(),
+            hook: None,
+            allocator: None,
+            poison: None,
+            limits: Some(limits.clone()),
+        });
+        self.limits = Some(limits);
+    }
+
+    /// Returns the limits of the store, if any.
+    pub fn limits(&self) -> Option<&StoreLimits> {
+        self.limits.as_ref()
+    }
+
+    /// Sets what the safe memory accessors, like [`WasmPtr::deref`], do
+    /// when a host function accesses the memories of this store out of
+    /// bounds: return an error to the host function (the default), or
+    /// trap the guest.
+    ///
+    /// Only the memories created or exported after the policy is set
+    /// are affected, since they keep a clone of their store.
+    ///
+    /// [`WasmPtr::deref`]: crate::WasmPtr::deref
+    pub fn set_out_of_bounds_policy(&mut self, policy: OutOfBoundsPolicy) {
+        self.out_of_bounds = policy;
+    }
+
+    /// Returns what the safe memory accessors do on an out-of-bounds
+    /// access.
+    pub fn out_of_bounds_policy(&self) -> OutOfBoundsPolicy {
+        self.out_of_bounds
+    }
+
+    /// Interrupts running WebAssembly code once `ticks` more epochs have
+    /// elapsed, counting from the current epoch.
+    ///
+    /// The interrupted call fails with a trap of code
+    /// `TrapCode::Interrupt`. Only code compiled with
+    /// `CompilerConfig::enable_epoch_interruption` checks the deadline.
+    pub fn set_epoch_deadline(&self, ticks: u64) {
+        self.epoch.set_deadline(ticks);
+    }
+
+    /// Advances the epoch of this store by one.
+    ///
+    /// This is cheap and can be called from any thread, e.g. a timer thread
+    /// driving preemption, without relying on signals.
+    pub fn increment_epoch(&self) {
+        self.epoch.increment();
+    }
+
+    /// Returns the epoch shared with the instances of this store.
+    pub(crate) fn epoch(&self) -> &Arc<VMEpoch> {
+        &self.epoch
+    }
+
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
@@ -115,6 +342,12 @@ impl Default for Store {
         Store {
             engine: Arc::new(engine),
             tunables: Arc::new(tunables),
+            epoch: Arc::new(VMEpoch::default()),
+            record_import_calls: false,
+            keep_binaries: false,
+            policy: None,
+            limits: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
         }
     }
 }
@@ -0,0 +1,148 @@
+//! Instances of a module running on host threads, sharing one shared
+//! memory, as the building block of the threads of the guests.
+
+use crate::exports::ExportError;
+use crate::externals::Memory;
+use crate::import_object::ImportObject;
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::{InstantiationError, RuntimeError};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use thiserror::Error;
+
+/// The exported function called by [`WasmThreads::spawn`] on the new
+/// thread, with the `(tid: i32, start_arg: i32)` signature of the
+/// wasi-threads proposal.
+pub const THREAD_START_EXPORT: &str = "wasi_thread_start";
+
+/// An error while running a module on several threads.
+#[derive(Error, Debug)]
+pub enum ThreadError {
+    /// The memory given to [`WasmThreads::new`] isn't shared.
+    #[error("the memory of the threads must be shared")]
+    NotShared,
+    /// The module couldn't be instantiated on a thread.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    /// The module doesn't export the thread start function.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The thread trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    /// The host thread panicked.
+    #[error("the thread {0} panicked")]
+    Panicked(u32),
+}
+
+type ImportsFactory = dyn Fn(&Memory) -> ImportObject + Send + Sync;
+
+/// A module instantiated once per host thread, every instance importing
+/// the same shared memory.
+///
+/// The imports of each instance are created on its thread by a factory
+/// given the shared memory, since host functions can't be shared
+/// between threads. The memory should have a maximum size, so that
+/// growing it never moves it while other threads access it.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::*;
+/// # fn run(store: &Store) -> anyhow::Result<()> {
+/// // The engine of `store` has the threads feature enabled.
+/// let module = Module::new(store, r#"(module
+///     (import "env" "memory" (memory 1 1 shared))
+///     (func (export "wasi_thread_start") (param $tid i32) (param $arg i32)
+///         (i32.store (i32.mul (local.get $tid) (i32.const 4)) (local.get $arg))))"#)?;
+/// let memory = Memory::new(store, MemoryType::new(1, Some(1), true))?;
+/// let threads = WasmThreads::new(&module, memory, |memory| {
+///     imports! { "env" => { "memory" => memory.clone() } }
+/// })?;
+/// let thread = threads.spawn(42);
+/// let tid = thread.id();
+/// thread.join()?;
+/// assert_eq!(threads.memory().view::<u32>()[tid as usize].get(), 42);
+/// # Ok(())
+/// # }
+/// ```
+pub struct WasmThreads {
+    module: Module,
+    memory: Memory,
+    imports: Arc<ImportsFactory>,
+    next_id: AtomicU32,
+}
+
+impl WasmThreads {
+    /// Creates the threads of `module`, sharing `memory`, with the
+    /// imports returned by `imports`.
+    pub fn new<F>(module: &Module, memory: Memory, imports: F) -> Result<Self, ThreadError>
+    where
+        F: Fn(&Memory) -> ImportObject + Send + Sync + 'static,
+    {
+        if !memory.ty().shared {
+            return Err(ThreadError::NotShared);
+        }
+        Ok(Self {
+            module: module.clone(),
+            memory,
+            imports: Arc::new(imports),
+            next_id: AtomicU32::new(1),
+        })
+    }
+
+    /// Returns the shared memory.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Instantiates the module on the current thread, e.g. for the main
+    /// thread of the guest.
+    pub fn instantiate(&self) -> Result<Instance, ThreadError> {
+        Ok(Instance::new(&self.module, &(self.imports)(&self.memory))?)
+    }
+
+    /// Instantiates the module on a new host thread, and calls its
+    /// [`THREAD_START_EXPORT`] function with the id of the thread and
+    /// `start_arg`, usually a pointer to the start routine of the guest
+    /// and its argument.
+    ///
+    /// The ids start at 1 and are never reused.
+    pub fn spawn(&self, start_arg: i32) -> WasmThread {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let module = self.module.clone();
+        let memory = self.memory.clone();
+        let imports = self.imports.clone();
+        let handle = thread::spawn(move || {
+            let instance = Instance::new(&module, &imports(&memory))?;
+            let start = instance
+                .exports
+                .get_native_function::<(i32, i32), ()>(THREAD_START_EXPORT)?;
+            start.call(id as i32, start_arg)?;
+            Ok(())
+        });
+        WasmThread { id, handle }
+    }
+}
+
+/// A thread started by [`WasmThreads::spawn`].
+pub struct WasmThread {
+    id: u32,
+    handle: JoinHandle<Result<(), ThreadError>>,
+}
+
+impl WasmThread {
+    /// Returns the id of the thread, passed to its start function.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Waits for the thread to finish, returning how its start function
+    /// failed, if it did.
+    pub fn join(self) -> Result<(), ThreadError> {
+        let id = self.id;
+        self.handle.join().unwrap_or(Err(ThreadError::Panicked(id)))
+    }
+}
@@ -1,11 +1,19 @@
+use crate::limits::StoreLimits;
+use crate::memory_debug::PoisonedMemory;
 use crate::{MemoryType, Pages, TableType};
 use std::cmp::min;
 use std::sync::Arc;
 use target_lexicon::{OperatingSystem, PointerWidth};
 use wasmer_compiler::Target;
-use wasmer_engine::Tunables as BaseTunables;
-use wasmer_vm::MemoryError;
-use wasmer_vm::{LinearMemory, LinearTable, Memory, MemoryStyle, Table, TableStyle};
+use wasmer_engine::{LinkError, Tunables as BaseTunables};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, TableIndex,
+};
+use wasmer_vm::{
+    signal_handler_policy, InstanceAllocator, MemoryError, ModuleInfo, SignalHandlerPolicy,
+};
+use wasmer_vm::{Global, LinearMemory, LinearTable, Memory, MemoryStyle, Table, TableStyle};
 
 /// Tunable parameters for WebAssembly compilation.
 #[derive(Clone)]
@@ -49,11 +57,19 @@ impl Tunables {
             static_memory_offset_guard_size = min(static_memory_offset_guard_size, 0x10000);
         }
 
-        Self {
+        let mut tunables = Self {
             static_memory_bound,
             static_memory_offset_guard_size,
             dynamic_memory_offset_guard_size,
+        };
+        if signal_handler_policy() == SignalHandlerPolicy::Disabled {
+            // Out-of-bounds accesses can't be caught without the signal
+            // handlers, so rely on explicit bounds checks only.
+            tunables.static_memory_bound = 0.into();
+            tunables.static_memory_offset_guard_size = 0;
+            tunables.dynamic_memory_offset_guard_size = 0;
         }
+        tunables
     }
 }
 
@@ -65,7 +81,7 @@ impl BaseTunables for Tunables {
         //
         // If the module doesn't declare an explicit maximum treat it as 4GiB.
         let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
-        if maximum <= self.static_memory_bound {
+        if self.static_memory_bound > Pages(0) && maximum <= self.static_memory_bound {
             MemoryStyle::Static {
                 bound: self.static_memory_bound,
                 offset_guard_size: self.static_memory_offset_guard_size,
@@ -96,3 +112,109 @@ impl BaseTunables for Tunables {
         Ok(Arc::new(LinearTable::new(&ty, &style)?))
     }
 }
+
+/// The hook set by [`Store::set_memory_style_hook`].
+///
+/// [`Store::set_memory_style_hook`]: crate::Store::set_memory_style_hook
+pub(crate) type MemoryStyleHook =
+    dyn Fn(Option<&str>, &MemoryType, MemoryStyle) -> MemoryStyle + Send + Sync;
+
+/// Tunables letting a hook override the memory styles chosen by other
+/// tunables, per module, or overriding their instance allocator, or
+/// poisoning the pages their memories grow by, or limiting their growth.
+pub(crate) struct HookedTunables {
+    pub(crate) inner: Arc<dyn BaseTunables + Send + Sync>,
+    pub(crate) hook: Option<Box<MemoryStyleHook>>,
+    pub(crate) allocator: Option<Arc<dyn InstanceAllocator>>,
+    pub(crate) poison: Option<u8>,
+    pub(crate) limits: Option<StoreLimits>,
+}
+
+impl HookedTunables {
+    fn poison(&self, memory: Arc<dyn Memory>) -> Arc<dyn Memory> {
+        match self.poison {
+            Some(byte) => Arc::new(PoisonedMemory {
+                inner: memory,
+                byte,
+            }),
+            None => memory,
+        }
+    }
+}
+
+impl BaseTunables for HookedTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.inner.memory_style(memory)
+    }
+
+    fn module_memory_style(
+        &self,
+        module: &ModuleInfo,
+        index: MemoryIndex,
+        memory: &MemoryType,
+    ) -> MemoryStyle {
+        let style = self.inner.module_memory_style(module, index, memory);
+        match &self.hook {
+            Some(hook) => hook(module.name.as_deref(), memory, style),
+            None => style,
+        }
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.inner.table_style(table)
+    }
+
+    fn create_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        Ok(self.poison(self.inner.create_memory(ty, style)?))
+    }
+
+    fn create_table(&self, ty: &TableType, style: &TableStyle) -> Result<Arc<dyn Table>, String> {
+        self.inner.create_table(ty, style)
+    }
+
+    fn create_global(&self, ty: GlobalType) -> Result<Arc<Global>, String> {
+        self.inner.create_global(ty)
+    }
+
+    fn instance_allocator(&self) -> Arc<dyn InstanceAllocator> {
+        match &self.allocator {
+            Some(allocator) => allocator.clone(),
+            None => self.inner.instance_allocator(),
+        }
+    }
+
+    fn create_memories(
+        &self,
+        module: &ModuleInfo,
+        memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
+    ) -> Result<PrimaryMap<LocalMemoryIndex, Arc<dyn Memory>>, LinkError> {
+        let mut memories = self.inner.create_memories(module, memory_styles)?;
+        for memory in memories.values_mut() {
+            *memory = self.poison(memory.clone());
+            // only the memories of instances count in the limits
+            if let Some(limits) = &self.limits {
+                *memory = limits.limit_memory(memory.clone());
+            }
+        }
+        Ok(memories)
+    }
+
+    fn create_tables(
+        &self,
+        module: &ModuleInfo,
+        table_styles: &PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<PrimaryMap<LocalTableIndex, Arc<dyn Table>>, LinkError> {
+        self.inner.create_tables(module, table_styles)
+    }
+
+    fn create_globals(
+        &self,
+        module: &ModuleInfo,
+    ) -> Result<PrimaryMap<LocalGlobalIndex, Arc<Global>>, LinkError> {
+        self.inner.create_globals(module)
+    }
+}
@@ -79,6 +79,7 @@ impl ValFuncRef for Val {
             kind: wasmer_vm::VMFunctionKind::Static,
             vmctx: item.vmctx,
             call_trampoline: None,
+            instance_vmctx: None,
         };
         let f = Function::from_export(store, export);
         Self::FuncRef(f)
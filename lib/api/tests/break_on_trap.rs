@@ -0,0 +1,33 @@
+//! These tests enable breaking on traps for the whole process, so they run
+//! in their own binary.
+
+use anyhow::Result;
+use wasmer::*;
+
+#[test]
+fn host_errors_dont_break() -> Result<()> {
+    set_break_on_trap(true);
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+            (import "env" "fail" (func $fail (result i32)))
+            (func (export "run") (result i32)
+                (call $fail)))"#,
+    )?;
+    let imports = imports! {
+        "env" => {
+            "fail" => Function::new_native(&store, || -> Result<i32, RuntimeError> {
+                Err(RuntimeError::new("host error"))
+            }),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let run = instance.exports.get_function("run")?;
+
+    // without a debugger attached, breaking would kill the process
+    let error = run.call(&[]).unwrap_err();
+    assert_eq!(error.message(), "host error");
+    set_break_on_trap(false);
+    Ok(())
+}
@@ -0,0 +1,30 @@
+//! These tests set the signal handler policy of the whole process, so they
+//! run in their own binary.
+
+use anyhow::Result;
+use wasmer::*;
+
+#[test]
+fn memories_have_no_guards() -> Result<()> {
+    set_signal_handler_policy(SignalHandlerPolicy::Disabled)?;
+
+    let tunables = Tunables::for_target(&Target::default());
+    assert_eq!(tunables.static_memory_bound, Pages(0));
+    assert_eq!(tunables.static_memory_offset_guard_size, 0);
+    assert_eq!(tunables.dynamic_memory_offset_guard_size, 0);
+
+    // the accesses are checked explicitly
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "last") (result i32)
+                (i32.store (i32.const 0xfffc) (i32.const 42))
+                (i32.load (i32.const 0xfffc))))"#,
+    )?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let last = instance.exports.get_native_function::<(), i32>("last")?;
+    assert_eq!(last.call()?, 42);
+    Ok(())
+}
@@ -1,4 +1,6 @@
 use anyhow::Result;
+use std::thread;
+use std::time::Duration;
 use wasmer::*;
 
 #[test]
@@ -177,6 +179,171 @@ fn memory_grow() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn memory_poison() -> Result<()> {
+    let mut store = Store::default();
+    store.set_memory_poison(0xaa);
+
+    let memory = Memory::new(&store, MemoryType::new(Pages(1), None, false))?;
+    assert_eq!(memory.view::<u8>()[0].get(), 0);
+    memory.grow(Pages(1))?;
+    assert_eq!(memory.view::<u8>()[WASM_PAGE_SIZE - 1].get(), 0);
+    assert_eq!(memory.view::<u8>()[WASM_PAGE_SIZE].get(), 0xaa);
+
+    Ok(())
+}
+
+#[test]
+fn memory_canaries() -> Result<()> {
+    let store = Store::default();
+    let memory = Memory::new(&store, MemoryType::new(Pages(1), None, false))?;
+    let mut canaries = MemoryCanaries::new(&memory);
+
+    canaries.guard(16, 4)?;
+    memory.view::<u8>()[19].set(1);
+    canaries.check()?;
+    memory.view::<u8>()[20].set(1);
+    assert_eq!(
+        canaries.check(),
+        Err(CanaryError::Overwritten {
+            offset: 16,
+            len: 4,
+            canary: 20,
+        })
+    );
+    canaries.release(16);
+    canaries.check()?;
+
+    assert!(canaries.guard(4, 4).is_err());
+    assert!(canaries.guard(WASM_PAGE_SIZE as u32 - 8, 4).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn memory_atomics() -> Result<()> {
+    let store = Store::default();
+    let memory = Memory::new(&store, MemoryType::new(Pages(1), None, false))?;
+    let view = memory.view::<u32>();
+    let atomic_view = view.atomically();
+
+    assert_eq!(atomic_view.compare_exchange(0, 0, 5), Some(Ok(0)));
+    assert_eq!(atomic_view.compare_exchange(0, 0, 6), Some(Err(5)));
+    assert_eq!(atomic_view.fetch_add(0, 2), Some(5));
+    assert_eq!(atomic_view.fetch_sub(0, 1), Some(7));
+    assert_eq!(atomic_view.fetch_add(view.len(), 1), None);
+
+    assert_eq!(atomic_view.wait(0, 0, None), Some(WaitResult::NotEqual));
+    assert_eq!(
+        atomic_view.wait(0, 6, Some(Duration::from_millis(10))),
+        Some(WaitResult::TimedOut)
+    );
+    assert_eq!(atomic_view.notify(0, 1), Some(0));
+
+    let (data, length) = (memory.data_ptr() as usize, view.len() as u32);
+    let notifier = thread::spawn(move || {
+        let view = unsafe { MemoryView::<u32>::new(data as *mut u32, length) };
+        let atomic_view = view.atomically();
+        while atomic_view.notify(0, 1) == Some(0) {
+            thread::yield_now();
+        }
+    });
+    assert_eq!(atomic_view.wait(0, 6, None), Some(WaitResult::Woken));
+    notifier.join().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn memory_little_endian_struct() -> Result<()> {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[repr(C)]
+    struct Header {
+        tag: u8,
+        length: u64,
+        flags: [u16; 3],
+    }
+    little_endian_struct!(Header {
+        tag: u8,
+        length: u64,
+        flags: [u16; 3],
+    });
+    assert_eq!(Header::SIZE, 24);
+    assert_eq!(Header::ALIGN, 8);
+
+    let store = Store::default();
+    let memory = Memory::new(&store, MemoryType::new(Pages(1), None, false))?;
+    let view = memory.view::<u8>();
+    for cell in view[..32].iter() {
+        cell.set(0xff);
+    }
+
+    let header = Header {
+        tag: 7,
+        length: 0x0102_0304_0506_0708,
+        flags: [1, 2, 3],
+    };
+    // An unaligned pointer.
+    let ptr: WasmPtr<Header> = WasmPtr::new(3);
+    ptr.write(&memory, &header).unwrap();
+    assert_eq!(
+        view[3..27]
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<_>>(),
+        vec![7, 0, 0, 0, 0, 0, 0, 0, 8, 7, 6, 5, 4, 3, 2, 1, 1, 0, 2, 0, 3, 0, 0, 0]
+    );
+    assert_eq!(ptr.read(&memory), Some(header));
+    assert_eq!(WasmPtr::<Header>::new(0xffff).read(&memory), None);
+
+    Ok(())
+}
+
+#[test]
+fn memory_out_of_bounds_policy() -> Result<()> {
+    let wat = r#"(module
+    (import "env" "memory" (memory 1))
+    (import "env" "load" (func $load (param i32) (result i32)))
+    (func (export "load") (param i32) (result i32) (call $load (local.get 0))))"#;
+    let instantiate = |store: &Store| -> Result<Instance> {
+        let module = Module::new(store, wat)?;
+        let memory = Memory::new(store, MemoryType::new(Pages(1), None, false))?;
+        let load = Function::new_native_with_env(
+            store,
+            memory.clone(),
+            |memory: &mut Memory, ptr: WasmPtr<u32>| -> i32 {
+                match ptr.deref(memory) {
+                    Some(cell) => cell.get() as i32,
+                    None => -1,
+                }
+            },
+        );
+        Ok(Instance::new(
+            &module,
+            &imports! { "env" => { "memory" => memory, "load" => load } },
+        )?)
+    };
+
+    let mut store = Store::default();
+    assert_eq!(store.out_of_bounds_policy(), OutOfBoundsPolicy::Error);
+    let instance = instantiate(&store)?;
+    let load = instance.exports.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, 0);
+    assert_eq!(load.call(0x10000)?, -1);
+
+    store.set_out_of_bounds_policy(OutOfBoundsPolicy::Trap);
+    let instance = instantiate(&store)?;
+    let load = instance.exports.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, 0);
+    let error = load.call(0x10000).unwrap_err();
+    assert_eq!(
+        error.downcast::<MemoryAccessError>()?,
+        MemoryAccessError { offset: 0x10000 }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn function_new() -> Result<()> {
     let store = Store::default();
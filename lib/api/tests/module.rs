@@ -1,4 +1,5 @@
 use anyhow::Result;
+use std::sync::Arc;
 use wasmer::*;
 
 #[test]
@@ -246,3 +247,1402 @@ fn calling_host_functions_with_negative_values_works() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn instance_exports_lookup() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (func (export "one") (result i32) (i32.const 1))
+    (memory (export "memory") 1)
+    (func (export "two") (result i32) (i32.const 2))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+
+    let functions: Vec<&Function> = instance.exports.get_many(&["two", "one"])?;
+    assert_eq!(functions[0].call(&[])?.to_vec(), vec![Val::I32(2)]);
+    assert_eq!(functions[1].call(&[])?.to_vec(), vec![Val::I32(1)]);
+    assert!(matches!(
+        instance.exports.get_many::<Function>(&["one", "memory"]),
+        Err(ExportError::IncompatibleType)
+    ));
+
+    assert_eq!(instance.exports.get_index_of("memory"), Some(1));
+    assert_eq!(instance.exports.get_index_of("missing"), None);
+    let two: &Function = instance.exports.get_by_index(2)?;
+    assert_eq!(two.call(&[])?.to_vec(), vec![Val::I32(2)]);
+    assert!(instance.exports.get_by_index::<Memory>(1).is_ok());
+    assert!(matches!(
+        instance.exports.get_by_index::<Function>(3),
+        Err(ExportError::Missing(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn custom_sections() -> Result<()> {
+    let store = Store::default();
+    let wasm = Module::with_custom_section("(module)", "license", b"MIT")?;
+    let wasm = Module::with_custom_section(wasm, "producers", b"")?;
+    let module = Module::new(&store, wasm)?;
+
+    assert_eq!(
+        module
+            .custom_sections("license")
+            .map(|data| data.to_vec())
+            .collect::<Vec<_>>(),
+        vec![b"MIT".to_vec()]
+    );
+    assert_eq!(
+        module
+            .all_custom_sections()
+            .map(|(name, data)| (name.to_string(), data.to_vec()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("license".to_string(), b"MIT".to_vec()),
+            ("producers".to_string(), vec![]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn function_and_local_names() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "host" "log" (func $log (param i32)))
+    (func $double (param $value i32) (result i32)
+        (local $tmp i32)
+        (i32.add (local.get $value) (local.get $value)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    assert_eq!(module.function_name(0), Some("log"));
+    assert_eq!(module.function_name(1), Some("double"));
+    assert_eq!(module.function_name(2), None);
+    assert_eq!(module.local_name(1, 0), Some("value"));
+    assert_eq!(module.local_name(1, 1), Some("tmp"));
+    assert_eq!(module.local_name(1, 2), None);
+
+    let invalid = wat2wasm(br#"(module (func $bad (result i32) (i64.const 0)))"#)?;
+    let error = Module::validate(&store, &invalid).unwrap_err();
+    assert!(
+        error.to_string().contains("`bad`"),
+        "wrong error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn compile_info() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "host" "log" (func $log (param i32)))
+    (func $first (result i32) (i32.const 1))
+    (func $second (param i32) (result i32) (i32.mul (local.get 0) (local.get 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let stats = module.compile_info().collect::<Vec<_>>();
+    assert_eq!(
+        stats.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert!(stats.iter().all(|(_, stats)| stats.code_size > 0));
+
+    Ok(())
+}
+
+#[test]
+fn memory_style_hook() -> Result<()> {
+    let mut store = Store::default();
+    store.set_memory_style_hook(|name, _memory, style| {
+        if name == Some("plugin") {
+            MemoryStyle::Dynamic {
+                offset_guard_size: 0,
+            }
+        } else {
+            style
+        }
+    });
+    let plugin = Module::new(&store, r#"(module $plugin (memory (export "mem") 1 2))"#)?;
+    let other = Module::new(&store, "(module (memory 1 2))")?;
+    let plugin_style = plugin.artifact().memory_styles().values().next().cloned();
+    let other_style = other.artifact().memory_styles().values().next().cloned();
+    assert_eq!(
+        plugin_style,
+        Some(MemoryStyle::Dynamic {
+            offset_guard_size: 0
+        })
+    );
+    assert_ne!(plugin_style, other_style);
+
+    let instance = Instance::new(&plugin, &imports! {})?;
+    let memory = instance.exports.get_memory("mem")?;
+    memory.grow(1)?;
+    assert_eq!(memory.size(), Pages(2));
+
+    Ok(())
+}
+
+#[test]
+fn instance_memory_usage() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (memory 2)
+    (table 10 funcref)
+    (global (mut i32) (i32.const 0))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let usage = instance.memory_usage();
+    assert_eq!(usage.memories, 2 * 0x10000);
+    assert!(usage.tables > 0);
+    assert!(usage.vmctx > 0);
+    assert!(usage.host_metadata > 0);
+    assert_eq!(
+        usage.total(),
+        usage.memories + usage.tables + usage.vmctx + usage.host_metadata
+    );
+
+    Ok(())
+}
+
+#[test]
+fn instance_cpu_time() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (func (export "spin") (param $n i32)
+        (loop $continue
+            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+            (br_if $continue (local.get $n))))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    assert_eq!(instance.cpu_time(), std::time::Duration::default());
+
+    let spin = instance.exports.get_function("spin")?;
+    spin.call(&[Value::I32(10_000_000)])?;
+    let used = instance.cpu_time();
+    assert!(used > std::time::Duration::default());
+
+    spin.call(&[Value::I32(10_000_000)])?;
+    assert!(instance.cpu_time() > used);
+
+    Ok(())
+}
+
+#[test]
+fn reexported_host_function_cpu_time() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "env" "answer" (func $answer (result i32)))
+    (export "answer" (func $answer))
+    (func (export "call_answer") (result i32) (call $answer))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "answer" => Function::new_native(&store, || 42),
+            },
+        },
+    )?;
+
+    // the host function isn't a function of the instance, the instance isn't
+    // charged for calling it through its export
+    let answer = instance.exports.get_function("answer")?;
+    assert_eq!(answer.call(&[])?.to_vec(), vec![Value::I32(42)]);
+    assert_eq!(answer.native::<(), i32>()?.call()?, 42);
+    assert_eq!(instance.cpu_time(), std::time::Duration::default());
+
+    let call_answer = instance.exports.get_function("call_answer")?;
+    assert_eq!(call_answer.call(&[])?.to_vec(), vec![Value::I32(42)]);
+
+    Ok(())
+}
+
+#[test]
+fn store_limits() -> Result<()> {
+    let mut store = Store::default();
+    let limits = StoreLimits::new()
+        .instances(3)
+        .memory(3 * 0x10000)
+        .tables(1);
+    store.set_limits(limits.clone());
+
+    let with_memory = Module::new(&store, "(module (memory 2))")?;
+    let with_table = Module::new(&store, "(module (table 1 funcref))")?;
+
+    let first = Instance::new(&with_memory, &imports! {})?;
+    assert!(matches!(
+        Instance::new(&with_memory, &imports! {}),
+        Err(InstantiationError::Limit(StoreLimitError::Memory {
+            requested: 0x40000,
+            limit: 0x30000,
+        }))
+    ));
+    let table = Instance::new(&with_table, &imports! {})?;
+    assert!(matches!(
+        Instance::new(&with_table, &imports! {}),
+        Err(InstantiationError::Limit(StoreLimitError::Tables {
+            requested: 2,
+            limit: 1,
+        }))
+    ));
+    assert_eq!(
+        limits.usage(),
+        StoreUsage {
+            instances: 2,
+            memory_bytes: 2 * 0x10000,
+            tables: 1,
+        }
+    );
+
+    // the clones of an instance hold its resources
+    let clone = first.clone();
+    drop(first);
+    assert_eq!(limits.usage().instances, 2);
+    drop(clone);
+    drop(table);
+    assert_eq!(limits.usage(), StoreUsage::default());
+    Instance::new(&with_memory, &imports! {})?;
+
+    Ok(())
+}
+
+#[test]
+fn store_limits_memory_growth() -> Result<()> {
+    let mut store = Store::default();
+    let limits = StoreLimits::new().memory(4 * 0x10000);
+    store.set_limits(limits.clone());
+    let wat = r#"(module
+    (memory (export "memory") 1)
+    (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let grow = instance.exports.get_native_function::<i32, i32>("grow")?;
+    assert_eq!(grow.call(1)?, 1);
+    assert_eq!(limits.usage().memory_bytes, 2 * 0x10000);
+    let other = Instance::new(&module, &imports! {})?;
+    assert_eq!(limits.usage().memory_bytes, 3 * 0x10000);
+
+    // growing over the limit fails, from the guest or from the host
+    assert_eq!(grow.call(2)?, -1);
+    let memory = instance.exports.get_memory("memory")?;
+    assert!(memory.grow(2).is_err());
+    assert_eq!(memory.size(), Pages(2));
+    assert_eq!(grow.call(1)?, 2);
+    assert_eq!(limits.usage().memory_bytes, 4 * 0x10000);
+
+    // the growth is released when the memory is reset, or dropped
+    unsafe { instance.reset()? };
+    assert_eq!(limits.usage().memory_bytes, 2 * 0x10000);
+    let grow = other.exports.get_native_function::<i32, i32>("grow")?;
+    assert_eq!(grow.call(1)?, 1);
+    drop(instance);
+    drop(grow);
+    drop(other);
+    assert_eq!(limits.usage(), StoreUsage::default());
+
+    Ok(())
+}
+
+#[test]
+fn module_hash() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module (func (export "one") (result i32) (i32.const 1)))"#;
+    let binary = wat2wasm(wat.as_bytes())?;
+
+    let module = Module::new(&store, wat)?;
+    let hash = module.hash().unwrap();
+    assert_eq!(hash, ModuleHash::generate(&binary));
+    assert_eq!(Module::new(&Store::default(), &binary)?.hash(), Some(hash));
+    assert_eq!(hash.to_string().len(), 64);
+    assert_ne!(Module::new(&store, "(module)")?.hash(), Some(hash));
+
+    // the code cache keys off the hash, whatever the format
+    let cache = CodeCache::new();
+    let from_text = cache.module(&store, wat)?;
+    let from_binary = cache.module(&store, &binary)?;
+    assert_eq!(cache.len(), 1);
+    assert!(Arc::ptr_eq(from_text.artifact(), from_binary.artifact()));
+
+    Ok(())
+}
+
+#[test]
+fn code_cache_shared_by_isolated_stores() -> Result<()> {
+    let cache = CodeCache::new();
+    let mut tenant_a = Store::default();
+    tenant_a.set_limits(StoreLimits::new().instances(1));
+    let tenant_b = tenant_a.isolated();
+    assert!(tenant_b.limits().is_none());
+
+    let wat = r#"(module (memory (export "memory") 1))"#;
+    let module_a = cache.module(&tenant_a, wat)?;
+    let module_b = cache.module(&tenant_b, wat)?;
+    assert_eq!(cache.len(), 1);
+    assert!(Arc::ptr_eq(module_a.artifact(), module_b.artifact()));
+    assert!(Store::same(module_b.store(), &tenant_b));
+
+    // the instances, and the limits of each store, are separate
+    let instance_a = Instance::new(&module_a, &imports! {})?;
+    assert!(Instance::new(&module_a, &imports! {}).is_err());
+    let instance_b = Instance::new(&module_b, &imports! {})?;
+    Instance::new(&module_b, &imports! {})?;
+    instance_a.exports.get_memory("memory")?.view::<u8>()[0].set(1);
+    assert_eq!(
+        instance_b.exports.get_memory("memory")?.view::<u8>()[0].get(),
+        0
+    );
+
+    // another engine gets its own entry
+    let other = Store::default();
+    cache.module(&other, wat)?;
+    assert_eq!(cache.len(), 2);
+    cache.clear();
+    assert!(cache.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn instance_reset() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (memory (export "memory") 1)
+    (table (export "table") 2 funcref)
+    (global $g (export "g") (mut i32) (i32.const 7))
+    (func $f (result i32) (i32.const 42))
+    (elem (i32.const 0) $f)
+    (data (i32.const 16) "hello")
+    (func (export "dirty")
+        (i32.store (i32.const 0) (i32.const 1))
+        (i32.store8 (i32.const 16) (i32.const 0))
+        (drop (memory.grow (i32.const 2)))
+        (global.set $g (i32.const 8)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    instance.exports.get_function("dirty")?.call(&[])?;
+    let memory = instance.exports.get_memory("memory")?;
+    assert_eq!(memory.size(), Pages(3));
+    let table = instance.exports.get_table("table")?;
+    let f = table.get(0).unwrap();
+    table.set(0, Val::null())?;
+    table.set(1, f)?;
+
+    unsafe { instance.reset()? };
+    assert_eq!(memory.size(), Pages(1));
+    let view = memory.view::<u8>();
+    assert_eq!(view[0].get(), 0);
+    assert_eq!(view[16].get(), b'h');
+    assert_eq!(instance.exports.get_global("g")?.get(), Value::I32(7));
+    assert!(matches!(table.get(0), Some(Val::FuncRef(_))));
+    assert!(matches!(
+        table.get(1),
+        Some(Val::ExternRef(ExternRef::Null))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn instance_pool() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (global $count (export "count") (mut i32) (i32.const 0))
+    (func (export "handle") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (global.get $count))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let pool = InstancePool::new(&module, &imports! {}, 1)?;
+
+    let first = pool.get()?;
+    assert_eq!(pool.idle(), 0);
+    // instances are created on demand when the pool is empty
+    let second = pool.get()?;
+    second.exports.get_function("handle")?.call(&[])?;
+    drop(second);
+    assert_eq!(pool.idle(), 1);
+    drop(first);
+    assert_eq!(pool.idle(), 1);
+
+    let instance = pool.get()?;
+    let handle = instance.exports.get_function("handle")?;
+    assert_eq!(handle.call(&[])?[0], Value::I32(1));
+    let detached = instance.detach();
+    assert_eq!(pool.idle(), 0);
+    assert_eq!(detached.exports.get_global("count")?.get(), Value::I32(1));
+
+    Ok(())
+}
+
+#[test]
+fn instance_pool_drops_shared_instances() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (global $count (export "count") (mut i32) (i32.const 0))
+    (func (export "handle") (result i32)
+        (global.set $count (i32.add (global.get $count) (i32.const 1)))
+        (global.get $count))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let pool = InstancePool::new(&module, &imports! {}, 1)?;
+
+    // a clone of the instance outlives it
+    let instance = pool.get()?;
+    let clone = (*instance).clone();
+    drop(instance);
+    assert_eq!(pool.idle(), 0);
+    drop(clone);
+
+    // a clone of one of its exports does
+    let instance = pool.get()?;
+    instance.exports.get_function("handle")?.call(&[])?;
+    let count = instance.exports.get_global("count")?.clone();
+    drop(instance);
+    assert_eq!(pool.idle(), 0);
+    assert_eq!(count.get(), Value::I32(1));
+    drop(count);
+
+    let instance = pool.get()?;
+    instance.exports.get_function("handle")?.call(&[])?;
+    drop(instance);
+    assert_eq!(pool.idle(), 1);
+    let instance = pool.get()?;
+    assert_eq!(instance.exports.get_global("count")?.get(), Value::I32(0));
+
+    Ok(())
+}
+
+#[test]
+fn scheduler() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (global $left (export "left") (mut i32) (i32.const 5))
+    (func (export "_step") (result i32)
+        (global.set $left (i32.sub (global.get $left) (i32.const 1)))
+        (global.get $left)))"#;
+    let scheduler = Scheduler::new(2);
+    let spawn = |options| -> Result<TaskHandle> {
+        let module = Module::new(&store.isolated(), wat)?;
+        let instance = Instance::new(&module, &imports! {})?;
+        Ok(scheduler.spawn(instance, options))
+    };
+
+    let tasks = (0..8)
+        .map(|_| spawn(TaskOptions::new()))
+        .collect::<Result<Vec<_>>>()?;
+    let late = spawn(TaskOptions::new().deadline(std::time::Instant::now()))?;
+    for task in tasks {
+        let instance = task.join()?;
+        assert_eq!(instance.exports.get_global("left")?.get(), Value::I32(0));
+    }
+    assert!(matches!(late.join(), Err(TaskError::DeadlineMissed)));
+
+    let module = Module::new(&store, "(module)")?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let task = scheduler.spawn(instance, TaskOptions::new());
+    assert!(matches!(task.join(), Err(TaskError::Export(_))));
+
+    Ok(())
+}
+
+#[test]
+fn host_info() -> Result<()> {
+    let store = Store::default();
+    let mut features = Features::default();
+    features.simd(true);
+    let host_info = HostCapabilities::new()
+        .features(&features)
+        .feature("wasi")
+        .capability("log", 3);
+    let wat = r#"(module
+    (import "host_info" "version" (func $version (result i32)))
+    (import "host_info" "has_feature" (func $has_feature (param i32 i32) (result i32)))
+    (import "host_info" "capability_version"
+        (func $capability_version (param i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "simdthreadslogfs")
+    (func (export "version") (result i32) (call $version))
+    (func (export "features") (result i32 i32)
+        (call $has_feature (i32.const 0) (i32.const 4))
+        (call $has_feature (i32.const 4) (i32.const 7)))
+    (func (export "capabilities") (result i32 i32)
+        (call $capability_version (i32.const 11) (i32.const 3))
+        (call $capability_version (i32.const 14) (i32.const 2)))
+    (func (export "out_of_bounds") (result i32)
+        (call $has_feature (i32.const 65535) (i32.const 2))))"#;
+    let module = Module::new(&store, wat)?;
+    let exports = host_info.exports(&store);
+    let instance = Instance::new(&module, &imports! { HOST_INFO_NAMESPACE => exports })?;
+    host_info.set_memory(instance.exports.get_memory("memory")?.clone());
+
+    let version = instance
+        .exports
+        .get_native_function::<(), i32>("version")?
+        .call()?;
+    let major: i32 = VERSION.split('.').next().unwrap().parse()?;
+    assert_eq!(version >> 16, major);
+    let features = instance.exports.get_function("features")?.call(&[])?;
+    assert_eq!(*features, [Value::I32(1), Value::I32(0)]);
+    let capabilities = instance.exports.get_function("capabilities")?.call(&[])?;
+    assert_eq!(*capabilities, [Value::I32(3), Value::I32(0)]);
+    assert!(host_info.has_feature("wasi"));
+    assert!(instance
+        .exports
+        .get_function("out_of_bounds")?
+        .call(&[])
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn guest_allocator() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 16))
+    (global $freed (export "freed") (mut i32) (i32.const 0))
+    (func (export "malloc") (param $size i32) (result i32)
+        (global.get $next)
+        (global.set $next (i32.add (global.get $next) (local.get $size))))
+    (func (export "free") (param $ptr i32)
+        (global.set $freed (i32.add (global.get $freed) (i32.const 1))))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let freed = instance.exports.get_global("freed")?;
+    let allocator = GuestAllocator::new(&instance)?;
+
+    let buffer = allocator.alloc_bytes(b"hello")?;
+    assert_eq!(buffer.offset(), 16);
+    assert_eq!(buffer.len(), 5);
+    assert_eq!(
+        buffer
+            .ptr()
+            .get_utf8_string(allocator.memory(), buffer.len()),
+        Some("hello")
+    );
+    drop(buffer);
+    assert_eq!(freed.get(), Value::I32(1));
+
+    let offset = allocator.alloc(4)?.into_guest();
+    assert_eq!(offset, 21);
+    assert_eq!(freed.get(), Value::I32(1));
+
+    let no_allocator = Module::new(&store, r#"(module (memory (export "memory") 1))"#)?;
+    let instance = Instance::new(&no_allocator, &imports! {})?;
+    assert!(matches!(
+        GuestAllocator::new(&instance),
+        Err(GuestAllocatorError::NoAllocator)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn callback_table() -> Result<()> {
+    let store = Store::default();
+    let callbacks = CallbackTable::new();
+    let subscribe = Function::new_native_with_env(
+        &store,
+        callbacks.clone(),
+        |callbacks: &mut CallbackTable, function_pointer: u32| -> u32 {
+            callbacks
+                .register_index::<i32, i32>(function_pointer)
+                .map(CallbackId::as_u32)
+                .unwrap_or(u32::max_value())
+        },
+    );
+    let wat = r#"(module
+    (import "env" "subscribe" (func $subscribe (param i32) (result i32)))
+    (table (export "__indirect_function_table") 2 funcref)
+    (elem (i32.const 0) $double $log)
+    (func $double (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+    (func $log (param i64))
+    (func (export "subscribe") (param i32) (result i32) (call $subscribe (local.get 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "env" => { "subscribe" => subscribe },
+        },
+    )?;
+    callbacks.set_function_table(
+        instance
+            .exports
+            .get_table("__indirect_function_table")?
+            .clone(),
+    );
+    let subscribe = instance
+        .exports
+        .get_native_function::<u32, u32>("subscribe")?;
+
+    let id = subscribe.call(0)?;
+    assert_eq!(id, 0);
+    // Wrong signature.
+    assert_eq!(subscribe.call(1)?, u32::max_value());
+    // Out of bounds.
+    assert_eq!(subscribe.call(2)?, u32::max_value());
+
+    let id = CallbackId::from(id);
+    assert_eq!(callbacks.ids(), vec![id]);
+    assert_eq!(callbacks.get::<i32, i32>(id)?.call(21)?, 42);
+    assert!(callbacks.get::<i64, ()>(id).is_err());
+    assert!(callbacks.unregister(id));
+    assert!(callbacks.get::<i32, i32>(id).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn event_loop() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "wasmer_events" "set_timeout" (func $set_timeout (param i32 i32 i32) (result i32)))
+    (import "wasmer_events" "set_interval" (func $set_interval (param i32 i32 i32) (result i32)))
+    (import "wasmer_events" "clear_timer" (func $clear_timer (param i32)))
+    (import "wasmer_events" "subscribe" (func $subscribe (param i32 i32 i32) (result i32)))
+    (table (export "__indirect_function_table") 2 funcref)
+    (elem (i32.const 0) $add $bad)
+    (global $sum (export "sum") (mut i32) (i32.const 0))
+    (func $add (param i32 i32)
+        (global.set $sum (i32.add (global.get $sum) (local.get 0))))
+    (func $bad (param i32))
+    (func (export "set_timeout") (param i32 i32) (result i32)
+        (call $set_timeout (i32.const 0) (local.get 0) (local.get 1)))
+    (func (export "set_interval") (param i32 i32) (result i32)
+        (call $set_interval (i32.const 0) (local.get 0) (local.get 1)))
+    (func (export "clear_timer") (param i32) (call $clear_timer (local.get 0)))
+    (func (export "subscribe") (param i32 i32) (result i32)
+        (call $subscribe (local.get 0) (i32.const 0) (local.get 1)))
+    (func (export "subscribe_bad") (result i32)
+        (call $subscribe (i32.const 0) (i32.const 1) (i32.const 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let event_loop = EventLoop::new();
+    let events = event_loop.exports(&store);
+    let mut instance = Instance::new(&module, &imports! { "wasmer_events" => events })?;
+    assert_eq!(instance.poll_events()?, 0);
+    instance.set_event_loop(event_loop.clone())?;
+    let sum = instance.exports.get_global("sum")?;
+    let set_timeout = instance
+        .exports
+        .get_native_function::<(u32, i32), u32>("set_timeout")?;
+    let set_interval = instance
+        .exports
+        .get_native_function::<(u32, i32), u32>("set_interval")?;
+    let clear_timer = instance
+        .exports
+        .get_native_function::<u32, ()>("clear_timer")?;
+    let subscribe = instance
+        .exports
+        .get_native_function::<(u32, i32), u32>("subscribe")?;
+
+    // Timers.
+    set_timeout.call(0, 1)?;
+    let cancelled = set_timeout.call(0, 10)?;
+    set_timeout.call(60_000, 100)?;
+    let interval = set_interval.call(0, 1000)?;
+    clear_timer.call(cancelled)?;
+    assert!(event_loop.next_deadline().is_some());
+    assert_eq!(instance.poll_events()?, 2);
+    assert_eq!(sum.get(), Value::I32(1001));
+    assert_eq!(instance.poll_events()?, 1);
+    assert_eq!(sum.get(), Value::I32(2001));
+    clear_timer.call(interval)?;
+    assert_eq!(instance.poll_events()?, 0);
+
+    // Events.
+    subscribe.call(7, 10_000)?;
+    subscribe.call(8, 100_000)?;
+    event_loop.post_event(7, 0);
+    event_loop.post_event(7, 0);
+    assert!(event_loop.has_pending_events());
+    assert_eq!(instance.poll_events()?, 2);
+    assert!(!event_loop.has_pending_events());
+    assert_eq!(sum.get(), Value::I32(22001));
+
+    // Callbacks with the wrong signature trap.
+    let subscribe_bad = instance
+        .exports
+        .get_native_function::<(), u32>("subscribe_bad")?;
+    assert!(subscribe_bad.call().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn plugin_interfaces() -> Result<()> {
+    struct Log;
+
+    impl HostInterface for Log {
+        const NAME: &'static str = "log";
+        const VERSION: u32 = 2;
+
+        fn exports(&self, store: &Store) -> Exports {
+            let mut exports = Exports::new();
+            exports.insert("info", Function::new_native(store, |_: i32| {}));
+            exports
+        }
+    }
+
+    let store = Store::default();
+    let mut host = PluginHost::new(&store);
+    host.provide(&Log);
+    assert_eq!(host.version("log"), Some(2));
+    assert_eq!(host.version("fs"), None);
+
+    let wat = r#"(module (import "log" "info" (func (param i32))))"#;
+    let plugin = |requirements: &[u8]| -> Result<Module> {
+        let wasm = Module::with_custom_section(wat, INTERFACES_SECTION, requirements)?;
+        Ok(Module::new(&store, wasm)?)
+    };
+
+    let module = plugin(b"log 1\n")?;
+    assert_eq!(
+        required_interfaces(&module)?,
+        vec![InterfaceRequirement {
+            name: "log".to_string(),
+            version: 1,
+        }]
+    );
+    host.instantiate(&module)?;
+    host.instantiate(&plugin(b"log v2")?)?;
+
+    let error = host.instantiate(&plugin(b"log 3")?).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "plugin requires log v3, host provides v2"
+    );
+    let error = host.instantiate(&plugin(b"log 1\nfs 1")?).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "plugin requires fs v1, host doesn't provide fs"
+    );
+    assert!(matches!(
+        host.instantiate(&plugin(b"log")?),
+        Err(PluginError::MalformedRequirements(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn instance_hot_swap() -> Result<()> {
+    let store = Store::default();
+    let v1 = Module::new(
+        &store,
+        r#"(module
+    (memory (export "memory") 1)
+    (data (i32.const 16) "\04\00\00\00state")
+    (func (export "save") (result i32) (i32.const 16))
+    (func (export "get") (param i32) (result i32) (i32.load8_u (local.get 0))))"#,
+    )?;
+    let v2 = Module::new(
+        &store,
+        r#"(module
+    (memory (export "memory") 2)
+    (global $next (mut i32) (i32.const 1024))
+    (global $state (export "state") (mut i32) (i32.const 0))
+    (global $restored (export "restored") (mut i32) (i32.const 0))
+    (func (export "malloc") (param i32) (result i32)
+        (global.get $next)
+        (global.set $next (i32.add (global.get $next) (local.get 0))))
+    (func (export "free") (param i32))
+    (func (export "restore") (param i32 i32)
+        (global.set $state (local.get 0))
+        (global.set $restored (local.get 1)))
+    (func (export "get") (param i32) (result i32) (i32.load8_u (local.get 0))))"#,
+    )?;
+
+    let mut instance = Instance::new(&v1, &imports! {})?;
+    let old = instance.hot_swap(&v2, &imports! {}, migrate_with_exports("save", "restore"))?;
+    assert!(old.exports.get_function("restore").is_err());
+    assert_eq!(
+        instance.exports.get_global("restored")?.get(),
+        Value::I32(4)
+    );
+    let state = instance.exports.get_global("state")?.get().unwrap_i32();
+    let get = instance.exports.get_native_function::<i32, i32>("get")?;
+    assert_eq!(get.call(state)?, b's' as i32);
+    assert_eq!(get.call(state + 3)?, b't' as i32);
+
+    // Copying the memory needs the new module to have the same exports.
+    let mut instance = Instance::new(&v1, &imports! {})?;
+    instance.hot_swap(&v2, &imports! {}, migrate_memory)?;
+    let get = instance.exports.get_native_function::<i32, i32>("get")?;
+    assert_eq!(get.call(20)?, b's' as i32);
+    let error = instance
+        .hot_swap(&v1, &imports! {}, migrate_memory)
+        .unwrap_err();
+    assert!(matches!(error, HotSwapError::IncompatibleLayout(_)));
+    // The failed swap left the instance unchanged.
+    assert!(instance.exports.get_function("restore").is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn linker() -> Result<()> {
+    let store = Store::default();
+    let counter = Module::new(
+        &store,
+        r#"(module
+    (global $count (export "count") (mut i32) (i32.const 0))
+    (func (export "increment") (global.set $count (i32.add (global.get $count) (i32.const 1)))))"#,
+    )?;
+    let app = Module::new(
+        &store,
+        r#"(module
+    (import "counter" "increment" (func $increment))
+    (import "env" "step" (global $step i32))
+    (func (export "run") (result i32) (call $increment) (global.get $step)))"#,
+    )?;
+
+    let mut linker = Linker::new(&store);
+    linker.define("env", "step", Global::new(&store, Value::I32(1)))?;
+    let counter = linker.module("counter", &counter)?;
+    assert!(linker.get("counter", "count").is_some());
+    assert_eq!(linker.iter().count(), 3);
+
+    let app = linker.instantiate(&app)?;
+    let run = app.exports.get_native_function::<(), i32>("run")?;
+    assert_eq!(run.call()?, 1);
+    assert_eq!(counter.exports.get_global("count")?.get(), Value::I32(1));
+
+    // Shadowing.
+    let error = linker
+        .define("env", "step", Global::new(&store, Value::I32(2)))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "`env`.`step` is already defined");
+    linker.allow_shadowing(true);
+    linker.define("env", "step", Global::new(&store, Value::I32(2)))?;
+    let app = Module::new(
+        &store,
+        r#"(module
+    (import "env" "step" (global $step i32))
+    (func (export "step") (result i32) (global.get $step)))"#,
+    )?;
+    let app = linker.instantiate(&app)?;
+    let step = app.exports.get_native_function::<(), i32>("step")?;
+    assert_eq!(step.call()?, 2);
+
+    // Missing imports are reported by the instantiation.
+    let app = Module::new(&store, r#"(module (import "env" "missing" (func)))"#)?;
+    assert!(matches!(
+        linker.instantiate(&app),
+        Err(LinkerError::Instantiation(InstantiationError::Link(_)))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn import_call_recording() -> Result<()> {
+    let mut store = Store::default();
+    let wat = r#"(module
+    (import "env" "add" (func $add (param i32 i32) (result i32)))
+    (import "env" "double" (func $double (param i32) (result i32)))
+    (import "env" "legacy" (func))
+    (func (export "run") (param i32) (result i32)
+        (call $double (call $add (local.get 0) (i32.const 1)))))"#;
+    let imports = |store: &Store| {
+        imports! {
+            "env" => {
+                "add" => Function::new_native(store, |a: i32, b: i32| a + b),
+                "double" => Function::new(
+                    store,
+                    &FunctionType::new(vec![Type::I32], vec![Type::I32]),
+                    |args| Ok(vec![Value::I32(args[0].unwrap_i32() * 2)]),
+                ),
+                "legacy" => Function::new_native(store, || {}),
+            },
+        }
+    };
+
+    let module = Module::new(&store, wat)?;
+    assert!(module.import_calls().is_none());
+
+    store.set_import_call_recording(true);
+    let module = Module::new(&store, wat)?;
+    for _ in 0..2 {
+        let instance = Instance::new(&module, &imports(&store))?;
+        let run = instance.exports.get_native_function::<i32, i32>("run")?;
+        assert_eq!(run.call(20)?, 42);
+    }
+    let calls = module
+        .import_calls()
+        .unwrap()
+        .into_iter()
+        .map(|count| (count.name, count.calls))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        vec![
+            ("add".to_string(), 2),
+            ("double".to_string(), 2),
+            ("legacy".to_string(), 0),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn store_policy() -> Result<()> {
+    let wat = r#"(module $plugin
+    (import "env" "log" (func))
+    (import "env" "exec" (func))
+    (import "wasi_snapshot_preview1" "fd_write" (func)))"#;
+    let mut policy = StorePolicy::new();
+    policy
+        .allow("env", "log")
+        .allow_namespace("wasi_snapshot_preview1");
+    assert!(policy.is_allowed("env", "log"));
+    assert!(policy.is_allowed("wasi_snapshot_preview1", "anything"));
+    assert!(!policy.is_allowed("env", "exec"));
+
+    let mut store = Store::default();
+    let imports = imports! {
+        "env" => {
+            "log" => Function::new_native(&store, || {}),
+            "exec" => Function::new_native(&store, || {}),
+        },
+        "wasi_snapshot_preview1" => {
+            "fd_write" => Function::new_native(&store, || {}),
+        },
+    };
+    store.set_policy(policy.clone());
+    let module = Module::new(&store, wat)?;
+    let violations = policy.check(&module);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].module_name.as_deref(), Some("plugin"));
+    assert_eq!(
+        (
+            violations[0].namespace.as_str(),
+            violations[0].name.as_str()
+        ),
+        ("env", "exec")
+    );
+    match Instance::new(&module, &imports) {
+        Err(InstantiationError::Link(LinkError::Import(
+            namespace,
+            name,
+            ImportError::NotAllowed(_),
+        ))) => {
+            assert_eq!((namespace.as_str(), name.as_str()), ("env", "exec"));
+        }
+        _ => panic!("the instantiation should fail"),
+    }
+
+    // Dry run.
+    policy.dry_run(true);
+    store.set_policy(policy.clone());
+    let module = Module::new(&store, wat)?;
+    Instance::new(&module, &imports)?;
+    assert_eq!(policy.violations(), violations);
+
+    Ok(())
+}
+
+#[test]
+fn module_analyze() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "env" "log" (func $log (param i32)))
+    (type $t (func (result i32)))
+    (table 2 funcref)
+    (elem (i32.const 0) $one $two)
+    (memory 2 4)
+    (func $one (result i32)
+        (i32.const 1))
+    (func $two (result i32)
+        (i32.add (call $one) (i32.const 1)))
+    (func (export "indirect") (param i32) (result i32)
+        (call_indirect (type $t) (local.get 0)))
+    (func $fact (export "fact") (param i64) (result i64)
+        (if (result i64) (i64.eqz (local.get 0))
+            (then (i64.const 1))
+            (else (i64.mul (local.get 0) (call $fact (i64.sub (local.get 0) (i64.const 1)))))))
+    (func (export "sum") (param i32 i32) (result i32) (local i32)
+        (call $log (local.get 0))
+        (i32.add (i32.add (local.get 0) (local.get 1)) (local.get 2))))"#;
+    let analysis = Module::analyze(&store, wat)?;
+    assert_eq!(analysis.functions.len(), 5);
+    assert_eq!(analysis.initial_memory, Bytes(2 * WASM_PAGE_SIZE));
+    assert_eq!(analysis.maximum_memory, Some(Bytes(4 * WASM_PAGE_SIZE)));
+
+    let two = analysis.functions[LocalFunctionIndex::from_u32(1)];
+    assert_eq!(
+        two,
+        FunctionAnalysis {
+            locals: 0,
+            max_operand_stack: 2,
+            max_call_depth: Some(2),
+            max_stack_values: Some(3),
+        }
+    );
+    assert_eq!(
+        analysis.export("sum"),
+        Some(&FunctionAnalysis {
+            locals: 3,
+            max_operand_stack: 2,
+            max_call_depth: Some(2),
+            max_stack_values: Some(5),
+        })
+    );
+    // The indirect call can target `$one` or `$two`.
+    let indirect = analysis.export("indirect").unwrap();
+    assert_eq!(indirect.max_call_depth, Some(3));
+    assert_eq!(indirect.max_stack_values, Some(5));
+    // The recursion is unbounded.
+    let fact = analysis.export("fact").unwrap();
+    assert_eq!(fact.locals, 1);
+    assert_eq!(fact.max_call_depth, None);
+    assert_eq!(fact.max_stack_values, None);
+
+    Ok(())
+}
+
+#[test]
+fn module_tree_shake() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (type $t (func (result i32)))
+    (table 1 funcref)
+    (elem (i32.const 0) $library)
+    (memory (export "memory") 1)
+    (data (i32.const 0) "library data")
+    (func $library (result i32)
+        (call_indirect (type $t) (i32.const 0)))
+    (func $double (param i32) (result i32)
+        (i32.mul (local.get 0) (i32.const 2)))
+    (func (export "run") (param i32) (result i32)
+        (call $double (local.get 0)))
+    (func (export "library") (result i32)
+        (call $library)))"#;
+    let original = wat2wasm(wat.as_bytes())?;
+    let wasm = Module::tree_shake(&original, &["run"])?;
+    assert!(wasm.len() < original.len());
+
+    let module = Module::new(&store, &wasm)?;
+    assert_eq!(
+        module
+            .exports()
+            .map(|e| e.name().to_string())
+            .collect::<Vec<_>>(),
+        vec!["memory".to_string(), "run".to_string()]
+    );
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(21)?, 42);
+    // The memory is exported, so its data is kept.
+    let memory = instance.exports.get_memory("memory")?;
+    assert_eq!(&unsafe { memory.data_unchecked() }[..12], b"library data");
+
+    assert!(Module::tree_shake(&original, &["missing"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn split_module() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (memory (export "memory") 1)
+    (global $calls (mut i32) (i32.const 0))
+    (func $count
+        (global.set $calls (i32.add (global.get $calls) (i32.const 1))))
+    (func (export "run") (param i32) (result i32)
+        (call $count)
+        (if (result i32) (local.get 0)
+            (then (call $cold (local.get 0) (i32.const 2)))
+            (else (global.get $calls))))
+    (func $cold (param i32 i32) (result i32)
+        (call $count)
+        (i32.store (i32.const 0) (global.get $calls))
+        (i32.mul (local.get 0) (local.get 1))))"#;
+    let (primary, secondary) = SplitModule::split(wat, &["run"])?;
+    assert!(Module::validate(&store, &primary).is_ok());
+
+    let split = SplitModule::from_parts(&store, &primary, secondary)?;
+    let instance = split.instantiate(&imports! {})?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    let loaded = instance.exports.get_global("__wasmer_split_loaded")?;
+    assert_eq!(run.call(0)?, 1);
+    assert_eq!(loaded.get(), Value::I32(0));
+
+    // The cold function shares the memory and globals of the primary
+    // module.
+    assert_eq!(run.call(21)?, 42);
+    assert_eq!(loaded.get(), Value::I32(1));
+    let memory = instance.exports.get_memory("memory")?;
+    assert_eq!(memory.view::<i32>()[0].get(), 3);
+    assert_eq!(run.call(0)?, 4);
+
+    assert!(SplitModule::split(wat, &["missing"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn debug_module() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (memory 1)
+    (table 1 funcref)
+    (elem (i32.const 0) $square)
+    (type $unary (func (param i32) (result i32)))
+    (start $init)
+    (func $init (i32.store (i32.const 0) (i32.const 7)))
+    (func $square (param i32) (result i32)
+        (local i32)
+        (local.set 1 (i32.mul (local.get 0) (local.get 0)))
+        (local.get 1))
+    (func (export "run") (param i32) (result i32)
+        (call_indirect (type $unary) (local.get 0) (i32.const 0))))"#;
+    let module = DebugModule::new(&store, wat)?;
+    assert_eq!(module.function_index("square"), Some(1));
+    assert_eq!(module.function_index("missing"), None);
+
+    let entries = Arc::new(std::sync::Mutex::new(vec![]));
+    let recorded = entries.clone();
+    let instance = module.instantiate(&imports! {}, move |entry: &FunctionEntry| {
+        let frames = entry.frames();
+        assert_eq!(frames[0].func_index(), entry.function_index());
+        recorded
+            .lock()
+            .unwrap()
+            .push((entry.function_index(), frames.len()));
+        Ok(())
+    })?;
+    // The start function runs before the hook is installed.
+    assert!(entries.lock().unwrap().is_empty());
+    let memory = DebugModule::memory(&instance).expect("the memory is exported");
+    assert_eq!(memory.view::<i32>()[0].get(), 7);
+
+    // The table of the module still works, and the locals are kept.
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(6)?, 36);
+    assert_eq!(*entries.lock().unwrap(), vec![(2, 1), (1, 2)]);
+
+    // An error of the hook traps in the guest.
+    let instance = module.instantiate(&imports! {}, |_: &FunctionEntry| {
+        Err(RuntimeError::new("stopped"))
+    })?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(6).unwrap_err().message(), "stopped");
+
+    Ok(())
+}
+
+#[test]
+fn module_to_wat() -> Result<()> {
+    let mut store = Store::default();
+    let wat = r#"(module $calc
+    (func $double (export "double") (param $value i32) (result i32)
+        (i32.mul (local.get $value) (i32.const 2))))"#;
+    // The binaries are only kept when asked for.
+    assert!(Module::new(&store, wat)?.to_wat().is_err());
+
+    store.set_keep_module_binaries(true);
+    let module = Module::new(&store, wat)?;
+    let printed = module.to_wat()?;
+    assert!(printed.contains("$calc"));
+    assert!(printed.contains("$double"));
+    assert!(printed.contains("$value"));
+
+    // The printed module round-trips.
+    let module = Module::new(&store, &printed)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let double = instance.exports.get_native_function::<i32, i32>("double")?;
+    assert_eq!(double.call(21)?, 42);
+
+    // Deserialized modules don't keep their binary.
+    let module = unsafe { Module::deserialize(&store, &module.serialize()?)? };
+    assert!(module.to_wat().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn fibers() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "wasmer_fibers" "fiber_new" (func $new (param i32 i32) (result i32)))
+    (import "wasmer_fibers" "fiber_resume" (func $resume (param i32 i32) (result i32)))
+    (import "wasmer_fibers" "fiber_suspend" (func $suspend (param i32) (result i32)))
+    (import "wasmer_fibers" "fiber_status" (func $status (param i32) (result i32)))
+    (import "wasmer_fibers" "fiber_drop" (func $drop (param i32)))
+    (table (export "__indirect_function_table") 3 funcref)
+    (elem (i32.const 0) $twice $trap $bad)
+    (func $twice (param i32) (result i32)
+        (call $suspend (i32.mul (local.get 0) (i32.const 2))))
+    (func $trap (param i32) (result i32) (unreachable))
+    (func $bad (param i64))
+    (func (export "new") (param i32 i32) (result i32) (call $new (local.get 0) (local.get 1)))
+    (func (export "resume") (param i32 i32) (result i32) (call $resume (local.get 0) (local.get 1)))
+    (func (export "suspend") (param i32) (result i32) (call $suspend (local.get 0)))
+    (func (export "status") (param i32) (result i32) (call $status (local.get 0)))
+    (func (export "drop") (param i32) (call $drop (local.get 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let fibers = Fibers::new();
+    let exports = fibers.exports(&store);
+    let instance = Instance::new(&module, &imports! { "wasmer_fibers" => exports })?;
+    fibers.set_function_table(
+        instance
+            .exports
+            .get_table("__indirect_function_table")?
+            .clone(),
+    );
+    let new = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("new")?;
+    let resume = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("resume")?;
+    let suspend = instance
+        .exports
+        .get_native_function::<i32, i32>("suspend")?;
+    let status = instance.exports.get_native_function::<i32, i32>("status")?;
+    let drop = instance.exports.get_native_function::<i32, ()>("drop")?;
+
+    // A fiber suspending once, then returning the resume value.
+    let fiber = new.call(0, 21)?;
+    assert_eq!(fibers.status(fiber as u32)?, FiberStatus::Suspended);
+    assert_eq!(resume.call(fiber, 0)?, 42);
+    assert_eq!(status.call(fiber)?, FiberStatus::Suspended as i32);
+    assert_eq!(resume.call(fiber, 7)?, 7);
+    assert_eq!(status.call(fiber)?, FiberStatus::Finished as i32);
+    assert!(resume.call(fiber, 0).is_err());
+    drop.call(fiber)?;
+    assert!(status.call(fiber).is_err());
+
+    // Dropping a suspended fiber.
+    let fiber = new.call(0, 1)?;
+    assert_eq!(resume.call(fiber, 0)?, 2);
+    drop.call(fiber)?;
+    assert!(fibers.status(fiber as u32).is_err());
+
+    // Traps.
+    let fiber = new.call(1, 0)?;
+    assert!(resume.call(fiber, 0).is_err());
+    assert_eq!(fibers.status(fiber as u32)?, FiberStatus::Finished);
+    assert!(suspend.call(0).is_err());
+    assert!(new.call(2, 0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn module_vmctx_layout() -> Result<()> {
+    assert_vmcontext_layout_version!(1);
+    let store = Store::default();
+    let wat = r#"(module
+    (import "env" "counter" (global i32))
+    (memory 2)
+    (global i64 (i64.const 42)))"#;
+    let module = Module::new(&store, wat)?;
+    let layout = module.vmctx_layout();
+    assert!(layout.is_version(VMCONTEXT_LAYOUT_VERSION));
+    assert_eq!(layout.imported_memories.len(), 0);
+    assert_eq!(layout.memories.len(), 1);
+    assert_eq!(layout.imported_globals.len(), 1);
+    assert_eq!(layout.globals.len(), 1);
+    assert!(layout.globals[0] < layout.size);
+
+    let counter = Global::new(&store, Value::I32(7));
+    let instance = Instance::new(&module, &imports! { "env" => { "counter" => counter } })?;
+    unsafe {
+        let vmctx = instance.vmctx_ptr() as *const u8;
+        let memory = vmctx.add(layout.memories[0] as usize);
+        let length = *(memory.add(layout.memory_definition_current_length as usize) as *const u32);
+        assert_eq!(length, 2 * WASM_PAGE_SIZE as u32);
+        let global = *(vmctx.add(layout.globals[0] as usize) as *const *const i64);
+        assert_eq!(*global, 42);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn module_import_errors() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+    (import "env" "missing" (func (param i32)))
+    (import "env" "sum" (func (param i32 i32) (result i32)))
+    (import "env" "memory" (memory 2 3)))"#;
+    let module = Module::new(&store, wat)?;
+    let imports = imports! {
+        "env" => {
+            "sum" => Function::new_native(&store, |a: i64, b: i64| a + b),
+            "memory" => Memory::new(&store, MemoryType::new(1, None, false))?,
+        },
+    };
+    let errors = match Instance::new(&module, &imports) {
+        Err(InstantiationError::Link(LinkError::Imports(errors))) => errors,
+        _ => panic!("the instantiation should fail"),
+    };
+    let names = errors
+        .iter()
+        .map(|(namespace, name, _)| (namespace.as_str(), name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![("env", "missing"), ("env", "sum"), ("env", "memory")]
+    );
+    assert!(matches!(errors[0].2, ImportError::UnknownImport(_)));
+    assert_eq!(
+        errors[1].2.to_string(),
+        "incompatible import type. Expected function [I32, I32] -> [I32] but received function [I64, I64] -> [I64]"
+    );
+    assert_eq!(
+        errors[2].2.to_string(),
+        "incompatible import type. Expected memory not shared (2 pages..3 pages) but received memory not shared (1 pages..)"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn module_disassemble() -> Result<()> {
+    let store = Store::default();
+    let wat = r#"(module
+        (import "env" "f" (func))
+        (func (export "add") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            i32.add))"#;
+    let module = Module::new(&store, wat)?;
+
+    assert!(module.disassemble(0).is_none());
+    assert!(module.disassemble(2).is_none());
+
+    let disassembly = module.disassemble(1).unwrap();
+    assert_eq!(disassembly.function_index(), 1);
+    assert!(!disassembly.code().is_empty());
+    assert!(!disassembly.mappings().is_empty());
+    for mapping in disassembly.mappings() {
+        assert!(mapping.code_offset + mapping.code_len <= disassembly.code().len());
+        assert_eq!(
+            disassembly.wasm_offset(mapping.code_offset),
+            Some(mapping.wasm_offset)
+        );
+    }
+    assert_eq!(disassembly.wasm_offset(disassembly.code().len()), None);
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! These tests set the signal handler policy of the whole process, so they
+//! run in their own binary.
+
+use anyhow::Result;
+use wasmer::*;
+
+#[cfg(unix)]
+extern "C" fn embedder_handler(_signum: libc::c_int) {}
+
+/// The handler of `SIGSEGV` installed in the process.
+#[cfg(unix)]
+fn segv_handler() -> libc::sighandler_t {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, std::ptr::null(), &mut action),
+            0
+        );
+        action.sa_sigaction
+    }
+}
+
+#[test]
+fn traps_are_caught_while_the_wasm_code_runs() -> Result<()> {
+    set_signal_handler_policy(SignalHandlerPolicy::Scoped)?;
+    #[cfg(unix)]
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = embedder_handler as libc::sighandler_t;
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"(module
+            (memory 1)
+            (func (export "unreachable")
+                (unreachable))
+            (func (export "out_of_bounds") (result i32)
+                (i32.load (i32.const 0x10000))))"#,
+    )?;
+    let instance = Instance::new(&module, &imports! {})?;
+
+    let error = instance
+        .exports
+        .get_function("unreachable")?
+        .call(&[])
+        .unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    let error = instance
+        .exports
+        .get_function("out_of_bounds")?
+        .call(&[])
+        .unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+
+    // the embedder's handler is back once no wasm code runs anymore
+    #[cfg(unix)]
+    assert_eq!(segv_handler(), embedder_handler as libc::sighandler_t);
+
+    // and the policy can't change anymore
+    assert!(set_signal_handler_policy(SignalHandlerPolicy::Chain).is_err());
+    Ok(())
+}
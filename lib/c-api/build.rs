@@ -387,5 +387,6 @@ fn exclude_items_from_wasm_c_api(builder: Builder) -> Builder {
         .exclude_item("wasm_module_set_name")
         .exclude_item("wasmer_compiler_t")
         .exclude_item("wasmer_engine_t")
+        .exclude_item("wasmer_instance_vmctx")
         .exclude_item("wat2wasm")
 }
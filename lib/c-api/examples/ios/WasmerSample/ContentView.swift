@@ -0,0 +1,18 @@
+import SwiftUI
+
+struct ContentView: View {
+    @State private var message = ""
+
+    var body: some View {
+        Text(message)
+            .padding()
+            .onAppear {
+                var result: Int32 = 0
+                if wasmer_sample_sum(40, 2, &result) {
+                    message = "sum(40, 2) = \(result)"
+                } else {
+                    message = "The WebAssembly module failed, see the logs"
+                }
+            }
+    }
+}
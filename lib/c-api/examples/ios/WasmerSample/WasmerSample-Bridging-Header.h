@@ -0,0 +1,2 @@
+// Exposes the C glue calling the WebAssembly module to Swift.
+#include "WasmerSum.h"
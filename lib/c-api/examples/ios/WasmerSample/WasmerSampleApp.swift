@@ -0,0 +1,10 @@
+import SwiftUI
+
+@main
+struct WasmerSampleApp: App {
+    var body: some Scene {
+        WindowGroup {
+            ContentView()
+        }
+    }
+}
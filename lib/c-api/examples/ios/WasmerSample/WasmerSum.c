@@ -0,0 +1,79 @@
+#include "WasmerSum.h"
+
+#include "wasmer_wasm.h"
+#include "wasm.h"
+#include "sum.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+
+static wasm_engine_t* engine = NULL;
+static wasm_store_t* store = NULL;
+static wasm_instance_t* instance = NULL;
+static wasm_func_t* sum = NULL;
+
+static void print_wasmer_error(const char* context) {
+    int error_len = wasmer_last_error_length();
+    char* error_str = (char*) malloc(error_len);
+    wasmer_last_error_message(error_str, error_len);
+    fprintf(stderr, "%s: %s\n", context, error_str);
+    free(error_str);
+}
+
+// Instantiates the module once, on the first call.
+static bool instantiate(void) {
+    if (sum) {
+        return true;
+    }
+
+    wasm_config_t* config = wasm_config_new();
+    wasm_config_set_engine(config, OBJECT_FILE);
+    engine = wasm_engine_new_with_config(config);
+    store = wasm_store_new(engine);
+
+    wasm_module_t* module = wasmer_object_file_engine_new(store, "sum.wasm");
+    if (!module) {
+        print_wasmer_error("Failed to create the module");
+        return false;
+    }
+
+    // `sum.wat` has no imports.
+    instance = wasm_instance_new(store, module, NULL, NULL);
+    wasm_module_delete(module);
+    if (!instance) {
+        print_wasmer_error("Failed to instantiate the module");
+        return false;
+    }
+
+    wasm_extern_vec_t exports;
+    wasm_instance_exports(instance, &exports);
+    if (exports.size < 1 || !(sum = wasm_extern_as_func(exports.data[0]))) {
+        fprintf(stderr, "The module doesn't export `sum`\n");
+        return false;
+    }
+    return true;
+}
+
+bool wasmer_sample_sum(int32_t a, int32_t b, int32_t* result) {
+    if (!instantiate()) {
+        return false;
+    }
+
+    wasm_val_t args_val[2] = { WASM_I32_VAL(a), WASM_I32_VAL(b) };
+    wasm_val_t results_val[1] = { WASM_INIT_VAL };
+    wasm_val_vec_t args = WASM_ARRAY_VEC(args_val);
+    wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+
+    wasm_trap_t* trap = wasm_func_call(sum, &args, &results);
+    if (trap) {
+        wasm_message_t message;
+        wasm_trap_message(trap, &message);
+        fprintf(stderr, "`sum` trapped: %.*s\n", (int) message.size, message.data);
+        wasm_byte_vec_delete(&message);
+        wasm_trap_delete(trap);
+        return false;
+    }
+
+    *result = results_val[0].of.i32;
+    return true;
+}
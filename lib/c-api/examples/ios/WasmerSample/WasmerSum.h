@@ -0,0 +1,12 @@
+#ifndef WASMER_SUM_H
+#define WASMER_SUM_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+// Calls the `sum` function exported by `sum.wat`, compiled ahead of
+// time in `sum.o`. Returns false and logs the error if the module
+// couldn't be instantiated or the call trapped.
+bool wasmer_sample_sum(int32_t a, int32_t b, int32_t* result);
+
+#endif
@@ -27,7 +27,9 @@ pub unsafe extern "C" fn wasmer_emscripten_get_globals(
     match EmscriptenGlobals::new(module) {
         Ok(globals) => Box::into_raw(Box::new(globals)) as *mut wasmer_emscripten_globals_t,
         Err(msg) => {
-            update_last_error(CApiError { msg });
+            update_last_error(CApiError {
+                msg: msg.to_string(),
+            });
             return ptr::null_mut();
         }
     }
@@ -1,7 +1,9 @@
 //! Wasmer-specific extensions to the Wasm C API.
 
+use super::instance::wasm_instance_t;
 use super::module::wasm_module_t;
 use super::types::wasm_name_t;
+use std::ffi::c_void;
 use std::str;
 use std::sync::Arc;
 
@@ -33,3 +35,14 @@ pub unsafe extern "C" fn wasm_module_set_name(
         None => false,
     }
 }
+
+/// Gets the `vmctx` of an instance, which compiled functions take as
+/// their first argument.
+///
+/// This is used by the functions generated by `wasmer compile
+/// --object-file --export-c-symbols` to call exported functions
+/// directly.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_vmctx(instance: &wasm_instance_t) -> *mut c_void {
+    instance.inner.vmctx_ptr() as *mut c_void
+}
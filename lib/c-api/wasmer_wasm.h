@@ -167,6 +167,16 @@ void wasm_module_name(const wasm_module_t *module, wasm_name_t *out);
 
 bool wasm_module_set_name(wasm_module_t *module, const wasm_name_t *name);
 
+/**
+ * Gets the `vmctx` of an instance, which compiled functions take as
+ * their first argument.
+ *
+ * This is used by the functions generated by `wasmer compile
+ * --object-file --export-c-symbols` to call exported functions
+ * directly.
+ */
+void *wasmer_instance_vmctx(const wasm_instance_t *instance);
+
 /**
  * Gets the length in bytes of the last error if any.
  *
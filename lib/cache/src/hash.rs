@@ -1,9 +1,13 @@
 use crate::DeserializeError;
 use std::str::FromStr;
 use std::string::ToString;
+use wasmer::ModuleHash;
 
 /// A hash used as a key when loading and storing modules in a
 /// [`Cache`].
+///
+/// The hash of a binary is its [`ModuleHash`], so a module can be
+/// stored with the key `module.hash()`.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 // Hash is made up of a 32 byte array
 pub struct Hash([u8; 32]);
@@ -16,8 +20,7 @@ impl Hash {
 
     /// Creates a new hash from a slice of bytes.
     pub fn generate(bytes: &[u8]) -> Self {
-        let hash = blake3::hash(bytes);
-        Self::new(hash.into())
+        ModuleHash::generate(bytes).into()
     }
 
     pub(crate) fn into_array(self) -> [u8; 32] {
@@ -27,6 +30,12 @@ impl Hash {
     }
 }
 
+impl From<ModuleHash> for Hash {
+    fn from(hash: ModuleHash) -> Self {
+        Self::new(*hash.as_bytes())
+    }
+}
+
 impl ToString for Hash {
     /// Create the hexadecimal representation of the
     /// stored hash.
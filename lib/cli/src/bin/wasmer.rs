@@ -1,9 +1,15 @@
 use anyhow::Result;
+#[cfg(all(feature = "compiler", feature = "engine"))]
+use wasmer_cli::commands::Bench;
 #[cfg(all(feature = "object-file", feature = "compiler"))]
 use wasmer_cli::commands::CreateExe;
+#[cfg(feature = "compiler")]
+use wasmer_cli::commands::Dap;
 #[cfg(feature = "wast")]
 use wasmer_cli::commands::Wast;
-use wasmer_cli::commands::{Cache, Compile, Config, Inspect, Run, SelfUpdate, Validate};
+use wasmer_cli::commands::{Cache, Compile, Config, Inspect, Repl, Run, SelfUpdate, Validate};
+#[cfg(feature = "wat")]
+use wasmer_cli::commands::{Wasm2Wat, Wat2Wasm};
 use wasmer_cli::error::PrettyError;
 
 use structopt::{clap::ErrorKind, StructOpt};
@@ -28,6 +34,11 @@ enum WasmerCLIOptions {
     #[structopt(name = "compile")]
     Compile(Compile),
 
+    /// Benchmark a WebAssembly binary with one or more compilers
+    #[cfg(all(feature = "compiler", feature = "engine"))]
+    #[structopt(name = "bench")]
+    Bench(Bench),
+
     /// Compile a WebAssembly binary into a native executable
     #[cfg(all(feature = "object-file", feature = "compiler"))]
     #[structopt(name = "create-exe")]
@@ -46,6 +57,25 @@ enum WasmerCLIOptions {
     #[structopt(name = "inspect")]
     Inspect(Inspect),
 
+    /// Explore the exports of a WebAssembly file interactively
+    #[structopt(name = "repl")]
+    Repl(Repl),
+
+    /// Serve the Debug Adapter Protocol to debug a WebAssembly file
+    #[cfg(feature = "compiler")]
+    #[structopt(name = "dap")]
+    Dap(Dap),
+
+    /// Convert a WebAssembly text file to a binary
+    #[cfg(feature = "wat")]
+    #[structopt(name = "wat2wasm")]
+    Wat2Wasm(Wat2Wasm),
+
+    /// Print a WebAssembly binary in the text format
+    #[cfg(feature = "wat")]
+    #[structopt(name = "wasm2wat")]
+    Wasm2Wat(Wasm2Wat),
+
     /// Run spec testsuite
     #[cfg(feature = "wast")]
     #[structopt(name = "wast")]
@@ -60,10 +90,19 @@ impl WasmerCLIOptions {
             Self::Cache(cache) => cache.execute(),
             Self::Validate(validate) => validate.execute(),
             Self::Compile(compile) => compile.execute(),
+            #[cfg(all(feature = "compiler", feature = "engine"))]
+            Self::Bench(bench) => bench.execute(),
             #[cfg(all(feature = "object-file", feature = "compiler"))]
             Self::CreateExe(create_exe) => create_exe.execute(),
             Self::Config(config) => config.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Repl(repl) => repl.execute(),
+            #[cfg(feature = "compiler")]
+            Self::Dap(dap) => dap.execute(),
+            #[cfg(feature = "wat")]
+            Self::Wat2Wasm(wat2wasm) => wat2wasm.execute(),
+            #[cfg(feature = "wat")]
+            Self::Wasm2Wat(wasm2wat) => wasm2wat.execute(),
             #[cfg(feature = "wast")]
             Self::Wast(wast) => wast.execute(),
         }
@@ -82,8 +121,8 @@ fn main() {
     let args = std::env::args().collect::<Vec<_>>();
     let command = args.get(1);
     let options = match command.unwrap_or(&"".to_string()).as_ref() {
-        "cache" | "compile" | "config" | "create-exe" | "help" | "inspect" | "run"
-        | "self-update" | "validate" | "wast" => WasmerCLIOptions::from_args(),
+        "bench" | "cache" | "compile" | "config" | "create-exe" | "dap" | "help" | "inspect"
+        | "repl" | "run" | "self-update" | "validate" | "wast" => WasmerCLIOptions::from_args(),
         _ => {
             WasmerCLIOptions::from_iter_safe(args.iter()).unwrap_or_else(|e| {
                 match e.kind {
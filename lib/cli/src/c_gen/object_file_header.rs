@@ -1,7 +1,9 @@
 //! Generate a header file for the object file produced by the ObjectFile engine.
 
 use super::{generate_c, CStatement, CType};
+use std::collections::HashSet;
 use wasmer_compiler::{Symbol, SymbolRegistry};
+use wasmer_types::{ExportIndex, FunctionType, Type};
 use wasmer_vm::ModuleInfo;
 
 /// Helper functions to simplify the usage of the object file engine.
@@ -68,11 +70,81 @@ wasm_module_t* wasmer_object_file_engine_new(wasm_store_t* store, const char* wa
 }
 "#;
 
+/// The C type of a Wasm value type, if it has one.
+fn ctype_of(ty: &Type) -> Option<CType> {
+    match ty {
+        Type::I32 => Some(CType::I32),
+        Type::I64 => Some(CType::I64),
+        Type::F32 => Some(CType::F32),
+        Type::F64 => Some(CType::F64),
+        _ => None,
+    }
+}
+
+/// The C type of a compiled Wasm function, taking the `vmctx` as its
+/// first argument, if the function can be called from C: all its
+/// values must have a C type, and it must return at most one value.
+fn function_ctype(func_type: &FunctionType) -> Option<CType> {
+    let mut arguments = vec![CType::void_ptr()];
+    for param in func_type.params() {
+        arguments.push(ctype_of(param)?);
+    }
+    let return_value = match func_type.results() {
+        [] => None,
+        [result] => Some(Box::new(ctype_of(result)?)),
+        _ => return None,
+    };
+    Some(CType::Function {
+        arguments,
+        return_value,
+    })
+}
+
+/// Generate a C wrapper named `wasmer_export_{name}` calling the
+/// compiled function `function_name` directly with the `vmctx` of a
+/// `wasm_instance_t`.
+fn generate_export_wrapper(name: &str, function_name: &str, func_type: &FunctionType) -> String {
+    let mut return_type = String::new();
+    func_type
+        .results()
+        .first()
+        .and_then(ctype_of)
+        .unwrap_or_default()
+        .generate_c(&mut return_type);
+    let mut parameters = String::from("wasm_instance_t* instance");
+    let mut arguments = String::from("wasmer_instance_vmctx(instance)");
+    for (i, param) in func_type.params().iter().enumerate() {
+        parameters.push_str(", ");
+        ctype_of(param)
+            .unwrap()
+            .generate_c_with_name(&format!("arg{}", i), &mut parameters);
+        arguments.push_str(&format!(", arg{}", i));
+    }
+    let return_keyword = if func_type.results().is_empty() {
+        ""
+    } else {
+        "return "
+    };
+    format!(
+        "\n// Calls the exported function `{}` of `instance`.\n\
+         static inline {} wasmer_export_{}({}) {{\n\
+         \t{}{}({});\n\
+         }}\n",
+        name, return_type, name, parameters, return_keyword, function_name, arguments
+    )
+}
+
 /// Generate the header file that goes with the generated object file.
+///
+/// With `export_c_symbols`, a `wasmer_export_{name}` C function is
+/// also generated for each exported function that can be called from
+/// C, so it can be called without going through the Wasm C API. Traps
+/// raised by these calls are not caught.
 pub fn generate_header_file(
     module_info: &ModuleInfo,
     symbol_registry: &dyn SymbolRegistry,
     metadata_length: usize,
+    export_c_symbols: bool,
 ) -> String {
     let mut c_statements = vec![];
     c_statements.push(CStatement::LiteralConstant {
@@ -102,18 +174,19 @@ pub fn generate_header_file(
         .filter_map(|(f_index, sig_index)| {
             Some((module_info.local_func_index(f_index)?, sig_index))
         })
-        .map(|(function_local_index, _sig_index)| {
+        .map(|(function_local_index, sig_index)| {
             let function_name =
                 symbol_registry.symbol_to_name(Symbol::LocalFunction(function_local_index));
-            // TODO: figure out the signature here too
+            let ctype =
+                function_ctype(&module_info.signatures[*sig_index]).unwrap_or(CType::Function {
+                    arguments: vec![CType::Void],
+                    return_value: None,
+                });
             CStatement::Declaration {
                 name: function_name,
                 is_extern: false,
                 is_const: false,
-                ctype: CType::Function {
-                    arguments: vec![CType::Void],
-                    return_value: None,
-                },
+                ctype,
                 definition: None,
             }
         });
@@ -285,6 +358,44 @@ pub fn generate_header_file(
         value: HELPER_FUNCTIONS.to_string(),
     });
 
+    if export_c_symbols {
+        let mut names = HashSet::new();
+        for (name, export_index) in module_info.exports.iter() {
+            let function_index = match export_index {
+                ExportIndex::Function(function_index) => *function_index,
+                _ => continue,
+            };
+            let local_index = match module_info.local_func_index(function_index) {
+                Some(local_index) => local_index,
+                None => continue,
+            };
+            let func_type = &module_info.signatures[module_info.functions[function_index]];
+            if function_ctype(func_type).is_none() {
+                continue;
+            }
+            // Export names can be any string, so we keep them as valid C
+            // identifiers.
+            let c_name = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            if !names.insert(c_name.clone()) {
+                c_statements.push(CStatement::LiteralConstant {
+                    value: format!(
+                        "\n// The exported function {:?} has no wrapper: `wasmer_export_{}` is taken.\n",
+                        name, c_name
+                    ),
+                });
+                continue;
+            }
+            let name = c_name;
+            let function_name = symbol_registry.symbol_to_name(Symbol::LocalFunction(local_index));
+            c_statements.push(CStatement::LiteralConstant {
+                value: generate_export_wrapper(&name, &function_name, func_type),
+            });
+        }
+    }
+
     let inner_c = generate_c(&c_statements);
 
     // we wrap the inner C to work with C++ too
@@ -293,3 +404,159 @@ pub fn generate_header_file(
         inner_c
     )
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::FunctionIndex;
+
+    struct Names;
+
+    impl SymbolRegistry for Names {
+        fn symbol_to_name(&self, symbol: Symbol) -> String {
+            match symbol {
+                Symbol::LocalFunction(index) => format!("wasmer_function_{}", index.index()),
+                Symbol::Section(index) => format!("wasmer_section_{}", index.index()),
+                Symbol::FunctionCallTrampoline(index) => {
+                    format!("wasmer_trampoline_{}", index.index())
+                }
+                Symbol::DynamicFunctionTrampoline(index) => {
+                    format!("wasmer_dynamic_{}", index.index())
+                }
+            }
+        }
+
+        fn name_to_symbol(&self, _name: &str) -> Option<Symbol> {
+            None
+        }
+    }
+
+    /// A module importing its first function, and defining one function
+    /// for each of `signatures`.
+    fn module_info(signatures: Vec<FunctionType>, exports: &[(&str, usize)]) -> ModuleInfo {
+        let mut module_info = ModuleInfo::new();
+        let imported = module_info
+            .signatures
+            .push(FunctionType::new(vec![Type::I32], vec![]));
+        module_info.functions.push(imported);
+        module_info.num_imported_functions = 1;
+        for signature in signatures {
+            let sig_index = module_info.signatures.push(signature);
+            module_info.functions.push(sig_index);
+        }
+        for (name, index) in exports {
+            module_info.exports.insert(
+                name.to_string(),
+                ExportIndex::Function(FunctionIndex::new(*index)),
+            );
+        }
+        module_info
+    }
+
+    fn header(module_info: &ModuleInfo) -> String {
+        generate_header_file(module_info, &Names, 0, true)
+    }
+
+    fn function_c(func_type: FunctionType) -> Option<String> {
+        let mut w = String::new();
+        function_ctype(&func_type)?.generate_c(&mut w);
+        Some(w)
+    }
+
+    #[test]
+    fn function_ctypes() {
+        assert_eq!(
+            function_c(FunctionType::new(
+                vec![Type::I32, Type::F64],
+                vec![Type::I64]
+            ))
+            .as_deref(),
+            Some("long long (*)(void*, int, double)")
+        );
+        assert_eq!(
+            function_c(FunctionType::new(vec![], vec![])).as_deref(),
+            Some("void (*)(void*)")
+        );
+        // several results or values without a C type can't be called from C
+        assert_eq!(
+            function_c(FunctionType::new(vec![], vec![Type::I32, Type::I32])),
+            None
+        );
+        assert_eq!(
+            function_c(FunctionType::new(vec![Type::V128], vec![])),
+            None
+        );
+        assert_eq!(
+            function_c(FunctionType::new(vec![], vec![Type::ExternRef])),
+            None
+        );
+    }
+
+    #[test]
+    fn local_functions_have_their_prototypes() {
+        let header = header(&module_info(
+            vec![
+                FunctionType::new(vec![Type::I32, Type::I64], vec![Type::F32]),
+                FunctionType::new(vec![], vec![Type::I32, Type::I32]),
+            ],
+            &[],
+        ));
+        assert!(header.contains("float wasmer_function_0(void*, int, long long);\n"));
+        // the functions that can't be called from C are only referenced
+        assert!(header.contains("void wasmer_function_1(void);\n"));
+        assert!(!header.contains("wasmer_export_"));
+    }
+
+    #[test]
+    fn exported_functions_have_wrappers() {
+        let header = header(&module_info(
+            vec![
+                FunctionType::new(vec![Type::I32, Type::I64], vec![Type::I32]),
+                FunctionType::new(vec![Type::F64], vec![]),
+            ],
+            &[("add", 1), ("log", 2)],
+        ));
+        assert!(header.contains(
+            "static inline int wasmer_export_add(wasm_instance_t* instance, int arg0, long long arg1) {\n\
+             \treturn wasmer_function_0(wasmer_instance_vmctx(instance), arg0, arg1);\n\
+             }\n"
+        ));
+        assert!(header.contains(
+            "static inline void wasmer_export_log(wasm_instance_t* instance, double arg0) {\n\
+             \twasmer_function_1(wasmer_instance_vmctx(instance), arg0);\n\
+             }\n"
+        ));
+    }
+
+    #[test]
+    fn exported_functions_are_skipped_without_c_types() {
+        let header = header(&module_info(
+            vec![
+                FunctionType::new(vec![], vec![Type::I32, Type::I32]),
+                FunctionType::new(vec![Type::V128], vec![]),
+            ],
+            &[("pair", 1), ("simd", 2), ("imported", 0)],
+        ));
+        assert!(!header.contains("wasmer_export_"));
+    }
+
+    #[test]
+    fn export_names_are_c_identifiers() {
+        let header = header(&module_info(
+            vec![
+                FunctionType::new(vec![], vec![]),
+                FunctionType::new(vec![], vec![Type::I32]),
+            ],
+            &[("log-message", 1), ("log.message", 2), ("héllo", 1)],
+        ));
+        assert!(header.contains("void wasmer_export_log_message(wasm_instance_t* instance)"));
+        assert!(header.contains("void wasmer_export_h_llo(wasm_instance_t* instance)"));
+        // the second export with the same C identifier is only mentioned
+        assert_eq!(header.matches("wasmer_export_log_message(").count(), 1);
+        assert!(header.contains(
+            "// The exported function \"log.message\" has no wrapper: `wasmer_export_log_message` is taken.\n"
+        ));
+        assert!(!header.contains("return wasmer_function_1"));
+    }
+}
@@ -1,18 +1,33 @@
 //! The commands available in the Wasmer binary.
+#[cfg(all(feature = "compiler", feature = "engine"))]
+mod bench;
 mod cache;
 mod compile;
 mod config;
 #[cfg(all(feature = "object-file", feature = "compiler"))]
 mod create_exe;
+#[cfg(feature = "compiler")]
+mod dap;
 mod inspect;
+mod repl;
 mod run;
 mod self_update;
 mod validate;
 #[cfg(feature = "wast")]
 mod wast;
+#[cfg(feature = "wat")]
+mod wat;
 
+#[cfg(all(feature = "compiler", feature = "engine"))]
+pub use bench::*;
 #[cfg(all(feature = "object-file", feature = "compiler"))]
 pub use create_exe::*;
+#[cfg(feature = "compiler")]
+pub use dap::*;
 #[cfg(feature = "wast")]
 pub use wast::*;
-pub use {cache::*, compile::*, config::*, inspect::*, run::*, self_update::*, validate::*};
+#[cfg(feature = "wat")]
+pub use wat::*;
+pub use {
+    cache::*, compile::*, config::*, inspect::*, repl::*, run::*, self_update::*, validate::*,
+};
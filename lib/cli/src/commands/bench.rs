@@ -0,0 +1,213 @@
+//! Benchmarks a WebAssembly module with one or more compilers
+use crate::store::{CompilerType, StoreOptions};
+use crate::utils::parse_args;
+use anyhow::{bail, Context, Result};
+use serde::{Serialize, Serializer};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use wasmer::*;
+
+/// A module exporting an empty function, to measure the overhead of calls.
+const NOP_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: [] -> []
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x07, 0x07, 0x01, 0x03, b'n', b'o', b'p', 0x00, 0x00, // export section: "nop"
+    0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+];
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer bench` subcommand
+pub struct Bench {
+    /// File to benchmark. It must not have imports.
+    #[structopt(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Benchmark all the compilers included in this binary, instead of
+    /// the selected one.
+    #[structopt(long)]
+    all_compilers: bool,
+
+    /// How many times each step is measured.
+    #[structopt(long, default_value = "10")]
+    iterations: u32,
+
+    /// Exported function to measure the throughput of.
+    #[structopt(long = "invoke", short = "i")]
+    invoke: Option<String>,
+
+    /// Where to write the results, as JSON. They're printed otherwise.
+    #[structopt(long, short = "o", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    store: StoreOptions,
+
+    /// Arguments of the invoked function
+    #[structopt(name = "ARGS")]
+    args: Vec<String>,
+}
+
+/// The measurements of a module with one compiler and engine. The
+/// durations are the medians of the iterations, serialized in
+/// nanoseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    /// The compiler used.
+    pub compiler: String,
+    /// The engine used.
+    pub engine: String,
+    /// The time to compile the module.
+    #[serde(rename = "compile_ns", serialize_with = "serialize_nanos")]
+    pub compile: Duration,
+    /// The time to instantiate the module.
+    #[serde(rename = "instantiate_ns", serialize_with = "serialize_nanos")]
+    pub instantiate: Duration,
+    /// The time of a call to an empty function, from the host.
+    #[serde(rename = "call_overhead_ns", serialize_with = "serialize_nanos")]
+    pub call_overhead: Duration,
+    /// The time of a call to the invoked function, if any.
+    #[serde(rename = "invoke_ns", serialize_with = "serialize_optional_nanos")]
+    pub invoke: Option<Duration>,
+}
+
+fn serialize_nanos<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    // `u64` nanoseconds cover more than 500 years
+    serializer.serialize_u64(duration.as_nanos() as u64)
+}
+
+fn serialize_optional_nanos<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_nanos(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Bench {
+    /// Runs logic for the `bench` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to benchmark `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        if self.iterations == 0 {
+            bail!("At least one iteration is needed");
+        }
+        let stores = if self.all_compilers {
+            CompilerType::enabled()
+                .iter()
+                .map(|compiler| self.store.with_compiler(compiler))
+                .collect()
+        } else {
+            vec![self.store.clone()]
+        };
+        let wasm = std::fs::read(&self.path)?;
+
+        let results = stores
+            .iter()
+            .map(|store| self.bench(store, &wasm))
+            .collect::<Result<Vec<_>>>()?;
+        let mut json = serde_json::to_string_pretty(&results)?;
+        json.push('\n');
+
+        match &self.output {
+            Some(output) => std::fs::write(output, json)?,
+            None => print!("{}", json),
+        }
+        Ok(())
+    }
+
+    /// Measures the module with the store of `options`.
+    pub fn bench(&self, options: &StoreOptions, wasm: &[u8]) -> Result<BenchResult> {
+        let (store, engine_type, compiler_type) = options.get_store()?;
+        let imports = ImportObject::new();
+
+        let compile = self.median(|| {
+            Module::new(&store, wasm)?;
+            Ok(())
+        })?;
+        let module = Module::new(&store, wasm)?;
+        let instantiate = self.median(|| {
+            Instance::new(&module, &imports)?;
+            Ok(())
+        })?;
+        let instance = Instance::new(&module, &imports)?;
+
+        let nop = Instance::new(&Module::new(&store, NOP_MODULE)?, &imports)?;
+        let nop = nop.exports.get_function("nop")?.native::<(), ()>()?;
+        let call_overhead = self.median(|| Ok(nop.call()?))?;
+
+        let invoke = match &self.invoke {
+            Some(name) => {
+                let function = instance.exports.get_function(name)?;
+                let args = parse_args(function.ty(), &self.args)?;
+                Some(self.median(|| {
+                    function.call(&args)?;
+                    Ok(())
+                })?)
+            }
+            None => None,
+        };
+
+        Ok(BenchResult {
+            compiler: compiler_type.to_string(),
+            engine: engine_type.to_string(),
+            compile,
+            instantiate,
+            call_overhead,
+            invoke,
+        })
+    }
+
+    /// Returns the median time of `f`, over the iterations.
+    fn median(&self, mut f: impl FnMut() -> Result<()>) -> Result<Duration> {
+        let mut times = Vec::with_capacity(self.iterations as usize);
+        for _ in 0..self.iterations {
+            let start = Instant::now();
+            f()?;
+            times.push(start.elapsed());
+        }
+        times.sort();
+        Ok(times[times.len() / 2])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bench_result_to_json() {
+        let mut result = BenchResult {
+            compiler: "cranelift".to_string(),
+            engine: "jit".to_string(),
+            compile: Duration::from_millis(2),
+            instantiate: Duration::from_micros(3),
+            call_overhead: Duration::from_nanos(40),
+            invoke: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({
+                "compiler": "cranelift",
+                "engine": "jit",
+                "compile_ns": 2_000_000,
+                "instantiate_ns": 3_000,
+                "call_overhead_ns": 40,
+                "invoke_ns": 1_000_000_000,
+            })
+        );
+
+        result.invoke = None;
+        result.compiler = "a \"quoted\" name".to_string();
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["invoke_ns"], json!(null));
+        assert_eq!(value["compiler"], json!("a \"quoted\" name"));
+    }
+}
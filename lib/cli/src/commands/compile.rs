@@ -20,6 +20,10 @@ pub struct Compile {
     #[structopt(name = "HEADER PATH", long = "header", parse(from_os_str))]
     header_path: Option<PathBuf>,
 
+    /// Generate C functions in the header to call the exported functions directly
+    #[structopt(long = "export-c-symbols")]
+    export_c_symbols: bool,
+
     /// Compilation Target triple
     #[structopt(long = "target")]
     target_triple: Option<Triple>,
@@ -114,6 +118,7 @@ impl Compile {
                 module_info,
                 symbol_registry,
                 metadata_length,
+                self.export_c_symbols,
             );
 
             let header_path = self.header_path.as_ref().cloned().unwrap_or_else(|| {
@@ -92,6 +92,7 @@ impl CreateExe {
             module_info,
             symbol_registry,
             metadata_length,
+            false,
         );
 
         generate_header(header_file_src.as_bytes())?;
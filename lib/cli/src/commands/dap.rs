@@ -0,0 +1,814 @@
+//! Serves the Debug Adapter Protocol, so editors like VS Code can debug
+//! WebAssembly modules at the granularity of their functions
+use crate::store::StoreOptions;
+use crate::utils::{parse_args, parse_number, stub_imports};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use structopt::StructOpt;
+use wasmer::*;
+use wasmer_compiler::{DEBUG_ATTACHED_EXPORT, DEBUG_HOOK_SLOT_EXPORT};
+#[cfg(feature = "wasi")]
+use wasmer_wasi::{get_wasi_version, WasiError, WasiState};
+
+mod protocol;
+
+use protocol::{spawn_reader, Connection};
+
+/// The message of the trap aborting the guest when the client
+/// disconnects.
+const DISCONNECTED: &str = "the debugger disconnected";
+
+/// The only thread of the guest.
+const THREAD_ID: u64 = 1;
+
+/// The reference of the variables holding the exported globals.
+const GLOBALS_REFERENCE: u64 = 1;
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer dap` subcommand
+pub struct Dap {
+    /// The address to listen on for the debugger
+    #[structopt(long = "listen", name = "ADDRESS", default_value = "127.0.0.1:4711")]
+    listen: String,
+
+    #[structopt(flatten)]
+    store: StoreOptions,
+}
+
+impl Dap {
+    /// Runs logic for the `dap` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context("failed to serve the debug adapter")
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let (store, _engine_type, _compiler_type) = self.store.get_store()?;
+        let listener = TcpListener::bind(&self.listen)
+            .with_context(|| format!("Can't listen on `{}`", self.listen))?;
+        eprintln!("Waiting for a debugger on {}", listener.local_addr()?);
+        let (stream, address) = listener.accept()?;
+        eprintln!("Debugger connected from {}", address);
+        serve(store, stream)
+    }
+}
+
+/// Debugs the programs launched by the client connected with `stream`,
+/// until it disconnects.
+fn serve(store: Store, stream: TcpStream) -> Result<()> {
+    let adapter = Adapter {
+        store,
+        connection: Connection::new(stream.try_clone()?),
+        requests: spawn_reader(stream),
+        state: State::Configuring,
+        launch: None,
+        instance: None,
+        breakpoints: HashSet::new(),
+        step: Step::None,
+        pause: false,
+        frames: vec![],
+    };
+    Adapter::serve(&Rc::new(RefCell::new(adapter)))
+}
+
+/// Where the debugging session is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the program to launch and the breakpoints.
+    Configuring,
+    Running,
+    Stopped,
+    Terminated,
+    Disconnected,
+}
+
+/// Where the guest stops next, besides at the breakpoints. The guest
+/// only stops on entry of its functions, so stepping goes from a
+/// function entry to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    None,
+    /// On entry of the first function, when launched with `stopOnEntry`.
+    Entry,
+    /// On entry of the next function.
+    In,
+    /// On entry of the next function not deeper in the stack than the
+    /// given depth, once the function stopped at has returned.
+    Over(usize),
+    /// On entry of the next function less deep in the stack than the
+    /// given depth, once the caller of the function stopped at has
+    /// returned.
+    Out(usize),
+}
+
+/// The program launched by the debugger.
+struct Launch {
+    #[cfg_attr(not(feature = "wasi"), allow(dead_code))]
+    path: PathBuf,
+    module: DebugModule,
+    args: Vec<String>,
+    /// The function to call instead of `_start`.
+    invoke: Option<String>,
+}
+
+/// The state of a debugging session, shared with the hook of the guest.
+struct Adapter {
+    store: Store,
+    connection: Connection,
+    requests: Receiver<Value>,
+    state: State,
+    launch: Option<Launch>,
+    instance: Option<Instance>,
+    /// The indices of the functions to stop on entry of.
+    breakpoints: HashSet<u32>,
+    step: Step,
+    /// Whether the debugger asked to pause the guest.
+    pause: bool,
+    /// The stack of the guest while stopped, the innermost frame first.
+    frames: Vec<FrameInfo>,
+}
+
+impl Adapter {
+    /// Handles the requests of the debugger until it disconnects.
+    fn serve(adapter: &Rc<RefCell<Self>>) -> Result<()> {
+        loop {
+            let state = adapter.borrow().state;
+            match state {
+                State::Disconnected => return Ok(()),
+                State::Running => Self::run(adapter)?,
+                _ => adapter.borrow_mut().wait()?,
+            }
+        }
+    }
+
+    /// Runs the launched program until it exits.
+    fn run(adapter: &Rc<RefCell<Self>>) -> Result<()> {
+        let hook_adapter = adapter.clone();
+        let started = adapter
+            .borrow_mut()
+            .start(move |entry: &FunctionEntry| hook_adapter.borrow_mut().enter(entry));
+        let (function, args) = match started {
+            Ok(started) => started,
+            Err(e) => {
+                let mut adapter = adapter.borrow_mut();
+                adapter.connection.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{:#}\n", e) }),
+                )?;
+                return adapter.terminate(1);
+            }
+        };
+        // The hook borrows the adapter while the guest runs.
+        let result = function.call(&args);
+        adapter.borrow_mut().finish(result)
+    }
+
+    /// Instantiates the launched program with the `hook`, returning the
+    /// function to run and its arguments.
+    fn start<H>(&mut self, hook: H) -> Result<(Function, Vec<Val>)>
+    where
+        H: FnMut(&FunctionEntry) -> Result<(), RuntimeError> + 'static,
+    {
+        let launch = self
+            .launch
+            .as_ref()
+            .ok_or_else(|| anyhow!("No program was launched"))?;
+        let module = launch.module.module();
+        #[cfg(feature = "wasi")]
+        {
+            if launch.invoke.is_none() && get_wasi_version(module, false).is_some() {
+                let program_name = launch
+                    .path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+                let mut wasi_env = WasiState::new(program_name).args(&launch.args).finalize()?;
+                let imports = wasi_env.import_object(module)?;
+                let instance = launch.module.instantiate(&imports, hook)?;
+                wasi_env.set_memory(instance.exports.get_memory("memory")?.clone());
+                let start = instance.exports.get_function("_start")?.clone();
+                self.instance = Some(instance);
+                return Ok((start, vec![]));
+            }
+        }
+        let imports = stub_imports(module)?;
+        let instance = launch.module.instantiate(&imports, hook)?;
+        let function = instance
+            .exports
+            .get_function(launch.invoke.as_deref().unwrap_or("_start"))?
+            .clone();
+        let args = parse_args(function.ty(), &launch.args)?;
+        self.instance = Some(instance);
+        Ok((function, args))
+    }
+
+    /// Reports the end of the program, stopping on its trap if any.
+    fn finish(&mut self, result: Result<Box<[Val]>, RuntimeError>) -> Result<()> {
+        if self.state == State::Disconnected {
+            return Ok(());
+        }
+        let error = match result {
+            Ok(_) => return self.terminate(0),
+            Err(error) => error,
+        };
+        #[cfg(feature = "wasi")]
+        let error = match error.downcast::<WasiError>() {
+            Ok(WasiError::Exit(exit_code)) => return self.terminate(exit_code as i64),
+            Ok(error) => RuntimeError::new(error.to_string()),
+            Err(error) => error,
+        };
+        self.frames = error.trace().to_vec();
+        self.stop("exception", Some(&error.message()))?;
+        while self.state == State::Stopped {
+            self.wait()?;
+        }
+        self.terminate(1)
+    }
+
+    fn terminate(&mut self, exit_code: i64) -> Result<()> {
+        if self.state == State::Disconnected {
+            return Ok(());
+        }
+        self.state = State::Terminated;
+        self.instance = None;
+        self.frames.clear();
+        self.connection
+            .event("exited", json!({ "exitCode": exit_code }))?;
+        self.connection.event("terminated", json!({}))
+    }
+
+    /// The hook called on entry of each function of the guest.
+    fn enter(&mut self, entry: &FunctionEntry) -> Result<(), RuntimeError> {
+        if let Err(e) = self.stop_at(entry) {
+            // The debugger can't be reached anymore.
+            eprintln!("Lost the debugger: {:#}", e);
+            self.state = State::Disconnected;
+        }
+        if self.state == State::Disconnected {
+            return Err(RuntimeError::new(DISCONNECTED));
+        }
+        Ok(())
+    }
+
+    /// Stops the guest on entry of a function if the debugger asked to,
+    /// until it resumes.
+    fn stop_at(&mut self, entry: &FunctionEntry) -> Result<()> {
+        // Handles the requests sent while the guest runs, like `pause`.
+        loop {
+            match self.requests.try_recv() {
+                Ok(request) => self.handle(&request)?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.state = State::Disconnected;
+                    return Ok(());
+                }
+            }
+        }
+        if self.state == State::Disconnected {
+            return Ok(());
+        }
+        let reason = match self.stop_reason(entry) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+        self.frames = entry.frames();
+        self.stop(reason, None)?;
+        while self.state == State::Stopped {
+            self.wait()?;
+        }
+        Ok(())
+    }
+
+    /// Returns why the guest stops on `entry`, if it does.
+    fn stop_reason(&self, entry: &FunctionEntry) -> Option<&'static str> {
+        if self.pause {
+            return Some("pause");
+        }
+        if self.breakpoints.contains(&entry.function_index()) {
+            return Some("breakpoint");
+        }
+        // The depth of the stack is only computed when stepping over or
+        // out, as it walks the stack.
+        match self.step {
+            Step::None => None,
+            Step::Entry => Some("entry"),
+            Step::In => Some("step"),
+            Step::Over(depth) if entry.frames().len() <= depth => Some("step"),
+            Step::Out(depth) if entry.frames().len() < depth => Some("step"),
+            Step::Over(_) | Step::Out(_) => None,
+        }
+    }
+
+    fn stop(&mut self, reason: &str, text: Option<&str>) -> Result<()> {
+        self.state = State::Stopped;
+        self.step = Step::None;
+        self.pause = false;
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = text.into();
+        }
+        self.connection.event("stopped", body)
+    }
+
+    /// Waits for a request of the debugger and handles it.
+    fn wait(&mut self) -> Result<()> {
+        match self.requests.recv() {
+            Ok(request) => self.handle(&request),
+            Err(_) => {
+                self.state = State::Disconnected;
+                Ok(())
+            }
+        }
+    }
+
+    fn handle(&mut self, request: &Value) -> Result<()> {
+        let command = request["command"].as_str().unwrap_or_default();
+        match self.execute(command, &request["arguments"]) {
+            Ok(body) => self.connection.respond(request, body)?,
+            Err(e) => return self.connection.fail(request, &format!("{:#}", e)),
+        }
+        match command {
+            // The breakpoints are resolved against the launched program.
+            "launch" => self.connection.event("initialized", json!({})),
+            "disconnect" => {
+                self.state = State::Disconnected;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Executes a request, returning the body of its response.
+    fn execute(&mut self, command: &str, arguments: &Value) -> Result<Value> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => {
+                let count = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+                let unverified = json!({
+                    "verified": false,
+                    "message": "Only function breakpoints are supported",
+                });
+                Ok(json!({ "breakpoints": vec![unverified; count] }))
+            }
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "setFunctionBreakpoints" => self.set_function_breakpoints(arguments),
+            "configurationDone" => {
+                if self.state != State::Configuring || self.launch.is_none() {
+                    bail!("No program was launched");
+                }
+                self.state = State::Running;
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(arguments),
+            "scopes" => {
+                self.expect_stopped()?;
+                Ok(json!({
+                    "scopes": [{
+                        "name": "Globals",
+                        "variablesReference": GLOBALS_REFERENCE,
+                        "expensive": false,
+                    }]
+                }))
+            }
+            "variables" => self.variables(arguments),
+            "readMemory" => self.read_memory(arguments),
+            "continue" => {
+                self.resume(Step::None)?;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                self.resume(Step::Over(self.frames.len()))?;
+                Ok(json!({}))
+            }
+            "stepIn" => {
+                self.resume(Step::In)?;
+                Ok(json!({}))
+            }
+            "stepOut" => {
+                self.resume(Step::Out(self.frames.len()))?;
+                Ok(json!({}))
+            }
+            "pause" => {
+                if self.state != State::Running {
+                    bail!("The program isn't running");
+                }
+                self.pause = true;
+                Ok(json!({}))
+            }
+            "disconnect" => Ok(json!({})),
+            _ => bail!("The `{}` request isn't supported", command),
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value> {
+        if self.state != State::Configuring || self.launch.is_some() {
+            bail!("A program was already launched");
+        }
+        let path = arguments["program"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The `program` to debug is missing"))?;
+        let args = match arguments["args"].as_array() {
+            Some(args) => args
+                .iter()
+                .map(|arg| {
+                    arg.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| anyhow!("The `args` must be strings"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![],
+        };
+        let bytes = std::fs::read(path).with_context(|| format!("Can't read `{}`", path))?;
+        let module = DebugModule::new(&self.store, &bytes)?;
+        if arguments["stopOnEntry"].as_bool().unwrap_or(false) {
+            self.step = Step::Entry;
+        }
+        self.launch = Some(Launch {
+            path: PathBuf::from(path),
+            module,
+            args,
+            invoke: arguments["invoke"].as_str().map(str::to_string),
+        });
+        Ok(json!({}))
+    }
+
+    fn set_function_breakpoints(&mut self, arguments: &Value) -> Result<Value> {
+        let launch = self
+            .launch
+            .as_ref()
+            .ok_or_else(|| anyhow!("No program was launched"))?;
+        let requested: &[Value] = arguments["breakpoints"]
+            .as_array()
+            .map_or(&[], |breakpoints| breakpoints.as_slice());
+        self.breakpoints.clear();
+        let mut breakpoints = vec![];
+        for breakpoint in requested {
+            let name = breakpoint["name"].as_str().unwrap_or_default();
+            match function_index(&launch.module, name) {
+                Some(index) => {
+                    self.breakpoints.insert(index);
+                    breakpoints.push(json!({ "verified": true }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "message": format!("No function `{}`", name),
+                })),
+            }
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&self, arguments: &Value) -> Result<Value> {
+        self.expect_stopped()?;
+        let start = arguments["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match arguments["levels"].as_u64() {
+            Some(levels) if levels > 0 => levels as usize,
+            _ => self.frames.len(),
+        };
+        let frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, frame)| {
+                let name = match frame.function_name() {
+                    Some(name) => name.to_string(),
+                    None => format!("wasm-function[{}]", frame.func_index()),
+                };
+                json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("0x{:x}", frame.module_offset()),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({ "stackFrames": frames, "totalFrames": self.frames.len() }))
+    }
+
+    /// Returns the exported globals, with the `i32` ones referencing
+    /// the memory at their value.
+    fn variables(&self, arguments: &Value) -> Result<Value> {
+        self.expect_stopped()?;
+        let instance = match self.instance.as_ref() {
+            Some(instance) if arguments["variablesReference"] == GLOBALS_REFERENCE => instance,
+            _ => return Ok(json!({ "variables": [] })),
+        };
+        let mut variables = vec![];
+        for (name, export) in instance.exports.iter() {
+            let global = match export {
+                Extern::Global(global)
+                    if name != DEBUG_ATTACHED_EXPORT && name != DEBUG_HOOK_SLOT_EXPORT =>
+                {
+                    global
+                }
+                _ => continue,
+            };
+            let value = global.get();
+            let (text, ty) = describe_value(&value);
+            let mut variable = json!({
+                "name": name,
+                "value": text,
+                "type": ty,
+                "variablesReference": 0,
+            });
+            if let Val::I32(address) = value {
+                variable["memoryReference"] = format!("0x{:x}", address as u32).into();
+            }
+            variables.push(variable);
+        }
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value> {
+        self.expect_stopped()?;
+        let memory = self
+            .instance
+            .as_ref()
+            .and_then(DebugModule::memory)
+            .ok_or_else(|| anyhow!("The program has no memory"))?;
+        let reference = arguments["memoryReference"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The `memoryReference` is missing"))?;
+        let address = parse_number(reference)? as i64 + arguments["offset"].as_i64().unwrap_or(0);
+        let count = arguments["count"].as_u64().unwrap_or(0);
+        let size = memory.data_size();
+        if address < 0 || address as u64 >= size {
+            return Ok(json!({ "address": reference, "unreadableBytes": count }));
+        }
+        let start = address as u64;
+        let end = size.min(start.saturating_add(count));
+        let view = memory.view::<u8>();
+        let bytes = view[start as usize..end as usize]
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<_>>();
+        Ok(json!({
+            "address": format!("0x{:x}", start),
+            "data": base64::encode(&bytes),
+            "unreadableBytes": count - (end - start),
+        }))
+    }
+
+    fn resume(&mut self, step: Step) -> Result<()> {
+        self.expect_stopped()?;
+        self.step = step;
+        self.state = State::Running;
+        self.frames.clear();
+        Ok(())
+    }
+
+    fn expect_stopped(&self) -> Result<()> {
+        if self.state != State::Stopped {
+            bail!("The program isn't stopped");
+        }
+        Ok(())
+    }
+}
+
+/// Returns the index of the function named `name` in the name section,
+/// or as `wasm-function[N]`, or the index `name` itself.
+fn function_index(module: &DebugModule, name: &str) -> Option<u32> {
+    if let Some(index) = module.function_index(name) {
+        return Some(index);
+    }
+    let index = if name.starts_with("wasm-function[") && name.ends_with(']') {
+        &name["wasm-function[".len()..name.len() - 1]
+    } else {
+        name
+    };
+    let index = index.parse::<u32>().ok()?;
+    if (index as usize) < module.module().info().functions.len() {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+/// Returns the text of `value`, and the name of its type.
+fn describe_value(value: &Val) -> (String, &'static str) {
+    match value {
+        Val::I32(value) => (value.to_string(), "i32"),
+        Val::I64(value) => (value.to_string(), "i64"),
+        Val::F32(value) => (value.to_string(), "f32"),
+        Val::F64(value) => (value.to_string(), "f64"),
+        Val::V128(value) => (format!("0x{:032x}", value), "v128"),
+        Val::ExternRef(_) => ("externref".to_string(), "externref"),
+        Val::FuncRef(_) => ("funcref".to_string(), "funcref"),
+    }
+}
+
+#[cfg(all(test, feature = "compiler"))]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::io::Write;
+    use std::thread::{self, JoinHandle};
+
+    /// A client of an adapter served on a thread, over a loopback
+    /// connection.
+    struct Client {
+        stream: TcpStream,
+        messages: Receiver<Value>,
+        events: VecDeque<Value>,
+        seq: u64,
+        server: JoinHandle<Result<()>>,
+    }
+
+    impl Client {
+        fn connect() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            let (server_stream, _) = listener.accept().unwrap();
+            let server = thread::spawn(move || {
+                let (store, _, _) = StoreOptions::from_iter(&["dap"]).get_store()?;
+                serve(store, server_stream)
+            });
+            Self {
+                messages: spawn_reader(stream.try_clone().unwrap()),
+                stream,
+                events: VecDeque::new(),
+                seq: 0,
+                server,
+            }
+        }
+
+        /// Sends a request, returning its response and keeping the
+        /// events received meanwhile.
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            let content = serde_json::to_vec(&request).unwrap();
+            write!(self.stream, "Content-Length: {}\r\n\r\n", content.len()).unwrap();
+            self.stream.write_all(&content).unwrap();
+            loop {
+                let message = self.messages.recv().unwrap();
+                if message["type"] == "response" && message["request_seq"] == self.seq {
+                    return message;
+                }
+                self.events.push_back(message);
+            }
+        }
+
+        /// Returns the next event, which must be `name`.
+        fn event(&mut self, name: &str) -> Value {
+            let event = match self.events.pop_front() {
+                Some(event) => event,
+                None => self.messages.recv().unwrap(),
+            };
+            assert_eq!(event["event"], name, "unexpected event {}", event);
+            event["body"].clone()
+        }
+
+        /// Returns the names of the functions of the stack, the innermost
+        /// first.
+        fn stack(&mut self) -> Vec<String> {
+            let response = self.request("stackTrace", json!({ "threadId": THREAD_ID }));
+            response["body"]["stackFrames"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|frame| frame["name"].as_str().unwrap().to_string())
+                .collect()
+        }
+
+        fn disconnect(mut self) {
+            assert_eq!(self.request("disconnect", json!({}))["success"], true);
+            self.server.join().unwrap().unwrap();
+        }
+    }
+
+    /// Launches a program calling `middle` twice, which calls `leaf`
+    /// twice.
+    fn launch(client: &mut Client) -> tempfile::NamedTempFile {
+        let mut program = tempfile::NamedTempFile::new().unwrap();
+        program
+            .write_all(
+                br#"(module
+                    (func $leaf (result i32)
+                        (i32.const 1))
+                    (func $middle (result i32)
+                        (i32.add (call $leaf) (call $leaf)))
+                    (func $run (export "run") (result i32)
+                        (i32.add (call $middle) (call $middle))))"#,
+            )
+            .unwrap();
+        assert_eq!(client.request("initialize", json!({}))["success"], true);
+        let response = client.request(
+            "launch",
+            json!({ "program": program.path(), "invoke": "run" }),
+        );
+        assert_eq!(response["success"], true);
+        client.event("initialized");
+        program
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut client = Client::connect();
+        let _program = launch(&mut client);
+        let response = client.request(
+            "setFunctionBreakpoints",
+            json!({ "breakpoints": [
+                { "name": "middle" },
+                { "name": "wasm-function[0]" },
+                { "name": "missing" },
+            ] }),
+        );
+        let verified = response["body"]["breakpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|breakpoint| breakpoint["verified"].as_bool().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(verified, vec![true, true, false]);
+        client.request("configurationDone", json!({}));
+
+        // Both calls of `middle` and the four calls of `leaf`.
+        let expected = [
+            vec!["middle", "run"],
+            vec!["leaf", "middle", "run"],
+            vec!["leaf", "middle", "run"],
+            vec!["middle", "run"],
+            vec!["leaf", "middle", "run"],
+            vec!["leaf", "middle", "run"],
+        ];
+        for stack in expected.iter() {
+            assert_eq!(client.event("stopped")["reason"], "breakpoint");
+            assert_eq!(client.stack(), *stack);
+            client.request("continue", json!({ "threadId": THREAD_ID }));
+        }
+        assert_eq!(client.event("exited")["exitCode"], 0);
+        client.event("terminated");
+        client.disconnect();
+    }
+
+    #[test]
+    fn steps() {
+        let mut client = Client::connect();
+        let _program = launch(&mut client);
+        client.request(
+            "setFunctionBreakpoints",
+            json!({ "breakpoints": [{ "name": "middle" }] }),
+        );
+        client.request("configurationDone", json!({}));
+        assert_eq!(client.event("stopped")["reason"], "breakpoint");
+        assert_eq!(client.stack(), vec!["middle", "run"]);
+        client.request("setFunctionBreakpoints", json!({ "breakpoints": [] }));
+
+        client.request("stepIn", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("stopped")["reason"], "step");
+        assert_eq!(client.stack(), vec!["leaf", "middle", "run"]);
+
+        // The next call of `leaf`, by the same `middle`.
+        client.request("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("stopped")["reason"], "step");
+        assert_eq!(client.stack(), vec!["leaf", "middle", "run"]);
+
+        // The second call of `middle`, once the first one returned.
+        client.request("stepOut", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("stopped")["reason"], "step");
+        assert_eq!(client.stack(), vec!["middle", "run"]);
+
+        client.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("exited")["exitCode"], 0);
+        client.event("terminated");
+        client.disconnect();
+    }
+
+    #[test]
+    fn requests_need_a_stopped_program() {
+        let mut client = Client::connect();
+        let response = client.request("configurationDone", json!({}));
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], "No program was launched");
+        let _program = launch(&mut client);
+        let response = client.request("stackTrace", json!({ "threadId": THREAD_ID }));
+        assert_eq!(response["message"], "The program isn't stopped");
+        let response = client.request("unknown", json!({}));
+        assert_eq!(response["message"], "The `unknown` request isn't supported");
+        client.disconnect();
+    }
+}
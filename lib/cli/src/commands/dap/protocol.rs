@@ -0,0 +1,204 @@
+//! The framing of the messages of the Debug Adapter Protocol, JSON
+//! objects preceded by a `Content-Length` header.
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const CONTENT_LENGTH: &str = "Content-Length:";
+
+/// The largest message accepted from the client, so a bogus length
+/// doesn't allocate the memory of the adapter away.
+const MAX_CONTENT_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Reads the messages of the client on a thread, so they can be polled
+/// while the guest runs. The channel is closed when the client
+/// disconnects.
+pub fn spawn_reader(stream: TcpStream) -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Reads a message, or `None` at the end of the stream.
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if header.starts_with(CONTENT_LENGTH) {
+            length = Some(header[CONTENT_LENGTH.len()..].trim().parse::<usize>()?);
+        }
+    }
+    let length = match length {
+        Some(length) => length,
+        None => bail!("A message has no `Content-Length` header"),
+    };
+    if length > MAX_CONTENT_LENGTH {
+        bail!(
+            "A message of {} bytes is larger than the limit of {} bytes",
+            length,
+            MAX_CONTENT_LENGTH
+        );
+    }
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content)?;
+    Ok(Some(
+        serde_json::from_slice(&content).context("A message isn't valid JSON")?,
+    ))
+}
+
+/// The sending half of a connection to a client.
+pub struct Connection {
+    stream: TcpStream,
+    seq: u64,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream, seq: 0 }
+    }
+
+    /// Responds successfully to `request`.
+    pub fn respond(&mut self, request: &Value, body: Value) -> Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    /// Responds to `request` with an error.
+    pub fn fail(&mut self, request: &Value, message: &str) -> Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    /// Sends the `event`.
+    pub fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        let content = serde_json::to_vec(&message)?;
+        write!(self.stream, "Content-Length: {}\r\n\r\n", content.len())?;
+        self.stream.write_all(&content)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+
+    /// Returns both ends of a loopback connection.
+    fn connected() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn messages_are_framed() {
+        let (client, server) = connected();
+        let mut connection = Connection::new(server);
+        let request = json!({ "seq": 7, "command": "threads" });
+        connection
+            .respond(&request, json!({ "threads": [] }))
+            .unwrap();
+        connection
+            .event("stopped", json!({ "reason": "step" }))
+            .unwrap();
+        connection.fail(&request, "failure").unwrap();
+
+        let mut reader = BufReader::new(client);
+        let response = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            response,
+            json!({
+                "seq": 1,
+                "type": "response",
+                "request_seq": 7,
+                "command": "threads",
+                "success": true,
+                "body": { "threads": [] },
+            })
+        );
+        let event = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(event["seq"], 2);
+        assert_eq!(event["event"], "stopped");
+        assert_eq!(event["body"]["reason"], "step");
+        let failure = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(failure["seq"], 3);
+        assert_eq!(failure["success"], false);
+        assert_eq!(failure["message"], "failure");
+    }
+
+    #[test]
+    fn requests_are_read_until_the_client_disconnects() {
+        let (mut client, server) = connected();
+        let requests = spawn_reader(server);
+        // The other headers are ignored, and a message may be split
+        // across writes.
+        client
+            .write_all(b"Content-Type: application/json\r\nContent-Length: 12\r\n\r\n{\"seq\":")
+            .unwrap();
+        client.flush().unwrap();
+        client.write_all(b"1}   ").unwrap();
+        client
+            .write_all(b"Content-Length: 9\r\n\r\n{\"seq\":2}")
+            .unwrap();
+        assert_eq!(requests.recv().unwrap(), json!({ "seq": 1 }));
+        assert_eq!(requests.recv().unwrap(), json!({ "seq": 2 }));
+        drop(client);
+        assert!(requests.recv().is_err());
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let mut missing_length = Cursor::new(b"Content-Type: application/json\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut missing_length).is_err());
+
+        let mut too_large = Cursor::new(b"Content-Length: 1000000000\r\n\r\n".to_vec());
+        let error = read_message(&mut too_large).unwrap_err();
+        assert!(error.to_string().contains("larger than the limit"));
+
+        let mut not_json = Cursor::new(b"Content-Length: 3\r\n\r\nnah".to_vec());
+        assert!(read_message(&mut not_json).is_err());
+
+        let mut empty = Cursor::new(vec![]);
+        assert!(read_message(&mut empty).unwrap().is_none());
+    }
+}
@@ -52,6 +52,19 @@ impl Inspect {
         for f in module.imports().globals() {
             println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
         }
+        println!("Functions:");
+        let module_info = module.info();
+        for (index, signature) in module_info
+            .functions
+            .iter()
+            .skip(module_info.num_imported_functions)
+        {
+            let ty = &module_info.signatures[*signature];
+            match module.function_name(index.as_u32()) {
+                Some(name) => println!("  {} (#{}): {}", name, index.as_u32(), ty),
+                None => println!("  #{}: {}", index.as_u32(), ty),
+            }
+        }
         println!("Exports:");
         println!("  Functions:");
         for f in module.exports().functions() {
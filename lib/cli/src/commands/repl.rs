@@ -0,0 +1,310 @@
+//! Explores the exports of a WebAssembly module interactively
+use crate::store::StoreOptions;
+use crate::utils::{parse_args, parse_number, parse_value, stub_imports};
+use anyhow::{anyhow, bail, Context, Result};
+use colored::*;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmer::*;
+
+const HELP: &str = "\
+Commands:
+  exports                            List the exports of the module
+  call <function> [args...]          Call an exported function
+  globals                            List the exported globals with their values
+  get <global>                       Print the value of an exported global
+  set <global> <value>               Set the value of an exported mutable global
+  peek <memory> <offset> <length>    Print a range of an exported memory
+  poke <memory> <offset> <bytes...>  Write bytes to an exported memory
+  help                               Print this help
+  quit                               Leave the REPL
+
+Offsets, lengths and bytes can be given in hexadecimal with a `0x` prefix.";
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer repl` subcommand
+pub struct Repl {
+    /// File to explore
+    #[structopt(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    #[structopt(flatten)]
+    store: StoreOptions,
+}
+
+impl Repl {
+    /// Runs logic for the `repl` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to explore `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let (store, _engine_type, _compiler_type) = self.store.get_store()?;
+        let module_contents = std::fs::read(&self.path)?;
+        let module = Module::new(&store, &module_contents)?;
+        let imports = stub_imports(&module)?;
+        let instance =
+            Instance::new(&module, &imports).with_context(|| "Can't instantiate the module")?;
+        let session = Session { instance };
+
+        println!(
+            "Exploring `{}`. Type `help` for the commands.",
+            self.path.display()
+        );
+        let stdin = io::stdin();
+        session.run(stdin.lock(), &mut io::stdout(), &mut io::stderr())
+    }
+}
+
+/// An instance explored by the REPL.
+struct Session {
+    instance: Instance,
+}
+
+impl Session {
+    /// Evaluates the commands read from `input` until its end or a
+    /// `quit`, writing their output to `out` and their errors to `err`.
+    fn run(&self, input: impl BufRead, out: &mut dyn Write, err: &mut dyn Write) -> Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            match self.eval(&line, out) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => writeln!(err, "{}: {:#}", "error".red().bold(), e)?,
+            }
+        }
+    }
+
+    /// Evaluates a command, writing its output to `out`, and returns
+    /// whether the REPL goes on.
+    fn eval(&self, line: &str, out: &mut dyn Write) -> Result<bool> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => {}
+            ["help"] => writeln!(out, "{}", HELP)?,
+            ["quit"] | ["exit"] => return Ok(false),
+            ["exports"] => self.exports(out)?,
+            ["call", name, args @ ..] => self.call(name, args, out)?,
+            ["globals"] => self.globals(out)?,
+            ["get", name] => {
+                let global = self.instance.exports.get_global(name)?;
+                writeln!(out, "{}", format_value(&global.get()))?;
+            }
+            ["set", name, value] => {
+                let global = self.instance.exports.get_global(name)?;
+                global.set(parse_value(global.ty().ty, value)?)?;
+            }
+            ["peek", name, offset, length] => {
+                self.peek(name, parse_number(offset)?, parse_number(length)?, out)?
+            }
+            ["poke", name, offset, bytes @ ..] if !bytes.is_empty() => {
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| {
+                        u8::try_from(parse_number(byte)?)
+                            .map_err(|_| anyhow!("`{}` isn't a byte", byte))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                self.poke(name, parse_number(offset)?, &bytes)?
+            }
+            _ => bail!(
+                "Unknown command `{}`. Type `help` for the commands.",
+                line.trim()
+            ),
+        }
+        Ok(true)
+    }
+
+    fn exports(&self, out: &mut dyn Write) -> Result<()> {
+        for export in self.instance.module().exports() {
+            writeln!(out, "  \"{}\": {}", export.name(), export.ty())?;
+        }
+        Ok(())
+    }
+
+    fn call(&self, name: &str, args: &[&str], out: &mut dyn Write) -> Result<()> {
+        let function = self.instance.exports.get_function(name)?;
+        let args = parse_args(function.ty(), args)?;
+        let results = function.call(&args)?;
+        let results = results.iter().map(format_value).collect::<Vec<_>>();
+        if !results.is_empty() {
+            writeln!(out, "{}", results.join(", "))?;
+        }
+        Ok(())
+    }
+
+    fn globals(&self, out: &mut dyn Write) -> Result<()> {
+        for (name, export) in self.instance.exports.iter() {
+            if let Extern::Global(global) = export {
+                writeln!(
+                    out,
+                    "  \"{}\": {} = {}",
+                    name,
+                    global.ty(),
+                    format_value(&global.get())
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&self, name: &str, offset: u64, length: u64, out: &mut dyn Write) -> Result<()> {
+        let memory = self.instance.exports.get_memory(name)?;
+        let range = memory_range(memory, offset, length)?;
+        let view = memory.view::<u8>();
+        let bytes = view[range]
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<_>>();
+        for (line, chunk) in bytes.chunks(16).enumerate() {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let text = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            writeln!(
+                out,
+                "{:08x}  {:<47}  {}",
+                offset + line as u64 * 16,
+                hex,
+                text
+            )?;
+        }
+        Ok(())
+    }
+
+    fn poke(&self, name: &str, offset: u64, bytes: &[u8]) -> Result<()> {
+        let memory = self.instance.exports.get_memory(name)?;
+        let range = memory_range(memory, offset, bytes.len() as u64)?;
+        let view = memory.view::<u8>();
+        for (cell, byte) in view[range].iter().zip(bytes) {
+            cell.set(*byte);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the range of `length` bytes at `offset` in `memory`, if in
+/// bounds.
+fn memory_range(memory: &Memory, offset: u64, length: u64) -> Result<std::ops::Range<usize>> {
+    let size = memory.data_size();
+    match offset.checked_add(length) {
+        Some(end) if end <= size => Ok(offset as usize..end as usize),
+        _ => bail!(
+            "The range of {} bytes at 0x{:x} is out of the memory of {} bytes",
+            length,
+            offset,
+            size
+        ),
+    }
+}
+
+fn format_value(value: &Val) -> String {
+    match value {
+        Val::I32(value) => format!("{}: i32", value),
+        Val::I64(value) => format!("{}: i64", value),
+        Val::F32(value) => format!("{}: f32", value),
+        Val::F64(value) => format!("{}: f64", value),
+        Val::V128(value) => format!("0x{:032x}: v128", value),
+        Val::ExternRef(_) => "externref".to_string(),
+        Val::FuncRef(_) => "funcref".to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "compiler"))]
+mod test {
+    use super::*;
+
+    const WAT: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 16) "hello")
+        (global (export "counter") (mut i32) (i32.const 7))
+        (global (export "answer") i64 (i64.const 42))
+        (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))"#;
+
+    fn session() -> Session {
+        let (store, _, _) = StoreOptions::from_iter(&["repl"]).get_store().unwrap();
+        let module = Module::new(&store, WAT).unwrap();
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        Session { instance }
+    }
+
+    /// Runs the commands of `input`, returning the output and the errors.
+    fn run(session: &Session, input: &str) -> (String, String) {
+        let (mut out, mut err) = (vec![], vec![]);
+        session.run(input.as_bytes(), &mut out, &mut err).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn calls_and_globals() {
+        let session = session();
+        let (out, err) = run(
+            &session,
+            "call add 40 2\nget counter\nset counter 9\nget counter\nglobals\n",
+        );
+        assert_eq!(err, "");
+        assert_eq!(
+            out,
+            "> 42: i32\n\
+             > 7: i32\n\
+             > > 9: i32\n\
+             >   \"counter\": I32 (mutable) = 9: i32\n  \"answer\": I64 (constant) = 42: i64\n\
+             > "
+        );
+    }
+
+    #[test]
+    fn memory_commands() {
+        let session = session();
+        let (out, err) = run(&session, "poke memory 0x10 0x48\npeek memory 16 5\n");
+        assert_eq!(err, "");
+        assert_eq!(
+            out,
+            format!("> > 00000010  {:<47}  Hello\n> ", "48 65 6c 6c 6f")
+        );
+
+        let (_, err) = run(&session, "peek memory 0xffff 2\npoke memory 0 256\n");
+        assert_eq!(err.lines().count(), 2);
+        assert!(err.contains("out of the memory of 65536 bytes"));
+        assert!(err.contains("`256` isn't a byte"));
+    }
+
+    #[test]
+    fn errors_and_quit() {
+        let session = session();
+        let (out, err) = run(&session, "frobnicate\nset answer 1\nquit\ncall add 1 2\n");
+        assert_eq!(out, "> > > ");
+        assert_eq!(err.lines().count(), 2);
+        assert!(err.contains("Unknown command `frobnicate`"));
+
+        // the input may end without a `quit`
+        let (out, err) = run(&session, "exports");
+        assert_eq!(err, "");
+        assert!(out.contains("\"add\": function [I32, I32] -> [I32]"));
+        assert!(out.ends_with("> "));
+    }
+}
@@ -64,6 +64,19 @@ pub struct Run {
     #[structopt(long = "debug", short = "d")]
     debug: bool,
 
+    /// Run under a debugger: register the compiled functions with gdb
+    /// and lldb (JIT engine only), and print the PID of the process
+    #[structopt(long = "debugger")]
+    debugger: bool,
+
+    /// Wait for a debugger to attach before running the module
+    #[structopt(long = "pause", requires = "debugger")]
+    pause: bool,
+
+    /// Stop in the attached debugger at the frame raising a trap
+    #[structopt(long = "break-on-trap")]
+    break_on_trap: bool,
+
     /// Application arguments
     #[structopt(name = "--", multiple = true)]
     args: Vec<String>,
@@ -90,7 +103,11 @@ impl Run {
     }
 
     fn inner_execute(&self) -> Result<()> {
+        set_break_on_trap(self.break_on_trap);
         let module = self.get_module()?;
+        if self.debugger {
+            self.wait_for_debugger()?;
+        }
         // Do we want to invoke a function?
         if let Some(ref invoke) = self.invoke {
             let imports = imports! {};
@@ -110,7 +127,7 @@ impl Run {
         {
             use wasmer_emscripten::{
                 generate_emscripten_env, is_emscripten_module, run_emscripten_instance, EmEnv,
-                EmscriptenGlobals,
+                EmscriptenError, EmscriptenGlobals, SourceMap,
             };
             // TODO: refactor this
             if is_emscripten_module(&module) {
@@ -122,7 +139,7 @@ impl Run {
                 let mut instance = Instance::new(&module, &import_object)
                     .with_context(|| "Can't instantiate emscripten module")?;
 
-                run_emscripten_instance(
+                let result = run_emscripten_instance(
                     &mut instance,
                     &mut em_env,
                     &mut emscripten_globals,
@@ -134,7 +151,29 @@ impl Run {
                     self.args.iter().map(|arg| arg.as_str()).collect(),
                     None,   //run.em_entrypoint.clone(),
                     vec![], //mapped_dirs,
-                )?;
+                );
+                match result.map_err(|e| e.downcast::<EmscriptenError>()) {
+                    Ok(()) | Err(Ok(EmscriptenError::Exit(0))) => {}
+                    // We should exit with the provided exit code
+                    Err(Ok(EmscriptenError::Exit(code))) => std::process::exit(code),
+                    Err(Ok(error)) => return Err(anyhow!("{}", error)),
+                    Err(Err(e)) => {
+                        // Show the positions in the original sources if the
+                        // module references a source map.
+                        let base_dir = self
+                            .path
+                            .parent()
+                            .unwrap_or_else(|| std::path::Path::new("."));
+                        return Err(match SourceMap::for_module(&module, base_dir) {
+                            Ok(Some(source_map)) => anyhow!("{}", source_map.format_error(&e)),
+                            Ok(None) => e.into(),
+                            Err(message) => {
+                                warning!("{}", message);
+                                e.into()
+                            }
+                        });
+                    }
+                }
                 return Ok(());
             }
         }
@@ -142,22 +181,32 @@ impl Run {
         // If WASI is enabled, try to execute it with it
         #[cfg(feature = "wasi")]
         {
+            let program_name = self
+                .command_name
+                .clone()
+                .or_else(|| {
+                    self.path
+                        .file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+                })
+                .unwrap_or_default();
             let wasi_version = Wasi::get_version(&module);
             if wasi_version.is_some() {
-                let program_name = self
-                    .command_name
-                    .clone()
-                    .or_else(|| {
-                        self.path
-                            .file_name()
-                            .map(|f| f.to_string_lossy().to_string())
-                    })
-                    .unwrap_or_default();
                 return self
                     .wasi
                     .execute(module, program_name, self.args.clone())
                     .with_context(|| "WASI execution failed");
             }
+            // Emscripten's standalone modules mix WASI and Emscripten imports
+            #[cfg(feature = "emscripten")]
+            {
+                if wasmer_emscripten::is_standalone_emscripten_module(&module) {
+                    return self
+                        .wasi
+                        .execute_standalone_emscripten(module, program_name, self.args.clone())
+                        .with_context(|| "Emscripten standalone execution failed");
+                }
+            }
         }
 
         // Try to instantiate the wasm file, with no provided imports
@@ -183,13 +232,20 @@ impl Run {
         #[cfg(feature = "jit")]
         {
             if wasmer_engine_jit::JITArtifact::is_deserializable(&contents) {
-                let engine = wasmer_engine_jit::JIT::headless().engine();
+                let engine = wasmer_engine_jit::JIT::headless()
+                    .debug_info(self.debugger)
+                    .engine();
                 let store = Store::new(&engine);
                 let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
                 return Ok(module);
             }
         }
-        let (store, engine_type, compiler_type) = self.store.get_store()?;
+        let store_options = if self.debugger {
+            self.store.with_debug_info()
+        } else {
+            self.store.clone()
+        };
+        let (store, engine_type, compiler_type) = store_options.get_store()?;
         #[cfg(feature = "cache")]
         let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
             self.get_module_from_cache(&store, &contents, &engine_type, &compiler_type)
@@ -212,6 +268,22 @@ impl Run {
         Ok(module)
     }
 
+    /// Prints the PID of the process for a debugger to attach to, and
+    /// waits for it with `--pause`.
+    fn wait_for_debugger(&self) -> Result<()> {
+        let pid = std::process::id();
+        eprintln!("Running `{}` in process {}", self.path.display(), pid);
+        if self.pause {
+            eprintln!(
+                "Attach a debugger to process {}, then press Enter to start",
+                pid
+            );
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "cache")]
     fn get_module_from_cache(
         &self,
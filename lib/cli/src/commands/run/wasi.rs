@@ -1,8 +1,12 @@
+#[cfg(feature = "emscripten")]
+use crate::runner::run_standalone_emscripten;
 use crate::utils::{parse_envvar, parse_mapdir};
+#[cfg(feature = "emscripten")]
+use anyhow::bail;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
-use wasmer::{Instance, Module};
-use wasmer_wasi::{get_wasi_version, WasiError, WasiState, WasiVersion};
+use wasmer::Module;
+use wasmer_wasi::{get_wasi_version, WasiEnv, WasiState, WasiVersion};
 
 use structopt::StructOpt;
 
@@ -37,6 +41,39 @@ impl Wasi {
 
     /// Helper function for executing Wasi from the `Run` command.
     pub fn execute(&self, module: Module, program_name: String, args: Vec<String>) -> Result<()> {
+        let mut wasi_env = self.prepare_env(program_name, args)?;
+        let exit_code = wasi_env
+            .run_command(&module)
+            .with_context(|| "failed to run WASI `_start` function")?;
+        if exit_code != 0 {
+            // We should exit with the provided exit code
+            std::process::exit(exit_code as _);
+        }
+        Ok(())
+    }
+
+    /// Helper function for executing a module built by emscripten with
+    /// `-s STANDALONE_WASM` from the `Run` command, with the imports of
+    /// emscripten along the WASI ones.
+    #[cfg(feature = "emscripten")]
+    pub fn execute_standalone_emscripten(
+        &self,
+        module: Module,
+        program_name: String,
+        args: Vec<String>,
+    ) -> Result<()> {
+        let mut wasi_env = self.prepare_env(program_name, args)?;
+        let status = run_standalone_emscripten(&module, &mut wasi_env)?;
+        match status.code {
+            Some(0) => Ok(()),
+            // We should exit with the provided exit code
+            Some(code) => std::process::exit(code),
+            None => bail!("The program {}", status),
+        }
+    }
+
+    /// Builds the environment of the program from the options.
+    fn prepare_env(&self, program_name: String, args: Vec<String>) -> Result<WasiEnv> {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
         let mut wasi_state_builder = WasiState::new(program_name);
@@ -54,29 +91,6 @@ impl Wasi {
             }
         }
 
-        let mut wasi_env = wasi_state_builder.finalize()?;
-        let import_object = wasi_env.import_object(&module)?;
-        let instance = Instance::new(&module, &import_object)?;
-
-        wasi_env.set_memory(instance.exports.get_memory("memory")?.clone());
-
-        let start = instance.exports.get_function("_start")?;
-        let result = start.call(&[]);
-
-        match result {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                let err: anyhow::Error = match err.downcast::<WasiError>() {
-                    Ok(WasiError::Exit(exit_code)) => {
-                        // We should exit with the provided exit code
-                        std::process::exit(exit_code as _);
-                    }
-                    Ok(err) => err.into(),
-                    Err(err) => err.into(),
-                };
-                Err(err)
-            }
-        }
-        .with_context(|| "failed to run WASI `_start` function")
+        Ok(wasi_state_builder.finalize()?)
     }
 }
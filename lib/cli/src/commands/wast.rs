@@ -1,14 +1,15 @@
 //! Runs a .wast WebAssembly test suites
 use crate::store::StoreOptions;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::path::PathBuf;
 use structopt::StructOpt;
-use wasmer_wast::Wast as WastSpectest;
+use wasmer_wast::{run_wasi_testsuite, run_wast_suite, Wast as WastSpectest};
 
 #[derive(Debug, StructOpt)]
 /// The options for the `wasmer wast` subcommand
 pub struct Wast {
-    /// Wast file to run
+    /// Wast file to run, or directory of wast files to run, such as the
+    /// spec test suite
     #[structopt(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
@@ -18,6 +19,10 @@ pub struct Wast {
     #[structopt(short, long)]
     /// A flag to indicate wast stop at the first error or continue.
     fail_fast: bool,
+
+    /// Run the WASI testsuite in the given directory instead of wast files.
+    #[structopt(long)]
+    wasi_testsuite: bool,
 }
 
 impl Wast {
@@ -28,6 +33,23 @@ impl Wast {
     }
     fn inner_execute(&self) -> Result<()> {
         let (store, _engine_name, _compiler_name) = self.store.get_store()?;
+        if self.wasi_testsuite || self.path.is_dir() {
+            let report = if self.wasi_testsuite {
+                run_wasi_testsuite(&store, &self.path)?
+            } else {
+                run_wast_suite(&self.path, |_| {
+                    let mut wast = WastSpectest::new_with_spectest(store.clone());
+                    wast.fail_fast = self.fail_fast;
+                    Ok(wast)
+                })?
+            };
+            eprintln!("{}", report);
+            if !report.is_success() {
+                bail!("tests failed");
+            }
+            eprintln!("Wast tests succeeded for `{}`.", self.path.display());
+            return Ok(());
+        }
         let mut wast = WastSpectest::new_with_spectest(store);
         wast.fail_fast = self.fail_fast;
         wast.run_file(&self.path).with_context(|| "tests failed")?;
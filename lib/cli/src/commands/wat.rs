@@ -0,0 +1,70 @@
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmer::*;
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer wat2wasm` subcommand
+pub struct Wat2Wasm {
+    /// File in the WebAssembly text format to convert
+    #[structopt(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output file, defaults to the input file with a `.wasm` extension
+    #[structopt(name = "OUTPUT PATH", short = "o", parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl Wat2Wasm {
+    /// Runs logic for the `wat2wasm` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to convert `{}`", self.path.display()))
+    }
+    fn inner_execute(&self) -> Result<()> {
+        let contents = std::fs::read(&self.path)?;
+        if is_wasm(&contents) {
+            bail!("the file is already a WebAssembly binary");
+        }
+        let wasm = wat2wasm(&contents)?;
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| self.path.with_extension("wasm"));
+        std::fs::write(&output, wasm)?;
+        eprintln!("✔ File converted successfully to `{}`.", output.display());
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer wasm2wat` subcommand
+pub struct Wasm2Wat {
+    /// WebAssembly binary to convert
+    #[structopt(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Output file, defaults to the standard output
+    #[structopt(name = "OUTPUT PATH", short = "o", parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl Wasm2Wat {
+    /// Runs logic for the `wasm2wat` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to convert `{}`", self.path.display()))
+    }
+    fn inner_execute(&self) -> Result<()> {
+        let contents = std::fs::read(&self.path)?;
+        if !is_wasm(&contents) {
+            bail!("the file is not a WebAssembly binary");
+        }
+        let wat = wasm2wat(&contents)?;
+        match &self.output {
+            Some(output) => std::fs::write(output, wat)?,
+            None => println!("{}", wat),
+        }
+        Ok(())
+    }
+}
@@ -23,6 +23,8 @@ pub mod error;
 pub mod c_gen;
 #[cfg(feature = "debug")]
 pub mod logging;
+#[cfg(feature = "wasi")]
+pub mod runner;
 pub mod store;
 pub mod suggestions;
 pub mod utils;
@@ -0,0 +1,351 @@
+//! Running a program whatever its ABI, WASI, Emscripten or none, with
+//! [`run_program`].
+//!
+//! This lives above both `wasmer-wasi` and `wasmer-emscripten`, so that
+//! neither depends on the other.
+
+use std::fmt;
+use std::path::PathBuf;
+use wasmer::{
+    imports, CompileError, ExitStatus, ExportError, Instance, InstantiationError, Module, Store,
+};
+use wasmer_wasi::{
+    get_wasi_version, WasiEnv, WasiError, WasiRuntimeError, WasiState, WasiStateCreationError,
+};
+
+/// How to run a program with [`run_program`].
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    store: Store,
+    program_name: String,
+    args: Vec<String>,
+    envs: Vec<(String, String)>,
+    preopen_dirs: Vec<PathBuf>,
+    mapped_dirs: Vec<(String, PathBuf)>,
+}
+
+impl RunConfig {
+    /// Creates the configuration of a program named `program_name`,
+    /// compiled in `store`, without arguments, environment variables or
+    /// directories.
+    pub fn new(store: &Store, program_name: impl Into<String>) -> Self {
+        Self {
+            store: store.clone(),
+            program_name: program_name.into(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            preopen_dirs: Vec::new(),
+            mapped_dirs: Vec::new(),
+        }
+    }
+
+    /// Adds an argument.
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds the arguments.
+    pub fn args<I>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds an environment variable, for WASI programs.
+    pub fn env(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Preopens a directory at its own path, for WASI programs.
+    pub fn preopen_dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.preopen_dirs.push(dir.into());
+        self
+    }
+
+    /// Maps a directory of the host at `alias` in the guest.
+    pub fn map_dir(&mut self, alias: impl Into<String>, dir: impl Into<PathBuf>) -> &mut Self {
+        self.mapped_dirs.push((alias.into(), dir.into()));
+        self
+    }
+
+    fn wasi_env(&self) -> Result<WasiEnv, WasiStateCreationError> {
+        WasiState::new(&self.program_name)
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
+            .preopen_dirs(&self.preopen_dirs)?
+            .map_dirs(self.mapped_dirs.iter().cloned())?
+            .finalize()
+    }
+}
+
+/// An error preventing [`run_program`] from running a program. How the
+/// program ended once running, e.g. with a trap, is its [`ExitStatus`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RunError {
+    /// The module couldn't be compiled.
+    Compile(CompileError),
+    /// The state of WASI couldn't be created from the configuration.
+    WasiState(WasiStateCreationError),
+    /// The environment of Emscripten couldn't be created.
+    #[cfg(feature = "emscripten")]
+    Emscripten(wasmer_emscripten::EmscriptenSetupError),
+    /// The module couldn't be instantiated.
+    Instantiation(InstantiationError),
+    /// The module doesn't export its entry point or its memory.
+    Export(ExportError),
+    /// The program couldn't run with WASI.
+    Wasi(WasiRuntimeError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Compile(error) => error.fmt(f),
+            Self::WasiState(error) => error.fmt(f),
+            #[cfg(feature = "emscripten")]
+            Self::Emscripten(error) => write!(f, "Emscripten setup failed: {}", error),
+            Self::Instantiation(error) => error.fmt(f),
+            Self::Export(error) => error.fmt(f),
+            Self::Wasi(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Compile(error) => error.source(),
+            Self::WasiState(error) => error.source(),
+            #[cfg(feature = "emscripten")]
+            Self::Emscripten(error) => Some(error),
+            Self::Instantiation(error) => error.source(),
+            Self::Export(error) => error.source(),
+            Self::Wasi(error) => error.source(),
+        }
+    }
+}
+
+impl From<CompileError> for RunError {
+    fn from(error: CompileError) -> Self {
+        Self::Compile(error)
+    }
+}
+
+impl From<WasiStateCreationError> for RunError {
+    fn from(error: WasiStateCreationError) -> Self {
+        Self::WasiState(error)
+    }
+}
+
+#[cfg(feature = "emscripten")]
+impl From<wasmer_emscripten::EmscriptenSetupError> for RunError {
+    fn from(error: wasmer_emscripten::EmscriptenSetupError) -> Self {
+        Self::Emscripten(error)
+    }
+}
+
+impl From<InstantiationError> for RunError {
+    fn from(error: InstantiationError) -> Self {
+        Self::Instantiation(error)
+    }
+}
+
+impl From<ExportError> for RunError {
+    fn from(error: ExportError) -> Self {
+        Self::Export(error)
+    }
+}
+
+impl From<WasiRuntimeError> for RunError {
+    fn from(error: WasiRuntimeError) -> Self {
+        Self::Wasi(error)
+    }
+}
+
+impl From<WasiError> for RunError {
+    fn from(error: WasiError) -> Self {
+        Self::Wasi(error.into())
+    }
+}
+
+/// Compiles and runs the program in `bytes`, with the imports of its ABI,
+/// and returns how it ended.
+///
+/// The ABI is detected from the imports of the module:
+/// - the modules using only WASI are run as WASI commands,
+/// - the modules built by Emscripten, with its JavaScript runtime or with
+///   `-s STANDALONE_WASM`, are run with its imports, when the
+///   `emscripten` feature is enabled,
+/// - the other modules are instantiated without imports, and their
+///   `_start` function is called.
+///
+/// The Rust panic messages the program writes to stderr are found for
+/// WASI and Emscripten programs.
+pub fn run_program(bytes: impl AsRef<[u8]>, config: &RunConfig) -> Result<ExitStatus, RunError> {
+    let module = Module::new(&config.store, bytes)?;
+
+    #[cfg(feature = "emscripten")]
+    {
+        if wasmer_emscripten::is_emscripten_module(&module) {
+            return run_emscripten(&module, config);
+        }
+        if wasmer_emscripten::is_standalone_emscripten_module(&module) {
+            return run_standalone_emscripten(&module, &mut config.wasi_env()?);
+        }
+    }
+
+    if get_wasi_version(&module, true).is_some() {
+        let mut wasi_env = config.wasi_env()?;
+        return Ok(wasi_env.run_command_with_status(&module)?);
+    }
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let start = instance.exports.get_function("_start")?;
+    Ok(match start.call(&[]) {
+        Ok(_) => ExitStatus::exited(0),
+        Err(error) => ExitStatus::trapped(error.message()),
+    })
+}
+
+#[cfg(feature = "emscripten")]
+fn run_emscripten(module: &Module, config: &RunConfig) -> Result<ExitStatus, RunError> {
+    use wasmer_emscripten::{
+        generate_emscripten_env, run_emscripten_instance, EmEnv, EmscriptenGlobals,
+    };
+
+    let mut globals = EmscriptenGlobals::new(module.store(), module)?;
+    let mut em_env = EmEnv::new();
+    let import_object = generate_emscripten_env(module.store(), &mut globals, &mut em_env);
+    let mut instance = Instance::new(module, &import_object)?;
+    let result = run_emscripten_instance(
+        &mut instance,
+        &mut em_env,
+        &mut globals,
+        &config.program_name,
+        config.args.iter().map(String::as_str).collect(),
+        None,
+        config.mapped_dirs.clone(),
+    );
+    Ok(em_env.exit_status(result))
+}
+
+/// Runs a module built by Emscripten with `-s STANDALONE_WASM`, see
+/// [`wasmer_emscripten::is_standalone_emscripten_module`], with the WASI
+/// imports of `wasi_env` and the functions of Emscripten, and returns how
+/// it ended.
+#[cfg(feature = "emscripten")]
+pub fn run_standalone_emscripten(
+    module: &Module,
+    wasi_env: &mut WasiEnv,
+) -> Result<ExitStatus, RunError> {
+    use wasmer_emscripten::{generate_standalone_env, EmEnv, EmscriptenError};
+
+    let mut em_env = EmEnv::new();
+    let mut import_object = wasi_env.import_object(module)?;
+    import_object.register("env", generate_standalone_env(module, &em_env));
+    let instance = Instance::new(module, &import_object)?;
+    let memory = match instance.exports.iter().memories().next() {
+        Some((_, memory)) => memory.clone(),
+        None => return Err(ExportError::Missing("memory".to_string()).into()),
+    };
+    wasi_env.set_memory(memory.clone());
+    em_env.set_memory(memory);
+    let start = instance.exports.get_function("_start")?;
+    let error = match start.call(&[]) {
+        Ok(_) => return Ok(wasi_env.exit_status(Ok(()))?),
+        Err(error) => error,
+    };
+    // The functions of Emscripten abort the program without WASI.
+    let status = match error.downcast::<EmscriptenError>() {
+        Ok(EmscriptenError::Abort(reason)) => ExitStatus::aborted(reason),
+        Ok(EmscriptenError::Exit(code)) => ExitStatus::exited(code),
+        Ok(error) => ExitStatus::trapped(error.to_string()),
+        Err(error) => return Ok(wasi_env.exit_status(Err(error))?),
+    };
+    Ok(status.with_panic_message(wasi_env.state().stderr_capture()))
+}
+
+#[cfg(all(test, feature = "compiler"))]
+mod test {
+    use super::*;
+    use crate::store::StoreOptions;
+    use structopt::StructOpt;
+
+    fn run(wat: &str) -> ExitStatus {
+        let (store, _, _) = StoreOptions::from_iter(&["run"]).get_store().unwrap();
+        let mut config = RunConfig::new(&store, "program");
+        config.arg("--flag");
+        run_program(wat, &config).unwrap()
+    }
+
+    #[test]
+    fn wasi_programs() {
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (import "wasi_snapshot_preview1" "args_sizes_get"
+                (func $args_sizes_get (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "_start")
+                (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+                ;; exits with the number of arguments
+                (call $proc_exit (i32.load (i32.const 0)))))"#);
+        assert_eq!(status, ExitStatus::exited(2));
+    }
+
+    #[test]
+    fn plain_programs() {
+        let status = run(r#"(module
+            (func (export "_start")))"#);
+        assert_eq!(status, ExitStatus::exited(0));
+
+        let status = run(r#"(module
+            (func (export "_start")
+                (unreachable)))"#);
+        assert_eq!(status.code, None);
+        assert!(status.trap.is_some());
+    }
+
+    #[cfg(feature = "emscripten")]
+    #[test]
+    fn emscripten_programs() {
+        let status = run(r#"(module
+            (import "env" "memory" (memory 256 256))
+            (import "env" "table" (table 0 funcref))
+            (import "env" "_emscripten_memcpy_big" (func (param i32 i32 i32) (result i32)))
+            (import "env" "abort" (func $abort (param i32)))
+            (func (export "_main")
+                (call $abort (i32.const 5))))"#);
+        assert_eq!(status, ExitStatus::aborted("Program aborted with value 5"));
+    }
+
+    #[cfg(feature = "emscripten")]
+    #[test]
+    fn standalone_emscripten_programs() {
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (import "env" "emscripten_notify_memory_growth" (func (param i32)))
+            (memory (export "memory") 1)
+            (func (export "_start")
+                (call $proc_exit (i32.const 3))))"#);
+        assert_eq!(status, ExitStatus::exited(3));
+
+        // The functions of Emscripten which need its JavaScript runtime
+        // abort the program.
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (import "env" "emscripten_get_now" (func $get_now (result f64)))
+            (memory (export "memory") 1)
+            (func (export "_start")
+                (drop (call $get_now))))"#);
+        assert_eq!(
+            status,
+            ExitStatus::aborted("missing function: emscripten_get_now")
+        );
+    }
+}
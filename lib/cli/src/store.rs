@@ -30,6 +30,22 @@ pub struct StoreOptions {
     /// Use ObjectFile Engine.
     #[structopt(long, conflicts_with_all = &["jit", "native"])]
     object_file: bool,
+
+    /// Register the compiled functions with the debuggers (JIT engine
+    /// only).
+    #[structopt(skip)]
+    #[cfg_attr(not(feature = "jit"), allow(dead_code))]
+    debug_info: bool,
+}
+
+impl StoreOptions {
+    /// Returns these options with the compiled functions registered
+    /// with the debuggers attached to the process.
+    pub fn with_debug_info(&self) -> Self {
+        let mut options = self.clone();
+        options.debug_info = true;
+        options
+    }
 }
 
 #[derive(Debug, Clone, StructOpt)]
@@ -55,6 +71,17 @@ pub struct CompilerOptions {
     #[structopt(long, parse(from_os_str))]
     llvm_debug_dir: Option<PathBuf>,
 
+    /// LLVM branch hints file, guiding the block layout. It has the
+    /// format of the `metadata.code.branch_hint` custom section, whose
+    /// hints are used as well.
+    #[structopt(long, parse(from_os_str))]
+    llvm_branch_hints: Option<PathBuf>,
+
+    /// LLVM inlining budget, inlining the small functions of a module in
+    /// their callers when compiling with the native engine.
+    #[structopt(long)]
+    llvm_inline_threshold: Option<u32>,
+
     /// The deprecated backend flag - Please do not use
     #[structopt(long = "backend", hidden = true, conflicts_with_all = &["singlepass", "cranelift", "llvm"])]
     backend: Option<String>,
@@ -141,6 +168,7 @@ impl CompilerOptions {
                 wasmer_engine_jit::JIT::new(&*compiler_config)
                     .features(features)
                     .target(target)
+                    .debug_info(self.debug_info)
                     .engine(),
             ),
             #[cfg(feature = "native")]
@@ -200,6 +228,7 @@ impl CompilerOptions {
                 use std::fmt;
                 use std::fs::File;
                 use std::io::Write;
+                use wasmer_compiler::BranchHints;
                 use wasmer_compiler_llvm::{
                     CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks, LLVM,
                 };
@@ -292,6 +321,11 @@ impl CompilerOptions {
                 if let Some(ref llvm_debug_dir) = self.llvm_debug_dir {
                     config.callbacks(Some(Arc::new(Callbacks::new(llvm_debug_dir.clone())?)));
                 }
+                if let Some(ref llvm_branch_hints) = self.llvm_branch_hints {
+                    let data = std::fs::read(llvm_branch_hints)?;
+                    config.branch_hints(BranchHints::parse(&data)?);
+                }
+                config.inline_threshold(self.llvm_inline_threshold);
                 if self.enable_verifier {
                     config.enable_verifier();
                 }
@@ -400,6 +434,16 @@ impl StoreOptions {
         Ok((store, engine_type, compiler_type))
     }
 
+    /// Returns these options with the compiler replaced by `compiler`.
+    pub fn with_compiler(&self, compiler: &CompilerType) -> Self {
+        let mut options = self.clone();
+        options.compiler.singlepass = *compiler == CompilerType::Singlepass;
+        options.compiler.cranelift = *compiler == CompilerType::Cranelift;
+        options.compiler.llvm = *compiler == CompilerType::LLVM;
+        options.compiler.backend = None;
+        options
+    }
+
     fn get_engine_with_compiler(
         &self,
         target: Target,
@@ -445,7 +489,11 @@ impl StoreOptions {
         let engine_type = self.get_engine()?;
         let engine: Arc<dyn Engine + Send + Sync> = match engine_type {
             #[cfg(feature = "jit")]
-            EngineType::JIT => Arc::new(wasmer_engine_jit::JIT::headless().engine()),
+            EngineType::JIT => Arc::new(
+                wasmer_engine_jit::JIT::headless()
+                    .debug_info(self.debug_info)
+                    .engine(),
+            ),
             #[cfg(feature = "native")]
             EngineType::Native => Arc::new(wasmer_engine_native::Native::headless().engine()),
             #[cfg(feature = "object-file")]
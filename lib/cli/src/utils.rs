@@ -1,7 +1,12 @@
 //! Utility functions for the WebAssembly module
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
+use wasmer::{
+    Exports, Extern, ExternType, Function, FunctionType, Global, ImportObject, Memory, Module,
+    RuntimeError, Table, Val, ValType,
+};
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -51,3 +56,94 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
         );
     }
 }
+
+/// Parses the arguments of a function of type `ty`
+pub fn parse_args<S: AsRef<str>>(ty: &FunctionType, args: &[S]) -> Result<Vec<Val>> {
+    if ty.params().len() != args.len() {
+        bail!(
+            "Function expected {} arguments, but received {}",
+            ty.params().len(),
+            args.len()
+        );
+    }
+    args.iter()
+        .zip(ty.params())
+        .map(|(arg, param_type)| parse_value(*param_type, arg.as_ref()))
+        .collect()
+}
+
+/// Parses a value of type `ty`
+pub fn parse_value(ty: ValType, value: &str) -> Result<Val> {
+    let val = match ty {
+        ValType::I32 => value.parse().map(Val::I32).ok(),
+        ValType::I64 => value.parse().map(Val::I64).ok(),
+        ValType::F32 => value.parse().map(Val::F32).ok(),
+        ValType::F64 => value.parse().map(Val::F64).ok(),
+        _ => None,
+    };
+    val.ok_or_else(|| anyhow!("Can't convert `{}` into a {:?}", value, ty))
+}
+
+/// Returns imports standing in for the imports of `module`, so modules
+/// written for a host can be instantiated: functions trapping when called,
+/// and new memories, tables and globals.
+pub fn stub_imports(module: &Module) -> Result<ImportObject> {
+    let store = module.store();
+    let mut namespaces = HashMap::new();
+    for import in module.imports() {
+        let export: Extern = match import.ty() {
+            ExternType::Function(ty) => {
+                let name = format!("{}.{}", import.module(), import.name());
+                Function::new(store, ty, move |_| {
+                    Err(RuntimeError::new(format!(
+                        "The import `{}` isn't available",
+                        name
+                    )))
+                })
+                .into()
+            }
+            ExternType::Memory(ty) => Memory::new(store, *ty)?.into(),
+            ExternType::Table(ty) => Table::new(store, *ty, Val::null())?.into(),
+            ExternType::Global(ty) => {
+                let value = match ty.ty {
+                    ValType::I32 => Val::I32(0),
+                    ValType::I64 => Val::I64(0),
+                    ValType::F32 => Val::F32(0.0),
+                    ValType::F64 => Val::F64(0.0),
+                    ValType::V128 => Val::V128(0),
+                    ValType::ExternRef => Val::null(),
+                    ValType::FuncRef => bail!(
+                        "The funcref global import `{}`.`{}` can't be stubbed",
+                        import.module(),
+                        import.name()
+                    ),
+                };
+                if ty.mutability.is_mutable() {
+                    Global::new_mut(store, value).into()
+                } else {
+                    Global::new(store, value).into()
+                }
+            }
+        };
+        namespaces
+            .entry(import.module().to_string())
+            .or_insert_with(Exports::new)
+            .insert(import.name(), export);
+    }
+
+    let mut imports = ImportObject::new();
+    for (name, namespace) in namespaces {
+        imports.register(name, namespace);
+    }
+    Ok(imports)
+}
+
+/// Parses a decimal, or hexadecimal with a `0x` prefix, number.
+pub fn parse_number(number: &str) -> Result<u64> {
+    let parsed = if number.starts_with("0x") {
+        u64::from_str_radix(&number[2..], 16)
+    } else {
+        number.parse()
+    };
+    parsed.map_err(|_| anyhow!("`{}` isn't a number", number))
+}
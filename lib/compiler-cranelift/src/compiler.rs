@@ -17,13 +17,19 @@ use cranelift_codegen::ir;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{binemit, Context};
 #[cfg(feature = "unwind")]
-use gimli::write::{Address, EhFrame, FrameTable};
+use gimli::write::{Address, EhFrame, FrameDescriptionEntry, FrameTable};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+#[cfg(feature = "unwind")]
+use std::any::Any;
+#[cfg(feature = "unwind")]
+use std::sync::Arc;
+use std::time::Instant;
 use wasmer_compiler::CompileError;
 use wasmer_compiler::{CallingConvention, ModuleTranslationState, Target};
 use wasmer_compiler::{
-    Compilation, CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo,
-    CompiledFunctionUnwindInfo, Compiler, Dwarf, FunctionBody, FunctionBodyData, SectionIndex,
+    Compilation, CompileLimits, CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo,
+    CompiledFunctionStats, CompiledFunctionUnwindInfo, Compiler, Dwarf, FunctionBody,
+    FunctionBodyData, FunctionKeys, SectionIndex,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
@@ -49,6 +55,10 @@ impl CraneliftCompiler {
 }
 
 impl Compiler for CraneliftCompiler {
+    fn limits(&self) -> CompileLimits {
+        self.config.limits
+    }
+
     /// Compile the module using Cranelift, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -77,7 +87,7 @@ impl Compiler for CraneliftCompiler {
             // FDEs will cause some issues in Linux.
             None
         } else {
-            use std::sync::{Arc, Mutex};
+            use std::sync::Mutex;
             match target.triple().default_calling_convention() {
                 Ok(CallingConvention::SystemV) => {
                     match isa.create_systemv_cie() {
@@ -94,11 +104,56 @@ impl Compiler for CraneliftCompiler {
             }
         };
 
+        let budget = self.config.limits.start();
+        let function_cache = self.config.function_cache.as_ref().map(|cache| {
+            let keys = FunctionKeys::new(
+                "cranelift",
+                &self.config.fingerprint(),
+                target,
+                compile_info,
+            );
+            (cache, keys)
+        });
         let functions = function_body_inputs
             .into_iter()
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(FuncTranslator::new, |func_translator, (i, input)| {
+                self.config.limits.check_function(*i, input)?;
+                budget.check()?;
+                let start = Instant::now();
+                let entry = function_cache
+                    .as_ref()
+                    .map(|(cache, keys)| (cache, keys.key(*i, input, &())));
+                if let Some((cache, key)) = &entry {
+                    if let Some(cached) = cache.get(key, input.module_offset) {
+                        let mut compiled_function = cached.function;
+                        // The FDE of the function goes in the frame table of the module
+                        #[cfg(feature = "unwind")]
+                        {
+                            if let Some(CompiledFunctionUnwindInfo::Dwarf) =
+                                compiled_function.body.unwind_info
+                            {
+                                let fde = cached
+                                    .data
+                                    .as_ref()
+                                    .and_then(|data| data.downcast_ref::<FrameDescriptionEntry>());
+                                match (fde, &dwarf_frametable) {
+                                    (Some(fde), Some((dwarf_frametable, cie_id))) => {
+                                        dwarf_frametable
+                                            .lock()
+                                            .expect("Can't write into DWARF frametable")
+                                            .add_fde(*cie_id, fde.clone());
+                                    }
+                                    _ => compiled_function.body.unwind_info = None,
+                                }
+                            }
+                        }
+                        compiled_function.stats.compile_time = start.elapsed();
+                        return Ok(compiled_function);
+                    }
+                }
+
                 let func_index = module.func_index(*i);
                 let mut context = Context::new();
                 let mut func_env = FuncEnvironment::new(
@@ -107,6 +162,7 @@ impl Compiler for CraneliftCompiler {
                     &signatures,
                     &memory_styles,
                     &table_styles,
+                    self.config.enable_epoch_interruption,
                 );
                 context.func.name = get_function_name(func_index);
                 context.func.signature = signatures[module.functions[func_index]].clone();
@@ -140,39 +196,55 @@ impl Compiler for CraneliftCompiler {
                         CompileError::Codegen(pretty_error(&context.func, Some(&*isa), error))
                     })?;
 
-                let unwind_info = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::FDE(fde) => {
-                        if let Some((dwarf_frametable, cie_id)) = &dwarf_frametable {
-                            dwarf_frametable
-                                .lock()
-                                .expect("Can't write into DWARF frametable")
-                                .add_fde(
-                                    *cie_id,
-                                    fde.to_fde(Address::Symbol {
-                                        // The symbol is the kind of relocation.
-                                        // "0" is used for functions
-                                        symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                        // We use the addend as a way to specify the
-                                        // function index
-                                        addend: i.index() as _,
-                                    }),
-                                );
-                            // The unwind information is inserted into the dwarf section
-                            Some(CompiledFunctionUnwindInfo::Dwarf)
-                        } else {
-                            None
+                let (unwind_info, unwind_data) =
+                    match compiled_function_unwind_info(&*isa, &context)? {
+                        #[cfg(feature = "unwind")]
+                        CraneliftUnwindInfo::FDE(fde) => {
+                            if let Some((dwarf_frametable, cie_id)) = &dwarf_frametable {
+                                let fde = fde.to_fde(Address::Symbol {
+                                    // The symbol is the kind of relocation.
+                                    // "0" is used for functions
+                                    symbol: WriterRelocate::FUNCTION_SYMBOL,
+                                    // We use the addend as a way to specify the
+                                    // function index
+                                    addend: i.index() as _,
+                                });
+                                dwarf_frametable
+                                    .lock()
+                                    .expect("Can't write into DWARF frametable")
+                                    .add_fde(*cie_id, fde.clone());
+                                // The unwind information is inserted into the dwarf section
+                                let fde: Arc<dyn Any + Send + Sync> = Arc::new(fde);
+                                (Some(CompiledFunctionUnwindInfo::Dwarf), Some(fde))
+                            } else {
+                                (None, None)
+                            }
                         }
-                    }
-                    other => other.maybe_into_to_windows_unwind(),
-                };
+                        other => (other.maybe_into_to_windows_unwind(), None),
+                    };
 
-                let address_map = get_function_address_map(&context, input, code_buf.len(), &*isa);
+                let code_size = code_buf.len();
+                let address_map = get_function_address_map(&context, input, code_size, &*isa);
 
                 // We transform the Cranelift JumpTable's into compiler JumpTables
                 let func_jt_offsets = transform_jump_table(context.func.jt_offsets);
 
-                Ok(CompiledFunction {
+                // The legacy backends insert spills in the function, the
+                // new (`MachInst`) ones don't report them.
+                let register_spills = if isa.get_mach_backend().is_none() {
+                    let func = &context.func;
+                    let spills = func
+                        .layout
+                        .blocks()
+                        .flat_map(|block| func.layout.block_insts(block))
+                        .filter(|inst| func.dfg[*inst].opcode() == ir::Opcode::Spill)
+                        .count();
+                    Some(spills as u32)
+                } else {
+                    None
+                };
+
+                let compiled_function = CompiledFunction {
                     body: FunctionBody {
                         body: code_buf,
                         unwind_info,
@@ -183,7 +255,16 @@ impl Compiler for CraneliftCompiler {
                         address_map,
                         traps: trap_sink.traps,
                     },
-                })
+                    stats: CompiledFunctionStats {
+                        code_size,
+                        compile_time: start.elapsed(),
+                        register_spills,
+                    },
+                };
+                if let Some((cache, key)) = entry {
+                    cache.insert(key, input.module_offset, &compiled_function, unwind_data);
+                }
+                Ok(compiled_function)
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
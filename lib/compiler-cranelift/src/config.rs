@@ -1,9 +1,11 @@
 use crate::compiler::CraneliftCompiler;
 use cranelift_codegen::isa::{lookup, TargetIsa};
 use cranelift_codegen::settings::{self, Configurable};
+use std::hash::Hash;
 use std::sync::Arc;
 use wasmer_compiler::{
-    Architecture, Compiler, CompilerConfig, CpuFeature, FunctionMiddlewareGenerator, Target,
+    Architecture, CompileLimits, Compiler, CompilerConfig, CpuFeature, FunctionCache,
+    FunctionMiddlewareGenerator, Target,
 };
 
 // Runtime Environment
@@ -33,6 +35,9 @@ pub struct Cranelift {
     enable_verifier: bool,
     enable_simd: bool,
     enable_pic: bool,
+    pub(crate) enable_epoch_interruption: bool,
+    pub(crate) limits: CompileLimits,
+    pub(crate) function_cache: Option<Arc<FunctionCache>>,
     opt_level: OptLevel,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn FunctionMiddlewareGenerator>>,
@@ -48,6 +53,9 @@ impl Cranelift {
             opt_level: OptLevel::Speed,
             enable_pic: false,
             enable_simd: true,
+            enable_epoch_interruption: false,
+            limits: CompileLimits::default(),
+            function_cache: None,
             middlewares: vec![],
         }
     }
@@ -61,6 +69,24 @@ impl Cranelift {
         self
     }
 
+    /// The settings the compiled code depends on, identifying it in the
+    /// function cache.
+    pub(crate) fn fingerprint(&self) -> impl Hash {
+        let middlewares = self
+            .middlewares
+            .iter()
+            .map(|middleware| format!("{:?}", middleware))
+            .collect::<Vec<_>>();
+        (
+            self.enable_nan_canonicalization,
+            self.enable_simd,
+            self.enable_pic,
+            self.enable_epoch_interruption,
+            format!("{:?}", self.opt_level),
+            middlewares,
+        )
+    }
+
     /// Enable SIMD support.
     pub fn enable_simd(&mut self, enable: bool) -> &mut Self {
         self.enable_simd = enable;
@@ -193,6 +219,18 @@ impl CompilerConfig for Cranelift {
         self.enable_verifier = true;
     }
 
+    fn enable_epoch_interruption(&mut self) {
+        self.enable_epoch_interruption = true;
+    }
+
+    fn set_limits(&mut self, limits: CompileLimits) {
+        self.limits = limits;
+    }
+
+    fn set_function_cache(&mut self, cache: Arc<FunctionCache>) {
+        self.function_cache = Some(cache);
+    }
+
     /// Transform it into the compiler
     fn compiler(&self) -> Box<dyn Compiler + Send> {
         Box::new(CraneliftCompiler::new(&self))
@@ -89,6 +89,9 @@ pub struct FuncEnvironment<'module_environment> {
 
     /// The table styles
     table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
+
+    /// Whether to check the store epoch at loop headers.
+    epoch_interruption: bool,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
@@ -98,6 +101,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
         memory_styles: &'module_environment PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
+        epoch_interruption: bool,
     ) -> Self {
         Self {
             target_config,
@@ -116,6 +120,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             offsets: VMOffsets::new(target_config.pointer_bytes(), module),
             memory_styles,
             table_styles,
+            epoch_interruption,
         }
     }
 
@@ -611,6 +616,40 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         unreachable!("we don't make any custom globals")
     }
 
+    fn translate_loop_header(&mut self, mut pos: FuncCursor) -> WasmResult<()> {
+        if !self.epoch_interruption {
+            return Ok(());
+        }
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(&mut pos.func);
+        let base = pos.ins().global_value(pointer_type, vmctx);
+
+        let mut mem_flags = ir::MemFlags::trusted();
+        mem_flags.set_readonly();
+        let epoch_offset = i32::try_from(self.offsets.vmctx_epoch()).unwrap();
+        let epoch = pos.ins().load(pointer_type, mem_flags, base, epoch_offset);
+
+        // The epoch fields are updated concurrently by the embedder.
+        let mem_flags = ir::MemFlags::trusted();
+        let current = pos.ins().load(
+            I64,
+            mem_flags,
+            epoch,
+            i32::from(self.offsets.vmepoch_current()),
+        );
+        let deadline = pos.ins().load(
+            I64,
+            mem_flags,
+            epoch,
+            i32::from(self.offsets.vmepoch_deadline()),
+        );
+        let reached = pos
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, current, deadline);
+        pos.ins().trapnz(reached, ir::TrapCode::Interrupt);
+        Ok(())
+    }
+
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
         let pointer_type = self.pointer_type();
 
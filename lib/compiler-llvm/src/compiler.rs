@@ -1,23 +1,32 @@
 use crate::config::LLVM;
+use crate::object_file::CompiledFunction;
 use crate::trampoline::FuncTrampoline;
 use crate::translator::FuncTranslator;
 use crate::CompiledKind;
 use inkwell::context::Context;
 use inkwell::memory_buffer::MemoryBuffer;
 use inkwell::module::{Linkage, Module};
+use inkwell::passes::{PassManager, PassManagerBuilder};
 use inkwell::targets::FileType;
 use inkwell::DLLStorageClass;
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::sync::Arc;
+use std::time::Instant;
 use wasmer_compiler::{
-    Compilation, CompileError, CompileModuleInfo, Compiler, CustomSection, CustomSectionProtection,
-    Dwarf, FunctionBodyData, ModuleTranslationState, RelocationTarget, SectionBody, SectionIndex,
-    Symbol, SymbolRegistry, Target,
+    Compilation, CompileError, CompileLimits, CompileModuleInfo, Compiler, CustomSection,
+    CustomSectionProtection, CustomSections, Dwarf, FunctionBodyData, FunctionKeys,
+    ModuleTranslationState, RelocationTarget, SectionBody, SectionIndex, Symbol, SymbolRegistry,
+    Target,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
 
 //use std::sync::{Arc, Mutex};
 
+/// The custom sections of a function, cached with it in a
+/// `FunctionCache`, and the indices of its `.eh_frame` sections.
+type CachedSections = (CustomSections, Vec<SectionIndex>);
+
 /// A compiler that compiles a WebAssembly module with LLVM, translating the Wasm to LLVM IR,
 /// optimizing it and then translating to assembly.
 pub struct LLVMCompiler {
@@ -86,10 +95,12 @@ impl LLVMCompiler {
         let target_machine = self.config().target_machine(target);
         let ctx = Context::create();
         let merged_module = ctx.create_module("");
+        let branch_hints = self.config().module_branch_hints(&compile_info.module);
 
         // TODO: make these steps run in parallel instead of in three phases
         // with a serial step in between them.
 
+        let budget = self.config().limits.start();
         function_body_inputs
             .into_iter()
             .collect::<Vec<_>>()
@@ -100,6 +111,8 @@ impl LLVMCompiler {
                     FuncTranslator::new(target_machine)
                 },
                 |func_translator, (i, input)| {
+                    self.config().limits.check_function(*i, input)?;
+                    budget.check()?;
                     let module = func_translator.translate_to_module(
                         &compile_info.module,
                         module_translation,
@@ -108,6 +121,7 @@ impl LLVMCompiler {
                         self.config(),
                         &compile_info.memory_styles,
                         &compile_info.table_styles,
+                        &branch_hints,
                         symbol_registry,
                     )?;
                     Ok(module.write_bitcode_to_memory().as_slice().to_vec())
@@ -177,6 +191,17 @@ impl LLVMCompiler {
                 merged_module.link_in_module(m).unwrap();
             });
 
+        // All the functions are in the same module now, so the small ones
+        // can be inlined in their callers.
+        if let Some(threshold) = self.config().inline_threshold {
+            let pass_manager = PassManager::create(());
+            let pass_manager_builder = PassManagerBuilder::create();
+            pass_manager_builder.set_optimization_level(self.config().opt_level);
+            pass_manager_builder.set_inliner_with_threshold(threshold);
+            pass_manager_builder.populate_module_pass_manager(&pass_manager);
+            pass_manager.run_on(&merged_module);
+        }
+
         let i8_ty = ctx.i8_type();
         let metadata_init = i8_ty.const_array(
             wasmer_metadata
@@ -207,6 +232,10 @@ impl LLVMCompiler {
 }
 
 impl Compiler for LLVMCompiler {
+    fn limits(&self) -> CompileLimits {
+        self.config.limits
+    }
+
     fn experimental_native_compile_module<'data, 'module>(
         &self,
         target: &Target,
@@ -241,12 +270,19 @@ impl Compiler for LLVMCompiler {
         let memory_styles = &compile_info.memory_styles;
         let table_styles = &compile_info.table_styles;
         let module = &compile_info.module;
+        let branch_hints = self.config().module_branch_hints(module);
 
         // TODO: merge constants in sections.
 
         let mut module_custom_sections = PrimaryMap::new();
         let mut frame_section_bytes = vec![];
         let mut frame_section_relocations = vec![];
+        let budget = self.config().limits.start();
+        let function_cache = self.config().function_cache.as_ref().map(|cache| {
+            let keys =
+                FunctionKeys::new("llvm", &self.config().fingerprint(), target, compile_info);
+            (cache, keys)
+        });
         let functions = function_body_inputs
             .into_iter()
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
@@ -259,7 +295,30 @@ impl Compiler for LLVMCompiler {
                 |func_translator, (i, input)| {
                     // TODO: remove (to serialize)
                     //let _data = data.lock().unwrap();
-                    func_translator.translate(
+                    self.config().limits.check_function(*i, input)?;
+                    budget.check()?;
+                    let start = Instant::now();
+                    let entry = function_cache.as_ref().map(|(cache, keys)| {
+                        let hints = branch_hints.function_hints(module.func_index(*i));
+                        (cache, keys.key(*i, input, &hints))
+                    });
+                    if let Some((cache, key)) = &entry {
+                        let cached = cache.get(key, input.module_offset).and_then(|cached| {
+                            let sections = cached.data?;
+                            let sections = sections.downcast_ref::<CachedSections>()?;
+                            Some(CompiledFunction {
+                                compiled_function: cached.function,
+                                custom_sections: sections.0.clone(),
+                                eh_frame_section_indices: sections.1.clone(),
+                            })
+                        });
+                        if let Some(mut compiled_function) = cached {
+                            compiled_function.compiled_function.stats.compile_time =
+                                start.elapsed();
+                            return Ok(compiled_function);
+                        }
+                    }
+                    let mut compiled_function = func_translator.translate(
                         &module,
                         module_translation,
                         i,
@@ -267,8 +326,23 @@ impl Compiler for LLVMCompiler {
                         self.config(),
                         memory_styles,
                         &table_styles,
+                        &branch_hints,
                         &ShortNames {},
-                    )
+                    )?;
+                    compiled_function.compiled_function.stats.compile_time = start.elapsed();
+                    if let Some((cache, key)) = entry {
+                        let sections: CachedSections = (
+                            compiled_function.custom_sections.clone(),
+                            compiled_function.eh_frame_section_indices.clone(),
+                        );
+                        cache.insert(
+                            key,
+                            input.module_offset,
+                            &compiled_function.compiled_function,
+                            Some(Arc::new(sections)),
+                        );
+                    }
+                    Ok(compiled_function)
                 },
             )
             .collect::<Result<Vec<_>, CompileError>>()?
@@ -6,10 +6,15 @@ use inkwell::targets::{
 use inkwell::OptimizationLevel;
 use itertools::Itertools;
 use std::fmt::Debug;
+use std::hash::Hash;
 use std::sync::Arc;
 use target_lexicon::Architecture;
-use wasmer_compiler::{Compiler, CompilerConfig, FunctionMiddlewareGenerator, Target, Triple};
+use wasmer_compiler::{
+    BranchHints, CompileLimits, Compiler, CompilerConfig, FunctionCache,
+    FunctionMiddlewareGenerator, Target, Triple,
+};
 use wasmer_types::{FunctionType, LocalFunctionIndex};
+use wasmer_vm::ModuleInfo;
 
 /// The InkWell ModuleInfo type
 pub type InkwellModule<'ctx> = inkwell::module::Module<'ctx>;
@@ -41,9 +46,15 @@ pub trait LLVMCallbacks: Debug + Send + Sync {
 pub struct LLVM {
     pub(crate) enable_nan_canonicalization: bool,
     pub(crate) enable_verifier: bool,
+    pub(crate) enable_epoch_interruption: bool,
+    pub(crate) limits: CompileLimits,
+    pub(crate) function_cache: Option<Arc<FunctionCache>>,
     pub(crate) opt_level: OptimizationLevel,
+    pub(crate) inline_threshold: Option<u32>,
     is_pic: bool,
     pub(crate) callbacks: Option<Arc<dyn LLVMCallbacks>>,
+    /// Branch hints given in addition to those of the modules.
+    branch_hints: Option<Arc<BranchHints>>,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn FunctionMiddlewareGenerator>>,
 }
@@ -55,9 +66,14 @@ impl LLVM {
         Self {
             enable_nan_canonicalization: false,
             enable_verifier: false,
+            enable_epoch_interruption: false,
+            limits: CompileLimits::default(),
+            function_cache: None,
             opt_level: OptimizationLevel::Aggressive,
+            inline_threshold: None,
             is_pic: false,
             callbacks: None,
+            branch_hints: None,
             middlewares: vec![],
         }
     }
@@ -71,12 +87,42 @@ impl LLVM {
         self
     }
 
+    /// The settings the compiled code depends on, identifying it in the
+    /// function cache.
+    pub(crate) fn fingerprint(&self) -> impl Hash {
+        let middlewares = self
+            .middlewares
+            .iter()
+            .map(|middleware| format!("{:?}", middleware))
+            .collect::<Vec<_>>();
+        (
+            self.enable_nan_canonicalization,
+            self.enable_epoch_interruption,
+            format!("{:?}", self.opt_level),
+            self.inline_threshold,
+            self.is_pic,
+            middlewares,
+        )
+    }
+
     /// The optimization levels when optimizing the IR.
     pub fn opt_level(&mut self, opt_level: OptimizationLevel) -> &mut Self {
         self.opt_level = opt_level;
         self
     }
 
+    /// Inline the functions of a module in their callers, within the
+    /// given budget (the LLVM inlining threshold, 225 being its default
+    /// at `-O2`), or don't if `None`, the default.
+    ///
+    /// Each function is compiled separately, so this only applies when
+    /// the whole module is compiled to a single object file, as with the
+    /// native and object-file engines.
+    pub fn inline_threshold(&mut self, inline_threshold: Option<u32>) -> &mut Self {
+        self.inline_threshold = inline_threshold;
+        self
+    }
+
     /// Callbacks that will triggered in the different compilation
     /// phases in LLVM.
     pub fn callbacks(&mut self, callbacks: Option<Arc<dyn LLVMCallbacks>>) -> &mut Self {
@@ -84,6 +130,25 @@ impl LLVM {
         self
     }
 
+    /// Branch hints guiding the block layout, e.g. from a profile of a
+    /// training run, in the format of [`BranchHints`].
+    ///
+    /// They are used in addition to the hints of the branch hinting
+    /// section of the compiled modules, taking precedence over them.
+    pub fn branch_hints(&mut self, branch_hints: BranchHints) -> &mut Self {
+        self.branch_hints = Some(Arc::new(branch_hints));
+        self
+    }
+
+    /// The branch hints to compile `module` with.
+    pub(crate) fn module_branch_hints(&self, module: &ModuleInfo) -> BranchHints {
+        let mut branch_hints = BranchHints::from_module(module);
+        if let Some(ref extra) = self.branch_hints {
+            branch_hints.extend(BranchHints::clone(extra));
+        }
+        branch_hints
+    }
+
     fn reloc_mode(&self) -> RelocMode {
         if self.is_pic {
             RelocMode::PIC
@@ -206,6 +271,21 @@ impl CompilerConfig for LLVM {
         self.enable_verifier = true;
     }
 
+    /// Whether to check the store epoch at loop headers.
+    fn enable_epoch_interruption(&mut self) {
+        self.enable_epoch_interruption = true;
+    }
+
+    /// Limits the resources used to compile a module.
+    fn set_limits(&mut self, limits: CompileLimits) {
+        self.limits = limits;
+    }
+
+    /// Reuses the functions of `cache` that didn't change.
+    fn set_function_cache(&mut self, cache: Arc<FunctionCache>) {
+        self.function_cache = Some(cache);
+    }
+
     /// Transform it into the compiler.
     fn compiler(&self) -> Box<dyn Compiler + Send> {
         Box::new(LLVMCompiler::new(&self))
@@ -2,9 +2,10 @@ use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 
 use wasmer_compiler::{
-    CompileError, CompiledFunctionFrameInfo, CustomSection, CustomSectionProtection,
-    CustomSections, FunctionAddressMap, FunctionBody, InstructionAddressMap, Relocation,
-    RelocationKind, RelocationTarget, SectionBody, SectionIndex, SourceLoc,
+    CompileError, CompiledFunctionFrameInfo, CompiledFunctionStats, CustomSection,
+    CustomSectionProtection, CustomSections, FunctionAddressMap, FunctionBody,
+    InstructionAddressMap, Relocation, RelocationKind, RelocationTarget, SectionBody, SectionIndex,
+    SourceLoc,
 };
 use wasmer_types::entity::{PrimaryMap, SecondaryMap};
 use wasmer_vm::libcalls::LibCall;
@@ -289,6 +290,11 @@ where
         body_len: function_body.body.len(),
     };
 
+    let stats = CompiledFunctionStats {
+        code_size: function_body.body.len(),
+        ..Default::default()
+    };
+
     Ok(CompiledFunction {
         compiled_function: wasmer_compiler::CompiledFunction {
             body: function_body,
@@ -300,6 +306,7 @@ where
                 address_map,
                 traps: vec![],
             },
+            stats,
         },
         custom_sections,
         eh_frame_section_indices,
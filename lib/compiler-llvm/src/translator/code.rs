@@ -26,8 +26,9 @@ use crate::config::{CompiledKind, LLVM};
 use crate::object_file::{load_object_file, CompiledFunction};
 use wasmer_compiler::wasmparser::{MemoryImmediate, Operator};
 use wasmer_compiler::{
-    to_wasm_error, wptype_to_type, CompileError, FunctionBodyData, GenerateMiddlewareChain,
-    MiddlewareBinaryReader, ModuleTranslationState, RelocationTarget, Symbol, SymbolRegistry,
+    to_wasm_error, wptype_to_type, BranchHints, CompileError, FunctionBodyData,
+    GenerateMiddlewareChain, MiddlewareBinaryReader, ModuleTranslationState, RelocationTarget,
+    Symbol, SymbolRegistry,
 };
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
@@ -70,6 +71,7 @@ impl FuncTranslator {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn translate_to_module(
         &self,
         wasm_module: &ModuleInfo,
@@ -79,6 +81,7 @@ impl FuncTranslator {
         config: &LLVM,
         memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
         _table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        branch_hints: &BranchHints,
         symbol_registry: &dyn SymbolRegistry,
     ) -> Result<Module, CompileError> {
         // The function type, used for the callbacks.
@@ -217,6 +220,10 @@ impl FuncTranslator {
             wasm_module,
             symbol_registry,
             abi: &*self.abi,
+            epoch_interruption: config.enable_epoch_interruption,
+            func_index,
+            body_offset: function_body.module_offset as u32,
+            branch_hints,
         };
         fcg.ctx.add_func(
             func_index,
@@ -283,6 +290,7 @@ impl FuncTranslator {
         Ok(module)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn translate(
         &self,
         wasm_module: &ModuleInfo,
@@ -292,6 +300,7 @@ impl FuncTranslator {
         config: &LLVM,
         memory_styles: &PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &PrimaryMap<TableIndex, TableStyle>,
+        branch_hints: &BranchHints,
         symbol_registry: &dyn SymbolRegistry,
     ) -> Result<CompiledFunction, CompileError> {
         let module = self.translate_to_module(
@@ -302,6 +311,7 @@ impl FuncTranslator {
             config,
             memory_styles,
             table_styles,
+            branch_hints,
             symbol_registry,
         )?;
         let function = CompiledKind::Local(*local_func_index);
@@ -1325,10 +1335,102 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     wasm_module: &'a ModuleInfo,
     symbol_registry: &'a dyn SymbolRegistry,
     abi: &'a dyn Abi,
+    epoch_interruption: bool,
+    func_index: FunctionIndex,
+    /// The offset of the function body in the module, as branch hints
+    /// are relative to it.
+    body_offset: u32,
+    branch_hints: &'a BranchHints,
 }
 
 impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
-    fn translate_operator(&mut self, op: Operator, _source_loc: u32) -> Result<(), CompileError> {
+    /// Traps with `TrapCode::Interrupt` once the store epoch reaches its
+    /// deadline.
+    fn trap_if_epoch_deadline_reached(&mut self) {
+        let (current_ptr, deadline_ptr) = self.ctx.epoch(self.intrinsics);
+        // The epoch is advanced concurrently by the embedder, so these loads
+        // must not be hoisted out of the loop.
+        let current = self.builder.build_load(current_ptr, "epoch_current");
+        current
+            .as_instruction_value()
+            .unwrap()
+            .set_volatile(true)
+            .unwrap();
+        let deadline = self.builder.build_load(deadline_ptr, "epoch_deadline");
+        deadline
+            .as_instruction_value()
+            .unwrap()
+            .set_volatile(true)
+            .unwrap();
+        let should_trap = self.builder.build_int_compare(
+            IntPredicate::UGE,
+            current.into_int_value(),
+            deadline.into_int_value(),
+            "epoch_deadline_reached",
+        );
+
+        let should_trap = self
+            .builder
+            .build_call(
+                self.intrinsics.expect_i1,
+                &[
+                    should_trap.as_basic_value_enum(),
+                    self.intrinsics.i1_ty.const_zero().as_basic_value_enum(),
+                ],
+                "should_trap_expect",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let shouldnt_trap_block = self
+            .context
+            .append_basic_block(self.function, "shouldnt_trap_block");
+        let should_trap_block = self
+            .context
+            .append_basic_block(self.function, "should_trap_block");
+        self.builder
+            .build_conditional_branch(should_trap, should_trap_block, shouldnt_trap_block);
+        self.builder.position_at_end(should_trap_block);
+        self.builder.build_call(
+            self.intrinsics.throw_trap,
+            &[self.intrinsics.trap_interrupt],
+            "throw",
+        );
+        self.builder.build_unreachable();
+        self.builder.position_at_end(shouldnt_trap_block);
+    }
+
+    /// Applies the branch hint of the instruction at `source_loc`, if
+    /// any, to the condition `cond` of its branch.
+    fn hint_branch(&self, cond: IntValue<'ctx>, source_loc: u32) -> IntValue<'ctx> {
+        let likely = match self
+            .branch_hints
+            .get(self.func_index, source_loc - self.body_offset)
+        {
+            Some(likely) => likely,
+            None => return cond,
+        };
+        self.builder
+            .build_call(
+                self.intrinsics.expect_i1,
+                &[
+                    cond.as_basic_value_enum(),
+                    self.intrinsics
+                        .i1_ty
+                        .const_int(likely as u64, false)
+                        .as_basic_value_enum(),
+                ],
+                "branch_hint",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    fn translate_operator(&mut self, op: Operator, source_loc: u32) -> Result<(), CompileError> {
         // TODO: remove this vmctx by moving everything into CtxType. Values
         // computed off vmctx usually benefit from caching.
         let vmctx = &self.ctx.basic().into_pointer_value();
@@ -1433,6 +1535,10 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     self.state.push1(phi.as_basic_value());
                 }
 
+                if self.epoch_interruption {
+                    self.trap_if_epoch_deadline_reached();
+                }
+
                 /*
                 if self.track_state {
                     if let Some(offset) = opcode_offset {
@@ -1531,6 +1637,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     self.intrinsics.i32_zero,
                     "",
                 );
+                let cond_value = self.hint_branch(cond_value, source_loc);
                 self.builder
                     .build_conditional_branch(cond_value, *frame.br_dest(), else_block);
                 self.builder.position_at_end(else_block);
@@ -1628,6 +1735,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     self.intrinsics.i32_zero,
                     "",
                 );
+                let cond_value = self.hint_branch(cond_value, source_loc);
 
                 self.builder
                     .build_conditional_branch(cond_value, if_then_block, if_else_block);
@@ -166,6 +166,7 @@ pub struct Intrinsics<'ctx> {
     pub trap_bad_conversion_to_integer: BasicValueEnum<'ctx>,
     pub trap_unaligned_atomic: BasicValueEnum<'ctx>,
     pub trap_table_access_oob: BasicValueEnum<'ctx>,
+    pub trap_interrupt: BasicValueEnum<'ctx>,
 
     // VM intrinsics.
     pub throw_trap: FunctionValue<'ctx>,
@@ -436,6 +437,9 @@ impl<'ctx> Intrinsics<'ctx> {
             trap_table_access_oob: i32_ty
                 .const_int(TrapCode::TableAccessOutOfBounds as _, false)
                 .as_basic_value_enum(),
+            trap_interrupt: i32_ty
+                .const_int(TrapCode::Interrupt as _, false)
+                .as_basic_value_enum(),
 
             // VM intrinsics.
             throw_trap: module.add_function(
@@ -545,6 +549,7 @@ pub struct CtxType<'ctx, 'a> {
     cached_functions: HashMap<FunctionIndex, FunctionCache<'ctx>>,
     cached_memory_grow: HashMap<MemoryIndex, PointerValue<'ctx>>,
     cached_memory_size: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_epoch: Option<(PointerValue<'ctx>, PointerValue<'ctx>)>,
 
     offsets: VMOffsets,
 }
@@ -570,6 +575,7 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             cached_functions: HashMap::new(),
             cached_memory_grow: HashMap::new(),
             cached_memory_size: HashMap::new(),
+            cached_epoch: None,
 
             // TODO: pointer width
             offsets: VMOffsets::new(8, &wasm_module),
@@ -1015,6 +1021,48 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         })
     }
 
+    /// Returns pointers to the `current` and `deadline` fields of the
+    /// store's `VMEpoch`.
+    pub fn epoch(
+        &mut self,
+        intrinsics: &Intrinsics<'ctx>,
+    ) -> (PointerValue<'ctx>, PointerValue<'ctx>) {
+        let (cached_epoch, offsets, cache_builder, ctx_ptr_value) = (
+            &mut self.cached_epoch,
+            &self.offsets,
+            &self.cache_builder,
+            &self.ctx_ptr_value,
+        );
+        *cached_epoch.get_or_insert_with(|| {
+            let offset = intrinsics
+                .i32_ty
+                .const_int(offsets.vmctx_epoch().into(), false);
+            let epoch_ptr_ptr = unsafe { cache_builder.build_gep(*ctx_ptr_value, &[offset], "") };
+            let epoch_ptr_ptr = cache_builder
+                .build_bitcast(
+                    epoch_ptr_ptr,
+                    intrinsics.i8_ptr_ty.ptr_type(AddressSpace::Generic),
+                    "",
+                )
+                .into_pointer_value();
+            let epoch_ptr = cache_builder
+                .build_load(epoch_ptr_ptr, "epoch")
+                .into_pointer_value();
+
+            let field = |offset: u8| {
+                let offset = intrinsics.i32_ty.const_int(offset.into(), false);
+                let ptr = unsafe { cache_builder.build_gep(epoch_ptr, &[offset], "") };
+                cache_builder
+                    .build_bitcast(ptr, intrinsics.i64_ptr_ty, "")
+                    .into_pointer_value()
+            };
+            (
+                field(offsets.vmepoch_current()),
+                field(offsets.vmepoch_deadline()),
+            )
+        })
+    }
+
     pub fn memory_grow(
         &mut self,
         memory_index: MemoryIndex,
@@ -7,9 +7,9 @@ use wasmer_compiler::wasmparser::{
     MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
 };
 use wasmer_compiler::{
-    CompiledFunction, CompiledFunctionFrameInfo, CustomSection, CustomSectionProtection,
-    FunctionBody, Relocation, RelocationKind, RelocationTarget, SectionBody, SectionIndex,
-    TrapInformation,
+    CompiledFunction, CompiledFunctionFrameInfo, CompiledFunctionStats, CustomSection,
+    CustomSectionProtection, FunctionBody, Relocation, RelocationKind, RelocationTarget,
+    SectionBody, SectionIndex, TrapInformation,
 };
 use wasmer_types::{
     entity::{EntityRef, PrimaryMap, SecondaryMap},
@@ -88,6 +88,7 @@ struct SpecialLabelSet {
     table_access_oob: DynamicLabel,
     indirect_call_null: DynamicLabel,
     bad_signature: DynamicLabel,
+    interrupt: DynamicLabel,
 }
 
 /// A trap table for a `RunnableModuleInfo`.
@@ -315,6 +316,38 @@ impl<'a> FuncGen<'a> {
         self.trap_table.offset_to_code.insert(offset, code);
     }
 
+    /// Jumps to the interrupt trap once the store epoch reaches its deadline.
+    fn emit_epoch_check(&mut self) {
+        let current = self.machine.acquire_temp_gpr().unwrap();
+        let deadline = self.machine.acquire_temp_gpr().unwrap();
+
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_epoch() as i32,
+            ),
+            Location::GPR(current),
+        );
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(current, self.vmoffsets.vmepoch_deadline() as i32),
+            Location::GPR(deadline),
+        );
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(current, self.vmoffsets.vmepoch_current() as i32),
+            Location::GPR(current),
+        );
+        self.assembler
+            .emit_cmp(Size::S64, Location::GPR(deadline), Location::GPR(current));
+        self.assembler
+            .emit_jmp(Condition::AboveEqual, self.special_labels.interrupt);
+
+        self.machine.release_temp_gpr(deadline);
+        self.machine.release_temp_gpr(current);
+    }
+
     /// Canonicalizes the floating point value at `input` into `output`.
     fn canonicalize_nan(&mut self, sz: Size, input: Location, output: Location) {
         let tmp1 = self.machine.acquire_temp_xmm().unwrap();
@@ -1808,6 +1841,7 @@ impl<'a> FuncGen<'a> {
             table_access_oob: assembler.get_label(),
             indirect_call_null: assembler.get_label(),
             bad_signature: assembler.get_label(),
+            interrupt: assembler.get_label(),
         };
 
         let mut fg = FuncGen {
@@ -5612,7 +5646,9 @@ impl<'a> FuncGen<'a> {
                 });
                 self.assembler.emit_label(label);
 
-                // TODO: Re-enable interrupt signal check without branching
+                if self.config.enable_epoch_interruption {
+                    self.emit_epoch_check();
+                }
             }
             Operator::Nop => {}
             Operator::MemorySize { reserved } => {
@@ -8163,11 +8199,20 @@ impl<'a> FuncGen<'a> {
         self.mark_address_with_trap_code(TrapCode::BadSignature);
         self.assembler.emit_ud2();
 
+        self.assembler.emit_label(self.special_labels.interrupt);
+        self.mark_address_with_trap_code(TrapCode::Interrupt);
+        self.assembler.emit_ud2();
+
         // Notify the assembler backend to generate necessary code at end of function.
         self.assembler.finalize_function();
+        let body = self.assembler.finalize().unwrap().to_vec();
+        let stats = CompiledFunctionStats {
+            code_size: body.len(),
+            ..Default::default()
+        };
         CompiledFunction {
             body: FunctionBody {
-                body: self.assembler.finalize().unwrap().to_vec(),
+                body,
                 unwind_info: None,
             },
             relocations: self.relocations,
@@ -8185,6 +8230,7 @@ impl<'a> FuncGen<'a> {
                     .collect(),
                 ..Default::default()
             },
+            stats,
         }
     }
 }
@@ -9,14 +9,17 @@ use crate::codegen_x64::{
 use crate::config::Singlepass;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
+use std::time::Instant;
 use wasmer_compiler::wasmparser::BinaryReaderError;
 use wasmer_compiler::TrapInformation;
-use wasmer_compiler::{Compilation, CompileError, CompiledFunction, Compiler, SectionIndex};
+use wasmer_compiler::{
+    Compilation, CompileError, CompileLimits, CompiledFunction, Compiler, SectionIndex,
+};
 use wasmer_compiler::{
     CompileModuleInfo, CompilerConfig, GenerateMiddlewareChain, MiddlewareBinaryReader,
     ModuleTranslationState, Target,
 };
-use wasmer_compiler::{FunctionBody, FunctionBodyData};
+use wasmer_compiler::{FunctionBody, FunctionBodyData, FunctionKeys};
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, FunctionType, LocalFunctionIndex, MemoryIndex, TableIndex};
 use wasmer_vm::{ModuleInfo, TrapCode, VMOffsets};
@@ -42,11 +45,15 @@ impl SinglepassCompiler {
 }
 
 impl Compiler for SinglepassCompiler {
+    fn limits(&self) -> CompileLimits {
+        self.config.limits
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
         &self,
-        _target: &Target,
+        target: &Target,
         compile_info: &CompileModuleInfo,
         _module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
@@ -68,11 +75,35 @@ impl Compiler for SinglepassCompiler {
             .collect::<Vec<_>>()
             .into_iter()
             .collect();
+        let budget = self.config.limits.start();
+        let function_cache = self.config.function_cache.as_ref().map(|cache| {
+            let keys = FunctionKeys::new(
+                "singlepass",
+                &self.config.fingerprint(),
+                target,
+                compile_info,
+            );
+            (cache, keys)
+        });
         let functions = function_body_inputs
             .into_iter()
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map(|(i, input)| {
+                self.config.limits.check_function(*i, input)?;
+                budget.check()?;
+                let start = Instant::now();
+                let entry = function_cache
+                    .as_ref()
+                    .map(|(cache, keys)| (cache, keys.key(*i, input, &())));
+                if let Some((cache, key)) = &entry {
+                    if let Some(cached) = cache.get(key, input.module_offset) {
+                        let mut compiled_function = cached.function;
+                        compiled_function.stats.compile_time = start.elapsed();
+                        return Ok(compiled_function);
+                    }
+                }
+
                 let middleware_chain = self.config.middlewares.generate_middleware_chain(*i);
                 let mut reader =
                     MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
@@ -107,7 +138,12 @@ impl Compiler for SinglepassCompiler {
                     generator.feed_operator(op).map_err(to_compile_error)?;
                 }
 
-                Ok(generator.finalize())
+                let mut compiled_function = generator.finalize();
+                compiled_function.stats.compile_time = start.elapsed();
+                if let Some((cache, key)) = entry {
+                    cache.insert(key, input.module_offset, &compiled_function, None);
+                }
+                Ok(compiled_function)
             })
             .collect::<Result<Vec<CompiledFunction>, CompileError>>()?
             .into_iter()
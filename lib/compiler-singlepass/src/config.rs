@@ -2,14 +2,21 @@
 #![allow(unused_imports, dead_code)]
 
 use crate::compiler::SinglepassCompiler;
+use std::hash::Hash;
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, CompilerConfig, CpuFeature, FunctionMiddlewareGenerator, Target};
+use wasmer_compiler::{
+    CompileLimits, Compiler, CompilerConfig, CpuFeature, FunctionCache,
+    FunctionMiddlewareGenerator, Target,
+};
 use wasmer_types::Features;
 
 #[derive(Debug, Clone)]
 pub struct Singlepass {
     pub(crate) enable_nan_canonicalization: bool,
     pub(crate) enable_stack_check: bool,
+    pub(crate) enable_epoch_interruption: bool,
+    pub(crate) limits: CompileLimits,
+    pub(crate) function_cache: Option<Arc<FunctionCache>>,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn FunctionMiddlewareGenerator>>,
 }
@@ -21,6 +28,9 @@ impl Singlepass {
         Self {
             enable_nan_canonicalization: true,
             enable_stack_check: false,
+            enable_epoch_interruption: false,
+            limits: CompileLimits::default(),
+            function_cache: None,
             middlewares: vec![],
         }
     }
@@ -45,6 +55,22 @@ impl Singlepass {
         self.enable_nan_canonicalization = enable;
         self
     }
+
+    /// The settings the compiled code depends on, identifying it in the
+    /// function cache.
+    pub(crate) fn fingerprint(&self) -> impl Hash {
+        let middlewares = self
+            .middlewares
+            .iter()
+            .map(|middleware| format!("{:?}", middleware))
+            .collect::<Vec<_>>();
+        (
+            self.enable_nan_canonicalization,
+            self.enable_stack_check,
+            self.enable_epoch_interruption,
+            middlewares,
+        )
+    }
 }
 
 impl CompilerConfig for Singlepass {
@@ -53,6 +79,18 @@ impl CompilerConfig for Singlepass {
         // PIC code.
     }
 
+    fn enable_epoch_interruption(&mut self) {
+        self.enable_epoch_interruption = true;
+    }
+
+    fn set_limits(&mut self, limits: CompileLimits) {
+        self.limits = limits;
+    }
+
+    fn set_function_cache(&mut self, cache: Arc<FunctionCache>) {
+        self.function_cache = Some(cache);
+    }
+
     /// Transform it into the compiler
     fn compiler(&self) -> Box<dyn Compiler + Send> {
         Box::new(SinglepassCompiler::new(&self))
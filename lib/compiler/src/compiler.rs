@@ -3,10 +3,13 @@
 
 use crate::error::CompileError;
 use crate::function::Compilation;
+use crate::function_cache::FunctionCache;
 use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
+use crate::limits::CompileLimits;
 use crate::module::CompileModuleInfo;
 use crate::target::Target;
+use crate::translator::describe_function_at_offset;
 use crate::translator::FunctionMiddlewareGenerator;
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
@@ -36,6 +39,29 @@ pub trait CompilerConfig {
         // in case they create an IR that they can verify.
     }
 
+    /// Enable epoch interruption.
+    ///
+    /// Compiled code checks the store epoch at every loop header and traps
+    /// once its deadline is reached, see `Store::set_epoch_deadline`.
+    fn enable_epoch_interruption(&mut self) {
+        // By default we do nothing, each backend will need to customize this
+        // in case they can emit epoch checks.
+    }
+
+    /// Limit the resources used to compile a module, see [`CompileLimits`].
+    fn set_limits(&mut self, _limits: CompileLimits) {
+        // By default we do nothing, each backend will need to customize this
+        // in case they can enforce the limits.
+    }
+
+    /// Reuse the compiled functions of `cache` whose body and context
+    /// didn't change, and cache the newly compiled ones, see
+    /// [`FunctionCache`].
+    fn set_function_cache(&mut self, _cache: Arc<FunctionCache>) {
+        // By default we do nothing, each backend will need to customize this
+        // in case they can reuse compiled functions.
+    }
+
     /// Gets the custom compiler config
     fn compiler(&self) -> Box<dyn Compiler + Send>;
 
@@ -50,14 +76,22 @@ pub trait CompilerConfig {
 
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
 pub trait Compiler {
+    /// The limits on the resources used to compile a module.
+    fn limits(&self) -> CompileLimits {
+        CompileLimits::default()
+    }
+
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
+    /// Modules exceeding the maximum size of the [`CompileLimits`] are
+    /// rejected before being parsed.
     fn validate_module<'data>(
         &self,
         features: &Features,
         data: &'data [u8],
     ) -> Result<(), CompileError> {
+        self.limits().check_module(data)?;
         let config = ValidatingParserConfig {
             operator_config: OperatorValidatorConfig {
                 enable_threads: features.threads,
@@ -68,7 +102,12 @@ pub trait Compiler {
                 enable_multi_value: features.multi_value,
             },
         };
-        validate(data, Some(config)).map_err(|e| CompileError::Validate(format!("{}", e)))
+        validate(data, Some(config)).map_err(|e| {
+            match describe_function_at_offset(data, e.offset()) {
+                Some(function) => CompileError::Validate(format!("{} in function {}", e, function)),
+                None => CompileError::Validate(format!("{}", e)),
+            }
+        })
     }
 
     /// Compiles a parsed module.
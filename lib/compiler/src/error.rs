@@ -15,6 +15,7 @@ use thiserror::Error;
 /// [compiler-error]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/CompileError
 #[derive(Debug)]
 #[cfg_attr(feature = "std", derive(Error))]
+#[non_exhaustive]
 pub enum CompileError {
     /// A Wasm translation error occured.
     #[cfg_attr(feature = "std", error("WebAssembly translation error: {0}"))]
@@ -35,6 +36,25 @@ pub enum CompileError {
     /// Insufficient resources available for execution.
     #[cfg_attr(feature = "std", error("Insufficient resources: {0}"))]
     Resource(String),
+
+    /// The signature of the module is missing, untrusted or invalid.
+    #[cfg_attr(feature = "std", error("Signature error: {0}"))]
+    Signature(String),
+}
+
+impl CompileError {
+    /// Returns a stable code identifying the kind of error, e.g. to
+    /// report it through FFI or in metrics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Wasm(_) => "wasm",
+            Self::Codegen(_) => "codegen",
+            Self::Validate(_) => "validate",
+            Self::UnsupportedFeature(_) => "unsupported_feature",
+            Self::Resource(_) => "resource",
+            Self::Signature(_) => "signature",
+        }
+    }
 }
 
 /// A WebAssembly translation error.
@@ -43,6 +63,7 @@ pub enum CompileError {
 /// to describe the failure.
 #[derive(Debug)]
 #[cfg_attr(feature = "std", derive(Error))]
+#[non_exhaustive]
 pub enum WasmError {
     /// The input WebAssembly code is invalid.
     ///
@@ -86,3 +107,27 @@ pub enum ParseCpuFeatureError {
 
 /// A convenient alias for a `Result` that uses `WasmError` as the error type.
 pub type WasmResult<T> = Result<T, WasmError>;
+
+#[cfg(test)]
+mod tests {
+    use super::{CompileError, WasmError};
+    use crate::lib::std::string::ToString;
+
+    #[test]
+    fn codes() {
+        let errors = [
+            (CompileError::Wasm(WasmError::ImplLimitExceeded), "wasm"),
+            (CompileError::Codegen("".to_string()), "codegen"),
+            (CompileError::Validate("".to_string()), "validate"),
+            (
+                CompileError::UnsupportedFeature("".to_string()),
+                "unsupported_feature",
+            ),
+            (CompileError::Resource("".to_string()), "resource"),
+            (CompileError::Signature("".to_string()), "signature"),
+        ];
+        for (error, code) in errors.iter() {
+            assert_eq!(error.code(), *code, "{:?}", error);
+        }
+    }
+}
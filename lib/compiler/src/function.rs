@@ -12,6 +12,7 @@ use crate::lib::std::vec::Vec;
 use crate::section::{CustomSection, SectionIndex};
 use crate::trap::TrapInformation;
 use crate::{CompiledFunctionUnwindInfo, FunctionAddressMap, JumpTableOffsets, Relocation};
+use core::time::Duration;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 use wasmer_types::entity::PrimaryMap;
@@ -33,6 +34,24 @@ pub struct CompiledFunctionFrameInfo {
     pub address_map: FunctionAddressMap,
 }
 
+/// Statistics about the compilation of a function.
+///
+/// They help finding the functions that make a module big or slow
+/// to compile.
+#[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CompiledFunctionStats {
+    /// The size of the generated machine code, in bytes.
+    pub code_size: usize,
+
+    /// The time spent compiling the function.
+    pub compile_time: Duration,
+
+    /// The number of values the register allocator spilled to the
+    /// stack, when the compiler reports it.
+    pub register_spills: Option<u32>,
+}
+
 /// The function body.
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -64,6 +83,9 @@ pub struct CompiledFunction {
 
     /// The frame information.
     pub frame_info: CompiledFunctionFrameInfo,
+
+    /// The compilation statistics.
+    pub stats: CompiledFunctionStats,
 }
 
 /// The compiled functions map (index in the Wasm -> function)
@@ -207,6 +229,14 @@ impl Compilation {
             .collect::<PrimaryMap<LocalFunctionIndex, _>>()
     }
 
+    /// Gets functions compilation statistics.
+    pub fn get_function_stats(&self) -> PrimaryMap<LocalFunctionIndex, CompiledFunctionStats> {
+        self.functions
+            .iter()
+            .map(|(_, func)| func.stats.clone())
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+    }
+
     /// Gets function call trampolines.
     pub fn get_function_call_trampolines(&self) -> PrimaryMap<SignatureIndex, FunctionBody> {
         self.function_call_trampolines.clone()
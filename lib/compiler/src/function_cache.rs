@@ -0,0 +1,253 @@
+//! A cache of compiled functions, reusing the code of the functions that
+//! didn't change when recompiling a new version of a module.
+
+use crate::function::CompiledFunction;
+use crate::module::CompileModuleInfo;
+use crate::sourceloc::SourceLoc;
+use crate::target::Target;
+use crate::translator::FunctionBodyData;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer_types::LocalFunctionIndex;
+
+/// A cache of compiled functions, set with
+/// `CompilerConfig::set_function_cache`.
+///
+/// A function is identified by a content hash of its body, of its index
+/// and of the declarations of the module it depends on (signatures,
+/// functions, tables, memories and globals), so recompiling a module
+/// where only some function bodies changed reuses the code of the other
+/// functions. The functions are cached with their offset in the module,
+/// so their trap and address information stays right when the functions
+/// before them change size.
+///
+/// The settings of the compiler the code depends on, such as the NaN
+/// canonicalization, the epoch interruption, the optimization level and
+/// the middlewares, are part of the identity of the functions too, so a
+/// cache can be shared by differently configured compilers. The
+/// middlewares are identified by their `Debug` representation, which
+/// must then reflect their settings.
+#[derive(Default)]
+pub struct FunctionCache {
+    functions: Mutex<HashMap<FunctionKey, CachedFunction>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl FunctionCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the function of `key`, moved to `module_offset`, if
+    /// cached.
+    pub fn get(&self, key: &FunctionKey, module_offset: usize) -> Option<CachedFunction> {
+        let cached = self.functions.lock().unwrap().get(key).cloned();
+        match cached {
+            Some(mut cached) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                cached.move_to(module_offset);
+                Some(cached)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Caches the `function` compiled from a body at `module_offset`,
+    /// with the compiler specific `data` it needs to be reused.
+    pub fn insert(
+        &self,
+        key: FunctionKey,
+        module_offset: usize,
+        function: &CompiledFunction,
+        data: Option<Arc<dyn Any + Send + Sync>>,
+    ) {
+        let cached = CachedFunction {
+            function: function.clone(),
+            data,
+            module_offset,
+        };
+        self.functions.lock().unwrap().insert(key, cached);
+    }
+
+    /// Returns the number of functions in the cache.
+    pub fn len(&self) -> usize {
+        self.functions.lock().unwrap().len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of functions reused from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of functions looked up without being found.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Removes all the functions from the cache.
+    pub fn clear(&self) {
+        self.functions.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for FunctionCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FunctionCache")
+            .field("len", &self.len())
+            .field("hits", &self.hits())
+            .field("misses", &self.misses())
+            .finish()
+    }
+}
+
+/// A function found in a [`FunctionCache`].
+#[derive(Clone)]
+pub struct CachedFunction {
+    /// The compiled function.
+    pub function: CompiledFunction,
+    /// The compiler specific data cached with the function, e.g. its
+    /// unwind information when it's kept out of the function.
+    pub data: Option<Arc<dyn Any + Send + Sync>>,
+    module_offset: usize,
+}
+
+impl CachedFunction {
+    /// Moves the source locations of the function to a body at
+    /// `module_offset`.
+    fn move_to(&mut self, module_offset: usize) {
+        let from = self.module_offset as u32;
+        let to = module_offset as u32;
+        let move_loc = |loc: &mut SourceLoc| {
+            if !loc.is_default() {
+                *loc = SourceLoc::new(loc.bits().wrapping_sub(from).wrapping_add(to));
+            }
+        };
+        let frame_info = &mut self.function.frame_info;
+        for trap in frame_info.traps.iter_mut() {
+            move_loc(&mut trap.source_loc);
+        }
+        let address_map = &mut frame_info.address_map;
+        for instruction in address_map.instructions.iter_mut() {
+            move_loc(&mut instruction.srcloc);
+        }
+        move_loc(&mut address_map.start_srcloc);
+        move_loc(&mut address_map.end_srcloc);
+        self.module_offset = module_offset;
+    }
+}
+
+impl fmt::Debug for CachedFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CachedFunction")
+            .field("function", &self.function)
+            .field("module_offset", &self.module_offset)
+            .finish()
+    }
+}
+
+/// The identity of a function in a [`FunctionCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FunctionKey([u8; 32]);
+
+/// The keys of the functions of a module, hashing what their code
+/// depends on once per module.
+#[derive(Clone)]
+pub struct FunctionKeys {
+    context: blake3::Hasher,
+}
+
+impl FunctionKeys {
+    /// Creates the keys of the functions of the module of `compile_info`,
+    /// compiled for `target` by the `compiler` with the settings `config`
+    /// the code depends on.
+    pub fn new<T: Hash + ?Sized>(
+        compiler: &str,
+        config: &T,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+    ) -> Self {
+        let mut hasher = ContentHasher(blake3::Hasher::new());
+        compiler.hash(&mut hasher);
+        config.hash(&mut hasher);
+        target.hash(&mut hasher);
+        compile_info.features.hash(&mut hasher);
+        let module = &compile_info.module;
+        module.signatures.len().hash(&mut hasher);
+        for signature in module.signatures.values() {
+            signature.hash(&mut hasher);
+        }
+        module.functions.len().hash(&mut hasher);
+        for signature in module.functions.values() {
+            signature.hash(&mut hasher);
+        }
+        module.tables.len().hash(&mut hasher);
+        for table in module.tables.values() {
+            table.hash(&mut hasher);
+        }
+        module.memories.len().hash(&mut hasher);
+        for memory in module.memories.values() {
+            memory.hash(&mut hasher);
+        }
+        module.globals.len().hash(&mut hasher);
+        for global in module.globals.values() {
+            global.hash(&mut hasher);
+        }
+        module.num_imported_functions.hash(&mut hasher);
+        module.num_imported_tables.hash(&mut hasher);
+        module.num_imported_memories.hash(&mut hasher);
+        module.num_imported_globals.hash(&mut hasher);
+        compile_info.memory_styles.len().hash(&mut hasher);
+        for style in compile_info.memory_styles.values() {
+            style.hash(&mut hasher);
+        }
+        compile_info.table_styles.len().hash(&mut hasher);
+        for style in compile_info.table_styles.values() {
+            style.hash(&mut hasher);
+        }
+        Self { context: hasher.0 }
+    }
+
+    /// Returns the key of the function `index` with `body`, given the
+    /// `extra` inputs of the compiler, e.g. the hints of the function.
+    pub fn key<T: Hash + ?Sized>(
+        &self,
+        index: LocalFunctionIndex,
+        body: &FunctionBodyData,
+        extra: &T,
+    ) -> FunctionKey {
+        let mut hasher = ContentHasher(self.context.clone());
+        index.hash(&mut hasher);
+        body.data.hash(&mut hasher);
+        extra.hash(&mut hasher);
+        FunctionKey(*hasher.0.finalize().as_bytes())
+    }
+}
+
+/// Feeds the values hashed with [`Hash`] to a content hash.
+struct ContentHasher(blake3::Hasher);
+
+impl Hasher for ContentHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.0.finalize().as_bytes()[..8]);
+        u64::from_le_bytes(bytes)
+    }
+}
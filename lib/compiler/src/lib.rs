@@ -54,7 +54,11 @@ mod address_map;
 mod compiler;
 mod error;
 mod function;
+#[cfg(feature = "translator")]
+mod function_cache;
 mod jump_table;
+#[cfg(feature = "translator")]
+mod limits;
 mod module;
 mod relocation;
 mod target;
@@ -71,10 +75,14 @@ pub use crate::address_map::{FunctionAddressMap, InstructionAddressMap};
 pub use crate::compiler::{Compiler, CompilerConfig, Symbol, SymbolRegistry};
 pub use crate::error::{CompileError, ParseCpuFeatureError, WasmError, WasmResult};
 pub use crate::function::{
-    Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
-    Functions,
+    Compilation, CompiledFunction, CompiledFunctionFrameInfo, CompiledFunctionStats,
+    CustomSections, Dwarf, FunctionBody, Functions,
 };
+#[cfg(feature = "translator")]
+pub use crate::function_cache::{CachedFunction, FunctionCache, FunctionKey, FunctionKeys};
 pub use crate::jump_table::{JumpTable, JumpTableOffsets};
+#[cfg(feature = "translator")]
+pub use crate::limits::{CompileBudget, CompileLimits};
 pub use crate::module::CompileModuleInfo;
 pub use crate::relocation::{Relocation, RelocationKind, RelocationTarget, Relocations};
 pub use crate::section::{CustomSection, CustomSectionProtection, SectionBody, SectionIndex};
@@ -85,9 +93,13 @@ pub use crate::target::{
 };
 #[cfg(feature = "translator")]
 pub use crate::translator::{
-    to_wasm_error, translate_module, wptype_to_type, FunctionBodyData, FunctionMiddleware,
-    FunctionMiddlewareGenerator, GenerateMiddlewareChain, MiddlewareBinaryReader,
-    MiddlewareReaderState, ModuleEnvironment, ModuleInfoTranslation, ModuleTranslationState,
+    analyze_module, describe_function_at_offset, instrument_function_entries, split_module,
+    to_wasm_error, translate_module, tree_shake, wptype_to_type, BranchHints, FunctionAnalysis,
+    FunctionBodyData, FunctionMiddleware, FunctionMiddlewareGenerator, GenerateMiddlewareChain,
+    MiddlewareBinaryReader, MiddlewareReaderState, ModuleAnalysis, ModuleEnvironment,
+    ModuleInfoTranslation, ModuleTranslationState, BRANCH_HINT_SECTION, DEBUG_ATTACHED_EXPORT,
+    DEBUG_HOOK_SLOT_EXPORT, DEBUG_MEMORY_EXPORT, DEBUG_TABLE_EXPORT, SPLIT_LOADED_EXPORT,
+    SPLIT_NAMESPACE, SPLIT_TABLE_EXPORT,
 };
 pub use crate::trap::TrapInformation;
 pub use crate::unwind::CompiledFunctionUnwindInfo;
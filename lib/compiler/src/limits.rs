@@ -0,0 +1,153 @@
+//! Limits on the resources used by the compilers, so pathological
+//! modules are rejected instead of exhausting the host.
+
+use crate::error::CompileError;
+use crate::lib::std::string::ToString;
+use crate::translator::FunctionBodyData;
+use std::time::{Duration, Instant};
+use wasmer_types::LocalFunctionIndex;
+use wasmparser::{BinaryReader, BinaryReaderError};
+
+/// The limits on the resources used to compile a module, set with
+/// `CompilerConfig::set_limits`.
+///
+/// No limit is set by default. A module exceeding a limit fails to
+/// compile with a [`CompileError::Resource`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompileLimits {
+    /// The maximum size of a module, in bytes.
+    pub max_module_size: Option<usize>,
+    /// The maximum size of the body of a function, in bytes.
+    pub max_function_size: Option<usize>,
+    /// The maximum number of locals of a function, not counting its
+    /// parameters.
+    pub max_locals: Option<u32>,
+    /// The maximum time spent compiling the functions of a module.
+    pub time_budget: Option<Duration>,
+}
+
+impl CompileLimits {
+    /// Creates limits with no limit set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum size of a module, in bytes.
+    pub fn max_module_size(&mut self, size: usize) -> &mut Self {
+        self.max_module_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum size of the body of a function, in bytes.
+    pub fn max_function_size(&mut self, size: usize) -> &mut Self {
+        self.max_function_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum number of locals of a function.
+    pub fn max_locals(&mut self, locals: u32) -> &mut Self {
+        self.max_locals = Some(locals);
+        self
+    }
+
+    /// Sets the maximum time spent compiling the functions of a module.
+    ///
+    /// The budget is checked before compiling each function, so a
+    /// compilation exceeding it stops at the next function; bounding the
+    /// size of the functions bounds the overrun.
+    pub fn time_budget(&mut self, budget: Duration) -> &mut Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Checks the size of the module `data`.
+    pub fn check_module(&self, data: &[u8]) -> Result<(), CompileError> {
+        match self.max_module_size {
+            Some(max) if data.len() > max => Err(CompileError::Resource(format!(
+                "the module is {} bytes long, more than the limit of {} bytes",
+                data.len(),
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size and the number of locals of the function `index`.
+    pub fn check_function(
+        &self,
+        index: LocalFunctionIndex,
+        body: &FunctionBodyData,
+    ) -> Result<(), CompileError> {
+        if let Some(max) = self.max_function_size {
+            if body.data.len() > max {
+                return Err(CompileError::Resource(format!(
+                    "the body of the local function {} is {} bytes long, more than the limit of {} bytes",
+                    index.as_u32(),
+                    body.data.len(),
+                    max
+                )));
+            }
+        }
+        if let Some(max) = self.max_locals {
+            let locals = count_locals(body)?;
+            if locals > max as usize {
+                return Err(CompileError::Resource(format!(
+                    "the local function {} has {} locals, more than the limit of {}",
+                    index.as_u32(),
+                    locals,
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the [`time_budget`](Self::time_budget) of a compilation.
+    pub fn start(&self) -> CompileBudget {
+        CompileBudget {
+            budget: self.time_budget,
+            deadline: self.time_budget.map(|budget| Instant::now() + budget),
+        }
+    }
+}
+
+/// The time left to compile a module, as started by
+/// [`CompileLimits::start`].
+#[derive(Debug, Clone, Copy)]
+pub struct CompileBudget {
+    budget: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl CompileBudget {
+    /// Fails if the time budget is exhausted.
+    pub fn check(&self) -> Result<(), CompileError> {
+        match (self.budget, self.deadline) {
+            (Some(budget), Some(deadline)) if Instant::now() >= deadline => {
+                Err(CompileError::Resource(format!(
+                    "the compilation took longer than its budget of {:?}",
+                    budget
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Counts the locals declared by a function body, without allocating
+/// them.
+fn count_locals(body: &FunctionBodyData) -> Result<usize, CompileError> {
+    let mut reader = BinaryReader::new_with_offset(body.data, body.module_offset);
+    let mut locals = 0;
+    let declarations = reader.read_local_count().map_err(to_compile_error)?;
+    for _ in 0..declarations {
+        reader
+            .read_local_decl(&mut locals)
+            .map_err(to_compile_error)?;
+    }
+    Ok(locals)
+}
+
+fn to_compile_error(error: BinaryReaderError) -> CompileError {
+    CompileError::Validate(error.to_string())
+}
@@ -0,0 +1,494 @@
+//! Static analysis of the stack and memory requirements of a module,
+//! without compiling it.
+
+use super::environ::{FunctionBodyData, ModuleEnvironment};
+use super::error::to_wasm_error;
+use super::state::ModuleTranslationState;
+use crate::lib::std::collections::HashSet;
+use crate::lib::std::{string::String, vec::Vec};
+use crate::WasmResult;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{Bytes, ExportIndex, FunctionIndex, LocalFunctionIndex, SignatureIndex};
+use wasmer_vm::ModuleInfo;
+use wasmparser::{BinaryReader, Operator, TypeOrFuncType};
+
+/// The estimated stack requirements of a function.
+///
+/// The estimates are conservative: they are upper bounds of what the
+/// function needs, counted in WebAssembly values (up to 16 bytes each,
+/// for a `v128`). The code of the imported functions it calls isn't
+/// accounted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionAnalysis {
+    /// The number of locals of the function, including its parameters.
+    pub locals: u32,
+    /// The maximum height of the value stack in the function body.
+    pub max_operand_stack: u32,
+    /// The maximum depth of the calls starting from the function,
+    /// including itself, or `None` if it's unbounded, e.g. because the
+    /// function is recursive.
+    pub max_call_depth: Option<u32>,
+    /// The maximum number of values (locals and operands) live on the
+    /// stack during a call of the function, or `None` if it's
+    /// unbounded.
+    pub max_stack_values: Option<u64>,
+}
+
+/// The estimated requirements of a module, see [`analyze_module`].
+#[derive(Debug, Clone)]
+pub struct ModuleAnalysis {
+    /// The analyses of the functions defined in the module.
+    pub functions: PrimaryMap<LocalFunctionIndex, FunctionAnalysis>,
+    /// The analyses of the exported functions defined in the module,
+    /// with their export name.
+    pub exports: Vec<(String, FunctionAnalysis)>,
+    /// The size of the memories defined in the module when it's
+    /// instantiated.
+    pub initial_memory: Bytes,
+    /// The maximum size of the memories defined in the module, or
+    /// `None` if one of them has no maximum.
+    pub maximum_memory: Option<Bytes>,
+}
+
+impl ModuleAnalysis {
+    /// Returns the analysis of the exported function `name`, if any.
+    pub fn export(&self, name: &str) -> Option<&FunctionAnalysis> {
+        self.exports
+            .iter()
+            .find(|(export, _)| export == name)
+            .map(|(_, analysis)| analysis)
+    }
+}
+
+/// What is known of a function body, before following its calls.
+struct BodyAnalysis {
+    locals: u32,
+    max_operand_stack: u32,
+    callees: HashSet<FunctionIndex>,
+    indirect_callees: HashSet<SignatureIndex>,
+}
+
+/// A block of a function body being analyzed.
+struct Frame {
+    /// The height of the value stack below the block.
+    height: u32,
+    params: u32,
+    results: u32,
+}
+
+/// Estimates the maximum stack depth and the memory needed by the
+/// functions of a valid WebAssembly module.
+///
+/// Indirect calls are assumed to target any function of the matching
+/// signature in the element segments, unless a table is imported or
+/// exported, in which case the host can add any function to it and the
+/// call depth is unbounded.
+pub fn analyze_module(data: &[u8]) -> WasmResult<ModuleAnalysis> {
+    let translation = ModuleEnvironment::new().translate(data)?;
+    let module = &translation.module;
+    let state = translation
+        .module_translation
+        .as_ref()
+        .expect("the module translation state is set by the translation");
+
+    let bodies = translation
+        .function_body_inputs
+        .iter()
+        .map(|(local_index, body)| {
+            analyze_body(module, state, module.func_index(local_index), body)
+        })
+        .collect::<WasmResult<Vec<_>>>()?;
+
+    let mut call_graph = CallGraph {
+        module,
+        bodies: &bodies,
+        indirect_targets: indirect_targets(module),
+        results: vec![Visit::Pending; bodies.len()],
+    };
+    let functions = bodies
+        .iter()
+        .enumerate()
+        .map(|(index, body)| {
+            let (max_call_depth, max_stack_values) =
+                match call_graph.visit(LocalFunctionIndex::from_u32(index as u32)) {
+                    Some((depth, values)) => (Some(depth), Some(values)),
+                    None => (None, None),
+                };
+            FunctionAnalysis {
+                locals: body.locals,
+                max_operand_stack: body.max_operand_stack,
+                max_call_depth,
+                max_stack_values,
+            }
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+    let exports = module
+        .exports
+        .iter()
+        .filter_map(|(name, export)| match export {
+            ExportIndex::Function(index) => module
+                .local_func_index(*index)
+                .map(|local_index| (name.clone(), functions[local_index])),
+            _ => None,
+        })
+        .collect();
+
+    let memories = module
+        .memories
+        .iter()
+        .filter(|(index, _)| !module.is_imported_memory(*index))
+        .map(|(_, memory)| memory);
+    let initial_memory = memories
+        .clone()
+        .map(|memory| Bytes::from(memory.minimum).0)
+        .sum::<usize>();
+    let maximum_memory = memories
+        .map(|memory| memory.maximum.map(|maximum| Bytes::from(maximum).0))
+        .sum::<Option<usize>>();
+
+    Ok(ModuleAnalysis {
+        functions,
+        exports,
+        initial_memory: Bytes(initial_memory),
+        maximum_memory: maximum_memory.map(Bytes),
+    })
+}
+
+/// Estimates the maximum height of the value stack in a function body,
+/// and collects the functions it calls.
+fn analyze_body(
+    module: &ModuleInfo,
+    state: &ModuleTranslationState,
+    index: FunctionIndex,
+    body: &FunctionBodyData,
+) -> WasmResult<BodyAnalysis> {
+    let signature = &module.signatures[module.functions[index]];
+    let mut reader = BinaryReader::new_with_offset(body.data, body.module_offset);
+    let mut locals = signature.params().len() as u32;
+    let mut locals_total = 0;
+    for _ in 0..reader.read_local_count().map_err(to_wasm_error)? {
+        let (count, _) = reader
+            .read_local_decl(&mut locals_total)
+            .map_err(to_wasm_error)?;
+        locals += count;
+    }
+
+    let mut callees = HashSet::new();
+    let mut indirect_callees = HashSet::new();
+    let mut frames = vec![Frame {
+        height: 0,
+        params: 0,
+        results: signature.results().len() as u32,
+    }];
+    let mut height = 0u32;
+    let mut max_operand_stack = 0;
+    while let Some(frame) = frames.last() {
+        let frame_height = frame.height;
+        match reader.read_operator().map_err(to_wasm_error)? {
+            Operator::Block { ty } | Operator::Loop { ty } => {
+                frames.push(block_frame(state, ty, height)?);
+            }
+            Operator::If { ty } => {
+                // The condition is popped before entering the block.
+                height = height.saturating_sub(1);
+                frames.push(block_frame(state, ty, height)?);
+            }
+            Operator::Else => {
+                let frame = frames.last().unwrap();
+                height = frame.height + frame.params;
+            }
+            Operator::End => {
+                let frame = frames.pop().unwrap();
+                height = frame.height + frame.results;
+            }
+            // The rest of the block is unreachable.
+            Operator::Br { .. }
+            | Operator::BrTable { .. }
+            | Operator::Return
+            | Operator::Unreachable => height = frame_height,
+            Operator::Call { function_index } => {
+                let index = FunctionIndex::from_u32(function_index);
+                let callee = &module.signatures[module.functions[index]];
+                height = height.saturating_sub(callee.params().len() as u32)
+                    + callee.results().len() as u32;
+                callees.insert(index);
+            }
+            Operator::CallIndirect { index, .. } => {
+                let index = SignatureIndex::from_u32(index);
+                let callee = &module.signatures[index];
+                height = height.saturating_sub(callee.params().len() as u32 + 1)
+                    + callee.results().len() as u32;
+                indirect_callees.insert(index);
+            }
+            Operator::I32Const { .. }
+            | Operator::I64Const { .. }
+            | Operator::F32Const { .. }
+            | Operator::F64Const { .. }
+            | Operator::V128Const { .. }
+            | Operator::LocalGet { .. }
+            | Operator::GlobalGet { .. }
+            | Operator::MemorySize { .. }
+            | Operator::RefNull { .. }
+            | Operator::RefFunc { .. }
+            | Operator::TableSize { .. } => height += 1,
+            Operator::Drop
+            | Operator::LocalSet { .. }
+            | Operator::GlobalSet { .. }
+            | Operator::BrIf { .. }
+            | Operator::I32Add
+            | Operator::I32Sub
+            | Operator::I32Mul
+            | Operator::I32DivS
+            | Operator::I32DivU
+            | Operator::I32RemS
+            | Operator::I32RemU
+            | Operator::I32And
+            | Operator::I32Or
+            | Operator::I32Xor
+            | Operator::I32Shl
+            | Operator::I32ShrS
+            | Operator::I32ShrU
+            | Operator::I32Rotl
+            | Operator::I32Rotr
+            | Operator::I32Eq
+            | Operator::I32Ne
+            | Operator::I32LtS
+            | Operator::I32LtU
+            | Operator::I32GtS
+            | Operator::I32GtU
+            | Operator::I32LeS
+            | Operator::I32LeU
+            | Operator::I32GeS
+            | Operator::I32GeU
+            | Operator::I64Add
+            | Operator::I64Sub
+            | Operator::I64Mul
+            | Operator::I64DivS
+            | Operator::I64DivU
+            | Operator::I64RemS
+            | Operator::I64RemU
+            | Operator::I64And
+            | Operator::I64Or
+            | Operator::I64Xor
+            | Operator::I64Shl
+            | Operator::I64ShrS
+            | Operator::I64ShrU
+            | Operator::I64Rotl
+            | Operator::I64Rotr
+            | Operator::I64Eq
+            | Operator::I64Ne
+            | Operator::I64LtS
+            | Operator::I64LtU
+            | Operator::I64GtS
+            | Operator::I64GtU
+            | Operator::I64LeS
+            | Operator::I64LeU
+            | Operator::I64GeS
+            | Operator::I64GeU
+            | Operator::F32Add
+            | Operator::F32Sub
+            | Operator::F32Mul
+            | Operator::F32Div
+            | Operator::F32Min
+            | Operator::F32Max
+            | Operator::F32Copysign
+            | Operator::F32Eq
+            | Operator::F32Ne
+            | Operator::F32Lt
+            | Operator::F32Gt
+            | Operator::F32Le
+            | Operator::F32Ge
+            | Operator::F64Add
+            | Operator::F64Sub
+            | Operator::F64Mul
+            | Operator::F64Div
+            | Operator::F64Min
+            | Operator::F64Max
+            | Operator::F64Copysign
+            | Operator::F64Eq
+            | Operator::F64Ne
+            | Operator::F64Lt
+            | Operator::F64Gt
+            | Operator::F64Le
+            | Operator::F64Ge => height = height.saturating_sub(1),
+            Operator::Select
+            | Operator::TypedSelect { .. }
+            | Operator::I32Store { .. }
+            | Operator::I64Store { .. }
+            | Operator::F32Store { .. }
+            | Operator::F64Store { .. }
+            | Operator::I32Store8 { .. }
+            | Operator::I32Store16 { .. }
+            | Operator::I64Store8 { .. }
+            | Operator::I64Store16 { .. }
+            | Operator::I64Store32 { .. } => height = height.saturating_sub(2),
+            // The other operators don't grow the stack: they're
+            // approximated as leaving it unchanged, which can only
+            // overestimate its height.
+            _ => {}
+        }
+        max_operand_stack = max_operand_stack.max(height);
+    }
+
+    Ok(BodyAnalysis {
+        locals,
+        max_operand_stack,
+        callees,
+        indirect_callees,
+    })
+}
+
+fn block_frame(
+    state: &ModuleTranslationState,
+    ty: TypeOrFuncType,
+    height: u32,
+) -> WasmResult<Frame> {
+    let (params, results) = state.blocktype_params_results(ty)?;
+    Ok(Frame {
+        height: height.saturating_sub(params.len() as u32),
+        params: params.len() as u32,
+        results: results.len() as u32,
+    })
+}
+
+/// Returns the functions indirect calls can target, or `None` if they
+/// can target any function because the host has access to a table.
+fn indirect_targets(module: &ModuleInfo) -> Option<Vec<FunctionIndex>> {
+    let host_table = module
+        .tables
+        .keys()
+        .any(|index| module.is_imported_table(index))
+        || module
+            .exports
+            .values()
+            .any(|export| matches!(export, ExportIndex::Table(_)));
+    if host_table {
+        return None;
+    }
+    let targets = module
+        .table_initializers
+        .iter()
+        .map(|initializer| &initializer.elements)
+        .chain(module.passive_elements.values())
+        .flat_map(|elements| elements.iter().copied())
+        .collect::<HashSet<_>>();
+    Some(targets.into_iter().collect())
+}
+
+#[derive(Clone, Copy)]
+enum Visit {
+    Pending,
+    InProgress,
+    /// The maximum call depth and number of stack values, if bounded.
+    Done(Option<(u32, u64)>),
+}
+
+/// The call graph of the functions of a module, walked to bound the
+/// stack used by their calls.
+struct CallGraph<'a> {
+    module: &'a ModuleInfo,
+    bodies: &'a [BodyAnalysis],
+    indirect_targets: Option<Vec<FunctionIndex>>,
+    results: Vec<Visit>,
+}
+
+/// A function being visited by [`CallGraph::visit`].
+struct Frame {
+    index: LocalFunctionIndex,
+    /// The callees left to visit, or `None` once the calls are known to
+    /// be unbounded.
+    callees: Option<std::vec::IntoIter<FunctionIndex>>,
+    /// The maximum call depth and number of stack values of the callees
+    /// visited so far.
+    depth: u32,
+    values: u64,
+    /// The number of stack values of the function itself.
+    own_values: u64,
+}
+
+impl Frame {
+    /// Accounts for the `result` of visiting a callee.
+    fn add(&mut self, result: Option<(u32, u64)>) {
+        match result {
+            Some((depth, values)) => {
+                self.depth = self.depth.max(depth);
+                self.values = self.values.max(values);
+            }
+            None => self.callees = None,
+        }
+    }
+}
+
+impl<'a> CallGraph<'a> {
+    /// Returns the maximum call depth and number of stack values of
+    /// the calls of the function `index`, or `None` if it's unbounded.
+    ///
+    /// The graph is walked depth first with an explicit stack, as call
+    /// chains can be as deep as the module has functions.
+    fn visit(&mut self, index: LocalFunctionIndex) -> Option<(u32, u64)> {
+        match self.results[index.index()] {
+            Visit::Done(result) => return result,
+            // A recursion.
+            Visit::InProgress => return None,
+            Visit::Pending => {}
+        }
+        let mut stack = vec![self.enter(index)];
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let result = match frame.callees.as_mut().map(Iterator::next) {
+                Some(Some(callee)) => {
+                    match self.module.local_func_index(callee) {
+                        Some(callee) => match self.results[callee.index()] {
+                            Visit::Done(result) => frame.add(result),
+                            // A recursion.
+                            Visit::InProgress => frame.add(None),
+                            Visit::Pending => {
+                                let callee = self.enter(callee);
+                                stack.push(callee);
+                            }
+                        },
+                        // The imported functions are only counted as a call.
+                        None => frame.add(Some((1, 0))),
+                    }
+                    continue;
+                }
+                Some(None) => Some((frame.depth + 1, frame.values + frame.own_values)),
+                None => None,
+            };
+            let frame = stack.pop().unwrap();
+            self.results[frame.index.index()] = Visit::Done(result);
+            match stack.last_mut() {
+                Some(caller) => caller.add(result),
+                None => return result,
+            }
+        }
+    }
+
+    /// Starts visiting the function `index`, listing its callees.
+    fn enter(&mut self, index: LocalFunctionIndex) -> Frame {
+        self.results[index.index()] = Visit::InProgress;
+        let body = &self.bodies[index.index()];
+        let mut callees = body.callees.iter().copied().collect::<Vec<_>>();
+        let callees = if body.indirect_callees.is_empty() {
+            Some(callees)
+        } else if let Some(targets) = &self.indirect_targets {
+            let module = self.module;
+            callees.extend(targets.iter().copied().filter(|target| {
+                body.indirect_callees.iter().any(|signature| {
+                    module.signatures[*signature] == module.signatures[module.functions[*target]]
+                })
+            }));
+            Some(callees)
+        } else {
+            None
+        };
+        Frame {
+            index,
+            callees: callees.map(Vec::into_iter),
+            depth: 0,
+            values: 0,
+            own_values: u64::from(body.locals) + u64::from(body.max_operand_stack),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Branch hints, telling the compilers which way the conditional
+//! branches of a module usually go.
+
+use super::error::to_wasm_error;
+use crate::lib::std::collections::HashMap;
+use crate::lib::std::vec::Vec;
+use crate::WasmResult;
+use wasmer_types::FunctionIndex;
+use wasmer_vm::ModuleInfo;
+use wasmparser::BinaryReader;
+
+/// The name of the custom section holding the branch hints of a module,
+/// as defined by the branch hinting proposal.
+pub const BRANCH_HINT_SECTION: &str = "metadata.code.branch_hint";
+
+/// The branch hints of a module: whether the `br_if` and `if`
+/// instructions of its functions are likely to be taken.
+///
+/// The hints are encoded like the [`BRANCH_HINT_SECTION`] custom
+/// section: a vector of function indices, each with a vector of hints.
+/// A hint is the offset of the instruction from the start of the
+/// function body (its locals declaration), a size of 1 and a byte, 1 if
+/// the branch is likely taken and 0 otherwise. This is also the format
+/// of the profiles given to the compilers from outside the module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchHints {
+    functions: HashMap<FunctionIndex, HashMap<u32, bool>>,
+}
+
+impl BranchHints {
+    /// Parses encoded branch hints.
+    pub fn parse(data: &[u8]) -> WasmResult<Self> {
+        let mut reader = BinaryReader::new(data);
+        let mut hints = Self::default();
+        let num_functions = reader.read_var_u32().map_err(to_wasm_error)?;
+        for _ in 0..num_functions {
+            let function = FunctionIndex::from_u32(reader.read_var_u32().map_err(to_wasm_error)?);
+            let num_hints = reader.read_var_u32().map_err(to_wasm_error)?;
+            for _ in 0..num_hints {
+                let offset = reader.read_var_u32().map_err(to_wasm_error)?;
+                let size = reader.read_var_u32().map_err(to_wasm_error)?;
+                if size != 1 {
+                    return Err(wasm_unsupported!("branch hint of size {}", size));
+                }
+                let likely = match reader.read_u8().map_err(to_wasm_error)? {
+                    0 => false,
+                    1 => true,
+                    value => return Err(wasm_unsupported!("branch hint value {}", value)),
+                };
+                hints.insert(function, offset, likely);
+            }
+        }
+        Ok(hints)
+    }
+
+    /// Returns the branch hints of `module`, from its
+    /// [`BRANCH_HINT_SECTION`] custom section.
+    ///
+    /// Malformed hints are ignored, as they don't change the semantics
+    /// of the module.
+    pub fn from_module(module: &ModuleInfo) -> Self {
+        let mut hints = Self::default();
+        for section in module.custom_sections(BRANCH_HINT_SECTION) {
+            if let Ok(section) = Self::parse(&section) {
+                hints.extend(section);
+            }
+        }
+        hints
+    }
+
+    /// Sets whether the branch at `offset` in `function` is likely taken.
+    pub fn insert(&mut self, function: FunctionIndex, offset: u32, likely: bool) {
+        self.functions
+            .entry(function)
+            .or_insert_with(HashMap::new)
+            .insert(offset, likely);
+    }
+
+    /// Adds the hints of `other`, replacing the hints given for the same
+    /// branches.
+    pub fn extend(&mut self, other: Self) {
+        for (function, hints) in other.functions {
+            self.functions
+                .entry(function)
+                .or_insert_with(HashMap::new)
+                .extend(hints);
+        }
+    }
+
+    /// Returns whether the branch at `offset` in `function` is likely
+    /// taken, if it has a hint.
+    pub fn get(&self, function: FunctionIndex, offset: u32) -> Option<bool> {
+        self.functions.get(&function)?.get(&offset).copied()
+    }
+
+    /// Returns the hints of `function`, by offset.
+    pub fn function_hints(&self, function: FunctionIndex) -> Vec<(u32, bool)> {
+        let mut hints = self
+            .functions
+            .get(&function)
+            .map_or_else(Vec::new, |hints| {
+                hints
+                    .iter()
+                    .map(|(offset, likely)| (*offset, *likely))
+                    .collect()
+            });
+        hints.sort_unstable();
+        hints
+    }
+
+    /// Returns whether there are no hints.
+    pub fn is_empty(&self) -> bool {
+        self.functions.values().all(HashMap::is_empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_branch_hints() {
+        let hints = BranchHints::parse(&[1, 2, 2, 5, 1, 1, 9, 1, 0]).unwrap();
+        assert_eq!(hints.get(FunctionIndex::from_u32(2), 5), Some(true));
+        assert_eq!(hints.get(FunctionIndex::from_u32(2), 9), Some(false));
+        assert_eq!(hints.get(FunctionIndex::from_u32(2), 7), None);
+        assert_eq!(hints.get(FunctionIndex::from_u32(0), 5), None);
+
+        assert!(BranchHints::parse(&[1, 2, 1, 5, 2, 1]).is_err());
+        assert!(BranchHints::parse(&[1, 2, 1, 5, 1, 2]).is_err());
+        assert!(BranchHints::parse(&[1, 2, 1, 5]).is_err());
+    }
+}
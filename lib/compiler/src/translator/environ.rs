@@ -17,7 +17,7 @@ use wasmer_types::{
     LocalFunctionIndex, MemoryIndex, MemoryType, SignatureIndex, TableIndex, TableInitializer,
     TableType,
 };
-use wasmer_vm::ModuleInfo;
+use wasmer_vm::{ModuleHash, ModuleInfo};
 
 /// Contains function data: bytecode and its offset in the module.
 #[derive(Hash)]
@@ -72,6 +72,7 @@ impl<'data> ModuleEnvironment<'data> {
     /// `ModuleEnvironment` and produces a `ModuleInfoTranslation`.
     pub fn translate(mut self, data: &'data [u8]) -> WasmResult<ModuleInfoTranslation<'data>> {
         assert!(self.result.module_translation.is_none());
+        self.result.module.hash = Some(ModuleHash::generate(data));
         let module_translation = translate_module(data, &mut self)?;
         self.result.module_translation = Some(module_translation);
         Ok(self.result)
@@ -445,6 +446,21 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn declare_local_name(
+        &mut self,
+        func_index: FunctionIndex,
+        local_index: u32,
+        name: &'data str,
+    ) -> WasmResult<()> {
+        self.result
+            .module
+            .local_names
+            .entry(func_index)
+            .or_default()
+            .insert(local_index, name.to_string());
+        Ok(())
+    }
+
     /// Provides the number of imports up front. By default this does nothing, but
     /// implementations can use this to preallocate memory if desired.
     pub(crate) fn reserve_imports(&mut self, _num: u32) -> WasmResult<()> {
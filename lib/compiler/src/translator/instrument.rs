@@ -0,0 +1,214 @@
+//! Instrumentation of a module calling a debugger hook on entry of each
+//! function.
+
+use super::environ::{FunctionBodyData, ModuleEnvironment};
+use super::error::to_wasm_error;
+use super::shake::write_leb128_u32;
+use super::split::{
+    read_sections, section_entries, value_type, write_limits, write_module, write_name,
+    write_sleb128_i32, CODE_SECTION, EXPORT_SECTION, GLOBAL_SECTION, TABLE_SECTION, TYPE_SECTION,
+};
+use crate::lib::std::{string::ToString, vec::Vec};
+use crate::{WasmError, WasmResult};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{LocalFunctionIndex, Type};
+use wasmer_vm::ModuleInfo;
+use wasmparser::BinaryReader;
+
+/// The export of an instrumented module holding its table.
+pub const DEBUG_TABLE_EXPORT: &str = "__wasmer_debug_table";
+
+/// The exported immutable `i32` global of an instrumented module holding
+/// the slot of the hook in its table.
+pub const DEBUG_HOOK_SLOT_EXPORT: &str = "__wasmer_debug_hook_slot";
+
+/// The exported `i32` global of an instrumented module to set to 1 once
+/// the hook is installed.
+pub const DEBUG_ATTACHED_EXPORT: &str = "__wasmer_debug_attached";
+
+/// The export of the first memory of an instrumented module, if it has
+/// one, so the debugger can read it even if the module doesn't export
+/// it.
+pub const DEBUG_MEMORY_EXPORT: &str = "__wasmer_debug_memory";
+
+/// Instruments a valid WebAssembly module so each of its functions
+/// calls a hook on entry, with its index in the function index space.
+///
+/// The hook is a `(i32) -> ()` function called through the slot of the
+/// table exported as [`DEBUG_TABLE_EXPORT`] given by the global exported
+/// as [`DEBUG_HOOK_SLOT_EXPORT`], while the global exported as
+/// [`DEBUG_ATTACHED_EXPORT`] is set. The host sets the hook
+/// after instantiating the module, so the start function doesn't call
+/// it.
+///
+/// The function indices are unchanged, so the name section stays valid,
+/// but the offsets of the code in the module move.
+pub fn instrument_function_entries(data: &[u8]) -> WasmResult<Vec<u8>> {
+    let translation = ModuleEnvironment::new().translate(data)?;
+    let module = &translation.module;
+    if module.num_imported_tables > 0 {
+        return Err(WasmError::Unsupported(
+            "instrumenting a module importing its table".to_string(),
+        ));
+    }
+    if let Some(table) = module.tables.values().next() {
+        if table.ty != Type::FuncRef {
+            return Err(WasmError::Unsupported(
+                "instrumenting a module whose first table isn't a funcref table".to_string(),
+            ));
+        }
+    }
+    let layout = Layout {
+        hook_slot: module
+            .tables
+            .values()
+            .next()
+            .map_or(0, |table| table.minimum),
+        hook_type: module.signatures.len() as u32,
+        attached_global: module.globals.len() as u32,
+    };
+
+    let sections = read_sections(data)?;
+    let replacements = vec![
+        (TYPE_SECTION, instrumented_types(&sections)?),
+        (TABLE_SECTION, instrumented_tables(module)),
+        (GLOBAL_SECTION, instrumented_globals(&sections, &layout)?),
+        (
+            EXPORT_SECTION,
+            instrumented_exports(module, &sections, &layout)?,
+        ),
+        (
+            CODE_SECTION,
+            instrumented_code(module, &translation.function_body_inputs, &layout)?,
+        ),
+    ];
+    Ok(write_module(data, &sections, &replacements))
+}
+
+/// The indices of what the instrumentation adds to the module.
+struct Layout {
+    hook_slot: u32,
+    hook_type: u32,
+    attached_global: u32,
+}
+
+/// The types of the module, followed by the `(i32) -> ()` type of the
+/// hook.
+fn instrumented_types(sections: &[(u8, &[u8])]) -> WasmResult<Vec<u8>> {
+    let (count, entries) = section_entries(sections, TYPE_SECTION)?;
+    let mut section = vec![];
+    write_leb128_u32(&mut section, count + 1);
+    section.extend_from_slice(entries);
+    section.extend_from_slice(&[0x60, 0x01, value_type(Type::I32), 0x00]);
+    Ok(section)
+}
+
+/// The tables of the module, with a slot for the hook at the end of the
+/// first one.
+fn instrumented_tables(module: &ModuleInfo) -> Vec<u8> {
+    let mut section = vec![];
+    if module.tables.is_empty() {
+        write_leb128_u32(&mut section, 1);
+        section.push(value_type(Type::FuncRef));
+        write_limits(&mut section, 1, Some(1), false);
+        return section;
+    }
+    write_leb128_u32(&mut section, module.tables.len() as u32);
+    for (index, table) in module.tables.values().enumerate() {
+        let grow = if index == 0 { 1 } else { 0 };
+        section.push(value_type(table.ty));
+        write_limits(
+            &mut section,
+            table.minimum + grow,
+            table.maximum.map(|maximum| maximum + grow),
+            false,
+        );
+    }
+    section
+}
+
+/// The globals of the module, followed by the attached global and the
+/// hook slot global.
+fn instrumented_globals(sections: &[(u8, &[u8])], layout: &Layout) -> WasmResult<Vec<u8>> {
+    let (count, entries) = section_entries(sections, GLOBAL_SECTION)?;
+    let mut section = vec![];
+    write_leb128_u32(&mut section, count + 2);
+    section.extend_from_slice(entries);
+    // A mutable `i32` initialized to 0.
+    section.extend_from_slice(&[value_type(Type::I32), 0x01, 0x41, 0x00, 0x0b]);
+    // An immutable `i32` initialized to the hook slot.
+    section.extend_from_slice(&[value_type(Type::I32), 0x00, 0x41]);
+    write_sleb128_i32(&mut section, layout.hook_slot as i32);
+    section.push(0x0b);
+    Ok(section)
+}
+
+/// The exports of the module, followed by the exports of the debugger.
+fn instrumented_exports(
+    module: &ModuleInfo,
+    sections: &[(u8, &[u8])],
+    layout: &Layout,
+) -> WasmResult<Vec<u8>> {
+    let (count, entries) = section_entries(sections, EXPORT_SECTION)?;
+    let mut exports = vec![
+        (DEBUG_TABLE_EXPORT, 0x01, 0),
+        (DEBUG_ATTACHED_EXPORT, 0x03, layout.attached_global),
+        (DEBUG_HOOK_SLOT_EXPORT, 0x03, layout.attached_global + 1),
+    ];
+    if !module.memories.is_empty() {
+        exports.push((DEBUG_MEMORY_EXPORT, 0x02, 0));
+    }
+
+    let mut section = vec![];
+    write_leb128_u32(&mut section, count + exports.len() as u32);
+    section.extend_from_slice(entries);
+    for (name, kind, index) in exports {
+        write_name(&mut section, name);
+        section.push(kind);
+        write_leb128_u32(&mut section, index);
+    }
+    Ok(section)
+}
+
+/// The code of the module, with the call of the hook at the start of
+/// each body, after its locals.
+fn instrumented_code(
+    module: &ModuleInfo,
+    bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+    layout: &Layout,
+) -> WasmResult<Vec<u8>> {
+    let mut section = vec![];
+    write_leb128_u32(&mut section, bodies.len() as u32);
+    for (local_index, body) in bodies.values().enumerate() {
+        let mut reader = BinaryReader::new_with_offset(body.data, body.module_offset);
+        let mut locals_total = 0;
+        for _ in 0..reader.read_local_count().map_err(to_wasm_error)? {
+            reader
+                .read_local_decl(&mut locals_total)
+                .map_err(to_wasm_error)?;
+        }
+        let (locals, code) = body
+            .data
+            .split_at(reader.original_position() - body.module_offset);
+
+        let mut instrumented = locals.to_vec();
+        // global.get $attached, if
+        instrumented.push(0x23);
+        write_leb128_u32(&mut instrumented, layout.attached_global);
+        instrumented.extend_from_slice(&[0x04, 0x40]);
+        // call_indirect $hook with the index of the function
+        let index = module.num_imported_functions + local_index;
+        instrumented.push(0x41);
+        write_sleb128_i32(&mut instrumented, index as i32);
+        instrumented.push(0x41);
+        write_sleb128_i32(&mut instrumented, layout.hook_slot as i32);
+        instrumented.push(0x11);
+        write_leb128_u32(&mut instrumented, layout.hook_type);
+        instrumented.extend_from_slice(&[0x00, 0x0b]);
+        instrumented.extend_from_slice(code);
+
+        write_leb128_u32(&mut section, instrumented.len() as u32);
+        section.extend(instrumented);
+    }
+    Ok(section)
+}
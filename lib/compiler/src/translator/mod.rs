@@ -5,20 +5,33 @@
 //! compilers rather than just Cranelift.
 //!
 //! [cranelift-wasm]: https://crates.io/crates/cranelift-wasm/
+mod analysis;
+mod branch_hints;
 mod environ;
+mod instrument;
 mod middleware;
 mod module;
 mod state;
 #[macro_use]
 mod error;
 mod sections;
+mod shake;
+mod split;
 
+pub use self::analysis::{analyze_module, FunctionAnalysis, ModuleAnalysis};
+pub use self::branch_hints::{BranchHints, BRANCH_HINT_SECTION};
 pub use self::environ::{FunctionBodyData, ModuleEnvironment, ModuleInfoTranslation};
 pub use self::error::to_wasm_error;
+pub use self::instrument::{
+    instrument_function_entries, DEBUG_ATTACHED_EXPORT, DEBUG_HOOK_SLOT_EXPORT,
+    DEBUG_MEMORY_EXPORT, DEBUG_TABLE_EXPORT,
+};
 pub use self::middleware::{
     FunctionMiddleware, FunctionMiddlewareGenerator, GenerateMiddlewareChain,
     MiddlewareBinaryReader, MiddlewareReaderState,
 };
-pub use self::module::translate_module;
+pub use self::module::{describe_function_at_offset, translate_module};
 pub use self::sections::wptype_to_type;
+pub use self::shake::tree_shake;
+pub use self::split::{split_module, SPLIT_LOADED_EXPORT, SPLIT_NAMESPACE, SPLIT_TABLE_EXPORT};
 pub use self::state::ModuleTranslationState;
@@ -7,12 +7,16 @@ use super::environ::ModuleEnvironment;
 use super::error::to_wasm_error;
 use super::sections::{
     parse_code_section, parse_data_section, parse_element_section, parse_export_section,
-    parse_function_section, parse_global_section, parse_import_section, parse_memory_section,
-    parse_name_section, parse_start_section, parse_table_section, parse_type_section,
+    parse_function_name_subsection, parse_function_section, parse_global_section,
+    parse_import_section, parse_memory_section, parse_name_section, parse_start_section,
+    parse_table_section, parse_type_section,
 };
 use super::state::ModuleTranslationState;
 use crate::WasmResult;
-use wasmparser::{CustomSectionContent, ModuleReader, SectionContent};
+use wasmer_types::FunctionIndex;
+use wasmparser::{
+    CustomSectionContent, ImportSectionEntryType, ModuleReader, Name, SectionContent,
+};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a
 /// parsed ModuleInfo `ModuleTranslationState`.
@@ -94,3 +98,71 @@ pub fn translate_module<'data>(
 
     Ok(module_translation_state)
 }
+
+/// Describe the function whose body contains the given byte `offset`
+/// of a Wasm binary, using its name from the name section if any.
+///
+/// This is used to make errors pointing into a function body easier
+/// to understand. It doesn't require the binary to be valid, and
+/// returns `None` if the function can't be found.
+pub fn describe_function_at_offset(data: &[u8], offset: usize) -> Option<String> {
+    let mut reader = ModuleReader::new(data).ok()?;
+    let mut num_imported_functions = 0;
+    let mut func_index = None;
+    let mut function_name = None;
+
+    while !reader.eof() {
+        let section = match reader.read() {
+            Ok(section) => section,
+            Err(_) => break,
+        };
+        match section.content() {
+            Ok(SectionContent::Import(imports)) => {
+                for import in imports {
+                    if let Ok(import) = import {
+                        if let ImportSectionEntryType::Function(_) = import.ty {
+                            num_imported_functions += 1;
+                        }
+                    }
+                }
+            }
+            Ok(SectionContent::Code(code)) => {
+                for (index, body) in code.into_iter().enumerate() {
+                    let reader = match body {
+                        Ok(body) => body.get_binary_reader(),
+                        Err(_) => break,
+                    };
+                    let start = reader.original_position();
+                    if start <= offset && offset < start + reader.bytes_remaining() {
+                        func_index = Some(FunctionIndex::from_u32(
+                            (num_imported_functions + index) as u32,
+                        ));
+                        break;
+                    }
+                }
+            }
+            Ok(SectionContent::Custom {
+                content: Some(CustomSectionContent::Name(mut names)),
+                ..
+            }) => {
+                let func_index = func_index?;
+                while let Ok(subsection) = names.read() {
+                    if let Name::Function(function_subsection) = subsection {
+                        function_name = function_subsection
+                            .get_map()
+                            .ok()
+                            .and_then(parse_function_name_subsection)
+                            .and_then(|names| names.get(&func_index).map(|name| name.to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let func_index = func_index?;
+    Some(match function_name {
+        Some(name) => format!("`{}` (#{})", name, func_index.as_u32()),
+        None => format!("#{}", func_index.as_u32()),
+    })
+}
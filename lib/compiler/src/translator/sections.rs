@@ -446,13 +446,33 @@ pub fn parse_name_section<'data>(
                     environ.declare_module_name(name)?;
                 }
             }
-            wasmparser::Name::Local(_) => {}
+            wasmparser::Name::Local(local_subsection) => {
+                if let Ok(mut function_locals) = local_subsection.get_function_local_reader() {
+                    for _ in 0..function_locals.get_count() {
+                        let function_local = match function_locals.read() {
+                            Ok(function_local) => function_local,
+                            Err(_) => break,
+                        };
+                        let func_index = FunctionIndex::from_u32(function_local.func_index);
+                        if let Ok(mut naming_reader) = function_local.get_map() {
+                            for _ in 0..naming_reader.get_count() {
+                                match naming_reader.read() {
+                                    Ok(Naming { index, name }) => {
+                                        environ.declare_local_name(func_index, index, name)?
+                                    }
+                                    Err(_) => break,
+                                }
+                            }
+                        }
+                    }
+                }
+            }
         };
     }
     Ok(())
 }
 
-fn parse_function_name_subsection(
+pub(crate) fn parse_function_name_subsection(
     mut naming_reader: NamingReader<'_>,
 ) -> Option<HashMap<FunctionIndex, &str>> {
     let mut function_names = HashMap::new();
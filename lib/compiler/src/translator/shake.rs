@@ -0,0 +1,258 @@
+//! Removal of the code of a module unreachable from a set of entry
+//! exports, before compiling it.
+
+use super::environ::ModuleEnvironment;
+use super::error::to_wasm_error;
+use crate::lib::std::collections::HashSet;
+use crate::lib::std::vec::Vec;
+use crate::{WasmError, WasmResult};
+use wasmer_types::{ExportIndex, FunctionIndex, GlobalInit};
+use wasmer_vm::ModuleInfo;
+use wasmparser::{BinaryReader, Operator};
+
+/// The body of the functions removed by [`tree_shake`]: no locals and
+/// an `unreachable` instruction.
+const STUB_BODY: [u8; 4] = [0x03, 0x00, 0x00, 0x0b];
+
+const EXPORT_SECTION: u8 = 7;
+const ELEMENT_SECTION: u8 = 9;
+const CODE_SECTION: u8 = 10;
+const DATA_SECTION: u8 = 11;
+const DATA_COUNT_SECTION: u8 = 12;
+
+/// Removes from a valid WebAssembly module the code unreachable from
+/// the exported functions `entries`, returning the new module.
+///
+/// The other exported functions are removed from the exports. The
+/// functions not reachable from the entries, the start function or
+/// the global initializers have their body replaced by an
+/// `unreachable` instruction, which keeps the function indices (and so
+/// the name section) valid. The element segments are removed if no
+/// reachable code uses a table, and the data segments if no reachable
+/// code uses a memory, unless the table or memory is imported or
+/// exported.
+pub fn tree_shake(data: &[u8], entries: &[&str]) -> WasmResult<Vec<u8>> {
+    let translation = ModuleEnvironment::new().translate(data)?;
+    let module = &translation.module;
+    let mut roots = entries
+        .iter()
+        .map(|entry| match module.exports.get(*entry) {
+            Some(ExportIndex::Function(index)) => Ok(*index),
+            _ => Err(WasmError::Generic(format!(
+                "no exported function `{}`",
+                entry
+            ))),
+        })
+        .collect::<WasmResult<Vec<_>>>()?;
+    roots.extend(module.start_function);
+    roots.extend(
+        module
+            .global_initializers
+            .values()
+            .filter_map(|init| match init {
+                GlobalInit::RefFunc(index) => Some(*index),
+                _ => None,
+            }),
+    );
+
+    let mut reachability = Reachability {
+        module,
+        bodies: translation
+            .function_body_inputs
+            .values()
+            .map(|body| (body.data, body.module_offset))
+            .collect(),
+        reachable: HashSet::new(),
+        uses_tables: module
+            .tables
+            .keys()
+            .any(|index| module.is_imported_table(index))
+            || module
+                .exports
+                .values()
+                .any(|export| matches!(export, ExportIndex::Table(_))),
+        uses_memories: module
+            .memories
+            .keys()
+            .any(|index| module.is_imported_memory(index))
+            || module
+                .exports
+                .values()
+                .any(|export| matches!(export, ExportIndex::Memory(_))),
+        visited_elements: false,
+    };
+    reachability.visit(roots)?;
+
+    let mut output = data[..8].to_vec();
+    let mut position = 8;
+    while position < data.len() {
+        let id = data[position];
+        let mut payload_start = position + 1;
+        let size = read_leb128_u32(data, &mut payload_start)? as usize;
+        let end = payload_start + size;
+        let section = match id {
+            EXPORT_SECTION => Some(exports_section(module, entries)),
+            ELEMENT_SECTION if !reachability.uses_tables => None,
+            DATA_SECTION | DATA_COUNT_SECTION if !reachability.uses_memories => None,
+            CODE_SECTION => Some(code_section(&reachability)),
+            _ => Some(data[payload_start..end].to_vec()),
+        };
+        if let Some(section) = section {
+            output.push(id);
+            write_leb128_u32(&mut output, section.len() as u32);
+            output.extend(section);
+        }
+        position = end;
+    }
+    Ok(output)
+}
+
+/// The functions of a module reachable from some roots, and whether
+/// they use its tables and memories.
+struct Reachability<'a> {
+    module: &'a ModuleInfo,
+    /// The body and its offset in the module of the defined functions.
+    bodies: Vec<(&'a [u8], usize)>,
+    reachable: HashSet<FunctionIndex>,
+    uses_tables: bool,
+    uses_memories: bool,
+    visited_elements: bool,
+}
+
+impl<'a> Reachability<'a> {
+    /// Visits the functions reachable from `roots`.
+    fn visit(&mut self, mut pending: Vec<FunctionIndex>) -> WasmResult<()> {
+        loop {
+            if self.uses_tables && !self.visited_elements {
+                // Any function of the tables can be called.
+                self.visited_elements = true;
+                pending.extend(
+                    self.module
+                        .table_initializers
+                        .iter()
+                        .map(|initializer| &initializer.elements)
+                        .chain(self.module.passive_elements.values())
+                        .flat_map(|elements| elements.iter().copied()),
+                );
+            }
+            let index = match pending.pop() {
+                Some(index) => index,
+                None => break,
+            };
+            if !self.reachable.insert(index) {
+                continue;
+            }
+            let (body, offset) = match self.module.local_func_index(index) {
+                Some(local_index) => self.bodies[local_index.as_u32() as usize],
+                None => continue,
+            };
+            let mut reader = BinaryReader::new_with_offset(body, offset);
+            let mut locals_total = 0;
+            for _ in 0..reader.read_local_count().map_err(to_wasm_error)? {
+                reader
+                    .read_local_decl(&mut locals_total)
+                    .map_err(to_wasm_error)?;
+            }
+            while !reader.eof() {
+                let opcode = body[reader.original_position() - offset];
+                match reader.read_operator().map_err(to_wasm_error)? {
+                    Operator::Call { function_index } => {
+                        pending.push(FunctionIndex::from_u32(function_index))
+                    }
+                    // `ref.func` requires its function to be declared in
+                    // an element segment.
+                    Operator::RefFunc { function_index } => {
+                        pending.push(FunctionIndex::from_u32(function_index));
+                        self.uses_tables = true;
+                    }
+                    Operator::CallIndirect { .. }
+                    | Operator::TableGet { .. }
+                    | Operator::TableSet { .. }
+                    | Operator::TableSize { .. }
+                    | Operator::TableGrow { .. }
+                    | Operator::TableFill { .. }
+                    | Operator::TableCopy { .. }
+                    | Operator::TableInit { .. }
+                    | Operator::ElemDrop { .. } => self.uses_tables = true,
+                    Operator::MemoryInit { .. }
+                    | Operator::DataDrop { .. }
+                    | Operator::MemoryCopy { .. }
+                    | Operator::MemoryFill { .. } => self.uses_memories = true,
+                    // The loads, stores, `memory.size` and `memory.grow`,
+                    // and (conservatively) all the SIMD and atomic
+                    // operators.
+                    _ if (0x28..=0x40).contains(&opcode) || opcode == 0xfd || opcode == 0xfe => {
+                        self.uses_memories = true
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the export section keeping only the `entries` of the
+/// exported functions.
+fn exports_section(module: &ModuleInfo, entries: &[&str]) -> Vec<u8> {
+    let exports = module
+        .exports
+        .iter()
+        .filter(|(name, export)| match export {
+            ExportIndex::Function(_) => entries.contains(&name.as_str()),
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    let mut section = vec![];
+    write_leb128_u32(&mut section, exports.len() as u32);
+    for (name, export) in exports {
+        write_leb128_u32(&mut section, name.len() as u32);
+        section.extend_from_slice(name.as_bytes());
+        let (kind, index) = match export {
+            ExportIndex::Function(index) => (0, index.as_u32()),
+            ExportIndex::Table(index) => (1, index.as_u32()),
+            ExportIndex::Memory(index) => (2, index.as_u32()),
+            ExportIndex::Global(index) => (3, index.as_u32()),
+        };
+        section.push(kind);
+        write_leb128_u32(&mut section, index);
+    }
+    section
+}
+
+/// Returns the code section with the bodies of the unreachable
+/// functions replaced by stubs.
+fn code_section(reachability: &Reachability) -> Vec<u8> {
+    let module = reachability.module;
+    let mut section = vec![];
+    write_leb128_u32(&mut section, reachability.bodies.len() as u32);
+    for (local_index, (body, _)) in reachability.bodies.iter().enumerate() {
+        let index = FunctionIndex::from_u32((module.num_imported_functions + local_index) as u32);
+        if reachability.reachable.contains(&index) {
+            write_leb128_u32(&mut section, body.len() as u32);
+            section.extend_from_slice(body);
+        } else {
+            section.extend_from_slice(&STUB_BODY);
+        }
+    }
+    section
+}
+
+pub(super) fn read_leb128_u32(data: &[u8], position: &mut usize) -> WasmResult<u32> {
+    let mut reader = BinaryReader::new_with_offset(&data[*position..], *position);
+    let value = reader.read_var_u32().map_err(to_wasm_error)?;
+    *position = reader.original_position();
+    Ok(value)
+}
+
+pub(super) fn write_leb128_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,549 @@
+//! Splitting of a module into a primary module and a secondary module
+//! holding its cold code, loaded on demand.
+
+use super::environ::{FunctionBodyData, ModuleEnvironment};
+use super::error::to_wasm_error;
+use super::shake::{read_leb128_u32, write_leb128_u32};
+use crate::lib::std::collections::HashSet;
+use crate::lib::std::{string::ToString, vec::Vec};
+use crate::{WasmError, WasmResult};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{ExportIndex, FunctionIndex, GlobalInit, LocalFunctionIndex, Mutability, Type};
+use wasmer_vm::ModuleInfo;
+use wasmparser::{BinaryReader, Operator};
+
+/// The import namespace of the secondary module, where the primary
+/// module exports everything the cold code uses.
+pub const SPLIT_NAMESPACE: &str = "wasmer_split";
+
+/// The export of the primary module holding its table. The cold
+/// functions are at the end of the table, followed by the loader.
+pub const SPLIT_TABLE_EXPORT: &str = "__wasmer_split_table";
+
+/// The exported `i32` global of the primary module to set to 1 once the
+/// secondary module is loaded.
+pub const SPLIT_LOADED_EXPORT: &str = "__wasmer_split_loaded";
+
+const SPLIT_FUNCTION_PREFIX: &str = "__wasmer_split_function";
+const SPLIT_MEMORY_PREFIX: &str = "__wasmer_split_memory";
+const SPLIT_GLOBAL_PREFIX: &str = "__wasmer_split_global";
+
+const SECTION_ORDER: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 10, 11];
+pub(super) const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const FUNCTION_SECTION: u8 = 3;
+pub(super) const TABLE_SECTION: u8 = 4;
+pub(super) const GLOBAL_SECTION: u8 = 6;
+pub(super) const EXPORT_SECTION: u8 = 7;
+const ELEMENT_SECTION: u8 = 9;
+pub(super) const CODE_SECTION: u8 = 10;
+
+/// Splits a valid WebAssembly module into a primary module and a
+/// secondary module, returned in this order.
+///
+/// The functions reachable from the exported functions `hot_exports`,
+/// the start function and the global initializers stay in the primary
+/// module. The other functions (the cold ones) move to the secondary
+/// module, and their bodies in the primary module are replaced by
+/// stubs which:
+///
+/// 1. call the loader, at the last slot of the table exported as
+///    [`SPLIT_TABLE_EXPORT`], unless the global exported as
+///    [`SPLIT_LOADED_EXPORT`] is set;
+/// 2. call the cold function through its slot in the table.
+///
+/// The host sets the loader after instantiating the primary module. It
+/// instantiates the secondary module with the exports of the primary
+/// one as the [`SPLIT_NAMESPACE`] namespace, which fills the slots of
+/// the cold functions, and sets the loaded global.
+///
+/// The function indices are unchanged, so the name section of the
+/// primary module stays valid. The functions using `ref.func` or the
+/// segment instructions stay in the primary module.
+pub fn split_module(data: &[u8], hot_exports: &[&str]) -> WasmResult<(Vec<u8>, Vec<u8>)> {
+    let translation = ModuleEnvironment::new().translate(data)?;
+    let module = &translation.module;
+    if module.num_imported_tables > 0 {
+        return Err(WasmError::Unsupported(
+            "splitting a module importing its table".to_string(),
+        ));
+    }
+    let bodies = translation
+        .function_body_inputs
+        .values()
+        .map(scan_body)
+        .collect::<WasmResult<Vec<_>>>()?;
+
+    let mut pending = hot_exports
+        .iter()
+        .map(|name| match module.exports.get(*name) {
+            Some(ExportIndex::Function(index)) => Ok(*index),
+            _ => Err(WasmError::Generic(format!(
+                "no exported function `{}`",
+                name
+            ))),
+        })
+        .collect::<WasmResult<Vec<_>>>()?;
+    pending.extend(module.start_function);
+    pending.extend(
+        module
+            .global_initializers
+            .values()
+            .filter_map(|init| match init {
+                GlobalInit::RefFunc(index) => Some(*index),
+                _ => None,
+            }),
+    );
+    let mut hot = HashSet::new();
+    while let Some(index) = pending.pop() {
+        if hot.insert(index) {
+            if let Some(local_index) = module.local_func_index(index) {
+                pending.extend(
+                    bodies[local_index.as_u32() as usize]
+                        .callees
+                        .iter()
+                        .copied(),
+                );
+            }
+        }
+    }
+    let cold = (0..bodies.len())
+        .filter(|local_index| {
+            let index =
+                FunctionIndex::from_u32((module.num_imported_functions + local_index) as u32);
+            !hot.contains(&index) && !bodies[*local_index].pinned
+        })
+        .collect::<Vec<_>>();
+
+    let base = module
+        .tables
+        .values()
+        .next()
+        .map_or(0, |table| table.minimum);
+    let layout = Layout {
+        base,
+        loader_slot: base + cold.len() as u32,
+        loader_type: module.signatures.len() as u32,
+        loaded_global: module.globals.len() as u32,
+    };
+
+    let sections = read_sections(data)?;
+    let type_section = sections
+        .iter()
+        .find(|(id, _)| *id == TYPE_SECTION)
+        .map(|(_, payload)| *payload);
+
+    let replacements = vec![
+        (TYPE_SECTION, primary_types(module, type_section)?),
+        (TABLE_SECTION, primary_tables(module, &layout)),
+        (GLOBAL_SECTION, primary_globals(&sections)?),
+        (EXPORT_SECTION, primary_exports(module, &sections, &layout)?),
+        (
+            CODE_SECTION,
+            primary_code(module, &translation.function_body_inputs, &cold, &layout),
+        ),
+    ];
+    let primary = write_module(data, &sections, &replacements);
+
+    let secondary = secondary_module(
+        module,
+        type_section,
+        &translation.function_body_inputs,
+        &cold,
+        &layout,
+    );
+    Ok((primary, secondary))
+}
+
+/// What the split needs to know of a function body.
+struct BodyScan {
+    callees: Vec<FunctionIndex>,
+    /// Whether the function uses index spaces the secondary module
+    /// doesn't import, so it can't move there.
+    pinned: bool,
+}
+
+/// Where the split puts the cold functions, and the indices of what
+/// it adds to the primary module.
+struct Layout {
+    /// The table slot of the first cold function.
+    base: u32,
+    loader_slot: u32,
+    loader_type: u32,
+    loaded_global: u32,
+}
+
+fn scan_body(body: &FunctionBodyData) -> WasmResult<BodyScan> {
+    let mut reader = BinaryReader::new_with_offset(body.data, body.module_offset);
+    let mut locals_total = 0;
+    for _ in 0..reader.read_local_count().map_err(to_wasm_error)? {
+        reader
+            .read_local_decl(&mut locals_total)
+            .map_err(to_wasm_error)?;
+    }
+    let mut scan = BodyScan {
+        callees: vec![],
+        pinned: false,
+    };
+    while !reader.eof() {
+        match reader.read_operator().map_err(to_wasm_error)? {
+            Operator::Call { function_index } => {
+                scan.callees.push(FunctionIndex::from_u32(function_index))
+            }
+            Operator::RefFunc { .. }
+            | Operator::MemoryInit { .. }
+            | Operator::DataDrop { .. }
+            | Operator::TableInit { .. }
+            | Operator::ElemDrop { .. } => scan.pinned = true,
+            _ => {}
+        }
+    }
+    Ok(scan)
+}
+
+/// Returns the id and the payload of each section of the module `data`,
+/// in order.
+pub(super) fn read_sections(data: &[u8]) -> WasmResult<Vec<(u8, &[u8])>> {
+    let mut sections = vec![];
+    let mut position = 8;
+    while position < data.len() {
+        let id = data[position];
+        let mut payload_start = position + 1;
+        let size = read_leb128_u32(data, &mut payload_start)? as usize;
+        let end = payload_start + size;
+        sections.push((id, &data[payload_start..end]));
+        position = end;
+    }
+    Ok(sections)
+}
+
+/// Writes the module `data` made of `sections`, with the `replacements`
+/// payloads instead of the sections of the same id, or inserted in
+/// order where the module has no such section.
+pub(super) fn write_module(
+    data: &[u8],
+    sections: &[(u8, &[u8])],
+    replacements: &[(u8, Vec<u8>)],
+) -> Vec<u8> {
+    let mut module = data[..8].to_vec();
+    let mut written = HashSet::new();
+    for (id, payload) in sections {
+        if *id != 0 {
+            // Insert the new sections that go before this one.
+            for (new_id, new_payload) in replacements {
+                if rank(*new_id) < rank(*id) && written.insert(*new_id) {
+                    write_section(&mut module, *new_id, new_payload);
+                }
+            }
+        }
+        match replacements.iter().find(|(new_id, _)| new_id == id) {
+            Some((_, new_payload)) => {
+                if written.insert(*id) {
+                    write_section(&mut module, *id, new_payload);
+                }
+            }
+            None => write_section(&mut module, *id, payload),
+        }
+    }
+    for (new_id, new_payload) in replacements {
+        if written.insert(*new_id) {
+            write_section(&mut module, *new_id, new_payload);
+        }
+    }
+    module
+}
+
+fn rank(id: u8) -> usize {
+    SECTION_ORDER
+        .iter()
+        .position(|section| *section == id)
+        .unwrap_or(0)
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    out.push(id);
+    write_leb128_u32(out, payload.len() as u32);
+    out.extend_from_slice(payload);
+}
+
+/// Returns the number of entries of the section `id`, and their
+/// bytes.
+pub(super) fn section_entries<'a>(
+    sections: &[(u8, &'a [u8])],
+    id: u8,
+) -> WasmResult<(u32, &'a [u8])> {
+    match sections.iter().find(|(section, _)| *section == id) {
+        Some((_, payload)) => entries(payload),
+        None => Ok((0, &[])),
+    }
+}
+
+pub(super) fn entries(payload: &[u8]) -> WasmResult<(u32, &[u8])> {
+    let mut position = 0;
+    let count = read_leb128_u32(payload, &mut position)?;
+    Ok((count, &payload[position..]))
+}
+
+/// The types of the module, followed by the `() -> ()` type of the
+/// loader.
+fn primary_types(module: &ModuleInfo, type_section: Option<&[u8]>) -> WasmResult<Vec<u8>> {
+    let entries = match type_section {
+        Some(payload) => entries(payload)?.1,
+        None => &[],
+    };
+    let mut section = vec![];
+    write_leb128_u32(&mut section, module.signatures.len() as u32 + 1);
+    section.extend_from_slice(entries);
+    section.extend_from_slice(&[0x60, 0x00, 0x00]);
+    Ok(section)
+}
+
+/// The tables of the module, with room for the cold functions and the
+/// loader at the end of the first one.
+fn primary_tables(module: &ModuleInfo, layout: &Layout) -> Vec<u8> {
+    let grow = layout.loader_slot + 1 - layout.base;
+    let mut section = vec![];
+    if module.tables.is_empty() {
+        write_leb128_u32(&mut section, 1);
+        section.push(value_type(Type::FuncRef));
+        write_limits(&mut section, grow, Some(grow), false);
+        return section;
+    }
+    write_leb128_u32(&mut section, module.tables.len() as u32);
+    for (index, table) in module.tables.values().enumerate() {
+        let grow = if index == 0 { grow } else { 0 };
+        section.push(value_type(table.ty));
+        write_limits(
+            &mut section,
+            table.minimum + grow,
+            table.maximum.map(|maximum| maximum + grow),
+            false,
+        );
+    }
+    section
+}
+
+/// The globals of the module, followed by the loaded global.
+fn primary_globals(sections: &[(u8, &[u8])]) -> WasmResult<Vec<u8>> {
+    let (count, entries) = section_entries(sections, GLOBAL_SECTION)?;
+    let mut section = vec![];
+    write_leb128_u32(&mut section, count + 1);
+    section.extend_from_slice(entries);
+    // A mutable `i32` initialized to 0.
+    section.extend_from_slice(&[value_type(Type::I32), 0x01, 0x41, 0x00, 0x0b]);
+    Ok(section)
+}
+
+/// The exports of the module, followed by the exports the secondary
+/// module imports.
+fn primary_exports(
+    module: &ModuleInfo,
+    sections: &[(u8, &[u8])],
+    layout: &Layout,
+) -> WasmResult<Vec<u8>> {
+    let (count, entries) = section_entries(sections, EXPORT_SECTION)?;
+    let mut exports = vec![
+        (SPLIT_TABLE_EXPORT.to_string(), 0x01, 0),
+        (SPLIT_LOADED_EXPORT.to_string(), 0x03, layout.loaded_global),
+    ];
+    exports.extend(
+        (0..module.functions.len() as u32)
+            .map(|index| (format!("{}{}", SPLIT_FUNCTION_PREFIX, index), 0x00, index)),
+    );
+    exports.extend(
+        (0..module.memories.len() as u32)
+            .map(|index| (format!("{}{}", SPLIT_MEMORY_PREFIX, index), 0x02, index)),
+    );
+    exports.extend(
+        (0..module.globals.len() as u32)
+            .map(|index| (format!("{}{}", SPLIT_GLOBAL_PREFIX, index), 0x03, index)),
+    );
+
+    let mut section = vec![];
+    write_leb128_u32(&mut section, count + exports.len() as u32);
+    section.extend_from_slice(entries);
+    for (name, kind, index) in exports {
+        write_name(&mut section, &name);
+        section.push(kind);
+        write_leb128_u32(&mut section, index);
+    }
+    Ok(section)
+}
+
+/// The code of the module, with the bodies of the cold functions
+/// replaced by stubs.
+fn primary_code(
+    module: &ModuleInfo,
+    bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+    cold: &[usize],
+    layout: &Layout,
+) -> Vec<u8> {
+    let mut section = vec![];
+    write_leb128_u32(&mut section, bodies.len() as u32);
+    for (local_index, body) in bodies.values().enumerate() {
+        let slot = match cold.binary_search(&local_index) {
+            Ok(ordinal) => layout.base + ordinal as u32,
+            Err(_) => {
+                write_leb128_u32(&mut section, body.data.len() as u32);
+                section.extend_from_slice(body.data);
+                continue;
+            }
+        };
+        let index = FunctionIndex::from_u32((module.num_imported_functions + local_index) as u32);
+        let signature = module.functions[index];
+
+        let mut stub = vec![0x00];
+        // global.get $loaded, i32.eqz, if
+        stub.push(0x23);
+        write_leb128_u32(&mut stub, layout.loaded_global);
+        stub.extend_from_slice(&[0x45, 0x04, 0x40]);
+        // call_indirect $loader
+        stub.push(0x41);
+        write_sleb128_i32(&mut stub, layout.loader_slot as i32);
+        stub.push(0x11);
+        write_leb128_u32(&mut stub, layout.loader_type);
+        stub.extend_from_slice(&[0x00, 0x0b]);
+        // Forward the parameters to the cold function.
+        for param in 0..module.signatures[signature].params().len() as u32 {
+            stub.push(0x20);
+            write_leb128_u32(&mut stub, param);
+        }
+        stub.push(0x41);
+        write_sleb128_i32(&mut stub, slot as i32);
+        stub.push(0x11);
+        write_leb128_u32(&mut stub, signature.as_u32());
+        stub.extend_from_slice(&[0x00, 0x0b]);
+
+        write_leb128_u32(&mut section, stub.len() as u32);
+        section.extend(stub);
+    }
+    section
+}
+
+/// The secondary module, importing the functions, tables, memories and
+/// globals of the primary module with the same indices, and defining
+/// the cold functions.
+fn secondary_module(
+    module: &ModuleInfo,
+    type_section: Option<&[u8]>,
+    bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+    cold: &[usize],
+    layout: &Layout,
+) -> Vec<u8> {
+    let mut secondary = b"\0asm\x01\0\0\0".to_vec();
+    if let Some(payload) = type_section {
+        write_section(&mut secondary, TYPE_SECTION, payload);
+    }
+
+    let mut imports = vec![];
+    let count = module.functions.len() + 1 + module.memories.len() + module.globals.len();
+    write_leb128_u32(&mut imports, count as u32);
+    for (index, signature) in module.functions.iter() {
+        write_name(&mut imports, SPLIT_NAMESPACE);
+        write_name(
+            &mut imports,
+            &format!("{}{}", SPLIT_FUNCTION_PREFIX, index.as_u32()),
+        );
+        imports.push(0x00);
+        write_leb128_u32(&mut imports, signature.as_u32());
+    }
+    write_name(&mut imports, SPLIT_NAMESPACE);
+    write_name(&mut imports, SPLIT_TABLE_EXPORT);
+    imports.push(0x01);
+    imports.push(value_type(Type::FuncRef));
+    write_limits(&mut imports, layout.loader_slot + 1, None, false);
+    for (index, memory) in module.memories.iter() {
+        write_name(&mut imports, SPLIT_NAMESPACE);
+        write_name(
+            &mut imports,
+            &format!("{}{}", SPLIT_MEMORY_PREFIX, index.as_u32()),
+        );
+        imports.push(0x02);
+        write_limits(
+            &mut imports,
+            memory.minimum.0,
+            memory.maximum.map(|maximum| maximum.0),
+            memory.shared,
+        );
+    }
+    for (index, global) in module.globals.iter() {
+        write_name(&mut imports, SPLIT_NAMESPACE);
+        write_name(
+            &mut imports,
+            &format!("{}{}", SPLIT_GLOBAL_PREFIX, index.as_u32()),
+        );
+        imports.push(0x03);
+        imports.push(value_type(global.ty));
+        imports.push(match global.mutability {
+            Mutability::Const => 0x00,
+            Mutability::Var => 0x01,
+        });
+    }
+    write_section(&mut secondary, IMPORT_SECTION, &imports);
+
+    let mut functions = vec![];
+    write_leb128_u32(&mut functions, cold.len() as u32);
+    for local_index in cold {
+        let index = FunctionIndex::from_u32((module.num_imported_functions + local_index) as u32);
+        write_leb128_u32(&mut functions, module.functions[index].as_u32());
+    }
+    write_section(&mut secondary, FUNCTION_SECTION, &functions);
+
+    // An active segment putting the cold functions in their slots.
+    let mut elements = vec![0x01, 0x00, 0x41];
+    write_sleb128_i32(&mut elements, layout.base as i32);
+    elements.push(0x0b);
+    write_leb128_u32(&mut elements, cold.len() as u32);
+    for ordinal in 0..cold.len() {
+        write_leb128_u32(&mut elements, (module.functions.len() + ordinal) as u32);
+    }
+    write_section(&mut secondary, ELEMENT_SECTION, &elements);
+
+    let mut code = vec![];
+    write_leb128_u32(&mut code, cold.len() as u32);
+    for local_index in cold {
+        let body = &bodies[LocalFunctionIndex::from_u32(*local_index as u32)];
+        write_leb128_u32(&mut code, body.data.len() as u32);
+        code.extend_from_slice(body.data);
+    }
+    write_section(&mut secondary, CODE_SECTION, &code);
+    secondary
+}
+
+pub(super) fn value_type(ty: Type) -> u8 {
+    match ty {
+        Type::I32 => 0x7f,
+        Type::I64 => 0x7e,
+        Type::F32 => 0x7d,
+        Type::F64 => 0x7c,
+        Type::V128 => 0x7b,
+        Type::FuncRef => 0x70,
+        Type::ExternRef => 0x6f,
+    }
+}
+
+pub(super) fn write_limits(out: &mut Vec<u8>, minimum: u32, maximum: Option<u32>, shared: bool) {
+    out.push(maximum.is_some() as u8 | ((shared as u8) << 1));
+    write_leb128_u32(out, minimum);
+    if let Some(maximum) = maximum {
+        write_leb128_u32(out, maximum);
+    }
+}
+
+pub(super) fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_leb128_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+pub(super) fn write_sleb128_i32(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -14,12 +14,14 @@ use libc::c_char;
 
 use crate::{
     allocate_on_stack,
+    process::abort_with_message,
     ptr::{Array, WasmPtr},
     EmscriptenData,
 };
 
 use std::os::raw::c_int;
 
+use crate::utils::read_string_from_wasm;
 use crate::EmEnv;
 use wasmer::ValueType;
 
@@ -37,6 +39,12 @@ pub fn call_malloc_with_cast<T: Copy, Ty>(ctx: &mut EmEnv, size: u32) -> WasmPtr
     WasmPtr::new(call_malloc(ctx, size))
 }
 
+pub fn call_free(ctx: &mut EmEnv, pointer: u32) {
+    if let Some(free) = &get_emscripten_data(ctx).free {
+        free.call(pointer).unwrap()
+    }
+}
+
 pub fn call_memalign(ctx: &mut EmEnv, alignment: u32, size: u32) -> u32 {
     if let Some(memalign) = &get_emscripten_data(ctx).memalign {
         memalign.call(alignment, size).unwrap()
@@ -123,10 +131,33 @@ pub fn ___build_environment(ctx: &mut EmEnv, environ: c_int) {
     }
 }
 
-pub fn ___assert_fail(_ctx: &mut EmEnv, _a: c_int, _b: c_int, _c: c_int, _d: c_int) {
-    debug!("emscripten::___assert_fail {} {} {} {}", _a, _b, _c, _d);
-    // TODO: Implement like emscripten expects regarding memory/page size
-    // TODO raise an error
+pub fn ___assert_fail(
+    ctx: &mut EmEnv,
+    condition: c_int,
+    filename: c_int,
+    line: c_int,
+    function: c_int,
+) {
+    debug!(
+        "emscripten::___assert_fail {} {} {} {}",
+        condition, filename, line, function
+    );
+    let memory = ctx.memory(0);
+    let read = |offset: c_int, default: &str| {
+        if offset == 0 {
+            default.to_string()
+        } else {
+            read_string_from_wasm(memory, offset as u32)
+        }
+    };
+    let message = format!(
+        "Assertion failed: {}, at: {},{},{}",
+        read(condition, ""),
+        read(filename, "unknown filename"),
+        line,
+        read(function, "unknown function"),
+    );
+    abort_with_message(ctx, &message);
 }
 
 pub fn _pathconf(ctx: &mut EmEnv, path_addr: c_int, name: c_int) -> c_int {
@@ -1,7 +1,8 @@
-use crate::EmEnv;
+use crate::{EmEnv, EmscriptenError};
+use wasmer::RuntimeError;
 
 // __exit
 pub fn exit(_ctx: &mut EmEnv, value: i32) {
     debug!("emscripten::exit {}", value);
-    ::std::process::exit(value);
+    RuntimeError::raise(Box::new(EmscriptenError::Exit(value)));
 }
@@ -15,14 +15,15 @@ extern crate log;
 
 use lazy_static::lazy_static;
 use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::{f64, ffi::c_void};
 use wasmer::{
-    imports, namespace, Exports, ExternRef, Function, FunctionType, Global, ImportObject, Instance,
-    Memory, MemoryType, Module, NativeFunc, Pages, RuntimeError, Store, Table, TableType, Val,
-    ValType,
+    imports, namespace, ExitStatus, Exports, ExternRef, Function, FunctionType, Global,
+    ImportObject, Instance, Memory, MemoryType, Module, NativeFunc, Pages, RuntimeError,
+    StderrCapture, Store, Table, TableType, Val, ValType,
 };
 
 #[cfg(unix)]
@@ -54,7 +55,10 @@ mod memory;
 mod process;
 mod pthread;
 mod ptr;
+mod script;
 mod signal;
+mod source_map;
+mod standalone;
 mod storage;
 mod syscalls;
 mod time;
@@ -63,6 +67,9 @@ mod unistd;
 mod utils;
 mod varargs;
 
+pub use self::script::{ScriptCallback, ScriptValue};
+pub use self::source_map::{SourceLocation, SourceMap, SourceMapError};
+pub use self::standalone::{generate_standalone_env, is_standalone_emscripten_module};
 pub use self::storage::{align_memory, static_alloc};
 pub use self::utils::{
     allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size, get_emscripten_metadata,
@@ -74,6 +81,8 @@ pub use self::utils::{
 pub struct EmEnv {
     memory: Arc<Option<Memory>>,
     data: *mut *mut EmscriptenData<'static>,
+    stderr_capture: Arc<Mutex<StderrCapture>>,
+    script_callback: Arc<Mutex<Option<Box<ScriptCallback>>>>,
 }
 
 impl EmEnv {
@@ -82,6 +91,8 @@ impl EmEnv {
             memory: Arc::new(None),
             // TODO: clean this up
             data: Box::into_raw(Box::new(std::ptr::null_mut())),
+            stderr_capture: Arc::new(Mutex::new(StderrCapture::new())),
+            script_callback: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -96,12 +107,98 @@ impl EmEnv {
         unsafe { *self.data = data as _ };
     }
 
+    /// Sets the `callback` running the JavaScript of the guest, given to
+    /// `emscripten_run_script` and its variants or written in `EM_ASM`
+    /// blocks, with their arguments. Without it, the scripts are ignored
+    /// and have no value.
+    pub fn set_script_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&str, &[f64]) -> ScriptValue + Send + 'static,
+    {
+        *self.script_callback.lock().unwrap() = Some(Box::new(callback));
+    }
+
     /// Get a reference to the memory
     pub fn memory(&self, _mem_idx: u32) -> &Memory {
         (*self.memory).as_ref().unwrap()
     }
+
+    /// Records `bytes` written by the guest to stderr.
+    pub(crate) fn capture_stderr(&self, bytes: &[u8]) {
+        self.stderr_capture.lock().unwrap().write(bytes);
+    }
+
+    /// Returns how the guest ended, given the `result` of running it,
+    /// e.g. with [`run_emscripten_instance`].
+    pub fn exit_status(&self, result: Result<(), RuntimeError>) -> ExitStatus {
+        let status = match result {
+            Ok(()) => ExitStatus::exited(0),
+            Err(error) => match error.downcast::<EmscriptenError>() {
+                Ok(EmscriptenError::Exit(code)) => ExitStatus::exited(code),
+                Ok(EmscriptenError::Abort(reason)) => ExitStatus::aborted(reason),
+                Err(error) => ExitStatus::trapped(error.message()),
+            },
+        };
+        status.with_panic_message(&self.stderr_capture.lock().unwrap())
+    }
+}
+
+/// How an Emscripten guest ended early, raised as a [`RuntimeError`] by
+/// the imports and found with [`RuntimeError::downcast`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmscriptenError {
+    /// The guest called `exit` with the given code.
+    Exit(i32),
+    /// The guest called `abort`, or an assertion failed, for the given
+    /// reason.
+    Abort(String),
 }
 
+impl fmt::Display for EmscriptenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Exit(code) => write!(f, "Emscripten exited with code: {}", code),
+            Self::Abort(reason) => write!(f, "Emscripten aborted: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EmscriptenError {}
+
+/// An error while setting up the environment of an Emscripten module,
+/// returned by [`EmscriptenGlobals::new`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmscriptenSetupError {
+    /// The module doesn't import a table.
+    MissingTable,
+    /// The module doesn't import a memory.
+    MissingMemory,
+    /// The dynamic base of the Emscripten metadata is too small.
+    DynamicBase(u32),
+    /// The dynamic top pointer of the Emscripten metadata is too small.
+    DynamicTopPtr(u32),
+    /// The dynamic top pointer is beyond the memory.
+    DynamicTopPtrOutOfBounds(u32),
+}
+
+impl fmt::Display for EmscriptenSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingTable => write!(f, "Emscripten requires at least one imported table"),
+            Self::MissingMemory => write!(f, "Emscripten requires at least one imported memory"),
+            Self::DynamicBase(base) => write!(f, "emscripten unexpected dynamic_base {}", base),
+            Self::DynamicTopPtr(ptr) => write!(f, "emscripten unexpected dynamictop_ptr {}", ptr),
+            Self::DynamicTopPtrOutOfBounds(ptr) => {
+                write!(f, "dynamictop_ptr {} beyond memory len", ptr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmscriptenSetupError {}
+
 // TODO: Magic number - how is this calculated?
 const TOTAL_STACK: u32 = 5_242_880;
 // TODO: make this variable
@@ -119,6 +216,19 @@ lazy_static! {
 const GLOBAL_BASE: u32 = 1024;
 const STATIC_BASE: u32 = GLOBAL_BASE;
 
+/// A file-backed region created by `mmap2`, keyed by its guest address.
+///
+/// `len` only covers the bytes that were read from the file, so writing the
+/// region back never extends the file past its size at map time.
+#[derive(Clone)]
+pub struct FileMapping {
+    pub fd: i32,
+    pub offset: i64,
+    pub len: u32,
+    /// `MAP_SHARED` with `PROT_WRITE`: changes are written back to the file.
+    pub write_back: bool,
+}
+
 pub struct EmscriptenData<'a> {
     pub globals: &'a EmscriptenGlobalsData,
 
@@ -129,6 +239,9 @@ pub struct EmscriptenData<'a> {
     pub stack_alloc: Option<NativeFunc<'a, u32, u32>>,
     pub jumps: Vec<UnsafeCell<[u32; 27]>>,
     pub opened_dirs: HashMap<i32, Box<*mut LibcDir>>,
+    pub file_mappings: HashMap<u32, FileMapping>,
+    /// The guest addresses of the anonymous regions created by `mmap2`.
+    pub anonymous_mappings: HashSet<u32>,
 
     pub dyn_call_i: Option<NativeFunc<'a, i32, i32>>,
     pub dyn_call_ii: Option<NativeFunc<'a, (i32, i32), i32>>,
@@ -334,6 +447,8 @@ impl<'a> EmscriptenData<'a> {
             stack_alloc,
             jumps: Vec::new(),
             opened_dirs: HashMap::new(),
+            file_mappings: HashMap::new(),
+            anonymous_mappings: HashSet::new(),
 
             dyn_call_i,
             dyn_call_ii,
@@ -525,12 +640,14 @@ fn store_module_arguments(ctx: &mut EmEnv, args: Vec<&str>) -> (u32, u32) {
 pub fn emscripten_set_up_memory(
     memory: &Memory,
     globals: &EmscriptenGlobalsData,
-) -> Result<(), String> {
+) -> Result<(), EmscriptenSetupError> {
     let dynamictop_ptr = globals.dynamictop_ptr;
     let dynamic_base = globals.dynamic_base;
 
     if (dynamictop_ptr / 4) as usize >= memory.view::<u32>().len() {
-        return Err("dynamictop_ptr beyond memory len".to_string());
+        return Err(EmscriptenSetupError::DynamicTopPtrOutOfBounds(
+            dynamictop_ptr,
+        ));
     }
     memory.view::<u32>()[(dynamictop_ptr / 4) as usize].set(dynamic_base);
     Ok(())
@@ -564,7 +681,7 @@ impl EmscriptenGlobals {
     pub fn new(
         store: &Store,
         module: &Module, /*, static_bump: u32 */
-    ) -> Result<Self, String> {
+    ) -> Result<Self, EmscriptenSetupError> {
         let mut use_old_abort_on_cannot_grow_memory = false;
         for import in module.imports().functions() {
             if import.name() == "abortOnCannotGrowMemory" && import.module() == "env" {
@@ -841,6 +958,7 @@ pub fn generate_emscripten_env(
         "___syscall337" => Function::new_native_with_env(store, env.clone(), crate::syscalls::___syscall337),
         "___syscall340" => Function::new_native_with_env(store, env.clone(), crate::syscalls::___syscall340),
         "___syscall345" => Function::new_native_with_env(store, env.clone(), crate::syscalls::___syscall345),
+        "___syscall403" => Function::new_native_with_env(store, env.clone(), crate::syscalls::___syscall403),
 
         // Process
         "abort" => Function::new_native_with_env(store, env.clone(), crate::process::em_abort),
@@ -881,6 +999,11 @@ pub fn generate_emscripten_env(
         // Emscripten
         "_emscripten_asm_const_i" => Function::new_native_with_env(store, env.clone(), crate::emscripten_target::asm_const_i),
         "_emscripten_exit_with_live_runtime" => Function::new_native_with_env(store, env.clone(), crate::emscripten_target::exit_with_live_runtime),
+        "_emscripten_run_script" => Function::new_native_with_env(store, env.clone(), crate::script::_emscripten_run_script),
+        "_emscripten_run_script_int" => Function::new_native_with_env(store, env.clone(), crate::script::_emscripten_run_script_int),
+        "_emscripten_run_script_string" => Function::new_native_with_env(store, env.clone(), crate::script::_emscripten_run_script_string),
+        "emscripten_asm_const_int" => Function::new_native_with_env(store, env.clone(), crate::script::emscripten_asm_const_int),
+        "emscripten_asm_const_double" => Function::new_native_with_env(store, env.clone(), crate::script::emscripten_asm_const_double),
 
         // Signal
         "_sigemptyset" => Function::new_native_with_env(store, env.clone(), crate::signal::_sigemptyset),
@@ -895,6 +1018,9 @@ pub fn generate_emscripten_env(
         "_emscripten_memcpy_big" => Function::new_native_with_env(store, env.clone(), crate::memory::_emscripten_memcpy_big),
         "_emscripten_get_heap_size" => Function::new_native_with_env(store, env.clone(), crate::memory::_emscripten_get_heap_size),
         "_emscripten_resize_heap" => Function::new_native_with_env(store, env.clone(), crate::memory::_emscripten_resize_heap),
+        "emscripten_get_heap_size" => Function::new_native_with_env(store, env.clone(), crate::memory::_emscripten_get_heap_size),
+        "emscripten_resize_heap" => Function::new_native_with_env(store, env.clone(), crate::memory::_emscripten_resize_heap),
+        "emscripten_notify_memory_growth" => Function::new_native_with_env(store, env.clone(), crate::memory::emscripten_notify_memory_growth),
         "enlargeMemory" => Function::new_native_with_env(store, env.clone(), crate::memory::enlarge_memory),
         "segfault" => Function::new_native_with_env(store, env.clone(), crate::memory::segfault),
         "alignfault" => Function::new_native_with_env(store, env.clone(), crate::memory::alignfault),
@@ -1165,3 +1291,129 @@ pub fn nullfunc(ctx: &mut EmEnv, _x: u32) {
 
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Calls the `main` export of `wat`, importing `import` as
+    /// `env.import`, and returns how it ended.
+    fn run(env: &mut EmEnv, wat: &str, import: Function) -> ExitStatus {
+        let module = Module::new(import.store(), wat).unwrap();
+        let instance =
+            Instance::new(&module, &imports! { "env" => { "import" => import } }).unwrap();
+        env.set_memory(instance.exports.get_memory("memory").unwrap().clone());
+        let result = instance.exports.get_function("main").unwrap().call(&[]);
+        env.exit_status(result.map(|_| ()))
+    }
+
+    #[test]
+    fn exit_status_of_exit() {
+        let store = Store::default();
+        let mut env = EmEnv::new();
+        let exit = Function::new_native_with_env(&store, env.clone(), crate::process::_exit);
+        let wat = r#"(module
+            (import "env" "import" (func $exit (param i32)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (call $exit (i32.const 3))
+                (unreachable)))"#;
+        assert_eq!(run(&mut env, wat, exit), ExitStatus::exited(3));
+        assert_eq!(env.exit_status(Ok(())), ExitStatus::exited(0));
+    }
+
+    #[test]
+    fn exit_status_of_abort() {
+        let store = Store::default();
+        let mut env = EmEnv::new();
+        let abort = Function::new_native_with_env(&store, env.clone(), crate::process::em_abort);
+        let wat = r#"(module
+            (import "env" "import" (func $abort (param i32)))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (call $abort (i32.const 7))))"#;
+        assert_eq!(
+            run(&mut env, wat, abort),
+            ExitStatus::aborted("Program aborted with value 7")
+        );
+
+        let mut env = EmEnv::new();
+        let assert_fail =
+            Function::new_native_with_env(&store, env.clone(), crate::env::___assert_fail);
+        let wat = r#"(module
+            (import "env" "import" (func $assert_fail (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "x == 1\00")
+            (data (i32.const 32) "main.c\00")
+            (data (i32.const 48) "main\00")
+            (func (export "main")
+                (call $assert_fail (i32.const 16) (i32.const 32) (i32.const 12) (i32.const 48))))"#;
+        assert_eq!(
+            run(&mut env, wat, assert_fail),
+            ExitStatus::aborted("Assertion failed: x == 1, at: main.c,12,main")
+        );
+    }
+
+    #[test]
+    fn exit_status_of_trap() {
+        let store = Store::default();
+        let mut env = EmEnv::new();
+        let abort = Function::new_native_with_env(&store, env.clone(), crate::process::_abort);
+        let wat = r#"(module
+            (import "env" "import" (func $abort))
+            (memory (export "memory") 1)
+            (func (export "main")
+                (unreachable)))"#;
+        env.capture_stderr(b"thread 'main' panicked at 'oops', src/main.rs:2:5\n");
+        env.capture_stderr(b"note: run with `RUST_BACKTRACE=1` for a backtrace\n");
+        let status = run(&mut env, wat, abort);
+        assert_eq!(status.code, None);
+        assert!(status.trap.is_some());
+        assert_eq!(
+            status.panic_message.as_deref(),
+            Some("thread 'main' panicked at 'oops', src/main.rs:2:5")
+        );
+    }
+
+    #[test]
+    fn setup_errors() {
+        let store = Store::default();
+        let setup = |wat: &str| {
+            let module = Module::new(&store, wat).unwrap();
+            EmscriptenGlobals::new(&store, &module).map(|_| ())
+        };
+        assert_eq!(
+            setup(r#"(module (import "env" "memory" (memory 1)))"#),
+            Err(EmscriptenSetupError::MissingTable)
+        );
+        assert_eq!(
+            setup(r#"(module (import "env" "table" (table 0 funcref)))"#),
+            Err(EmscriptenSetupError::MissingMemory)
+        );
+
+        // the last two globals are the dynamic top pointer and base, plus 32
+        let with_metadata = |dynamictop_ptr: u32, dynamic_base: u32| {
+            setup(&format!(
+                r#"(module
+                    (import "env" "table" (table 0 funcref))
+                    (import "env" "memory" (memory 1))
+                    (global i32 (i32.const {}))
+                    (global i32 (i32.const {})))"#,
+                dynamictop_ptr as i32, dynamic_base as i32
+            ))
+        };
+        assert_eq!(with_metadata(1056, 2080), Ok(()));
+        assert_eq!(
+            with_metadata(1056, 16),
+            Err(EmscriptenSetupError::DynamicBase(16))
+        );
+        assert_eq!(
+            with_metadata(16, 2080),
+            Err(EmscriptenSetupError::DynamicTopPtr(16))
+        );
+        assert_eq!(
+            with_metadata(0x10020, 2080),
+            Err(EmscriptenSetupError::DynamicTopPtrOutOfBounds(0x10000))
+        );
+    }
+}
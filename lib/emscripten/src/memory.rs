@@ -40,41 +40,67 @@ fn align_up(mut val: usize, multiple: usize) -> usize {
 /// Note: this function only allows growing the size of heap
 pub fn _emscripten_resize_heap(ctx: &mut EmEnv, requested_size: u32) -> u32 {
     debug!("emscripten::_emscripten_resize_heap {}", requested_size);
-    let current_memory_pages = ctx.memory(0).size();
-    let current_memory = current_memory_pages.bytes().0 as u32;
+    let memory = ctx.memory(0);
+    let current_memory = memory.size().bytes().0;
+    let max_memory = memory
+        .ty()
+        .maximum
+        .unwrap_or(Pages(WASM_MAX_PAGES))
+        .bytes()
+        .0;
+    if requested_size as usize > max_memory {
+        debug!("=> the maximum of the memory is {} bytes", max_memory);
+        return 0;
+    }
 
     // implementation from emscripten
-    let mut new_size = usize::max(
-        current_memory as usize,
-        WASM_MIN_PAGES as usize * WASM_PAGE_SIZE,
-    );
+    let mut new_size = usize::max(current_memory, WASM_MIN_PAGES as usize * WASM_PAGE_SIZE);
     while new_size < requested_size as usize {
         if new_size <= 0x2000_0000 {
             new_size = align_up(new_size * 2, WASM_PAGE_SIZE);
         } else {
-            new_size = usize::min(
-                align_up((3 * new_size + 0x8000_0000) / 4, WASM_PAGE_SIZE),
-                WASM_PAGE_SIZE * WASM_MAX_PAGES as usize,
-            );
+            new_size = align_up((3 * new_size + 0x8000_0000) / 4, WASM_PAGE_SIZE);
         }
     }
+    let new_size = usize::min(new_size, max_memory);
 
-    let amount_to_grow = (new_size - current_memory as usize) / WASM_PAGE_SIZE;
-    if let Ok(_pages_allocated) = ctx.memory(0).grow(Pages(amount_to_grow as u32)) {
-        debug!("{} pages allocated", _pages_allocated.0);
-        1
-    } else {
-        0
+    let amount_to_grow = (new_size - current_memory) / WASM_PAGE_SIZE;
+    match memory.grow(Pages(amount_to_grow as u32)) {
+        Ok(_old_pages) => {
+            debug!(
+                "=> grown from {} to {} pages",
+                _old_pages.0,
+                memory.size().0
+            );
+            emscripten_notify_memory_growth(ctx, 0);
+            1
+        }
+        Err(_error) => {
+            debug!("=> the memory can't grow: {}", _error);
+            0
+        }
     }
 }
 
+/// emscripten: emscripten_notify_memory_growth
+/// Called by the guest after growing its memory itself, and by the
+/// imports after growing it for the guest. The views of the memory are
+/// taken again on each access, so nothing refers to the old size.
+pub fn emscripten_notify_memory_growth(ctx: &mut EmEnv, _memory_index: u32) {
+    debug!(
+        "emscripten::emscripten_notify_memory_growth {}: {} pages",
+        _memory_index,
+        ctx.memory(0).size().0
+    );
+}
+
 /// emscripten: sbrk
 pub fn sbrk(ctx: &mut EmEnv, increment: i32) -> i32 {
     debug!("emscripten::sbrk");
     // let old_dynamic_top = 0;
     // let new_dynamic_top = 0;
     let globals = get_emscripten_data(ctx).globals;
-    let dynamictop_ptr = (globals.dynamictop_ptr) as usize;
+    let dynamictop_ptr = (globals.dynamictop_ptr / 4) as usize;
     let old_dynamic_top = ctx.memory(0).view::<u32>()[dynamictop_ptr].get() as i32;
     let new_dynamic_top: i32 = old_dynamic_top + increment;
     let total_memory = _emscripten_get_heap_size(ctx) as i32;
@@ -105,11 +131,14 @@ pub fn get_total_memory(_ctx: &mut EmEnv) -> u32 {
 }
 
 /// emscripten: enlargeMemory
-pub fn enlarge_memory(_ctx: &mut EmEnv) -> u32 {
+/// Grows the memory so it ends after `DYNAMICTOP`, already moved by the
+/// guest. Returns whether it succeeded.
+pub fn enlarge_memory(ctx: &mut EmEnv) -> u32 {
     debug!("emscripten::enlarge_memory");
-    // instance.memories[0].grow(100);
-    // TODO: Fix implementation
-    0
+    let dynamictop_ptr = (get_emscripten_data(ctx).globals.dynamictop_ptr / 4) as usize;
+    let dynamic_top = ctx.memory(0).view::<u32>()[dynamictop_ptr].get();
+    debug!("=> dynamic top: {}", dynamic_top);
+    _emscripten_resize_heap(ctx, dynamic_top)
 }
 
 /// emscripten: abortOnCannotGrowMemory
@@ -153,3 +182,90 @@ pub fn ___map_file(_ctx: &mut EmEnv, _one: u32, _two: u32) -> c_int {
     // NOTE: TODO: Em returns -1 here as well. May need to implement properly
     -1
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{generate_emscripten_env, EmEnv, EmscriptenData, EmscriptenGlobals};
+    use std::collections::HashMap;
+    use wasmer::{Instance, Module, Store, Val, WASM_PAGE_SIZE};
+
+    /// A guest growing its heap, like with `-s ALLOW_MEMORY_GROWTH=1`.
+    const GUEST: &str = r#"(module
+        (import "env" "memory" (memory 256 700))
+        (import "env" "table" (table 0 funcref))
+        (import "env" "emscripten_resize_heap" (func $resize_heap (param i32) (result i32)))
+        (import "env" "_emscripten_resize_heap" (func $resize_heap_legacy (param i32) (result i32)))
+        (import "env" "enlargeMemory" (func $enlarge_memory (result i32)))
+        (import "env" "emscripten_notify_memory_growth" (func $notify (param i32)))
+        (import "env" "_emscripten_get_heap_size" (func $heap_size (result i32)))
+        (func (export "resize_heap") (param i32) (result i32)
+            (call $resize_heap (local.get 0)))
+        (func (export "resize_heap_legacy") (param i32) (result i32)
+            (call $resize_heap_legacy (local.get 0)))
+        (func (export "enlarge_memory") (result i32)
+            (call $enlarge_memory))
+        (func (export "heap_size") (result i32)
+            (call $heap_size))
+        (func (export "grow_and_notify") (param i32) (result i32)
+            (local $old i32)
+            (local.set $old (memory.grow (local.get 0)))
+            (call $notify (i32.const 0))
+            (local.get $old))
+        (func (export "touch") (param i32)
+            (i32.store8 (local.get 0) (i32.const 1))))"#;
+
+    fn pages(count: usize) -> i32 {
+        (count * WASM_PAGE_SIZE) as i32
+    }
+
+    #[test]
+    fn heap_growth() {
+        let store = Store::default();
+        let module = Module::new(&store, GUEST).unwrap();
+        let mut globals = EmscriptenGlobals::new(&store, &module).unwrap();
+        let mut env = EmEnv::new();
+        let imports = generate_emscripten_env(&store, &mut globals, &mut env);
+        let mut instance = Instance::new(&module, &imports).unwrap();
+        let mut data = EmscriptenData::new(&mut instance, &globals.data, HashMap::new());
+        env.set_memory(globals.memory.clone());
+        env.set_data(&mut data as *mut _ as *mut std::ffi::c_void);
+        let call = |name: &str, args: &[Val]| {
+            let function = instance.exports.get_function(name).unwrap();
+            function
+                .call(args)
+                .map(|result| result.get(0).map(Val::unwrap_i32))
+        };
+        let heap_size = || call("heap_size", &[]).unwrap().unwrap();
+        assert_eq!(heap_size(), pages(256));
+
+        // The guest moved `DYNAMICTOP` past the end of the memory.
+        let dynamictop_ptr = globals.data.dynamictop_ptr as usize / 4;
+        globals.memory.view::<u32>()[dynamictop_ptr].set(pages(300) as u32);
+        assert_eq!(call("enlarge_memory", &[]).unwrap(), Some(1));
+        assert_eq!(heap_size(), pages(512));
+
+        // The guest grows the memory itself.
+        assert_eq!(call("grow_and_notify", &[Val::I32(1)]).unwrap(), Some(512));
+        assert_eq!(heap_size(), pages(513));
+
+        // The size doubles, up to the maximum of the memory.
+        assert_eq!(
+            call("resize_heap", &[Val::I32(pages(514))]).unwrap(),
+            Some(1)
+        );
+        assert_eq!(heap_size(), pages(700));
+        assert_eq!(
+            call("resize_heap", &[Val::I32(pages(701))]).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            call("resize_heap_legacy", &[Val::I32(pages(100))]).unwrap(),
+            Some(1)
+        );
+        assert_eq!(heap_size(), pages(700));
+
+        // The grown memory is accessible up to its new end.
+        assert!(call("touch", &[Val::I32(pages(700) - 1)]).is_ok());
+        assert!(call("touch", &[Val::I32(pages(700))]).is_err());
+    }
+}
@@ -1,30 +1,27 @@
-use libc::{abort, c_int, exit, EAGAIN};
+use libc::{c_int, EAGAIN};
 
 #[cfg(not(target_os = "windows"))]
 type PidT = libc::pid_t;
 #[cfg(target_os = "windows")]
 type PidT = c_int;
 
-use crate::EmEnv;
+use crate::{EmEnv, EmscriptenError};
+use wasmer::RuntimeError;
 
-pub fn abort_with_message(ctx: &mut EmEnv, message: &str) {
+pub fn abort_with_message(_ctx: &mut EmEnv, message: &str) {
     debug!("emscripten::abort_with_message");
-    println!("{}", message);
-    _abort(ctx);
+    RuntimeError::raise(Box::new(EmscriptenError::Abort(message.to_string())));
 }
 
 /// The name of this call is `abort` but we want to avoid conflicts with libc::abort
 pub fn em_abort(ctx: &mut EmEnv, arg: u32) {
     debug!("emscripten::abort");
-    eprintln!("Program aborted with value {}", arg);
-    _abort(ctx);
+    abort_with_message(ctx, &format!("Program aborted with value {}", arg));
 }
 
-pub fn _abort(_ctx: &mut EmEnv) {
+pub fn _abort(ctx: &mut EmEnv) {
     debug!("emscripten::_abort");
-    unsafe {
-        abort();
-    }
+    abort_with_message(ctx, "abort()");
 }
 
 pub fn _prctl(ctx: &mut EmEnv, _a: i32, _b: i32) -> i32 {
@@ -50,11 +47,9 @@ pub fn _execve(_ctx: &mut EmEnv, _one: i32, _two: i32, _three: i32) -> i32 {
     -1
 }
 
-#[allow(unreachable_code)]
 pub fn _exit(_ctx: &mut EmEnv, status: c_int) {
-    // -> !
     debug!("emscripten::_exit {}", status);
-    unsafe { exit(status) }
+    RuntimeError::raise(Box::new(EmscriptenError::Exit(status)));
 }
 
 pub fn _kill(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
@@ -192,11 +187,9 @@ pub fn _system(_ctx: &mut EmEnv, _one: i32) -> c_int {
     EAGAIN
 }
 
-pub fn _popen(_ctx: &mut EmEnv, _one: i32, _two: i32) -> c_int {
+pub fn _popen(ctx: &mut EmEnv, _one: i32, _two: i32) -> c_int {
     debug!("emscripten::_popen");
     // TODO: May need to change this Em impl to a working version
-    eprintln!("Missing function: popen");
-    unsafe {
-        abort();
-    }
+    abort_with_message(ctx, "missing function: popen");
+    0
 }
@@ -0,0 +1,279 @@
+//! The JavaScript run by the guests, with `emscripten_run_script` and
+//! `EM_ASM`, handed to a callback of the embedder.
+
+use crate::utils::{copy_cstr_into_wasm, read_string_from_wasm};
+use crate::EmEnv;
+use byteorder::{ByteOrder, LittleEndian};
+use std::ffi::CString;
+use std::os::raw::c_int;
+use wasmer::RuntimeError;
+
+/// The callback of the embedder running the scripts of a guest, given
+/// their code and their arguments, see [`EmEnv::set_script_callback`].
+pub type ScriptCallback = dyn FnMut(&str, &[f64]) -> ScriptValue + Send;
+
+/// The value of a script run by the embedder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptValue {
+    /// The script has no value, like `undefined` in JavaScript.
+    Undefined,
+    /// A number.
+    Number(f64),
+    /// A string.
+    String(String),
+}
+
+impl ScriptValue {
+    /// Converts the value to an integer, as JavaScript would.
+    fn to_int(&self) -> c_int {
+        self.to_double() as c_int
+    }
+
+    /// Converts the value to a double, as JavaScript would.
+    fn to_double(&self) -> f64 {
+        match self {
+            Self::Undefined => 0.0,
+            Self::Number(number) => *number,
+            Self::String(string) => string.trim().parse().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Runs `code` with the callback of the embedder, if any.
+fn run(ctx: &EmEnv, code: &str, args: &[f64]) -> ScriptValue {
+    match ctx.script_callback.lock().unwrap().as_mut() {
+        Some(callback) => callback(code, args),
+        None => {
+            debug!("=> no script callback, ignoring the script");
+            ScriptValue::Undefined
+        }
+    }
+}
+
+/// Reads the arguments of an `EM_ASM` block, laid out in `buf` as
+/// described by the signature at `sig`: a `d` or `f` for each double,
+/// aligned on 8 bytes, and another letter for each 32-bit integer.
+///
+/// Fails if the signature or the arguments are out of bounds of the
+/// memory.
+fn read_args(ctx: &EmEnv, sig: u32, buf: u32) -> Result<Vec<f64>, RuntimeError> {
+    let memory = ctx.memory(0);
+    let view = memory.view::<u8>();
+    let out_of_bounds = || {
+        RuntimeError::new(format!(
+            "the arguments of the EM_ASM block at {} are out of bounds",
+            buf
+        ))
+    };
+    if sig as usize >= view.len() {
+        return Err(out_of_bounds());
+    }
+    let sig = read_string_from_wasm(memory, sig);
+    // The offsets are computed in 64 bits, so they can't overflow.
+    let read = |offset: u64, len: u64| {
+        if offset + len > view.len() as u64 {
+            return Err(out_of_bounds());
+        }
+        Ok(view[offset as usize..(offset + len) as usize]
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<u8>>())
+    };
+    let mut offset = u64::from(buf);
+    let mut args = Vec::with_capacity(sig.len());
+    for ch in sig.bytes() {
+        if ch == b'd' || ch == b'f' {
+            offset = (offset + 7) & !7;
+            args.push(LittleEndian::read_f64(&read(offset, 8)?));
+            offset += 8;
+        } else {
+            args.push(f64::from(LittleEndian::read_i32(&read(offset, 4)?)));
+            offset += 4;
+        }
+    }
+    Ok(args)
+}
+
+/// Reads the code of a script at `code`, failing if it's out of bounds of
+/// the memory.
+fn read_code(ctx: &EmEnv, code: u32) -> Result<String, RuntimeError> {
+    let memory = ctx.memory(0);
+    if code as usize >= memory.view::<u8>().len() {
+        return Err(RuntimeError::new(format!(
+            "the script at {} is out of bounds",
+            code
+        )));
+    }
+    Ok(read_string_from_wasm(memory, code))
+}
+
+pub fn _emscripten_run_script(ctx: &mut EmEnv, script: u32) -> Result<(), RuntimeError> {
+    debug!("emscripten::_emscripten_run_script");
+    let code = read_code(ctx, script)?;
+    run(ctx, &code, &[]);
+    Ok(())
+}
+
+pub fn _emscripten_run_script_int(ctx: &mut EmEnv, script: u32) -> Result<c_int, RuntimeError> {
+    debug!("emscripten::_emscripten_run_script_int");
+    let code = read_code(ctx, script)?;
+    Ok(run(ctx, &code, &[]).to_int())
+}
+
+/// Returns a string allocated in the guest memory, or NULL if the
+/// script has no value.
+pub fn _emscripten_run_script_string(ctx: &mut EmEnv, script: u32) -> Result<u32, RuntimeError> {
+    debug!("emscripten::_emscripten_run_script_string");
+    let code = read_code(ctx, script)?;
+    let string = match run(ctx, &code, &[]) {
+        ScriptValue::Undefined => return Ok(0),
+        ScriptValue::Number(number) => number.to_string(),
+        ScriptValue::String(string) => string,
+    };
+    let string = string.split('\0').next().unwrap_or_default();
+    let cstring = CString::new(string).unwrap();
+    Ok(unsafe { copy_cstr_into_wasm(ctx, cstring.as_ptr()) })
+}
+
+pub fn emscripten_asm_const_int(
+    ctx: &mut EmEnv,
+    code: u32,
+    sig: u32,
+    buf: u32,
+) -> Result<c_int, RuntimeError> {
+    debug!("emscripten::emscripten_asm_const_int");
+    let code = read_code(ctx, code)?;
+    let args = read_args(ctx, sig, buf)?;
+    Ok(run(ctx, &code, &args).to_int())
+}
+
+pub fn emscripten_asm_const_double(
+    ctx: &mut EmEnv,
+    code: u32,
+    sig: u32,
+    buf: u32,
+) -> Result<f64, RuntimeError> {
+    debug!("emscripten::emscripten_asm_const_double");
+    let code = read_code(ctx, code)?;
+    let args = read_args(ctx, sig, buf)?;
+    Ok(run(ctx, &code, &args).to_double())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use wasmer::{Memory, MemoryType, Store};
+
+    fn env_with_memory() -> EmEnv {
+        let mut env = EmEnv::new();
+        let memory = Memory::new(&Store::default(), MemoryType::new(1, None, false)).unwrap();
+        env.set_memory(memory);
+        env
+    }
+
+    fn write_memory(env: &EmEnv, offset: u32, bytes: &[u8]) {
+        let view = env.memory(0).view::<u8>();
+        for (cell, byte) in view[offset as usize..].iter().zip(bytes) {
+            cell.set(*byte);
+        }
+    }
+
+    #[test]
+    fn script_values() {
+        assert_eq!(ScriptValue::Undefined.to_int(), 0);
+        assert_eq!(ScriptValue::Undefined.to_double(), 0.0);
+        assert_eq!(ScriptValue::Number(2.75).to_int(), 2);
+        assert_eq!(ScriptValue::Number(-2.75).to_int(), -2);
+        assert_eq!(ScriptValue::Number(2.75).to_double(), 2.75);
+        assert_eq!(ScriptValue::String(" 42 ".to_string()).to_int(), 42);
+        assert_eq!(ScriptValue::String("1.5".to_string()).to_double(), 1.5);
+        assert_eq!(ScriptValue::String("nope".to_string()).to_double(), 0.0);
+    }
+
+    #[test]
+    fn em_asm_args() {
+        let env = env_with_memory();
+        write_memory(&env, 0, b"idi\0");
+        // The double is aligned on 8 bytes, after the first integer.
+        write_memory(&env, 68, &1i32.to_le_bytes());
+        write_memory(&env, 72, &2.5f64.to_le_bytes());
+        write_memory(&env, 80, &(-3i32).to_le_bytes());
+        assert_eq!(read_args(&env, 0, 68).unwrap(), vec![1.0, 2.5, -3.0]);
+
+        write_memory(&env, 16, b"\0");
+        assert_eq!(read_args(&env, 16, 68).unwrap(), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn em_asm_args_out_of_bounds() {
+        let env = env_with_memory();
+        let size = env.memory(0).data_size() as u32;
+        write_memory(&env, 0, b"d\0");
+        assert!(read_args(&env, 0, size - 4).is_err());
+        assert!(read_args(&env, 0, u32::MAX).is_err());
+        assert!(read_args(&env, u32::MAX, 0).is_err());
+        assert!(read_args(&env, 0, size - 8).is_ok());
+    }
+
+    #[test]
+    fn scripts_are_run_by_the_callback() {
+        let mut env = env_with_memory();
+        write_memory(&env, 0, b"return $0 * 2\0");
+        write_memory(&env, 32, b"i\0");
+        write_memory(&env, 64, &21i32.to_le_bytes());
+        // Without a callback, the scripts have no value.
+        assert_eq!(emscripten_asm_const_int(&mut env, 0, 32, 64).unwrap(), 0);
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let recorded = calls.clone();
+        env.set_script_callback(move |code, args| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((code.to_string(), args.to_vec()));
+            ScriptValue::Number(args.iter().sum::<f64>() * 2.0)
+        });
+        assert_eq!(emscripten_asm_const_int(&mut env, 0, 32, 64).unwrap(), 42);
+        assert_eq!(
+            emscripten_asm_const_double(&mut env, 0, 32, 64).unwrap(),
+            42.0
+        );
+        assert_eq!(_emscripten_run_script_int(&mut env, 0).unwrap(), 0);
+        assert!(emscripten_asm_const_int(&mut env, 0, 32, u32::MAX).is_err());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                ("return $0 * 2".to_string(), vec![21.0]),
+                ("return $0 * 2".to_string(), vec![21.0]),
+                ("return $0 * 2".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn scripts_out_of_bounds() {
+        let mut env = env_with_memory();
+        let size = env.memory(0).data_size() as u32;
+        write_memory(&env, 0, b"i\0");
+        let calls = Arc::new(Mutex::new(0));
+        let counted = calls.clone();
+        env.set_script_callback(move |_, _| {
+            *counted.lock().unwrap() += 1;
+            ScriptValue::String("result".to_string())
+        });
+
+        for code in [size, u32::MAX].iter().copied() {
+            assert!(_emscripten_run_script(&mut env, code).is_err());
+            assert!(_emscripten_run_script_int(&mut env, code).is_err());
+            assert!(_emscripten_run_script_string(&mut env, code).is_err());
+            assert!(emscripten_asm_const_int(&mut env, code, 0, 64).is_err());
+            assert!(emscripten_asm_const_double(&mut env, code, 0, 64).is_err());
+        }
+        assert_eq!(*calls.lock().unwrap(), 0);
+
+        // the last byte of the memory is an empty script
+        assert!(_emscripten_run_script(&mut env, size - 1).is_ok());
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,409 @@
+//! Source maps, used to translate the positions of traps in Emscripten
+//! modules (built with `-g4`) into positions in the original C/C++
+//! sources.
+//!
+//! Only the parts of the [source map format] used by WebAssembly are
+//! supported: the generated "column" of a mapping is an offset in the
+//! module binary, and there's a single generated line.
+//!
+//! [source map format]: https://sourcemaps.info/spec.html
+
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use wasmer::{Module, RuntimeError};
+
+/// A position in an original source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The source file.
+    pub file: String,
+    /// The line, starting at 1.
+    pub line: u32,
+    /// The column, starting at 1.
+    pub column: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// An error while loading a source map.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SourceMapError {
+    /// The source map file couldn't be read.
+    Read {
+        /// The path of the source map.
+        path: PathBuf,
+        /// The error reading it.
+        source: io::Error,
+    },
+    /// The source map isn't valid JSON.
+    Json(serde_json::Error),
+    /// The source map lacks a required field.
+    MissingField(&'static str),
+    /// The mappings contain a character that isn't a Base64 digit.
+    InvalidCharacter(char),
+    /// A value of the mappings doesn't fit in 64 bits.
+    Overflow,
+    /// A position of the mappings is negative or doesn't fit in 32 bits.
+    OutOfRange,
+    /// The `sourceMappingURL` custom section is malformed.
+    MalformedUrl,
+    /// The source map URL isn't a path, e.g. an HTTP or data URL.
+    UnsupportedUrl(String),
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, source } => write!(
+                f,
+                "Can't read the source map `{}`: {}",
+                path.display(),
+                source
+            ),
+            Self::Json(error) => write!(f, "Can't parse the source map: {}", error),
+            Self::MissingField(field) => write!(f, "The source map has no `{}`", field),
+            Self::InvalidCharacter(c) => write!(f, "Invalid character `{}` in source map", c),
+            Self::Overflow => write!(f, "Source map value overflow"),
+            Self::OutOfRange => write!(f, "Position out of range in source map"),
+            Self::MalformedUrl => write!(f, "The `sourceMappingURL` section is malformed"),
+            Self::UnsupportedUrl(url) => write!(f, "Unsupported source map URL `{}`", url),
+        }
+    }
+}
+
+impl std::error::Error for SourceMapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { source, .. } => Some(source),
+            Self::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    module_offset: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+/// A parsed source map of a WebAssembly module.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    /// The mappings with a source, sorted by module offset.
+    mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// Parse a source map in its JSON format.
+    pub fn parse(json: &[u8]) -> Result<Self, SourceMapError> {
+        let json: Value = serde_json::from_slice(json).map_err(SourceMapError::Json)?;
+        let source_root = json["sourceRoot"].as_str().unwrap_or("");
+        let sources = json["sources"]
+            .as_array()
+            .ok_or(SourceMapError::MissingField("sources"))?
+            .iter()
+            .map(|source| format!("{}{}", source_root, source.as_str().unwrap_or("<unknown>")))
+            .collect::<Vec<_>>();
+        let mappings = json["mappings"]
+            .as_str()
+            .ok_or(SourceMapError::MissingField("mappings"))?;
+
+        let mut mappings = parse_mappings(mappings)?;
+        mappings.retain(|mapping| (mapping.source as usize) < sources.len());
+        mappings.sort_by_key(|mapping| mapping.module_offset);
+        Ok(Self { sources, mappings })
+    }
+
+    /// Read and parse a source map file.
+    pub fn from_file(path: &Path) -> Result<Self, SourceMapError> {
+        let json = fs::read(path).map_err(|source| SourceMapError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::parse(&json)
+    }
+
+    /// Load the source map referenced by the `sourceMappingURL` custom
+    /// section of `module`, if any.
+    ///
+    /// Relative URLs are resolved against `base_dir`, usually the
+    /// directory of the module file.
+    pub fn for_module(module: &Module, base_dir: &Path) -> Result<Option<Self>, SourceMapError> {
+        let section = match module.custom_sections("sourceMappingURL").next() {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let url = parse_source_mapping_url(&section).ok_or(SourceMapError::MalformedUrl)?;
+        if url.contains("://") || url.starts_with("data:") {
+            return Err(SourceMapError::UnsupportedUrl(url.to_string()));
+        }
+        Self::from_file(&base_dir.join(url)).map(Some)
+    }
+
+    /// Get the original source position of the given offset in the
+    /// module binary.
+    pub fn lookup(&self, module_offset: usize) -> Option<SourceLocation> {
+        let index = match self
+            .mappings
+            .binary_search_by_key(&(module_offset as u32), |mapping| mapping.module_offset)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let mapping = self.mappings[index];
+        Some(SourceLocation {
+            file: self.sources[mapping.source as usize].clone(),
+            line: mapping.line + 1,
+            column: mapping.column + 1,
+        })
+    }
+
+    /// Format `error` like its `Display` implementation, with the
+    /// original source positions of its frames.
+    pub fn format_error(&self, error: &RuntimeError) -> String {
+        let mut message = format!("RuntimeError: {}", error.message());
+        for frame in error.trace() {
+            message.push_str("\n    at ");
+            message.push_str(frame.function_name().unwrap_or("<unnamed>"));
+            match self.lookup(frame.module_offset()) {
+                Some(location) => message.push_str(&format!(" ({})", location)),
+                None => message.push_str(&format!(
+                    " ({}[{}]:0x{:x})",
+                    frame.module_name(),
+                    frame.func_index(),
+                    frame.module_offset()
+                )),
+            }
+        }
+        message
+    }
+}
+
+/// Parse the payload of a `sourceMappingURL` custom section: a string
+/// prefixed with its LEB128 length.
+fn parse_source_mapping_url(section: &[u8]) -> Option<&str> {
+    let mut len = 0usize;
+    let mut shift = 0;
+    let mut position = 0;
+    loop {
+        let byte = *section.get(position)?;
+        position += 1;
+        len |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+    let url = section.get(position..position.checked_add(len)?)?;
+    std::str::from_utf8(url).ok()
+}
+
+/// Decode a Base64 VLQ digit.
+fn base64_digit(c: u8) -> Option<u32> {
+    Some(match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    } as u32)
+}
+
+/// Decode the Base64 VLQ values of a mapping segment.
+fn parse_segment(segment: &str) -> Result<Vec<i64>, SourceMapError> {
+    let mut values = vec![];
+    let mut value = 0i64;
+    let mut shift = 0;
+    for c in segment.bytes() {
+        let digit = base64_digit(c).ok_or(SourceMapError::InvalidCharacter(c as char))?;
+        value += ((digit & 0x1f) as i64) << shift;
+        if digit & 0x20 != 0 {
+            shift += 5;
+            // the digits must fit in an i64
+            if shift > 55 {
+                return Err(SourceMapError::Overflow);
+            }
+            continue;
+        }
+        values.push(if value & 1 == 1 {
+            -(value >> 1)
+        } else {
+            value >> 1
+        });
+        value = 0;
+        shift = 0;
+    }
+    Ok(values)
+}
+
+/// Add the relative `delta` of a mapping field to its `position`, which
+/// must stay within a `u32`.
+fn advance(position: &mut u32, delta: i64) -> Result<(), SourceMapError> {
+    *position = i64::from(*position)
+        .checked_add(delta)
+        .and_then(|position| u32::try_from(position).ok())
+        .ok_or(SourceMapError::OutOfRange)?;
+    Ok(())
+}
+
+/// Parse the `mappings` of a source map, keeping the segments that
+/// point into a source.
+fn parse_mappings(mappings: &str) -> Result<Vec<Mapping>, SourceMapError> {
+    let (mut source, mut line, mut column) = (0u32, 0u32, 0u32);
+    let mut result = vec![];
+    for generated_line in mappings.split(';') {
+        // The generated column is relative to the previous segment of
+        // the same line, the other fields to the previous segment.
+        let mut module_offset = 0u32;
+        for segment in generated_line.split(',').filter(|s| !s.is_empty()) {
+            let values = parse_segment(segment)?;
+            if values.is_empty() {
+                continue;
+            }
+            advance(&mut module_offset, values[0])?;
+            if values.len() < 4 {
+                continue;
+            }
+            advance(&mut source, values[1])?;
+            advance(&mut line, values[2])?;
+            advance(&mut column, values[3])?;
+            result.push(Mapping {
+                module_offset,
+                source,
+                line,
+                column,
+            });
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn source_map(sources: &str, mappings: &str) -> SourceMap {
+        let json = format!(
+            r#"{{"version": 3, "sourceRoot": "src/", "sources": {}, "mappings": "{}"}}"#,
+            sources, mappings
+        );
+        SourceMap::parse(json.as_bytes()).unwrap()
+    }
+
+    fn location(file: &str, line: u32, column: u32) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            file: file.to_string(),
+            line,
+            column,
+        })
+    }
+
+    #[test]
+    fn segments() {
+        assert_eq!(parse_segment("AAAA").unwrap(), vec![0, 0, 0, 0]);
+        assert_eq!(parse_segment("CD").unwrap(), vec![1, -1]);
+        // values over 15 take several digits
+        assert_eq!(parse_segment("gB").unwrap(), vec![16]);
+        assert_eq!(parse_segment("2H3H").unwrap(), vec![123, -123]);
+        assert_eq!(parse_segment("w+BAAA").unwrap(), vec![1000, 0, 0, 0]);
+
+        assert!(matches!(
+            parse_segment("A!"),
+            Err(SourceMapError::InvalidCharacter('!'))
+        ));
+        assert!(matches!(
+            parse_segment("gggggggggggggB"),
+            Err(SourceMapError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn negative_deltas() {
+        let map = source_map(r#"["a.c", "b.c"]"#, "UAIE,UCFG;eDAA");
+        assert_eq!(map.lookup(10), location("src/a.c", 5, 3));
+        // the fields are relative to the previous segment
+        assert_eq!(map.lookup(20), location("src/b.c", 3, 6));
+        // even across generated lines, unlike the module offsets
+        assert_eq!(map.lookup(15), location("src/a.c", 3, 6));
+    }
+
+    #[test]
+    fn positions_out_of_range() {
+        let maps: [&[u8]; 3] = [
+            br#"{"sources": [], "mappings": "D"}"#,
+            br#"{"sources": ["a.c"], "mappings": "AAAD"}"#,
+            br#"{"sources": ["a.c"], "mappings": "AAAA,ADAA"}"#,
+        ];
+        for map in maps.iter() {
+            assert!(matches!(
+                SourceMap::parse(map),
+                Err(SourceMapError::OutOfRange)
+            ));
+        }
+    }
+
+    #[test]
+    fn malformed_source_maps() {
+        assert!(matches!(
+            SourceMap::parse(b"{"),
+            Err(SourceMapError::Json(_))
+        ));
+        assert!(matches!(
+            SourceMap::parse(br#"{"mappings": ""}"#),
+            Err(SourceMapError::MissingField("sources"))
+        ));
+        assert!(matches!(
+            SourceMap::parse(br#"{"sources": []}"#),
+            Err(SourceMapError::MissingField("mappings"))
+        ));
+
+        let error = SourceMap::from_file(Path::new("missing.wasm.map")).unwrap_err();
+        match &error {
+            SourceMapError::Read { path, source } => {
+                assert_eq!(path, Path::new("missing.wasm.map"));
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn lookups_between_mappings() {
+        let map = source_map(r#"["a.c"]"#, "UAIE,UAFG,KCAA");
+        // before the first mapping
+        assert_eq!(map.lookup(0), None);
+        assert_eq!(map.lookup(9), None);
+        // a mapping covers the offsets up to the next one
+        assert_eq!(map.lookup(10), location("src/a.c", 5, 3));
+        assert_eq!(map.lookup(19), location("src/a.c", 5, 3));
+        // the mapping at 25 is into an unknown source and is dropped
+        assert_eq!(map.lookup(20), location("src/a.c", 3, 6));
+        assert_eq!(map.lookup(1000), location("src/a.c", 3, 6));
+    }
+
+    #[test]
+    fn source_mapping_urls() {
+        assert_eq!(parse_source_mapping_url(b"\x06a.wasm"), Some("a.wasm"));
+        assert_eq!(parse_source_mapping_url(b"\x07a.wasm"), None);
+        assert_eq!(parse_source_mapping_url(b"\x80"), None);
+    }
+}
@@ -0,0 +1,212 @@
+//! The modules built by emscripten with `-s STANDALONE_WASM`, which run
+//! without its JavaScript runtime: they import the system from WASI, and
+//! a few functions of emscripten from `env`.
+
+use crate::{EmEnv, EmscriptenError};
+use wasmer::{Exports, Function, Module, RuntimeError};
+
+/// The namespaces of the WASI imports, of its snapshots 1 and 0.
+const WASI_NAMESPACES: [&str; 2] = ["wasi_snapshot_preview1", "wasi_unstable"];
+
+/// Returns whether the module was built by emscripten with
+/// `-s STANDALONE_WASM`: it imports functions from WASI, and maybe from
+/// `env`, nothing else, and exports its memory instead of importing it.
+///
+/// As other toolchains build such modules too, one of the marks of
+/// emscripten is required as well: an `emscripten_*` function imported
+/// from `env`, or an `emscripten_stack_*` or `stackAlloc` export.
+pub fn is_standalone_emscripten_module(module: &Module) -> bool {
+    let mut imports_wasi = false;
+    let mut marked = false;
+    for import in module.imports().functions() {
+        match import.module() {
+            "env" => marked |= import.name().starts_with("emscripten_"),
+            namespace if WASI_NAMESPACES.contains(&namespace) => imports_wasi = true,
+            _ => return false,
+        }
+    }
+    marked |= module.exports().functions().any(|export| {
+        export.name().starts_with("emscripten_stack_") || export.name() == "stackAlloc"
+    });
+    imports_wasi
+        && marked
+        && module.imports().count() == module.imports().functions().count()
+        && module.exports().memories().next().is_some()
+}
+
+/// Generates the `env` namespace of a module built with
+/// `-s STANDALONE_WASM`, to register along its WASI imports.
+///
+/// The functions of emscripten which run without its JavaScript runtime
+/// are provided, the other functions imported from `env` abort the guest
+/// when called. The memory exported by the instance must be given to
+/// [`EmEnv::set_memory`] before calling its exports.
+pub fn generate_standalone_env(module: &Module, env: &EmEnv) -> Exports {
+    let store = module.store();
+    let mut exports = Exports::new();
+    for import in module.imports().functions() {
+        if import.module() != "env" {
+            continue;
+        }
+        let name = import.name();
+        let function = match name {
+            "emscripten_notify_memory_growth" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::memory::emscripten_notify_memory_growth,
+            ),
+            "emscripten_memcpy_big" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::memory::_emscripten_memcpy_big,
+            ),
+            "emscripten_get_heap_size" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::memory::_emscripten_get_heap_size,
+            ),
+            "emscripten_resize_heap" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::memory::_emscripten_resize_heap,
+            ),
+            "emscripten_run_script" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::script::_emscripten_run_script,
+            ),
+            "emscripten_run_script_int" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::script::_emscripten_run_script_int,
+            ),
+            "emscripten_asm_const_int" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::script::emscripten_asm_const_int,
+            ),
+            "emscripten_asm_const_double" => Function::new_native_with_env(
+                store,
+                env.clone(),
+                crate::script::emscripten_asm_const_double,
+            ),
+            _ => {
+                let reason = format!("missing function: {}", name);
+                Function::new(store, import.ty(), move |_| {
+                    RuntimeError::raise(Box::new(EmscriptenError::Abort(reason.clone())))
+                })
+            }
+        };
+        exports.insert(name, function);
+    }
+    exports
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer::{ImportObject, Instance, Store};
+
+    fn is_standalone(wat: &str) -> bool {
+        is_standalone_emscripten_module(&Module::new(&Store::default(), wat).unwrap())
+    }
+
+    #[test]
+    fn standalone_modules() {
+        // The stack functions exported by emscripten.
+        assert!(is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start"))
+                (func (export "emscripten_stack_init")))"#
+        ));
+        assert!(is_standalone(
+            r#"(module
+                (import "wasi_unstable" "proc_exit" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "stackAlloc") (param i32) (result i32) (local.get 0)))"#
+        ));
+        // A function of emscripten imported from `env`.
+        assert!(is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "env" "emscripten_notify_memory_growth" (func (param i32)))
+                (memory (export "memory") 1))"#
+        ));
+    }
+
+    #[test]
+    fn other_modules() {
+        // A WASI module importing its undefined symbols from `env`, as
+        // built by other toolchains.
+        assert!(!is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "env" "callback" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")))"#
+        ));
+        // A plain WASI module.
+        assert!(!is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")))"#
+        ));
+        // A module of the emscripten runtime, importing its memory.
+        assert!(!is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "env" "memory" (memory 1))
+                (func (export "stackAlloc") (param i32) (result i32) (local.get 0)))"#
+        ));
+        // Another namespace, or no WASI imports.
+        assert!(!is_standalone(
+            r#"(module
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "host" "log" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "stackAlloc") (param i32) (result i32) (local.get 0)))"#
+        ));
+        assert!(!is_standalone(
+            r#"(module
+                (import "env" "emscripten_notify_memory_growth" (func (param i32)))
+                (memory (export "memory") 1))"#
+        ));
+    }
+
+    #[test]
+    fn standalone_env() {
+        let store = Store::default();
+        let module = Module::new(
+            &store,
+            r#"(module
+                (import "env" "emscripten_get_heap_size" (func $heap_size (result i32)))
+                (import "env" "emscripten_missing" (func $missing (param i32)))
+                (memory (export "memory") 2)
+                (func (export "heap_size") (result i32)
+                    (call $heap_size))
+                (func (export "missing")
+                    (call $missing (i32.const 0))))"#,
+        )
+        .unwrap();
+        let mut env = EmEnv::new();
+        let mut imports = ImportObject::new();
+        imports.register("env", generate_standalone_env(&module, &env));
+        let instance = Instance::new(&module, &imports).unwrap();
+        env.set_memory(instance.exports.get_memory("memory").unwrap().clone());
+
+        let heap_size = instance
+            .exports
+            .get_native_function::<(), i32>("heap_size")
+            .unwrap();
+        assert_eq!(heap_size.call().unwrap(), 2 * 65536);
+        let missing = instance.exports.get_function("missing").unwrap();
+        let error = missing.call(&[]).unwrap_err();
+        assert_eq!(
+            error.downcast::<EmscriptenError>().unwrap(),
+            EmscriptenError::Abort("missing function: emscripten_missing".to_string())
+        );
+    }
+}
@@ -13,7 +13,7 @@ pub use self::windows::*;
 use crate::{
     ptr::{Array, WasmPtr},
     utils::{copy_stat_into_wasm, get_cstr_path, get_current_directory},
-    EmEnv,
+    EmEnv, EmscriptenError, FileMapping,
 };
 
 use super::varargs::VarArgs;
@@ -27,7 +27,6 @@ use libc::{
     // setsockopt, getppid
     close,
     dup2,
-    exit,
     fstat,
     getpid,
     // readlink,
@@ -42,22 +41,30 @@ use libc::{
     // writev,
     stat,
     write,
+    EFAULT,
+    EINVAL,
     // ENOTTY,
 };
 
 use super::env;
-use std::cell::Cell;
 #[allow(unused_imports)]
 use std::io::Error;
 use std::slice;
+use wasmer::RuntimeError;
+
+// mmap flags as defined by the guest's (musl) headers
+const PROT_WRITE: i32 = 0x2;
+const MAP_SHARED: i32 = 0x1;
+
+// pipe2 flags as defined by the guest's (musl) headers
+const O_NONBLOCK: i32 = 0o4000;
+const O_CLOEXEC: i32 = 0o2000000;
 
 /// exit
 pub fn ___syscall1(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) {
     debug!("emscripten::___syscall1 (exit) {}", _which);
     let status: i32 = varargs.get(ctx);
-    unsafe {
-        exit(status);
-    }
+    RuntimeError::raise(Box::new(EmscriptenError::Exit(status)));
 }
 
 /// read
@@ -82,7 +89,11 @@ pub fn ___syscall4(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_in
     let count: i32 = varargs.get(ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
     let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
-    unsafe { write(fd, buf_addr, count as _) as i32 }
+    let ret = unsafe { write(fd, buf_addr, count as _) as i32 };
+    if fd == 2 && ret > 0 {
+        ctx.capture_stderr(unsafe { slice::from_raw_parts(buf_addr as *const u8, ret as usize) });
+    }
+    ret
 }
 
 /// close
@@ -183,8 +194,8 @@ pub fn ___syscall38(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> i32
     let result = unsafe { rename(real_old_path, real_new_path) };
     debug!(
         "=> old_path: {}, new_path: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_old_path).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(real_new_path).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(real_old_path).to_string_lossy() },
+        unsafe { std::ffi::CStr::from_ptr(real_new_path).to_string_lossy() },
         result
     );
     result
@@ -208,28 +219,50 @@ pub fn ___syscall42(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_i
     debug!("emscripten::___syscall42 (pipe)");
     // offset to a file descriptor, which contains a read end and write end, 2 integers
     let fd_offset: u32 = varargs.get(ctx);
+    pipe(ctx, fd_offset, 0)
+}
 
-    let emscripten_memory = ctx.memory(0);
-
-    // convert the file descriptor into a vec with two slots
-    let mut fd_vec: Vec<c_int> = emscripten_memory.view()[((fd_offset / 4) as usize)..]
-        .iter()
-        .map(|pipe_end: &Cell<c_int>| pipe_end.get())
-        .take(2)
-        .collect();
-
-    // get it as a mutable pointer
-    let fd_ptr = fd_vec.as_mut_ptr();
+/// Creates a pipe with the guest `flags`, and writes its read end and
+/// write end at `fd_offset`.
+fn pipe(ctx: &mut EmEnv, fd_offset: u32, flags: c_int) -> c_int {
+    // checked first, so the pipe isn't leaked
+    if u64::from(fd_offset) + 8 > ctx.memory(0).data_size() {
+        return -EFAULT;
+    }
+    let mut fds: [c_int; 2] = [0; 2];
 
     // call pipe and store the pointers in this array
     #[cfg(target_os = "windows")]
-    let result: c_int = unsafe { libc::pipe(fd_ptr, 2048, 0) };
+    let result: c_int = unsafe { libc::pipe(fds.as_mut_ptr(), 2048, 0) };
     #[cfg(not(target_os = "windows"))]
-    let result: c_int = unsafe { libc::pipe(fd_ptr) };
+    let result: c_int = unsafe { libc::pipe(fds.as_mut_ptr()) };
     if result == -1 {
-        debug!("=> os error: {}", Error::last_os_error());
+        let errno = Error::last_os_error().raw_os_error().unwrap_or(0);
+        debug!("=> os error: errno {}", errno);
+        return -errno;
     }
-    result
+
+    // the flags of the guest differ from the host's, e.g. on macOS
+    #[cfg(not(target_os = "windows"))]
+    for &fd in fds.iter() {
+        unsafe {
+            if flags & O_NONBLOCK != 0 {
+                let status = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, status | libc::O_NONBLOCK);
+            }
+            if flags & O_CLOEXEC != 0 {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+    }
+    #[cfg(target_os = "windows")]
+    let _ = (flags, O_NONBLOCK, O_CLOEXEC);
+
+    let fd_view = ctx.memory(0).view::<c_int>();
+    fd_view[(fd_offset / 4) as usize].set(fds[0]);
+    fd_view[(fd_offset / 4) as usize + 1].set(fds[1]);
+    debug!("=> read end: {}, write end: {}", fds[0], fds[1]);
+    0
 }
 
 pub fn ___syscall51(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
@@ -278,8 +311,26 @@ pub fn ___syscall75(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn ___syscall91(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall91 - stub");
+/// munmap
+pub fn ___syscall91(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall91 (munmap) {}", _which);
+    let addr: u32 = varargs.get(ctx);
+    let _len: u32 = varargs.get(ctx);
+    debug!("=> addr: {}, len: {}", addr, _len);
+
+    // Only whole mappings are tracked, partial unmappings are ignored.
+    if env::get_emscripten_data(ctx)
+        .anonymous_mappings
+        .remove(&addr)
+    {
+        env::call_free(ctx, addr);
+    } else if let Some(mapping) = env::get_emscripten_data(ctx).file_mappings.remove(&addr) {
+        let ret = sync_file_mapping(ctx, addr, &mapping);
+        env::call_free(ctx, addr);
+        if ret < 0 {
+            return ret;
+        }
+    }
     0
 }
 
@@ -313,9 +364,28 @@ pub fn ___syscall133(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn ___syscall144(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall144");
-    -1
+/// msync
+pub fn ___syscall144(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall144 (msync) {}", _which);
+    let addr: u32 = varargs.get(ctx);
+    let len: u32 = varargs.get(ctx);
+    let _flags: i32 = varargs.get(ctx);
+    debug!("=> addr: {}, len: {}, flags: {}", addr, len, _flags);
+
+    let end = addr.saturating_add(len);
+    let overlapping: Vec<(u32, FileMapping)> = env::get_emscripten_data(ctx)
+        .file_mappings
+        .iter()
+        .filter(|(&base, mapping)| base < end && addr < base.saturating_add(mapping.len))
+        .map(|(&base, mapping)| (base, mapping.clone()))
+        .collect();
+    for (base, mapping) in overlapping {
+        let ret = sync_file_mapping(ctx, base, &mapping);
+        if ret < 0 {
+            return ret;
+        }
+    }
+    0
 }
 
 pub fn ___syscall147(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
@@ -353,11 +423,17 @@ pub fn ___syscall183(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> i3
     debug!("emscripten::___syscall183");
     let buf_offset: WasmPtr<libc::c_char, Array> = varargs.get(ctx);
     let _size: c_int = varargs.get(ctx);
-    let path = get_current_directory(ctx);
-    let path_string = path.unwrap().display().to_string();
+    let path = match get_current_directory(ctx) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let path_string = path.display().to_string();
     let len = path_string.len();
 
-    let buf_writer = buf_offset.deref(ctx.memory(0), 0, len as u32 + 1).unwrap();
+    let buf_writer = match buf_offset.deref(ctx.memory(0), 0, len as u32 + 1) {
+        Some(buf_writer) => buf_writer,
+        None => return -1,
+    };
     for (i, byte) in path_string.bytes().enumerate() {
         buf_writer[i].set(byte as _);
     }
@@ -370,13 +446,13 @@ pub fn ___syscall192(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     debug!("emscripten::___syscall192 (mmap2) {}", _which);
     let _addr: i32 = varargs.get(ctx);
     let len: u32 = varargs.get(ctx);
-    let _prot: i32 = varargs.get(ctx);
-    let _flags: i32 = varargs.get(ctx);
+    let prot: i32 = varargs.get(ctx);
+    let flags: i32 = varargs.get(ctx);
     let fd: i32 = varargs.get(ctx);
-    let _off: i32 = varargs.get(ctx);
+    let off: i32 = varargs.get(ctx);
     debug!(
         "=> addr: {}, len: {}, prot: {}, flags: {}, fd: {}, off: {}",
-        _addr, len, _prot, _flags, fd, _off
+        _addr, len, prot, flags, fd, off
     );
 
     if fd == -1 {
@@ -392,11 +468,36 @@ pub fn ___syscall192(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
                 assert_eq!(*real_ptr.add(i), 0);
             }
         }
+        env::get_emscripten_data(ctx).anonymous_mappings.insert(ptr);
         debug!("=> ptr: {}", ptr);
         return ptr as i32;
     } else {
-        // return ENODEV
-        return -19;
+        let ptr = env::call_memalign(ctx, 16384, len);
+        if ptr == 0 {
+            // ENOMEM
+            return -12;
+        }
+        env::call_memset(ctx, ptr, 0, len);
+        // mmap2 offsets are expressed in 4096-byte units
+        let offset = off as i64 * 4096;
+        let backed = match read_file_mapping(ctx, ptr, len, fd, offset) {
+            Ok(backed) => backed,
+            Err(errno) => {
+                env::call_free(ctx, ptr);
+                return errno;
+            }
+        };
+        let mapping = FileMapping {
+            fd,
+            offset,
+            len: backed,
+            write_back: flags & MAP_SHARED != 0 && prot & PROT_WRITE != 0,
+        };
+        env::get_emscripten_data(ctx)
+            .file_mappings
+            .insert(ptr, mapping);
+        debug!("=> ptr: {}, file bytes: {}", ptr, backed);
+        ptr as i32
     }
 }
 
@@ -412,7 +513,10 @@ pub fn ___syscall140(ctx: &mut EmEnv, _which: i32, mut varargs: VarArgs) -> i32
     let offset = offset_low;
     let ret = unsafe { lseek(fd, offset as _, whence) as i64 };
 
-    let result_ptr = result_ptr_value.deref(ctx.memory(0)).unwrap();
+    let result_ptr = match result_ptr_value.deref(ctx.memory(0)) {
+        Some(result_ptr) => result_ptr,
+        None => return -1,
+    };
     result_ptr.set(ret);
 
     debug!(
@@ -500,6 +604,9 @@ pub fn ___syscall146(ctx: &mut EmEnv, _which: i32, mut varargs: VarArgs) -> i32
                 debug!("=> os error: {}", Error::last_os_error());
                 return -1;
             }
+            if fd == 2 {
+                ctx.capture_stderr(slice::from_raw_parts(iov_base as *const u8, curr as usize));
+            }
             ret += curr;
         }
     }
@@ -547,7 +654,7 @@ pub fn ___syscall195(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
         let ret = stat(real_path, &mut _stat);
         debug!(
             "=> pathname: {}, buf: {} = {}",
-            std::ffi::CStr::from_ptr(real_path).to_str().unwrap(),
+            std::ffi::CStr::from_ptr(real_path).to_string_lossy(),
             buf,
             ret
         );
@@ -630,11 +737,6 @@ pub fn ___syscall298(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn ___syscall300(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall300");
-    -1
-}
-
 pub fn ___syscall301(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall301");
     -1
@@ -681,9 +783,13 @@ pub fn ___syscall320(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) ->
     0
 }
 
-pub fn ___syscall331(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall331");
-    -1
+// pipe2
+pub fn ___syscall331(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall331 (pipe2) {}", _which);
+    let fd_offset: u32 = varargs.get(ctx);
+    let flags: c_int = varargs.get(ctx);
+    debug!("=> fd_offset: {}, flags: {}", fd_offset, flags);
+    pipe(ctx, fd_offset, flags)
 }
 
 pub fn ___syscall333(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
@@ -732,3 +838,318 @@ pub fn ___syscall345(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall345");
     -1
 }
+
+// clock_gettime64
+pub fn ___syscall403(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall403 (clock_gettime64) {}", _which);
+    let clk_id: c_int = varargs.get(ctx);
+    let tp: u32 = varargs.get(ctx);
+    debug!("=> clk_id: {}, tp: {}", clk_id, tp);
+
+    let timespec = match crate::time::clock_time(clk_id as _) {
+        Some(timespec) => timespec,
+        None => return -EINVAL,
+    };
+    // the 64-bit `struct timespec`, with the nanoseconds padded to 64 bits
+    if u64::from(tp) + 16 > ctx.memory(0).data_size() {
+        return -EFAULT;
+    }
+    let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), tp) as *mut u8;
+    let buf = unsafe { slice::from_raw_parts_mut(buf_ptr, 16) };
+    LittleEndian::write_i64(&mut buf[..], timespec.sec);
+    LittleEndian::write_i64(&mut buf[8..], i64::from(timespec.nsec));
+    0
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::{generate_emscripten_env, EmEnv, EmscriptenData, EmscriptenGlobals};
+    use std::collections::HashMap;
+    use std::ffi::c_void;
+    use wasmer::{Instance, Memory, Module, Store, Val};
+
+    /// A guest calling the syscalls like musl does, with the allocator
+    /// used by `mmap2`.
+    const GUEST: &str = r#"(module
+        (import "env" "memory" (memory 256 256))
+        (import "env" "table" (table 0 funcref))
+        (import "env" "___syscall42" (func $pipe (param i32 i32) (result i32)))
+        (import "env" "___syscall91" (func $munmap (param i32 i32) (result i32)))
+        (import "env" "___syscall144" (func $msync (param i32 i32) (result i32)))
+        (import "env" "___syscall192" (func $mmap2 (param i32 i32) (result i32)))
+        (import "env" "___syscall221" (func $fcntl64 (param i32 i32) (result i32)))
+        (import "env" "___syscall300" (func $fstatat64 (param i32 i32) (result i32)))
+        (import "env" "___syscall331" (func $pipe2 (param i32 i32) (result i32)))
+        (import "env" "___syscall403" (func $clock_gettime64 (param i32 i32) (result i32)))
+        (import "env" "_clock_gettime" (func $clock_gettime (param i32 i32) (result i32)))
+        (global $heap (mut i32) (i32.const 1048576))
+        (func (export "_memalign") (param $align i32) (param $size i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr
+                (i32.and
+                    (i32.add (global.get $heap) (i32.sub (local.get $align) (i32.const 1)))
+                    (i32.sub (i32.const 0) (local.get $align))))
+            (global.set $heap (i32.add (local.get $ptr) (local.get $size)))
+            (local.get $ptr))
+        (func (export "_free") (param i32))
+        (func (export "_memset") (param $ptr i32) (param $value i32) (param $len i32) (result i32)
+            (local $i i32)
+            (block $done
+                (loop $fill
+                    (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                    (i32.store8 (i32.add (local.get $ptr) (local.get $i)) (local.get $value))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $fill)))
+            (local.get $ptr))
+        (func (export "pipe") (param i32 i32) (result i32)
+            (call $pipe (local.get 0) (local.get 1)))
+        (func (export "munmap") (param i32 i32) (result i32)
+            (call $munmap (local.get 0) (local.get 1)))
+        (func (export "msync") (param i32 i32) (result i32)
+            (call $msync (local.get 0) (local.get 1)))
+        (func (export "mmap2") (param i32 i32) (result i32)
+            (call $mmap2 (local.get 0) (local.get 1)))
+        (func (export "fcntl64") (param i32 i32) (result i32)
+            (call $fcntl64 (local.get 0) (local.get 1)))
+        (func (export "fstatat64") (param i32 i32) (result i32)
+            (call $fstatat64 (local.get 0) (local.get 1)))
+        (func (export "pipe2") (param i32 i32) (result i32)
+            (call $pipe2 (local.get 0) (local.get 1)))
+        (func (export "clock_gettime64") (param i32 i32) (result i32)
+            (call $clock_gettime64 (local.get 0) (local.get 1)))
+        (func (export "clock_gettime") (param i32 i32) (result i32)
+            (call $clock_gettime (local.get 0) (local.get 1))))"#;
+
+    /// Where the arguments of the syscalls are written.
+    const VARARGS: u32 = 64;
+    /// Where the syscalls write their results.
+    const OUT: u32 = 256;
+    /// Where the paths given to the syscalls are written.
+    const PATH: u32 = 1024;
+
+    struct Guest {
+        instance: Instance,
+        memory: Memory,
+    }
+
+    impl Guest {
+        /// Calls the syscall exported as `name` with the 32-bit `args`.
+        fn syscall(&self, name: &str, args: &[i32]) -> i32 {
+            for (index, arg) in args.iter().enumerate() {
+                self.write(VARARGS + 4 * index as u32, &arg.to_le_bytes());
+            }
+            let function = self.instance.exports.get_function(name).unwrap();
+            let result = function
+                .call(&[Val::I32(0), Val::I32(VARARGS as i32)])
+                .unwrap();
+            result[0].unwrap_i32()
+        }
+
+        fn write(&self, offset: u32, bytes: &[u8]) {
+            let view = self.memory.view::<u8>();
+            for (cell, byte) in view[offset as usize..].iter().zip(bytes) {
+                cell.set(*byte);
+            }
+        }
+
+        fn read(&self, offset: u32, len: usize) -> Vec<u8> {
+            let view = self.memory.view::<u8>();
+            view[offset as usize..offset as usize + len]
+                .iter()
+                .map(|cell| cell.get())
+                .collect()
+        }
+
+        fn read_i32(&self, offset: u32) -> i32 {
+            let view = self.memory.view::<u8>();
+            let mut bytes = [0; 4];
+            for (byte, cell) in bytes.iter_mut().zip(view[offset as usize..].iter()) {
+                *byte = cell.get();
+            }
+            i32::from_le_bytes(bytes)
+        }
+
+        fn read_i64(&self, offset: u32) -> i64 {
+            let low = self.read_i32(offset) as u32 as i64;
+            let high = self.read_i32(offset + 4) as i64;
+            low | (high << 32)
+        }
+    }
+
+    /// Runs `test` with a fresh instance of the guest.
+    fn with_guest(test: impl FnOnce(&Guest)) {
+        let store = Store::default();
+        let module = Module::new(&store, GUEST).unwrap();
+        let mut globals = EmscriptenGlobals::new(&store, &module).unwrap();
+        let mut env = EmEnv::new();
+        let imports = generate_emscripten_env(&store, &mut globals, &mut env);
+        let mut instance = Instance::new(&module, &imports).unwrap();
+        let mut data = EmscriptenData::new(&mut instance, &globals.data, HashMap::new());
+        env.set_memory(globals.memory.clone());
+        env.set_data(&mut data as *mut _ as *mut c_void);
+        test(&Guest {
+            instance,
+            memory: globals.memory.clone(),
+        });
+    }
+
+    #[test]
+    fn file_mappings() {
+        let path =
+            std::env::temp_dir().join(format!("wasmer-emscripten-mmap-{}", std::process::id()));
+        std::fs::write(&path, b"hello, world").unwrap();
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(&file);
+
+        with_guest(|guest| {
+            // a shared, writable mapping: PROT_READ | PROT_WRITE, MAP_SHARED
+            let addr = guest.syscall("mmap2", &[0, 4096, 3, 0x1, fd, 0]);
+            assert!(addr > 0);
+            assert_eq!(guest.read(addr as u32, 12), b"hello, world");
+            // the rest of the page is zeroed
+            assert_eq!(guest.read(addr as u32 + 12, 4), [0; 4]);
+
+            // msync writes the mapping back to the file, without growing it
+            guest.write(addr as u32, b"HELLO");
+            assert_eq!(guest.syscall("msync", &[addr, 4096, 4]), 0);
+            assert_eq!(std::fs::read(&path).unwrap(), b"HELLO, world");
+
+            // and so does munmap
+            guest.write(addr as u32 + 7, b"WORLD");
+            assert_eq!(guest.syscall("munmap", &[addr, 4096]), 0);
+            assert_eq!(std::fs::read(&path).unwrap(), b"HELLO, WORLD");
+
+            // a private mapping is never written back: MAP_PRIVATE
+            let addr = guest.syscall("mmap2", &[0, 4096, 3, 0x2, fd, 0]);
+            assert!(addr > 0);
+            guest.write(addr as u32, b"bye");
+            assert_eq!(guest.syscall("msync", &[addr, 4096, 4]), 0);
+            assert_eq!(guest.syscall("munmap", &[addr, 4096]), 0);
+            assert_eq!(std::fs::read(&path).unwrap(), b"HELLO, WORLD");
+        });
+
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn musl_syscalls() {
+        with_guest(musl_syscalls_of);
+    }
+
+    fn musl_syscalls_of(guest: &Guest) {
+        // pipe
+        assert_eq!(guest.syscall("pipe", &[OUT as i32]), 0);
+        let (read_end, write_end) = (guest.read_i32(OUT), guest.read_i32(OUT + 4));
+        assert!(read_end > 2 && write_end > 2 && read_end != write_end);
+        let mut byte = [0u8];
+        unsafe {
+            assert_eq!(libc::write(write_end, b"x".as_ptr() as *const _, 1), 1);
+            assert_eq!(libc::read(read_end, byte.as_mut_ptr() as *mut _, 1), 1);
+        }
+        assert_eq!(&byte, b"x");
+
+        // pipe2, with the flags of musl: O_NONBLOCK | O_CLOEXEC
+        assert_eq!(guest.syscall("pipe2", &[OUT as i32, 0o4000 | 0o2000000]), 0);
+        let (nonblocking, nonblocking_write) = (guest.read_i32(OUT), guest.read_i32(OUT + 4));
+        unsafe {
+            assert_ne!(
+                libc::fcntl(nonblocking, libc::F_GETFL) & libc::O_NONBLOCK,
+                0
+            );
+            assert_ne!(
+                libc::fcntl(nonblocking, libc::F_GETFD) & libc::FD_CLOEXEC,
+                0
+            );
+        }
+
+        // fcntl64: F_GETFL, F_GETLK64 and an unsupported command
+        assert_eq!(guest.syscall("fcntl64", &[nonblocking, 3, 0]), 0o4000);
+        assert_eq!(guest.syscall("fcntl64", &[read_end, 3, 0]), 0);
+        assert_eq!(guest.syscall("fcntl64", &[read_end, 12, OUT as i32]), 0);
+        assert_eq!(guest.read_i32(OUT) as i16, 2);
+        assert_eq!(guest.syscall("fcntl64", &[read_end, 999, 0]), -libc::EINVAL);
+
+        // fstatat64, relative to AT_FDCWD
+        let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml\0");
+        guest.write(PATH, manifest.as_bytes());
+        assert_eq!(
+            guest.syscall("fstatat64", &[-100, PATH as i32, OUT as i32, 0]),
+            0
+        );
+        let size = std::fs::metadata(&manifest[..manifest.len() - 1])
+            .unwrap()
+            .len();
+        assert_eq!(guest.read_i32(OUT + 36) as u64, size);
+        guest.write(PATH, b"/missing/file\0");
+        assert_eq!(
+            guest.syscall("fstatat64", &[-100, PATH as i32, OUT as i32, 0]),
+            -libc::ENOENT
+        );
+
+        // clock_gettime64, with a 64-bit `struct timespec`
+        assert_eq!(guest.syscall("clock_gettime64", &[0, OUT as i32]), 0);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        assert!((guest.read_i64(OUT) - now).abs() <= 1);
+        assert!(guest.read_i64(OUT + 8) < 1_000_000_000);
+        assert_eq!(
+            guest.syscall("clock_gettime64", &[99, OUT as i32]),
+            -libc::EINVAL
+        );
+
+        // the legacy clock_gettime takes its arguments directly
+        let clock_gettime = guest
+            .instance
+            .exports
+            .get_function("clock_gettime")
+            .unwrap();
+        let result = clock_gettime
+            .call(&[Val::I32(99), Val::I32(OUT as i32)])
+            .unwrap();
+        assert_eq!(result[0].unwrap_i32(), -libc::EINVAL);
+
+        // mmap2 and munmap of an anonymous region
+        let addr = guest.syscall("mmap2", &[0, 4096, 3, 0x22, -1, 0]);
+        assert!(addr > 0);
+        assert_eq!(addr % 16384, 0);
+        assert_eq!(guest.syscall("munmap", &[addr, 4096]), 0);
+
+        unsafe {
+            for fd in &[read_end, write_end, nonblocking, nonblocking_write] {
+                libc::close(*fd);
+            }
+        }
+    }
+
+    #[test]
+    fn syscalls_out_of_bounds() {
+        with_guest(|guest| {
+            let size = guest.memory.data_size() as u32;
+
+            // pipe and pipe2 write two file descriptors
+            for &offset in &[size - 4, u32::MAX - 3] {
+                assert_eq!(guest.syscall("pipe", &[offset as i32]), -libc::EFAULT);
+                assert_eq!(guest.syscall("pipe2", &[offset as i32, 0]), -libc::EFAULT);
+            }
+
+            // clock_gettime64 writes 16 bytes
+            for &offset in &[size - 8, u32::MAX - 7] {
+                assert_eq!(
+                    guest.syscall("clock_gettime64", &[0, offset as i32]),
+                    -libc::EFAULT
+                );
+            }
+            assert_eq!(
+                guest.syscall("clock_gettime64", &[0, (size - 16) as i32]),
+                0
+            );
+        });
+    }
+}
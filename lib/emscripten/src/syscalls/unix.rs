@@ -93,6 +93,21 @@ const WASM_TIOCSPGRP: u32 = 0x5410;
 const WASM_TIOCGWINSZ: u32 = 0x5413;
 const WASM_TCGETS: u32 = 0x5401;
 const WASM_TCSETSW: u32 = 0x5403;
+const WASM_F_DUPFD: c_int = 0;
+const WASM_F_GETFD: c_int = 1;
+const WASM_F_SETFD: c_int = 2;
+const WASM_F_GETFL: c_int = 3;
+const WASM_F_SETFL: c_int = 4;
+const WASM_F_GETLK64: c_int = 12;
+const WASM_F_SETLK64: c_int = 13;
+const WASM_F_SETLKW64: c_int = 14;
+const WASM_F_DUPFD_CLOEXEC: c_int = 1030;
+const WASM_F_UNLCK: i16 = 2;
+const WASM_O_ACCMODE: c_int = 0o3;
+const WASM_O_APPEND: c_int = 0o2000;
+const WASM_O_NONBLOCK: c_int = 0o4000;
+const WASM_AT_FDCWD: c_int = -100;
+const WASM_AT_SYMLINK_NOFOLLOW: c_int = 0x100;
 
 // Based on @syrusakbary sugerence at
 // https://github.com/wasmerio/wasmer/pull/532#discussion_r300837800
@@ -105,17 +120,42 @@ fn translate_ioctl(wasm_ioctl: u32) -> c_ulong {
         WASM_TCGETS => TCGETS as _,
         WASM_TCSETSW => TCSETSW as _,
         _otherwise => {
-            unimplemented!("The ioctl {} is not yet implemented", wasm_ioctl);
+            unreachable!("The ioctl {} is not translated", wasm_ioctl);
         }
     }
 }
 
+/// Translates the file status flags of the guest to the host's, which
+/// differ e.g. on macOS. The access mode is the same on every host.
+fn translate_status_flags_to_host(wasm_flags: c_int) -> c_int {
+    let mut flags = wasm_flags & WASM_O_ACCMODE;
+    if wasm_flags & WASM_O_APPEND != 0 {
+        flags |= libc::O_APPEND;
+    }
+    if wasm_flags & WASM_O_NONBLOCK != 0 {
+        flags |= libc::O_NONBLOCK;
+    }
+    flags
+}
+
+/// Translates the file status flags of the host to the guest's.
+fn translate_status_flags_to_wasm(flags: c_int) -> c_int {
+    let mut wasm_flags = flags & libc::O_ACCMODE;
+    if flags & libc::O_APPEND != 0 {
+        wasm_flags |= WASM_O_APPEND;
+    }
+    if flags & libc::O_NONBLOCK != 0 {
+        wasm_flags |= WASM_O_NONBLOCK;
+    }
+    wasm_flags
+}
+
 #[allow(unused_imports)]
 use std::ffi::CStr;
 
 use crate::env::EmSockAddr;
 use crate::utils::{self, get_cstr_path};
-use crate::EmEnv;
+use crate::{EmEnv, FileMapping};
 #[allow(unused_imports)]
 use std::io::Error;
 use std::mem;
@@ -167,7 +207,7 @@ pub fn ___syscall5(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_in
     } else {
         pathname_addr
     };
-    let _path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() };
+    let _path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_string_lossy() };
     let fd = unsafe { open(real_path, flags, mode) };
     debug!(
         "=> path: {}, flags: {}, mode: {} = fd: {}",
@@ -188,8 +228,8 @@ pub fn ___syscall9(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_in
     let result = unsafe { link(oldname_ptr, newname_ptr) };
     debug!(
         "=> oldname: {}, newname: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(oldname_ptr).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(newname_ptr).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(oldname_ptr).to_string_lossy() },
+        unsafe { std::ffi::CStr::from_ptr(newname_ptr).to_string_lossy() },
         result,
     );
     result
@@ -228,8 +268,8 @@ pub fn ___syscall83(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_i
     let result = unsafe { symlink(real_path1, real_path2) };
     debug!(
         "=> path1: {}, path2: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_path1).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(real_path2).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(real_path1).to_string_lossy() },
+        unsafe { std::ffi::CStr::from_ptr(real_path2).to_string_lossy() },
         result,
     );
     result
@@ -256,8 +296,8 @@ pub fn ___syscall85(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> i32
     }
     debug!(
         "=> path: {}, buf: {}, buf_size: {}, return: {} ",
-        unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(buf as _).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(real_path).to_string_lossy() },
+        unsafe { std::ffi::CStr::from_ptr(buf as _).to_string_lossy() },
         buf_size,
         ret
     );
@@ -278,7 +318,9 @@ pub fn ___syscall194(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
         ftruncate(_fd, _length)
     }
     #[cfg(target_os = "macos")]
-    unimplemented!("emscripten::___syscall194 (ftruncate64) {}", _which)
+    unsafe {
+        libc::ftruncate(_fd, _length)
+    }
 }
 
 /// lchown
@@ -296,7 +338,7 @@ pub fn ___syscall198(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     let result = unsafe { lchown(real_path, uid, gid) };
     debug!(
         "=> path: {}, uid: {}, gid: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(real_path).to_string_lossy() },
         uid,
         gid,
         result,
@@ -365,7 +407,7 @@ pub fn ___syscall33(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_i
     let result = unsafe { access(real_path, amode) };
     debug!(
         "=> path: {}, amode: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() },
+        unsafe { std::ffi::CStr::from_ptr(real_path).to_string_lossy() },
         amode,
         result
     );
@@ -549,8 +591,15 @@ pub fn ___syscall102(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
             }
 
             if ty_and_flags & SOCK_NON_BLOCK != 0 {
-                // do something here
-                unimplemented!("non blocking sockets");
+                // set_nonblocking
+                let mut on: c_int = 1;
+                unsafe {
+                    ioctl(
+                        fd,
+                        translate_ioctl(WASM_FIONBIO) as _,
+                        &mut on as *mut c_int,
+                    );
+                };
             }
 
             // why is this here?
@@ -626,14 +675,18 @@ pub fn ___syscall102(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
             let address: WasmPtr<EmSockAddr> = socket_varargs.get(ctx);
             let address_len: WasmPtr<u32> = socket_varargs.get(ctx);
 
+            let address_len_addr = match unsafe { address_len.deref_mut(ctx.memory(0)) } {
+                Some(address_len) => unsafe { address_len.get_mut() },
+                None => return -1,
+            };
+            let address_addr = match unsafe { address.deref_mut(ctx.memory(0)) } {
+                Some(address) => unsafe { address.get_mut() },
+                None => return -1,
+            };
             debug!(
                 "=> socket: {}, address: {:?}, address_len: {}",
-                socket,
-                address.deref(ctx.memory(0)).unwrap().get(),
-                address_len.deref(ctx.memory(0)).unwrap().get()
+                socket, address_addr, address_len_addr
             );
-            let address_len_addr =
-                unsafe { address_len.deref_mut(ctx.memory(0)).unwrap().get_mut() };
             // let mut address_len_addr: socklen_t = 0;
 
             let mut host_address: sockaddr = sockaddr {
@@ -643,7 +696,6 @@ pub fn ___syscall102(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
                 sa_len: Default::default(),
             };
             let fd = unsafe { accept(socket, &mut host_address, address_len_addr) };
-            let address_addr = unsafe { address.deref_mut(ctx.memory(0)).unwrap().get_mut() };
 
             address_addr.sa_family = host_address.sa_family as _;
             address_addr.sa_data = host_address.sa_data;
@@ -667,8 +719,14 @@ pub fn ___syscall102(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
             let socket: i32 = socket_varargs.get(ctx);
             let address: WasmPtr<EmSockAddr> = socket_varargs.get(ctx);
             let address_len: WasmPtr<u32> = socket_varargs.get(ctx);
-            let address_len_addr =
-                unsafe { address_len.deref_mut(ctx.memory(0)).unwrap().get_mut() };
+            let address_len_addr = match unsafe { address_len.deref_mut(ctx.memory(0)) } {
+                Some(address_len) => unsafe { address_len.get_mut() },
+                None => return -1,
+            };
+            let address_mut = match unsafe { address.deref_mut(ctx.memory(0)) } {
+                Some(address) => unsafe { address.get_mut() },
+                None => return -1,
+            };
 
             let mut sock_addr_host: sockaddr = sockaddr {
                 sa_family: Default::default(),
@@ -684,7 +742,6 @@ pub fn ___syscall102(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
                 )
             };
             // translate from host data into emscripten data
-            let mut address_mut = unsafe { address.deref_mut(ctx.memory(0)).unwrap().get_mut() };
             address_mut.sa_family = sock_addr_host.sa_family as _;
             address_mut.sa_data = sock_addr_host.sa_data;
 
@@ -859,7 +916,10 @@ pub fn ___syscall168(ctx: &mut EmEnv, _which: i32, mut varargs: VarArgs) -> i32
     let nfds: u32 = varargs.get(ctx);
     let timeout: i32 = varargs.get(ctx);
 
-    let fds_mut = unsafe { fds.deref_mut(ctx.memory(0)).unwrap().get_mut() };
+    let fds_mut = match unsafe { fds.deref_mut(ctx.memory(0)) } {
+        Some(fds) => unsafe { fds.get_mut() },
+        None => return -1,
+    };
 
     let ret = unsafe {
         libc::poll(
@@ -910,6 +970,68 @@ pub fn ___syscall181(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     status
 }
 
+/// Fills a freshly allocated mmap region from `fd`, returning how many bytes
+/// came from the file (reads past the end of the file leave zeroes).
+pub(crate) fn read_file_mapping(
+    ctx: &mut EmEnv,
+    ptr: u32,
+    len: u32,
+    fd: i32,
+    offset: i64,
+) -> Result<u32, c_int> {
+    let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), ptr) as *mut u8;
+    let mut filled = 0usize;
+    while filled < len as usize {
+        let ret = unsafe {
+            pread(
+                fd,
+                buf_ptr.add(filled) as *mut c_void,
+                len as usize - filled,
+                offset + filled as i64,
+            )
+        };
+        if ret < 0 {
+            let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+            debug!("=> mmap read failed: errno {}", errno);
+            return Err(-errno);
+        }
+        if ret == 0 {
+            break;
+        }
+        filled += ret as usize;
+    }
+    Ok(filled as u32)
+}
+
+/// Writes a shared, writable file mapping back to its file.
+pub(crate) fn sync_file_mapping(ctx: &mut EmEnv, addr: u32, mapping: &FileMapping) -> c_int {
+    if !mapping.write_back {
+        return 0;
+    }
+    let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), addr) as *const u8;
+    let mut written = 0usize;
+    while written < mapping.len as usize {
+        let ret = unsafe {
+            pwrite(
+                mapping.fd,
+                buf_ptr.add(written) as *const c_void,
+                mapping.len as usize - written,
+                mapping.offset + written as i64,
+            )
+        };
+        if ret < 0 {
+            let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+            debug!("=> mmap write-back failed: errno {}", errno);
+            return -errno;
+        }
+        if ret == 0 {
+            break;
+        }
+        written += ret as usize;
+    }
+    0
+}
+
 /// fchmod
 pub fn ___syscall94(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall118 (fchmod) {}", _which);
@@ -1098,9 +1220,7 @@ pub fn ___syscall220(ctx: &mut EmEnv, _which: i32, mut varargs: VarArgs) -> i32
             *(dirp.add(pos + 11 + i) as *mut c_char) = 0 as c_char;
             debug!(
                 "  => file {}",
-                CStr::from_ptr(dirp.add(pos + 11) as *const c_char)
-                    .to_str()
-                    .unwrap()
+                CStr::from_ptr(dirp.add(pos + 11) as *const c_char).to_string_lossy()
             );
         }
         pos += offset;
@@ -1114,11 +1234,30 @@ pub fn ___syscall221(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     let fd: i32 = varargs.get(ctx);
     let cmd: i32 = varargs.get(ctx);
     let arg: i32 = varargs.get(ctx);
-    // (FAPPEND   - 0x08
-    // |FASYNC    - 0x40
-    // |FFSYNC    - 0x80
-    // |FNONBLOCK - 0x04
-    let ret = unsafe { fcntl(fd, cmd, arg) };
+    let ret = match cmd {
+        WASM_F_DUPFD => unsafe { fcntl(fd, libc::F_DUPFD, arg) },
+        WASM_F_DUPFD_CLOEXEC => unsafe { fcntl(fd, libc::F_DUPFD_CLOEXEC, arg) },
+        // `FD_CLOEXEC` is 1 on every host
+        WASM_F_GETFD => unsafe { fcntl(fd, F_GETFD) },
+        WASM_F_SETFD => unsafe { fcntl(fd, F_SETFD, arg) },
+        WASM_F_GETFL => match unsafe { fcntl(fd, libc::F_GETFL) } {
+            -1 => -1,
+            flags => translate_status_flags_to_wasm(flags),
+        },
+        WASM_F_SETFL => unsafe { fcntl(fd, libc::F_SETFL, translate_status_flags_to_host(arg)) },
+        // Locks are not supported, like in emscripten: the file is
+        // always reported as unlocked, and locking it always succeeds.
+        WASM_F_GETLK64 => {
+            let l_type = emscripten_memory_pointer!(ctx.memory(0), arg) as *mut i16;
+            unsafe { *l_type = WASM_F_UNLCK };
+            0
+        }
+        WASM_F_SETLK64 | WASM_F_SETLKW64 => 0,
+        _ => {
+            debug!("=> unsupported command {}", cmd);
+            return -EINVAL;
+        }
+    };
     debug!("=> fd: {}, cmd: {} = {}", fd, cmd, ret);
     if ret == -1 {
         debug!("=> last os error: {}", Error::last_os_error(),);
@@ -1126,6 +1265,49 @@ pub fn ___syscall221(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     ret
 }
 
+/// fstatat64
+pub fn ___syscall300(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall300 (fstatat64) {}", _which);
+    let dirfd: c_int = varargs.get(ctx);
+    let path = varargs.get_str(ctx);
+    let buf_ptr: u32 = varargs.get(ctx);
+    let flags: c_int = varargs.get(ctx);
+    let real_path_owned = utils::get_cstr_path(ctx, path as *const _);
+    let real_path = if let Some(ref rp) = real_path_owned {
+        rp.as_c_str().as_ptr()
+    } else {
+        path
+    };
+    let dirfd = if dirfd == WASM_AT_FDCWD {
+        libc::AT_FDCWD
+    } else {
+        dirfd
+    };
+    let flags = if flags & WASM_AT_SYMLINK_NOFOLLOW != 0 {
+        libc::AT_SYMLINK_NOFOLLOW
+    } else {
+        0
+    };
+    unsafe {
+        let mut stat: stat = std::mem::zeroed();
+        let ret = libc::fstatat(dirfd, real_path, &mut stat, flags);
+        debug!(
+            "=> dirfd: {}, path: {}, flags: {} = {}",
+            dirfd,
+            CStr::from_ptr(real_path).to_string_lossy(),
+            flags,
+            ret
+        );
+        if ret != 0 {
+            let errno = Error::last_os_error().raw_os_error().unwrap_or(0);
+            debug!("=> os error: errno {}", errno);
+            return -errno;
+        }
+        utils::copy_stat_into_wasm(ctx, buf_ptr, &stat);
+    }
+    0
+}
+
 /// fallocate
 pub fn ___syscall324(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall324 (fallocate) {}", _which);
@@ -1139,6 +1321,6 @@ pub fn ___syscall324(ctx: &mut EmEnv, _which: c_int, mut varargs: VarArgs) -> c_
     }
     #[cfg(any(target_os = "freebsd", target_os = "macos", target_os = "android"))]
     {
-        unimplemented!("emscripten::___syscall324 (fallocate) {}", _which)
+        -1
     }
 }
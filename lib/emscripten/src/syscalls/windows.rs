@@ -1,6 +1,6 @@
 use crate::utils::{copy_cstr_into_wasm, get_cstr_path};
 use crate::varargs::VarArgs;
-use crate::EmEnv;
+use crate::{EmEnv, FileMapping};
 use libc::mkdir;
 use libc::open;
 use std::env;
@@ -26,8 +26,8 @@ pub fn ___syscall5(ctx: &mut EmEnv, which: c_int, mut varargs: VarArgs) -> c_int
     };
     let flags: i32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() };
-    match path_str {
+    let path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_string_lossy() };
+    match &*path_str {
         "/dev/urandom" => {
             // create a fake urandom file for windows, super hacky
             // put it in the temp directory so we can just forget about it
@@ -66,13 +66,13 @@ pub fn ___syscall5(ctx: &mut EmEnv, which: c_int, mut varargs: VarArgs) -> c_int
 /// link
 pub fn ___syscall9(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall9 (link) {}", _which);
-    unimplemented!("emscripten::___syscall9 (link) {}", _which);
+    -1
 }
 
 /// ftruncate64
 pub fn ___syscall194(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall194 - stub");
-    unimplemented!("emscripten::___syscall194 - stub")
+    -1
 }
 
 // chown
@@ -86,13 +86,13 @@ pub fn ___syscall212(_ctx: &mut EmEnv, which: c_int, mut _varargs: VarArgs) -> c
 /// access
 pub fn ___syscall33(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall33 (access) {}", _which);
-    unimplemented!("emscripten::___syscall33 (access) {}", _which);
+    -1
 }
 
 /// nice
 pub fn ___syscall34(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall34 (nice) {}", _which);
-    unimplemented!("emscripten::___syscall34 (nice) {}", _which);
+    -1
 }
 
 // mkdir
@@ -113,19 +113,19 @@ pub fn ___syscall39(ctx: &mut EmEnv, which: c_int, mut varargs: VarArgs) -> c_in
 /// dup
 pub fn ___syscall41(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall41 (dup) {}", _which);
-    unimplemented!("emscripten::___syscall41 (dup) {}", _which);
+    -1
 }
 
 /// getrusage
 pub fn ___syscall77(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall77 (getrusage) {}", _which);
-    unimplemented!("emscripten::___syscall77 (getrusage) {}", _which);
+    -1
 }
 
 /// symlink
 pub fn ___syscall83(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall83 (symlink) {}", _which);
-    unimplemented!("emscripten::___syscall83 (symlink) {}", _which);
+    -1
 }
 
 /// readlink
@@ -143,38 +143,38 @@ pub fn ___syscall132(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) ->
 /// lchown
 pub fn ___syscall198(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall198 (lchown) {}", _which);
-    unimplemented!("emscripten::___syscall198 (lchown) {}", _which);
+    -1
 }
 
 /// getgid32
 pub fn ___syscall200(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall200 (getgid32)");
-    unimplemented!("emscripten::___syscall200 (getgid32)");
+    -1
 }
 
 // geteuid32
 pub fn ___syscall201(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall201 (geteuid32)");
-    unimplemented!("emscripten::___syscall201 (geteuid32)");
+    -1
 }
 
 // getegid32
 pub fn ___syscall202(_ctx: &mut EmEnv, _one: i32, _two: i32) -> i32 {
     // gid_t
     debug!("emscripten::___syscall202 (getegid32)");
-    unimplemented!("emscripten::___syscall202 (getegid32)");
+    -1
 }
 
 /// getgroups
 pub fn ___syscall205(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall205 (getgroups) {}", _which);
-    unimplemented!("emscripten::___syscall205 (getgroups) {}", _which);
+    -1
 }
 
 /// madvise
 pub fn ___syscall219(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall212 (chown) {}", _which);
-    unimplemented!("emscripten::___syscall212 (chown) {}", _which);
+    -1
 }
 
 /// dup3
@@ -194,7 +194,7 @@ pub fn ___syscall54(_ctx: &mut EmEnv, which: c_int, mut _varargs: VarArgs) -> c_
 /// fchmod
 pub fn ___syscall94(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall118 (fchmod) {}", _which);
-    unimplemented!("emscripten::___syscall118 (fchmod) {}", _which);
+    -1
 }
 
 // socketcall
@@ -209,7 +209,7 @@ pub fn ___syscall102(_ctx: &mut EmEnv, which: c_int, mut _varargs: VarArgs) -> c
 /// fsync
 pub fn ___syscall118(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall118 (fsync) {}", _which);
-    unimplemented!("emscripten::___syscall118 (fsync) {}", _which);
+    -1
 }
 
 // pread
@@ -247,7 +247,7 @@ pub fn ___syscall142(_ctx: &mut EmEnv, which: c_int, mut _varargs: VarArgs) -> c
 /// fdatasync
 pub fn ___syscall148(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall148 (fdatasync) {}", _which);
-    unimplemented!("emscripten::___syscall148 (fdatasync) {}", _which);
+    -1
 }
 
 // setpgid
@@ -297,14 +297,36 @@ pub fn ___syscall221(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) ->
     -1
 }
 
+// fstatat64
+pub fn ___syscall300(_ctx: &mut EmEnv, _which: c_int, mut _varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall300 (fstatat64) {}", _which);
+    -1
+}
+
 /// fchown
 pub fn ___syscall207(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall207 (fchown) {}", _which);
-    unimplemented!("emscripten::___syscall207 (fchown) {}", _which)
+    -1
 }
 
 /// fallocate
 pub fn ___syscall324(_ctx: &mut EmEnv, _which: c_int, _varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall324 (fallocate) {}", _which);
-    unimplemented!("emscripten::___syscall324 (fallocate) {}", _which)
+    -1
+}
+
+/// File-backed mmap is not supported on Windows hosts.
+pub(crate) fn read_file_mapping(
+    _ctx: &mut EmEnv,
+    _ptr: u32,
+    _len: u32,
+    _fd: i32,
+    _offset: i64,
+) -> Result<u32, c_int> {
+    // ENODEV
+    Err(-19)
+}
+
+pub(crate) fn sync_file_mapping(_ctx: &mut EmEnv, _addr: u32, _mapping: &FileMapping) -> c_int {
+    0
 }
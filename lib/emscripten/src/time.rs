@@ -81,6 +81,23 @@ pub fn _clock_getres(_ctx: &mut EmEnv, _clk_id: i32, _tp: i32) -> i32 {
     0
 }
 
+/// Returns the time of the clock `clk_id`, if it is supported.
+pub(crate) fn clock_time(clk_id: clockid_t) -> Option<time::Timespec> {
+    #[allow(unreachable_patterns)]
+    match clk_id {
+        CLOCK_REALTIME => Some(time::get_time()),
+
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE => {
+            let precise_ns = time::precise_time_ns();
+            Some(time::Timespec::new(
+                (precise_ns / 1000000000) as i64,
+                (precise_ns % 1000000000) as i32,
+            ))
+        }
+        _ => None,
+    }
+}
+
 /// emscripten: _clock_gettime
 #[allow(clippy::cast_ptr_alignment)]
 pub fn _clock_gettime(ctx: &mut EmEnv, clk_id: clockid_t, tp: c_int) -> c_int {
@@ -92,18 +109,12 @@ pub fn _clock_gettime(ctx: &mut EmEnv, clk_id: clockid_t, tp: c_int) -> c_int {
         tv_nsec: i32,
     }
 
-    #[allow(unreachable_patterns)]
-    let timespec = match clk_id {
-        CLOCK_REALTIME => time::get_time(),
-
-        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE => {
-            let precise_ns = time::precise_time_ns();
-            time::Timespec::new(
-                (precise_ns / 1000000000) as i64,
-                (precise_ns % 1000000000) as i32,
-            )
+    let timespec = match clock_time(clk_id) {
+        Some(timespec) => timespec,
+        None => {
+            debug!("=> unsupported clock {}", clk_id);
+            return -libc::EINVAL;
         }
-        _ => panic!("Clock with id \"{}\" is not supported.", clk_id),
     };
 
     unsafe {
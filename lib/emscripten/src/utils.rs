@@ -1,7 +1,8 @@
 use super::env;
 use super::env::get_emscripten_data;
+use crate::standalone::is_standalone_emscripten_module;
 use crate::storage::align_memory;
-use crate::EmEnv;
+use crate::{EmEnv, EmscriptenSetupError};
 use libc::stat;
 use std::ffi::CStr;
 use std::mem::size_of;
@@ -10,8 +11,13 @@ use std::path::PathBuf;
 use std::slice;
 use wasmer::{GlobalInit, Memory, Module, Pages};
 
-/// We check if a provided module is an Emscripten generated one
+/// We check if a provided module is an Emscripten generated one, which
+/// needs its JavaScript runtime (see `is_standalone_emscripten_module`
+/// for the others)
 pub fn is_emscripten_module(module: &Module) -> bool {
+    if is_standalone_emscripten_module(module) {
+        return false;
+    }
     for import in module.imports().functions() {
         let name = import.name();
         let module = import.module();
@@ -26,21 +32,25 @@ pub fn is_emscripten_module(module: &Module) -> bool {
     false
 }
 
-pub fn get_emscripten_table_size(module: &Module) -> Result<(u32, Option<u32>), String> {
+pub fn get_emscripten_table_size(
+    module: &Module,
+) -> Result<(u32, Option<u32>), EmscriptenSetupError> {
     if let Some(import) = module.imports().tables().next() {
         let ty = import.ty();
         Ok((ty.minimum, ty.maximum))
     } else {
-        Err("Emscripten requires at least one imported table".to_string())
+        Err(EmscriptenSetupError::MissingTable)
     }
 }
 
-pub fn get_emscripten_memory_size(module: &Module) -> Result<(Pages, Option<Pages>, bool), String> {
+pub fn get_emscripten_memory_size(
+    module: &Module,
+) -> Result<(Pages, Option<Pages>, bool), EmscriptenSetupError> {
     if let Some(import) = module.imports().memories().next() {
         let ty = import.ty();
         Ok((ty.minimum, ty.maximum, ty.shared))
     } else {
-        Err("Emscripten requires at least one imported memory".to_string())
+        Err(EmscriptenSetupError::MissingMemory)
     }
 }
 
@@ -48,7 +58,9 @@ pub fn get_emscripten_memory_size(module: &Module) -> Result<(Pages, Option<Page
 /// Assumes values start from the end in this order:
 /// Last export: Dynamic Base
 /// Second-to-Last export: Dynamic top pointer
-pub fn get_emscripten_metadata(module: &Module) -> Result<Option<(u32, u32)>, String> {
+pub fn get_emscripten_metadata(
+    module: &Module,
+) -> Result<Option<(u32, u32)>, EmscriptenSetupError> {
     let max_idx = match module
         .info()
         .global_initializers
@@ -76,14 +88,12 @@ pub fn get_emscripten_metadata(module: &Module) -> Result<Option<(u32, u32)>, St
         &module.info().global_initializers[max_idx],
         &module.info().global_initializers[snd_max_idx],
     ) {
-        let dynamic_base = (*dynamic_base as u32).checked_sub(32).ok_or(format!(
-            "emscripten unexpected dynamic_base {}",
-            *dynamic_base as u32
-        ))?;
-        let dynamictop_ptr = (*dynamictop_ptr as u32).checked_sub(32).ok_or(format!(
-            "emscripten unexpected dynamictop_ptr {}",
-            *dynamictop_ptr as u32
-        ))?;
+        let dynamic_base = (*dynamic_base as u32)
+            .checked_sub(32)
+            .ok_or(EmscriptenSetupError::DynamicBase(*dynamic_base as u32))?;
+        let dynamictop_ptr = (*dynamictop_ptr as u32)
+            .checked_sub(32)
+            .ok_or(EmscriptenSetupError::DynamicTopPtr(*dynamictop_ptr as u32))?;
         Ok(Some((
             align_memory(dynamic_base),
             align_memory(dynamictop_ptr),
@@ -213,7 +223,6 @@ pub unsafe fn copy_stat_into_wasm(ctx: &mut EmEnv, buf: u32, stat: &stat) {
     (*stat_ptr).st_ino = stat.st_ino as _;
 }
 
-#[allow(dead_code)] // it's used in `env/windows/mod.rs`.
 pub fn read_string_from_wasm(memory: &Memory, offset: u32) -> String {
     let v: Vec<u8> = memory.view()[(offset as usize)..]
         .iter()
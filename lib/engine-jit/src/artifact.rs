@@ -1,20 +1,26 @@
 //! Define `JITArtifact` to allow compiling and instantiating to be
 //! done as separate steps.
 
-use crate::engine::{JITEngine, JITEngineInner};
+use crate::engine::{CodeMemoryRelease, JITEngine, JITEngineInner};
+use crate::gdb_jit::GdbJitRegistration;
 use crate::link::link_module;
 #[cfg(feature = "compiler")]
 use crate::serialize::SerializableCompilation;
-use crate::serialize::SerializableModule;
+use crate::serialize::{SerializableMetadata, SerializableModule};
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
 use std::sync::{Arc, Mutex};
-use wasmer_compiler::{CompileError, Features, Triple};
+use wasmer_compiler::{
+    CompileError, CompiledFunctionFrameInfo, CompiledFunctionStats, Features, Target, Triple,
+};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::{CompileModuleInfo, ModuleEnvironment};
+#[cfg(feature = "compiler")]
+use wasmer_engine::Tunables;
 use wasmer_engine::{
-    register_frame_info, Artifact, DeserializeError, GlobalFrameInfoRegistration, SerializeError,
+    register_frame_info, Artifact, DeserializeError, Engine, GlobalFrameInfoRegistration,
+    SerializableFunctionFrameInfo, SerializeError,
 };
-#[cfg(feature = "compiler")]
-use wasmer_engine::{Engine, SerializableFunctionFrameInfo, Tunables};
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
 use wasmer_types::{
     FunctionIndex, LocalFunctionIndex, MemoryIndex, OwnedDataInitializer, SignatureIndex,
@@ -27,16 +33,37 @@ use wasmer_vm::{
 /// A compiled wasm module, ready to be instantiated.
 pub struct JITArtifact {
     serializable: SerializableModule,
+    metadata: SerializableMetadata,
     finished_functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
     finished_function_call_trampolines: BoxedSlice<SignatureIndex, VMTrampoline>,
     finished_dynamic_function_trampolines: BoxedSlice<FunctionIndex, FunctionBodyPtr>,
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     frame_info_registration: Mutex<Option<GlobalFrameInfoRegistration>>,
+    code_memory_size: usize,
+    _gdb_jit_registration: Option<GdbJitRegistration>,
+    _code_memory_release: CodeMemoryRelease,
 }
 
 impl JITArtifact {
     const MAGIC_HEADER: &'static [u8] = b"\0wasmer-jit";
 
+    /// The version of the serialized artifact format.
+    ///
+    /// A serialized artifact is laid out as follows, with integers in
+    /// little-endian:
+    ///
+    /// * `MAGIC_HEADER`;
+    /// * the format version, as a `u32`;
+    /// * the length of the metadata, as a `u32`, followed by the
+    ///   bincode-encoded `SerializableMetadata`;
+    /// * the CRC32 of the rest of the artifact, as a `u32`;
+    /// * the uncompressed length of the module, as a `u64`, followed
+    ///   by the DEFLATE-compressed, bincode-encoded `SerializableModule`.
+    ///
+    /// It must be bumped whenever this layout or `SerializableModule`
+    /// changes.
+    const FORMAT_VERSION: u32 = 4;
+
     /// Check if the provided bytes look like a serialized `JITArtifact`.
     pub fn is_deserializable(bytes: &[u8]) -> bool {
         bytes.starts_with(Self::MAGIC_HEADER)
@@ -58,8 +85,10 @@ impl JITArtifact {
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
             .module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| {
+                tunables.module_memory_style(&translation.module, index, memory_type)
+            })
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = translation
             .module
@@ -105,6 +134,7 @@ impl JITArtifact {
             function_relocations: compilation.get_relocations(),
             function_jt_offsets: compilation.get_jt_offsets(),
             function_frame_info: frame_infos,
+            function_stats: compilation.get_function_stats(),
             function_call_trampolines,
             dynamic_function_trampolines,
             custom_sections: compilation.get_custom_sections(),
@@ -116,7 +146,7 @@ impl JITArtifact {
             compile_info,
             data_initializers,
         };
-        Self::from_parts(&mut inner_jit, serializable)
+        Self::from_parts(&mut inner_jit, jit.target(), serializable)
     }
 
     /// Compile a data buffer into a `JITArtifact`, which may then be instantiated.
@@ -127,7 +157,65 @@ impl JITArtifact {
         ))
     }
 
+    /// The DEFLATE compression level used for serialized artifacts.
+    const COMPRESSION_LEVEL: u8 = 6;
+
+    /// Compress the serialized module.
+    ///
+    /// The uncompressed length is stored first, so the decompression
+    /// can be checked. Identical function bodies (like the trampolines
+    /// of functions sharing a signature) are serialized next to each
+    /// other and get deduplicated by the compressor.
+    fn compress(bytes: &[u8]) -> Vec<u8> {
+        let mut compressed = (bytes.len() as u64).to_le_bytes().to_vec();
+        compressed.extend(compress_to_vec(bytes, Self::COMPRESSION_LEVEL));
+        compressed
+    }
+
+    /// Decompress the serialized module.
+    fn decompress(bytes: &[u8]) -> Result<Vec<u8>, DeserializeError> {
+        if bytes.len() < 8 {
+            return Err(DeserializeError::CorruptedBinary(
+                "The compressed module is truncated".to_string(),
+            ));
+        }
+        let (len, compressed) = bytes.split_at(8);
+        let mut len_bytes = [0; 8];
+        len_bytes.copy_from_slice(len);
+        let len = u64::from_le_bytes(len_bytes) as usize;
+        let decompressed = decompress_to_vec(compressed).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("Can't decompress the module: {:?}", e))
+        })?;
+        if decompressed.len() != len {
+            return Err(DeserializeError::CorruptedBinary(format!(
+                "The decompressed module is {} bytes long, expected {}",
+                decompressed.len(),
+                len
+            )));
+        }
+        Ok(decompressed)
+    }
+
+    /// Read a little-endian `u32` from the start of `bytes`, advancing it.
+    fn read_u32(bytes: &mut &[u8], what: &str) -> Result<u32, DeserializeError> {
+        if bytes.len() < 4 {
+            return Err(DeserializeError::CorruptedBinary(format!(
+                "The {} is truncated",
+                what
+            )));
+        }
+        let (value, rest) = bytes.split_at(4);
+        *bytes = rest;
+        let mut value_bytes = [0; 4];
+        value_bytes.copy_from_slice(value);
+        Ok(u32::from_le_bytes(value_bytes))
+    }
+
     /// Deserialize a JITArtifact
+    ///
+    /// The artifact is refused if it was serialized with another
+    /// format version or engine version, for another target, or if
+    /// its checksum doesn't match.
     pub fn deserialize(jit: &JITEngine, bytes: &[u8]) -> Result<Self, DeserializeError> {
         if !Self::is_deserializable(bytes) {
             return Err(DeserializeError::Incompatible(
@@ -135,20 +223,54 @@ impl JITArtifact {
             ));
         }
 
-        let inner_bytes = &bytes[Self::MAGIC_HEADER.len()..];
+        let mut bytes = &bytes[Self::MAGIC_HEADER.len()..];
+        let version = Self::read_u32(&mut bytes, "format version")?;
+        if version != Self::FORMAT_VERSION {
+            return Err(DeserializeError::Incompatible(format!(
+                "the artifact format version is {}, but this engine reads version {}; \
+                 recompile the module",
+                version,
+                Self::FORMAT_VERSION
+            )));
+        }
+
+        let metadata_len = Self::read_u32(&mut bytes, "metadata length")? as usize;
+        if bytes.len() < metadata_len {
+            return Err(DeserializeError::CorruptedBinary(
+                "The metadata is truncated".to_string(),
+            ));
+        }
+        let (metadata_bytes, rest) = bytes.split_at(metadata_len);
+        bytes = rest;
+        let metadata: SerializableMetadata = bincode::deserialize(metadata_bytes)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))?;
+        metadata
+            .check_compatible(jit.target())
+            .map_err(DeserializeError::Incompatible)?;
+
+        let checksum = Self::read_u32(&mut bytes, "checksum")?;
+        if crc32fast::hash(bytes) != checksum {
+            return Err(DeserializeError::CorruptedBinary(
+                "The checksum of the module doesn't match".to_string(),
+            ));
+        }
+
+        let inner_bytes = Self::decompress(bytes)?;
 
         // let r = flexbuffers::Reader::get_root(bytes).map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))?;
         // let serializable = SerializableModule::deserialize(r).map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))?;
 
-        let serializable: SerializableModule = bincode::deserialize(inner_bytes)
+        let serializable: SerializableModule = bincode::deserialize(&inner_bytes)
             .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))?;
 
-        Self::from_parts(&mut jit.inner_mut(), serializable).map_err(DeserializeError::Compiler)
+        Self::from_parts(&mut jit.inner_mut(), jit.target(), serializable)
+            .map_err(DeserializeError::Compiler)
     }
 
     /// Construct a `JITArtifact` from component parts.
     pub fn from_parts(
         inner_jit: &mut JITEngineInner,
+        target: &Target,
         serializable: SerializableModule,
     ) -> Result<Self, CompileError> {
         let (
@@ -163,6 +285,7 @@ impl JITArtifact {
             &serializable.compilation.dynamic_function_trampolines,
             &serializable.compilation.custom_sections,
         )?;
+        let code_memory_release = inner_jit.last_code_memory_release();
 
         link_module(
             &serializable.compile_info.module,
@@ -198,12 +321,19 @@ impl JITArtifact {
             }
             None => None,
         };
+        let code_memory_size = inner_jit.last_code_memory_size();
+
         // Make all code compiled thus far executable.
-        inner_jit.publish_compiled_code();
+        inner_jit.publish_compiled_code()?;
 
         inner_jit.publish_eh_frame(eh_frame)?;
 
         let finished_functions = finished_functions.into_boxed_slice();
+        let gdb_jit_registration = if inner_jit.debug_info() {
+            GdbJitRegistration::register(&serializable.compile_info.module, &finished_functions)
+        } else {
+            None
+        };
         let finished_function_call_trampolines =
             finished_function_call_trampolines.into_boxed_slice();
         let finished_dynamic_function_trampolines =
@@ -212,11 +342,15 @@ impl JITArtifact {
 
         Ok(Self {
             serializable,
+            metadata: SerializableMetadata::new(target),
             finished_functions,
             finished_function_call_trampolines,
             finished_dynamic_function_trampolines,
             signatures,
             frame_info_registration: Mutex::new(None),
+            code_memory_size,
+            _gdb_jit_registration: gdb_jit_registration,
+            _code_memory_release: code_memory_release,
         })
     }
 
@@ -289,6 +423,26 @@ impl Artifact for JITArtifact {
         &self.signatures
     }
 
+    fn code_memory_size(&self) -> usize {
+        self.code_memory_size
+    }
+
+    fn function_stats(&self) -> Option<&PrimaryMap<LocalFunctionIndex, CompiledFunctionStats>> {
+        Some(&self.serializable.compilation.function_stats)
+    }
+
+    fn function_frame_info(&self, index: LocalFunctionIndex) -> Option<CompiledFunctionFrameInfo> {
+        let frame_info = self
+            .serializable
+            .compilation
+            .function_frame_info
+            .get(index)?;
+        Some(match frame_info {
+            SerializableFunctionFrameInfo::Processed(processed) => processed.clone(),
+            SerializableFunctionFrameInfo::Unprocessed(unprocessed) => unprocessed.deserialize(),
+        })
+    }
+
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
         // let mut s = flexbuffers::FlexbufferSerializer::new();
         // self.serializable.serialize(&mut s).map_err(|e| SerializeError::Generic(format!("{:?}", e)));
@@ -296,9 +450,17 @@ impl Artifact for JITArtifact {
         let bytes = bincode::serialize(&self.serializable)
             .map_err(|e| SerializeError::Generic(format!("{:?}", e)))?;
 
+        let metadata = bincode::serialize(&self.metadata)
+            .map_err(|e| SerializeError::Generic(format!("{:?}", e)))?;
+        let compressed = Self::compress(&bytes);
+
         // Prepend the header.
         let mut serialized = Self::MAGIC_HEADER.to_vec();
-        serialized.extend(bytes);
+        serialized.extend(&Self::FORMAT_VERSION.to_le_bytes());
+        serialized.extend(&(metadata.len() as u32).to_le_bytes());
+        serialized.extend(metadata);
+        serialized.extend(&crc32fast::hash(&compressed).to_le_bytes());
+        serialized.extend(compressed);
         Ok(serialized)
     }
 }
@@ -1,5 +1,7 @@
-use crate::JITEngine;
+use crate::{CodeMemoryProvider, JITEngine};
+use std::sync::Arc;
 use wasmer_compiler::{CompilerConfig, Features, Target};
+use wasmer_engine::TrustStore;
 
 /// The JIT builder
 pub struct JIT<'a> {
@@ -7,6 +9,10 @@ pub struct JIT<'a> {
     compiler_config: Option<&'a dyn CompilerConfig>,
     target: Option<Target>,
     features: Option<Features>,
+    max_code_memory: Option<usize>,
+    trust_store: Option<TrustStore>,
+    code_memory_provider: Option<Arc<dyn CodeMemoryProvider>>,
+    debug_info: bool,
 }
 
 impl<'a> JIT<'a> {
@@ -16,6 +22,10 @@ impl<'a> JIT<'a> {
             compiler_config: Some(compiler_config),
             target: None,
             features: None,
+            max_code_memory: None,
+            trust_store: None,
+            code_memory_provider: None,
+            debug_info: false,
         }
     }
 
@@ -25,6 +35,10 @@ impl<'a> JIT<'a> {
             compiler_config: None,
             target: None,
             features: None,
+            max_code_memory: None,
+            trust_store: None,
+            code_memory_provider: None,
+            debug_info: false,
         }
     }
 
@@ -40,11 +54,45 @@ impl<'a> JIT<'a> {
         self
     }
 
+    /// Set the maximum number of bytes of code memory the engine can
+    /// allocate, across all the modules it compiles or deserializes.
+    ///
+    /// Compiling or deserializing a module that doesn't fit fails with
+    /// `CompileError::Resource`.
+    pub fn max_code_memory(mut self, bytes: usize) -> Self {
+        self.max_code_memory = Some(bytes);
+        self
+    }
+
+    /// Set the keys the modules must be signed with to be compiled.
+    ///
+    /// Compiling a module without a signature made with one of them
+    /// fails with `CompileError::Signature`.
+    pub fn trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.trust_store = Some(trust_store);
+        self
+    }
+
+    /// Set the provider allocating the pages of the code and data of
+    /// the modules, instead of mapping anonymous pages.
+    pub fn code_memory_provider(mut self, provider: impl CodeMemoryProvider + 'static) -> Self {
+        self.code_memory_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Set whether the functions of the modules are registered with
+    /// the GDB JIT interface, so debuggers attached to the process (gdb
+    /// and lldb) show their names in backtraces and can break on them.
+    pub fn debug_info(mut self, enabled: bool) -> Self {
+        self.debug_info = enabled;
+        self
+    }
+
     /// Build the `JITEngine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> JITEngine {
         let target = self.target.unwrap_or_default();
-        if let Some(compiler_config) = self.compiler_config {
+        let engine = if let Some(compiler_config) = self.compiler_config {
             let features = self
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
@@ -52,12 +100,26 @@ impl<'a> JIT<'a> {
             JITEngine::new(compiler, target, features)
         } else {
             JITEngine::headless()
+        };
+        engine.set_max_code_memory(self.max_code_memory);
+        engine.set_trust_store(self.trust_store);
+        if let Some(provider) = self.code_memory_provider {
+            engine.set_code_memory_provider(provider);
         }
+        engine.set_debug_info(self.debug_info);
+        engine
     }
 
     /// Build the `JITEngine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> JITEngine {
-        JITEngine::headless()
+        let engine = JITEngine::headless();
+        engine.set_max_code_memory(self.max_code_memory);
+        engine.set_trust_store(self.trust_store);
+        if let Some(provider) = self.code_memory_provider {
+            engine.set_code_memory_provider(provider);
+        }
+        engine.set_debug_info(self.debug_info);
+        engine
     }
 }
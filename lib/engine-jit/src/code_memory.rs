@@ -3,6 +3,7 @@
 
 //! Memory management for executable code.
 use crate::unwind::UnwindRegistry;
+use std::sync::Arc;
 use wasmer_compiler::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody};
 use wasmer_vm::{Mmap, VMFunctionBody};
 
@@ -17,42 +18,101 @@ const ARCH_FUNCTION_ALIGNMENT: usize = 16;
 ///
 const DATA_SECTION_ALIGNMENT: usize = 64;
 
+/// Pages allocated by a [`CodeMemoryProvider`] for the code and data of
+/// a module. They're released when dropped.
+pub trait CodeMemoryPages: Send + Sync {
+    /// The number of bytes of the pages.
+    fn size(&self) -> usize;
+
+    /// Returns the pages, readable and writable until
+    /// [`CodeMemoryPages::make_executable`] is called.
+    fn as_mut_slice(&mut self) -> &mut [u8];
+
+    /// Makes the first `len` bytes of the pages, rounded up to the page
+    /// size, readable and executable.
+    fn make_executable(&mut self, len: usize) -> Result<(), String>;
+}
+
+/// Allocates the pages holding the code and data of the modules of a
+/// JIT engine, e.g. to map them with `MAP_JIT` under the hardened
+/// runtime of macOS, from a `memfd`, or from pages given by a
+/// hypervisor.
+pub trait CodeMemoryProvider: Send + Sync {
+    /// Allocates at least `size` bytes of zeroed, page aligned, readable
+    /// and writable pages. `size` can be 0.
+    fn allocate(&self, size: usize) -> Result<Box<dyn CodeMemoryPages>, String>;
+}
+
+/// The default [`CodeMemoryProvider`], mapping anonymous pages.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MmapCodeMemoryProvider;
+
+impl CodeMemoryProvider for MmapCodeMemoryProvider {
+    fn allocate(&self, size: usize) -> Result<Box<dyn CodeMemoryPages>, String> {
+        Ok(Box::new(Mmap::with_at_least(size)?))
+    }
+}
+
+impl CodeMemoryPages for Mmap {
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        Mmap::as_mut_slice(self)
+    }
+
+    fn make_executable(&mut self, len: usize) -> Result<(), String> {
+        unsafe { region::protect(self.as_mut_ptr(), len, region::Protection::READ_EXECUTE) }
+            .map_err(|e| e.to_string())
+    }
+}
+
 /// Memory manager for executable code.
 pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
-    mmap: Mmap,
+    provider: Arc<dyn CodeMemoryProvider>,
+    pages: Option<Box<dyn CodeMemoryPages>>,
     start_of_nonexecutable_pages: usize,
 }
 
 impl CodeMemory {
     /// Create a new `CodeMemory` instance.
     pub fn new() -> Self {
+        Self::with_provider(Arc::new(MmapCodeMemoryProvider))
+    }
+
+    /// Create a new `CodeMemory` instance allocating its pages with the
+    /// given provider.
+    pub fn with_provider(provider: Arc<dyn CodeMemoryProvider>) -> Self {
         Self {
             unwind_registry: UnwindRegistry::new(),
-            mmap: Mmap::new(),
+            provider,
+            pages: None,
             start_of_nonexecutable_pages: 0,
         }
     }
 
+    /// The number of bytes mapped for the code and data.
+    pub fn size(&self) -> usize {
+        self.pages.as_ref().map_or(0, |pages| pages.size())
+    }
+
     /// Mutably get the UnwindRegistry.
     pub fn unwind_registry_mut(&mut self) -> &mut UnwindRegistry {
         &mut self.unwind_registry
     }
 
-    /// Allocate a single contiguous block of memory for the functions and custom sections, and copy the data in place.
-    pub fn allocate(
-        &mut self,
+    /// The number of bytes `allocate` needs for the given functions and
+    /// custom sections.
+    pub fn allocation_size(
         functions: &[&FunctionBody],
         executable_sections: &[&CustomSection],
         data_sections: &[&CustomSection],
-    ) -> Result<(Vec<&mut [VMFunctionBody]>, Vec<&mut [u8]>, Vec<&mut [u8]>), String> {
-        let mut function_result = vec![];
-        let mut data_section_result = vec![];
-        let mut executable_section_result = vec![];
-
+    ) -> usize {
         let page_size = region::page::size();
 
-        // 1. Calculate the total size, that is:
+        // The total size is:
         // - function body size, including all trampolines
         // -- windows unwind info
         // -- padding between functions
@@ -62,7 +122,7 @@ impl CodeMemory {
         // - data section body size
         // -- padding between data sections
 
-        let total_len = round_up(
+        round_up(
             functions.iter().fold(0, |acc, func| {
                 round_up(
                     acc + Self::function_allocation_size(func),
@@ -74,17 +134,37 @@ impl CodeMemory {
             page_size,
         ) + data_sections.iter().fold(0, |acc, data| {
             round_up(acc + data.bytes.len(), DATA_SECTION_ALIGNMENT)
-        });
+        })
+    }
+
+    /// Allocate a single contiguous block of memory for the functions and custom sections, and copy the data in place.
+    pub fn allocate(
+        &mut self,
+        functions: &[&FunctionBody],
+        executable_sections: &[&CustomSection],
+        data_sections: &[&CustomSection],
+    ) -> Result<(Vec<&mut [VMFunctionBody]>, Vec<&mut [u8]>, Vec<&mut [u8]>), String> {
+        let mut function_result = vec![];
+        let mut data_section_result = vec![];
+        let mut executable_section_result = vec![];
+
+        let page_size = region::page::size();
+
+        // 1. Calculate the total size.
+
+        let total_len = Self::allocation_size(functions, executable_sections, data_sections);
 
         // 2. Allocate the pages. Mark them all read-write.
 
-        self.mmap = Mmap::with_at_least(total_len)?;
+        self.pages = Some(self.provider.allocate(total_len)?);
+        let pages = self.pages.as_mut().unwrap();
+        assert!(pages.size() >= total_len);
 
         // 3. Determine where the pointers to each function, executable section
         // or data section are. Copy the functions. Collect the addresses of each and return them.
 
         let mut bytes = 0;
-        let mut buf = self.mmap.as_mut_slice();
+        let mut buf = pages.as_mut_slice();
         for func in functions {
             let len = round_up(
                 Self::function_allocation_size(func),
@@ -136,19 +216,13 @@ impl CodeMemory {
     }
 
     /// Apply the page permissions.
-    pub fn publish(&mut self) {
-        if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
-            return;
-        }
-        assert!(self.mmap.len() >= self.start_of_nonexecutable_pages);
-        unsafe {
-            region::protect(
-                self.mmap.as_mut_ptr(),
-                self.start_of_nonexecutable_pages,
-                region::Protection::READ_EXECUTE,
-            )
-        }
-        .expect("unable to make memory readonly and executable");
+    pub fn publish(&mut self) -> Result<(), String> {
+        let pages = match &mut self.pages {
+            Some(pages) if self.start_of_nonexecutable_pages != 0 => pages,
+            _ => return Ok(()),
+        };
+        assert!(pages.size() >= self.start_of_nonexecutable_pages);
+        pages.make_executable(self.start_of_nonexecutable_pages)
     }
 
     /// Calculates the allocation size of the given compiled function.
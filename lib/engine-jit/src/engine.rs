@@ -1,13 +1,14 @@
 //! JIT compilation.
 
-use crate::{CodeMemory, JITArtifact};
+use crate::{CodeMemory, CodeMemoryProvider, JITArtifact, MmapCodeMemoryProvider};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::Compiler;
 use wasmer_compiler::{
     CompileError, CustomSection, CustomSectionProtection, FunctionBody, SectionIndex, Target,
 };
-use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, Tunables};
+use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, TrustStore, Tunables};
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::Features;
 use wasmer_types::{FunctionIndex, FunctionType, LocalFunctionIndex, SignatureIndex};
@@ -33,6 +34,13 @@ impl JITEngine {
             inner: Arc::new(Mutex::new(JITEngineInner {
                 compiler: Some(compiler),
                 code_memory: vec![],
+                next_code_memory_id: 0,
+                released_code_memory: Arc::new(Mutex::new(vec![])),
+                max_code_memory: None,
+                trust_store: None,
+                code_memory_provider: Arc::new(MmapCodeMemoryProvider),
+                debug_info: false,
+                trampolines: TrampolineCache::default(),
                 signatures: SignatureRegistry::new(),
                 features,
             })),
@@ -60,6 +68,13 @@ impl JITEngine {
                 #[cfg(feature = "compiler")]
                 compiler: None,
                 code_memory: vec![],
+                next_code_memory_id: 0,
+                released_code_memory: Arc::new(Mutex::new(vec![])),
+                max_code_memory: None,
+                trust_store: None,
+                code_memory_provider: Arc::new(MmapCodeMemoryProvider),
+                debug_info: false,
+                trampolines: TrampolineCache::default(),
                 signatures: SignatureRegistry::new(),
                 features: Features::default(),
             })),
@@ -68,6 +83,61 @@ impl JITEngine {
         }
     }
 
+    /// Limits the bytes of code memory that can be allocated by this engine.
+    pub(crate) fn set_max_code_memory(&self, max_code_memory: Option<usize>) {
+        self.inner_mut().max_code_memory = max_code_memory;
+    }
+
+    /// Restricts this engine to the modules signed with a key of
+    /// `trust_store`.
+    pub(crate) fn set_trust_store(&self, trust_store: Option<TrustStore>) {
+        self.inner_mut().trust_store = trust_store.map(Arc::new);
+    }
+
+    /// Sets the provider allocating the code memory of this engine.
+    pub(crate) fn set_code_memory_provider(&self, provider: Arc<dyn CodeMemoryProvider>) {
+        self.inner_mut().code_memory_provider = provider;
+    }
+
+    /// Sets whether the functions of the modules are registered with
+    /// the debuggers attached to the process.
+    pub(crate) fn set_debug_info(&self, debug_info: bool) {
+        self.inner_mut().debug_info = debug_info;
+    }
+
+    /// Returns the bytes of code memory currently allocated by this engine,
+    /// for the code and data of all the modules it has compiled or
+    /// deserialized, except their shared trampolines.
+    pub fn code_memory_used(&self) -> usize {
+        self.inner().code_memory_used()
+    }
+
+    /// Returns the bytes of code memory allocated by this engine for the
+    /// trampolines shared by its modules.
+    ///
+    /// The trampolines only depend on a function signature, so each one
+    /// is allocated once per engine and reused by every module with this
+    /// signature. They're never released by [`JITEngine::purge`].
+    pub fn trampoline_memory_used(&self) -> usize {
+        self.inner().trampolines.memory_used()
+    }
+
+    /// Unmaps the code memory of the modules that have been dropped, and
+    /// returns the number of bytes released.
+    ///
+    /// A module's code memory is only queued for release once its
+    /// `Module` and every `Instance` created from it are dropped.
+    ///
+    /// # Safety
+    ///
+    /// Functions exported from those instances, or stored in tables or
+    /// imported by instances of other modules, keep pointing into the
+    /// released code. The caller must ensure none of them is called
+    /// afterwards.
+    pub unsafe fn purge(&self) -> usize {
+        self.inner_mut().purge()
+    }
+
     pub(crate) fn inner(&self) -> std::sync::MutexGuard<'_, JITEngineInner> {
         self.inner.lock().unwrap()
     }
@@ -100,6 +170,11 @@ impl Engine for JITEngine {
         self.inner().validate(binary)
     }
 
+    /// The keys the compiled modules must be signed with
+    fn trust_store(&self) -> Option<Arc<TrustStore>> {
+        self.inner().trust_store.clone()
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     fn compile(
@@ -146,7 +221,22 @@ pub struct JITEngineInner {
     features: Features,
     /// The code memory is responsible of publishing the compiled
     /// functions to memory.
-    code_memory: Vec<CodeMemory>,
+    code_memory: Vec<(usize, CodeMemory)>,
+    /// The id given to the next allocated code memory.
+    next_code_memory_id: usize,
+    /// The ids of the code memories whose artifact has been dropped.
+    released_code_memory: Arc<Mutex<Vec<usize>>>,
+    /// The maximum number of bytes `code_memory` can hold.
+    max_code_memory: Option<usize>,
+    /// The keys the compiled modules must be signed with, if any.
+    trust_store: Option<Arc<TrustStore>>,
+    /// The provider allocating the pages of `code_memory`.
+    code_memory_provider: Arc<dyn CodeMemoryProvider>,
+    /// Whether the functions of the modules are registered with the
+    /// debuggers.
+    debug_info: bool,
+    /// The trampolines shared by the modules, by signature.
+    trampolines: TrampolineCache,
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     signatures: SignatureRegistry,
@@ -183,10 +273,13 @@ impl JITEngineInner {
     }
 
     /// Allocate compiled functions into memory
+    ///
+    /// The trampolines are only allocated for the signatures without a
+    /// trampoline in the cache of the engine yet.
     #[allow(clippy::type_complexity)]
     pub(crate) fn allocate(
         &mut self,
-        _module: &ModuleInfo,
+        module: &ModuleInfo,
         functions: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
         function_call_trampolines: &PrimaryMap<SignatureIndex, FunctionBody>,
         dynamic_function_trampolines: &PrimaryMap<FunctionIndex, FunctionBody>,
@@ -200,51 +293,106 @@ impl JITEngineInner {
         ),
         CompileError,
     > {
-        let function_bodies = functions
-            .values()
-            .chain(function_call_trampolines.values())
-            .chain(dynamic_function_trampolines.values())
+        // The pages can't be made executable at runtime on iOS, only
+        // the modules compiled ahead of time can run there.
+        if cfg!(target_os = "ios") {
+            return Err(CompileError::Resource(
+                "iOS doesn't allow generating code at runtime: compile the module ahead of time with the object-file engine".to_string(),
+            ));
+        }
+        let function_call_signatures = function_call_trampolines
+            .keys()
+            .map(|index| &module.signatures[index])
             .collect::<Vec<_>>();
+        let dynamic_function_signatures = dynamic_function_trampolines
+            .keys()
+            .map(|index| &module.signatures[module.functions[index]])
+            .collect::<Vec<_>>();
+        let new_function_call_trampolines = self.trampolines.missing_function_call_trampolines(
+            &function_call_signatures,
+            function_call_trampolines.values(),
+        );
+        let new_dynamic_function_trampolines =
+            self.trampolines.missing_dynamic_function_trampolines(
+                &dynamic_function_signatures,
+                dynamic_function_trampolines.values(),
+            );
+
+        let function_bodies = functions.values().collect::<Vec<_>>();
         let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
             .values()
             .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
-        self.code_memory.push(CodeMemory::new());
-
-        let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
-            self.code_memory
-                .last_mut()
-                .unwrap()
-                .allocate(
-                    function_bodies.as_slice(),
-                    executable_sections.as_slice(),
-                    data_sections.as_slice(),
-                )
-                .map_err(|message| {
-                    CompileError::Resource(format!(
-                        "failed to allocate memory for functions: {}",
-                        message
-                    ))
-                })?;
+        if let Some(max_code_memory) = self.max_code_memory {
+            let used = self.code_memory_used() + self.trampolines.memory_used();
+            let trampoline_bodies = new_function_call_trampolines
+                .iter()
+                .chain(new_dynamic_function_trampolines.iter())
+                .map(|(_, body)| *body)
+                .collect::<Vec<_>>();
+            let needed = CodeMemory::allocation_size(
+                function_bodies.as_slice(),
+                executable_sections.as_slice(),
+                data_sections.as_slice(),
+            ) + CodeMemory::allocation_size(trampoline_bodies.as_slice(), &[], &[]);
+            if used + needed > max_code_memory {
+                return Err(CompileError::Resource(format!(
+                    "code memory limit exceeded: {} bytes are needed but only {} of {} are left",
+                    needed,
+                    max_code_memory.saturating_sub(used),
+                    max_code_memory
+                )));
+            }
+        }
+
+        self.trampolines
+            .allocate(
+                &self.code_memory_provider,
+                new_function_call_trampolines,
+                new_dynamic_function_trampolines,
+            )
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to allocate memory for trampolines: {}",
+                    message
+                ))
+            })?;
+
+        self.code_memory.push((
+            self.next_code_memory_id,
+            CodeMemory::with_provider(self.code_memory_provider.clone()),
+        ));
+        self.next_code_memory_id += 1;
+
+        let (allocated_functions, allocated_executable_sections, allocated_data_sections) = self
+            .code_memory
+            .last_mut()
+            .unwrap()
+            .1
+            .allocate(
+                function_bodies.as_slice(),
+                executable_sections.as_slice(),
+                data_sections.as_slice(),
+            )
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to allocate memory for functions: {}",
+                    message
+                ))
+            })?;
 
         let allocated_functions_result = allocated_functions
-            .drain(0..functions.len())
+            .into_iter()
             .map(|slice| FunctionBodyPtr(slice as *mut [_]))
             .collect::<PrimaryMap<LocalFunctionIndex, _>>();
 
-        let mut allocated_function_call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline> =
-            PrimaryMap::new();
-        for ptr in allocated_functions
-            .drain(0..function_call_trampolines.len())
-            .map(|slice| slice.as_ptr())
-        {
-            let trampoline =
-                unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(ptr) };
-            allocated_function_call_trampolines.push(trampoline);
-        }
+        let allocated_function_call_trampolines = function_call_signatures
+            .into_iter()
+            .map(|signature| self.trampolines.function_call[signature])
+            .collect::<PrimaryMap<SignatureIndex, _>>();
 
-        let allocated_dynamic_function_trampolines = allocated_functions
-            .drain(..)
-            .map(|slice| FunctionBodyPtr(slice as *mut [_]))
+        let allocated_dynamic_function_trampolines = dynamic_function_signatures
+            .into_iter()
+            .map(|signature| self.trampolines.dynamic_function[signature])
             .collect::<PrimaryMap<FunctionIndex, _>>();
 
         let mut exec_iter = allocated_executable_sections.iter();
@@ -272,9 +420,61 @@ impl JITEngineInner {
         ))
     }
 
+    /// The bytes of code memory allocated so far.
+    pub fn code_memory_used(&self) -> usize {
+        self.code_memory
+            .iter()
+            .map(|(_, code_memory)| code_memory.size())
+            .sum()
+    }
+
+    /// Whether the functions of the modules are registered with the
+    /// debuggers.
+    pub(crate) fn debug_info(&self) -> bool {
+        self.debug_info
+    }
+
+    /// The bytes of code memory allocated for the last module.
+    pub(crate) fn last_code_memory_size(&self) -> usize {
+        self.code_memory
+            .last()
+            .map_or(0, |(_, code_memory)| code_memory.size())
+    }
+
+    /// Returns a guard that queues the last allocated code memory for
+    /// release once dropped.
+    pub(crate) fn last_code_memory_release(&self) -> CodeMemoryRelease {
+        CodeMemoryRelease {
+            id: self.code_memory.last().unwrap().0,
+            released: self.released_code_memory.clone(),
+        }
+    }
+
+    /// Unmaps the code memories queued for release.
+    ///
+    /// # Safety
+    ///
+    /// See [`JITEngine::purge`].
+    pub(crate) unsafe fn purge(&mut self) -> usize {
+        let released = std::mem::take(&mut *self.released_code_memory.lock().unwrap());
+        let before = self.code_memory_used();
+        self.code_memory.retain(|(id, _)| !released.contains(id));
+        before - self.code_memory_used()
+    }
+
     /// Make memory containing compiled code executable.
-    pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
+    pub(crate) fn publish_compiled_code(&mut self) -> Result<(), CompileError> {
+        self.code_memory
+            .last_mut()
+            .unwrap()
+            .1
+            .publish()
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to make the functions executable: {}",
+                    message
+                ))
+            })
     }
 
     /// Register DWARF-type exception handling information associated with the code.
@@ -282,6 +482,7 @@ impl JITEngineInner {
         self.code_memory
             .last_mut()
             .unwrap()
+            .1
             .unwind_registry_mut()
             .publish(eh_frame)
             .map_err(|e| {
@@ -295,3 +496,115 @@ impl JITEngineInner {
         &self.signatures
     }
 }
+
+/// Queues the code memory of an artifact for release by
+/// [`JITEngine::purge`] once the artifact is dropped.
+pub(crate) struct CodeMemoryRelease {
+    id: usize,
+    released: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Drop for CodeMemoryRelease {
+    fn drop(&mut self) {
+        if let Ok(mut released) = self.released.lock() {
+            released.push(self.id);
+        }
+    }
+}
+
+/// The trampolines of the modules of an engine, by signature.
+///
+/// A function call trampoline or a dynamic function trampoline only
+/// depends on the signature of the function, so each one is allocated
+/// once and shared by all the modules, instead of once per module (and
+/// per imported function for the dynamic function trampolines).
+#[derive(Default)]
+struct TrampolineCache {
+    function_call: HashMap<FunctionType, VMTrampoline>,
+    dynamic_function: HashMap<FunctionType, FunctionBodyPtr>,
+    /// The code memory of the trampolines. It's never released, since
+    /// any module can use them.
+    code_memory: Vec<CodeMemory>,
+}
+
+impl TrampolineCache {
+    /// The bytes of code memory allocated for the trampolines.
+    fn memory_used(&self) -> usize {
+        self.code_memory.iter().map(CodeMemory::size).sum()
+    }
+
+    /// Returns the function call trampolines of `signatures` that
+    /// aren't cached, once per signature.
+    fn missing_function_call_trampolines<'a>(
+        &self,
+        signatures: &[&'a FunctionType],
+        bodies: impl Iterator<Item = &'a FunctionBody>,
+    ) -> Vec<(&'a FunctionType, &'a FunctionBody)> {
+        missing_trampolines(&self.function_call, signatures, bodies)
+    }
+
+    /// Returns the dynamic function trampolines of `signatures` that
+    /// aren't cached, once per signature.
+    fn missing_dynamic_function_trampolines<'a>(
+        &self,
+        signatures: &[&'a FunctionType],
+        bodies: impl Iterator<Item = &'a FunctionBody>,
+    ) -> Vec<(&'a FunctionType, &'a FunctionBody)> {
+        missing_trampolines(&self.dynamic_function, signatures, bodies)
+    }
+
+    /// Allocates and publishes the given trampolines, and caches them.
+    fn allocate(
+        &mut self,
+        provider: &Arc<dyn CodeMemoryProvider>,
+        function_call_trampolines: Vec<(&FunctionType, &FunctionBody)>,
+        dynamic_function_trampolines: Vec<(&FunctionType, &FunctionBody)>,
+    ) -> Result<(), String> {
+        if function_call_trampolines.is_empty() && dynamic_function_trampolines.is_empty() {
+            return Ok(());
+        }
+        let bodies = function_call_trampolines
+            .iter()
+            .chain(dynamic_function_trampolines.iter())
+            .map(|(_, body)| *body)
+            .collect::<Vec<_>>();
+        let mut code_memory = CodeMemory::with_provider(provider.clone());
+        let mut allocated = code_memory
+            .allocate(&bodies, &[], &[])?
+            .0
+            .into_iter()
+            .map(|slice| slice as *mut [VMFunctionBody])
+            .collect::<Vec<_>>()
+            .into_iter();
+        code_memory.publish()?;
+        code_memory.unwind_registry_mut().publish(None)?;
+        for (signature, _) in function_call_trampolines {
+            let ptr = allocated.next().unwrap() as *const VMFunctionBody;
+            let trampoline =
+                unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(ptr) };
+            self.function_call.insert(signature.clone(), trampoline);
+        }
+        for (signature, _) in dynamic_function_trampolines {
+            let ptr = FunctionBodyPtr(allocated.next().unwrap());
+            self.dynamic_function.insert(signature.clone(), ptr);
+        }
+        self.code_memory.push(code_memory);
+        Ok(())
+    }
+}
+
+fn missing_trampolines<'a, T>(
+    cache: &HashMap<FunctionType, T>,
+    signatures: &[&'a FunctionType],
+    bodies: impl Iterator<Item = &'a FunctionBody>,
+) -> Vec<(&'a FunctionType, &'a FunctionBody)> {
+    let mut missing: Vec<(&FunctionType, &FunctionBody)> = vec![];
+    for (signature, body) in signatures.iter().zip(bodies) {
+        if !cache.contains_key(*signature)
+            && !missing.iter().any(|(missing, _)| missing == signature)
+        {
+            missing.push((signature, body));
+        }
+    }
+    missing
+}
@@ -0,0 +1,248 @@
+//! Registration of the compiled functions with the GDB JIT interface,
+//! so debuggers attached to the process (gdb and lldb) know their names
+//! and address ranges.
+//!
+//! Each module is described to the debuggers by an in-memory ELF image
+//! with a symbol per function. It has no line information: backtraces
+//! and breakpoints use the names of the functions.
+
+use std::ptr;
+use std::sync::Mutex;
+use wasmer_types::entity::{BoxedSlice, EntityRef};
+use wasmer_types::LocalFunctionIndex;
+use wasmer_vm::{FunctionBodyPtr, ModuleInfo};
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+/// The debuggers read the entries from this descriptor, whose name is
+/// part of the interface.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The debuggers break on this function, whose name is part of the
+/// interface, to be notified of the changes of the descriptor.
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keeps the function, and the calls to it, from being optimized out.
+    unsafe {
+        ptr::read_volatile(&JIT_NOACTION);
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the changes of the descriptor.
+    static ref DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// The functions of a module, registered with the debuggers until this
+/// is dropped.
+pub(crate) struct GdbJitRegistration {
+    entry: *mut JitCodeEntry,
+    _image: Box<[u8]>,
+}
+
+impl GdbJitRegistration {
+    /// Registers the `functions` of `module`, returning `None` if there
+    /// are none or their architecture isn't supported.
+    pub(crate) fn register(
+        module: &ModuleInfo,
+        functions: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+    ) -> Option<Self> {
+        let image = elf_image(module, functions)?;
+        let entry = Box::into_raw(Box::new(JitCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        }));
+
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = &mut __jit_debug_descriptor;
+            (*entry).next_entry = descriptor.first_entry;
+            if !descriptor.first_entry.is_null() {
+                (*descriptor.first_entry).prev_entry = entry;
+            }
+            descriptor.first_entry = entry;
+            notify(descriptor, entry, JIT_REGISTER_FN);
+        }
+        Some(Self {
+            entry,
+            _image: image,
+        })
+    }
+}
+
+impl Drop for GdbJitRegistration {
+    fn drop(&mut self) {
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = &mut __jit_debug_descriptor;
+            let entry = &mut *self.entry;
+            if entry.prev_entry.is_null() {
+                descriptor.first_entry = entry.next_entry;
+            } else {
+                (*entry.prev_entry).next_entry = entry.next_entry;
+            }
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry.prev_entry;
+            }
+            notify(descriptor, self.entry, JIT_UNREGISTER_FN);
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+// The entry is only accessed with the descriptor lock held.
+unsafe impl Send for GdbJitRegistration {}
+unsafe impl Sync for GdbJitRegistration {}
+
+unsafe fn notify(descriptor: &mut JitDescriptor, entry: *mut JitCodeEntry, action: u32) {
+    descriptor.relevant_entry = entry;
+    descriptor.action_flag = action;
+    __jit_debug_register_code();
+    descriptor.action_flag = JIT_NOACTION;
+    descriptor.relevant_entry = ptr::null_mut();
+}
+
+/// The ELF machine of the host, the code being compiled for it.
+#[cfg(target_arch = "x86_64")]
+const ELF_MACHINE: Option<u16> = Some(62);
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: Option<u16> = Some(183);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ELF_MACHINE: Option<u16> = None;
+
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+
+/// Builds a little-endian ELF64 executable image with a `.text` section
+/// spanning the `functions`, and a symbol for each of them.
+fn elf_image(
+    module: &ModuleInfo,
+    functions: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+) -> Option<Box<[u8]>> {
+    let machine = ELF_MACHINE?;
+    let ranges = functions
+        .iter()
+        .map(|(index, body)| {
+            let body = unsafe { &***body };
+            let start = body.as_ptr() as u64;
+            (index, start, start + body.len() as u64)
+        })
+        .collect::<Vec<_>>();
+    let text_start = ranges.iter().map(|&(_, start, _)| start).min()?;
+    let text_end = ranges.iter().map(|&(_, _, end)| end).max()?;
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYMBOL_SIZE];
+    for &(index, start, end) in &ranges {
+        let func_index = module.func_index(index);
+        let name_offset = strtab.len() as u32;
+        match module.function_names.get(&func_index) {
+            Some(name) => strtab.extend_from_slice(name.as_bytes()),
+            None => strtab
+                .extend_from_slice(format!("wasm-function[{}]", func_index.index()).as_bytes()),
+        }
+        strtab.push(0);
+
+        symtab.extend_from_slice(&name_offset.to_le_bytes());
+        // STB_GLOBAL, STT_FUNC
+        symtab.push(0x12);
+        symtab.push(0);
+        // in `.text`
+        symtab.extend_from_slice(&1u16.to_le_bytes());
+        symtab.extend_from_slice(&start.to_le_bytes());
+        symtab.extend_from_slice(&(end - start).to_le_bytes());
+    }
+    let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+
+    let symtab_offset = 64;
+    let strtab_offset = symtab_offset + symtab.len();
+    let shstrtab_offset = strtab_offset + strtab.len();
+    let section_headers_offset = (shstrtab_offset + shstrtab.len() + 7) & !7;
+
+    let mut image = Vec::with_capacity(section_headers_offset + 5 * SECTION_HEADER_SIZE);
+    // ELF64, little-endian, current version
+    image.extend_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    // ET_EXEC
+    image.extend_from_slice(&2u16.to_le_bytes());
+    image.extend_from_slice(&machine.to_le_bytes());
+    image.extend_from_slice(&1u32.to_le_bytes());
+    // no entry point nor program headers
+    image.extend_from_slice(&0u64.to_le_bytes());
+    image.extend_from_slice(&0u64.to_le_bytes());
+    image.extend_from_slice(&(section_headers_offset as u64).to_le_bytes());
+    image.extend_from_slice(&0u32.to_le_bytes());
+    image.extend_from_slice(&64u16.to_le_bytes());
+    image.extend_from_slice(&0u16.to_le_bytes());
+    image.extend_from_slice(&0u16.to_le_bytes());
+    image.extend_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+    // 5 sections, the names in the last one
+    image.extend_from_slice(&5u16.to_le_bytes());
+    image.extend_from_slice(&4u16.to_le_bytes());
+
+    image.extend_from_slice(&symtab);
+    image.extend_from_slice(&strtab);
+    image.extend_from_slice(shstrtab);
+    // the null section
+    image.resize(section_headers_offset + SECTION_HEADER_SIZE, 0);
+
+    let mut section_header = |name: u32,
+                              kind: u32,
+                              flags: u64,
+                              addr: u64,
+                              offset: usize,
+                              size: usize,
+                              link: u32,
+                              info: u32,
+                              entsize: usize| {
+        image.extend_from_slice(&name.to_le_bytes());
+        image.extend_from_slice(&kind.to_le_bytes());
+        image.extend_from_slice(&flags.to_le_bytes());
+        image.extend_from_slice(&addr.to_le_bytes());
+        image.extend_from_slice(&(offset as u64).to_le_bytes());
+        image.extend_from_slice(&(size as u64).to_le_bytes());
+        image.extend_from_slice(&link.to_le_bytes());
+        image.extend_from_slice(&info.to_le_bytes());
+        image.extend_from_slice(&8u64.to_le_bytes());
+        image.extend_from_slice(&(entsize as u64).to_le_bytes());
+    };
+    // `.text`: SHT_NOBITS, SHF_ALLOC | SHF_EXECINSTR, the code being
+    // already loaded
+    let text_size = (text_end - text_start) as usize;
+    section_header(1, 8, 6, text_start, 0, text_size, 0, 0, 0);
+    // `.symtab`: SHT_SYMTAB, linked to `.strtab`, all the symbols but
+    // the null one being global
+    section_header(7, 2, 0, 0, symtab_offset, symtab.len(), 3, 1, SYMBOL_SIZE);
+    // `.strtab` and `.shstrtab`: SHT_STRTAB
+    section_header(15, 3, 0, 0, strtab_offset, strtab.len(), 0, 0, 0);
+    section_header(23, 3, 0, 0, shstrtab_offset, shstrtab.len(), 0, 0, 0);
+
+    Some(image.into_boxed_slice())
+}
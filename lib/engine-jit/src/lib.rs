@@ -28,13 +28,16 @@ mod artifact;
 mod builder;
 mod code_memory;
 mod engine;
+mod gdb_jit;
 mod link;
 mod serialize;
 mod unwind;
 
 pub use crate::artifact::JITArtifact;
 pub use crate::builder::JIT;
-pub use crate::code_memory::CodeMemory;
+pub use crate::code_memory::{
+    CodeMemory, CodeMemoryPages, CodeMemoryProvider, MmapCodeMemoryProvider,
+};
 pub use crate::engine::JITEngine;
 pub use crate::link::link_module;
 
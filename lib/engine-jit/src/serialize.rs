@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use wasmer_compiler::{
-    CompileModuleInfo, CustomSection, Dwarf, FunctionBody, JumpTableOffsets, Relocation,
-    SectionIndex,
+    CompileModuleInfo, CompiledFunctionStats, CustomSection, Dwarf, FunctionBody, JumpTableOffsets,
+    Relocation, SectionIndex, Target,
 };
 use wasmer_engine::SerializableFunctionFrameInfo;
 use wasmer_types::entity::PrimaryMap;
@@ -27,6 +27,7 @@ pub struct SerializableCompilation {
     // to allow lazy frame_info deserialization, we convert it to it's lazy binary
     // format upon serialization.
     pub function_frame_info: PrimaryMap<LocalFunctionIndex, SerializableFunctionFrameInfo>,
+    pub function_stats: PrimaryMap<LocalFunctionIndex, CompiledFunctionStats>,
     pub function_call_trampolines: PrimaryMap<SignatureIndex, FunctionBody>,
     pub dynamic_function_trampolines: PrimaryMap<FunctionIndex, FunctionBody>,
     pub custom_sections: PrimaryMap<SectionIndex, CustomSection>,
@@ -43,3 +44,63 @@ pub struct SerializableModule {
     pub compile_info: CompileModuleInfo,
     pub data_initializers: Box<[OwnedDataInitializer]>,
 }
+
+/// Metadata stored in front of a serialized module, describing the
+/// environment it was compiled for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SerializableMetadata {
+    /// The version of `wasmer-engine-jit` that serialized the module.
+    pub engine_version: String,
+    /// The target triple the module was compiled for.
+    pub triple: String,
+    /// The CPU features the compiled code may use.
+    pub cpu_features: Vec<String>,
+}
+
+impl SerializableMetadata {
+    /// Creates the metadata of a module compiled for `target`.
+    pub fn new(target: &Target) -> Self {
+        Self {
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            triple: target.triple().to_string(),
+            cpu_features: target
+                .cpu_features()
+                .iter()
+                .map(|feature| format!("{:?}", feature))
+                .collect(),
+        }
+    }
+
+    /// Checks that a module with this metadata can be loaded by an
+    /// engine for `target`, returning why it can't otherwise.
+    pub fn check_compatible(&self, target: &Target) -> Result<(), String> {
+        let host = Self::new(target);
+        if self.engine_version != host.engine_version {
+            return Err(format!(
+                "the module was serialized by wasmer-engine-jit {}, but this is {}; \
+                 recompile the module with this version",
+                self.engine_version, host.engine_version
+            ));
+        }
+        if self.triple != host.triple {
+            return Err(format!(
+                "the module was compiled for `{}`, but the engine targets `{}`",
+                self.triple, host.triple
+            ));
+        }
+        let missing = self
+            .cpu_features
+            .iter()
+            .filter(|feature| !host.cpu_features.contains(feature))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(format!(
+                "the module uses CPU features the engine target lacks: {}; \
+                 recompile the module for this target",
+                missing.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
@@ -75,7 +75,7 @@ impl NativeArtifact {
     /// system.
     pub fn is_deserializable(bytes: &[u8]) -> bool {
         cfg_if::cfg_if! {
-            if #[cfg(all(target_pointer_width = "64", target_os="macos"))] {
+            if #[cfg(all(target_pointer_width = "64", target_vendor="apple"))] {
                 bytes.starts_with(Self::MAGIC_HEADER_MH_CIGAM_64)
             }
             else if #[cfg(all(target_pointer_width = "64", target_os="linux"))] {
@@ -113,8 +113,10 @@ impl NativeArtifact {
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
             .module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| {
+                tunables.module_memory_style(&translation.module, index, memory_type)
+            })
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = translation
             .module
@@ -1,11 +1,13 @@
 use crate::NativeEngine;
 use wasmer_compiler::{CompilerConfig, Features, Target};
+use wasmer_engine::TrustStore;
 
 /// The Native builder
 pub struct Native<'a> {
     compiler_config: Option<&'a dyn CompilerConfig>,
     target: Option<Target>,
     features: Option<Features>,
+    trust_store: Option<TrustStore>,
 }
 
 impl<'a> Native<'a> {
@@ -18,6 +20,7 @@ impl<'a> Native<'a> {
             compiler_config: Some(compiler_config),
             target: None,
             features: None,
+            trust_store: None,
         }
     }
 
@@ -27,6 +30,7 @@ impl<'a> Native<'a> {
             compiler_config: None,
             target: None,
             features: None,
+            trust_store: None,
         }
     }
 
@@ -42,9 +46,18 @@ impl<'a> Native<'a> {
         self
     }
 
+    /// Set the keys the modules must be signed with to be compiled.
+    ///
+    /// Compiling a module without a signature made with one of them
+    /// fails with `CompileError::Signature`.
+    pub fn trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.trust_store = Some(trust_store);
+        self
+    }
+
     /// Build the `NativeEngine` for this configuration
     pub fn engine(self) -> NativeEngine {
-        if let Some(_compiler_config) = self.compiler_config {
+        let engine = if let Some(_compiler_config) = self.compiler_config {
             #[cfg(feature = "compiler")]
             {
                 let compiler_config = _compiler_config;
@@ -62,7 +75,9 @@ impl<'a> Native<'a> {
             }
         } else {
             NativeEngine::headless()
-        }
+        };
+        engine.set_trust_store(self.trust_store);
+        engine
     }
 }
 
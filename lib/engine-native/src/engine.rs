@@ -8,7 +8,7 @@ use std::sync::Mutex;
 use wasmer_compiler::{CompileError, Target};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::{Compiler, Triple};
-use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, Tunables};
+use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, TrustStore, Tunables};
 #[cfg(feature = "compiler")]
 use wasmer_types::Features;
 use wasmer_types::FunctionType;
@@ -51,6 +51,7 @@ impl NativeEngine {
                 compiler: Some(compiler),
                 signatures: SignatureRegistry::new(),
                 prefixer: None,
+                trust_store: None,
                 features,
                 is_cross_compiling,
                 linker,
@@ -83,6 +84,7 @@ impl NativeEngine {
                 features: Features::default(),
                 signatures: SignatureRegistry::new(),
                 prefixer: None,
+                trust_store: None,
                 is_cross_compiling: false,
                 linker: Linker::None,
                 libraries: vec![],
@@ -110,6 +112,12 @@ impl NativeEngine {
         inner.prefixer = Some(Box::new(prefixer));
     }
 
+    /// Restricts this engine to the modules signed with a key of
+    /// `trust_store`.
+    pub(crate) fn set_trust_store(&self, trust_store: Option<TrustStore>) {
+        self.inner_mut().trust_store = trust_store.map(Arc::new);
+    }
+
     pub(crate) fn inner(&self) -> std::sync::MutexGuard<'_, NativeEngineInner> {
         self.inner.lock().unwrap()
     }
@@ -142,6 +150,11 @@ impl Engine for NativeEngine {
         self.inner().validate(binary)
     }
 
+    /// The keys the compiled modules must be signed with
+    fn trust_store(&self) -> Option<Arc<TrustStore>> {
+        self.inner().trust_store.clone()
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     fn compile(
@@ -224,6 +237,8 @@ pub struct NativeEngineInner {
     /// the functions in the shared object generated by the `NativeEngine`,
     /// so we can assure no collisions.
     prefixer: Option<Box<dyn Fn(&[u8]) -> String + Send>>,
+    /// The keys the compiled modules must be signed with, if any.
+    trust_store: Option<Arc<TrustStore>>,
     /// Whether the native engine will cross-compile.
     is_cross_compiling: bool,
     /// The linker to use.
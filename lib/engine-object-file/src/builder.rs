@@ -1,11 +1,13 @@
 use crate::ObjectFileEngine;
 use wasmer_compiler::{CompilerConfig, Features, Target};
+use wasmer_engine::TrustStore;
 
 /// The ObjectFile builder
 pub struct ObjectFile<'a> {
     compiler_config: Option<&'a dyn CompilerConfig>,
     target: Option<Target>,
     features: Option<Features>,
+    trust_store: Option<TrustStore>,
 }
 
 impl<'a> ObjectFile<'a> {
@@ -18,6 +20,7 @@ impl<'a> ObjectFile<'a> {
             compiler_config: Some(compiler_config),
             target: None,
             features: None,
+            trust_store: None,
         }
     }
 
@@ -27,6 +30,7 @@ impl<'a> ObjectFile<'a> {
             compiler_config: None,
             target: None,
             features: None,
+            trust_store: None,
         }
     }
 
@@ -42,9 +46,18 @@ impl<'a> ObjectFile<'a> {
         self
     }
 
+    /// Set the keys the modules must be signed with to be compiled.
+    ///
+    /// Compiling a module without a signature made with one of them
+    /// fails with `CompileError::Signature`.
+    pub fn trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.trust_store = Some(trust_store);
+        self
+    }
+
     /// Build the `ObjectFileEngine` for this configuration
     pub fn engine(self) -> ObjectFileEngine {
-        if let Some(_compiler_config) = self.compiler_config {
+        let engine = if let Some(_compiler_config) = self.compiler_config {
             #[cfg(feature = "compiler")]
             {
                 let compiler_config = _compiler_config;
@@ -62,7 +75,9 @@ impl<'a> ObjectFile<'a> {
             }
         } else {
             ObjectFileEngine::headless()
-        }
+        };
+        engine.set_trust_store(self.trust_store);
+        engine
     }
 }
 
@@ -5,7 +5,7 @@ use std::sync::{Arc, Mutex};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::Compiler;
 use wasmer_compiler::{CompileError, Target};
-use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, Tunables};
+use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, TrustStore, Tunables};
 #[cfg(feature = "compiler")]
 use wasmer_types::Features;
 use wasmer_types::FunctionType;
@@ -29,6 +29,7 @@ impl ObjectFileEngine {
                 compiler: Some(compiler),
                 signatures: SignatureRegistry::new(),
                 prefixer: None,
+                trust_store: None,
                 features,
             })),
             target: Arc::new(target),
@@ -58,6 +59,7 @@ impl ObjectFileEngine {
                 features: Features::default(),
                 signatures: SignatureRegistry::new(),
                 prefixer: None,
+                trust_store: None,
             })),
             target: Arc::new(Target::default()),
             engine_id: EngineId::default(),
@@ -82,6 +84,12 @@ impl ObjectFileEngine {
         inner.prefixer = Some(Box::new(prefixer));
     }
 
+    /// Restricts this engine to the modules signed with a key of
+    /// `trust_store`.
+    pub(crate) fn set_trust_store(&self, trust_store: Option<TrustStore>) {
+        self.inner_mut().trust_store = trust_store.map(Arc::new);
+    }
+
     pub(crate) fn inner(&self) -> std::sync::MutexGuard<'_, ObjectFileEngineInner> {
         self.inner.lock().unwrap()
     }
@@ -114,6 +122,11 @@ impl Engine for ObjectFileEngine {
         self.inner().validate(binary)
     }
 
+    /// The keys the compiled modules must be signed with
+    fn trust_store(&self) -> Option<Arc<TrustStore>> {
+        self.inner().trust_store.clone()
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     fn compile(
@@ -179,6 +192,8 @@ pub struct ObjectFileEngineInner {
     /// the functions in the shared object generated by the `ObjectFileEngine`,
     /// so we can assure no collisions.
     prefixer: Option<Box<dyn Fn(&[u8]) -> String + Send>>,
+    /// The keys the compiled modules must be signed with, if any.
+    trust_store: Option<Arc<TrustStore>>,
 }
 
 impl ObjectFileEngineInner {
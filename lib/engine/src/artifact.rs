@@ -5,15 +5,15 @@ use std::any::Any;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
-use wasmer_compiler::Features;
+use wasmer_compiler::{CompiledFunctionFrameInfo, CompiledFunctionStats, Features};
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
 use wasmer_types::{
     DataInitializer, FunctionIndex, LocalFunctionIndex, MemoryIndex, OwnedDataInitializer,
     SignatureIndex, TableIndex,
 };
 use wasmer_vm::{
-    FunctionBodyPtr, InstanceHandle, MemoryStyle, ModuleInfo, TableStyle, VMSharedSignatureIndex,
-    VMTrampoline,
+    FunctionBodyPtr, InstanceHandle, MemoryStyle, ModuleHash, ModuleInfo, TableStyle,
+    VMSharedSignatureIndex, VMTrampoline,
 };
 
 /// An `Artifact` is the product that the `Engine`
@@ -66,6 +66,30 @@ pub trait Artifact: Send + Sync + Upcastable {
     /// Returns the associated VM signatures for this `Artifact`.
     fn signatures(&self) -> &BoxedSlice<SignatureIndex, VMSharedSignatureIndex>;
 
+    /// Returns the bytes of executable code memory the engine allocated
+    /// for this `Artifact`, or 0 when the engine doesn't track it.
+    fn code_memory_size(&self) -> usize {
+        0
+    }
+
+    /// Returns the content hash of the binary this `Artifact` was
+    /// compiled from, kept when it's serialized.
+    fn hash(&self) -> Option<ModuleHash> {
+        self.module_ref().hash
+    }
+
+    /// Returns the compilation statistics of the functions defined in
+    /// this `Artifact`, or `None` when the engine doesn't keep them.
+    fn function_stats(&self) -> Option<&PrimaryMap<LocalFunctionIndex, CompiledFunctionStats>> {
+        None
+    }
+
+    /// Returns the traps and address map of the local function `index`,
+    /// or `None` when the engine doesn't keep them.
+    fn function_frame_info(&self, _index: LocalFunctionIndex) -> Option<CompiledFunctionFrameInfo> {
+        None
+    }
+
     /// Serializes an artifact into bytes
     fn serialize(&self) -> Result<Vec<u8>, SerializeError>;
 
@@ -128,6 +152,7 @@ pub trait Artifact: Send + Sync + Upcastable {
             imports,
             self.signatures().clone(),
             host_state,
+            tunables.instance_allocator(),
         )
         .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
     }
@@ -153,6 +178,26 @@ pub trait Artifact: Send + Sync + Upcastable {
             .finish_instantiation(&data_initializers)
             .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
     }
+
+    /// Resets an `InstanceHandle` created from this artifact to its state
+    /// right after instantiation, so it can be reused.
+    ///
+    /// # Safety
+    ///
+    /// See [`InstanceHandle::reset`].
+    unsafe fn reset_instance(&self, handle: &InstanceHandle) -> Result<(), InstantiationError> {
+        let data_initializers = self
+            .data_initializers()
+            .iter()
+            .map(|init| DataInitializer {
+                location: init.location.clone(),
+                data: &*init.data,
+            })
+            .collect::<Vec<_>>();
+        handle
+            .reset(&data_initializers)
+            .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
+    }
 }
 
 // Implementation of `Upcastable` taken from https://users.rust-lang.org/t/why-does-downcasting-not-work-for-subtraits/33286/7 .
@@ -1,5 +1,6 @@
 //! JIT compilation.
 
+use crate::trust::TrustStore;
 use crate::tunables::Tunables;
 use crate::{Artifact, DeserializeError};
 use std::path::Path;
@@ -28,6 +29,12 @@ pub trait Engine {
     /// Validates a WebAssembly module
     fn validate(&self, binary: &[u8]) -> Result<(), CompileError>;
 
+    /// The keys the signatures of the compiled modules must be made
+    /// with, if the engine only compiles signed modules.
+    fn trust_store(&self) -> Option<Arc<TrustStore>> {
+        None
+    }
+
     /// Compile a WebAssembly binary
     fn compile(
         &self,
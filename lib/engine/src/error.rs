@@ -4,10 +4,12 @@ use std::io;
 use thiserror::Error;
 use wasmer_compiler::CompileError;
 use wasmer_types::ExternType;
+use wasmer_vm::MemoryStyle;
 
 /// The Serialize error can occur when serializing a
 /// compiled Module into a binary.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum SerializeError {
     /// An IO error
     #[error(transparent)]
@@ -20,6 +22,7 @@ pub enum SerializeError {
 /// The Deserialize error can occur when loading a
 /// compiled Module from a binary.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum DeserializeError {
     /// An IO error
     #[error(transparent)]
@@ -44,16 +47,29 @@ pub enum DeserializeError {
 /// Note: this error is not standard to WebAssembly, but it's
 /// useful to determine the import issue on the API side.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ImportError {
     /// Incompatible Import Type.
     /// This error occurs when the import types mismatch.
-    #[error("incompatible import type. Expected {0:?} but received {1:?}")]
+    #[error("incompatible import type. Expected {0} but received {1}")]
     IncompatibleType(ExternType, ExternType),
 
     /// Unknown Import.
     /// This error occurs when an import was expected but not provided.
-    #[error("unknown import. Expected {0:?}")]
+    #[error("unknown import. Expected {0}")]
     UnknownImport(ExternType),
+
+    /// Import Not Allowed.
+    /// This error occurs when the policy of the store doesn't allow
+    /// the import.
+    #[error("import not allowed by the store policy. Expected {0}")]
+    NotAllowed(ExternType),
+
+    /// Incompatible Memory Style.
+    /// This error occurs when an imported memory is smaller, or has
+    /// smaller guard pages, than the module was compiled for.
+    #[error("incompatible memory style. Expected {0:?} but received {1:?}")]
+    IncompatibleMemoryStyle(MemoryStyle, MemoryStyle),
 }
 
 /// The WebAssembly.LinkError object indicates an error during
@@ -64,11 +80,17 @@ pub enum ImportError {
 /// [link-error]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/LinkError
 #[derive(Error, Debug)]
 #[error("Link error: {0}")]
+#[non_exhaustive]
 pub enum LinkError {
     /// An error occurred when checking the import types.
     #[error("Error while importing {0:?}.{1:?}: {2}")]
     Import(String, String, ImportError),
 
+    /// Several imports failed the import type checks, with their
+    /// namespace and name, in the order of the imports of the module.
+    #[error("{}", format_imports(.0))]
+    Imports(Vec<(String, String, ImportError)>),
+
     /// A trap ocurred during linking.
     #[error("RuntimeError occurred during linking: {0}")]
     Trap(#[source] RuntimeError),
@@ -78,6 +100,44 @@ pub enum LinkError {
     Resource(String),
 }
 
+impl LinkError {
+    /// Returns a stable code identifying the kind of error, e.g. to
+    /// report it through FFI or in metrics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Import(..) => "import",
+            Self::Imports(_) => "imports",
+            Self::Trap(_) => "trap",
+            Self::Resource(_) => "resource",
+        }
+    }
+
+    /// Returns the error of the imports that failed the import type
+    /// checks: a [`LinkError::Import`] for a single import, and a
+    /// [`LinkError::Imports`] for several ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `errors` is empty.
+    pub fn from_imports(mut errors: Vec<(String, String, ImportError)>) -> Self {
+        assert!(!errors.is_empty(), "no import errors");
+        if errors.len() == 1 {
+            let (module, field, error) = errors.remove(0);
+            Self::Import(module, field, error)
+        } else {
+            Self::Imports(errors)
+        }
+    }
+}
+
+fn format_imports(errors: &[(String, String, ImportError)]) -> String {
+    let mut message = format!("{} imports failed", errors.len());
+    for (module, field, error) in errors {
+        message.push_str(&format!("\n  {:?}.{:?}: {}", module, field, error));
+    }
+    message
+}
+
 /// An error while instantiating a module.
 ///
 /// This is not a common WebAssembly error, however
@@ -86,6 +146,7 @@ pub enum LinkError {
 /// Trap that occurs when calling the WebAssembly module
 /// start function.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum InstantiationError {
     /// A linking ocurred during instantiation.
     #[error(transparent)]
@@ -94,4 +155,87 @@ pub enum InstantiationError {
     /// A runtime error occured while invoking the start function
     #[error(transparent)]
     Start(RuntimeError),
+
+    /// The instance would exceed the limits of its store.
+    #[error(transparent)]
+    Limit(StoreLimitError),
+}
+
+impl InstantiationError {
+    /// Returns a stable code identifying the kind of error, the one of
+    /// the [`LinkError`] or [`RuntimeError`] it wraps, or `limit`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Link(error) => error.code(),
+            Self::Start(error) => error.code(),
+            Self::Limit(_) => "limit",
+        }
+    }
+}
+
+/// A limit of a store exceeded by instantiating a module in it.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StoreLimitError {
+    /// The store already has as many live instances as it allows.
+    #[error("the store is limited to {0} live instances")]
+    Instances(usize),
+
+    /// The memories of the live instances would exceed the limit, in
+    /// bytes.
+    #[error("the memories would use {requested} bytes, over the limit of {limit} bytes")]
+    Memory {
+        /// The bytes the memories would use with the new instance.
+        requested: u64,
+        /// The limit of the store.
+        limit: u64,
+    },
+
+    /// The tables of the live instances would exceed the limit.
+    #[error("the instances would define {requested} tables, over the limit of {limit}")]
+    Tables {
+        /// The tables the instances would define with the new instance.
+        requested: usize,
+        /// The limit of the store.
+        limit: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer_types::{GlobalType, Mutability, Type};
+
+    fn import_error() -> (String, String, ImportError) {
+        let global = ExternType::Global(GlobalType::new(Type::I32, Mutability::Const));
+        (
+            "env".to_string(),
+            "global".to_string(),
+            ImportError::UnknownImport(global),
+        )
+    }
+
+    #[test]
+    fn link_error_codes() {
+        assert_eq!(
+            LinkError::from_imports(vec![import_error()]).code(),
+            "import"
+        );
+        assert_eq!(
+            LinkError::from_imports(vec![import_error(), import_error()]).code(),
+            "imports"
+        );
+        assert_eq!(LinkError::Trap(RuntimeError::new("")).code(), "trap");
+        assert_eq!(LinkError::Resource("".to_string()).code(), "resource");
+    }
+
+    #[test]
+    fn instantiation_error_codes() {
+        let link = LinkError::Resource("".to_string());
+        assert_eq!(InstantiationError::Link(link).code(), "resource");
+        let start = RuntimeError::new("");
+        assert_eq!(InstantiationError::Start(start).code(), "generic");
+        let limit = StoreLimitError::Instances(1);
+        assert_eq!(InstantiationError::Limit(limit).code(), "limit");
+    }
 }
@@ -26,12 +26,13 @@ mod error;
 mod resolver;
 mod serialize;
 mod trap;
+mod trust;
 mod tunables;
 
 pub use crate::artifact::Artifact;
 pub use crate::engine::{Engine, EngineId};
 pub use crate::error::{
-    DeserializeError, ImportError, InstantiationError, LinkError, SerializeError,
+    DeserializeError, ImportError, InstantiationError, LinkError, SerializeError, StoreLimitError,
 };
 pub use crate::resolver::{
     resolve_imports, ChainableNamedResolver, NamedResolver, NamedResolverChain, NullResolver,
@@ -39,6 +40,9 @@ pub use crate::resolver::{
 };
 pub use crate::serialize::SerializableFunctionFrameInfo;
 pub use crate::trap::*;
+pub use crate::trust::{
+    detached_signature, public_key, sign_module, TrustStore, SIGNATURE_LEN, SIGNATURE_SECTION,
+};
 pub use crate::tunables::Tunables;
 
 /// Version number of this crate.
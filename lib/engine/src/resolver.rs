@@ -2,7 +2,6 @@
 //! references.
 
 use crate::{ImportError, LinkError};
-use more_asserts::assert_ge;
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{ExternType, FunctionIndex, ImportIndex, MemoryIndex, TableIndex};
 
@@ -117,6 +116,8 @@ fn get_extern_from_export(_module: &ModuleInfo, export: &Export) -> ExternType {
 /// a `Resolver`.
 ///
 /// If all imports are satisfied returns an `Imports` instance required for a module instantiation.
+/// Otherwise, all the imports are checked and the error reports every
+/// missing or incompatible import.
 pub fn resolve_imports(
     module: &ModuleInfo,
     resolver: &dyn Resolver,
@@ -129,26 +130,63 @@ pub fn resolve_imports(
     let mut memory_imports = PrimaryMap::with_capacity(module.num_imported_memories);
     let mut global_imports = PrimaryMap::with_capacity(module.num_imported_globals);
 
+    let mut errors = vec![];
+
     for ((module_name, field, import_idx), import_index) in module.imports.iter() {
         let resolved = resolver.resolve(*import_idx, module_name, field);
         let import_extern = get_extern_from_import(module, import_index);
         let resolved = match resolved {
             None => {
-                return Err(LinkError::Import(
+                errors.push((
                     module_name.to_string(),
                     field.to_string(),
                     ImportError::UnknownImport(import_extern),
                 ));
+                continue;
             }
             Some(r) => r,
         };
         let export_extern = get_extern_from_export(module, &resolved);
         if !export_extern.is_compatible_with(&import_extern) {
-            return Err(LinkError::Import(
+            errors.push((
                 module_name.to_string(),
                 field.to_string(),
                 ImportError::IncompatibleType(import_extern, export_extern),
             ));
+            continue;
+        }
+        if let (Export::Memory(m), ImportIndex::Memory(index)) = (&resolved, import_index) {
+            // Ensure that the imported memory has at least the
+            // guard-page protections the importing module expects it to have.
+            let export_memory_style = m.style();
+            let import_memory_style = &memory_styles[*index];
+            let bound_fits = match (export_memory_style, import_memory_style) {
+                (
+                    MemoryStyle::Static { bound, .. },
+                    MemoryStyle::Static {
+                        bound: import_bound,
+                        ..
+                    },
+                ) => bound >= import_bound,
+                _ => true,
+            };
+            if !bound_fits
+                || export_memory_style.offset_guard_size() < import_memory_style.offset_guard_size()
+            {
+                errors.push((
+                    module_name.to_string(),
+                    field.to_string(),
+                    ImportError::IncompatibleMemoryStyle(
+                        import_memory_style.clone(),
+                        export_memory_style.clone(),
+                    ),
+                ));
+                continue;
+            }
+        }
+        if !errors.is_empty() {
+            // The imports are only checked from now on.
+            continue;
         }
         match resolved {
             Export::Function(ref f) => {
@@ -177,34 +215,6 @@ pub fn resolve_imports(
                 });
             }
             Export::Memory(ref m) => {
-                match import_index {
-                    ImportIndex::Memory(index) => {
-                        // Sanity-check: Ensure that the imported memory has at least
-                        // guard-page protections the importing module expects it to have.
-                        let export_memory_style = m.style();
-                        let import_memory_style = &memory_styles[*index];
-                        if let (
-                            MemoryStyle::Static { bound, .. },
-                            MemoryStyle::Static {
-                                bound: import_bound,
-                                ..
-                            },
-                        ) = (export_memory_style.clone(), &import_memory_style)
-                        {
-                            assert_ge!(bound, *import_bound);
-                        }
-                        assert_ge!(
-                            export_memory_style.offset_guard_size(),
-                            import_memory_style.offset_guard_size()
-                        );
-                    }
-                    _ => {
-                        // This should never be reached, as we did compatibility
-                        // checks before
-                        panic!("Memory resolution didn't matched");
-                    }
-                }
-
                 memory_imports.push(VMMemoryImport {
                     definition: m.from.vmmemory(),
                     from: m.from.clone(),
@@ -220,6 +230,10 @@ pub fn resolve_imports(
         }
     }
 
+    if !errors.is_empty() {
+        return Err(LinkError::from_imports(errors));
+    }
+
     Ok(Imports::new(
         function_imports,
         table_imports,
@@ -192,6 +192,26 @@ impl RuntimeError {
         &self.inner.wasm_trace
     }
 
+    /// Returns the trap code, if the `RuntimeError` comes from a trap
+    /// (rather than from a host function).
+    pub fn trap_code(&self) -> Option<TrapCode> {
+        match &self.inner.source {
+            RuntimeErrorSource::Trap(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns a stable code identifying the kind of error: `"trap"`
+    /// for a trap, `"user"` for an error raised by a host function, and
+    /// `"generic"` otherwise.
+    pub fn code(&self) -> &'static str {
+        match &self.inner.source {
+            RuntimeErrorSource::Generic(_) => "generic",
+            RuntimeErrorSource::User(_) => "user",
+            RuntimeErrorSource::Trap(_) => "trap",
+        }
+    }
+
     /// Attempts to downcast the `RuntimeError` to a concrete type.
     pub fn downcast<T: Error + 'static>(self) -> Result<T, Self> {
         match Arc::try_unwrap(self.inner) {
@@ -272,3 +292,25 @@ impl From<Trap> for RuntimeError {
         Self::from_trap(trap)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn codes() {
+        assert_eq!(RuntimeError::new("failed").code(), "generic");
+
+        let user = Box::new(io::Error::new(io::ErrorKind::Other, "failed"));
+        assert_eq!(
+            RuntimeError::from_trap(Trap::new_from_user(user)).code(),
+            "user"
+        );
+
+        let trap = Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds);
+        let error = RuntimeError::from_trap(trap);
+        assert_eq!(error.code(), "trap");
+        assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+    }
+}
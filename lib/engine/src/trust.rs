@@ -0,0 +1,183 @@
+//! Signing of WebAssembly modules, and verification of their signatures
+//! against the keys trusted by an engine.
+
+use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey, Signature};
+use std::convert::TryFrom;
+use wasmer_compiler::CompileError;
+
+/// The custom section holding the embedded signature of a module.
+///
+/// It must be the last section of the module, and holds the public key
+/// of the signer (32 bytes) followed by the ed25519 signature (64
+/// bytes) of all the bytes of the module preceding the section.
+pub const SIGNATURE_SECTION: &str = "wasmer_signature";
+
+/// The length of a signature: the public key of the signer followed by
+/// the ed25519 signature.
+pub const SIGNATURE_LEN: usize = 32 + 64;
+
+/// The public keys whose signatures an engine trusts.
+///
+/// An engine with a trust store only compiles the modules signed by one
+/// of its keys, either with an embedded [`SIGNATURE_SECTION`] or with a
+/// detached signature given alongside the module. The signature is
+/// checked before the module is validated or compiled.
+///
+/// Serialized artifacts aren't signed: deserializing them is already
+/// restricted to trusted inputs.
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    keys: Vec<PublicKey>,
+}
+
+impl TrustStore {
+    /// Creates a trust store trusting no key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts the signatures made with the ed25519 `public_key`.
+    pub fn trust(&mut self, public_key: &[u8; 32]) -> Result<&mut Self, CompileError> {
+        let key = PublicKey::from_bytes(public_key)
+            .map_err(|e| CompileError::Signature(format!("invalid public key: {}", e)))?;
+        if !self.is_trusted(public_key) {
+            self.keys.push(key);
+        }
+        Ok(self)
+    }
+
+    /// Returns whether the signatures made with `public_key` are trusted.
+    pub fn is_trusted(&self, public_key: &[u8; 32]) -> bool {
+        self.keys.iter().any(|key| key.as_bytes() == public_key)
+    }
+
+    /// Verifies the [`SIGNATURE_SECTION`] embedded in `binary`.
+    pub fn verify(&self, binary: &[u8]) -> Result<(), CompileError> {
+        let (signed, signature) = embedded_signature(binary)?
+            .ok_or_else(|| CompileError::Signature("the module isn't signed".to_string()))?;
+        self.verify_detached(signed, signature)
+    }
+
+    /// Verifies the detached `signature` of `binary`, as made by
+    /// [`detached_signature`].
+    pub fn verify_detached(&self, binary: &[u8], signature: &[u8]) -> Result<(), CompileError> {
+        if signature.len() != SIGNATURE_LEN {
+            return Err(CompileError::Signature(format!(
+                "the signature is {} bytes long instead of {}",
+                signature.len(),
+                SIGNATURE_LEN
+            )));
+        }
+        let (public_key, signature) = signature.split_at(32);
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.as_bytes()[..] == *public_key)
+            .ok_or_else(|| {
+                CompileError::Signature("the module is signed with an untrusted key".to_string())
+            })?;
+        let signature = Signature::try_from(signature)
+            .map_err(|e| CompileError::Signature(format!("malformed signature: {}", e)))?;
+        key.verify_strict(binary, &signature)
+            .map_err(|_| CompileError::Signature("the signature doesn't match".to_string()))
+    }
+}
+
+/// Signs `binary` with the ed25519 `secret_key`, returning the module
+/// with its signature embedded in a [`SIGNATURE_SECTION`].
+pub fn sign_module(binary: &[u8], secret_key: &[u8; 32]) -> Vec<u8> {
+    let signature = detached_signature(binary, secret_key);
+    let mut payload = vec![];
+    write_u32(&mut payload, SIGNATURE_SECTION.len() as u32);
+    payload.extend_from_slice(SIGNATURE_SECTION.as_bytes());
+    payload.extend_from_slice(&signature);
+
+    let mut signed = binary.to_vec();
+    signed.push(0);
+    write_u32(&mut signed, payload.len() as u32);
+    signed.extend_from_slice(&payload);
+    signed
+}
+
+/// Signs `binary` with the ed25519 `secret_key`, returning the
+/// signature to be distributed alongside the module.
+pub fn detached_signature(binary: &[u8], secret_key: &[u8; 32]) -> Vec<u8> {
+    let secret = SecretKey::from_bytes(secret_key).expect("a secret key is any 32 bytes");
+    let public = PublicKey::from(&secret);
+    let signature = ExpandedSecretKey::from(&secret).sign(binary, &public);
+    let mut bytes = public.as_bytes().to_vec();
+    bytes.extend_from_slice(&signature.to_bytes());
+    bytes
+}
+
+/// Returns the ed25519 public key of `secret_key`, to be trusted by the
+/// engines running the modules it signs.
+pub fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+    let secret = SecretKey::from_bytes(secret_key).expect("a secret key is any 32 bytes");
+    PublicKey::from(&secret).to_bytes()
+}
+
+/// Returns the bytes signed by the signature embedded in `binary`, and
+/// the signature, if the last section of `binary` is a
+/// [`SIGNATURE_SECTION`].
+fn embedded_signature(binary: &[u8]) -> Result<Option<(&[u8], &[u8])>, CompileError> {
+    let malformed = || CompileError::Signature("malformed module".to_string());
+    if binary.len() < 8 {
+        return Err(malformed());
+    }
+    let mut offset = 8;
+    let mut last = None;
+    while offset < binary.len() {
+        let start = offset;
+        let id = binary[offset];
+        offset += 1;
+        let size = read_u32(binary, &mut offset).ok_or_else(malformed)? as usize;
+        let end = offset.checked_add(size).ok_or_else(malformed)?;
+        if end > binary.len() {
+            return Err(malformed());
+        }
+        last = Some((start, id, offset, end));
+        offset = end;
+    }
+
+    let (start, id, mut offset, end) = match last {
+        Some(last) => last,
+        None => return Ok(None),
+    };
+    if id != 0 {
+        return Ok(None);
+    }
+    let name_len = read_u32(binary, &mut offset).ok_or_else(malformed)? as usize;
+    let name = binary
+        .get(offset..offset + name_len)
+        .ok_or_else(malformed)?;
+    if name != SIGNATURE_SECTION.as_bytes() {
+        return Ok(None);
+    }
+    Ok(Some((&binary[..start], &binary[offset + name_len..end])))
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*offset)?;
+        *offset += 1;
+        value |= u32::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
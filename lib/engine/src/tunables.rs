@@ -5,8 +5,8 @@ use wasmer_types::{
     GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
     TableIndex, TableType,
 };
-use wasmer_vm::MemoryError;
 use wasmer_vm::{Global, Memory, ModuleInfo, Table};
+use wasmer_vm::{InstanceAllocator, MemoryError, SystemInstanceAllocator};
 use wasmer_vm::{MemoryStyle, TableStyle};
 
 /// An engine delegates the creation of memories, tables, and globals
@@ -15,6 +15,20 @@ pub trait Tunables {
     /// Construct a `MemoryStyle` for the provided `MemoryType`
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle;
 
+    /// Construct a `MemoryStyle` for the memory `index` of `module`,
+    /// when compiling it.
+    ///
+    /// Defaults to [`Tunables::memory_style`]; implementors can override
+    /// it to pick different styles for different modules.
+    fn module_memory_style(
+        &self,
+        _module: &ModuleInfo,
+        _index: MemoryIndex,
+        memory: &MemoryType,
+    ) -> MemoryStyle {
+        self.memory_style(memory)
+    }
+
     /// Construct a `TableStyle` for the provided `TableType`
     fn table_style(&self, table: &TableType) -> TableStyle;
 
@@ -33,6 +47,14 @@ pub trait Tunables {
         Ok(Arc::new(Global::new(ty)))
     }
 
+    /// The allocator for the `VMContext` of the instances, holding their
+    /// imports and the definitions of their tables, memories and globals.
+    ///
+    /// Defaults to the global allocator.
+    fn instance_allocator(&self) -> Arc<dyn InstanceAllocator> {
+        Arc::new(SystemInstanceAllocator)
+    }
+
     /// Allocate memory for just the memories of the current module.
     fn create_memories(
         &self,
@@ -38,6 +38,10 @@ pub struct ExportFunction {
     /// Address of the function call trampoline owned by the same VMContext that owns the VMFunctionBody.
     /// May be None when the function is an host-function (FunctionType == Dynamic or vmctx == nullptr).
     pub call_trampoline: Option<VMTrampoline>,
+    /// The `VMContext` of the instance defining the function, which is
+    /// charged the CPU time of its calls.  `None` for the host functions, and
+    /// for the imports re-exported by an instance.
+    pub instance_vmctx: Option<*mut VMContext>,
 }
 
 /// # Safety
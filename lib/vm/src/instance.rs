@@ -7,13 +7,14 @@
 use crate::export::Export;
 use crate::global::Global;
 use crate::imports::Imports;
+use crate::instance_allocator::InstanceAllocator;
 use crate::memory::{Memory, MemoryError};
 use crate::table::Table;
 use crate::trap::{catch_traps, init_traps, Trap, TrapCode};
 use crate::vmcontext::{
-    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport,
-    VMFunctionKind, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition, VMMemoryImport,
-    VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
+    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMEpoch, VMFunctionBody,
+    VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition,
+    VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
 };
 use crate::{ExportFunction, ExportGlobal, ExportMemory, ExportTable};
 use crate::{FunctionBodyPtr, ModuleInfo, VMOffsets};
@@ -26,6 +27,7 @@ use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{mem, ptr, slice};
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
@@ -100,9 +102,19 @@ pub(crate) struct Instance {
     /// Hosts can store arbitrary per-instance information here.
     host_state: Box<dyn Any>,
 
+    /// The epoch compiled code checks at loop headers. Kept alive here since
+    /// the `vmctx` only holds a raw pointer to it.
+    epoch: RefCell<Arc<VMEpoch>>,
+
     /// Handler run when `SIGBUS`, `SIGFPE`, `SIGILL`, or `SIGSEGV` are caught by the instance thread.
     pub(crate) signal_handler: Cell<Option<Box<SignalHandler>>>,
 
+    /// The CPU time used by the calls into this instance.
+    cpu_time: Cell<Duration>,
+
+    /// The allocator this instance and its `vmctx` were allocated with.
+    allocator: Arc<dyn InstanceAllocator>,
+
     /// Additional context used by compiled wasm code. This field is last, and
     /// represents a dynamically-sized array that extends beyond the nominal
     /// end of the struct (similar to a flexible array member).
@@ -269,6 +281,19 @@ impl Instance {
         unsafe { self.vmctx_plus_offset(self.offsets.vmctx_builtin_functions_begin()) }
     }
 
+    /// Return a pointer to the `VMEpoch` pointer.
+    fn epoch_ptr(&self) -> *mut *const VMEpoch {
+        unsafe { self.vmctx_plus_offset(self.offsets.vmctx_epoch()) }
+    }
+
+    /// Replaces the epoch checked by compiled code.
+    fn set_epoch(&self, epoch: Arc<VMEpoch>) {
+        unsafe {
+            *self.epoch_ptr() = &*epoch as *const VMEpoch;
+        }
+        *self.epoch.borrow_mut() = epoch;
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     pub fn vmctx(&self) -> &VMContext {
         &self.vmctx
@@ -294,13 +319,16 @@ impl Instance {
         match export {
             ExportIndex::Function(index) => {
                 let sig_index = &self.module.functions[*index];
-                let (address, vmctx) = if let Some(def_index) = self.module.local_func_index(*index)
-                {
-                    (self.functions[def_index].0 as *const _, self.vmctx_ptr())
-                } else {
-                    let import = self.imported_function(*index);
-                    (import.body, import.vmctx)
-                };
+                let (address, vmctx, instance_vmctx) =
+                    if let Some(def_index) = self.module.local_func_index(*index) {
+                        let vmctx = self.vmctx_ptr();
+                        (self.functions[def_index].0 as *const _, vmctx, Some(vmctx))
+                    } else {
+                        // the import may be a host function, whose vmctx isn't
+                        // the one of an instance
+                        let import = self.imported_function(*index);
+                        (import.body, import.vmctx, None)
+                    };
                 let call_trampoline = Some(self.function_call_trampolines[*sig_index]);
                 let signature = self.module.signatures[*sig_index].clone();
                 ExportFunction {
@@ -313,6 +341,7 @@ impl Instance {
                     signature,
                     vmctx,
                     call_trampoline,
+                    instance_vmctx,
                 }
                 .into()
             }
@@ -386,9 +415,11 @@ impl Instance {
             }
         };
 
-        // Make the call.
+        // Make the call, charged to this instance even if the start
+        // function is an import.
         unsafe {
-            catch_traps(callee_vmctx, || {
+            let instance = InstanceHandle::from_vmctx(self.vmctx_ptr());
+            catch_traps(callee_vmctx, Some(&instance), || {
                 mem::transmute::<*const VMFunctionBody, unsafe extern "C" fn(*mut VMContext)>(
                     callee_address,
                 )(callee_vmctx)
@@ -763,6 +794,91 @@ impl Instance {
         let import = self.imported_table(index);
         &*import.from
     }
+
+    /// Restores the memories, tables, globals and segments the instance
+    /// defines to their state before the initializers were applied.
+    fn reset(&self) -> Result<(), Trap> {
+        for memory in self.memories.values() {
+            memory
+                .reset()
+                .map_err(|error| Trap::new_from_user(Box::new(error)))?;
+        }
+        // tables can't shrink, so the grown ones keep their size
+        for table in self.tables.values() {
+            for index in 0..table.size() {
+                table.set(index, VMCallerCheckedAnyfunc::default())?;
+            }
+        }
+        initialize_globals(self);
+        self.passive_elements.borrow_mut().clear();
+        initialize_passive_elements(self);
+        *self.passive_data.borrow_mut() = self.module.passive_data.clone();
+        Ok(())
+    }
+
+    /// Adds `time` to the CPU time used by the calls into this instance.
+    pub(crate) fn add_cpu_time(&self, time: Duration) {
+        self.cpu_time.set(self.cpu_time.get() + time);
+    }
+
+    /// Get the memory used by this instance.
+    fn memory_usage(&self) -> InstanceMemoryUsage {
+        let memories = self
+            .memories
+            .values()
+            .map(|memory| memory.size().bytes().0)
+            .sum::<usize>();
+        let tables = self
+            .tables
+            .values()
+            .map(|table| table.size() as usize * mem::size_of::<VMCallerCheckedAnyfunc>())
+            .sum::<usize>();
+        let vmctx = usize::try_from(self.offsets.size_of_vmctx()).unwrap();
+        let passive_elements = self
+            .passive_elements
+            .borrow()
+            .values()
+            .map(|elements| elements.len() * mem::size_of::<VMCallerCheckedAnyfunc>())
+            .sum::<usize>();
+        let host_metadata = mem::size_of_val(self)
+            + self.memories.len() * mem::size_of::<Arc<dyn Memory>>()
+            + self.tables.len() * mem::size_of::<Arc<dyn Table>>()
+            + self.globals.len() * (mem::size_of::<Arc<Global>>() + mem::size_of::<Global>())
+            + self.functions.len() * mem::size_of::<FunctionBodyPtr>()
+            + self.function_call_trampolines.len() * mem::size_of::<VMTrampoline>()
+            + passive_elements;
+        InstanceMemoryUsage {
+            memories,
+            tables,
+            vmctx,
+            host_metadata,
+        }
+    }
+}
+
+/// The memory used by an instance, in bytes.
+///
+/// Memories and tables imported by the instance are accounted for by
+/// the instance defining them, and the code and module metadata shared
+/// by all the instances of a module aren't included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InstanceMemoryUsage {
+    /// The size of the linear memories defined by the instance.
+    pub memories: usize,
+    /// The size of the elements of the tables defined by the instance.
+    pub tables: usize,
+    /// The size of the `VMContext` of the instance.
+    pub vmctx: usize,
+    /// The size of the host-side data of the instance (its globals,
+    /// passive elements and bookkeeping).
+    pub host_metadata: usize,
+}
+
+impl InstanceMemoryUsage {
+    /// The total memory used by the instance.
+    pub fn total(&self) -> usize {
+        self.memories + self.tables + self.vmctx + self.host_metadata
+    }
 }
 
 /// A handle holding an `Instance` of a WebAssembly module.
@@ -803,6 +919,7 @@ impl InstanceHandle {
         imports: Imports,
         vmshared_signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
         host_state: Box<dyn Any>,
+        allocator: Arc<dyn InstanceAllocator>,
     ) -> Result<Self, Trap> {
         // TODO: investigate `vmctx_tables` and `vmctx_memories`: both of these
         // appear to be dropped in this function which may cause memory problems
@@ -847,12 +964,15 @@ impl InstanceHandle {
                 passive_elements: Default::default(),
                 passive_data,
                 host_state,
+                epoch: RefCell::new(Arc::new(VMEpoch::default())),
                 signal_handler: Cell::new(None),
+                cpu_time: Cell::new(Duration::default()),
+                allocator,
                 vmctx: VMContext {},
             };
             let layout = instance.alloc_layout();
             #[allow(clippy::cast_ptr_alignment)]
-            let instance_ptr = alloc::alloc(layout) as *mut Instance;
+            let instance_ptr = instance.allocator.allocate(layout) as *mut Instance;
             if instance_ptr.is_null() {
                 alloc::handle_alloc_error(layout);
             }
@@ -907,6 +1027,10 @@ impl InstanceHandle {
             instance.builtin_functions_ptr() as *mut VMBuiltinFunctionsArray,
             VMBuiltinFunctionsArray::initialized(),
         );
+        ptr::write(
+            instance.epoch_ptr(),
+            &**instance.epoch.borrow() as *const VMEpoch,
+        );
 
         // Ensure that our signal handlers are ready for action.
         init_traps();
@@ -941,6 +1065,31 @@ impl InstanceHandle {
         Ok(())
     }
 
+    /// Resets the instance to the state it had right after it was
+    /// instantiated: its memories are shrunk and zeroed, its tables
+    /// cleared, its globals and segments restored, then the initializers
+    /// are applied and the start function is run again.
+    ///
+    /// The memories, tables and globals it imports aren't reset.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call while no code of the instance runs, and while
+    /// nothing holds references into its memories.
+    pub unsafe fn reset(&self, data_initializers: &[DataInitializer<'_>]) -> Result<(), Trap> {
+        self.instance().reset()?;
+        self.finish_instantiation(data_initializers)
+    }
+
+    /// Shares `epoch` with this instance, so its deadline interrupts the
+    /// instance's compiled code (when compiled with epoch interruption).
+    ///
+    /// Should be called before `finish_instantiation` so the start function
+    /// is covered too.
+    pub fn set_epoch(&self, epoch: Arc<VMEpoch>) {
+        self.instance().set_epoch(epoch)
+    }
+
     /// Create a new `InstanceHandle` pointing at the instance
     /// pointed to by the given `VMContext` pointer.
     ///
@@ -1060,6 +1209,16 @@ impl InstanceHandle {
         self.instance().get_local_table(index)
     }
 
+    /// Get the CPU time used by the calls into this instance.
+    pub fn cpu_time(&self) -> Duration {
+        self.instance().cpu_time.get()
+    }
+
+    /// Get the memory used by this instance.
+    pub fn memory_usage(&self) -> InstanceMemoryUsage {
+        self.instance().memory_usage()
+    }
+
     /// Return a reference to the contained `Instance`.
     pub(crate) fn instance(&self) -> &Instance {
         unsafe { &*(self.instance as *const Instance) }
@@ -1075,8 +1234,9 @@ impl InstanceHandle {
     pub unsafe fn dealloc(&self) {
         let instance = self.instance();
         let layout = instance.alloc_layout();
+        let allocator = Arc::clone(&instance.allocator);
         ptr::drop_in_place(self.instance);
-        alloc::dealloc(self.instance.cast(), layout);
+        allocator.deallocate(self.instance.cast(), layout);
     }
 }
 
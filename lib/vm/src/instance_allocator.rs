@@ -0,0 +1,46 @@
+//! Allocators for the per-instance VM data.
+
+use std::alloc::{self, Layout};
+use std::fmt;
+
+/// An allocator for the block holding an `Instance` and its `VMContext`.
+///
+/// This block holds all the per-instance data the compiled code accesses:
+/// the imported functions, tables, memories and globals, and the
+/// definitions of the local tables, memories and globals. Embedders
+/// instantiating frequently can move these allocations onto an arena, or
+/// count them.
+///
+/// The elements of the tables and the memories are allocated by the
+/// `Tunables` creating them.
+pub trait InstanceAllocator: fmt::Debug + Send + Sync {
+    /// Allocate a block of memory for `layout`, returning a null pointer
+    /// if it can't be allocated.
+    ///
+    /// # Safety
+    ///
+    /// `layout` must have a non-zero size.
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// Deallocate a block of memory allocated with `layout`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `allocate` on this allocator with
+    /// the same `layout`, and not have been deallocated already.
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The default `InstanceAllocator`, using the global allocator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemInstanceAllocator;
+
+impl InstanceAllocator for SystemInstanceAllocator {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        alloc::alloc(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        alloc::dealloc(ptr, layout)
+    }
+}
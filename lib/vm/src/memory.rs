@@ -113,6 +113,15 @@ pub trait Memory: fmt::Debug + Send + Sync {
     ///
     /// The pointer returned in [`VMMemoryDefinition`] must be valid for the lifetime of this memory.
     fn vmmemory(&self) -> NonNull<VMMemoryDefinition>;
+
+    /// Shrink the memory back to its minimum size, with all its bytes zeroed.
+    ///
+    /// Used to reset instances for reuse; by default, memories can't be reset.
+    fn reset(&self) -> Result<(), MemoryError> {
+        Err(MemoryError::Generic(
+            "this memory does not support being reset".to_string(),
+        ))
+    }
 }
 
 /// A linear memory instance.
@@ -333,4 +342,23 @@ impl Memory for LinearMemory {
             as *const VMMemoryDefinition as *mut VMMemoryDefinition;
         unsafe { NonNull::new_unchecked(ptr) }
     }
+
+    /// Shrink the memory back to its minimum size, with all its bytes zeroed.
+    ///
+    /// The pages are replaced rather than written, so resetting a memory
+    /// costs little more than the pages the guest touches afterwards.
+    fn reset(&self) -> Result<(), MemoryError> {
+        let mut mmap_guard = self.mmap.lock().unwrap();
+        let mmap = mmap_guard.borrow_mut();
+        mmap.alloc
+            .reset(self.memory.minimum.bytes().0)
+            .map_err(MemoryError::Region)?;
+        mmap.size = self.memory.minimum;
+        unsafe {
+            let md = &mut *self.vm_memory_definition.get();
+            md.current_length = self.memory.minimum.bytes().0.try_into().unwrap();
+            md.base = mmap.alloc.as_mut_ptr() as _;
+        }
+        Ok(())
+    }
 }
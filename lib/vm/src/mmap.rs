@@ -227,6 +227,84 @@ impl Mmap {
         Ok(())
     }
 
+    /// Replace the memory with zeroed pages, leaving the first
+    /// `accessible_size` bytes accessible and the rest reserved.
+    /// `accessible_size` must be a native page-size multiple within `self`.
+    #[cfg(not(target_os = "windows"))]
+    pub fn reset(&mut self, accessible_size: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(accessible_size & (page_size - 1), 0);
+        assert_le!(accessible_size, self.len);
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        // Mapping fresh anonymous pages over the old ones releases them,
+        // and the new ones read as zeros until they're written.
+        let ptr = unsafe {
+            libc::mmap(
+                self.ptr as *mut libc::c_void,
+                self.len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        if accessible_size == 0 {
+            return Ok(());
+        }
+        unsafe {
+            region::protect(
+                self.ptr as *const u8,
+                accessible_size,
+                region::Protection::READ_WRITE,
+            )
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Replace the memory with zeroed pages, leaving the first
+    /// `accessible_size` bytes accessible and the rest reserved.
+    /// `accessible_size` must be a native page-size multiple within `self`.
+    #[cfg(target_os = "windows")]
+    pub fn reset(&mut self, accessible_size: usize) -> Result<(), String> {
+        use winapi::ctypes::c_void;
+        use winapi::um::memoryapi::{VirtualAlloc, VirtualFree};
+        use winapi::um::winnt::{MEM_COMMIT, MEM_DECOMMIT, PAGE_READWRITE};
+        let page_size = region::page::size();
+        assert_eq!(accessible_size & (page_size - 1), 0);
+        assert_le!(accessible_size, self.len);
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        // Committing decommitted pages again gives zeroed pages.
+        if unsafe { VirtualFree(self.ptr as *mut c_void, self.len, MEM_DECOMMIT) } == 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        if accessible_size == 0 {
+            return Ok(());
+        }
+        if unsafe {
+            VirtualAlloc(
+                self.ptr as *mut c_void,
+                accessible_size,
+                MEM_COMMIT,
+                PAGE_READWRITE,
+            )
+        }
+        .is_null()
+        {
+            return Err(io::Error::last_os_error().to_string());
+        }
+
+        Ok(())
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
@@ -39,6 +39,41 @@ impl Default for ModuleId {
     }
 }
 
+/// The content hash of a WebAssembly module: the BLAKE3 hash of its
+/// binary.
+///
+/// It identifies a module across processes and engines, e.g. as a cache
+/// key or in logs and metrics. Its `Display` is the lowercase hex
+/// encoding of the hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ModuleHash([u8; 32]);
+
+impl ModuleHash {
+    /// Creates a hash from its bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Hashes the binary of a module.
+    pub fn generate(binary: &[u8]) -> Self {
+        Self(blake3::hash(binary).into())
+    }
+
+    /// Returns the bytes of the hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ModuleHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 /// A translated WebAssembly module, excluding the function bodies and
 /// memory initializers.
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,6 +88,9 @@ pub struct ModuleInfo {
     /// The name of this wasm module, often found in the wasm file.
     pub name: Option<String>,
 
+    /// The hash of the binary this module was translated from.
+    pub hash: Option<ModuleHash>,
+
     /// Imported entities with the (module, field, index_of_the_import)
     ///
     /// Keeping the `index_of_the_import` is important, as there can be
@@ -81,6 +119,9 @@ pub struct ModuleInfo {
     /// WebAssembly function names.
     pub function_names: HashMap<FunctionIndex, String>,
 
+    /// WebAssembly local names, by function and local index.
+    pub local_names: HashMap<FunctionIndex, HashMap<u32, String>>,
+
     /// WebAssembly function signatures.
     pub signatures: PrimaryMap<SignatureIndex, FunctionType>,
 
@@ -121,6 +162,7 @@ impl ModuleInfo {
         Self {
             id: ModuleId::default(),
             name: None,
+            hash: None,
             imports: IndexMap::new(),
             exports: IndexMap::new(),
             start_function: None,
@@ -129,6 +171,7 @@ impl ModuleInfo {
             passive_data: HashMap::new(),
             global_initializers: PrimaryMap::new(),
             function_names: HashMap::new(),
+            local_names: HashMap::new(),
             signatures: PrimaryMap::new(),
             functions: PrimaryMap::new(),
             tables: PrimaryMap::new(),
@@ -238,6 +281,18 @@ impl ModuleInfo {
             })
     }
 
+    /// Get all the custom sections of the module, with their names.
+    pub fn all_custom_sections(&self) -> impl Iterator<Item = (&str, Arc<[u8]>)> + '_ {
+        self.custom_sections
+            .iter()
+            .map(move |(section_name, section_index)| {
+                (
+                    section_name.as_str(),
+                    self.custom_sections_data[*section_index].clone(),
+                )
+            })
+    }
+
     /// Convert a `LocalFunctionIndex` into a `FunctionIndex`.
     pub fn func_index(&self, local_func: LocalFunctionIndex) -> FunctionIndex {
         FunctionIndex::new(self.num_imported_functions + local_func.index())
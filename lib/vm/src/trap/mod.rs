@@ -11,4 +11,7 @@ pub use traphandlers::{
     catch_traps, catch_traps_with_result, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
     Trap,
 };
-pub use traphandlers::{init_traps, resume_panic};
+pub use traphandlers::{init_traps, resume_panic, set_break_on_trap};
+pub use traphandlers::{
+    set_signal_handler_policy, signal_handler_policy, SignalHandlerPolicy, SignalHandlerPolicyError,
+};
@@ -5,7 +5,7 @@
 //! signalhandling mechanisms.
 
 use super::trapcode::TrapCode;
-use crate::instance::{InstanceHandle, SignalHandler};
+use crate::instance::{Instance, InstanceHandle, SignalHandler};
 use crate::vmcontext::{VMContext, VMFunctionBody, VMTrampoline};
 use backtrace::Backtrace;
 use std::any::Any;
@@ -14,7 +14,10 @@ use std::error::Error;
 use std::io;
 use std::mem;
 use std::ptr;
-use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+use thiserror::Error;
 
 extern "C" {
     fn RegisterSetjmp(
@@ -74,12 +77,33 @@ cfg_if::cfg_if! {
 
             // On ARM, handle Unaligned Accesses.
             // On Darwin, guard page accesses are raised as SIGBUS.
-            if cfg!(target_arch = "arm") || cfg!(target_os = "macos") {
+            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
                 register(&mut PREV_SIGBUS, libc::SIGBUS);
             }
         }
 
-        #[cfg(target_os = "macos")]
+        /// Restores the handlers that were installed before `platform_init`.
+        unsafe fn platform_uninit() {
+            let restore = |slot: &MaybeUninit<libc::sigaction>, signal: i32| {
+                if libc::sigaction(signal, slot.as_ptr(), ptr::null_mut()) != 0 {
+                    panic!(
+                        "unable to restore signal handler: {}",
+                        io::Error::last_os_error(),
+                    );
+                }
+            };
+
+            restore(&PREV_SIGSEGV, libc::SIGSEGV);
+            restore(&PREV_SIGILL, libc::SIGILL);
+            if cfg!(target_arch = "x86") || cfg!(target_arch = "x86_64") {
+                restore(&PREV_SIGFPE, libc::SIGFPE);
+            }
+            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
+                restore(&PREV_SIGBUS, libc::SIGBUS);
+            }
+        }
+
+        #[cfg(target_vendor = "apple")]
         unsafe fn thread_stack() -> (usize, usize) {
             let this_thread = libc::pthread_self();
             let stackaddr = libc::pthread_get_stackaddr_np(this_thread);
@@ -87,7 +111,7 @@ cfg_if::cfg_if! {
             (stackaddr as usize - stacksize, stacksize)
         }
 
-        #[cfg(not(target_os = "macos"))]
+        #[cfg(not(target_vendor = "apple"))]
         unsafe fn thread_stack() -> (usize, usize) {
             let this_thread = libc::pthread_self();
             let mut thread_attrs: libc::pthread_attr_t = mem::zeroed();
@@ -202,9 +226,12 @@ cfg_if::cfg_if! {
                 } else if #[cfg(all(target_os = "linux", target_arch = "aarch64"))] {
                     let cx = &*(cx as *const libc::ucontext_t);
                     cx.uc_mcontext.pc as *const u8
-                } else if #[cfg(target_os = "macos")] {
+                } else if #[cfg(all(target_vendor = "apple", target_arch = "x86_64"))] {
                     let cx = &*(cx as *const libc::ucontext_t);
                     (*cx.uc_mcontext).__ss.__rip as *const u8
+                } else if #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))] {
+                    let cx = &*(cx as *const libc::ucontext_t);
+                    (*cx.uc_mcontext).__ss.__pc as *const u8
                 } else {
                     compile_error!("unsupported platform");
                 }
@@ -216,15 +243,26 @@ cfg_if::cfg_if! {
         use winapi::um::minwinbase::*;
         use winapi::vc::excpt::*;
 
+        static mut EXCEPTION_HANDLER: PVOID = ptr::null_mut();
+
         unsafe fn platform_init() {
             // our trap handler needs to go first, so that we can recover from
             // wasm faults and continue execution, so pass `1` as a true value
             // here.
-            if AddVectoredExceptionHandler(1, Some(exception_handler)).is_null() {
+            EXCEPTION_HANDLER = AddVectoredExceptionHandler(1, Some(exception_handler));
+            if EXCEPTION_HANDLER.is_null() {
                 panic!("failed to add exception handler: {}", io::Error::last_os_error());
             }
         }
 
+        /// Removes the handler added by `platform_init`.
+        unsafe fn platform_uninit() {
+            if RemoveVectoredExceptionHandler(EXCEPTION_HANDLER) == 0 {
+                panic!("failed to remove exception handler: {}", io::Error::last_os_error());
+            }
+            EXCEPTION_HANDLER = ptr::null_mut();
+        }
+
         unsafe extern "system" fn exception_handler(
             exception_info: PEXCEPTION_POINTERS
         ) -> LONG {
@@ -279,6 +317,82 @@ cfg_if::cfg_if! {
     }
 }
 
+/// How the runtime installs its `SIGSEGV`, `SIGBUS`, `SIGILL` and `SIGFPE`
+/// handlers (a vectored exception handler on Windows).
+///
+/// Embedders that install their own handlers (Go through cgo, crash
+/// reporters, ...) can pick the policy that coexists with them, through
+/// [`set_signal_handler_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalHandlerPolicy {
+    /// Install the handlers once for the whole process, the first time an
+    /// instance is created. Faults that don't come from WebAssembly code are
+    /// forwarded to the previously installed handlers.
+    ///
+    /// This is the default.
+    Chain,
+
+    /// Install the handlers when entering WebAssembly code and restore the
+    /// previous ones once no thread is running WebAssembly code anymore.
+    ///
+    /// Handlers installed by the embedder while WebAssembly code is running
+    /// are overwritten when the last call returns.
+    Scoped,
+
+    /// Never install any handler.
+    ///
+    /// Stores created afterwards use explicit bounds checks on every memory
+    /// access instead of guard pages. Any other fault raised by WebAssembly
+    /// code (`unreachable`, integer division by zero, stack overflow, ...)
+    /// reaches the embedder's handlers, and crashes the process by default.
+    Disabled,
+}
+
+/// Error returned by [`set_signal_handler_policy`].
+#[derive(Error, Debug)]
+#[error("the signal handler policy can't be changed once an instance has been created")]
+pub struct SignalHandlerPolicyError;
+
+static POLICY: AtomicUsize = AtomicUsize::new(0);
+static POLICY_FROZEN: AtomicBool = AtomicBool::new(false);
+
+impl SignalHandlerPolicy {
+    fn to_usize(self) -> usize {
+        match self {
+            Self::Chain => 0,
+            Self::Scoped => 1,
+            Self::Disabled => 2,
+        }
+    }
+
+    fn from_usize(value: usize) -> Self {
+        match value {
+            0 => Self::Chain,
+            1 => Self::Scoped,
+            _ => Self::Disabled,
+        }
+    }
+}
+
+/// Sets how the runtime installs its signal handlers.
+///
+/// This must be called before the first instance is created, and before
+/// creating the stores that should honor [`SignalHandlerPolicy::Disabled`].
+pub fn set_signal_handler_policy(
+    policy: SignalHandlerPolicy,
+) -> Result<(), SignalHandlerPolicyError> {
+    if POLICY_FROZEN.load(Ordering::SeqCst) {
+        return Err(SignalHandlerPolicyError);
+    }
+    POLICY.store(policy.to_usize(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Returns the current signal handler policy.
+pub fn signal_handler_policy() -> SignalHandlerPolicy {
+    SignalHandlerPolicy::from_usize(POLICY.load(Ordering::SeqCst))
+}
+
 /// This function performs the low-overhead signal handler initialization that
 /// we want to do eagerly to ensure a more-deterministic global process state.
 ///
@@ -288,9 +402,50 @@ cfg_if::cfg_if! {
 /// function needs to be called at the end of the startup process, after other
 /// handlers have been installed. This function can thus be called multiple
 /// times, having no effect after the first call.
+///
+/// It also freezes the [`SignalHandlerPolicy`], and only installs the
+/// handlers with [`SignalHandlerPolicy::Chain`].
 pub fn init_traps() {
     static INIT: Once = Once::new();
-    INIT.call_once(real_init);
+    POLICY_FROZEN.store(true, Ordering::SeqCst);
+    if signal_handler_policy() == SignalHandlerPolicy::Chain {
+        INIT.call_once(real_init);
+    }
+}
+
+/// Keeps the handlers installed while WebAssembly code runs, with
+/// [`SignalHandlerPolicy::Scoped`].
+struct ScopedSignalHandlers;
+
+lazy_static::lazy_static! {
+    /// The number of threads running WebAssembly code.  It's locked while
+    /// installing or restoring the process-wide handlers, so a thread never
+    /// runs with the handlers being restored by another.
+    static ref SCOPED_USERS: Mutex<usize> = Mutex::new(0);
+}
+
+impl ScopedSignalHandlers {
+    fn enter() -> Option<Self> {
+        if signal_handler_policy() != SignalHandlerPolicy::Scoped {
+            return None;
+        }
+        let mut users = SCOPED_USERS.lock().unwrap_or_else(|e| e.into_inner());
+        if *users == 0 {
+            unsafe { platform_init() };
+        }
+        *users += 1;
+        Some(Self)
+    }
+}
+
+impl Drop for ScopedSignalHandlers {
+    fn drop(&mut self) {
+        let mut users = SCOPED_USERS.lock().unwrap_or_else(|e| e.into_inner());
+        *users -= 1;
+        if *users == 0 {
+            unsafe { platform_uninit() };
+        }
+    }
 }
 
 fn real_init() {
@@ -299,6 +454,34 @@ fn real_init() {
     }
 }
 
+static BREAK_ON_TRAP: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether traps stop in the debugger attached to the process.
+///
+/// When enabled, every trap of the wasm code raises a `SIGTRAP` (a
+/// breakpoint exception on Windows) from the trapping frame, before the
+/// stack is unwound, so an attached debugger stops there. Without a
+/// debugger attached, this kills the process. The errors returned by the
+/// host functions and the libcalls don't stop.
+pub fn set_break_on_trap(enabled: bool) {
+    BREAK_ON_TRAP.store(enabled, Ordering::SeqCst);
+}
+
+/// Stops in the debugger, if [`set_break_on_trap`] is enabled.
+fn break_on_trap() {
+    if !BREAK_ON_TRAP.load(Ordering::SeqCst) {
+        return;
+    }
+    #[cfg(unix)]
+    unsafe {
+        libc::raise(libc::SIGTRAP);
+    }
+    #[cfg(target_os = "windows")]
+    unsafe {
+        winapi::um::debugapi::DebugBreak();
+    }
+}
+
 /// Raises a user-defined trap immediately.
 ///
 /// This function performs as-if a wasm trap was just executed, only the trap
@@ -416,6 +599,8 @@ impl Trap {
 /// Call the wasm function pointed to by `callee`.
 ///
 /// * `vmctx` - the callee vmctx argument
+/// * `instance` - the instance defining the callee, charged the CPU time of
+///   the call; `None` if the callee isn't a function of an instance
 /// * `caller_vmctx` - the caller vmctx argument
 /// * `trampoline` - the jit-generated trampoline whose ABI takes 4 values, the
 ///   callee vmctx, the caller vmctx, the `callee` argument below, and then the
@@ -430,11 +615,12 @@ impl Trap {
 /// function pointers.
 pub unsafe fn wasmer_call_trampoline(
     vmctx: *mut VMContext,
+    instance: Option<&InstanceHandle>,
     trampoline: VMTrampoline,
     callee: *const VMFunctionBody,
     values_vec: *mut u8,
 ) -> Result<(), Trap> {
-    catch_traps(vmctx, || {
+    catch_traps(vmctx, instance, || {
         mem::transmute::<_, extern "C" fn(*mut VMContext, *const VMFunctionBody, *mut u8)>(
             trampoline,
         )(vmctx, callee, values_vec)
@@ -447,7 +633,11 @@ pub unsafe fn wasmer_call_trampoline(
 /// # Safety
 ///
 /// Highly unsafe since `closure` won't have any destructors run.
-pub unsafe fn catch_traps<F>(vmctx: *mut VMContext, mut closure: F) -> Result<(), Trap>
+pub unsafe fn catch_traps<F>(
+    vmctx: *mut VMContext,
+    instance: Option<&InstanceHandle>,
+    mut closure: F,
+) -> Result<(), Trap>
 where
     F: FnMut(),
 {
@@ -455,7 +645,9 @@ where
     #[cfg(unix)]
     setup_unix_sigaltstack()?;
 
-    return CallThreadState::new(vmctx).with(|cx| {
+    let _handlers = ScopedSignalHandlers::enter();
+
+    return CallThreadState::new(vmctx, instance).with(|cx| {
         RegisterSetjmp(
             cx.jmp_buf.as_ptr(),
             call_closure::<F>,
@@ -482,13 +674,14 @@ where
 /// Check [`catch_traps`].
 pub unsafe fn catch_traps_with_result<F, R>(
     vmctx: *mut VMContext,
+    instance: Option<&InstanceHandle>,
     mut closure: F,
 ) -> Result<R, Trap>
 where
     F: FnMut() -> R,
 {
     let mut global_results = mem::MaybeUninit::<R>::uninit();
-    catch_traps(vmctx, || {
+    catch_traps(vmctx, instance, || {
         global_results.as_mut_ptr().write(closure());
     })?;
     Ok(global_results.assume_init())
@@ -502,7 +695,12 @@ pub struct CallThreadState {
     reset_guard_page: Cell<bool>,
     prev: Option<*const CallThreadState>,
     vmctx: *mut VMContext,
+    /// The instance charged the CPU time of the call, null if there's none
+    instance: *const Instance,
     handling_trap: Cell<bool>,
+    /// The CPU time of the thread when the call last started running,
+    /// either when it was entered or when a nested call returned.
+    cpu_start: Cell<Duration>,
 }
 
 enum UnwindReason {
@@ -518,21 +716,35 @@ enum UnwindReason {
 }
 
 impl CallThreadState {
-    fn new(vmctx: *mut VMContext) -> Self {
+    fn new(vmctx: *mut VMContext, instance: Option<&InstanceHandle>) -> Self {
         Self {
             unwind: Cell::new(UnwindReason::None),
             vmctx,
+            instance: instance.map_or(ptr::null(), |handle| handle.instance() as *const _),
             jmp_buf: Cell::new(ptr::null()),
             reset_guard_page: Cell::new(false),
             prev: None,
             handling_trap: Cell::new(false),
+            cpu_start: Cell::new(Duration::default()),
         }
     }
 
     fn with(mut self, closure: impl FnOnce(&Self) -> i32) -> Result<(), Trap> {
         tls::with(|prev| {
             self.prev = prev.map(|p| p as *const _);
+            // The CPU time of a nested call is charged to its own instance,
+            // not to the instance of the call around it.
+            let start = thread_cpu_time();
+            if let Some(prev) = prev {
+                prev.charge_cpu_time(start);
+            }
+            self.cpu_start.set(start);
             let ret = tls::set(&self, || closure(&self));
+            let end = thread_cpu_time();
+            self.charge_cpu_time(end);
+            if let Some(prev) = prev {
+                prev.cpu_start.set(end);
+            }
             match self.unwind.replace(UnwindReason::None) {
                 UnwindReason::None => {
                     debug_assert_eq!(ret, 1);
@@ -559,6 +771,16 @@ impl CallThreadState {
         })
     }
 
+    /// Adds the CPU time used since `cpu_start` to the instance of the call,
+    /// if any.
+    fn charge_cpu_time(&self, now: Duration) {
+        let used = now.checked_sub(self.cpu_start.get()).unwrap_or_default();
+        // the instance outlives the call
+        if let Some(instance) = unsafe { self.instance.as_ref() } {
+            instance.add_cpu_time(used);
+        }
+    }
+
     fn any_instance(&self, func: impl Fn(&InstanceHandle) -> bool) -> bool {
         unsafe {
             if func(&InstanceHandle::from_vmctx(self.vmctx)) {
@@ -640,6 +862,7 @@ impl CallThreadState {
             self.handling_trap.set(false);
             return ptr::null();
         }
+        break_on_trap();
         let backtrace = Backtrace::new_unresolved();
         self.reset_guard_page.set(reset_guard_page);
         self.unwind.replace(UnwindReason::RuntimeTrap {
@@ -700,6 +923,47 @@ mod tls {
     }
 }
 
+/// Returns the CPU time used by the current thread.
+#[cfg(unix)]
+fn thread_cpu_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) } != 0 {
+        return Duration::default();
+    }
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+/// Returns the CPU time used by the current thread.
+#[cfg(target_os = "windows")]
+fn thread_cpu_time() -> Duration {
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::processthreadsapi::{GetCurrentThread, GetThreadTimes};
+
+    let mut creation: FILETIME = unsafe { mem::zeroed() };
+    let mut exit: FILETIME = unsafe { mem::zeroed() };
+    let mut kernel: FILETIME = unsafe { mem::zeroed() };
+    let mut user: FILETIME = unsafe { mem::zeroed() };
+    let ok = unsafe {
+        GetThreadTimes(
+            GetCurrentThread(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+    };
+    if ok == 0 {
+        return Duration::default();
+    }
+    // in units of 100 nanoseconds
+    let ticks =
+        |time: &FILETIME| u64::from(time.dwHighDateTime) << 32 | u64::from(time.dwLowDateTime);
+    Duration::from_nanos((ticks(&kernel) + ticks(&user)) * 100)
+}
+
 /// A module for registering a custom alternate signal stack (sigaltstack).
 ///
 /// Rust's libstd installs an alternate stack with size `SIGSTKSZ`, which is not
@@ -12,6 +12,7 @@ use crate::trap::{Trap, TrapCode};
 use std::any::Any;
 use std::convert::TryFrom;
 use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::u32;
 
@@ -421,6 +422,79 @@ mod test_vmtable_definition {
     }
 }
 
+/// The epoch counter and deadline shared by every instance of a store.
+///
+/// Compiled code loads both fields at loop headers and traps with
+/// `TrapCode::Interrupt` once `current` reaches `deadline`. The embedder
+/// advances `current` from any thread.
+#[derive(Debug)]
+#[repr(C)]
+pub struct VMEpoch {
+    /// The current epoch.
+    pub current: AtomicU64,
+
+    /// The epoch at which running code is interrupted.
+    pub deadline: AtomicU64,
+}
+
+impl VMEpoch {
+    /// Advances the current epoch by one.
+    pub fn increment(&self) {
+        self.current.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Interrupts running code once `ticks` more epochs have elapsed.
+    pub fn set_deadline(&self, ticks: u64) {
+        let current = self.current.load(Ordering::Relaxed);
+        self.deadline
+            .store(current.saturating_add(ticks), Ordering::Relaxed);
+    }
+
+    /// Returns the epoch at which running code is interrupted.
+    pub fn deadline(&self) -> u64 {
+        self.deadline.load(Ordering::Relaxed)
+    }
+
+    /// Interrupts running code once the epoch `deadline` is reached, e.g.
+    /// to restore a deadline returned by [`VMEpoch::deadline`].
+    pub fn set_deadline_at(&self, deadline: u64) {
+        self.deadline.store(deadline, Ordering::Relaxed);
+    }
+}
+
+impl Default for VMEpoch {
+    /// An epoch whose deadline is never reached.
+    fn default() -> Self {
+        Self {
+            current: AtomicU64::new(0),
+            deadline: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_vmepoch {
+    use super::VMEpoch;
+    use crate::{ModuleInfo, VMOffsets};
+    use memoffset::offset_of;
+    use std::mem::size_of;
+
+    #[test]
+    fn check_vmepoch_offsets() {
+        let module = ModuleInfo::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+        assert_eq!(size_of::<VMEpoch>(), usize::from(offsets.size_of_vmepoch()));
+        assert_eq!(
+            offset_of!(VMEpoch, current),
+            usize::from(offsets.vmepoch_current())
+        );
+        assert_eq!(
+            offset_of!(VMEpoch, deadline),
+            usize::from(offsets.vmepoch_deadline())
+        );
+    }
+}
+
 /// The storage for a WebAssembly global defined within the instance.
 ///
 /// TODO: Pack the globals more densely, rather than using the same size
@@ -10,6 +10,7 @@ use crate::module::ModuleInfo;
 use crate::VMBuiltinFunctionIndex;
 use more_asserts::assert_lt;
 use std::convert::TryFrom;
+use wasmer_types::entity::EntityRef;
 use wasmer_types::{
     FunctionIndex, GlobalIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
     SignatureIndex, TableIndex,
@@ -199,6 +200,28 @@ impl VMOffsets {
     }
 }
 
+/// Offsets for [`VMEpoch`].
+///
+/// [`VMEpoch`]: crate::vmcontext::VMEpoch
+impl VMOffsets {
+    /// The offset of the `current` field.
+    pub const fn vmepoch_current(&self) -> u8 {
+        0
+    }
+
+    /// The offset of the `deadline` field.
+    pub const fn vmepoch_deadline(&self) -> u8 {
+        8
+    }
+
+    /// Return the size of [`VMEpoch`].
+    ///
+    /// [`VMEpoch`]: crate::vmcontext::VMEpoch
+    pub const fn size_of_vmepoch(&self) -> u8 {
+        16
+    }
+}
+
 /// Offsets for [`VMMemoryImport`].
 ///
 /// [`VMMemoryImport`]: crate::vmcontext::VMMemoryImport
@@ -434,10 +457,10 @@ impl VMOffsets {
             .unwrap()
     }
 
-    /// Return the size of the [`VMContext`] allocation.
+    /// The offset of the pointer to the store's [`VMEpoch`].
     ///
-    /// [`VMContext`]: crate::vmcontext::VMContext
-    pub fn size_of_vmctx(&self) -> u32 {
+    /// [`VMEpoch`]: crate::vmcontext::VMEpoch
+    pub fn vmctx_epoch(&self) -> u32 {
         self.vmctx_builtin_functions_begin()
             .checked_add(
                 VMBuiltinFunctionIndex::builtin_functions_total_number()
@@ -447,6 +470,15 @@ impl VMOffsets {
             .unwrap()
     }
 
+    /// Return the size of the [`VMContext`] allocation.
+    ///
+    /// [`VMContext`]: crate::vmcontext::VMContext
+    pub fn size_of_vmctx(&self) -> u32 {
+        self.vmctx_epoch()
+            .checked_add(u32::from(self.pointer_size))
+            .unwrap()
+    }
+
     /// Return the offset to [`VMSharedSignatureIndex`] index `index`.
     ///
     /// [`VMSharedSignatureIndex`]: crate::vmcontext::VMSharedSignatureIndex
@@ -666,6 +698,139 @@ impl VMOffsets {
     }
 }
 
+/// The version of the layout described by [`VMContextLayout`], bumped
+/// whenever the layout of a [`VMContext`] or of the structs it holds
+/// changes.
+///
+/// [`VMContext`]: crate::vmcontext::VMContext
+pub const VMCONTEXT_LAYOUT_VERSION: u32 = 1;
+
+/// Fails to compile unless the layout described by [`VMContextLayout`]
+/// has the given version, so that the accessors generated from a
+/// layout are regenerated when it changes.
+///
+/// ```
+/// wasmer_vm::assert_vmcontext_layout_version!(1);
+/// ```
+#[macro_export]
+macro_rules! assert_vmcontext_layout_version {
+    ($version:expr) => {
+        const _: [(); 1] = [(); ($crate::VMCONTEXT_LAYOUT_VERSION == $version) as usize];
+    };
+}
+
+/// A description of where the memories, tables and globals of an
+/// instance of a module are in its [`VMContext`], for embedders
+/// accessing them directly, e.g. from generated code.
+///
+/// All the offsets are in bytes. The offsets in the `VMContext` are
+/// relative to the pointer to the `VMContext`, and the field offsets
+/// are relative to the struct holding the field.
+///
+/// The layout isn't stable across versions of this crate: check
+/// [`VMContextLayout::version`] at runtime, or use
+/// [`assert_vmcontext_layout_version!`] at compile time.
+///
+/// [`VMContext`]: crate::vmcontext::VMContext
+/// [`assert_vmcontext_layout_version!`]: crate::assert_vmcontext_layout_version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VMContextLayout {
+    /// The version of the layout, see [`VMCONTEXT_LAYOUT_VERSION`].
+    pub version: u32,
+    /// The size in bytes of a pointer.
+    pub pointer_size: u8,
+    /// The size of the `VMContext`.
+    pub size: u32,
+    /// The offsets of the [`VMMemoryImport`]s of the imported memories.
+    ///
+    /// [`VMMemoryImport`]: crate::vmcontext::VMMemoryImport
+    pub imported_memories: Vec<u32>,
+    /// The offsets of the [`VMMemoryDefinition`]s of the memories
+    /// defined by the module.
+    ///
+    /// [`VMMemoryDefinition`]: crate::vmcontext::VMMemoryDefinition
+    pub memories: Vec<u32>,
+    /// The offsets of the [`VMTableImport`]s of the imported tables.
+    ///
+    /// [`VMTableImport`]: crate::vmcontext::VMTableImport
+    pub imported_tables: Vec<u32>,
+    /// The offsets of the [`VMTableDefinition`]s of the tables defined
+    /// by the module.
+    ///
+    /// [`VMTableDefinition`]: crate::vmcontext::VMTableDefinition
+    pub tables: Vec<u32>,
+    /// The offsets of the [`VMGlobalImport`]s of the imported globals.
+    ///
+    /// [`VMGlobalImport`]: crate::vmcontext::VMGlobalImport
+    pub imported_globals: Vec<u32>,
+    /// The offsets of the pointers to the [`VMGlobalDefinition`]s of
+    /// the globals defined by the module.
+    ///
+    /// [`VMGlobalDefinition`]: crate::vmcontext::VMGlobalDefinition
+    pub globals: Vec<u32>,
+    /// The offset of the pointer to the definition in a
+    /// `VMMemoryImport`.
+    pub memory_import_definition: u8,
+    /// The offset of the base pointer in a `VMMemoryDefinition`.
+    pub memory_definition_base: u8,
+    /// The offset of the length in bytes in a `VMMemoryDefinition`.
+    pub memory_definition_current_length: u8,
+    /// The offset of the pointer to the definition in a
+    /// `VMTableImport`.
+    pub table_import_definition: u8,
+    /// The offset of the base pointer in a `VMTableDefinition`.
+    pub table_definition_base: u8,
+    /// The offset of the number of elements in a `VMTableDefinition`.
+    pub table_definition_current_elements: u8,
+    /// The offset of the pointer to the definition in a
+    /// `VMGlobalImport`.
+    pub global_import_definition: u8,
+}
+
+impl VMContextLayout {
+    /// Describes the layout of the `VMContext` of the instances of
+    /// `module` on the host.
+    pub fn for_module(module: &ModuleInfo) -> Self {
+        let offsets = VMOffsets::new(std::mem::size_of::<*mut u8>() as u8, module);
+        Self {
+            version: VMCONTEXT_LAYOUT_VERSION,
+            pointer_size: offsets.pointer_size,
+            size: offsets.size_of_vmctx(),
+            imported_memories: (0..module.num_imported_memories)
+                .map(|index| offsets.vmctx_vmmemory_import(MemoryIndex::new(index)))
+                .collect(),
+            memories: (0..module.memories.len() - module.num_imported_memories)
+                .map(|index| offsets.vmctx_vmmemory_definition(LocalMemoryIndex::new(index)))
+                .collect(),
+            imported_tables: (0..module.num_imported_tables)
+                .map(|index| offsets.vmctx_vmtable_import(TableIndex::new(index)))
+                .collect(),
+            tables: (0..module.tables.len() - module.num_imported_tables)
+                .map(|index| offsets.vmctx_vmtable_definition(LocalTableIndex::new(index)))
+                .collect(),
+            imported_globals: (0..module.num_imported_globals)
+                .map(|index| offsets.vmctx_vmglobal_import(GlobalIndex::new(index)))
+                .collect(),
+            globals: (0..module.globals.len() - module.num_imported_globals)
+                .map(|index| offsets.vmctx_vmglobal_definition(LocalGlobalIndex::new(index)))
+                .collect(),
+            memory_import_definition: offsets.vmmemory_import_definition(),
+            memory_definition_base: offsets.vmmemory_definition_base(),
+            memory_definition_current_length: offsets.vmmemory_definition_current_length(),
+            table_import_definition: offsets.vmtable_import_definition(),
+            table_definition_base: offsets.vmtable_definition_base(),
+            table_definition_current_elements: offsets.vmtable_definition_current_elements(),
+            global_import_definition: offsets.vmglobal_import_definition(),
+        }
+    }
+
+    /// Returns whether the layout has the version expected by the
+    /// caller, e.g. the version its accessors were generated from.
+    pub fn is_version(&self, version: u32) -> bool {
+        self.version == version
+    }
+}
+
 /// Target specific type for shared signature index.
 #[derive(Debug, Copy, Clone)]
 pub struct TargetSharedSignatureIndex(u32);
@@ -0,0 +1,475 @@
+//! Recording and replaying of WASI syscalls.
+//!
+//! A [`Journal`] in record mode logs the name, arguments, return value and
+//! every byte of guest memory modified by each syscall.  A journal opened
+//! for replay serves those results back in order without calling into the
+//! real syscall implementations, so a recorded run can be reproduced
+//! deterministically without touching the host.
+//!
+//! Attach a journal with [`WasiEnv::set_journal`] before generating the
+//! import object.
+
+use crate::syscalls::types::*;
+use crate::{WasiEnv, WasiError};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use thiserror::Error;
+use wasmer::RuntimeError;
+
+/// Error type for journal creation and recording.
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("journal I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("journal is malformed: {0}")]
+    Malformed(String),
+    #[error("WASI replay diverged from the journal: {0}")]
+    Diverged(String),
+}
+
+/// A single recorded syscall.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct JournalEntry {
+    /// The name of the syscall.
+    name: String,
+    /// The arguments of the syscall, in their `Debug` representation.
+    args: Vec<String>,
+    /// The returned errno, `None` for syscalls that don't return.
+    errno: Option<__wasi_errno_t>,
+    /// Ranges of guest memory written by the syscall, as `(offset, bytes)`.
+    writes: Vec<(u32, Vec<u8>)>,
+}
+
+enum JournalMode {
+    Record(Box<dyn Write + Send>),
+    Replay(VecDeque<JournalEntry>),
+}
+
+/// A log of syscalls, either being recorded or being replayed.
+pub struct Journal {
+    mode: JournalMode,
+}
+
+impl fmt::Debug for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.mode {
+            JournalMode::Record(_) => f.debug_struct("Journal").field("mode", &"record").finish(),
+            JournalMode::Replay(entries) => f
+                .debug_struct("Journal")
+                .field("mode", &"replay")
+                .field("remaining", &entries.len())
+                .finish(),
+        }
+    }
+}
+
+impl Journal {
+    /// Create a journal that records every syscall to `sink`.
+    ///
+    /// Entries are flushed as they are written, so the log stays usable
+    /// even if the guest exits or traps.  Recording copies the guest memory
+    /// around every syscall and is meant for debugging, not production use.
+    pub fn record<W: Write + Send + 'static>(sink: W) -> Self {
+        Self {
+            mode: JournalMode::Record(Box::new(sink)),
+        }
+    }
+
+    /// Create a journal that replays the syscalls previously recorded to
+    /// `source`.
+    pub fn replay<R: Read>(mut source: R) -> Result<Self, JournalError> {
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+        let mut cursor = &bytes[..];
+        let mut entries = VecDeque::new();
+        while !cursor.is_empty() {
+            let entry = bincode::deserialize_from(&mut cursor)
+                .map_err(|e| JournalError::Malformed(e.to_string()))?;
+            entries.push_back(entry);
+        }
+        Ok(Self {
+            mode: JournalMode::Replay(entries),
+        })
+    }
+
+    /// Whether this journal is replaying a previous recording.
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, JournalMode::Replay(_))
+    }
+
+    fn append(&mut self, entry: &JournalEntry) -> Result<(), JournalError> {
+        if let JournalMode::Record(sink) = &mut self.mode {
+            bincode::serialize_into(&mut *sink, entry)
+                .map_err(|e| JournalError::Malformed(e.to_string()))?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Take the next recorded entry, checking it matches the syscall the
+    /// guest is making now.
+    fn next_entry(&mut self, name: &str, args: &[String]) -> Result<JournalEntry, JournalError> {
+        let entry = match &mut self.mode {
+            JournalMode::Replay(entries) => entries.pop_front(),
+            JournalMode::Record(_) => unreachable!("next_entry called while recording"),
+        };
+        match entry {
+            Some(entry) if entry.name == name && entry.args == args => Ok(entry),
+            Some(entry) => Err(JournalError::Diverged(format!(
+                "expected {}({}), got {}({})",
+                entry.name,
+                entry.args.join(", "),
+                name,
+                args.join(", ")
+            ))),
+            None => Err(JournalError::Diverged(format!(
+                "journal exhausted at {}({})",
+                name,
+                args.join(", ")
+            ))),
+        }
+    }
+
+    /// Run the syscall `f` through the journal: record its effects, or
+    /// replay them instead of calling it.
+    pub(crate) fn dispatch<F>(
+        journal: &Mutex<Journal>,
+        env: &mut WasiEnv,
+        name: &str,
+        args: Vec<String>,
+        f: F,
+    ) -> __wasi_errno_t
+    where
+        F: FnOnce(&mut WasiEnv) -> __wasi_errno_t,
+    {
+        if journal.lock().unwrap().is_replaying() {
+            // The lock must be released before raising, as the trap doesn't unwind.
+            let entry = journal.lock().unwrap().next_entry(name, &args);
+            let entry =
+                entry.unwrap_or_else(|e| RuntimeError::raise(Box::new(WasiError::Journal(e))));
+            let memory = unsafe { env.memory().data_unchecked_mut() };
+            for (offset, bytes) in &entry.writes {
+                let start = *offset as usize;
+                match memory.get_mut(start..start + bytes.len()) {
+                    Some(dst) => dst.copy_from_slice(bytes),
+                    None => return __WASI_EFAULT,
+                }
+            }
+            return entry.errno.unwrap_or(__WASI_ESUCCESS);
+        }
+
+        let before = unsafe { env.memory().data_unchecked() }.to_vec();
+        let errno = f(env);
+        let after = unsafe { env.memory().data_unchecked() };
+        let entry = JournalEntry {
+            name: name.to_string(),
+            args,
+            errno: Some(errno),
+            writes: memory_diff(&before, after),
+        };
+        if let Err(e) = journal.lock().unwrap().append(&entry) {
+            tracing::warn!("wasi::journal: failed to record {}: {}", name, e);
+        }
+        errno
+    }
+
+    /// Journal a syscall that doesn't return, such as `proc_exit`.
+    ///
+    /// The entry is written (or checked) before the syscall runs; the
+    /// syscall itself is always called since it only unwinds the guest.
+    pub(crate) fn dispatch_noreturn<F>(
+        journal: &Mutex<Journal>,
+        env: &mut WasiEnv,
+        name: &str,
+        args: Vec<String>,
+        f: F,
+    ) where
+        F: FnOnce(&mut WasiEnv),
+    {
+        let replayed = {
+            let mut journal = journal.lock().unwrap();
+            if journal.is_replaying() {
+                journal.next_entry(name, &args).map(|_| ())
+            } else {
+                let entry = JournalEntry {
+                    name: name.to_string(),
+                    args,
+                    errno: None,
+                    writes: vec![],
+                };
+                if let Err(e) = journal.append(&entry) {
+                    tracing::warn!("wasi::journal: failed to record {}: {}", name, e);
+                }
+                Ok(())
+            }
+        };
+        if let Err(e) = replayed {
+            RuntimeError::raise(Box::new(WasiError::Journal(e)));
+        }
+        f(env)
+    }
+}
+
+/// Compute the ranges of `after` that differ from `before`.
+fn memory_diff(before: &[u8], after: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut writes = vec![];
+    let mut i = 0;
+    while i < after.len() {
+        if before.get(i) == Some(&after[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < after.len() && before.get(i) != Some(&after[i]) {
+            i += 1;
+        }
+        writes.push((start as u32, after[start..i].to_vec()));
+    }
+    writes
+}
+
+/// Generate wrappers around syscalls that go through the journal attached
+/// to the `WasiEnv`, if any, and record their metrics if they're enabled.
+///
+/// Host functions must be zero-sized, so each wrapper is a plain function
+/// calling its syscall by path rather than a closure capturing it.
+macro_rules! journaled_syscalls {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) => $target:path;)*) => {
+        /// The names of the syscalls, as recorded in the metrics.
+        #[allow(dead_code)]
+        pub(crate) const NAMES: &[&str] = &[$(stringify!($name)),*];
+
+        $(
+            pub fn $name(env: &mut WasiEnv, $($arg: $ty),*) -> __wasi_errno_t {
+                let start = env.metrics.as_ref().map(|_| Instant::now());
+                let errno = match env.journal.clone() {
+                    None => $target(env, $($arg),*),
+                    Some(journal) => {
+                        let args = vec![$(format!("{:?}", $arg)),*];
+                        Journal::dispatch(&journal, env, stringify!($name), args, move |env| {
+                            $target(env, $($arg),*)
+                        })
+                    }
+                };
+                if let (Some(metrics), Some(start)) = (&env.metrics, start) {
+                    metrics.record(stringify!($name), start.elapsed(), errno);
+                }
+                errno
+            }
+        )*
+    };
+}
+
+/// Journaled wrappers for every syscall, as exposed to the guest.
+pub(crate) mod syscalls {
+    use super::Journal;
+    use crate::ptr::{Array, WasmPtr};
+    use crate::syscalls;
+    use crate::syscalls::types::*;
+    use crate::WasiEnv;
+    use std::time::Instant;
+
+    journaled_syscalls! {
+        fn args_get(argv: WasmPtr<WasmPtr<u8, Array>, Array>, argv_buf: WasmPtr<u8, Array>) => syscalls::args_get;
+        fn args_sizes_get(argc: WasmPtr<u32>, argv_buf_size: WasmPtr<u32>) => syscalls::args_sizes_get;
+        fn clock_res_get(clock_id: __wasi_clockid_t, resolution: WasmPtr<__wasi_timestamp_t>) => syscalls::clock_res_get;
+        fn clock_time_get(clock_id: __wasi_clockid_t, precision: __wasi_timestamp_t, time: WasmPtr<__wasi_timestamp_t>) => syscalls::clock_time_get;
+        fn environ_get(environ: WasmPtr<WasmPtr<u8, Array>, Array>, environ_buf: WasmPtr<u8, Array>) => syscalls::environ_get;
+        fn environ_sizes_get(environ_count: WasmPtr<u32>, environ_buf_size: WasmPtr<u32>) => syscalls::environ_sizes_get;
+        fn fd_advise(fd: __wasi_fd_t, offset: __wasi_filesize_t, len: __wasi_filesize_t, advice: __wasi_advice_t) => syscalls::fd_advise;
+        fn fd_allocate(fd: __wasi_fd_t, offset: __wasi_filesize_t, len: __wasi_filesize_t) => syscalls::fd_allocate;
+        fn fd_close(fd: __wasi_fd_t) => syscalls::fd_close;
+        fn fd_datasync(fd: __wasi_fd_t) => syscalls::fd_datasync;
+        fn fd_fdstat_get(fd: __wasi_fd_t, buf_ptr: WasmPtr<__wasi_fdstat_t>) => syscalls::fd_fdstat_get;
+        fn fd_fdstat_set_flags(fd: __wasi_fd_t, flags: __wasi_fdflags_t) => syscalls::fd_fdstat_set_flags;
+        fn fd_fdstat_set_rights(fd: __wasi_fd_t, fs_rights_base: __wasi_rights_t, fs_rights_inheriting: __wasi_rights_t) => syscalls::fd_fdstat_set_rights;
+        fn fd_filestat_get(fd: __wasi_fd_t, buf: WasmPtr<__wasi_filestat_t>) => syscalls::fd_filestat_get;
+        fn fd_filestat_set_size(fd: __wasi_fd_t, st_size: __wasi_filesize_t) => syscalls::fd_filestat_set_size;
+        fn fd_filestat_set_times(fd: __wasi_fd_t, st_atim: __wasi_timestamp_t, st_mtim: __wasi_timestamp_t, fst_flags: __wasi_fstflags_t) => syscalls::fd_filestat_set_times;
+        fn fd_pread(fd: __wasi_fd_t, iovs: WasmPtr<__wasi_iovec_t, Array>, iovs_len: u32, offset: __wasi_filesize_t, nread: WasmPtr<u32>) => syscalls::fd_pread;
+        fn fd_prestat_get(fd: __wasi_fd_t, buf: WasmPtr<__wasi_prestat_t>) => syscalls::fd_prestat_get;
+        fn fd_prestat_dir_name(fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32) => syscalls::fd_prestat_dir_name;
+        fn fd_pwrite(fd: __wasi_fd_t, iovs: WasmPtr<__wasi_ciovec_t, Array>, iovs_len: u32, offset: __wasi_filesize_t, nwritten: WasmPtr<u32>) => syscalls::fd_pwrite;
+        fn fd_read(fd: __wasi_fd_t, iovs: WasmPtr<__wasi_iovec_t, Array>, iovs_len: u32, nread: WasmPtr<u32>) => syscalls::fd_read;
+        fn fd_readdir(fd: __wasi_fd_t, buf: WasmPtr<u8, Array>, buf_len: u32, cookie: __wasi_dircookie_t, bufused: WasmPtr<u32>) => syscalls::fd_readdir;
+        fn fd_renumber(from: __wasi_fd_t, to: __wasi_fd_t) => syscalls::fd_renumber;
+        fn fd_seek(fd: __wasi_fd_t, offset: __wasi_filedelta_t, whence: __wasi_whence_t, newoffset: WasmPtr<__wasi_filesize_t>) => syscalls::fd_seek;
+        fn fd_sync(fd: __wasi_fd_t) => syscalls::fd_sync;
+        fn fd_tell(fd: __wasi_fd_t, offset: WasmPtr<__wasi_filesize_t>) => syscalls::fd_tell;
+        fn fd_write(fd: __wasi_fd_t, iovs: WasmPtr<__wasi_ciovec_t, Array>, iovs_len: u32, nwritten: WasmPtr<u32>) => syscalls::fd_write;
+        fn path_create_directory(fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32) => syscalls::path_create_directory;
+        fn path_filestat_get(fd: __wasi_fd_t, flags: __wasi_lookupflags_t, path: WasmPtr<u8, Array>, path_len: u32, buf: WasmPtr<__wasi_filestat_t>) => syscalls::path_filestat_get;
+        fn path_filestat_set_times(fd: __wasi_fd_t, flags: __wasi_lookupflags_t, path: WasmPtr<u8, Array>, path_len: u32, st_atim: __wasi_timestamp_t, st_mtim: __wasi_timestamp_t, fst_flags: __wasi_fstflags_t) => syscalls::path_filestat_set_times;
+        fn path_link(old_fd: __wasi_fd_t, old_flags: __wasi_lookupflags_t, old_path: WasmPtr<u8, Array>, old_path_len: u32, new_fd: __wasi_fd_t, new_path: WasmPtr<u8, Array>, new_path_len: u32) => syscalls::path_link;
+        fn path_open(dirfd: __wasi_fd_t, dirflags: __wasi_lookupflags_t, path: WasmPtr<u8, Array>, path_len: u32, o_flags: __wasi_oflags_t, fs_rights_base: __wasi_rights_t, fs_rights_inheriting: __wasi_rights_t, fs_flags: __wasi_fdflags_t, fd: WasmPtr<__wasi_fd_t>) => syscalls::path_open;
+        fn path_readlink(dir_fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32, buf: WasmPtr<u8, Array>, buf_len: u32, buf_used: WasmPtr<u32>) => syscalls::path_readlink;
+        fn path_remove_directory(fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32) => syscalls::path_remove_directory;
+        fn path_rename(old_fd: __wasi_fd_t, old_path: WasmPtr<u8, Array>, old_path_len: u32, new_fd: __wasi_fd_t, new_path: WasmPtr<u8, Array>, new_path_len: u32) => syscalls::path_rename;
+        fn path_symlink(old_path: WasmPtr<u8, Array>, old_path_len: u32, fd: __wasi_fd_t, new_path: WasmPtr<u8, Array>, new_path_len: u32) => syscalls::path_symlink;
+        fn path_unlink_file(fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32) => syscalls::path_unlink_file;
+        fn poll_oneoff(in_: WasmPtr<__wasi_subscription_t, Array>, out_: WasmPtr<__wasi_event_t, Array>, nsubscriptions: u32, nevents: WasmPtr<u32>) => syscalls::poll_oneoff;
+        fn proc_raise(sig: __wasi_signal_t) => syscalls::proc_raise;
+        fn random_get(buf: WasmPtr<u8, Array>, buf_len: u32) => syscalls::random_get;
+        fn sched_yield() => syscalls::sched_yield;
+        fn sock_recv(sock: __wasi_fd_t, ri_data: WasmPtr<__wasi_iovec_t, Array>, ri_data_len: u32, ri_flags: __wasi_riflags_t, ro_datalen: WasmPtr<u32>, ro_flags: WasmPtr<__wasi_roflags_t>) => syscalls::sock_recv;
+        fn sock_send(sock: __wasi_fd_t, si_data: WasmPtr<__wasi_ciovec_t, Array>, si_data_len: u32, si_flags: __wasi_siflags_t, so_datalen: WasmPtr<u32>) => syscalls::sock_send;
+        fn sock_shutdown(sock: __wasi_fd_t, how: __wasi_sdflags_t) => syscalls::sock_shutdown;
+        fn fs_watch(fd: __wasi_fd_t, path: WasmPtr<u8, Array>, path_len: u32, flags: __wasi_watchflags_t, watch_fd: WasmPtr<__wasi_fd_t>) => syscalls::fs_watch;
+        fn tty_get(fd: __wasi_fd_t, tty: WasmPtr<__wasi_tty_t>) => syscalls::tty_get;
+        fn tty_set(fd: __wasi_fd_t, tty: WasmPtr<__wasi_tty_t>) => syscalls::tty_set;
+    }
+
+    pub fn proc_exit(env: &mut WasiEnv, code: __wasi_exitcode_t) {
+        match env.journal.clone() {
+            None => syscalls::proc_exit(env, code),
+            Some(journal) => Journal::dispatch_noreturn(
+                &journal,
+                env,
+                "proc_exit",
+                vec![format!("{:?}", code)],
+                move |env| syscalls::proc_exit(env, code),
+            ),
+        }
+    }
+
+    pub mod legacy {
+        pub mod snapshot0 {
+            use super::super::Journal;
+            use crate::ptr::{Array, WasmPtr};
+            use crate::syscalls::legacy::snapshot0 as syscalls;
+            use crate::syscalls::types::snapshot0;
+            use crate::syscalls::types::*;
+            use crate::WasiEnv;
+
+            journaled_syscalls! {
+                fn fd_filestat_get(fd: __wasi_fd_t, buf: WasmPtr<snapshot0::__wasi_filestat_t>) => syscalls::fd_filestat_get;
+                fn fd_seek(fd: __wasi_fd_t, offset: __wasi_filedelta_t, whence: snapshot0::__wasi_whence_t, newoffset: WasmPtr<__wasi_filesize_t>) => syscalls::fd_seek;
+                fn path_filestat_get(fd: __wasi_fd_t, flags: __wasi_lookupflags_t, path: WasmPtr<u8, Array>, path_len: u32, buf: WasmPtr<snapshot0::__wasi_filestat_t>) => syscalls::path_filestat_get;
+                fn poll_oneoff(in_: WasmPtr<snapshot0::__wasi_subscription_t, Array>, out_: WasmPtr<__wasi_event_t, Array>, nsubscriptions: u32, nevents: WasmPtr<u32>) => syscalls::poll_oneoff;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{WasiRuntimeError, WasiState};
+    use std::sync::Arc;
+    use wasmer::{ExitStatus, Module, Store};
+
+    /// A sink shared with the test, to read back what was recorded.
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A command writing 16 random bytes at offset 0, then exiting with
+    /// `code`.
+    fn command(random_len: u32, code: u32) -> Module {
+        let wat = format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "random_get"
+                    (func $random_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")
+                    (drop (call $random_get (i32.const 0) (i32.const {})))
+                    (call $proc_exit (i32.const {}))))"#,
+            random_len, code
+        );
+        Module::new(&Store::default(), wat).unwrap()
+    }
+
+    /// Runs `module` with `journal`, returning how it ended and the
+    /// random bytes it got.
+    fn run(module: &Module, journal: Journal) -> (Result<ExitStatus, WasiRuntimeError>, Vec<u8>) {
+        let mut env = WasiState::new("command").finalize().unwrap();
+        env.set_journal(journal);
+        let status = env.run_command_with_status(module);
+        let random = env.memory().view::<u8>()[..16]
+            .iter()
+            .map(|cell| cell.get())
+            .collect();
+        (status, random)
+    }
+
+    fn recording() -> (Vec<u8>, Vec<u8>) {
+        let sink = SharedSink::default();
+        let (status, random) = run(&command(16, 7), Journal::record(sink.clone()));
+        assert_eq!(status.unwrap(), ExitStatus::exited(7));
+        let recorded = sink.0.lock().unwrap().clone();
+        (recorded, random)
+    }
+
+    fn divergence(result: Result<ExitStatus, WasiRuntimeError>) -> String {
+        match result {
+            Err(WasiRuntimeError::Wasi(WasiError::Journal(JournalError::Diverged(message)))) => {
+                message
+            }
+            other => panic!("the replay didn't diverge: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn replay_a_recording() {
+        let (recorded, random) = recording();
+        assert_ne!(random, vec![0; 16]);
+
+        let journal = Journal::replay(&recorded[..]).unwrap();
+        assert!(journal.is_replaying());
+        let (status, replayed) = run(&command(16, 7), journal);
+        assert_eq!(status.unwrap(), ExitStatus::exited(7));
+        assert_eq!(replayed, random);
+    }
+
+    #[test]
+    fn replay_diverging_arguments() {
+        let (recorded, _) = recording();
+        let journal = Journal::replay(&recorded[..]).unwrap();
+        let (status, replayed) = run(&command(8, 7), journal);
+        let message = divergence(status);
+        assert!(message.starts_with("expected random_get("), "{}", message);
+        // the syscall isn't run
+        assert_eq!(replayed, vec![0; 16]);
+    }
+
+    #[test]
+    fn replay_diverging_proc_exit() {
+        let (recorded, _) = recording();
+        let journal = Journal::replay(&recorded[..]).unwrap();
+        let message = divergence(run(&command(16, 8), journal).0);
+        assert_eq!(message, "expected proc_exit(7), got proc_exit(8)");
+
+        // a journal ending early
+        let journal = Journal::replay(&[][..]).unwrap();
+        let message = divergence(run(&command(16, 7), journal).0);
+        assert!(
+            message.starts_with("journal exhausted at random_get("),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn replay_malformed_journal() {
+        let (recorded, _) = recording();
+        let truncated = &recorded[..recorded.len() - 1];
+        assert!(matches!(
+            Journal::replay(truncated),
+            Err(JournalError::Malformed(_))
+        ));
+    }
+}
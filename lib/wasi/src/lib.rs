@@ -14,22 +14,36 @@
 
 #[macro_use]
 mod macros;
+mod journal;
+mod metrics;
 mod ptr;
 mod state;
 mod syscalls;
 mod utils;
 
-use crate::syscalls::*;
+use crate::journal::syscalls::*;
+use crate::metrics::WasiMetrics;
+
+pub use crate::journal::{Journal, JournalError};
+pub use crate::metrics::{MetricsSnapshot, SyscallMetrics, LATENCY_BUCKETS_US};
 
 pub use crate::state::{
-    Fd, WasiFile, WasiFs, WasiFsError, WasiState, WasiStateBuilder, WasiStateCreationError,
-    ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    serve_remote_fs, Device, Fd, FreshnessPolicy, ImageFile, InteractiveStdin,
+    InteractiveStdinHandle, IoLimits, LogOutput, LogStream, ObjectFile, ObjectStore, ObjectStoreFs,
+    PathPolicy, RemoteFile, RemoteFs, ThrottleMode, TtyPolicy, WasiFile, WasiFs,
+    WasiFsCreationError, WasiFsError, WasiFsImage, WasiState, WasiStateBuilder,
+    WasiStateCreationError, WatchFile, ALL_RIGHTS, DEFAULT_BLOCK_SIZE, VIRTUAL_ROOT_FD,
 };
 pub use crate::syscalls::types;
-pub use crate::utils::{get_wasi_version, is_wasi_module, WasiVersion};
+pub use crate::utils::{
+    get_wasi_exec_model, get_wasi_version, is_wasi_module, WasiExecModel, WasiVersion,
+};
 
 use thiserror::Error;
-use wasmer::{imports, Function, ImportObject, Memory, Module, Store};
+use wasmer::{
+    imports, ExitStatus, ExportError, Function, ImportObject, Instance, InstantiationError, Linker,
+    LinkerError, Memory, Module, RuntimeError, Store,
+};
 
 use std::cell::UnsafeCell;
 use std::fmt;
@@ -40,11 +54,43 @@ use std::sync::{Arc, Mutex, MutexGuard};
 /// This is returned in `RuntimeError`.
 /// Use `downcast` or `downcast_ref` to retrieve the `ExitCode`.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum WasiError {
     #[error("WASI exited with code: {0}")]
     Exit(syscalls::types::__wasi_exitcode_t),
     #[error("The WASI version could not be determined")]
     UnknownWasiVersion,
+    #[error(transparent)]
+    Journal(#[from] JournalError),
+}
+
+impl WasiError {
+    /// Returns a stable code identifying the kind of error, e.g. to
+    /// report it through FFI or in metrics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Exit(_) => "exit",
+            Self::UnknownWasiVersion => "unknown_wasi_version",
+            Self::Journal(_) => "journal",
+        }
+    }
+}
+
+/// An error while instantiating or running a WASI module.
+#[derive(Error, Debug)]
+pub enum WasiRuntimeError {
+    #[error(transparent)]
+    Wasi(#[from] WasiError),
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    #[error("The module is not a WASI command: it doesn't export `_start`")]
+    NotACommand,
+    #[error("The module is not a WASI reactor")]
+    NotAReactor,
 }
 
 /// The environment provided to the WASI imports.
@@ -52,6 +98,8 @@ pub enum WasiError {
 pub struct WasiEnv {
     state: Arc<Mutex<WasiState>>,
     memory: Arc<WasiMemory>,
+    journal: Option<Arc<Mutex<Journal>>>,
+    metrics: Option<Arc<WasiMetrics>>,
 }
 
 /// Wrapper type around `Memory` used to delay initialization of the memory.
@@ -65,6 +113,12 @@ struct WasiMemory {
     mutate_lock: Mutex<()>,
 }
 
+// SAFETY: `memory` is only written once, under `mutate_lock`, before
+// `initialized` is set; afterwards it is only read, and `Memory` itself is
+// `Send + Sync`.
+unsafe impl Send for WasiMemory {}
+unsafe impl Sync for WasiMemory {}
+
 impl fmt::Debug for WasiMemory {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("WasiMemory")
@@ -138,12 +192,23 @@ impl Drop for WasiMemory {
 
 impl WasiEnv {
     pub fn new(state: WasiState) -> Self {
+        let metrics = state.metrics.clone();
         Self {
             state: Arc::new(Mutex::new(state)),
             memory: Arc::new(WasiMemory::new()),
+            journal: None,
+            metrics,
         }
     }
 
+    /// Record syscalls to, or replay them from, the given [`Journal`].
+    ///
+    /// This must be called before generating the import object, as the
+    /// imports capture a copy of the environment.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(Arc::new(Mutex::new(journal)));
+    }
+
     pub fn import_object(&mut self, module: &Module) -> Result<ImportObject, WasiError> {
         let wasi_version = get_wasi_version(module, false).ok_or(WasiError::UnknownWasiVersion)?;
         Ok(generate_import_object_from_env(
@@ -153,6 +218,88 @@ impl WasiEnv {
         ))
     }
 
+    /// Instantiate a WASI command, and run it by calling its `_start`
+    /// export. Returns its exit code, given to `proc_exit` or 0.
+    pub fn run_command(
+        &mut self,
+        module: &Module,
+    ) -> Result<syscalls::types::__wasi_exitcode_t, WasiRuntimeError> {
+        if get_wasi_exec_model(module) != Some(WasiExecModel::Command) {
+            return Err(WasiRuntimeError::NotACommand);
+        }
+        let instance = self.instantiate(module)?;
+        let start = instance.exports.get_function("_start")?;
+        match start.call(&[]) {
+            Ok(_) => Ok(0),
+            Err(err) => match err.downcast::<WasiError>() {
+                Ok(WasiError::Exit(exit_code)) => Ok(exit_code),
+                Ok(err) => Err(err.into()),
+                Err(err) => Err(err.into()),
+            },
+        }
+    }
+
+    /// Instantiate a WASI command, and run it by calling its `_start`
+    /// export, like [`WasiEnv::run_command`]. Returns how it ended,
+    /// including when it trapped, with the message of the Rust panic it
+    /// wrote to stderr, if any.
+    pub fn run_command_with_status(
+        &mut self,
+        module: &Module,
+    ) -> Result<ExitStatus, WasiRuntimeError> {
+        if get_wasi_exec_model(module) != Some(WasiExecModel::Command) {
+            return Err(WasiRuntimeError::NotACommand);
+        }
+        let instance = self.instantiate(module)?;
+        let start = instance.exports.get_function("_start")?;
+        let result = start.call(&[]).map(|_| ());
+        self.exit_status(result)
+    }
+
+    /// Returns how the program ended given the `result` of calling its
+    /// entry point, with the message of the Rust panic it wrote to
+    /// stderr, if any.
+    ///
+    /// The errors which aren't raised by the program, e.g. from a
+    /// diverging journal, are returned as is.
+    pub fn exit_status(
+        &self,
+        result: Result<(), RuntimeError>,
+    ) -> Result<ExitStatus, WasiRuntimeError> {
+        let status = match result {
+            Ok(()) => ExitStatus::exited(0),
+            Err(error) => match error.downcast::<WasiError>() {
+                Ok(WasiError::Exit(code)) => ExitStatus::exited(code as i32),
+                Ok(error) => return Err(error.into()),
+                Err(error) => ExitStatus::trapped(error.message()),
+            },
+        };
+        Ok(status.with_panic_message(&self.state().stderr_capture))
+    }
+
+    /// Instantiate a WASI reactor, and initialize it by calling its
+    /// `_initialize` export, if any. Its other exports can be called
+    /// afterwards.
+    pub fn initialize_reactor(&mut self, module: &Module) -> Result<Instance, WasiRuntimeError> {
+        if get_wasi_exec_model(module) != Some(WasiExecModel::Reactor) {
+            return Err(WasiRuntimeError::NotAReactor);
+        }
+        let instance = self.instantiate(module)?;
+        if let Ok(initialize) = instance.exports.get_function("_initialize") {
+            initialize.call(&[])?;
+        }
+        Ok(instance)
+    }
+
+    fn instantiate(&mut self, module: &Module) -> Result<Instance, WasiRuntimeError> {
+        let import_object = self.import_object(module)?;
+        let instance = Instance::new(module, &import_object)?;
+        // The memory must be set before calling any export, since the
+        // syscalls access it.
+        self.set_memory(instance.exports.get_memory("memory")?.clone());
+        Ok(instance)
+    }
+
     /// Set the memory
     pub fn set_memory(&mut self, memory: Memory) -> bool {
         self.memory.set_memory(memory)
@@ -183,6 +330,16 @@ impl WasiEnv {
     }
 }
 
+// The state is shared behind a mutex, so only the environment needs to be
+// `Sync` for host threads to issue syscalls.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send::<WasiFs>();
+    assert_send::<WasiState>();
+    assert_send_sync::<WasiEnv>();
+};
+
 /// Create an [`ImportObject`] with an existing [`WasiEnv`]. `WasiEnv`
 /// needs a [`WasiState`], that can be constructed from a
 /// [`WasiStateBuilder`](state::WasiStateBuilder).
@@ -199,6 +356,18 @@ pub fn generate_import_object_from_env(
     }
 }
 
+/// Define the WASI imports of the given version in a [`Linker`], with
+/// an existing [`WasiEnv`].
+pub fn define_wasi(
+    linker: &mut Linker,
+    wasi_env: WasiEnv,
+    version: WasiVersion,
+) -> Result<(), LinkerError> {
+    let import_object = generate_import_object_from_env(linker.store(), wasi_env, version);
+    linker.define_import_object(&import_object)?;
+    Ok(())
+}
+
 /// Combines a state generating function with the import list for legacy WASI
 fn generate_import_object_snapshot0(store: &Store, env: WasiEnv) -> ImportObject {
     imports! {
@@ -249,6 +418,13 @@ fn generate_import_object_snapshot0(store: &Store, env: WasiEnv) -> ImportObject
             "sock_send" => Function::new_native_with_env(store, env.clone(), sock_send),
             "sock_shutdown" => Function::new_native_with_env(store, env.clone(), sock_shutdown),
         },
+        "wasmer_tty" => {
+            "tty_get" => Function::new_native_with_env(store, env.clone(), tty_get),
+            "tty_set" => Function::new_native_with_env(store, env.clone(), tty_set),
+        },
+        "wasmer_fs" => {
+            "fs_watch" => Function::new_native_with_env(store, env.clone(), fs_watch),
+        },
     }
 }
 
@@ -301,6 +477,110 @@ fn generate_import_object_snapshot1(store: &Store, env: WasiEnv) -> ImportObject
             "sock_recv" => Function::new_native_with_env(store, env.clone(), sock_recv),
             "sock_send" => Function::new_native_with_env(store, env.clone(), sock_send),
             "sock_shutdown" => Function::new_native_with_env(store, env.clone(), sock_shutdown),
+        },
+        "wasmer_tty" => {
+            "tty_get" => Function::new_native_with_env(store, env.clone(), tty_get),
+            "tty_set" => Function::new_native_with_env(store, env.clone(), tty_set),
+        },
+        "wasmer_fs" => {
+            "fs_watch" => Function::new_native_with_env(store, env.clone(), fs_watch),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs the WASI command `wat`, returning how it ended.
+    fn run(wat: &str) -> Result<ExitStatus, WasiRuntimeError> {
+        let module = Module::new(&Store::default(), wat).unwrap();
+        let mut env = WasiState::new("command").finalize().unwrap();
+        env.run_command_with_status(&module)
+    }
+
+    #[test]
+    fn exit_status_of_proc_exit() {
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (memory (export "memory") 1)
+            (func (export "_start")
+                (call $proc_exit (i32.const 3))))"#)
+        .unwrap();
+        assert_eq!(status, ExitStatus::exited(3));
+        assert!(!status.success());
+
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (memory (export "memory") 1)
+            (func (export "_start")))"#)
+        .unwrap();
+        assert_eq!(status, ExitStatus::exited(0));
+        assert!(status.success());
+    }
+
+    #[test]
+    fn exit_status_of_trap_after_panic() {
+        // Writes a panic to stderr, then traps like Rust's `abort`.
+        let status = run(r#"(module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\10\00\00\00\64\00\00\00")
+            (data (i32.const 16) "thread 'main' panicked at 'oops', src/main.rs:2:5\0anote: run with `RUST_BACKTRACE=1` for a backtrace\0a")
+            (func (export "_start")
+                (drop (call $fd_write (i32.const 2) (i32.const 0) (i32.const 1) (i32.const 8)))
+                (unreachable)))"#)
+        .unwrap();
+        assert_eq!(status.code, None);
+        assert!(status.trap.is_some());
+        assert_eq!(
+            status.panic_message.as_deref(),
+            Some("thread 'main' panicked at 'oops', src/main.rs:2:5")
+        );
+    }
+
+    #[test]
+    fn exit_status_needs_a_command() {
+        let result = run(r#"(module
+            (memory (export "memory") 1)
+            (func (export "_initialize")))"#);
+        assert!(matches!(result, Err(WasiRuntimeError::NotACommand)));
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(WasiError::Exit(1).code(), "exit");
+        assert_eq!(WasiError::UnknownWasiVersion.code(), "unknown_wasi_version");
+        let journal = JournalError::Malformed("truncated".to_string());
+        assert_eq!(WasiError::from(journal).code(), "journal");
+    }
+
+    #[test]
+    fn fs_creation_errors_keep_their_source() {
+        use std::error::Error;
+
+        let missing = std::env::temp_dir().join("wasmer-wasi-missing-preopen");
+        #[allow(deprecated)]
+        let error = WasiFs::new(&[missing.clone()], &[]).unwrap_err();
+        assert!(matches!(&error, WasiFsCreationError::Metadata { path, .. } if *path == missing));
+
+        let error = WasiStateCreationError::WasiFsCreationError(error);
+        let source = error.source().unwrap();
+        assert!(source.is::<WasiFsCreationError>());
+        let source = source.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<WasiFsError>(),
+            Some(&WasiFsError::EntityNotFound)
+        );
+
+        let error = WasiFsCreationError::fs("fd", types::__WASI_EBADF);
+        assert_eq!(
+            error.source().unwrap().downcast_ref::<WasiFsError>(),
+            Some(&WasiFsError::InvalidFd)
+        );
+        assert!(WasiFsCreationError::DuplicateEntry("a".to_string())
+            .source()
+            .is_none());
+    }
+}
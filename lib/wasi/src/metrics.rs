@@ -0,0 +1,300 @@
+//! Counters and latency histograms of the WASI syscalls, for observing
+//! programs running in production.
+//!
+//! Enable them with [`WasiStateBuilder::metrics`], and read them with
+//! [`WasiState::metrics`].
+//!
+//! [`WasiStateBuilder::metrics`]: crate::WasiStateBuilder::metrics
+//! [`WasiState::metrics`]: crate::WasiState::metrics
+
+use crate::syscalls::types::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The upper bounds of the buckets of the latency histograms, in
+/// microseconds; the last bucket is unbounded.
+pub const LATENCY_BUCKETS_US: [u64; 7] = [1, 10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// The number of WASI errnos, from `__WASI_ESUCCESS` to `__WASI_ENOTCAPABLE`.
+const ERRNO_COUNT: usize = __WASI_ENOTCAPABLE as usize + 1;
+
+/// The metrics of the syscalls of the instances sharing a [`WasiState`].
+///
+/// The counters of every syscall are allocated upfront and updated
+/// atomically, so that the instances don't contend on a lock.
+///
+/// [`WasiState`]: crate::WasiState
+#[derive(Debug)]
+pub(crate) struct WasiMetrics {
+    syscalls: BTreeMap<&'static str, AtomicSyscallMetrics>,
+}
+
+#[derive(Debug)]
+struct AtomicSyscallMetrics {
+    count: AtomicU64,
+    errnos: Vec<AtomicU64>,
+    total_time_ns: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_US.len() + 1],
+    bytes: AtomicU64,
+}
+
+impl Default for AtomicSyscallMetrics {
+    fn default() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            errnos: (0..ERRNO_COUNT).map(|_| AtomicU64::new(0)).collect(),
+            total_time_ns: AtomicU64::new(0),
+            latency_buckets: Default::default(),
+            bytes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for WasiMetrics {
+    fn default() -> Self {
+        Self {
+            syscalls: crate::journal::syscalls::NAMES
+                .iter()
+                .map(|&name| (name, AtomicSyscallMetrics::default()))
+                .collect(),
+        }
+    }
+}
+
+impl WasiMetrics {
+    /// Counts a call of `syscall` that took `elapsed` and returned `errno`.
+    pub(crate) fn record(&self, syscall: &'static str, elapsed: Duration, errno: __wasi_errno_t) {
+        let metrics = match self.syscalls.get(syscall) {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        metrics.count.fetch_add(1, Ordering::Relaxed);
+        if let Some(count) = metrics.errnos.get(errno as usize) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        metrics
+            .total_time_ns
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        let micros = elapsed.as_micros();
+        let bucket = LATENCY_BUCKETS_US
+            .iter()
+            .position(|&bound| micros <= bound as u128)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        metrics.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts `bytes` read or written by `syscall`.
+    pub(crate) fn record_bytes(&self, syscall: &'static str, bytes: u64) {
+        if let Some(metrics) = self.syscalls.get(syscall) {
+            metrics.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a copy of the current metrics.
+    ///
+    /// The counters are read one by one, so a snapshot taken while
+    /// syscalls run may count a call in some of them only.
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            syscalls: self
+                .syscalls
+                .iter()
+                .filter(|(_, metrics)| load(&metrics.count) > 0 || load(&metrics.bytes) > 0)
+                .map(|(name, metrics)| {
+                    let mut latency_buckets = [0; LATENCY_BUCKETS_US.len() + 1];
+                    for (bucket, count) in latency_buckets
+                        .iter_mut()
+                        .zip(metrics.latency_buckets.iter())
+                    {
+                        *bucket = load(count);
+                    }
+                    let metrics = SyscallMetrics {
+                        count: load(&metrics.count),
+                        errnos: metrics
+                            .errnos
+                            .iter()
+                            .enumerate()
+                            .map(|(errno, count)| (errno as __wasi_errno_t, load(count)))
+                            .filter(|&(_, count)| count > 0)
+                            .collect(),
+                        total_time: Duration::from_nanos(load(&metrics.total_time_ns)),
+                        latency_buckets,
+                        bytes: load(&metrics.bytes),
+                    };
+                    (name.to_string(), metrics)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The metrics of a syscall.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyscallMetrics {
+    /// The number of calls.
+    pub count: u64,
+    /// The number of calls by returned errno, including `__WASI_ESUCCESS`.
+    pub errnos: BTreeMap<__wasi_errno_t, u64>,
+    /// The time spent in the calls.
+    pub total_time: Duration,
+    /// The number of calls by latency: the calls of the bucket `i` took at
+    /// most `LATENCY_BUCKETS_US[i]` microseconds, and more than the bound of
+    /// the previous bucket. The last bucket counts the slower calls.
+    pub latency_buckets: [u64; LATENCY_BUCKETS_US.len() + 1],
+    /// The number of bytes read or written, for the syscalls doing I/O.
+    pub bytes: u64,
+}
+
+/// A copy of the metrics of the syscalls, returned by
+/// [`WasiState::metrics`](crate::WasiState::metrics).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The metrics of each syscall called at least once, by name.
+    pub syscalls: BTreeMap<String, SyscallMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// Encodes the metrics in the text format of Prometheus.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP wasi_syscalls_total The number of WASI syscalls, by returned errno.\n",
+        );
+        out.push_str("# TYPE wasi_syscalls_total counter\n");
+        for (name, metrics) in self.syscalls.iter() {
+            for (errno, count) in metrics.errnos.iter() {
+                let _ = writeln!(
+                    out,
+                    "wasi_syscalls_total{{syscall=\"{}\",errno=\"{}\"}} {}",
+                    name, errno, count
+                );
+            }
+        }
+
+        out.push_str("# HELP wasi_syscall_duration_seconds The latency of WASI syscalls.\n");
+        out.push_str("# TYPE wasi_syscall_duration_seconds histogram\n");
+        for (name, metrics) in self.syscalls.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_US
+                .iter()
+                .zip(metrics.latency_buckets.iter())
+            {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "wasi_syscall_duration_seconds_bucket{{syscall=\"{}\",le=\"{}\"}} {}",
+                    name,
+                    *bound as f64 / 1_000_000.0,
+                    cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "wasi_syscall_duration_seconds_bucket{{syscall=\"{}\",le=\"+Inf\"}} {}",
+                name, metrics.count
+            );
+            let _ = writeln!(
+                out,
+                "wasi_syscall_duration_seconds_sum{{syscall=\"{}\"}} {}",
+                name,
+                metrics.total_time.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "wasi_syscall_duration_seconds_count{{syscall=\"{}\"}} {}",
+                name, metrics.count
+            );
+        }
+
+        out.push_str(
+            "# HELP wasi_syscall_bytes_total The bytes read or written by WASI syscalls.\n",
+        );
+        out.push_str("# TYPE wasi_syscall_bytes_total counter\n");
+        for (name, metrics) in self.syscalls.iter() {
+            if metrics.bytes > 0 {
+                let _ = writeln!(
+                    out,
+                    "wasi_syscall_bytes_total{{syscall=\"{}\"}} {}",
+                    name, metrics.bytes
+                );
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn to_prometheus() {
+        let metrics = WasiMetrics::default();
+        metrics.record("fd_read", Duration::from_micros(5), __WASI_ESUCCESS);
+        metrics.record("fd_read", Duration::from_millis(2), __WASI_EBADF);
+        metrics.record_bytes("fd_read", 4);
+        metrics.record("not_a_syscall", Duration::from_micros(5), __WASI_ESUCCESS);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            snapshot.syscalls.keys().collect::<Vec<_>>(),
+            vec!["fd_read"]
+        );
+        assert_eq!(
+            snapshot.to_prometheus(),
+            "\
+# HELP wasi_syscalls_total The number of WASI syscalls, by returned errno.
+# TYPE wasi_syscalls_total counter
+wasi_syscalls_total{syscall=\"fd_read\",errno=\"0\"} 1
+wasi_syscalls_total{syscall=\"fd_read\",errno=\"8\"} 1
+# HELP wasi_syscall_duration_seconds The latency of WASI syscalls.
+# TYPE wasi_syscall_duration_seconds histogram
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.000001\"} 0
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.00001\"} 1
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.0001\"} 1
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.001\"} 1
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.01\"} 2
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"0.1\"} 2
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"1\"} 2
+wasi_syscall_duration_seconds_bucket{syscall=\"fd_read\",le=\"+Inf\"} 2
+wasi_syscall_duration_seconds_sum{syscall=\"fd_read\"} 0.002005
+wasi_syscall_duration_seconds_count{syscall=\"fd_read\"} 2
+# HELP wasi_syscall_bytes_total The bytes read or written by WASI syscalls.
+# TYPE wasi_syscall_bytes_total counter
+wasi_syscall_bytes_total{syscall=\"fd_read\"} 4
+"
+        );
+    }
+
+    #[test]
+    fn concurrent_records() {
+        let metrics = Arc::new(WasiMetrics::default());
+        let threads = (0..4)
+            .map(|_| {
+                let metrics = metrics.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        metrics.record("fd_write", Duration::from_micros(1), __WASI_ESUCCESS);
+                        metrics.record_bytes("fd_write", 2);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let fd_write = &metrics.snapshot().syscalls["fd_write"];
+        assert_eq!(fd_write.count, 4000);
+        assert_eq!(fd_write.errnos[&__WASI_ESUCCESS], 4000);
+        assert_eq!(fd_write.latency_buckets[0], 4000);
+        assert_eq!(fd_write.total_time, Duration::from_millis(4));
+        assert_eq!(fd_write.bytes, 8000);
+    }
+}
@@ -1,10 +1,16 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{WasiFile, WasiFs, WasiFsError, WasiState};
+use crate::metrics::WasiMetrics;
+use crate::state::{
+    Device, FreshnessPolicy, IoLimits, ObjectStoreFs, PathPolicy, RemoteFs, TtyPolicy, WasiFile,
+    WasiFs, WasiFsError, WasiFsImage, WasiState,
+};
 use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
 use crate::WasiEnv;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
+use wasmer::StderrCapture;
 
 /// Creates an empty [`WasiStateBuilder`].
 ///
@@ -37,6 +43,17 @@ pub struct WasiStateBuilder {
     args: Vec<Vec<u8>>,
     envs: Vec<Vec<u8>>,
     preopens: Vec<PreopenedDir>,
+    path_policy: PathPolicy,
+    max_open_fds: Option<u32>,
+    mount_devices: bool,
+    images: Vec<(String, Arc<WasiFsImage>)>,
+    remotes: Vec<(String, Arc<RemoteFs>)>,
+    object_stores: Vec<(String, Arc<ObjectStoreFs>)>,
+    tty_policy: TtyPolicy,
+    host_tty_changes: bool,
+    freshness_policy: FreshnessPolicy,
+    io_limits: IoLimits,
+    metrics: bool,
     #[allow(clippy::type_complexity)]
     setup_fs_fn: Option<Box<dyn Fn(&mut WasiFs) -> Result<(), String> + Send>>,
     stdout_override: Option<Box<dyn WasiFile>>,
@@ -50,6 +67,17 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("args", &self.args)
             .field("envs", &self.envs)
             .field("preopens", &self.preopens)
+            .field("path_policy", &self.path_policy)
+            .field("max_open_fds", &self.max_open_fds)
+            .field("mount_devices", &self.mount_devices)
+            .field("images", &self.images)
+            .field("remotes", &self.remotes)
+            .field("object_stores", &self.object_stores)
+            .field("tty_policy", &self.tty_policy)
+            .field("host_tty_changes", &self.host_tty_changes)
+            .field("freshness_policy", &self.freshness_policy)
+            .field("io_limits", &self.io_limits)
+            .field("metrics", &self.metrics)
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
@@ -60,6 +88,7 @@ impl std::fmt::Debug for WasiStateBuilder {
 
 /// Error type returned when bad data is given to [`WasiStateBuilder`].
 #[derive(Error, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum WasiStateCreationError {
     #[error("bad environment variable format: `{0}`")]
     EnvironmentVariableFormatError(String),
@@ -72,7 +101,7 @@ pub enum WasiStateCreationError {
     #[error("mapped dir alias has wrong format: `{0}`")]
     MappedDirAliasFormattingError(String),
     #[error("wasi filesystem creation error: `{0}`")]
-    WasiFsCreationError(String),
+    WasiFsCreationError(#[source] WasiFsCreationError),
     #[error("wasi filesystem setup error: `{0}`")]
     WasiFsSetupError(String),
     #[error(transparent)]
@@ -274,6 +303,112 @@ impl WasiStateBuilder {
         Ok(self)
     }
 
+    /// Set how the WASI filesystem compares file names, see [`PathPolicy`].
+    ///
+    /// Defaults to [`PathPolicy::CaseSensitive`].
+    pub fn path_policy(&mut self, path_policy: PathPolicy) -> &mut Self {
+        self.path_policy = path_policy;
+
+        self
+    }
+
+    /// Limit the number of fds the WASI program can have open at once, like
+    /// `RLIMIT_NOFILE`.  Stdio and preopened directories count towards the limit.
+    ///
+    /// Opening more fds fails with `__WASI_EMFILE`.
+    pub fn max_open_fds(&mut self, max_open_fds: u32) -> &mut Self {
+        self.max_open_fds = Some(max_open_fds);
+
+        self
+    }
+
+    /// Preopen a virtual `/dev` directory with the built-in [`Device`]s:
+    /// `null`, `zero`, `urandom` and `tty`.
+    ///
+    /// Many programs open these paths unconditionally.
+    pub fn mount_devices(&mut self) -> &mut Self {
+        self.mount_devices = true;
+
+        self
+    }
+
+    /// Preopen a virtual directory `alias` with the contents of `image`.
+    ///
+    /// The image isn't copied: it can be built once, and mounted in the
+    /// filesystems of many instances. The program sees its own changes to
+    /// the files only.
+    pub fn mount_image(&mut self, alias: &str, image: Arc<WasiFsImage>) -> &mut Self {
+        self.images.push((alias.to_string(), image));
+
+        self
+    }
+
+    /// Preopen a virtual directory `alias` with the root of a remote
+    /// filesystem, served e.g. by [`serve_remote_fs`](crate::serve_remote_fs).
+    ///
+    /// The connection can be shared by many instances. Creating and moving
+    /// directories in it isn't supported.
+    pub fn mount_remote(&mut self, alias: &str, fs: Arc<RemoteFs>) -> &mut Self {
+        self.remotes.push((alias.to_string(), fs));
+
+        self
+    }
+
+    /// Preopen a read-only virtual directory `alias` with the objects of an
+    /// object store, as files named after their keys.
+    ///
+    /// The objects are read by blocks when the program reads them, and the
+    /// blocks are cached locally for all the instances sharing `fs`.
+    pub fn mount_object_store(&mut self, alias: &str, fs: Arc<ObjectStoreFs>) -> &mut Self {
+        self.object_stores.push((alias.to_string(), fs));
+
+        self
+    }
+
+    /// Set whether stdio fds are reported to the program as terminals.
+    /// Defaults to [`TtyPolicy::Host`].
+    pub fn tty_policy(&mut self, tty_policy: TtyPolicy) -> &mut Self {
+        self.tty_policy = tty_policy;
+
+        self
+    }
+
+    /// Let the program change the echo and line buffering of the host
+    /// terminals, when the [`TtyPolicy`] is `Host`.  Their settings are
+    /// restored when the environment is dropped.
+    /// Defaults to `false`: the settings are only emulated.
+    pub fn host_tty_changes(&mut self, allow: bool) -> &mut Self {
+        self.host_tty_changes = allow;
+
+        self
+    }
+
+    /// Set how long the cached entries of the preopened host directories
+    /// are trusted, when the host may change them while the program runs.
+    /// Defaults to [`FreshnessPolicy::Cached`].
+    pub fn freshness_policy(&mut self, freshness_policy: FreshnessPolicy) -> &mut Self {
+        self.freshness_policy = freshness_policy;
+
+        self
+    }
+
+    /// Set the limits on the reads and writes of files by the program.
+    /// Defaults to no limits.
+    pub fn io_limits(&mut self, io_limits: IoLimits) -> &mut Self {
+        self.io_limits = io_limits;
+
+        self
+    }
+
+    /// Set whether the count, latency and errnos of the syscalls, and the
+    /// bytes they read and write, are collected for
+    /// [`WasiState::metrics`]. Defaults to `false`.
+    pub fn metrics(&mut self, enabled: bool) -> &mut Self {
+        self.metrics = enabled;
+
+        self
+    }
+
     /// Overwrite the default WASI `stdout`, if you want to hold on to the
     /// original `stdout` use [`WasiFs::swap_file`] after building.
     pub fn stdout(&mut self, new_file: Box<dyn WasiFile>) -> &mut Self {
@@ -362,8 +497,31 @@ impl WasiStateBuilder {
 
         // this deprecation warning only applies to external callers
         #[allow(deprecated)]
-        let mut wasi_fs = WasiFs::new_with_preopen(&self.preopens)
+        let mut wasi_fs = WasiFs::new_with_preopen(&self.preopens, self.path_policy)
             .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        if self.mount_devices {
+            wasi_fs
+                .mount_devices()
+                .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        }
+        for (alias, image) in self.images.iter() {
+            validate_mapped_dir_alias(alias)?;
+            wasi_fs
+                .mount_image(alias, image.clone())
+                .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        }
+        for (alias, fs) in self.remotes.iter() {
+            validate_mapped_dir_alias(alias)?;
+            wasi_fs
+                .mount_remote(alias, fs.clone())
+                .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        }
+        for (alias, fs) in self.object_stores.iter() {
+            validate_mapped_dir_alias(alias)?;
+            wasi_fs
+                .mount_object_store(alias, fs.clone())
+                .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        }
         // set up the file system, overriding base files and calling the setup function
         if let Some(stdin_override) = self.stdin_override.take() {
             wasi_fs
@@ -383,10 +541,24 @@ impl WasiStateBuilder {
         if let Some(f) = &self.setup_fs_fn {
             f(&mut wasi_fs).map_err(WasiStateCreationError::WasiFsSetupError)?;
         }
+        wasi_fs.tty_policy = self.tty_policy;
+        wasi_fs.host_tty_changes = self.host_tty_changes;
+        wasi_fs.io_limits = self.io_limits;
+        wasi_fs
+            .set_freshness_policy(self.freshness_policy)
+            .map_err(WasiStateCreationError::WasiFsError)?;
+        // set last so that the fds opened while building don't trip the limit
+        wasi_fs.max_open_fds = self.max_open_fds;
         Ok(WasiState {
             fs: wasi_fs,
             args: self.args.clone(),
             envs: self.envs.clone(),
+            metrics: if self.metrics {
+                Some(Arc::new(WasiMetrics::default()))
+            } else {
+                None
+            },
+            stderr_capture: StderrCapture::new(),
         })
     }
 
@@ -540,4 +712,45 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    #[test]
+    fn path_policy() {
+        let state = create_wasi_state("test_prog")
+            .path_policy(PathPolicy::CaseInsensitivePreserving)
+            .build()
+            .unwrap();
+        assert_eq!(state.fs.path_policy, PathPolicy::CaseInsensitivePreserving);
+        assert_eq!(state.fs.path_policy.key("README.md"), "readme.md");
+        assert_eq!(state.fs.path_policy.normalize("README.md"), "README.md");
+
+        // "é" as `e` followed by a combining acute accent
+        let decomposed = "e\u{301}.txt";
+        assert_eq!(
+            PathPolicy::NfcNormalized.key(decomposed),
+            PathPolicy::NfcNormalized.key("\u{e9}.txt")
+        );
+        assert_ne!(
+            PathPolicy::CaseSensitive.key(decomposed),
+            PathPolicy::CaseSensitive.key("\u{e9}.txt")
+        );
+    }
+
+    #[test]
+    fn mount_devices() {
+        let mut state = create_wasi_state("test_prog")
+            .mount_devices()
+            .build()
+            .unwrap();
+        let dev_fd = *state.fs.preopen_fds.last().unwrap();
+        for device in Device::ALL.iter() {
+            let inode = state
+                .fs
+                .get_inode_at_path(dev_fd, device.name(), false)
+                .unwrap();
+            assert_eq!(
+                state.fs.inodes[inode].stat.st_filetype,
+                crate::syscalls::types::__WASI_FILETYPE_CHARACTER_DEVICE
+            );
+        }
+    }
 }
@@ -0,0 +1,528 @@
+//! Read-only filesystem images shared among many instances.
+
+use crate::state::{WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// A tree of directories and files built once, e.g. from the unpacked
+/// contents of a package, and mounted in the [`WasiFs`] of many instances
+/// with [`WasiStateBuilder::mount_image`].
+///
+/// The image is never modified: the files are copied on their first write
+/// by an instance, and the directories are loaded lazily in the inodes of
+/// an instance when it first looks into them.
+///
+/// [`WasiFs`]: crate::WasiFs
+/// [`WasiStateBuilder::mount_image`]: crate::WasiStateBuilder::mount_image
+#[derive(Debug, Clone, Default)]
+pub struct WasiFsImage {
+    root: ImageDir,
+}
+
+/// A directory of a [`WasiFsImage`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ImageDir {
+    pub(crate) entries: BTreeMap<String, ImageEntry>,
+}
+
+/// An entry of a directory of a [`WasiFsImage`].
+#[derive(Debug, Clone)]
+pub(crate) enum ImageEntry {
+    File(Arc<[u8]>),
+    Dir(ImageDir),
+}
+
+impl WasiFsImage {
+    /// The default limit on the total size of the files read from an
+    /// archive, 1 GiB.
+    pub const DEFAULT_ARCHIVE_LIMIT: u64 = 1 << 30;
+
+    /// Creates an empty image.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an image with the contents of the host directory `path`.
+    /// Symlinks and special files are skipped.
+    pub fn from_host_dir(path: impl AsRef<Path>) -> io::Result<Self> {
+        fn load(path: &Path) -> io::Result<ImageDir> {
+            let mut dir = ImageDir::default();
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    dir.entries
+                        .insert(name, ImageEntry::Dir(load(&entry.path())?));
+                } else if file_type.is_file() {
+                    let contents = fs::read(entry.path())?;
+                    dir.entries.insert(name, ImageEntry::File(contents.into()));
+                }
+            }
+            Ok(dir)
+        }
+
+        Ok(Self {
+            root: load(path.as_ref())?,
+        })
+    }
+
+    /// Creates an image with the contents of a tar archive. Links, special
+    /// files and entries escaping the archive, such as `../file`, are
+    /// skipped.
+    ///
+    /// The archive is read when the image is built: the instances share
+    /// the contents of its files then, without extracting them. The files
+    /// are held in memory, so they may not exceed
+    /// [`WasiFsImage::DEFAULT_ARCHIVE_LIMIT`] in total, see
+    /// [`WasiFsImage::from_tar_with_limit`].
+    #[cfg(feature = "tar")]
+    pub fn from_tar(reader: impl Read) -> io::Result<Self> {
+        Self::from_tar_with_limit(reader, Self::DEFAULT_ARCHIVE_LIMIT)
+    }
+
+    /// Creates an image with the contents of a tar archive, like
+    /// [`WasiFsImage::from_tar`], failing with an
+    /// [`io::ErrorKind::InvalidData`] error if its files exceed `limit`
+    /// bytes in total.
+    #[cfg(feature = "tar")]
+    pub fn from_tar_with_limit(reader: impl Read, limit: u64) -> io::Result<Self> {
+        let mut image = Self::new();
+        let mut size = SizeLimit::new(limit);
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if !is_enclosed(&path) {
+                continue;
+            }
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => {
+                    image.add_dir(&path).map_err(invalid_archive)?;
+                }
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let contents = size.read(&mut entry)?;
+                    image.add_file(&path, contents).map_err(invalid_archive)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(image)
+    }
+
+    /// Creates an image with the contents of a zip archive.
+    ///
+    /// Like with [`WasiFsImage::from_tar`], the archive is read when the
+    /// image is built, its files may not exceed
+    /// [`WasiFsImage::DEFAULT_ARCHIVE_LIMIT`] in total, and the entries
+    /// escaping it are skipped.
+    #[cfg(feature = "zip")]
+    pub fn from_zip(reader: impl Read + Seek) -> io::Result<Self> {
+        Self::from_zip_with_limit(reader, Self::DEFAULT_ARCHIVE_LIMIT)
+    }
+
+    /// Creates an image with the contents of a zip archive, like
+    /// [`WasiFsImage::from_zip`], failing with an
+    /// [`io::ErrorKind::InvalidData`] error if its files exceed `limit`
+    /// bytes in total once decompressed.
+    #[cfg(feature = "zip")]
+    pub fn from_zip_with_limit(reader: impl Read + Seek, limit: u64) -> io::Result<Self> {
+        let mut image = Self::new();
+        let mut size = SizeLimit::new(limit);
+        let mut archive = zip::ZipArchive::new(reader).map_err(invalid_archive)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(invalid_archive)?;
+            // entries escaping the archive, such as `../file`, are skipped
+            let path = match file.enclosed_name() {
+                Some(path) => path.to_path_buf(),
+                None => continue,
+            };
+            if file.is_dir() {
+                image.add_dir(&path).map_err(invalid_archive)?;
+            } else {
+                let contents = size.read(&mut file)?;
+                image.add_file(&path, contents).map_err(invalid_archive)?;
+            }
+        }
+        Ok(image)
+    }
+
+    /// Creates an image with the root filesystem of an OCI image, from the
+    /// tarballs of its layers, from the lowest to the topmost.
+    ///
+    /// The layers must be uncompressed, e.g. by reading them through a
+    /// gzip decoder first. See [`WasiFsImage::apply_oci_layer`].
+    #[cfg(feature = "tar")]
+    pub fn from_oci_layers<R: Read>(layers: impl IntoIterator<Item = R>) -> io::Result<Self> {
+        let mut image = Self::new();
+        for layer in layers {
+            image.apply_oci_layer(layer)?;
+        }
+        Ok(image)
+    }
+
+    /// Applies the tarball of an OCI image layer on top of the image.
+    ///
+    /// The whiteouts of the layer remove entries of the image: `.wh.<name>`
+    /// removes `<name>`, and `.wh..wh..opq` empties its directory. The hard
+    /// links share the contents of their target, and the symlinks, special
+    /// files and escaping entries are skipped, like with
+    /// [`WasiFsImage::from_tar`]. The files of each layer may not exceed
+    /// [`WasiFsImage::DEFAULT_ARCHIVE_LIMIT`] in total.
+    #[cfg(feature = "tar")]
+    pub fn apply_oci_layer(&mut self, layer: impl Read) -> io::Result<&mut Self> {
+        let mut upper = Self::new();
+        let mut size = SizeLimit::new(Self::DEFAULT_ARCHIVE_LIMIT);
+        let mut whiteouts = Vec::new();
+        let mut opaque_dirs = Vec::new();
+        let mut links = Vec::new();
+        let mut archive = tar::Archive::new(layer);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if !is_enclosed(&path) {
+                continue;
+            }
+            let name = match path.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+            if name == ".wh..wh..opq" {
+                opaque_dirs.push(path.with_file_name(""));
+                continue;
+            }
+            if name.starts_with(".wh.") {
+                whiteouts.push(path.with_file_name(&name[".wh.".len()..]));
+                continue;
+            }
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => {
+                    upper.add_dir(&path).map_err(invalid_archive)?;
+                }
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let contents = size.read(&mut entry)?;
+                    upper.add_file(&path, contents).map_err(invalid_archive)?;
+                }
+                tar::EntryType::Link => {
+                    if let Some(target) = entry.link_name()? {
+                        links.push((path, target.into_owned()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // The whiteouts only hide the entries of the lower layers.
+        for path in opaque_dirs {
+            if let Some(dir) = self.dir_mut(&path) {
+                dir.entries.clear();
+            }
+        }
+        for path in whiteouts {
+            self.remove(&path);
+        }
+        merge(&mut self.root, upper.root);
+        for (path, target) in links {
+            let contents = self.file(&target).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("the target of the link `{}` is not a file", path.display()),
+                )
+            })?;
+            self.add_file(&path, contents).map_err(invalid_archive)?;
+        }
+        Ok(self)
+    }
+
+    /// Adds a file at `path` with `contents`, creating its parent
+    /// directories, and replacing any entry at `path`.
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Arc<[u8]>>,
+    ) -> Result<&mut Self, WasiFsError> {
+        let (parent, name) = self.parent_mut(path.as_ref())?;
+        parent
+            .entries
+            .insert(name, ImageEntry::File(contents.into()));
+        Ok(self)
+    }
+
+    /// Adds an empty directory at `path`, creating its parent directories.
+    pub fn add_dir(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, WasiFsError> {
+        let (parent, name) = self.parent_mut(path.as_ref())?;
+        match parent
+            .entries
+            .entry(name)
+            .or_insert_with(|| ImageEntry::Dir(ImageDir::default()))
+        {
+            ImageEntry::Dir(_) => Ok(self),
+            ImageEntry::File(_) => Err(WasiFsError::AlreadyExists),
+        }
+    }
+
+    /// Returns the directory at `path` in the image, if any.
+    pub(crate) fn dir(&self, path: &Path) -> Option<&ImageDir> {
+        let mut dir = &self.root;
+        for component in path.components() {
+            match dir.entries.get(&*component.as_os_str().to_string_lossy())? {
+                ImageEntry::Dir(child) => dir = child,
+                ImageEntry::File(_) => return None,
+            }
+        }
+        Some(dir)
+    }
+
+    /// Returns the contents of the file at `path` in the image, if any.
+    #[cfg(feature = "tar")]
+    fn file(&self, path: &Path) -> Option<Arc<[u8]>> {
+        let mut names = names(path).ok()?;
+        let name = names.pop()?;
+        let mut dir = &self.root;
+        for parent in names {
+            match dir.entries.get(&parent)? {
+                ImageEntry::Dir(child) => dir = child,
+                ImageEntry::File(_) => return None,
+            }
+        }
+        match dir.entries.get(&name)? {
+            ImageEntry::File(contents) => Some(contents.clone()),
+            ImageEntry::Dir(_) => None,
+        }
+    }
+
+    /// Returns the directory at `path` in the image, if any, to change it.
+    #[cfg(feature = "tar")]
+    fn dir_mut(&mut self, path: &Path) -> Option<&mut ImageDir> {
+        let mut dir = &mut self.root;
+        for name in names(path).ok()? {
+            match dir.entries.get_mut(&name)? {
+                ImageEntry::Dir(child) => dir = child,
+                ImageEntry::File(_) => return None,
+            }
+        }
+        Some(dir)
+    }
+
+    /// Removes the entry at `path` in the image, if any.
+    #[cfg(feature = "tar")]
+    fn remove(&mut self, path: &Path) {
+        let mut names = match names(path) {
+            Ok(names) => names,
+            Err(_) => return,
+        };
+        if let Some(name) = names.pop() {
+            let parent: PathBuf = names.iter().collect();
+            if let Some(dir) = self.dir_mut(&parent) {
+                dir.entries.remove(&name);
+            }
+        }
+    }
+
+    /// Returns the parent directory of `path`, creating it, and the name of
+    /// `path` in it.
+    fn parent_mut(&mut self, path: &Path) -> Result<(&mut ImageDir, String), WasiFsError> {
+        let mut names = names(path)?;
+        let name = names.pop().ok_or(WasiFsError::InvalidInput)?;
+        let mut dir = &mut self.root;
+        for parent in names {
+            let entry = dir
+                .entries
+                .entry(parent)
+                .or_insert_with(|| ImageEntry::Dir(ImageDir::default()));
+            dir = match entry {
+                ImageEntry::Dir(child) => child,
+                ImageEntry::File(_) => return Err(WasiFsError::BaseNotDirectory),
+            };
+        }
+        Ok((dir, name))
+    }
+}
+
+/// Returns the names of the components of `path`, relative to the root of
+/// an image.
+fn names(path: &Path) -> Result<Vec<String>, WasiFsError> {
+    let mut names = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => names.push(name.to_string_lossy().to_string()),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return Err(WasiFsError::InvalidInput),
+        }
+    }
+    Ok(names)
+}
+
+/// Whether `path` stays in the archive it comes from, i.e. it has no `..`
+/// component. The absolute paths are relative to the root of the archive.
+#[cfg(feature = "tar")]
+fn is_enclosed(path: &Path) -> bool {
+    names(path).is_ok()
+}
+
+/// Merges the entries of `upper` into `lower`, replacing the entries of
+/// `lower` unless both are directories.
+#[cfg(feature = "tar")]
+fn merge(lower: &mut ImageDir, upper: ImageDir) {
+    for (name, entry) in upper.entries {
+        match (lower.entries.get_mut(&name), entry) {
+            (Some(ImageEntry::Dir(lower)), ImageEntry::Dir(upper)) => merge(lower, upper),
+            (_, entry) => {
+                lower.entries.insert(name, entry);
+            }
+        }
+    }
+}
+
+/// The bytes left to read from the files of an archive.
+#[cfg(any(feature = "tar", feature = "zip"))]
+struct SizeLimit {
+    limit: u64,
+    remaining: u64,
+}
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+impl SizeLimit {
+    fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            remaining: limit,
+        }
+    }
+
+    /// Reads the contents of a file of the archive, failing if they exceed
+    /// the bytes left.
+    fn read(&mut self, file: impl Read) -> io::Result<Vec<u8>> {
+        // the size in the header isn't trusted to allocate
+        let mut contents = Vec::new();
+        file.take(self.remaining.saturating_add(1))
+            .read_to_end(&mut contents)?;
+        if contents.len() as u64 > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the files of the archive exceed the limit of {} bytes",
+                    self.limit
+                ),
+            ));
+        }
+        self.remaining -= contents.len() as u64;
+        Ok(contents)
+    }
+}
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+fn invalid_archive(error: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// A file of a [`WasiFsImage`], or created in one of its directories,
+/// holding its contents in memory.
+///
+/// The contents are shared with the image, and copied on the first
+/// write, so each instance sees its own changes only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageFile {
+    contents: Arc<[u8]>,
+    copy: Option<Vec<u8>>,
+    cursor: u64,
+}
+
+impl ImageFile {
+    /// Creates a file sharing `contents`.
+    pub fn new(contents: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            contents: contents.into(),
+            copy: None,
+            cursor: 0,
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        match &self.copy {
+            Some(copy) => copy,
+            None => &self.contents,
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut Vec<u8> {
+        let contents = &self.contents;
+        self.copy.get_or_insert_with(|| contents.to_vec())
+    }
+}
+
+impl Read for ImageFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data();
+        let start = (self.cursor as usize).min(data.len());
+        let read = (&data[start..]).read(buf)?;
+        self.cursor += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for ImageFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let cursor = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.data().len() as i64 + offset,
+            SeekFrom::Current(offset) => self.cursor as i64 + offset,
+        };
+        if cursor < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking before the start of the file",
+            ));
+        }
+        self.cursor = cursor as u64;
+        Ok(self.cursor)
+    }
+}
+
+impl Write for ImageFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let cursor = self.cursor as usize;
+        let data = self.data_mut();
+        if data.len() < cursor + buf.len() {
+            data.resize(cursor + buf.len(), 0);
+        }
+        data[cursor..cursor + buf.len()].copy_from_slice(buf);
+        self.cursor += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for ImageFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.data().len() as u64
+    }
+    fn set_len(&mut self, new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        self.data_mut().resize(new_size as usize, 0);
+        Ok(())
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.data().len().saturating_sub(self.cursor as usize))
+    }
+}
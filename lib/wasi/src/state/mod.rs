@@ -16,24 +16,41 @@
 #![allow(clippy::cognitive_complexity, clippy::too_many_arguments)]
 
 mod builder;
+mod image;
+mod object_store;
+mod remote;
+mod throttle;
 mod types;
+mod watch;
 
 pub use self::builder::*;
+use self::image::ImageEntry;
+pub use self::image::{ImageFile, WasiFsImage};
+pub use self::object_store::{ObjectFile, ObjectStore, ObjectStoreFs, DEFAULT_BLOCK_SIZE};
+pub use self::remote::{serve_remote_fs, RemoteFile, RemoteFs};
+use self::throttle::IoThrottle;
 pub use self::types::*;
+#[cfg(feature = "notify")]
+use self::watch::HostWatcher;
+pub use self::watch::WatchFile;
+use crate::metrics::{MetricsSnapshot, WasiMetrics};
 use crate::syscalls::types::*;
+use crate::syscalls::{platform_tty_save, HostTtySettings};
 use generational_arena::Arena;
 pub use generational_arena::Index as Inode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::{
     borrow::Borrow,
-    cell::Cell,
     fs,
     io::Write,
     path::{Path, PathBuf},
-    time::SystemTime,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 use tracing::debug;
+use wasmer::StderrCapture;
 
 /// the fd value of the virtual root
 pub const VIRTUAL_ROOT_FD: __wasi_fd_t = 3;
@@ -53,6 +70,26 @@ const STDOUT_DEFAULT_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_DATASYNC
     | __WASI_RIGHT_POLL_FD_READWRITE;
 const STDERR_DEFAULT_RIGHTS: __wasi_rights_t = STDOUT_DEFAULT_RIGHTS;
 
+/// The rights of the virtual directories of images and remote filesystems:
+/// creating or moving directories in them isn't supported yet.
+const VIRTUAL_DIR_RIGHTS: __wasi_rights_t = __WASI_RIGHT_FD_ADVISE
+    | __WASI_RIGHT_FD_TELL
+    | __WASI_RIGHT_FD_SEEK
+    | __WASI_RIGHT_FD_READ
+    | __WASI_RIGHT_FD_WRITE
+    | __WASI_RIGHT_FD_READDIR
+    | __WASI_RIGHT_FD_FILESTAT_GET
+    | __WASI_RIGHT_FD_FILESTAT_SET_SIZE
+    | __WASI_RIGHT_PATH_OPEN
+    | __WASI_RIGHT_PATH_CREATE_FILE
+    | __WASI_RIGHT_PATH_FILESTAT_GET
+    | __WASI_RIGHT_PATH_UNLINK_FILE
+    | __WASI_RIGHT_POLL_FD_READWRITE;
+
+/// The number of inodes below which [`WasiFs::collect_garbage`] isn't run
+/// automatically when fds are closed
+const MIN_GC_INODES: usize = 1024;
+
 /// A completely aribtrary "big enough" number used as the upper limit for
 /// the number of symlinks that can be traversed when resolving a path
 pub const MAX_SYMLINKS: u32 = 128;
@@ -89,14 +126,14 @@ pub enum Kind {
         // TODO: wrap it like WasiFile
         path: PathBuf,
         /// The entries of a directory are lazily filled.
-        entries: HashMap<String, Inode>,
+        entries: BTreeMap<String, Inode>,
     },
     /// The same as Dir but without the irrelevant bits
     /// The root is immutable after creation; generally the Kind::Root
     /// branch of whatever code you're writing will be a simpler version of
     /// your Kind::Dir logic
     Root {
-        entries: HashMap<String, Inode>,
+        entries: BTreeMap<String, Inode>,
     },
     /// The first two fields are data _about_ the symlink
     /// the last field is the data _inside_ the symlink
@@ -115,6 +152,10 @@ pub enum Kind {
     Buffer {
         buffer: Vec<u8>,
     },
+    /// A built-in character device, such as `/dev/null`
+    Device {
+        device: Device,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -153,16 +194,74 @@ impl Fd {
 #[derive(Debug, Serialize, Deserialize)]
 /// Warning, modifying these fields directly may cause invariants to break and
 /// should be considered unsafe.  These fields may be made private in a future release
+///
+/// `WasiFs` is `Send` and its fd and inode counters are atomic, so the
+/// [`WasiState`] holding it can be shared behind a mutex by host threads
+/// issuing syscalls on behalf of the same instance.
+///
+/// Its maps are ordered, so iterating them (e.g. when listing a directory or
+/// serializing the filesystem) gives the same order on every run.
 pub struct WasiFs {
     //pub repo: Repo,
     pub preopen_fds: Vec<u32>,
-    pub name_map: HashMap<String, Inode>,
+    pub name_map: BTreeMap<String, Inode>,
     pub inodes: Arena<InodeVal>,
-    pub fd_map: HashMap<u32, Fd>,
-    pub next_fd: Cell<u32>,
-    inode_counter: Cell<u64>,
+    pub fd_map: BTreeMap<u32, Fd>,
+    next_fd: AtomicU32,
+    inode_counter: AtomicU64,
     /// for fds still open after the file has been deleted
-    pub orphan_fds: HashMap<Inode, InodeVal>,
+    pub orphan_fds: BTreeMap<Inode, InodeVal>,
+    /// how names are compared when looking up directory entries
+    #[serde(default)]
+    pub path_policy: PathPolicy,
+    /// the maximum number of fds that may be open at once, stdio and preopened
+    /// directories included; `None` means there's no limit
+    #[serde(default)]
+    pub max_open_fds: Option<u32>,
+    /// whether stdio fds are reported as terminals
+    #[serde(default)]
+    pub tty_policy: TtyPolicy,
+    /// the terminal settings last set by the guest
+    #[serde(default)]
+    pub tty: __wasi_tty_t,
+    /// whether `tty_set` changes the settings of the host terminals too,
+    /// when the `tty_policy` is `Host`
+    #[serde(default)]
+    pub host_tty_changes: bool,
+    /// the settings of the host terminals before the guest first changed
+    /// them, restored when the filesystem is dropped
+    #[serde(skip)]
+    saved_host_ttys: BTreeMap<__wasi_fd_t, HostTtySettings>,
+    /// how long the cached entries of host directories are trusted, see
+    /// [`WasiFs::set_freshness_policy`]
+    #[serde(default)]
+    freshness_policy: FreshnessPolicy,
+    /// when the entries of host directories were last loaded again, for
+    /// `FreshnessPolicy::MaxAge`
+    #[serde(skip)]
+    validated_at: BTreeMap<Inode, Instant>,
+    /// the notifications of changes in the preopened directories, for
+    /// `FreshnessPolicy::Watch`
+    #[cfg(feature = "notify")]
+    #[serde(skip)]
+    host_watcher: Option<std::sync::Mutex<HostWatcher>>,
+    /// the number of inodes at which `close_fd` collects garbage next
+    #[serde(skip)]
+    next_gc: usize,
+    /// the directories of mounted images whose entries aren't loaded yet,
+    /// with their path in the image
+    #[serde(skip)]
+    images: BTreeMap<Inode, (Arc<WasiFsImage>, PathBuf)>,
+    /// the directories of mounted remote filesystems, with their remote
+    /// path and whether their entries are loaded
+    #[serde(skip)]
+    remote_dirs: BTreeMap<Inode, (Arc<RemoteFs>, PathBuf, bool)>,
+    /// the limits on the reads and writes of files
+    #[serde(default)]
+    pub io_limits: IoLimits,
+    /// the state of the throttling enforcing `io_limits`
+    #[serde(skip)]
+    io_throttle: IoThrottle,
 }
 
 impl WasiFs {
@@ -175,21 +274,17 @@ impl WasiFs {
     pub fn new(
         preopened_dirs: &[PathBuf],
         mapped_dirs: &[(String, PathBuf)],
-    ) -> Result<Self, String> {
-        let (mut wasi_fs, root_inode) = Self::new_init()?;
+    ) -> Result<Self, WasiFsCreationError> {
+        let (mut wasi_fs, root_inode) = Self::new_init(PathPolicy::default())?;
 
         debug!("wasi::fs::preopen_dirs");
         for dir in preopened_dirs {
             debug!("Attempting to preopen {}", &dir.to_string_lossy());
             // TODO: think about this
             let default_rights = ALL_RIGHTS;
-            let cur_dir_metadata = dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    dir,
-                    e.to_string()
-                )
-            })?;
+            let cur_dir_metadata = dir
+                .metadata()
+                .map_err(|e| WasiFsCreationError::metadata(dir, e))?;
             let kind = if cur_dir_metadata.is_dir() {
                 Kind::Dir {
                     parent: Some(root_inode),
@@ -197,20 +292,12 @@ impl WasiFs {
                     entries: Default::default(),
                 }
             } else {
-                return Err(format!(
-                    "WASI only supports pre-opened directories right now; found \"{}\"",
-                    &dir.to_string_lossy()
-                ));
+                return Err(WasiFsCreationError::NotADirectory(dir.clone()));
             };
             // TODO: handle nested pats in `file`
             let inode = wasi_fs
                 .create_inode(kind, true, dir.to_string_lossy().into_owned())
-                .map_err(|e| {
-                    format!(
-                        "Failed to create inode for preopened dir: WASI error code: {}",
-                        e
-                    )
-                })?;
+                .map_err(|e| WasiFsCreationError::fs("inode of a preopened directory", e))?;
             let fd = wasi_fs
                 .create_fd(
                     default_rights,
@@ -219,13 +306,13 @@ impl WasiFs {
                     Fd::READ | Fd::WRITE,
                     inode,
                 )
-                .map_err(|e| format!("Could not open fd for file {:?}: {}", dir, e))?;
+                .map_err(|e| WasiFsCreationError::fs(format!("fd of {:?}", dir), e))?;
             if let Kind::Root { entries } = &mut wasi_fs.inodes[root_inode].kind {
-                let result = entries.insert(dir.to_string_lossy().into_owned(), inode);
+                let key = wasi_fs.path_policy.key(&dir.to_string_lossy());
+                let result = entries.insert(key, inode);
                 if result.is_some() {
-                    return Err(format!(
-                        "Error: found collision in preopened directory names, `{}`",
-                        dir.to_string_lossy()
+                    return Err(WasiFsCreationError::DuplicateEntry(
+                        dir.to_string_lossy().into_owned(),
                     ));
                 }
             }
@@ -236,13 +323,9 @@ impl WasiFs {
             debug!("Attempting to open {:?} at {}", real_dir, alias);
             // TODO: think about this
             let default_rights = ALL_RIGHTS;
-            let cur_dir_metadata = real_dir.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    &real_dir,
-                    e.to_string()
-                )
-            })?;
+            let cur_dir_metadata = real_dir
+                .metadata()
+                .map_err(|e| WasiFsCreationError::metadata(real_dir, e))?;
             let kind = if cur_dir_metadata.is_dir() {
                 Kind::Dir {
                     parent: Some(root_inode),
@@ -250,20 +333,12 @@ impl WasiFs {
                     entries: Default::default(),
                 }
             } else {
-                return Err(format!(
-                    "WASI only supports pre-opened directories right now; found \"{:?}\"",
-                    &real_dir,
-                ));
+                return Err(WasiFsCreationError::NotADirectory(real_dir.clone()));
             };
             // TODO: handle nested pats in `file`
             let inode = wasi_fs
                 .create_inode(kind, true, alias.clone())
-                .map_err(|e| {
-                    format!(
-                        "Failed to create inode for preopened dir: WASI error code: {}",
-                        e
-                    )
-                })?;
+                .map_err(|e| WasiFsCreationError::fs("inode of a preopened directory", e))?;
             let fd = wasi_fs
                 .create_fd(
                     default_rights,
@@ -272,14 +347,11 @@ impl WasiFs {
                     Fd::READ | Fd::WRITE,
                     inode,
                 )
-                .map_err(|e| format!("Could not open fd for file {:?}: {}", &real_dir, e))?;
+                .map_err(|e| WasiFsCreationError::fs(format!("fd of {:?}", real_dir), e))?;
             if let Kind::Root { entries } = &mut wasi_fs.inodes[root_inode].kind {
-                let result = entries.insert(alias.clone(), inode);
+                let result = entries.insert(wasi_fs.path_policy.key(alias), inode);
                 if result.is_some() {
-                    return Err(format!(
-                        "Error: found collision in preopened directory aliases, `{}`",
-                        alias,
-                    ));
+                    return Err(WasiFsCreationError::DuplicateEntry(alias.clone()));
                 }
             }
             wasi_fs.preopen_fds.push(fd);
@@ -290,8 +362,11 @@ impl WasiFs {
     }
 
     /// Created for the builder API. like `new` but with more information
-    pub(crate) fn new_with_preopen(preopens: &[PreopenedDir]) -> Result<Self, String> {
-        let (mut wasi_fs, root_inode) = Self::new_init()?;
+    pub(crate) fn new_with_preopen(
+        preopens: &[PreopenedDir],
+        path_policy: PathPolicy,
+    ) -> Result<Self, WasiFsCreationError> {
+        let (mut wasi_fs, root_inode) = Self::new_init(path_policy)?;
 
         for PreopenedDir {
             path,
@@ -306,13 +381,9 @@ impl WasiFs {
                 &path.to_string_lossy(),
                 &alias
             );
-            let cur_dir_metadata = path.metadata().map_err(|e| {
-                format!(
-                    "Could not get metadata for file {:?}: {}",
-                    path,
-                    e.to_string()
-                )
-            })?;
+            let cur_dir_metadata = path
+                .metadata()
+                .map_err(|e| WasiFsCreationError::metadata(path, e))?;
 
             let kind = if cur_dir_metadata.is_dir() {
                 Kind::Dir {
@@ -321,10 +392,7 @@ impl WasiFs {
                     entries: Default::default(),
                 }
             } else {
-                return Err(format!(
-                    "WASI only supports pre-opened directories right now; found \"{}\"",
-                    &path.to_string_lossy()
-                ));
+                return Err(WasiFsCreationError::NotADirectory(path.clone()));
             };
 
             let rights = {
@@ -374,12 +442,7 @@ impl WasiFs {
             } else {
                 wasi_fs.create_inode(kind, true, path.to_string_lossy().into_owned())
             }
-            .map_err(|e| {
-                format!(
-                    "Failed to create inode for preopened dir: WASI error code: {}",
-                    e
-                )
-            })?;
+            .map_err(|e| WasiFsCreationError::fs("inode of a preopened directory", e))?;
             let fd_flags = {
                 let mut fd_flags = 0;
                 if *read {
@@ -396,16 +459,16 @@ impl WasiFs {
             };
             let fd = wasi_fs
                 .create_fd(rights, rights, 0, fd_flags, inode)
-                .map_err(|e| format!("Could not open fd for file {:?}: {}", path, e))?;
+                .map_err(|e| WasiFsCreationError::fs(format!("fd of {:?}", path), e))?;
             if let Kind::Root { entries } = &mut wasi_fs.inodes[root_inode].kind {
                 let key = if let Some(alias) = &alias {
                     alias.clone()
                 } else {
                     path.to_string_lossy().into_owned()
                 };
-                let existing_entry = entries.insert(key.clone(), inode);
+                let existing_entry = entries.insert(wasi_fs.path_policy.key(&key), inode);
                 if existing_entry.is_some() {
-                    return Err(format!("Found duplicate entry for alias `{}`", key));
+                    return Err(WasiFsCreationError::DuplicateEntry(key));
                 }
                 assert!(existing_entry.is_none())
             }
@@ -415,19 +478,458 @@ impl WasiFs {
         Ok(wasi_fs)
     }
 
+    /// Preopens a virtual `/dev` directory containing every built-in [`Device`].
+    pub(crate) fn mount_devices(&mut self) -> Result<(), WasiFsCreationError> {
+        let root_inode = self
+            .get_fd(VIRTUAL_ROOT_FD)
+            .map_err(|e| WasiFsCreationError::fs("root inode", e))?
+            .inode;
+        let dev_key = self.path_policy.key("/dev");
+        if let Kind::Root { entries } = &self.inodes[root_inode].kind {
+            if entries.contains_key(&dev_key) {
+                return Err(WasiFsCreationError::DuplicateEntry("/dev".to_string()));
+            }
+        }
+
+        let kind = Kind::Dir {
+            parent: Some(root_inode),
+            path: PathBuf::new(),
+            entries: BTreeMap::new(),
+        };
+        let dev_stat = __wasi_filestat_t {
+            st_filetype: __WASI_FILETYPE_DIRECTORY,
+            st_nlink: 1,
+            ..__wasi_filestat_t::default()
+        };
+        let dev_inode = self.create_inode_with_stat(kind, true, "/dev".to_string(), dev_stat);
+        for device in Device::ALL.iter().copied() {
+            let inode = self
+                .create_inode(Kind::Device { device }, false, device.name().to_string())
+                .map_err(|e| {
+                    WasiFsCreationError::fs(format!("inode of /dev/{}", device.name()), e)
+                })?;
+            if let Kind::Dir { entries, .. } = &mut self.inodes[dev_inode].kind {
+                entries.insert(self.path_policy.key(device.name()), inode);
+            }
+        }
+
+        let rights = __WASI_RIGHT_FD_READ
+            | __WASI_RIGHT_FD_WRITE
+            | __WASI_RIGHT_PATH_OPEN
+            | __WASI_RIGHT_FD_READDIR
+            | __WASI_RIGHT_PATH_FILESTAT_GET
+            | __WASI_RIGHT_FD_FILESTAT_GET
+            | __WASI_RIGHT_POLL_FD_READWRITE;
+        let fd = self
+            .create_fd(rights, rights, 0, Fd::READ | Fd::WRITE, dev_inode)
+            .map_err(|e| WasiFsCreationError::fs("fd of `/dev`", e))?;
+        if let Kind::Root { entries } = &mut self.inodes[root_inode].kind {
+            entries.insert(dev_key, dev_inode);
+        }
+        self.preopen_fds.push(fd);
+        Ok(())
+    }
+
+    /// Preopens a virtual directory `alias` with the contents of `image`.
+    ///
+    /// Its directories are loaded lazily, and its files are copied on
+    /// their first write, so the image is shared with the other instances
+    /// it's mounted in.
+    pub(crate) fn mount_image(
+        &mut self,
+        alias: &str,
+        image: Arc<WasiFsImage>,
+    ) -> Result<(), WasiFsCreationError> {
+        let inode = self.mount_virtual_dir(alias, VIRTUAL_DIR_RIGHTS)?;
+        self.images.insert(inode, (image, PathBuf::new()));
+        Ok(())
+    }
+
+    /// Preopens a virtual directory `alias` with the root of the remote
+    /// filesystem `fs`.
+    ///
+    /// Its directories are loaded lazily, and the operations on its files
+    /// are sent to the file server.
+    pub(crate) fn mount_remote(
+        &mut self,
+        alias: &str,
+        fs: Arc<RemoteFs>,
+    ) -> Result<(), WasiFsCreationError> {
+        let inode = self.mount_virtual_dir(alias, VIRTUAL_DIR_RIGHTS)?;
+        self.remote_dirs.insert(inode, (fs, PathBuf::new(), false));
+        Ok(())
+    }
+
+    /// Returns the remote filesystem of `inode` and its path in it, if
+    /// it's a directory of a mounted remote filesystem.
+    pub(crate) fn remote_dir(&self, inode: Inode) -> Option<(Arc<RemoteFs>, PathBuf)> {
+        self.remote_dirs
+            .get(&inode)
+            .map(|(fs, path, _)| (fs.clone(), path.clone()))
+    }
+
+    /// Preopens a read-only virtual directory `alias` with the objects of
+    /// `fs`, as files named after their keys.
+    ///
+    /// The objects are listed when mounting, and their contents are read
+    /// from the object store when the files are read.
+    pub(crate) fn mount_object_store(
+        &mut self,
+        alias: &str,
+        fs: Arc<ObjectStoreFs>,
+    ) -> Result<(), WasiFsCreationError> {
+        let objects = fs.list().map_err(|e| WasiFsCreationError::Fs {
+            what: format!("listing of `{}`", alias),
+            source: e.into(),
+        })?;
+        let rights = __WASI_RIGHT_FD_ADVISE
+            | __WASI_RIGHT_FD_TELL
+            | __WASI_RIGHT_FD_SEEK
+            | __WASI_RIGHT_FD_READ
+            | __WASI_RIGHT_FD_READDIR
+            | __WASI_RIGHT_FD_FILESTAT_GET
+            | __WASI_RIGHT_PATH_OPEN
+            | __WASI_RIGHT_PATH_FILESTAT_GET
+            | __WASI_RIGHT_POLL_FD_READWRITE;
+        let root = self.mount_virtual_dir(alias, rights)?;
+
+        let mut dirs = BTreeMap::new();
+        for (key, size) in objects {
+            // the keys ending with a `/`, such as the folders of S3, are
+            // directories
+            let key_is_dir = key.ends_with('/');
+            let mut names = key.split('/').filter(|name| !name.is_empty()).peekable();
+            let mut parent = root;
+            let mut dir_path = String::new();
+            while let Some(name) = names.next() {
+                let is_dir = names.peek().is_some() || key_is_dir;
+                if is_dir {
+                    dir_path.push('/');
+                    dir_path.push_str(name);
+                    if let Some(dir) = dirs.get(&dir_path) {
+                        parent = *dir;
+                        continue;
+                    }
+                }
+                // a file and a directory, or two files, with the same name
+                let name_key = self.path_policy.key(name);
+                if let Kind::Dir { entries, .. } = &self.inodes[parent].kind {
+                    if entries.contains_key(&name_key) {
+                        return Err(WasiFsCreationError::DuplicateEntry(key.clone()));
+                    }
+                }
+                let child = if is_dir {
+                    let dir = self.create_virtual_dir_inode(parent, name.to_string(), false);
+                    dirs.insert(dir_path.clone(), dir);
+                    dir
+                } else {
+                    let kind = Kind::File {
+                        handle: Some(Box::new(ObjectFile::new(fs.clone(), key.clone(), size))),
+                        path: PathBuf::new(),
+                        fd: None,
+                    };
+                    let stat = __wasi_filestat_t {
+                        st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                        st_nlink: 1,
+                        st_size: size,
+                        ..__wasi_filestat_t::default()
+                    };
+                    self.create_inode_with_stat(kind, false, name.to_string(), stat)
+                };
+                if let Kind::Dir { entries, .. } = &mut self.inodes[parent].kind {
+                    entries.insert(name_key, child);
+                }
+                parent = child;
+            }
+        }
+        Ok(())
+    }
+
+    /// Preopens an empty virtual directory `alias`, with `rights`.
+    fn mount_virtual_dir(
+        &mut self,
+        alias: &str,
+        rights: __wasi_rights_t,
+    ) -> Result<Inode, WasiFsCreationError> {
+        let root_inode = self
+            .get_fd(VIRTUAL_ROOT_FD)
+            .map_err(|e| WasiFsCreationError::fs("root inode", e))?
+            .inode;
+        let key = self.path_policy.key(alias);
+        if let Kind::Root { entries } = &self.inodes[root_inode].kind {
+            if entries.contains_key(&key) {
+                return Err(WasiFsCreationError::DuplicateEntry(alias.to_string()));
+            }
+        }
+
+        let inode = self.create_virtual_dir_inode(root_inode, alias.to_string(), true);
+        let fd = self
+            .create_fd(rights, rights, 0, Fd::READ | Fd::WRITE, inode)
+            .map_err(|e| WasiFsCreationError::fs(format!("fd of `{}`", alias), e))?;
+        if let Kind::Root { entries } = &mut self.inodes[root_inode].kind {
+            entries.insert(key, inode);
+        }
+        self.preopen_fds.push(fd);
+        Ok(inode)
+    }
+
+    fn create_virtual_dir_inode(
+        &mut self,
+        parent: Inode,
+        name: String,
+        is_preopened: bool,
+    ) -> Inode {
+        let kind = Kind::Dir {
+            parent: Some(parent),
+            path: PathBuf::new(),
+            entries: BTreeMap::new(),
+        };
+        let stat = __wasi_filestat_t {
+            st_filetype: __WASI_FILETYPE_DIRECTORY,
+            st_nlink: 1,
+            ..__wasi_filestat_t::default()
+        };
+        self.create_inode_with_stat(kind, is_preopened, name, stat)
+    }
+
+    /// Admits a read, or a write, of a file if it's within the
+    /// [`IoLimits`].  Otherwise returns how long to wait before trying
+    /// again, or `__WASI_EAGAIN` if the limits say not to wait.
+    pub(crate) fn throttle_io(&mut self, write: bool) -> Result<Option<Duration>, __wasi_errno_t> {
+        match self.io_throttle.admit(&self.io_limits, write) {
+            Ok(()) => Ok(None),
+            Err(wait) => match self.io_limits.mode {
+                ThrottleMode::Block => Ok(Some(wait)),
+                ThrottleMode::Again => Err(__WASI_EAGAIN),
+            },
+        }
+    }
+
+    /// Charges the bytes read, or written, by a call admitted by
+    /// [`WasiFs::throttle_io`].
+    pub(crate) fn charge_io(&mut self, write: bool, bytes: usize) {
+        self.io_throttle.charge(&self.io_limits, write, bytes)
+    }
+
+    /// Sets how long the cached entries of host directories are trusted.
+    /// With `FreshnessPolicy::Watch`, the preopened directories are watched
+    /// from now on.
+    pub fn set_freshness_policy(&mut self, policy: FreshnessPolicy) -> Result<(), WasiFsError> {
+        #[cfg(feature = "notify")]
+        {
+            self.host_watcher = None;
+            if policy == FreshnessPolicy::Watch {
+                let paths = self
+                    .preopen_fds
+                    .iter()
+                    .filter_map(|fd| self.fd_map.get(fd))
+                    .filter_map(|fd| match &self.inodes[fd.inode].kind {
+                        Kind::Dir { path, .. } if !path.as_os_str().is_empty() => {
+                            Some(path.clone())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let watcher = HostWatcher::new(&paths).map_err(|e| {
+                    debug!("Could not watch the preopened directories: {}", e);
+                    WasiFsError::IOError
+                })?;
+                self.host_watcher = Some(std::sync::Mutex::new(watcher));
+            }
+        }
+        self.freshness_policy = policy;
+        self.validated_at.clear();
+        Ok(())
+    }
+
+    /// Drops the cached entries under the host directory `inode`, and their
+    /// metadata, so they're loaded again from the host when looked up.
+    ///
+    /// The entries of open fds are kept.
+    pub fn invalidate(&mut self, inode: Inode) {
+        if !self.inodes.contains(inode) {
+            return;
+        }
+        let pinned = self.pinned_inodes();
+        let mut reachable = BTreeSet::new();
+        self.prune_inode(inode, &pinned, &mut reachable);
+    }
+
+    /// Invalidates the cached entries of the host directory `inode` if
+    /// they're older than allowed by `FreshnessPolicy::MaxAge`.
+    fn revalidate_host_dir(&mut self, inode: Inode) {
+        let max_age = match self.freshness_policy {
+            FreshnessPolicy::MaxAge(max_age) => max_age,
+            _ => return,
+        };
+        match &self.inodes[inode].kind {
+            Kind::Dir { path, .. } if !path.as_os_str().is_empty() => {}
+            _ => return,
+        }
+        let now = Instant::now();
+        let fresh = self.validated_at.get(&inode).map_or(false, |validated_at| {
+            now.duration_since(*validated_at) <= max_age
+        });
+        if !fresh {
+            self.invalidate(inode);
+            self.validated_at.insert(inode, now);
+        }
+    }
+
+    /// Invalidates the host directories the host notified changes in, for
+    /// `FreshnessPolicy::Watch`.
+    ///
+    /// Applied before resolving a path rather than along it, so the
+    /// directories on the path stay attached to their parent.
+    #[cfg(feature = "notify")]
+    fn apply_host_changes(&mut self) {
+        let changed_dirs = match &self.host_watcher {
+            Some(watcher) => watcher.lock().unwrap().changed_dirs(),
+            None => return,
+        };
+        if changed_dirs.as_ref().map_or(false, BTreeSet::is_empty) {
+            return;
+        }
+        // the host reports canonical paths
+        let stale = self
+            .inodes
+            .iter()
+            .filter_map(|(inode, iv)| match &iv.kind {
+                Kind::Dir { path, .. } if !path.as_os_str().is_empty() => Some((inode, path)),
+                _ => None,
+            })
+            .filter(|(_, path)| match &changed_dirs {
+                Some(changed_dirs) => {
+                    fs::canonicalize(path).map_or(true, |path| changed_dirs.contains(&path))
+                }
+                None => true,
+            })
+            .map(|(inode, _)| inode)
+            .collect::<Vec<_>>();
+        for inode in stale {
+            self.invalidate(inode);
+        }
+    }
+
+    /// Loads the entries of `inode` from its image or remote filesystem,
+    /// if it's a directory of one of them that isn't loaded yet.
+    ///
+    /// A remote directory that couldn't be listed is left unloaded, to be
+    /// listed again on the next lookup, and `__WASI_EIO` is returned.
+    pub(crate) fn load_virtual_dir(&mut self, inode: Inode) -> Result<(), __wasi_errno_t> {
+        self.load_remote_dir(inode)?;
+        let (image, path) = match self.images.remove(&inode) {
+            Some(image_dir) => image_dir,
+            None => return Ok(()),
+        };
+        let dir = match image.dir(&path) {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        for (name, entry) in dir.entries.iter() {
+            let child = match entry {
+                ImageEntry::Dir(_) => {
+                    let child = self.create_virtual_dir_inode(inode, name.clone(), false);
+                    self.images.insert(child, (image.clone(), path.join(name)));
+                    child
+                }
+                ImageEntry::File(contents) => {
+                    let kind = Kind::File {
+                        handle: Some(Box::new(ImageFile::new(contents.clone()))),
+                        path: PathBuf::new(),
+                        fd: None,
+                    };
+                    let stat = __wasi_filestat_t {
+                        st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                        st_nlink: 1,
+                        st_size: contents.len() as u64,
+                        ..__wasi_filestat_t::default()
+                    };
+                    self.create_inode_with_stat(kind, false, name.clone(), stat)
+                }
+            };
+            let key = self.path_policy.key(name);
+            if let Kind::Dir { entries, .. } = &mut self.inodes[inode].kind {
+                entries.insert(key, child);
+            }
+        }
+        Ok(())
+    }
+
+    fn load_remote_dir(&mut self, inode: Inode) -> Result<(), __wasi_errno_t> {
+        let (fs, path) = match self.remote_dirs.get(&inode) {
+            Some((fs, path, loaded)) if !*loaded => (fs.clone(), path.clone()),
+            _ => return Ok(()),
+        };
+        let remote_entries = match fs.read_dir(&path) {
+            Ok(remote_entries) => remote_entries,
+            Err(e) => {
+                debug!("Could not read the remote directory {:?}: {}", path, e);
+                return Err(__WASI_EIO);
+            }
+        };
+        if let Some((_, _, loaded)) = self.remote_dirs.get_mut(&inode) {
+            *loaded = true;
+        }
+        for entry in remote_entries {
+            let child = if entry.is_dir {
+                let child = self.create_virtual_dir_inode(inode, entry.name.clone(), false);
+                self.remote_dirs
+                    .insert(child, (fs.clone(), path.join(&entry.name), false));
+                child
+            } else {
+                let kind = Kind::File {
+                    handle: Some(Box::new(RemoteFile::new(
+                        fs.clone(),
+                        path.join(&entry.name),
+                    ))),
+                    path: PathBuf::new(),
+                    fd: None,
+                };
+                let stat = __wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_nlink: 1,
+                    st_size: entry.size,
+                    ..__wasi_filestat_t::default()
+                };
+                self.create_inode_with_stat(kind, false, entry.name.clone(), stat)
+            };
+            let key = self.path_policy.key(&entry.name);
+            if let Kind::Dir { entries, .. } = &mut self.inodes[inode].kind {
+                entries.insert(key, child);
+            }
+        }
+        Ok(())
+    }
+
     /// Private helper function to init the filesystem, called in `new` and
     /// `new_with_preopen`
-    fn new_init() -> Result<(Self, Inode), String> {
+    fn new_init(path_policy: PathPolicy) -> Result<(Self, Inode), WasiFsCreationError> {
         debug!("Initializing WASI filesystem");
         let inodes = Arena::new();
         let mut wasi_fs = Self {
             preopen_fds: vec![],
-            name_map: HashMap::new(),
+            name_map: BTreeMap::new(),
             inodes,
-            fd_map: HashMap::new(),
-            next_fd: Cell::new(3),
-            inode_counter: Cell::new(1024),
-            orphan_fds: HashMap::new(),
+            fd_map: BTreeMap::new(),
+            next_fd: AtomicU32::new(3),
+            inode_counter: AtomicU64::new(1024),
+            orphan_fds: BTreeMap::new(),
+            path_policy,
+            max_open_fds: None,
+            tty_policy: TtyPolicy::default(),
+            tty: __wasi_tty_t::default(),
+            host_tty_changes: false,
+            saved_host_ttys: BTreeMap::new(),
+            freshness_policy: FreshnessPolicy::default(),
+            validated_at: BTreeMap::new(),
+            #[cfg(feature = "notify")]
+            host_watcher: None,
+            next_gc: MIN_GC_INODES,
+            images: BTreeMap::new(),
+            remote_dirs: BTreeMap::new(),
+            io_limits: IoLimits::default(),
+            io_throttle: IoThrottle::default(),
         };
         wasi_fs.create_stdin();
         wasi_fs.create_stdout();
@@ -456,7 +958,7 @@ impl WasiFs {
             let inode = wasi_fs.create_virtual_root();
             let fd = wasi_fs
                 .create_fd(root_rights, root_rights, 0, Fd::READ, inode)
-                .map_err(|e| format!("Could not create root fd: {}", e))?;
+                .map_err(|e| WasiFsCreationError::fs("root fd", e))?;
             wasi_fs.preopen_fds.push(fd);
             inode
         };
@@ -525,11 +1027,14 @@ impl WasiFs {
         }
     }
 
+    /// The fd that will be given to the next file opened.
+    pub fn next_fd(&self) -> __wasi_fd_t {
+        self.next_fd.load(Ordering::Acquire)
+    }
+
     /// Returns the next available inode index for creating a new inode.
-    fn get_next_inode_index(&mut self) -> u64 {
-        let next = self.inode_counter.get();
-        self.inode_counter.set(next + 1);
-        next
+    fn get_next_inode_index(&self) -> u64 {
+        self.inode_counter.fetch_add(1, Ordering::AcqRel)
     }
 
     /// This function is like create dir all, but it also opens it.
@@ -550,6 +1055,7 @@ impl WasiFs {
         rights_inheriting: __wasi_rights_t,
         flags: __wasi_fdflags_t,
     ) -> Result<__wasi_fd_t, WasiFsError> {
+        self.check_fd_limit().map_err(WasiFsError::from_wasi_err)?;
         let base_fd = self.get_fd(base).map_err(WasiFsError::from_wasi_err)?;
         // TODO: check permissions here? probably not, but this should be
         // an explicit choice, so justify it in a comment when we remove this one
@@ -558,10 +1064,11 @@ impl WasiFs {
         let path: &Path = Path::new(&name);
         //let n_components = path.components().count();
         for c in path.components() {
-            let segment_name = c.as_os_str().to_string_lossy().to_string();
+            let segment_name = self.path_policy.normalize(&c.as_os_str().to_string_lossy());
+            let segment_key = self.path_policy.key(&segment_name);
             match &self.inodes[cur_inode].kind {
                 Kind::Dir { ref entries, .. } | Kind::Root { ref entries } => {
-                    if let Some(_entry) = entries.get(&segment_name) {
+                    if let Some(_entry) = entries.get(&segment_key) {
                         // TODO: this should be fixed
                         return Err(WasiFsError::AlreadyExists);
                     }
@@ -569,18 +1076,17 @@ impl WasiFs {
                     let kind = Kind::Dir {
                         parent: Some(cur_inode),
                         path: PathBuf::from(""),
-                        entries: HashMap::new(),
+                        entries: BTreeMap::new(),
                     };
 
-                    let inode =
-                        self.create_inode_with_default_stat(kind, false, segment_name.clone());
+                    let inode = self.create_inode_with_default_stat(kind, false, segment_name);
                     // reborrow to insert
                     match &mut self.inodes[cur_inode].kind {
                         Kind::Dir {
                             ref mut entries, ..
                         }
                         | Kind::Root { ref mut entries } => {
-                            entries.insert(segment_name, inode);
+                            entries.insert(segment_key, inode);
                         }
                         _ => unreachable!("Dir or Root became not Dir or Root"),
                     }
@@ -616,14 +1122,17 @@ impl WasiFs {
         rights_inheriting: __wasi_rights_t,
         flags: __wasi_fdflags_t,
     ) -> Result<__wasi_fd_t, WasiFsError> {
+        self.check_fd_limit().map_err(WasiFsError::from_wasi_err)?;
         let base_fd = self.get_fd(base).map_err(WasiFsError::from_wasi_err)?;
         // TODO: check permissions here? probably not, but this should be
         // an explicit choice, so justify it in a comment when we remove this one
         let base_inode = base_fd.inode;
+        let name = self.path_policy.normalize(&name);
+        let key = self.path_policy.key(&name);
 
         match &self.inodes[base_inode].kind {
             Kind::Dir { ref entries, .. } | Kind::Root { ref entries } => {
-                if let Some(_entry) = entries.get(&name) {
+                if let Some(_entry) = entries.get(&key) {
                     // TODO: eventually change the logic here to allow overwrites
                     return Err(WasiFsError::AlreadyExists);
                 }
@@ -631,11 +1140,11 @@ impl WasiFs {
                 let kind = Kind::File {
                     handle: Some(file),
                     path: PathBuf::from(""),
-                    fd: Some(self.next_fd.get()),
+                    fd: Some(self.next_fd()),
                 };
 
                 let inode = self
-                    .create_inode(kind, false, name.clone())
+                    .create_inode(kind, false, name)
                     .map_err(|_| WasiFsError::IOError)?;
                 // reborrow to insert
                 match &mut self.inodes[base_inode].kind {
@@ -643,7 +1152,7 @@ impl WasiFs {
                         ref mut entries, ..
                     }
                     | Kind::Root { ref mut entries } => {
-                        entries.insert(name, inode);
+                        entries.insert(key, inode);
                     }
                     _ => unreachable!("Dir or Root became not Dir or Root"),
                 }
@@ -736,8 +1245,11 @@ impl WasiFs {
             return Err(__WASI_EMLINK);
         }
 
+        #[cfg(feature = "notify")]
+        self.apply_host_changes();
         let base_dir = self.get_fd(base)?;
         let path: &Path = Path::new(path);
+        let path_policy = self.path_policy;
 
         let mut cur_inode = base_dir.inode;
         let n_components = path.components().count();
@@ -748,8 +1260,10 @@ impl WasiFs {
             // for each component traverse file structure
             // loading inodes as necessary
             'symlink_resolution: while symlink_count < MAX_SYMLINKS {
+                self.load_virtual_dir(cur_inode)?;
+                self.revalidate_host_dir(cur_inode);
                 match &mut self.inodes[cur_inode].kind {
-                    Kind::Buffer { .. } => unimplemented!("state::get_inode_at_path for buffers"),
+                    Kind::Buffer { .. } => return Err(__WASI_ENOTDIR),
                     Kind::Dir {
                         ref mut entries,
                         ref path,
@@ -770,14 +1284,17 @@ impl WasiFs {
                         }
                         // used for full resolution of symlinks
                         let mut loop_for_symlink = false;
-                        if let Some(entry) =
-                            entries.get(component.as_os_str().to_string_lossy().as_ref())
-                        {
+                        let component_name = component.as_os_str().to_string_lossy();
+                        let entry_key = path_policy.key(&component_name);
+                        if let Some(entry) = entries.get(&entry_key) {
                             cur_inode = *entry;
                         } else {
+                            let host_name = path_policy
+                                .find_host_entry(path, component.as_os_str())
+                                .ok_or(__WASI_EINVAL)?;
                             let file = {
                                 let mut cd = path.clone();
-                                cd.push(component);
+                                cd.push(host_name);
                                 cd
                             };
                             let metadata = file.symlink_metadata().ok().ok_or(__WASI_EINVAL)?;
@@ -809,7 +1326,8 @@ impl WasiFs {
                                 let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
                                     self.path_into_pre_open_and_relative_path(&file)?
                                 } else {
-                                    unimplemented!("Absolute symlinks are not yet supported");
+                                    // Absolute symlinks are not yet supported.
+                                    return Err(__WASI_ENOTSUP);
                                 };
                                 loop_for_symlink = true;
                                 symlink_count += 1;
@@ -835,7 +1353,9 @@ impl WasiFs {
                                         // a `__WASI_FILETYPE_SOCKET_DGRAM`?
                                         __WASI_FILETYPE_SOCKET_STREAM
                                     } else {
-                                        unimplemented!("state::get_inode_at_path unknown file type: not file, directory, symlink, char device, block device, fifo, or socket");
+                                        // Not a file, directory, symlink, char device,
+                                        // block device, fifo, or socket.
+                                        return Err(__WASI_ENOTSUP);
                                     };
 
                                     let kind = Kind::File {
@@ -856,10 +1376,7 @@ impl WasiFs {
                                         ref mut entries, ..
                                     } = &mut self.inodes[cur_inode].kind
                                     {
-                                        entries.insert(
-                                            component.as_os_str().to_string_lossy().to_string(),
-                                            new_inode,
-                                        );
+                                        entries.insert(entry_key, new_inode);
                                     } else {
                                         unreachable!(
                                             "Attempted to insert special device into non-directory"
@@ -868,8 +1385,9 @@ impl WasiFs {
                                     // perhaps just continue with symlink resolution and return at the end
                                     return Ok(new_inode);
                                 }
+                                // Not a file, directory, or symlink.
                                 #[cfg(not(unix))]
-                                unimplemented!("state::get_inode_at_path unknown file type: not file, directory, or symlink");
+                                return Err(__WASI_ENOTSUP);
                             };
 
                             let new_inode =
@@ -879,10 +1397,7 @@ impl WasiFs {
                                     ref mut entries, ..
                                 } = &mut self.inodes[cur_inode].kind
                                 {
-                                    entries.insert(
-                                        component.as_os_str().to_string_lossy().to_string(),
-                                        new_inode,
-                                    );
+                                    entries.insert(entry_key, new_inode);
                                 }
                             }
                             cur_inode = new_inode;
@@ -902,15 +1417,15 @@ impl WasiFs {
                             _ => (),
                         }
 
-                        if let Some(entry) =
-                            entries.get(component.as_os_str().to_string_lossy().as_ref())
-                        {
+                        let entry_key =
+                            path_policy.key(component.as_os_str().to_string_lossy().as_ref());
+                        if let Some(entry) = entries.get(&entry_key) {
                             cur_inode = *entry;
                         } else {
                             return Err(__WASI_EINVAL);
                         }
                     }
-                    Kind::File { .. } => {
+                    Kind::File { .. } | Kind::Device { .. } => {
                         return Err(__WASI_ENOTDIR);
                     }
                     Kind::Symlink {
@@ -954,6 +1469,7 @@ impl WasiFs {
             }
         }
 
+        self.load_virtual_dir(cur_inode)?;
         Ok(cur_inode)
     }
 
@@ -1063,7 +1579,7 @@ impl WasiFs {
     }
 
     /// Returns the parent Dir or Root that the file at a given path is in and the file name
-    /// stripped off
+    /// stripped off, normalized according to the [`PathPolicy`]
     pub(crate) fn get_parent_inode_at_path(
         &mut self,
         base: __wasi_fd_t,
@@ -1072,12 +1588,13 @@ impl WasiFs {
     ) -> Result<(Inode, String), __wasi_errno_t> {
         let mut parent_dir = std::path::PathBuf::new();
         let mut components = path.components().rev();
-        let new_entity_name = components
-            .next()
-            .ok_or(__WASI_EINVAL)?
-            .as_os_str()
-            .to_string_lossy()
-            .to_string();
+        let new_entity_name = self.path_policy.normalize(
+            &components
+                .next()
+                .ok_or(__WASI_EINVAL)?
+                .as_os_str()
+                .to_string_lossy(),
+        );
         for comp in components.rev() {
             parent_dir.push(comp);
         }
@@ -1105,11 +1622,74 @@ impl WasiFs {
         Ok(self.inodes[fd.inode].stat)
     }
 
+    /// The stdio fd to use in place of `fd` when it's `/dev/tty`: stdout when
+    /// `write`, stdin otherwise.  The terminal of the guest is its stdio, as
+    /// set on the [`WasiStateBuilder`], never the host's own.  Other fds are
+    /// returned as is, as are the fds lacking the rights to read or write.
+    pub(crate) fn tty_stdio_fd(&self, fd: __wasi_fd_t, write: bool) -> __wasi_fd_t {
+        let (right, stdio_fd) = if write {
+            (__WASI_RIGHT_FD_WRITE, __WASI_STDOUT_FILENO)
+        } else {
+            (__WASI_RIGHT_FD_READ, __WASI_STDIN_FILENO)
+        };
+        match self.fd_map.get(&fd) {
+            Some(entry)
+                if entry.rights & right != 0
+                    && matches!(
+                        self.inodes.get(entry.inode).map(|inode| &inode.kind),
+                        Some(Kind::Device {
+                            device: Device::Tty
+                        })
+                    ) =>
+            {
+                stdio_fd
+            }
+            _ => fd,
+        }
+    }
+
+    /// Whether `fd` is a stdio fd that is reported as a terminal, according to
+    /// the [`TtyPolicy`].
+    pub fn is_tty(&self, fd: __wasi_fd_t) -> bool {
+        let file = match fd {
+            __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => {
+                self.std_dev_get(fd)
+            }
+            _ => return false,
+        };
+        match self.tty_policy {
+            TtyPolicy::Always => true,
+            TtyPolicy::Never => false,
+            TtyPolicy::Host => matches!(file, Ok(Some(file)) if file.is_tty()),
+        }
+    }
+
+    /// Save the settings of the host terminal of the stdio `fd` before the
+    /// guest first changes them, to restore them when the filesystem is
+    /// dropped.
+    pub(crate) fn save_host_tty(&mut self, fd: __wasi_fd_t) {
+        if !self.saved_host_ttys.contains_key(&fd) {
+            if let Some(settings) = platform_tty_save(fd as i32) {
+                self.saved_host_ttys.insert(fd, settings);
+            }
+        }
+    }
+
+    /// The filetype of a stdio fd: terminals are character devices, anything
+    /// else is reported as unknown, like a host pipe.
+    fn stdio_filetype(&self, fd: __wasi_fd_t) -> __wasi_filetype_t {
+        if self.is_tty(fd) {
+            __WASI_FILETYPE_CHARACTER_DEVICE
+        } else {
+            __WASI_FILETYPE_UNKNOWN
+        }
+    }
+
     pub fn fdstat(&self, fd: __wasi_fd_t) -> Result<__wasi_fdstat_t, __wasi_errno_t> {
         match fd {
             __WASI_STDIN_FILENO => {
                 return Ok(__wasi_fdstat_t {
-                    fs_filetype: __WASI_FILETYPE_CHARACTER_DEVICE,
+                    fs_filetype: self.stdio_filetype(fd),
                     fs_flags: 0,
                     fs_rights_base: STDIN_DEFAULT_RIGHTS,
                     fs_rights_inheriting: 0,
@@ -1117,7 +1697,7 @@ impl WasiFs {
             }
             __WASI_STDOUT_FILENO => {
                 return Ok(__wasi_fdstat_t {
-                    fs_filetype: __WASI_FILETYPE_CHARACTER_DEVICE,
+                    fs_filetype: self.stdio_filetype(fd),
                     fs_flags: __WASI_FDFLAG_APPEND,
                     fs_rights_base: STDOUT_DEFAULT_RIGHTS,
                     fs_rights_inheriting: 0,
@@ -1125,7 +1705,7 @@ impl WasiFs {
             }
             __WASI_STDERR_FILENO => {
                 return Ok(__wasi_fdstat_t {
-                    fs_filetype: __WASI_FILETYPE_CHARACTER_DEVICE,
+                    fs_filetype: self.stdio_filetype(fd),
                     fs_flags: __WASI_FDFLAG_APPEND,
                     fs_rights_base: STDERR_DEFAULT_RIGHTS,
                     fs_rights_inheriting: 0,
@@ -1151,6 +1731,7 @@ impl WasiFs {
                 Kind::File { .. } => __WASI_FILETYPE_REGULAR_FILE,
                 Kind::Dir { .. } => __WASI_FILETYPE_DIRECTORY,
                 Kind::Symlink { .. } => __WASI_FILETYPE_SYMBOLIC_LINK,
+                Kind::Device { .. } => __WASI_FILETYPE_CHARACTER_DEVICE,
                 _ => __WASI_FILETYPE_UNKNOWN,
             },
             fs_flags: fd.flags,
@@ -1212,8 +1793,9 @@ impl WasiFs {
                     }
                     // TODO: verify this behavior
                     Kind::Dir { .. } => return Err(__WASI_EISDIR),
-                    Kind::Symlink { .. } => unimplemented!("WasiFs::flush Kind::Symlink"),
+                    Kind::Symlink { .. } => return Err(__WASI_EINVAL),
                     Kind::Buffer { .. } => (),
+                    Kind::Device { device } => device.flush().map_err(|_| __WASI_EIO)?,
                     _ => return Err(__WASI_EIO),
                 }
             }
@@ -1261,6 +1843,15 @@ impl WasiFs {
         })
     }
 
+    /// Returns `__WASI_EMFILE` if another fd can't be opened without going over
+    /// [`WasiFs::max_open_fds`]
+    pub(crate) fn check_fd_limit(&self) -> Result<(), __wasi_errno_t> {
+        match self.max_open_fds {
+            Some(max) if self.fd_map.len() >= max as usize => Err(__WASI_EMFILE),
+            _ => Ok(()),
+        }
+    }
+
     pub fn create_fd(
         &mut self,
         rights: __wasi_rights_t,
@@ -1269,8 +1860,8 @@ impl WasiFs {
         open_flags: u16,
         inode: Inode,
     ) -> Result<__wasi_fd_t, __wasi_errno_t> {
-        let idx = self.next_fd.get();
-        self.next_fd.set(idx + 1);
+        self.check_fd_limit()?;
+        let idx = self.next_fd.fetch_add(1, Ordering::AcqRel);
         self.fd_map.insert(
             idx,
             Fd {
@@ -1304,7 +1895,7 @@ impl WasiFs {
             ..__wasi_filestat_t::default()
         };
         let root_kind = Kind::Root {
-            entries: HashMap::new(),
+            entries: BTreeMap::new(),
         };
 
         self.inodes.insert(InodeVal {
@@ -1424,10 +2015,18 @@ impl WasiFs {
                     _ => unreachable!("Symlink pointing to something that's not a directory as its base preopened directory"),
                 }
             }
+            Kind::Device { .. } => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_CHARACTER_DEVICE,
+                    st_nlink: 1,
+                    ..__wasi_filestat_t::default()
+                })
+            }
             _ => return None,
         };
         Some(__wasi_filestat_t {
             st_filetype: host_file_type_to_wasi_file_type(md.file_type()),
+            st_nlink: host_nlink(&md),
             st_size: md.len(),
             st_atim: md
                 .accessed()
@@ -1451,60 +2050,202 @@ impl WasiFs {
         })
     }
 
+    /// Sets the link count of every inode that refers to the file `st_ino`.
+    ///
+    /// Host-backed hard links get one inode per directory entry, so the link
+    /// count has to be kept in sync across all of them.
+    pub(crate) fn set_nlink(&mut self, st_ino: __wasi_inode_t, nlink: __wasi_linkcount_t) {
+        for (_, inode_val) in self.inodes.iter_mut() {
+            if inode_val.stat.st_ino == st_ino {
+                inode_val.stat.st_nlink = nlink;
+            }
+        }
+    }
+
     /// Closes an open FD, handling all details such as FD being preopen
+    ///
+    /// The host handle of a file is closed when the last fd referring to it is
+    /// closed.  Inodes that are no longer needed are reclaimed from time to time
+    /// by [`WasiFs::collect_garbage`].
     pub(crate) fn close_fd(&mut self, fd: __wasi_fd_t) -> Result<(), __wasi_errno_t> {
-        let inodeval_mut = self.get_inodeval_mut(fd)?;
-        let is_preopened = inodeval_mut.is_preopened;
-
-        match &mut inodeval_mut.kind {
-            Kind::File { ref mut handle, .. } => {
-                let mut empty_handle = None;
-                std::mem::swap(handle, &mut empty_handle);
+        let path_policy = self.path_policy;
+        let inode = self.get_fd(fd)?.inode;
+        let inodeval = self.get_inodeval_mut(fd)?;
+        let is_preopened = inodeval.is_preopened;
+        let key = path_policy.key(&inodeval.name);
+
+        match &inodeval.kind {
+            Kind::File { fd: Some(_), .. } => {
+                // special files can only have a single fd which is handed out again
+                // when they're looked up by path, so they stay open
+                debug!("Not closing special file {:?}", &inodeval.name);
+                return Ok(());
             }
             Kind::Dir { parent, path, .. } => {
                 debug!("Closing dir {:?}", &path);
-                let key = path
-                    .file_name()
-                    .ok_or(__WASI_EINVAL)?
-                    .to_string_lossy()
-                    .to_string();
-                if let Some(p) = *parent {
-                    match &mut self.inodes[p].kind {
+                let parent = match *parent {
+                    Some(p) => p,
+                    None => {
+                        // this shouldn't be possible anymore due to Root
+                        debug!("HIT UNREACHABLE CODE! Non-root directory does not have a parent");
+                        return Err(__WASI_EINVAL);
+                    }
+                };
+                // only remove the entry if this is the original preopen FD, calling
+                // `path_open` can give you an fd to the same inode as a preopen fd
+                let preopen_idx = if is_preopened {
+                    self.preopen_fds.iter().position(|po_fd| *po_fd == fd)
+                } else {
+                    None
+                };
+                if let Some(i) = preopen_idx {
+                    match &mut self.inodes[parent].kind {
                         Kind::Dir { entries, .. } | Kind::Root { entries } => {
-                            self.fd_map.remove(&fd).unwrap();
-                            if is_preopened {
-                                let mut idx = None;
-                                for (i, po_fd) in self.preopen_fds.iter().enumerate() {
-                                    if *po_fd == fd {
-                                        idx = Some(i);
-                                        break;
-                                    }
-                                }
-                                if let Some(i) = idx {
-                                    // only remove entry properly if this is the original preopen FD
-                                    // calling `path_open` can give you an fd to the same inode as a preopen fd
-                                    entries.remove(&key);
-                                    self.preopen_fds.remove(i);
-                                    // Maybe recursively closes fds if original preopen?
-                                }
-                            }
+                            entries.remove(&key);
                         }
                         _ => unreachable!(
                             "Fatal internal logic error, directory's parent is not a directory"
                         ),
                     }
-                } else {
-                    // this shouldn't be possible anymore due to Root
-                    debug!("HIT UNREACHABLE CODE! Non-root directory does not have a parent");
-                    return Err(__WASI_EINVAL);
+                    self.preopen_fds.remove(i);
                 }
             }
             Kind::Root { .. } => return Err(__WASI_EACCES),
-            Kind::Symlink { .. } | Kind::Buffer { .. } => return Err(__WASI_EINVAL),
+            Kind::File { .. }
+            | Kind::Symlink { .. }
+            | Kind::Buffer { .. }
+            | Kind::Device { .. } => (),
+        }
+
+        self.fd_map.remove(&fd);
+        if !self.fd_map.values().any(|open_fd| open_fd.inode == inode) {
+            // dropping the inode of a deleted file or the handle of a regular
+            // file closes the file on the host
+            if self.orphan_fds.remove(&inode).is_none() {
+                if let Some(InodeVal {
+                    kind: Kind::File { handle, .. },
+                    ..
+                }) = self.inodes.get_mut(inode)
+                {
+                    *handle = None;
+                }
+            }
+        }
+
+        if self.inodes.len() >= self.next_gc {
+            let collected = self.collect_garbage();
+            debug!("Reclaimed {} inodes", collected);
+            self.next_gc = std::cmp::max(self.inodes.len() * 2, MIN_GC_INODES);
         }
 
         Ok(())
     }
+
+    /// Reclaims inodes that are no longer needed.
+    ///
+    /// Files and directories that were loaded from the host and aren't used by any
+    /// open fd are forgotten; they're loaded again the next time they're looked up.
+    /// Inodes that can't be reached from the root or from an open fd, such as
+    /// symlinks created during path resolution, are removed.
+    ///
+    /// Returns the number of inodes that were removed.
+    pub fn collect_garbage(&mut self) -> usize {
+        let pinned = self.pinned_inodes();
+
+        let mut reachable = BTreeSet::new();
+        let roots = self
+            .inodes
+            .iter()
+            .filter(|(_, iv)| iv.is_preopened)
+            .map(|(inode, _)| inode)
+            .chain(pinned.iter().cloned())
+            .chain(self.name_map.values().cloned())
+            .collect::<Vec<_>>();
+        for inode in roots {
+            if self.inodes.contains(inode) {
+                self.prune_inode(inode, &pinned, &mut reachable);
+            }
+        }
+
+        let garbage = self
+            .inodes
+            .iter()
+            .filter(|(inode, iv)| !iv.is_preopened && !reachable.contains(inode))
+            .map(|(inode, _)| inode)
+            .collect::<Vec<_>>();
+        for inode in garbage.iter() {
+            self.inodes.remove(*inode);
+        }
+        garbage.len()
+    }
+
+    /// Returns the inodes of the open fds and every directory above them,
+    /// which must survive.
+    fn pinned_inodes(&self) -> BTreeSet<Inode> {
+        let mut pinned = BTreeSet::new();
+        for fd in self.fd_map.values() {
+            let mut cur = Some(fd.inode);
+            while let Some(inode) = cur {
+                if !pinned.insert(inode) {
+                    break;
+                }
+                cur = match self.inodes.get(inode).map(|iv| &iv.kind) {
+                    Some(Kind::Dir { parent, .. }) => *parent,
+                    _ => None,
+                };
+            }
+        }
+        pinned
+    }
+
+    /// Removes the entries below `inode` that can be loaded again from the host and
+    /// marks the rest as reachable.
+    ///
+    /// Returns whether `inode` itself can be loaded again from the host.
+    fn prune_inode(
+        &mut self,
+        inode: Inode,
+        pinned: &BTreeSet<Inode>,
+        reachable: &mut BTreeSet<Inode>,
+    ) -> bool {
+        if !reachable.insert(inode) {
+            // already visited through another entry
+            return false;
+        }
+        let children = match &self.inodes[inode].kind {
+            Kind::Dir { entries, .. } | Kind::Root { entries } => entries
+                .iter()
+                .map(|(name, child)| (name.clone(), *child))
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        let mut pruned = vec![];
+        for (name, child) in children {
+            if self.prune_inode(child, pinned, reachable) {
+                reachable.remove(&child);
+                pruned.push(name);
+            }
+        }
+
+        let inodeval = &mut self.inodes[inode];
+        if let Kind::Dir { entries, .. } | Kind::Root { entries } = &mut inodeval.kind {
+            for name in pruned.iter() {
+                entries.remove(name);
+            }
+        }
+        if inodeval.is_preopened || pinned.contains(&inode) {
+            return false;
+        }
+        match &inodeval.kind {
+            Kind::File {
+                handle: None,
+                fd: None,
+                path,
+            } => !path.as_os_str().is_empty(),
+            Kind::Dir { entries, path, .. } => entries.is_empty() && !path.as_os_str().is_empty(),
+            _ => false,
+        }
+    }
 }
 
 /// Top level data type containing all* the state with which WASI can
@@ -1540,6 +2281,10 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
+    #[serde(skip)]
+    pub(crate) metrics: Option<Arc<WasiMetrics>>,
+    #[serde(skip)]
+    pub(crate) stderr_capture: StderrCapture,
 }
 
 impl WasiState {
@@ -1550,6 +2295,18 @@ impl WasiState {
         create_wasi_state(program_name.as_ref())
     }
 
+    /// Returns the end of what the program wrote to stderr, to find the
+    /// message of its last Rust panic.
+    pub fn stderr_capture(&self) -> &StderrCapture {
+        &self.stderr_capture
+    }
+
+    /// Returns a copy of the metrics of the syscalls, or `None` if they
+    /// weren't enabled with [`WasiStateBuilder::metrics`].
+    pub fn metrics(&self) -> Option<MetricsSnapshot> {
+        self.metrics.as_ref().map(|metrics| metrics.snapshot())
+    }
+
     /// Turn the WasiState into bytes
     pub fn freeze(&self) -> Option<Vec<u8>> {
         bincode::serialize(self).ok()
@@ -1561,6 +2318,19 @@ impl WasiState {
     }
 }
 
+/// The number of hard links to a file on the host, or 1 on platforms that
+/// don't expose it.
+#[cfg(unix)]
+fn host_nlink(md: &fs::Metadata) -> __wasi_linkcount_t {
+    use std::os::unix::fs::MetadataExt;
+    md.nlink() as __wasi_linkcount_t
+}
+
+#[cfg(not(unix))]
+fn host_nlink(_md: &fs::Metadata) -> __wasi_linkcount_t {
+    1
+}
+
 pub fn host_file_type_to_wasi_file_type(file_type: fs::FileType) -> __wasi_filetype_t {
     // TODO: handle other file types
     if file_type.is_dir() {
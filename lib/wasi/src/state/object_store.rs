@@ -0,0 +1,320 @@
+//! Read-only files backed by an object store, such as S3, fetched in
+//! blocks with ranged requests and cached on the local disk.
+
+use crate::state::{WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// An object store, such as a bucket of S3, or a prefix in it.
+///
+/// Implement it with the client of your object store, to mount it with
+/// [`WasiStateBuilder::mount_object_store`]. The objects are assumed not
+/// to change while they are mounted.
+///
+/// [`WasiStateBuilder::mount_object_store`]: crate::WasiStateBuilder::mount_object_store
+pub trait ObjectStore: fmt::Debug + Send + Sync {
+    /// Identifies the object store, e.g. with the URL of its bucket and
+    /// prefix. It tells apart the cached blocks of the objects with the
+    /// same key in stores sharing a cache directory.
+    fn id(&self) -> String;
+
+    /// Returns the keys of all the objects, with their size. The keys are
+    /// paths with `/` separators, like `models/model.bin`. The keys ending
+    /// with a `/` are directories.
+    fn list(&self) -> io::Result<Vec<(String, u64)>>;
+
+    /// Returns the `len` bytes at `offset` of the object `key`, e.g. with a
+    /// GET request with a `Range` header.
+    fn get_range(&self, key: &str, offset: u64, len: u64) -> io::Result<Vec<u8>>;
+}
+
+/// The default size of the blocks fetched from an object store.
+pub const DEFAULT_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// An [`ObjectStore`] with a cache of the blocks of its objects in a local
+/// directory, shared by the instances it's mounted in.
+#[derive(Debug)]
+pub struct ObjectStoreFs {
+    store: Box<dyn ObjectStore>,
+    cache_dir: PathBuf,
+    block_size: u64,
+}
+
+impl ObjectStoreFs {
+    /// Creates a filesystem reading the objects of `store`, and caching
+    /// them in `cache_dir`. The directory is created if needed.
+    pub fn new(store: impl ObjectStore + 'static, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            store: Box::new(store),
+            cache_dir: cache_dir.into(),
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+
+    /// Sets the size of the blocks fetched from the object store.
+    /// Defaults to [`DEFAULT_BLOCK_SIZE`].
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Returns the keys of all the objects, with their size.
+    pub(crate) fn list(&self) -> io::Result<Vec<(String, u64)>> {
+        self.store.list()
+    }
+
+    /// Reads the bytes of the object `key` of `size` bytes at `offset`, from
+    /// the cache, or from the object store by blocks.
+    fn read_at(&self, key: &str, size: u64, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = offset / self.block_size;
+        let block_offset = index * self.block_size;
+        let block_len = self.block_size.min(size - block_offset);
+        let start = offset - block_offset;
+        let len = (buf.len() as u64).min(block_len - start) as usize;
+
+        let path = self.block_path(key, index);
+        match fs::File::open(&path) {
+            Ok(mut block) => {
+                block.seek(SeekFrom::Start(start))?;
+                block.read_exact(&mut buf[..len])?;
+                return Ok(len);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let data = self.store.get_range(key, block_offset, block_len)?;
+        if data.len() as u64 != block_len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("the object `{}` is shorter than expected", key),
+            ));
+        }
+        self.cache_block(&path, &data)?;
+        buf[..len].copy_from_slice(&data[start as usize..start as usize + len]);
+        Ok(len)
+    }
+
+    /// Returns the path of the block `index` of `key` in the cache. It's
+    /// named after a hash of the store id and the key, which is the same
+    /// across processes and versions.
+    fn block_path(&self, key: &str, index: u64) -> PathBuf {
+        let id = self.store.id();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(id.len() as u64).to_le_bytes());
+        hasher.update(id.as_bytes());
+        hasher.update(key.as_bytes());
+        self.cache_dir.join(format!(
+            "{}-{}-{}",
+            hasher.finalize().to_hex(),
+            self.block_size,
+            index
+        ))
+    }
+
+    /// Writes a block in the cache. It's written to a temporary file first,
+    /// so readers never see a partial block.
+    fn cache_block(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+        fs::create_dir_all(&self.cache_dir)?;
+        let temp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, data)?;
+        fs::rename(&temp, path)
+    }
+}
+
+/// A read-only file of an [`ObjectStoreFs`].
+///
+/// A deserialized file has no object store, and reading it fails.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectFile {
+    #[serde(skip)]
+    fs: Option<Arc<ObjectStoreFs>>,
+    key: String,
+    size: u64,
+    cursor: u64,
+}
+
+impl ObjectFile {
+    /// Creates a file reading the object `key` of `size` bytes from `fs`.
+    pub fn new(fs: Arc<ObjectStoreFs>, key: String, size: u64) -> Self {
+        Self {
+            fs: Some(fs),
+            key,
+            size,
+            cursor: 0,
+        }
+    }
+}
+
+impl Read for ObjectFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fs = self.fs.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "the object store is missing")
+        })?;
+        let read = fs.read_at(&self.key, self.size, self.cursor, buf)?;
+        self.cursor += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for ObjectFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let cursor = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.size as i64 + offset,
+            SeekFrom::Current(offset) => self.cursor as i64 + offset,
+        };
+        if cursor < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking before the start of the file",
+            ));
+        }
+        self.cursor = cursor as u64;
+        Ok(self.cursor)
+    }
+}
+
+impl Write for ObjectFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the files of object stores are read-only",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for ObjectFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.size
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn rename_file(&self, _new_name: &Path) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.size.saturating_sub(self.cursor) as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+
+    /// An object store in memory, counting the ranges fetched
+    #[derive(Debug)]
+    struct MemoryStore {
+        id: String,
+        objects: HashMap<String, Vec<u8>>,
+        fetched: AtomicUsize,
+    }
+
+    impl MemoryStore {
+        fn new(id: &str, objects: &[(&str, &[u8])]) -> Self {
+            Self {
+                id: id.to_string(),
+                objects: objects
+                    .iter()
+                    .map(|(key, data)| (key.to_string(), data.to_vec()))
+                    .collect(),
+                fetched: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl ObjectStore for Arc<MemoryStore> {
+        fn id(&self) -> String {
+            self.id.clone()
+        }
+
+        fn list(&self) -> io::Result<Vec<(String, u64)>> {
+            Ok(self
+                .objects
+                .iter()
+                .map(|(key, data)| (key.clone(), data.len() as u64))
+                .collect())
+        }
+
+        fn get_range(&self, key: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+            self.fetched.fetch_add(1, Ordering::SeqCst);
+            let data = &self.objects[key];
+            let end = (offset + len).min(data.len() as u64);
+            Ok(data[offset as usize..end as usize].to_vec())
+        }
+    }
+
+    fn read_object(fs: &Arc<ObjectStoreFs>, key: &str, size: u64) -> Vec<u8> {
+        let mut file = ObjectFile::new(fs.clone(), key.to_string(), size);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn blocks_are_cached() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MemoryStore::new("first", &[("key", b"0123456789")]));
+        let objects = Arc::new(ObjectStoreFs::new(store.clone(), cache_dir.path()).block_size(4));
+
+        assert_eq!(read_object(&objects, "key", 10), b"0123456789");
+        assert_eq!(store.fetched.load(Ordering::SeqCst), 3);
+        assert_eq!(fs::read_dir(cache_dir.path()).unwrap().count(), 3);
+
+        // the blocks are read from the cache now
+        let mut file = ObjectFile::new(objects.clone(), "key".to_string(), 10);
+        file.seek(SeekFrom::Start(3)).unwrap();
+        let mut buf = [0; 3];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"345");
+        assert_eq!(read_object(&objects, "key", 10), b"0123456789");
+        assert_eq!(store.fetched.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn stores_sharing_a_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let first = Arc::new(MemoryStore::new("first", &[("key", b"first")]));
+        let second = Arc::new(MemoryStore::new("second", &[("key", b"second")]));
+        let first = Arc::new(ObjectStoreFs::new(first, cache_dir.path()));
+        let second = Arc::new(ObjectStoreFs::new(second, cache_dir.path()));
+
+        assert_eq!(read_object(&first, "key", 5), b"first");
+        assert_eq!(read_object(&second, "key", 6), b"second");
+        assert_eq!(read_object(&first, "key", 5), b"first");
+    }
+}
@@ -0,0 +1,642 @@
+//! A filesystem served by a remote file server, so the machines running
+//! the instances don't need a disk of their own.
+//!
+//! The client and the server speak a simple protocol over a stream, such
+//! as a TCP connection. Each request is an operation byte, followed by a
+//! path relative to the root of the server and the arguments of the
+//! operation. Each reply is a WASI errno, followed by the results of the
+//! operation if it's `__WASI_ESUCCESS`. The integers are little-endian,
+//! and the strings and byte buffers are prefixed with their length as a
+//! `u32`.
+//!
+//! A connection starts with the client sending the token shared with the
+//! server, as a byte buffer, and the server replying with
+//! `__WASI_ESUCCESS`, or `__WASI_EACCES` before closing the connection if
+//! the token is wrong.
+
+use crate::state::{WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// `path` → `count: u32`, `count` times `is_dir: u8, name: string, size: u64`
+const READ_DIR: u8 = 1;
+/// `path, offset: u64, len: u32` → `data: bytes`
+const READ: u8 = 2;
+/// `path, offset: u64, data: bytes` → `written: u32`
+const WRITE: u8 = 3;
+/// `path, size: u64` → nothing
+const SET_LEN: u8 = 4;
+/// `path` → `size: u64`
+const SIZE: u8 = 5;
+/// `path` → nothing, creating or truncating the file
+const CREATE: u8 = 6;
+/// `path` → nothing, removing the file
+const REMOVE: u8 = 7;
+
+/// The length of the longest token accepted by the servers.
+const MAX_TOKEN_LEN: u32 = 1024;
+
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+type Connector = Box<dyn Fn() -> io::Result<Box<dyn Stream>> + Send + Sync>;
+
+/// A connection to a remote file server, mounted in the [`WasiFs`] of
+/// instances with [`WasiStateBuilder::mount_remote`].
+///
+/// The connection is shared by the instances it's mounted in, and its
+/// requests are sent one at a time. A server can be run with
+/// [`serve_remote_fs`].
+///
+/// [`WasiFs`]: crate::WasiFs
+/// [`WasiStateBuilder::mount_remote`]: crate::WasiStateBuilder::mount_remote
+pub struct RemoteFs {
+    token: Vec<u8>,
+    /// Opens a new connection once the current one broke.
+    connect: Connector,
+    /// The current connection, `None` once it broke.
+    stream: Mutex<Option<Box<dyn Stream>>>,
+}
+
+impl fmt::Debug for RemoteFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteFs").finish()
+    }
+}
+
+/// An entry of a remote directory.
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteEntry {
+    pub(crate) name: String,
+    pub(crate) is_dir: bool,
+    pub(crate) size: u64,
+}
+
+impl RemoteFs {
+    /// Creates a client sending its requests over `stream`, after
+    /// authenticating with `token`.
+    ///
+    /// The client can't reconnect: once the stream broke, e.g. in the
+    /// middle of a reply, its requests fail. See [`RemoteFs::connect`].
+    pub fn new(stream: impl Read + Write + Send + 'static, token: &[u8]) -> io::Result<Self> {
+        let mut stream: Box<dyn Stream> = Box::new(stream);
+        authenticate(&mut stream, token)?;
+        Ok(Self {
+            token: token.to_vec(),
+            connect: Box::new(|| -> io::Result<Box<dyn Stream>> {
+                Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "the connection to the file server broke",
+                ))
+            }),
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    /// Connects to the file server at `addr` over TCP, authenticating
+    /// with `token`.
+    ///
+    /// Once the connection broke, the client connects again on its next
+    /// request.
+    pub fn connect(addr: impl ToSocketAddrs, token: &[u8]) -> io::Result<Self> {
+        let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+        let connect: Connector = Box::new(move || -> io::Result<Box<dyn Stream>> {
+            let stream = TcpStream::connect(&addrs[..])?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        });
+        let mut stream = connect()?;
+        authenticate(&mut stream, token)?;
+        Ok(Self {
+            token: token.to_vec(),
+            connect,
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    /// Returns the entries of the directory at `path`.
+    pub(crate) fn read_dir(&self, path: &Path) -> Result<Vec<RemoteEntry>, WasiFsError> {
+        self.request(READ_DIR, path, &[], |reply| {
+            let count = reply.read_u32::<LittleEndian>()?;
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                let is_dir = reply.read_u8()? != 0;
+                let name = read_string(reply)?;
+                let size = reply.read_u64::<LittleEndian>()?;
+                entries.push(RemoteEntry { name, is_dir, size });
+            }
+            Ok(entries)
+        })
+    }
+
+    fn read_at(&self, path: &Path, offset: u64, buf: &mut [u8]) -> Result<usize, WasiFsError> {
+        let mut args = Vec::with_capacity(12);
+        args.write_u64::<LittleEndian>(offset)?;
+        args.write_u32::<LittleEndian>(buf.len() as u32)?;
+        self.request(READ, path, &args, |reply| {
+            let data = read_bytes(reply)?;
+            let read = data.len().min(buf.len());
+            buf[..read].copy_from_slice(&data[..read]);
+            Ok(read)
+        })
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, data: &[u8]) -> Result<usize, WasiFsError> {
+        let mut args = Vec::with_capacity(12 + data.len());
+        args.write_u64::<LittleEndian>(offset)?;
+        write_bytes(&mut args, data)?;
+        self.request(WRITE, path, &args, |reply| {
+            Ok(reply.read_u32::<LittleEndian>()? as usize)
+        })
+    }
+
+    fn set_len(&self, path: &Path, size: u64) -> Result<(), WasiFsError> {
+        let mut args = Vec::with_capacity(8);
+        args.write_u64::<LittleEndian>(size)?;
+        self.request(SET_LEN, path, &args, |_| Ok(()))
+    }
+
+    fn size(&self, path: &Path) -> Result<u64, WasiFsError> {
+        self.request(SIZE, path, &[], |reply| reply.read_u64::<LittleEndian>())
+    }
+
+    /// Creates the file at `path`, or truncates it.
+    pub(crate) fn create(&self, path: &Path) -> Result<(), WasiFsError> {
+        self.request(CREATE, path, &[], |_| Ok(()))
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), WasiFsError> {
+        self.request(REMOVE, path, &[], |_| Ok(()))
+    }
+
+    /// Sends a request, and reads its reply with `read_reply` if it
+    /// succeeded.
+    fn request<T>(
+        &self,
+        op: u8,
+        path: &Path,
+        args: &[u8],
+        read_reply: impl FnOnce(&mut dyn Read) -> io::Result<T>,
+    ) -> Result<T, WasiFsError> {
+        let mut request = Vec::with_capacity(1 + 4 + args.len());
+        request.push(op);
+        write_string(&mut request, &remote_path(path))?;
+        request.extend_from_slice(args);
+
+        let mut stream = self.stream.lock().map_err(|_| WasiFsError::IOError)?;
+        if stream.is_none() {
+            let mut new_stream = (self.connect)()?;
+            authenticate(&mut new_stream, &self.token)?;
+            *stream = Some(new_stream);
+        }
+        match exchange(stream.as_mut().unwrap(), &request, read_reply) {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(errno)) => Err(WasiFsError::from_wasi_err(errno)),
+            Err(e) => {
+                // the stream may be left in the middle of a request or a
+                // reply, it's not used anymore
+                *stream = None;
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Sends the token of a new connection, and reads whether the server
+/// accepted it.
+fn authenticate(stream: &mut Box<dyn Stream>, token: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(4 + token.len());
+    write_bytes(&mut message, token)?;
+    stream.write_all(&message)?;
+    stream.flush()?;
+    match stream.read_u16::<LittleEndian>()? {
+        __WASI_ESUCCESS => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the file server rejected the token",
+        )),
+    }
+}
+
+/// Sends a request and reads its reply, returning the errno of the reply
+/// if it's not `__WASI_ESUCCESS`.
+fn exchange<T>(
+    stream: &mut Box<dyn Stream>,
+    request: &[u8],
+    read_reply: impl FnOnce(&mut dyn Read) -> io::Result<T>,
+) -> io::Result<Result<T, __wasi_errno_t>> {
+    stream.write_all(request)?;
+    stream.flush()?;
+    match stream.read_u16::<LittleEndian>()? {
+        __WASI_ESUCCESS => Ok(Ok(read_reply(stream)?)),
+        errno => Ok(Err(errno)),
+    }
+}
+
+/// A file of a remote file server, proxying its operations to it.
+///
+/// A deserialized file has no connection, and its operations fail.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFile {
+    #[serde(skip)]
+    fs: Option<Arc<RemoteFs>>,
+    path: PathBuf,
+    cursor: u64,
+}
+
+impl RemoteFile {
+    /// Creates a file proxying the operations on `path` to `fs`.
+    pub fn new(fs: Arc<RemoteFs>, path: PathBuf) -> Self {
+        Self {
+            fs: Some(fs),
+            path,
+            cursor: 0,
+        }
+    }
+
+    fn fs(&self) -> Result<&RemoteFs, WasiFsError> {
+        self.fs.as_deref().ok_or(WasiFsError::NotConnected)
+    }
+
+    fn size_or_error(&self) -> Result<u64, WasiFsError> {
+        self.fs()?.size(&self.path)
+    }
+}
+
+impl Read for RemoteFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self
+            .fs()
+            .and_then(|fs| fs.read_at(&self.path, self.cursor, buf))
+            .map_err(into_io_error)?;
+        self.cursor += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for RemoteFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let cursor = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.size_or_error().map_err(into_io_error)? as i64 + offset,
+            SeekFrom::Current(offset) => self.cursor as i64 + offset,
+        };
+        if cursor < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking before the start of the file",
+            ));
+        }
+        self.cursor = cursor as u64;
+        Ok(self.cursor)
+    }
+}
+
+impl Write for RemoteFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self
+            .fs()
+            .and_then(|fs| fs.write_at(&self.path, self.cursor, buf))
+            .map_err(into_io_error)?;
+        self.cursor += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for RemoteFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.size_or_error().unwrap_or(0)
+    }
+    fn set_len(&mut self, new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        self.fs()?.set_len(&self.path, new_size)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        self.fs()?.remove(&self.path)
+    }
+    fn rename_file(&self, _new_name: &Path) -> Result<(), WasiFsError> {
+        Err(WasiFsError::Unsupported)
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.size_or_error()?.saturating_sub(self.cursor) as usize)
+    }
+}
+
+/// Serves the host directory `root` to a [`RemoteFs`] connected with
+/// `stream`, until the client disconnects.
+///
+/// The client must authenticate with `token`, which should be secret and
+/// hard to guess. Only the regular files and the directories under `root`
+/// are served; the symlinks pointing out of `root` aren't followed.
+pub fn serve_remote_fs(
+    mut stream: impl Read + Write,
+    root: impl AsRef<Path>,
+    token: &[u8],
+) -> io::Result<()> {
+    let root = fs::canonicalize(root)?;
+    let len = stream.read_u32::<LittleEndian>()?;
+    let mut client_token = Vec::new();
+    if len <= MAX_TOKEN_LEN {
+        Read::by_ref(&mut stream)
+            .take(u64::from(len))
+            .read_to_end(&mut client_token)?;
+    }
+    if len > MAX_TOKEN_LEN || !same_token(&client_token, token) {
+        stream.write_u16::<LittleEndian>(__WASI_EACCES)?;
+        stream.flush()?;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the client sent a wrong token",
+        ));
+    }
+    stream.write_u16::<LittleEndian>(__WASI_ESUCCESS)?;
+    stream.flush()?;
+
+    loop {
+        let op = match stream.read_u8() {
+            Ok(op) => op,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let path = read_string(&mut stream)?;
+        // the arguments are read before the path is checked, to stay in
+        // sync with the client
+        let request = match op {
+            READ_DIR | SIZE | CREATE | REMOVE => Request::Path,
+            READ => Request::Read {
+                offset: stream.read_u64::<LittleEndian>()?,
+                len: stream.read_u32::<LittleEndian>()?,
+            },
+            WRITE => Request::Write {
+                offset: stream.read_u64::<LittleEndian>()?,
+                data: read_bytes(&mut stream)?,
+            },
+            SET_LEN => Request::SetLen {
+                size: stream.read_u64::<LittleEndian>()?,
+            },
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown remote filesystem operation {}", op),
+                ))
+            }
+        };
+
+        let mut reply = Vec::new();
+        let result = match host_path(&root, &path) {
+            Some(path) => serve_request(op, request, &path, &mut reply),
+            None => Err(WasiFsError::InvalidInput),
+        };
+        let mut message = Vec::with_capacity(2 + reply.len());
+        match result {
+            Ok(()) => {
+                message.write_u16::<LittleEndian>(__WASI_ESUCCESS)?;
+                message.extend_from_slice(&reply);
+            }
+            Err(e) => message.write_u16::<LittleEndian>(e.into_wasi_err())?,
+        }
+        stream.write_all(&message)?;
+        stream.flush()?;
+    }
+}
+
+enum Request {
+    Path,
+    Read { offset: u64, len: u32 },
+    Write { offset: u64, data: Vec<u8> },
+    SetLen { size: u64 },
+}
+
+fn serve_request(
+    op: u8,
+    request: Request,
+    path: &Path,
+    reply: &mut Vec<u8>,
+) -> Result<(), WasiFsError> {
+    match (op, request) {
+        (READ_DIR, _) => {
+            let mut entries = Vec::new();
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() || metadata.is_file() {
+                    entries.push((entry.file_name(), metadata));
+                }
+            }
+            reply.write_u32::<LittleEndian>(entries.len() as u32)?;
+            for (name, metadata) in entries {
+                reply.write_u8(metadata.is_dir() as u8)?;
+                write_string(reply, &name.to_string_lossy())?;
+                reply.write_u64::<LittleEndian>(metadata.len())?;
+            }
+        }
+        (READ, Request::Read { offset, len }) => {
+            let mut file = fs::File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut data = Vec::new();
+            file.take(u64::from(len)).read_to_end(&mut data)?;
+            write_bytes(reply, &data)?;
+        }
+        (WRITE, Request::Write { offset, data }) => {
+            let mut file = fs::OpenOptions::new().write(true).open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&data)?;
+            reply.write_u32::<LittleEndian>(data.len() as u32)?;
+        }
+        (SET_LEN, Request::SetLen { size }) => {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(path)?
+                .set_len(size)?;
+        }
+        (SIZE, _) => reply.write_u64::<LittleEndian>(fs::metadata(path)?.len())?,
+        (CREATE, _) => {
+            fs::File::create(path)?;
+        }
+        (REMOVE, _) => fs::remove_file(path)?,
+        _ => return Err(WasiFsError::InvalidInput),
+    }
+    Ok(())
+}
+
+/// Whether the tokens are the same, comparing all their bytes so that the
+/// time taken doesn't tell where they differ.
+fn same_token(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Returns the host path of the remote `path` under the canonical `root`,
+/// with its symlinks resolved, if it doesn't escape `root`.
+fn host_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut host_path = root.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => host_path.push(name),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    let resolved = match fs::canonicalize(&host_path) {
+        Ok(resolved) => resolved,
+        // a file to create, whose parent must be resolved instead, unless
+        // it's a dangling symlink
+        Err(_) if host_path.symlink_metadata().is_err() => fs::canonicalize(host_path.parent()?)
+            .ok()?
+            .join(host_path.file_name()?),
+        Err(_) => return None,
+    };
+    if resolved.starts_with(root) {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+/// Returns `path` with `/` separators, as sent to the server.
+fn remote_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn into_io_error(error: WasiFsError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+fn read_bytes(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<LittleEndian>()?;
+    let mut data = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+fn write_bytes(writer: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+    writer.write_u32::<LittleEndian>(data.len() as u32)?;
+    writer.extend_from_slice(data);
+    Ok(())
+}
+
+fn read_string(reader: &mut dyn Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_string(writer: &mut Vec<u8>, string: &str) -> io::Result<()> {
+    write_bytes(writer, string.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+
+    const TOKEN: &[u8] = b"secret";
+
+    /// Serves `root` to the connections of a local listener, returning
+    /// its address
+    fn spawn_server(root: &Path) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let root = root.to_path_buf();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let _ = serve_remote_fs(stream.unwrap(), &root, TOKEN);
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn remote_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("dir")).unwrap();
+        fs::write(dir.path().join("dir/file"), b"data").unwrap();
+        let remote = Arc::new(RemoteFs::connect(spawn_server(dir.path()), TOKEN).unwrap());
+
+        let entries = remote.read_dir(Path::new("")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "dir");
+        assert!(entries[0].is_dir);
+        let entries = remote.read_dir(Path::new("dir")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "file");
+        assert!(!entries[0].is_dir);
+        assert_eq!(entries[0].size, 4);
+
+        let mut file = RemoteFile::new(remote.clone(), PathBuf::from("dir/file"));
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "data");
+
+        remote.create(Path::new("new")).unwrap();
+        let mut file = RemoteFile::new(remote.clone(), PathBuf::from("new"));
+        file.write_all(b"hello").unwrap();
+        assert_eq!(WasiFile::size(&file), 5);
+        assert_eq!(fs::read(dir.path().join("new")).unwrap(), b"hello");
+
+        assert!(matches!(
+            remote.read_dir(Path::new("missing")),
+            Err(WasiFsError::EntityNotFound)
+        ));
+        assert!(matches!(
+            remote.read_dir(Path::new("../")),
+            Err(WasiFsError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn wrong_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = spawn_server(dir.path());
+        let error = RemoteFs::connect(addr, b"wrong").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(RemoteFs::connect(addr, TOKEN).is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn host_paths_stay_under_root() {
+        let root = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(root.path()).unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret"), b"secret").unwrap();
+        fs::create_dir(root.join("dir")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("missing"), root.join("dangling")).unwrap();
+        std::os::unix::fs::symlink(root.join("dir"), root.join("inside")).unwrap();
+
+        assert_eq!(host_path(&root, ""), Some(root.clone()));
+        assert_eq!(host_path(&root, "dir/new"), Some(root.join("dir/new")));
+        assert_eq!(host_path(&root, "inside"), Some(root.join("dir")));
+        assert_eq!(host_path(&root, "../secret"), None);
+        assert_eq!(host_path(&root, "link"), None);
+        assert_eq!(host_path(&root, "link/secret"), None);
+        assert_eq!(host_path(&root, "dangling"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Throttling of the I/O syscalls of an instance, according to its
+//! [`IoLimits`].
+
+use crate::state::IoLimits;
+use std::time::{Duration, Instant};
+
+/// The state of the token buckets enforcing [`IoLimits`].  Each bucket holds
+/// at most one second worth of tokens, so bursts are bounded too.
+#[derive(Debug, Default)]
+pub(crate) struct IoThrottle {
+    read_bytes: Bucket,
+    write_bytes: Bucket,
+    ops: Bucket,
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    /// the available tokens, negative when the last calls exceeded the limit
+    tokens: f64,
+    /// when the tokens were last refilled; `None` for a full bucket
+    refilled_at: Option<Instant>,
+}
+
+impl Bucket {
+    /// Returns how long to wait until the bucket holds `needed` tokens.
+    fn wait_for(&mut self, rate: u64, needed: f64) -> Duration {
+        let rate = rate.max(1) as f64;
+        let now = Instant::now();
+        self.tokens = match self.refilled_at {
+            Some(refilled_at) => (self.tokens + (now - refilled_at).as_secs_f64() * rate).min(rate),
+            None => rate,
+        };
+        self.refilled_at = Some(now);
+        if self.tokens >= needed {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((needed - self.tokens) / rate)
+        }
+    }
+}
+
+impl IoThrottle {
+    /// Admits a read, or a write, if it's within `limits`, or returns how
+    /// long to wait before trying again.  The bytes are charged afterwards
+    /// with [`IoThrottle::charge`], once they're known.
+    ///
+    /// This doesn't wait itself, so that the caller can release the state
+    /// while waiting.
+    pub(crate) fn admit(&mut self, limits: &IoLimits, write: bool) -> Result<(), Duration> {
+        let (bytes, bytes_per_sec) = if write {
+            (&mut self.write_bytes, limits.write_bytes_per_sec)
+        } else {
+            (&mut self.read_bytes, limits.read_bytes_per_sec)
+        };
+        let mut wait = Duration::from_secs(0);
+        if let Some(rate) = bytes_per_sec {
+            wait = wait.max(bytes.wait_for(rate, 0.0));
+        }
+        if let Some(rate) = limits.ops_per_sec {
+            wait = wait.max(self.ops.wait_for(rate, 1.0));
+        }
+        if wait > Duration::from_secs(0) {
+            return Err(wait);
+        }
+        if limits.ops_per_sec.is_some() {
+            self.ops.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    /// Charges the bytes of a read, or a write, admitted by
+    /// [`IoThrottle::admit`].
+    pub(crate) fn charge(&mut self, limits: &IoLimits, write: bool, bytes: usize) {
+        let (bucket, bytes_per_sec) = if write {
+            (&mut self.write_bytes, limits.write_bytes_per_sec)
+        } else {
+            (&mut self.read_bytes, limits.read_bytes_per_sec)
+        };
+        if let Some(rate) = bytes_per_sec {
+            bucket.wait_for(rate, 0.0);
+            bucket.tokens -= bytes as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ops_are_limited() {
+        let limits = IoLimits {
+            ops_per_sec: Some(2),
+            ..IoLimits::default()
+        };
+        let mut throttle = IoThrottle::default();
+        assert_eq!(throttle.admit(&limits, false), Ok(()));
+        assert_eq!(throttle.admit(&limits, true), Ok(()));
+        let wait = throttle.admit(&limits, false).unwrap_err();
+        assert!(wait > Duration::from_secs(0));
+        assert!(wait <= Duration::from_millis(500));
+
+        std::thread::sleep(wait);
+        assert_eq!(throttle.admit(&limits, false), Ok(()));
+    }
+
+    #[test]
+    fn bytes_are_charged_after_the_call() {
+        let limits = IoLimits {
+            read_bytes_per_sec: Some(100),
+            ..IoLimits::default()
+        };
+        let mut throttle = IoThrottle::default();
+        assert_eq!(throttle.admit(&limits, false), Ok(()));
+        // a call may exceed the limit, the next ones wait for the excess
+        throttle.charge(&limits, false, 150);
+        let wait = throttle.admit(&limits, false).unwrap_err();
+        assert!(wait > Duration::from_millis(400));
+        assert!(wait <= Duration::from_millis(500));
+        // the writes are limited separately
+        assert_eq!(throttle.admit(&limits, true), Ok(()));
+
+        std::thread::sleep(wait);
+        assert_eq!(throttle.admit(&limits, false), Ok(()));
+    }
+}
@@ -3,16 +3,20 @@ use crate::syscalls::types::*;
 use serde::{de, Deserialize, Serialize};
 use std::any::Any;
 #[cfg(unix)]
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::{
+    collections::VecDeque,
+    ffi::{OsStr, OsString},
     fs,
     io::{self, Read, Seek, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
     time::SystemTime,
 };
 use thiserror::Error;
 use tracing::debug;
+use unicode_normalization::UnicodeNormalization;
 
 /// Error type for external users
 #[derive(Error, Copy, Clone, Debug, PartialEq, Eq)]
@@ -86,6 +90,12 @@ pub enum WasiFsError {
     /// A call to write returned 0
     #[error("write returned 0")]
     WriteZero,
+    /// The operation is not supported by this kind of file or backend
+    #[error("operation not supported")]
+    Unsupported,
+    /// No more fds can be opened
+    #[error("too many open files")]
+    TooManyOpenFiles,
     /// A WASI error without an external name.  If you encounter this it means
     /// that there's probably a bug on our side (maybe as simple as forgetting to wrap
     /// this error, but perhaps something broke)
@@ -115,6 +125,8 @@ impl WasiFsError {
             __WASI_EPROTO => WasiFsError::UnexpectedEof,
             __WASI_EAGAIN => WasiFsError::WouldBlock,
             __WASI_ENOSPC => WasiFsError::WriteZero,
+            __WASI_ENOTSUP => WasiFsError::Unsupported,
+            __WASI_EMFILE => WasiFsError::TooManyOpenFiles,
             _ => WasiFsError::UnknownError(err),
         }
     }
@@ -143,11 +155,287 @@ impl WasiFsError {
             WasiFsError::UnexpectedEof => __WASI_EPROTO,
             WasiFsError::WouldBlock => __WASI_EAGAIN,
             WasiFsError::WriteZero => __WASI_ENOSPC,
+            WasiFsError::Unsupported => __WASI_ENOTSUP,
+            WasiFsError::TooManyOpenFiles => __WASI_EMFILE,
             WasiFsError::UnknownError(ec) => ec,
         }
     }
 }
 
+/// An error while creating the WASI filesystem, see [`WasiStateBuilder::build`].
+///
+/// [`WasiStateBuilder::build`]: crate::WasiStateBuilder::build
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WasiFsCreationError {
+    /// The metadata of a preopened directory couldn't be read.
+    #[error("could not get metadata for file {path:?}: {source}")]
+    Metadata {
+        path: PathBuf,
+        #[source]
+        source: WasiFsError,
+    },
+    /// A preopened path isn't a directory.
+    #[error("WASI only supports pre-opened directories right now; found {0:?}")]
+    NotADirectory(PathBuf),
+    /// An inode or a file descriptor of the filesystem couldn't be created.
+    #[error("failed to create the {what}: {source}")]
+    Fs {
+        what: String,
+        #[source]
+        source: WasiFsError,
+    },
+    /// Several preopened directories have the same name or alias.
+    #[error("found duplicate entry for alias `{0}`")]
+    DuplicateEntry(String),
+}
+
+impl WasiFsCreationError {
+    /// Returns the error of the WASI filesystem creating `what`.
+    pub(crate) fn fs(what: impl Into<String>, errno: __wasi_errno_t) -> Self {
+        Self::Fs {
+            what: what.into(),
+            source: WasiFsError::from_wasi_err(errno),
+        }
+    }
+
+    pub(crate) fn metadata(path: &Path, error: io::Error) -> Self {
+        Self::Metadata {
+            path: path.to_owned(),
+            source: error.into(),
+        }
+    }
+}
+
+/// How names in the WASI filesystem are compared and stored.
+///
+/// Hosts disagree about this: Linux is case sensitive, macOS and Windows are
+/// usually case insensitive and macOS normalizes unicode as well.  The policy
+/// is applied by the WASI filesystem itself so that the sandbox behaves the same
+/// on every host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathPolicy {
+    /// Names are compared byte for byte.  This is the default.
+    CaseSensitive,
+    /// Names are compared ignoring case, but keep the case they were created with.
+    CaseInsensitivePreserving,
+    /// Names are normalized to Unicode NFC when created and looked up.
+    NfcNormalized,
+}
+
+impl Default for PathPolicy {
+    fn default() -> Self {
+        PathPolicy::CaseSensitive
+    }
+}
+
+impl PathPolicy {
+    /// The name as it should be stored in the filesystem and created on the host
+    pub fn normalize(self, name: &str) -> String {
+        match self {
+            PathPolicy::NfcNormalized => name.nfc().collect(),
+            PathPolicy::CaseSensitive | PathPolicy::CaseInsensitivePreserving => name.to_string(),
+        }
+    }
+
+    /// The key under which `name` is found in a directory's entries
+    pub fn key(self, name: &str) -> String {
+        match self {
+            PathPolicy::CaseSensitive => name.to_string(),
+            PathPolicy::CaseInsensitivePreserving => name.to_lowercase(),
+            PathPolicy::NfcNormalized => name.nfc().collect(),
+        }
+    }
+
+    /// Finds the entry of the host directory `dir` that `name` refers to.
+    ///
+    /// The directory is listed instead of asking the host for `name` directly,
+    /// otherwise the host's own comparison rules would leak into the sandbox.
+    /// An exact match is preferred over one that's only equal under the policy,
+    /// and under `CaseSensitive` only an exact match is found.  Entries the host
+    /// fails to read are skipped.
+    pub(crate) fn find_host_entry(self, dir: &Path, name: &OsStr) -> Option<OsString> {
+        // a missing name saves listing the directory, but the host may find
+        // `FOO` for `foo`, so a name it finds is still confirmed
+        if self == PathPolicy::CaseSensitive && dir.join(name).symlink_metadata().is_err() {
+            return None;
+        }
+        let key = name.to_str().map(|name| self.key(name));
+        let mut found = None;
+        for entry in fs::read_dir(dir).ok()? {
+            let entry_name = match entry {
+                Ok(entry) => entry.file_name(),
+                Err(_) => continue,
+            };
+            if entry_name == name {
+                return Some(entry_name);
+            }
+            if self != PathPolicy::CaseSensitive
+                && found.is_none()
+                && key.is_some()
+                && entry_name.to_str().map(|entry_str| self.key(entry_str)) == key
+            {
+                found = Some(entry_name);
+            }
+        }
+        found
+    }
+}
+
+/// Whether stdio fds are reported to the guest as terminals.
+///
+/// Guests check this with `isatty` to decide whether to use colors and line
+/// editing, so it's useful to override when the host's answer is wrong for
+/// the embedding, e.g. when output is captured but rendered in a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TtyPolicy {
+    /// Report a stdio fd as a terminal if its [`WasiFile`] is one.
+    Host,
+    /// Always report stdio fds as terminals.
+    Always,
+    /// Never report stdio fds as terminals.
+    Never,
+}
+
+impl Default for TtyPolicy {
+    fn default() -> Self {
+        TtyPolicy::Host
+    }
+}
+
+/// How long the entries and metadata of host directories cached by the
+/// WASI filesystem are trusted, when the directories may be changed by the
+/// host while an instance runs.
+///
+/// Listing a directory always reads it from the host; this applies to the
+/// entries loaded when looking up paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FreshnessPolicy {
+    /// The entries are cached until they're collected as garbage.  This is
+    /// the default, for directories only changed by the instance.
+    Cached,
+    /// The entries of a directory are loaded again when looked up after
+    /// this long.
+    MaxAge(std::time::Duration),
+    /// The entries of a directory are loaded again when the host notifies
+    /// of changes in it.
+    #[cfg(feature = "notify")]
+    Watch,
+}
+
+impl Default for FreshnessPolicy {
+    fn default() -> Self {
+        FreshnessPolicy::Cached
+    }
+}
+
+/// Limits on the I/O of an instance, so that one program can't saturate
+/// the disks of the host. Set them with
+/// [`WasiStateBuilder::io_limits`](crate::WasiStateBuilder::io_limits).
+///
+/// They apply to `fd_read`, `fd_write`, `fd_pread` and `fd_pwrite` on
+/// every fd but stdio. A call may exceed the byte limits, and the calls
+/// after it are then delayed until the excess is paid off; `None` means
+/// there's no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IoLimits {
+    /// The number of bytes read per second.
+    pub read_bytes_per_sec: Option<u64>,
+    /// The number of bytes written per second.
+    pub write_bytes_per_sec: Option<u64>,
+    /// The number of reads and writes per second.
+    pub ops_per_sec: Option<u64>,
+    /// What happens to the calls over the limits.
+    pub mode: ThrottleMode,
+}
+
+/// What happens to the I/O syscalls exceeding the [`IoLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThrottleMode {
+    /// The call sleeps until it's within the limits.
+    Block,
+    /// The call fails with `__WASI_EAGAIN`, for the program to retry later.
+    Again,
+}
+
+impl Default for ThrottleMode {
+    fn default() -> Self {
+        ThrottleMode::Block
+    }
+}
+
+/// Character devices built into the WASI filesystem, mounted under `/dev`
+/// with [`WasiStateBuilder::mount_devices`](crate::WasiStateBuilder::mount_devices).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Device {
+    /// `/dev/null`: reads return end of file, writes are discarded.
+    Null,
+    /// `/dev/zero`: reads return zeroes, writes are discarded.
+    Zero,
+    /// `/dev/urandom`: reads return random bytes, writes are discarded.
+    Urandom,
+    /// `/dev/tty`: reads from the guest's stdin and writes to its stdout, as
+    /// set on the [`WasiStateBuilder`](crate::WasiStateBuilder).
+    Tty,
+}
+
+impl Device {
+    /// All devices, in the order they are mounted.
+    pub const ALL: [Device; 4] = [Device::Null, Device::Zero, Device::Urandom, Device::Tty];
+
+    /// The name of the device under `/dev`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Device::Null => "null",
+            Device::Zero => "zero",
+            Device::Urandom => "urandom",
+            Device::Tty => "tty",
+        }
+    }
+}
+
+impl Read for Device {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Device::Null => Ok(0),
+            Device::Zero => {
+                buf.iter_mut().for_each(|b| *b = 0);
+                Ok(buf.len())
+            }
+            Device::Urandom => {
+                getrandom::getrandom(buf)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                Ok(buf.len())
+            }
+            Device::Tty => Err(tty_is_stdio()),
+        }
+    }
+}
+
+impl Write for Device {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Device::Null | Device::Zero | Device::Urandom => Ok(buf.len()),
+            Device::Tty => Err(tty_is_stdio()),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            // the stdout of the guest is flushed on its own
+            Device::Null | Device::Zero | Device::Urandom | Device::Tty => Ok(()),
+        }
+    }
+}
+
+/// `/dev/tty` is read and written through the stdio files of the guest, see
+/// `WasiFs::tty_stdio_fd`.
+fn tty_is_stdio() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "/dev/tty is read and written through stdio",
+    )
+}
+
 /// This trait relies on your file closing when it goes out of scope via `Drop`
 #[typetag::serde(tag = "type")]
 pub trait WasiFile: fmt::Debug + Send + Write + Read + Seek + 'static + Upcastable {
@@ -202,9 +490,41 @@ pub trait WasiFile: fmt::Debug + Send + Write + Read + Seek + 'static + Upcastab
         panic!("Default implementation for now as this method is unstable; this default implementation or this entire method may be removed in a future release.");
     }
 
+    /// Creates a hard link to this file at `new_name`.
+    /// Default implementation returns `WasiFsError::Unsupported`; implement this method if
+    /// your backend has a notion of multiple directory entries sharing the same file
+    fn hard_link(&self, _new_name: &std::path::Path) -> Result<(), WasiFsError> {
+        debug!(
+            "{:?} can not be hard linked: WasiFile::hard_link is not implemented for this type",
+            self
+        );
+        Err(WasiFsError::Unsupported)
+    }
+
     /// Returns the number of bytes available.  This function must not block
     fn bytes_available(&self) -> Result<usize, WasiFsError>;
 
+    /// Whether a read would return without blocking.  Only used to poll files
+    /// that have no host fd, see [`WasiFile::get_raw_fd`]; by default a file is
+    /// ready when it has bytes available.
+    fn is_read_ready(&self) -> bool {
+        self.bytes_available().map(|n| n > 0).unwrap_or(true)
+    }
+
+    /// For files whose reads block until another thread provides input: a
+    /// function waiting until a read would return without blocking.  The
+    /// syscalls call it after unlocking the WASI state, so that the threads
+    /// sharing it can go on; by default reads don't wait for other threads.
+    fn read_waiter(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        None
+    }
+
+    /// Whether this file is an interactive terminal.  Used to report stdio
+    /// fds as character devices when the [`TtyPolicy`] is `Host`.
+    fn is_tty(&self) -> bool {
+        false
+    }
+
     /// Used for polling.  Default returns `None` because this method cannot be implemented for most types
     /// Returns the underlying host fd
     fn get_raw_fd(&self) -> Option<i32> {
@@ -360,6 +680,19 @@ impl PollEventBuilder {
     }
 }
 
+/// Readiness of files that have no host fd to poll: reads are ready when
+/// [`WasiFile::is_read_ready`] says so and writes never block.
+fn poll_virtual(file: &dyn WasiFile, events: PollEventSet) -> PollEventSet {
+    let mut peb = PollEventBuilder::new();
+    if events & PollEvent::PollIn as PollEventSet != 0 && file.is_read_ready() {
+        peb = peb.add(PollEvent::PollIn);
+    }
+    if events & PollEvent::PollOut as PollEventSet != 0 {
+        peb = peb.add(PollEvent::PollOut);
+    }
+    peb.build()
+}
+
 #[cfg(unix)]
 pub(crate) fn poll(
     selfs: &[&dyn WasiFile],
@@ -369,37 +702,63 @@ pub(crate) fn poll(
     if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
         return Err(WasiFsError::InvalidInput);
     }
-    let mut fds = selfs
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| s.get_raw_fd().map(|rfd| (i, rfd)))
-        .map(|(i, host_fd)| libc::pollfd {
-            fd: host_fd,
-            events: poll_event_set_to_platform_poll_events(events[i]),
-            revents: 0,
-        })
-        .collect::<Vec<_>>();
-    let result = unsafe { libc::poll(fds.as_mut_ptr(), selfs.len() as _, 1) };
+    let mut ready = 0;
+    let mut host_idxs = vec![];
+    let mut fds = vec![];
+    for (i, s) in selfs.iter().enumerate() {
+        match s.get_raw_fd() {
+            Some(host_fd) => {
+                host_idxs.push(i);
+                fds.push(libc::pollfd {
+                    fd: host_fd,
+                    events: poll_event_set_to_platform_poll_events(events[i]),
+                    revents: 0,
+                });
+            }
+            None => {
+                seen_events[i] = poll_virtual(*s, events[i]);
+                if seen_events[i] != 0 {
+                    ready += 1;
+                }
+            }
+        }
+    }
+    // don't wait on the host if a virtual file is already ready
+    let timeout = if ready > 0 { 0 } else { 1 };
+    let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, timeout) };
 
     if result < 0 {
         // TODO: check errno and return value
         return Err(WasiFsError::IOError);
     }
     // convert result and write back values
-    for (i, fd) in fds.into_iter().enumerate() {
+    for (i, fd) in host_idxs.into_iter().zip(fds.into_iter()) {
         seen_events[i] = platform_poll_events_to_pollevent_set(fd.revents);
     }
     // unwrap is safe because we check for negative values above
-    Ok(result.try_into().unwrap())
+    Ok(ready + u32::try_from(result).unwrap())
 }
 
 #[cfg(not(unix))]
 pub(crate) fn poll(
-    _selfs: &[&dyn WasiFile],
-    _events: &[PollEventSet],
-    _seen_events: &mut [PollEventSet],
-) -> Result<(), WasiFsError> {
-    unimplemented!("HostFile::poll in WasiFile is not implemented for non-Unix-like targets yet");
+    selfs: &[&dyn WasiFile],
+    events: &[PollEventSet],
+    seen_events: &mut [PollEventSet],
+) -> Result<u32, WasiFsError> {
+    if !(selfs.len() == events.len() && events.len() == seen_events.len()) {
+        return Err(WasiFsError::InvalidInput);
+    }
+    let mut ready = 0;
+    for (i, s) in selfs.iter().enumerate() {
+        if s.get_raw_fd().is_some() {
+            return Err(WasiFsError::Unsupported);
+        }
+        seen_events[i] = poll_virtual(*s, events[i]);
+        if seen_events[i] != 0 {
+            ready += 1;
+        }
+    }
+    Ok(ready)
 }
 
 pub trait WasiPath {}
@@ -623,9 +982,13 @@ impl WasiFile for HostFile {
         std::fs::rename(&self.host_path, new_name).map_err(Into::into)
     }
 
+    fn hard_link(&self, new_name: &std::path::Path) -> Result<(), WasiFsError> {
+        std::fs::hard_link(&self.host_path, new_name).map_err(Into::into)
+    }
+
     fn bytes_available(&self) -> Result<usize, WasiFsError> {
-        // unwrap is safe because of get_raw_fd implementation
-        let host_fd = self.get_raw_fd().unwrap();
+        // there's no host fd on the targets where the files can't be polled
+        let host_fd = self.get_raw_fd().ok_or(WasiFsError::Unsupported)?;
 
         host_file_bytes_available(host_fd)
     }
@@ -635,12 +998,6 @@ impl WasiFile for HostFile {
         use std::os::unix::io::AsRawFd;
         Some(self.inner.as_raw_fd())
     }
-    #[cfg(not(unix))]
-    fn get_raw_fd(&self) -> Option<i32> {
-        unimplemented!(
-            "HostFile::get_raw_fd in WasiFile is not implemented for non-Unix-like targets yet"
-        );
-    }
 }
 
 impl From<io::Error> for WasiFsError {
@@ -687,7 +1044,20 @@ fn host_file_bytes_available(host_fd: i32) -> Result<usize, WasiFsError> {
 
 #[cfg(not(unix))]
 fn host_file_bytes_available(_raw_fd: i32) -> Result<usize, WasiFsError> {
-    unimplemented!("host_file_bytes_available not yet implemented for non-Unix-like targets.  This probably means the program tried to use wasi::poll_oneoff")
+    Err(WasiFsError::Unsupported)
+}
+
+/// Whether the host fd refers to a terminal.
+#[cfg(any(unix, windows))]
+fn host_isatty(host_fd: i32) -> bool {
+    unsafe { libc::isatty(host_fd) == 1 }
+}
+
+/// Whether the host fd refers to a terminal: terminals can't be detected
+/// on the other targets, so none is reported.
+#[cfg(not(any(unix, windows)))]
+fn host_isatty(_host_fd: i32) -> bool {
+    false
 }
 
 /// A wrapper type around Stdout that implements `WasiFile` and
@@ -762,9 +1132,13 @@ impl WasiFile for Stdout {
         Ok(())
     }
 
+    fn is_tty(&self) -> bool {
+        host_isatty(1)
+    }
+
     fn bytes_available(&self) -> Result<usize, WasiFsError> {
-        // unwrap is safe because of get_raw_fd implementation
-        let host_fd = self.get_raw_fd().unwrap();
+        // there's no host fd on the targets where the files can't be polled
+        let host_fd = self.get_raw_fd().ok_or(WasiFsError::Unsupported)?;
 
         host_file_bytes_available(host_fd)
     }
@@ -774,13 +1148,6 @@ impl WasiFile for Stdout {
         use std::os::unix::io::AsRawFd;
         Some(io::stdout().as_raw_fd())
     }
-
-    #[cfg(not(unix))]
-    fn get_raw_fd(&self) -> Option<i32> {
-        unimplemented!(
-            "Stdout::get_raw_fd in WasiFile is not implemented for non-Unix-like targets yet"
-        );
-    }
 }
 
 /// A wrapper type around Stderr that implements `WasiFile` and
@@ -855,9 +1222,13 @@ impl WasiFile for Stderr {
         Ok(())
     }
 
+    fn is_tty(&self) -> bool {
+        host_isatty(2)
+    }
+
     fn bytes_available(&self) -> Result<usize, WasiFsError> {
-        // unwrap is safe because of get_raw_fd implementation
-        let host_fd = self.get_raw_fd().unwrap();
+        // there's no host fd on the targets where the files can't be polled
+        let host_fd = self.get_raw_fd().ok_or(WasiFsError::Unsupported)?;
 
         host_file_bytes_available(host_fd)
     }
@@ -867,13 +1238,6 @@ impl WasiFile for Stderr {
         use std::os::unix::io::AsRawFd;
         Some(io::stderr().as_raw_fd())
     }
-
-    #[cfg(not(unix))]
-    fn get_raw_fd(&self) -> Option<i32> {
-        unimplemented!(
-            "Stderr::get_raw_fd in WasiFile is not implemented for non-Unix-like targets yet"
-        );
-    }
 }
 
 /// A wrapper type around Stdin that implements `WasiFile` and
@@ -949,9 +1313,13 @@ impl WasiFile for Stdin {
         Ok(())
     }
 
+    fn is_tty(&self) -> bool {
+        host_isatty(0)
+    }
+
     fn bytes_available(&self) -> Result<usize, WasiFsError> {
-        // unwrap is safe because of get_raw_fd implementation
-        let host_fd = self.get_raw_fd().unwrap();
+        // there's no host fd on the targets where the files can't be polled
+        let host_fd = self.get_raw_fd().ok_or(WasiFsError::Unsupported)?;
 
         host_file_bytes_available(host_fd)
     }
@@ -961,12 +1329,385 @@ impl WasiFile for Stdin {
         use std::os::unix::io::AsRawFd;
         Some(io::stdin().as_raw_fd())
     }
+}
 
-    #[cfg(not(unix))]
-    fn get_raw_fd(&self) -> Option<i32> {
-        unimplemented!(
-            "Stdin::get_raw_fd in WasiFile is not implemented for non-Unix-like targets yet"
+/// The input queued for an [`InteractiveStdin`].
+#[derive(Debug, Default)]
+struct StdinQueue {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+type StdinCallback = Box<dyn FnMut() -> Option<Vec<u8>> + Send>;
+
+/// A stdin that is fed by the embedder while the program runs, for REPL-style
+/// programs that can't be given all of their input up front.
+///
+/// Input comes either from an [`InteractiveStdinHandle`], which can be moved to
+/// another thread or an async task to push input as it arrives, or from a
+/// callback that is called whenever the program reads and no input is queued.
+/// Reads block until input is available and return at most one line, like a
+/// terminal in canonical mode.
+///
+/// Pending input and the callback are not serialized; a deserialized
+/// `InteractiveStdin` is at end of file.
+#[derive(Serialize, Deserialize)]
+pub struct InteractiveStdin {
+    #[serde(skip, default = "InteractiveStdin::closed_queue")]
+    queue: Arc<(Mutex<StdinQueue>, Condvar)>,
+    #[serde(skip)]
+    callback: Option<Arc<Mutex<StdinCallback>>>,
+}
+
+/// The embedder's end of an [`InteractiveStdin`].
+#[derive(Debug, Clone)]
+pub struct InteractiveStdinHandle {
+    queue: Arc<(Mutex<StdinQueue>, Condvar)>,
+}
+
+impl InteractiveStdin {
+    /// Create a stdin fed through the returned handle.
+    pub fn new() -> (Self, InteractiveStdinHandle) {
+        let queue = Arc::new((Mutex::new(StdinQueue::default()), Condvar::new()));
+        let handle = InteractiveStdinHandle {
+            queue: queue.clone(),
+        };
+        (
+            Self {
+                queue,
+                callback: None,
+            },
+            handle,
+        )
+    }
+
+    /// Create a stdin that calls `callback` for more input whenever the
+    /// program reads and no input is queued.  The callback may block; it
+    /// returns `None` at end of file.
+    pub fn with_callback<F>(callback: F) -> Self
+    where
+        F: FnMut() -> Option<Vec<u8>> + Send + 'static,
+    {
+        let (mut stdin, _) = Self::new();
+        stdin.callback = Some(Arc::new(Mutex::new(Box::new(callback))));
+        stdin
+    }
+
+    /// Wait until input is queued or the stdin is closed, calling the
+    /// callback for more input if there's one.
+    fn wait_for_input<'a>(
+        queue: &'a (Mutex<StdinQueue>, Condvar),
+        callback: Option<&Mutex<StdinCallback>>,
+    ) -> MutexGuard<'a, StdinQueue> {
+        let (lock, cvar) = queue;
+        let mut queue = lock.lock().unwrap();
+        while queue.data.is_empty() && !queue.closed {
+            if let Some(callback) = callback {
+                // don't hold the queue while the callback blocks
+                drop(queue);
+                let input = (callback.lock().unwrap())();
+                queue = lock.lock().unwrap();
+                match input {
+                    Some(input) => queue.data.extend(input),
+                    None => queue.closed = true,
+                }
+            } else {
+                queue = cvar.wait(queue).unwrap();
+            }
+        }
+        queue
+    }
+
+    fn closed_queue() -> Arc<(Mutex<StdinQueue>, Condvar)> {
+        let queue = StdinQueue {
+            data: VecDeque::new(),
+            closed: true,
+        };
+        Arc::new((Mutex::new(queue), Condvar::new()))
+    }
+}
+
+impl InteractiveStdinHandle {
+    /// Queue `data` to be read by the program, waking it up if it's blocked
+    /// reading stdin.
+    pub fn push(&self, data: &[u8]) {
+        let (lock, cvar) = &*self.queue;
+        lock.lock().unwrap().data.extend(data);
+        cvar.notify_all();
+    }
+
+    /// Signal end of file: once the queued input has been read, reads return 0.
+    pub fn close(&self) {
+        let (lock, cvar) = &*self.queue;
+        lock.lock().unwrap().closed = true;
+        cvar.notify_all();
+    }
+}
+
+impl fmt::Debug for InteractiveStdin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InteractiveStdin")
+            .field("queue", &self.queue.0)
+            .field("callback exists", &self.callback.is_some())
+            .finish()
+    }
+}
+
+impl Read for InteractiveStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut queue = Self::wait_for_input(&self.queue, self.callback.as_deref());
+
+        let mut read = 0;
+        while read < buf.len() {
+            match queue.data.pop_front() {
+                Some(byte) => {
+                    buf[read] = byte;
+                    read += 1;
+                    if byte == b'\n' {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Seek for InteractiveStdin {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Other, "can not seek stdin"))
+    }
+}
+
+impl Write for InteractiveStdin {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to stdin",
+        ))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to stdin",
+        ))
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for InteractiveStdin {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        debug!("Calling WasiFile::set_len on stdin; this is probably a bug");
+        Err(WasiFsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(self.queue.0.lock().unwrap().data.len())
+    }
+
+    /// Reads don't block at end of file, and a callback is expected to
+    /// produce input when called.
+    fn is_read_ready(&self) -> bool {
+        let queue = self.queue.0.lock().unwrap();
+        !queue.data.is_empty() || queue.closed || self.callback.is_some()
+    }
+
+    fn read_waiter(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        let queue = self.queue.clone();
+        let callback = self.callback.clone();
+        Some(Box::new(move || {
+            Self::wait_for_input(&queue, callback.as_deref());
+        }))
+    }
+}
+
+/// The standard stream captured by a [`LogOutput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogStream {
+    /// The standard output.
+    Stdout,
+    /// The standard error.
+    Stderr,
+}
+
+impl LogStream {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// Lines longer than this are split, so a program never writing a newline
+/// doesn't buffer without bound.
+const LOG_OUTPUT_MAX_LINE: usize = 16 * 1024;
+
+/// A stdout or stderr forwarding the output of the program to `tracing`
+/// (and to `log` through its `log` feature), one event per line.
+///
+/// The events have the `wasmer_wasi::guest` target, and are labelled with
+/// the instance id and module name given at creation and with the stream,
+/// so the logs of many instances can be interleaved and still be
+/// attributed. Stdout lines are logged at the `INFO` level and stderr lines
+/// at the `WARN` level.
+///
+/// A line without a trailing newline is logged when the file is flushed or
+/// dropped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogOutput {
+    instance_id: String,
+    module_name: String,
+    stream: LogStream,
+    #[serde(skip)]
+    buffer: Vec<u8>,
+}
+
+impl LogOutput {
+    /// Create a stdout logging the lines written by the program.
+    pub fn stdout(instance_id: impl Into<String>, module_name: impl Into<String>) -> Self {
+        Self::new(instance_id.into(), module_name.into(), LogStream::Stdout)
+    }
+
+    /// Create a stderr logging the lines written by the program.
+    pub fn stderr(instance_id: impl Into<String>, module_name: impl Into<String>) -> Self {
+        Self::new(instance_id.into(), module_name.into(), LogStream::Stderr)
+    }
+
+    fn new(instance_id: String, module_name: String, stream: LogStream) -> Self {
+        Self {
+            instance_id,
+            module_name,
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn log_line(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        let stream = self.stream.name();
+        match self.stream {
+            LogStream::Stdout => tracing::info!(
+                target: "wasmer_wasi::guest",
+                instance = %self.instance_id,
+                module = %self.module_name,
+                stream,
+                "{}",
+                line
+            ),
+            LogStream::Stderr => tracing::warn!(
+                target: "wasmer_wasi::guest",
+                instance = %self.instance_id,
+                module = %self.module_name,
+                stream,
+                "{}",
+                line
+            ),
+        }
+    }
+
+    fn log_pending(&mut self) {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.log_line(&buffer);
+        }
+    }
+}
+
+impl Drop for LogOutput {
+    fn drop(&mut self) {
+        self.log_pending();
+    }
+}
+
+impl Read for LogOutput {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("can not read from {}", self.stream.name()),
+        ))
+    }
+}
+
+impl Seek for LogOutput {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("can not seek {}", self.stream.name()),
+        ))
+    }
+}
+
+impl Write for LogOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        let mut start = 0;
+        while let Some(end) = self.buffer[start..].iter().position(|byte| *byte == b'\n') {
+            self.log_line(&self.buffer[start..start + end]);
+            start += end + 1;
+        }
+        while self.buffer.len() - start >= LOG_OUTPUT_MAX_LINE {
+            self.log_line(&self.buffer[start..start + LOG_OUTPUT_MAX_LINE]);
+            start += LOG_OUTPUT_MAX_LINE;
+        }
+        self.buffer.drain(..start);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.log_pending();
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for LogOutput {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        debug!(
+            "Calling WasiFile::set_len on {}; this is probably a bug",
+            self.stream.name()
         );
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        Ok(0)
     }
 }
 
@@ -979,3 +1720,146 @@ trait WasiFdBacking: std::fmt::Debug {
     fn get_name(&self) -> &str;
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_host_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Foo"), b"").unwrap();
+        let find =
+            |policy: PathPolicy, name: &str| policy.find_host_entry(dir.path(), OsStr::new(name));
+
+        assert_eq!(find(PathPolicy::CaseSensitive, "Foo"), Some("Foo".into()));
+        // even when the host itself ignores case
+        assert_eq!(find(PathPolicy::CaseSensitive, "foo"), None);
+        assert_eq!(find(PathPolicy::CaseSensitive, "bar"), None);
+        assert_eq!(
+            find(PathPolicy::CaseInsensitivePreserving, "FOO"),
+            Some("Foo".into())
+        );
+        assert_eq!(find(PathPolicy::CaseInsensitivePreserving, "bar"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn find_host_entry_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9");
+        fs::write(dir.path().join(name), b"").unwrap();
+        for policy in &[PathPolicy::CaseSensitive, PathPolicy::NfcNormalized] {
+            assert_eq!(
+                policy.find_host_entry(dir.path(), name),
+                Some(name.to_os_string())
+            );
+        }
+        // names that aren't UTF-8 are never equal under a policy
+        let other = OsStr::from_bytes(b"caf\xea");
+        assert_eq!(
+            PathPolicy::CaseInsensitivePreserving.find_host_entry(dir.path(), other),
+            None
+        );
+    }
+
+    /// A subscriber capturing the level and message of the events.
+    #[derive(Clone, Default)]
+    struct CapturedEvents(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+
+    impl CapturedEvents {
+        fn take(&self) -> Vec<(tracing::Level, String)> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl tracing::Subscriber for CapturedEvents {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message(String);
+            impl tracing::field::Visit for Message {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                    if field.name() == "message" {
+                        self.0 = format!("{:?}", value);
+                    }
+                }
+            }
+            if event.metadata().target() != "wasmer_wasi::guest" {
+                return;
+            }
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0
+                .lock()
+                .unwrap()
+                .push((*event.metadata().level(), message.0));
+        }
+        fn enter(&self, _span: &tracing::span::Id) {}
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    fn info(line: &str) -> (tracing::Level, String) {
+        (tracing::Level::INFO, line.to_string())
+    }
+
+    #[test]
+    fn log_output_splits_lines() {
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut stdout = LogOutput::stdout("id", "module");
+            stdout.write_all(b"first\nsecond\r\nthi").unwrap();
+            assert_eq!(events.take(), vec![info("first"), info("second")]);
+            stdout.write_all(b"rd\n\nlast").unwrap();
+            assert_eq!(events.take(), vec![info("third"), info("")]);
+            stdout.flush().unwrap();
+            assert_eq!(events.take(), vec![info("last")]);
+            // nothing is pending anymore
+            stdout.flush().unwrap();
+            assert_eq!(events.take(), vec![]);
+
+            let mut stderr = LogOutput::stderr("id", "module");
+            stderr.write_all(b"error\n").unwrap();
+            assert_eq!(
+                events.take(),
+                vec![(tracing::Level::WARN, "error".to_string())]
+            );
+        });
+    }
+
+    #[test]
+    fn log_output_cuts_long_lines() {
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut stdout = LogOutput::stdout("id", "module");
+            let long = "a".repeat(LOG_OUTPUT_MAX_LINE);
+            stdout.write_all(long[..10].as_bytes()).unwrap();
+            assert_eq!(events.take(), vec![]);
+            stdout.write_all(long[10..].as_bytes()).unwrap();
+            stdout.write_all(b"bb").unwrap();
+            assert_eq!(events.take(), vec![info(&long)]);
+            stdout.write_all(b"\n").unwrap();
+            assert_eq!(events.take(), vec![info("bb")]);
+        });
+    }
+
+    #[test]
+    fn log_output_logs_the_pending_line_on_drop() {
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut stdout = LogOutput::stdout("id", "module");
+            stdout.write_all(b"done\nno newline\r").unwrap();
+            assert_eq!(events.take(), vec![info("done")]);
+            drop(stdout);
+            assert_eq!(events.take(), vec![info("no newline")]);
+        });
+    }
+}
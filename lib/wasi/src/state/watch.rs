@@ -0,0 +1,260 @@
+//! Watching host directories for changes, for the `wasmer_fs` extension
+//! and for [`FreshnessPolicy::Watch`](crate::FreshnessPolicy).
+
+use crate::state::{WasiFile, WasiFsError};
+use crate::syscalls::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// The minimum time between two scans of a watched directory.
+const SCAN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The size of the header of an event: its kind, 3 bytes of padding, and
+/// the length of its name as a little-endian `u32`.
+const EVENT_HEADER_SIZE: usize = 8;
+
+/// A file reporting the changes under a host directory, created by
+/// `fs_watch`.
+///
+/// Reading it returns the events, each one an 8-byte header followed by
+/// the path of the changed entry relative to the watched directory. The
+/// directory is scanned for changes when the file is polled or read, so
+/// the changes of the program and of the host are both reported.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatchFile {
+    host_path: PathBuf,
+    recursive: bool,
+    #[serde(skip)]
+    state: Mutex<WatchState>,
+}
+
+#[derive(Debug, Default)]
+struct WatchState {
+    /// the entries seen by the last scan, with their size and modification time
+    entries: BTreeMap<String, (u64, u128)>,
+    events: VecDeque<Vec<u8>>,
+    last_scan: Option<Instant>,
+}
+
+impl WatchFile {
+    /// Watches the entries of the host directory `host_path`, and of its
+    /// subdirectories if `recursive`.
+    pub fn new(host_path: PathBuf, recursive: bool) -> Self {
+        let mut entries = BTreeMap::new();
+        scan(&host_path, "", recursive, &mut entries);
+        Self {
+            host_path,
+            recursive,
+            state: Mutex::new(WatchState {
+                entries,
+                events: VecDeque::new(),
+                last_scan: Some(Instant::now()),
+            }),
+        }
+    }
+
+    /// Scans the directory if it wasn't scanned recently, and queues an
+    /// event for each change since the last scan.
+    fn update(&self, state: &mut WatchState) {
+        if let Some(last_scan) = state.last_scan {
+            if last_scan.elapsed() < SCAN_INTERVAL {
+                return;
+            }
+        }
+        let mut entries = BTreeMap::new();
+        scan(&self.host_path, "", self.recursive, &mut entries);
+        if state.last_scan.is_some() {
+            for (name, stat) in entries.iter() {
+                match state.entries.get(name) {
+                    None => state.events.push_back(event(__WASI_WATCH_CREATED, name)),
+                    Some(old_stat) if old_stat != stat => {
+                        state.events.push_back(event(__WASI_WATCH_MODIFIED, name))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for name in state.entries.keys() {
+                if !entries.contains_key(name) {
+                    state.events.push_back(event(__WASI_WATCH_REMOVED, name));
+                }
+            }
+        }
+        state.entries = entries;
+        state.last_scan = Some(Instant::now());
+    }
+}
+
+/// Adds the entries of the host directory `path` to `entries`, named
+/// after their path under `prefix`.
+fn scan(path: &Path, prefix: &str, recursive: bool, entries: &mut BTreeMap<String, (u64, u128)>) {
+    let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+    for entry in dir.filter_map(Result::ok) {
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos());
+        // a directory is modified when its entries are, which is reported
+        // for the entries themselves
+        let stat = if metadata.is_dir() {
+            (0, 0)
+        } else {
+            (metadata.len(), modified)
+        };
+        entries.insert(name.clone(), stat);
+        if recursive && metadata.is_dir() {
+            scan(&entry.path(), &format!("{}/", name), recursive, entries);
+        }
+    }
+}
+
+/// Notifications of the host about changes in the preopened directories.
+#[cfg(feature = "notify")]
+pub(crate) struct HostWatcher {
+    // dropping the watcher stops the notifications
+    _watcher: notify::RecommendedWatcher,
+    changes: std::sync::mpsc::Receiver<notify::DebouncedEvent>,
+}
+
+#[cfg(feature = "notify")]
+impl std::fmt::Debug for HostWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostWatcher").finish()
+    }
+}
+
+#[cfg(feature = "notify")]
+impl HostWatcher {
+    /// Watches the host directories `paths` and their subdirectories.
+    pub(crate) fn new(paths: &[PathBuf]) -> notify::Result<Self> {
+        use notify::Watcher;
+
+        let (sender, changes) = std::sync::mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(50))?;
+        for path in paths {
+            watcher.watch(path, notify::RecursiveMode::Recursive)?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Returns the directories whose entries changed since the last call,
+    /// or `None` if they all must be considered changed.
+    pub(crate) fn changed_dirs(&self) -> Option<std::collections::BTreeSet<PathBuf>> {
+        use notify::DebouncedEvent;
+
+        let mut dirs = std::collections::BTreeSet::new();
+        for change in self.changes.try_iter() {
+            let paths = match change {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Chmod(path)
+                | DebouncedEvent::Remove(path) => vec![path],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                DebouncedEvent::Rescan | DebouncedEvent::Error(..) => return None,
+                DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => vec![],
+            };
+            for path in paths {
+                // the changed entry, and the directory listing it
+                if let Some(parent) = path.parent() {
+                    dirs.insert(parent.to_path_buf());
+                }
+                dirs.insert(path);
+            }
+        }
+        Some(dirs)
+    }
+}
+
+fn event(kind: __wasi_watch_event_kind_t, name: &str) -> Vec<u8> {
+    let mut event = Vec::with_capacity(EVENT_HEADER_SIZE + name.len());
+    event.extend_from_slice(&[kind, 0, 0, 0]);
+    event.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    event.extend_from_slice(name.as_bytes());
+    event
+}
+
+impl Read for WatchFile {
+    /// Reads whole events only; fails if the first one doesn't fit in `buf`.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        self.update(&mut state);
+        let mut read = 0;
+        while let Some(event) = state.events.front() {
+            if read + event.len() > buf.len() {
+                if read == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the buffer is too small for the next event",
+                    ));
+                }
+                break;
+            }
+            buf[read..read + event.len()].copy_from_slice(event);
+            read += event.len();
+            state.events.pop_front();
+        }
+        Ok(read)
+    }
+}
+
+impl Seek for WatchFile {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+impl Write for WatchFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "can not write to a watch",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for WatchFile {
+    fn last_accessed(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn last_modified(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn created_time(&self) -> __wasi_timestamp_t {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        let mut state = self.state.lock().unwrap();
+        self.update(&mut state);
+        Ok(state.events.iter().map(Vec::len).sum())
+    }
+}
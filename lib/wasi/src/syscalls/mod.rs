@@ -17,9 +17,9 @@ use self::types::*;
 use crate::{
     ptr::{Array, WasmPtr},
     state::{
-        self, host_file_type_to_wasi_file_type, iterate_poll_events, poll, Fd, HostFile, Inode,
-        InodeVal, Kind, PollEvent, PollEventBuilder, WasiFile, WasiFsError, WasiState,
-        MAX_SYMLINKS,
+        self, host_file_type_to_wasi_file_type, iterate_poll_events, poll, Fd, HostFile, ImageFile,
+        Inode, InodeVal, Kind, PollEvent, PollEventBuilder, RemoteFile, TtyPolicy, WasiFile,
+        WasiFsError, WasiState, WatchFile, MAX_SYMLINKS,
     },
     WasiEnv, WasiError,
 };
@@ -28,7 +28,7 @@ use std::cell::Cell;
 use std::convert::{Infallible, TryInto};
 use std::io::{self, Read, Seek, Write};
 use tracing::{debug, trace};
-use wasmer::{Memory, RuntimeError};
+use wasmer::{Memory, RuntimeError, StderrCapture};
 
 #[cfg(any(
     target_os = "freebsd",
@@ -70,6 +70,25 @@ fn write_bytes<T: Write>(
     result
 }
 
+/// Writes to the stderr of the guest, recording what's written so the
+/// message of a panic can be reported in its exit status.
+struct CapturedStderr<'a, W: Write> {
+    stderr: W,
+    capture: &'a mut StderrCapture,
+}
+
+impl<W: Write> Write for CapturedStderr<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stderr.write(buf)?;
+        self.capture.write(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stderr.flush()
+    }
+}
+
 fn read_bytes<T: Read>(
     mut reader: T,
     memory: &Memory,
@@ -82,11 +101,73 @@ fn read_bytes<T: Read>(
         let bytes = iov_inner.buf.deref(memory, 0, iov_inner.buf_len)?;
         let mut raw_bytes: &mut [u8] =
             unsafe { &mut *(bytes as *const [_] as *mut [_] as *mut [u8]) };
-        bytes_read += reader.read(raw_bytes).map_err(|_| __WASI_EIO)? as u32;
+        let n = reader.read(raw_bytes).map_err(|_| __WASI_EIO)?;
+        bytes_read += n as u32;
+        // a short read means there's no more input for now; reading the next
+        // iovec could block on an interactive stdin
+        if n < raw_bytes.len() {
+            break;
+        }
     }
     Ok(bytes_read)
 }
 
+/// Waits until a read, or a write, of `fd` is within the `IoLimits` of the
+/// filesystem, or returns `__WASI_EAGAIN` if they say so.  The state is
+/// unlocked while waiting, so that the other threads sharing it can go on.
+fn throttle_io(env: &WasiEnv, fd: __wasi_fd_t, write: bool) -> Result<(), __wasi_errno_t> {
+    // the standard streams aren't limited
+    if fd <= __WASI_STDERR_FILENO {
+        return Ok(());
+    }
+    loop {
+        // the guard is dropped at the end of the statement
+        let wait = env.state().fs.throttle_io(write)?;
+        match wait {
+            Some(wait) => std::thread::sleep(wait),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Waits until a read of `fd` doesn't block, when its reads block until
+/// another thread provides input, like those of an `InteractiveStdin`.  The
+/// state is unlocked while waiting, so that the other threads sharing it can
+/// go on.
+fn wait_read_ready(env: &WasiEnv, fd: __wasi_fd_t) {
+    let waiter =
+        {
+            let state = env.state();
+            let fd = state.fs.tty_stdio_fd(fd, false);
+            state.fs.fd_map.get(&fd).and_then(|fd_entry| {
+                match &state.fs.inodes[fd_entry.inode].kind {
+                    Kind::File {
+                        handle: Some(handle),
+                        ..
+                    } => handle.read_waiter(),
+                    _ => None,
+                }
+            })
+        };
+    if let Some(wait) = waiter {
+        wait();
+    }
+}
+
+/// Converts an error from opening a file on the host.  The host running out of
+/// fds is reported as `__WASI_ENFILE`, `__WASI_EMFILE` is used for the limit set
+/// on the guest with `WasiFs::max_open_fds`.
+fn host_open_error(err: io::Error) -> __wasi_errno_t {
+    debug!("Error opening file {}", err);
+    #[cfg(unix)]
+    {
+        if let Some(libc::EMFILE) | Some(libc::ENFILE) = err.raw_os_error() {
+            return __WASI_ENFILE;
+        }
+    }
+    __WASI_EIO
+}
+
 /// checks that `rights_check_set` is a subset of `rights_set`
 fn has_rights(rights_set: __wasi_rights_t, rights_check_set: __wasi_rights_t) -> bool {
     rights_set | rights_check_set == rights_set
@@ -150,11 +231,7 @@ pub fn args_get(
             .args
             .iter()
             .enumerate()
-            .map(|(i, v)| format!(
-                "{:>20}: {}",
-                i,
-                ::std::str::from_utf8(v).unwrap().to_string()
-            ))
+            .map(|(i, v)| format!("{:>20}: {}", i, String::from_utf8_lossy(v)))
             .collect::<Vec<String>>()
             .join("\n")
     );
@@ -354,6 +431,7 @@ pub fn fd_allocate(
             buffer.resize(new_size as usize, 0);
         }
         Kind::Symlink { .. } => return __WASI_EBADF,
+        Kind::Device { .. } => return __WASI_EINVAL,
         Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
     }
     state.fs.inodes[inode].stat.st_size = new_size;
@@ -549,6 +627,7 @@ pub fn fd_filestat_set_size(
             buffer.resize(st_size as usize, 0);
         }
         Kind::Symlink { .. } => return __WASI_EBADF,
+        Kind::Device { .. } => return __WASI_EINVAL,
         Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
     }
     state.fs.inodes[inode].stat.st_size = st_size;
@@ -647,7 +726,9 @@ pub fn fd_pread(
     nread: WasmPtr<u32>,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_pread: fd={}, offset={}", fd, offset);
+    wasi_try!(throttle_io(env, fd, false));
     let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let fd = state.fs.tty_stdio_fd(fd, false);
 
     let iov_cells = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nread_cell = wasi_try!(nread.deref(memory));
@@ -677,7 +758,7 @@ pub fn fd_pread(
                 );
                 return __WASI_EACCES;
             }
-            match &mut state.fs.inodes[inode].kind {
+            let bytes_read = match &mut state.fs.inodes[inode].kind {
                 Kind::File { handle, .. } => {
                     if let Some(h) = handle {
                         wasi_try!(
@@ -690,16 +771,25 @@ pub fn fd_pread(
                     }
                 }
                 Kind::Dir { .. } | Kind::Root { .. } => return __WASI_EISDIR,
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pread"),
-                Kind::Buffer { buffer } => {
-                    wasi_try!(read_bytes(&buffer[(offset as usize)..], memory, iov_cells))
-                }
-            }
+                Kind::Symlink { .. } => return __WASI_ENOTSUP,
+                Kind::Buffer { buffer } => wasi_try!(read_bytes(
+                    buffer.get(offset as usize..).unwrap_or_default(),
+                    memory,
+                    iov_cells
+                )),
+                Kind::Device { device } => wasi_try!(read_bytes(device, memory, iov_cells)),
+            };
+            state.fs.charge_io(false, bytes_read as usize);
+
+            bytes_read
         }
     };
 
     nread_cell.set(bytes_read);
     debug!("Success: {} bytes read", bytes_read);
+    if let Some(metrics) = &state.metrics {
+        metrics.record_bytes("fd_pread", bytes_read as u64);
+    }
     __WASI_ESUCCESS
 }
 
@@ -757,10 +847,9 @@ pub fn fd_prestat_dir_name(
 
                 debug!(
                     "=> result: \"{}\"",
-                    ::std::str::from_utf8(unsafe {
+                    String::from_utf8_lossy(unsafe {
                         &*(&path_chars[..] as *const [_] as *const [u8])
                     })
-                    .unwrap()
                 );
 
                 __WASI_ESUCCESS
@@ -768,7 +857,9 @@ pub fn fd_prestat_dir_name(
                 __WASI_EOVERFLOW
             }
         }
-        Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::File { .. } => __WASI_ENOTDIR,
+        Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::File { .. } | Kind::Device { .. } => {
+            __WASI_ENOTDIR
+        }
     }
 }
 
@@ -796,7 +887,9 @@ pub fn fd_pwrite(
 ) -> __wasi_errno_t {
     debug!("wasi::fd_pwrite");
     // TODO: refactor, this is just copied from `fd_write`...
+    wasi_try!(throttle_io(env, fd, true));
     let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let fd = state.fs.tty_stdio_fd(fd, true);
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nwritten_cell = wasi_try!(nwritten.deref(memory));
 
@@ -812,9 +905,14 @@ pub fn fd_pwrite(
             }
         }
         __WASI_STDERR_FILENO => {
+            let state = &mut *state;
             if let Some(ref mut stderr) =
                 wasi_try!(state.fs.stderr_mut().map_err(WasiFsError::into_wasi_err))
             {
+                let stderr = CapturedStderr {
+                    stderr,
+                    capture: &mut state.stderr_capture,
+                };
                 wasi_try!(write_bytes(stderr, memory, iovs_arr_cell))
             } else {
                 return __WASI_EBADF;
@@ -832,7 +930,7 @@ pub fn fd_pwrite(
             let inode_idx = fd_entry.inode;
             let inode = &mut state.fs.inodes[inode_idx];
 
-            match &mut inode.kind {
+            let bytes_written = match &mut inode.kind {
                 Kind::File { handle, .. } => {
                     if let Some(handle) = handle {
                         handle.seek(std::io::SeekFrom::Start(offset as u64));
@@ -845,18 +943,26 @@ pub fn fd_pwrite(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_pwrite"),
+                Kind::Symlink { .. } => return __WASI_ENOTSUP,
                 Kind::Buffer { buffer } => wasi_try!(write_bytes(
-                    &mut buffer[(offset as usize)..],
+                    wasi_try!(buffer.get_mut(offset as usize..), __WASI_EINVAL),
                     memory,
                     iovs_arr_cell
                 )),
-            }
+                Kind::Device { device } => wasi_try!(write_bytes(device, memory, iovs_arr_cell)),
+            };
+            state.fs.charge_io(true, bytes_written as usize);
+
+            bytes_written
         }
     };
 
     nwritten_cell.set(bytes_written);
 
+    if let Some(metrics) = &state.metrics {
+        metrics.record_bytes("fd_pwrite", bytes_written as u64);
+    }
+
     __WASI_ESUCCESS
 }
 
@@ -880,7 +986,10 @@ pub fn fd_read(
     nread: WasmPtr<u32>,
 ) -> __wasi_errno_t {
     debug!("wasi::fd_read: fd={}", fd);
+    wasi_try!(throttle_io(env, fd, false));
+    wait_read_ready(env, fd);
     let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let fd = state.fs.tty_stdio_fd(fd, false);
 
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nread_cell = wasi_try!(nread.deref(memory));
@@ -921,11 +1030,15 @@ pub fn fd_read(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_read"),
-                Kind::Buffer { buffer } => {
-                    wasi_try!(read_bytes(&buffer[offset..], memory, iovs_arr_cell))
-                }
+                Kind::Symlink { .. } => return __WASI_ENOTSUP,
+                Kind::Buffer { buffer } => wasi_try!(read_bytes(
+                    buffer.get(offset..).unwrap_or_default(),
+                    memory,
+                    iovs_arr_cell
+                )),
+                Kind::Device { device } => wasi_try!(read_bytes(device, memory, iovs_arr_cell)),
             };
+            state.fs.charge_io(false, bytes_read as usize);
 
             // reborrow
             let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
@@ -937,6 +1050,10 @@ pub fn fd_read(
 
     nread_cell.set(bytes_read);
 
+    if let Some(metrics) = &state.metrics {
+        metrics.record_bytes("fd_read", bytes_read as u64);
+    }
+
     __WASI_ESUCCESS
 }
 
@@ -970,6 +1087,9 @@ pub fn fd_readdir(
 
     let buf_arr_cell = wasi_try!(buf.deref(memory, 0, buf_len));
     let bufused_cell = wasi_try!(bufused.deref(memory));
+    // the entries of the directories of images and remote filesystems are loaded lazily
+    let working_dir_inode = wasi_try!(state.fs.get_fd(fd)).inode;
+    wasi_try!(state.fs.load_virtual_dir(working_dir_inode));
     let working_dir = wasi_try!(state.fs.fd_map.get(&fd).ok_or(__WASI_EBADF));
     let mut cur_cookie = cookie;
     let mut buf_idx = 0;
@@ -980,9 +1100,15 @@ pub fn fd_readdir(
             // we need to support multiple calls,
             // simple and obviously correct implementation for now:
             // maintain consistent order via lexacographic sorting
-            let fs_info = wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|_| __WASI_EIO));
+            // virtual directories, such as `/dev`, have no host directory to list
+            let is_virtual = path.as_os_str().is_empty();
+            let fs_info = if is_virtual {
+                vec![]
+            } else {
+                wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| __WASI_EIO))
+            };
             let mut entry_vec = wasi_try!(fs_info
                 .into_iter()
                 .map(|entry| Ok((
@@ -994,7 +1120,7 @@ pub fn fd_readdir(
             entry_vec.extend(
                 entries
                     .iter()
-                    .filter(|(_, inode)| state.fs.inodes[**inode].is_preopened)
+                    .filter(|(_, inode)| is_virtual || state.fs.inodes[**inode].is_preopened)
                     .map(|(name, inode)| {
                         let entry = &state.fs.inodes[*inode];
                         (
@@ -1026,7 +1152,9 @@ pub fn fd_readdir(
                 })
                 .collect()
         }
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => return __WASI_ENOTDIR,
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Device { .. } => {
+            return __WASI_ENOTDIR
+        }
     };
 
     for (entry_path_str, wasi_file_type, ino) in entries.iter().skip(cookie as usize) {
@@ -1076,12 +1204,19 @@ pub fn fd_renumber(env: &mut WasiEnv, from: __wasi_fd_t, to: __wasi_fd_t) -> __w
     debug!("wasi::fd_renumber: from={}, to={}", from, to);
     let (memory, mut state) = env.get_memory_and_wasi_state(0);
     let fd_entry = wasi_try!(state.fs.fd_map.get(&from).ok_or(__WASI_EBADF));
+    if from == to {
+        return __WASI_ESUCCESS;
+    }
     let new_fd_entry = Fd {
         // TODO: verify this is correct
         rights: fd_entry.rights_inheriting,
         ..*fd_entry
     };
 
+    // `to` is closed first so that whatever it referred to isn't leaked
+    if state.fs.fd_map.contains_key(&to) {
+        wasi_try!(state.fs.close_fd(to));
+    }
     state.fs.fd_map.insert(to, new_fd_entry);
     state.fs.fd_map.remove(&from);
     __WASI_ESUCCESS
@@ -1135,9 +1270,7 @@ pub fn fd_seek(
                         return __WASI_EINVAL;
                     }
                 }
-                Kind::Symlink { .. } => {
-                    unimplemented!("wasi::fd_seek not implemented for symlinks")
-                }
+                Kind::Symlink { .. } => return __WASI_ENOTSUP,
                 Kind::Dir { .. } | Kind::Root { .. } => {
                     // TODO: check this
                     return __WASI_EINVAL;
@@ -1147,6 +1280,8 @@ pub fn fd_seek(
                     // TODO: implement this
                     return __WASI_EINVAL;
                 }
+                // character devices have no position; seeking them is a no-op
+                Kind::Device { .. } => (),
             }
         }
         __WASI_WHENCE_SET => fd_entry.offset = offset as u64,
@@ -1188,6 +1323,7 @@ pub fn fd_sync(env: &mut WasiEnv, fd: __wasi_fd_t) -> __wasi_errno_t {
             }
         }
         Kind::Root { .. } | Kind::Dir { .. } => return __WASI_EISDIR,
+        Kind::Device { device } => wasi_try!(device.flush().map_err(|_| __WASI_EIO)),
         Kind::Buffer { .. } | Kind::Symlink { .. } => return __WASI_EINVAL,
     }
 
@@ -1251,7 +1387,9 @@ pub fn fd_write(
     } else {
         trace!("wasi::fd_write: fd={}", fd);
     }
+    wasi_try!(throttle_io(env, fd, true));
     let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let fd = state.fs.tty_stdio_fd(fd, true);
     let iovs_arr_cell = wasi_try!(iovs.deref(memory, 0, iovs_len));
     let nwritten_cell = wasi_try!(nwritten.deref(memory));
 
@@ -1267,9 +1405,14 @@ pub fn fd_write(
             }
         }
         __WASI_STDERR_FILENO => {
+            let state = &mut *state;
             if let Some(ref mut stderr) =
                 wasi_try!(state.fs.stderr_mut().map_err(WasiFsError::into_wasi_err))
             {
+                let stderr = CapturedStderr {
+                    stderr,
+                    capture: &mut state.stderr_capture,
+                };
                 wasi_try!(write_bytes(stderr, memory, iovs_arr_cell))
             } else {
                 return __WASI_EBADF;
@@ -1299,11 +1442,15 @@ pub fn fd_write(
                     // TODO: verify
                     return __WASI_EISDIR;
                 }
-                Kind::Symlink { .. } => unimplemented!("Symlinks in wasi::fd_write"),
-                Kind::Buffer { buffer } => {
-                    wasi_try!(write_bytes(&mut buffer[offset..], memory, iovs_arr_cell))
-                }
+                Kind::Symlink { .. } => return __WASI_ENOTSUP,
+                Kind::Buffer { buffer } => wasi_try!(write_bytes(
+                    wasi_try!(buffer.get_mut(offset..), __WASI_EINVAL),
+                    memory,
+                    iovs_arr_cell
+                )),
+                Kind::Device { device } => wasi_try!(write_bytes(device, memory, iovs_arr_cell)),
             };
+            state.fs.charge_io(true, bytes_written as usize);
 
             // reborrow
             let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
@@ -1316,6 +1463,10 @@ pub fn fd_write(
 
     nwritten_cell.set(bytes_written);
 
+    if let Some(metrics) = &state.metrics {
+        metrics.record_bytes("fd_write", bytes_written as u64);
+    }
+
     __WASI_ESUCCESS
 }
 
@@ -1367,9 +1518,12 @@ pub fn path_create_directory(
 
     debug!("Looking at components {:?}", &path_vec);
 
+    let path_policy = state.fs.path_policy;
     let mut cur_dir_inode = working_dir.inode;
     for comp in &path_vec {
         debug!("Creating dir {}", comp);
+        let comp = path_policy.normalize(comp);
+        let entry_key = path_policy.key(&comp);
         match &mut state.fs.inodes[cur_dir_inode].kind {
             Kind::Dir {
                 ref mut entries,
@@ -1386,15 +1540,20 @@ pub fn path_create_directory(
                     "." => continue,
                     _ => (),
                 }
-                if let Some(child) = entries.get(comp) {
+                if let Some(child) = entries.get(&entry_key) {
                     cur_dir_inode = *child;
                 } else {
                     let mut adjusted_path = path.clone();
                     // TODO: double check this doesn't risk breaking the sandbox
-                    adjusted_path.push(comp);
-                    if adjusted_path.exists() && !adjusted_path.is_dir() {
-                        return __WASI_ENOTDIR;
-                    } else if !adjusted_path.exists() {
+                    if let Some(host_name) =
+                        path_policy.find_host_entry(path, std::ffi::OsStr::new(&comp))
+                    {
+                        adjusted_path.push(host_name);
+                        if !adjusted_path.is_dir() {
+                            return __WASI_ENOTDIR;
+                        }
+                    } else {
+                        adjusted_path.push(&comp);
                         wasi_try!(std::fs::create_dir(&adjusted_path).ok(), __WASI_EIO);
                     }
                     let kind = Kind::Dir {
@@ -1402,13 +1561,13 @@ pub fn path_create_directory(
                         path: adjusted_path,
                         entries: Default::default(),
                     };
-                    let new_inode = wasi_try!(state.fs.create_inode(kind, false, comp.to_string()));
+                    let new_inode = wasi_try!(state.fs.create_inode(kind, false, comp));
                     // reborrow to insert
                     if let Kind::Dir {
                         ref mut entries, ..
                     } = &mut state.fs.inodes[cur_dir_inode].kind
                     {
-                        entries.insert(comp.to_string(), new_inode);
+                        entries.insert(entry_key, new_inode);
                     }
                     cur_dir_inode = new_inode;
                 }
@@ -1624,17 +1783,62 @@ pub fn path_link(
     if state.fs.inodes[source_inode].stat.st_nlink == __wasi_linkcount_t::max_value() {
         return __WASI_EMLINK;
     }
-    match &mut state.fs.inodes[target_parent_inode].kind {
-        Kind::Dir { entries, .. } => {
-            if entries.contains_key(&new_entry_name) {
+    let new_entry_key = state.fs.path_policy.key(&new_entry_name);
+    let target_host_path = match &state.fs.inodes[target_parent_inode].kind {
+        Kind::Dir { entries, path, .. } => {
+            if entries.contains_key(&new_entry_key) {
                 return __WASI_EEXIST;
             }
-            entries.insert(new_entry_name, source_inode);
+            let mut out_path = path.clone();
+            out_path.push(&new_entry_name);
+            out_path
         }
         Kind::Root { .. } => return __WASI_EINVAL,
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => return __WASI_ENOTDIR,
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Device { .. } => {
+            return __WASI_ENOTDIR
+        }
+    };
+
+    let link_inode = match &state.fs.inodes[source_inode].kind {
+        // files that live on the host get a real hard link and an inode of their own, so
+        // that unlinking either entry removes the right host path
+        Kind::File { handle, path, .. } if !path.as_os_str().is_empty() => {
+            let result = if let Some(h) = handle {
+                h.hard_link(&target_host_path)
+            } else {
+                std::fs::hard_link(path, &target_host_path).map_err(Into::into)
+            };
+            wasi_try!(result.map_err(WasiFsError::into_wasi_err));
+            let kind = Kind::File {
+                handle: None,
+                path: target_host_path,
+                fd: None,
+            };
+            let st_ino = state.fs.inodes[source_inode].stat.st_ino;
+            let new_inode = wasi_try!(state.fs.create_inode(kind, false, new_entry_name.clone()));
+            let nlink = state.fs.inodes[new_inode].stat.st_nlink;
+            state.fs.inodes[new_inode].stat.st_ino = st_ino;
+            state.fs.set_nlink(st_ino, nlink);
+            new_inode
+        }
+        // virtual files only exist in the WASI FS, so the entries share one inode
+        Kind::File { .. } | Kind::Buffer { .. } => {
+            state.fs.inodes[source_inode].stat.st_nlink += 1;
+            source_inode
+        }
+        Kind::Dir { .. } | Kind::Root { .. } | Kind::Device { .. } => {
+            debug!("wasi::path_link: hard links to directories and devices are not allowed");
+            return __WASI_EPERM;
+        }
+        Kind::Symlink { .. } => {
+            debug!("wasi::path_link: hard links to symlinks are not supported");
+            return __WASI_ENOTSUP;
+        }
+    };
+
+    if let Kind::Dir { entries, .. } = &mut state.fs.inodes[target_parent_inode].kind {
+        entries.insert(new_entry_key, link_inode);
     }
-    state.fs.inodes[source_inode].stat.st_nlink += 1;
 
     __WASI_ESUCCESS
 }
@@ -1686,6 +1890,8 @@ pub fn path_open(
     }
 
     let fd_cell = wasi_try!(fd.deref(memory));
+    // fail before anything is opened or created on the host
+    wasi_try!(state.fs.check_fd_limit());
 
     // o_flags:
     // - __WASI_O_CREAT (create if it does not exist)
@@ -1733,48 +1939,75 @@ pub fn path_open(
                 if o_flags & __WASI_O_DIRECTORY != 0 {
                     return __WASI_ENOTDIR;
                 }
-                if o_flags & __WASI_O_EXCL != 0 && path.exists() {
+                // in-memory files, such as the files of images, stay open
+                if path.as_os_str().is_empty() && handle.is_some() {
+                    if o_flags & __WASI_O_EXCL != 0 {
+                        return __WASI_EEXIST;
+                    }
+                    open_flags |= Fd::READ;
+                    if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
+                        open_flags |= Fd::WRITE;
+                        if let (Some(handle), true) = (handle, o_flags & __WASI_O_TRUNC != 0) {
+                            wasi_try!(handle.set_len(0).map_err(WasiFsError::into_wasi_err));
+                            open_flags |= Fd::TRUNCATE;
+                        }
+                    }
+                } else {
+                    if o_flags & __WASI_O_EXCL != 0 && path.exists() {
+                        return __WASI_EEXIST;
+                    }
+                    let mut open_options = std::fs::OpenOptions::new();
+                    let write_permission = adjusted_rights & __WASI_RIGHT_FD_WRITE != 0;
+                    // append, truncate, and create all require the permission to write
+                    let (append_permission, truncate_permission, create_permission) =
+                        if write_permission {
+                            (
+                                fs_flags & __WASI_FDFLAG_APPEND != 0,
+                                o_flags & __WASI_O_TRUNC != 0,
+                                o_flags & __WASI_O_CREAT != 0,
+                            )
+                        } else {
+                            (false, false, false)
+                        };
+                    let open_options = open_options
+                        .read(true)
+                        // TODO: ensure these rights are actually valid given parent, etc.
+                        .write(write_permission)
+                        .create(create_permission)
+                        .append(append_permission)
+                        .truncate(truncate_permission);
+                    open_flags |= Fd::READ;
+                    if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
+                        open_flags |= Fd::WRITE;
+                    }
+                    if o_flags & __WASI_O_CREAT != 0 {
+                        open_flags |= Fd::CREATE;
+                    }
+                    if o_flags & __WASI_O_TRUNC != 0 {
+                        open_flags |= Fd::TRUNCATE;
+                    }
+                    *handle = Some(Box::new(HostFile::new(
+                        wasi_try!(open_options.open(&path).map_err(host_open_error)),
+                        path.to_path_buf(),
+                        true,
+                        adjusted_rights & __WASI_RIGHT_FD_WRITE != 0,
+                        false,
+                    )));
+                }
+            }
+            Kind::Buffer { .. } => return __WASI_ENOTSUP,
+            Kind::Device { .. } => {
+                if o_flags & __WASI_O_EXCL != 0 {
                     return __WASI_EEXIST;
                 }
-                let mut open_options = std::fs::OpenOptions::new();
-                let write_permission = adjusted_rights & __WASI_RIGHT_FD_WRITE != 0;
-                // append, truncate, and create all require the permission to write
-                let (append_permission, truncate_permission, create_permission) =
-                    if write_permission {
-                        (
-                            fs_flags & __WASI_FDFLAG_APPEND != 0,
-                            o_flags & __WASI_O_TRUNC != 0,
-                            o_flags & __WASI_O_CREAT != 0,
-                        )
-                    } else {
-                        (false, false, false)
-                    };
-                let open_options = open_options
-                    .read(true)
-                    // TODO: ensure these rights are actually valid given parent, etc.
-                    .write(write_permission)
-                    .create(create_permission)
-                    .append(append_permission)
-                    .truncate(truncate_permission);
+                if o_flags & __WASI_O_DIRECTORY != 0 {
+                    return __WASI_ENOTDIR;
+                }
                 open_flags |= Fd::READ;
                 if adjusted_rights & __WASI_RIGHT_FD_WRITE != 0 {
                     open_flags |= Fd::WRITE;
                 }
-                if o_flags & __WASI_O_CREAT != 0 {
-                    open_flags |= Fd::CREATE;
-                }
-                if o_flags & __WASI_O_TRUNC != 0 {
-                    open_flags |= Fd::TRUNCATE;
-                }
-                *handle = Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&path).map_err(|_| __WASI_EIO)),
-                    path.to_path_buf(),
-                    true,
-                    adjusted_rights & __WASI_RIGHT_FD_WRITE != 0,
-                    false,
-                )));
             }
-            Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
             Kind::Dir { .. } | Kind::Root { .. } => {
                 // TODO: adjust these to be correct
                 if o_flags & __WASI_O_EXCL != 0 && path_arg.exists() {
@@ -1786,9 +2019,8 @@ pub fn path_open(
                 path_to_symlink,
                 relative_path,
             } => {
-                // I think this should return an error (because symlinks should be resolved away by the path traversal)
-                // TODO: investigate this
-                unimplemented!("SYMLINKS IN PATH_OPEN");
+                // Symlinks should be resolved away by the path traversal.
+                return __WASI_ENOTSUP;
             }
         }
         inode
@@ -1808,6 +2040,8 @@ pub fn path_open(
                 dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0
             ));
             let new_file_host_path = match &state.fs.inodes[parent_inode].kind {
+                // virtual directories, such as the ones of images, get in-memory files
+                Kind::Dir { path, .. } if path.as_os_str().is_empty() => std::path::PathBuf::new(),
                 Kind::Dir { path, .. } => {
                     let mut new_path = path.clone();
                     new_path.push(&new_entity_name);
@@ -1818,7 +2052,15 @@ pub fn path_open(
             };
             // once we got the data we need from the parent, we lookup the host file
             // todo: extra check that opening with write access is okay
-            let handle = {
+            let handle = if let Some((fs, remote_path)) = state.fs.remote_dir(parent_inode) {
+                let remote_path = remote_path.join(&new_entity_name);
+                wasi_try!(fs.create(&remote_path).map_err(WasiFsError::into_wasi_err));
+                open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
+                Some(Box::new(RemoteFile::new(fs, remote_path)) as Box<dyn WasiFile>)
+            } else if new_file_host_path.as_os_str().is_empty() {
+                open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
+                Some(Box::new(ImageFile::new(Vec::new())) as Box<dyn WasiFile>)
+            } else {
                 let mut open_options = std::fs::OpenOptions::new();
                 let open_options = open_options
                     .read(true)
@@ -1830,10 +2072,9 @@ pub fn path_open(
                 open_flags |= Fd::READ | Fd::WRITE | Fd::CREATE | Fd::TRUNCATE;
 
                 Some(Box::new(HostFile::new(
-                    wasi_try!(open_options.open(&new_file_host_path).map_err(|e| {
-                        debug!("Error opening file {}", e);
-                        __WASI_EIO
-                    })),
+                    wasi_try!(open_options
+                        .open(&new_file_host_path)
+                        .map_err(host_open_error)),
                     new_file_host_path.clone(),
                     true,
                     true,
@@ -1850,11 +2091,12 @@ pub fn path_open(
                 wasi_try!(state.fs.create_inode(kind, false, new_entity_name.clone()))
             };
 
+            let new_entity_key = state.fs.path_policy.key(&new_entity_name);
             if let Kind::Dir {
                 ref mut entries, ..
             } = &mut state.fs.inodes[parent_inode].kind
             {
-                entries.insert(new_entity_name, new_inode);
+                entries.insert(new_entity_key, new_inode);
             }
 
             new_inode
@@ -1963,6 +2205,7 @@ pub fn path_remove_directory(
         wasi_try!(state
             .fs
             .get_parent_inode_at_path(fd, std::path::Path::new(path_str), false));
+    let childs_key = state.fs.path_policy.key(&childs_name);
 
     let host_path_to_remove = match &state.fs.inodes[inode].kind {
         Kind::Dir { entries, path, .. } => {
@@ -1981,14 +2224,12 @@ pub fn path_remove_directory(
         Kind::Dir {
             ref mut entries, ..
         } => {
-            let removed_inode = wasi_try!(entries.remove(&childs_name).ok_or(__WASI_EINVAL));
+            let removed_inode = wasi_try!(entries.remove(&childs_key).ok_or(__WASI_EINVAL));
             // TODO: make this a debug assert in the future
             assert!(inode == removed_inode);
         }
         Kind::Root { .. } => return __WASI_EACCES,
-        _ => unreachable!(
-            "Internal logic error in wasi::path_remove_directory, parent is not a directory"
-        ),
+        _ => return __WASI_ENOTDIR,
     }
 
     if std::fs::remove_dir(path_str).is_err() {
@@ -1997,7 +2238,7 @@ pub fn path_remove_directory(
             ref mut entries, ..
         } = &mut state.fs.inodes[parent_inode].kind
         {
-            entries.insert(childs_name, inode);
+            entries.insert(childs_key, inode);
         }
         // TODO: more intelligently return error value by inspecting returned error value
         return __WASI_EIO;
@@ -2052,10 +2293,12 @@ pub fn path_rename(
         wasi_try!(state.fs.get_parent_inode_at_path(old_fd, source_path, true));
     let (target_parent_inode, target_entry_name) =
         wasi_try!(state.fs.get_parent_inode_at_path(new_fd, target_path, true));
+    let source_entry_key = state.fs.path_policy.key(&source_entry_name);
+    let target_entry_key = state.fs.path_policy.key(&target_entry_name);
 
     let host_adjusted_target_path = match &state.fs.inodes[target_parent_inode].kind {
         Kind::Dir { entries, path, .. } => {
-            if entries.contains_key(&target_entry_name) {
+            if entries.contains_key(&target_entry_key) {
                 return __WASI_EEXIST;
             }
             let mut out_path = path.clone();
@@ -2065,15 +2308,15 @@ pub fn path_rename(
             out_path
         }
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
-            unreachable!("Fatal internal logic error: parent of inode is not a directory")
+        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } | Kind::Device { .. } => {
+            return __WASI_ENOTDIR
         }
     };
     let source_entry = match &mut state.fs.inodes[source_parent_inode].kind {
-        Kind::Dir { entries, .. } => wasi_try!(entries.remove(&source_entry_name), __WASI_EINVAL),
+        Kind::Dir { entries, .. } => wasi_try!(entries.remove(&source_entry_key), __WASI_EINVAL),
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } => {
-            unreachable!("Fatal internal logic error: parent of inode is not a directory")
+        Kind::Symlink { .. } | Kind::File { .. } | Kind::Buffer { .. } | Kind::Device { .. } => {
+            return __WASI_ENOTDIR
         }
     };
 
@@ -2095,19 +2338,25 @@ pub fn path_rename(
             // if the above operation failed we have to revert the previous change and then fail
             if let Err(e) = result {
                 if let Kind::Dir { entries, .. } = &mut state.fs.inodes[source_parent_inode].kind {
-                    entries.insert(source_entry_name, source_entry);
+                    entries.insert(source_entry_key, source_entry);
                     return e;
                 }
             }
         }
-        Kind::Dir { path, .. } => unimplemented!("wasi::path_rename on Directories"),
+        Kind::Dir { .. } | Kind::Root { .. } => {
+            // Renaming directories isn't supported yet.
+            if let Kind::Dir { entries, .. } = &mut state.fs.inodes[source_parent_inode].kind {
+                entries.insert(source_entry_key, source_entry);
+            }
+            return __WASI_ENOTSUP;
+        }
         Kind::Buffer { .. } => {}
+        Kind::Device { .. } => {}
         Kind::Symlink { .. } => {}
-        Kind::Root { .. } => unreachable!("The root can not be moved"),
     }
 
     if let Kind::Dir { entries, .. } = &mut state.fs.inodes[target_parent_inode].kind {
-        let result = entries.insert(target_entry_name, source_entry);
+        let result = entries.insert(target_entry_key, source_entry);
         assert!(
             result.is_none(),
             "Fatal error: race condition on filesystem detected or internal logic error"
@@ -2156,16 +2405,17 @@ pub fn path_symlink(
     let (target_parent_inode, entry_name) =
         wasi_try!(state.fs.get_parent_inode_at_path(fd, new_path_path, true));
 
+    let entry_key = state.fs.path_policy.key(&entry_name);
     // short circuit if anything is wrong, before we create an inode
     match &state.fs.inodes[target_parent_inode].kind {
         Kind::Dir { entries, .. } => {
-            if entries.contains_key(&entry_name) {
+            if entries.contains_key(&entry_key) {
                 return __WASI_EEXIST;
             }
         }
         Kind::Root { .. } => return __WASI_ENOTCAPABLE,
-        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } => {
-            unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
+        Kind::File { .. } | Kind::Symlink { .. } | Kind::Buffer { .. } | Kind::Device { .. } => {
+            return __WASI_ENOTDIR
         }
     }
 
@@ -2188,13 +2438,13 @@ pub fn path_symlink(
     };
     let new_inode = state
         .fs
-        .create_inode_with_default_stat(kind, false, entry_name.clone());
+        .create_inode_with_default_stat(kind, false, entry_name);
 
     if let Kind::Dir {
         ref mut entries, ..
     } = &mut state.fs.inodes[target_parent_inode].kind
     {
-        entries.insert(entry_name, new_inode);
+        entries.insert(entry_key, new_inode);
     }
 
     __WASI_ESUCCESS
@@ -2230,25 +2480,35 @@ pub fn path_unlink_file(
         wasi_try!(state
             .fs
             .get_parent_inode_at_path(fd, std::path::Path::new(path_str), false));
+    let childs_key = state.fs.path_policy.key(&childs_name);
 
     let removed_inode = match &mut state.fs.inodes[parent_inode].kind {
         Kind::Dir {
             ref mut entries, ..
         } => {
-            let removed_inode = wasi_try!(entries.remove(&childs_name).ok_or(__WASI_EINVAL));
+            let removed_inode = wasi_try!(entries.remove(&childs_key).ok_or(__WASI_EINVAL));
             // TODO: make this a debug assert in the future
             assert!(inode == removed_inode);
             debug_assert!(state.fs.inodes[inode].stat.st_nlink > 0);
             removed_inode
         }
         Kind::Root { .. } => return __WASI_EACCES,
-        _ => unreachable!(
-            "Internal logic error in wasi::path_unlink_file, parent is not a directory"
-        ),
+        _ => return __WASI_ENOTDIR,
     };
 
-    state.fs.inodes[removed_inode].stat.st_nlink -= 1;
-    if state.fs.inodes[removed_inode].stat.st_nlink == 0 {
+    let is_host_file = if let Kind::File { path, .. } = &state.fs.inodes[removed_inode].kind {
+        !path.as_os_str().is_empty()
+    } else {
+        false
+    };
+    let st_ino = state.fs.inodes[removed_inode].stat.st_ino;
+    let nlink = state.fs.inodes[removed_inode]
+        .stat
+        .st_nlink
+        .saturating_sub(1);
+    state.fs.set_nlink(st_ino, nlink);
+    // every hard link to a host file has an inode of its own, see `path_link`
+    if nlink == 0 || is_host_file {
         match &mut state.fs.inodes[removed_inode].kind {
             Kind::File { handle, path, .. } => {
                 if let Some(h) = handle {
@@ -2264,7 +2524,8 @@ pub fn path_unlink_file(
             Kind::Symlink { .. } => {
                 // TODO: actually delete real symlinks and do nothing for virtual symlinks
             }
-            _ => unimplemented!("wasi::path_unlink_file for Buffer"),
+            // devices and buffers are virtual, removing the entry is all there is to do
+            Kind::Device { .. } | Kind::Buffer { .. } => (),
         }
         // TODO: test this on Windows and actually make it portable
         // make the file an orphan fd if the fd is still open
@@ -2274,23 +2535,44 @@ pub fn path_unlink_file(
         } else {
             false
         };
-        let removed_inode_val = unsafe { state.fs.remove_inode(removed_inode) };
-        assert!(
-            removed_inode_val.is_some(),
-            "Inode could not be removed because it doesn't exist"
+        let removed_inode_val = wasi_try!(
+            unsafe { state.fs.remove_inode(removed_inode) },
+            __WASI_ENOENT
         );
 
         if fd_is_orphaned {
-            state
-                .fs
-                .orphan_fds
-                .insert(removed_inode, removed_inode_val.unwrap());
+            state.fs.orphan_fds.insert(removed_inode, removed_inode_val);
         }
     }
 
     __WASI_ESUCCESS
 }
 
+/// The file polled for `fd` by `poll_oneoff`.
+fn poll_file(fs: &state::WasiFs, fd: __wasi_fd_t) -> Result<&dyn WasiFile, __wasi_errno_t> {
+    let file = match fd {
+        __WASI_STDERR_FILENO => fs.stderr().map_err(WasiFsError::into_wasi_err)?,
+        __WASI_STDIN_FILENO => fs.stdin().map_err(WasiFsError::into_wasi_err)?,
+        __WASI_STDOUT_FILENO => fs.stdout().map_err(WasiFsError::into_wasi_err)?,
+        _ => {
+            let fd_entry = fs.get_fd(fd)?;
+            if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
+                return Err(__WASI_EACCES);
+            }
+
+            match &fs.inodes[fd_entry.inode].kind {
+                Kind::File { handle, .. } => handle,
+                Kind::Dir { .. }
+                | Kind::Root { .. }
+                | Kind::Buffer { .. }
+                | Kind::Device { .. }
+                | Kind::Symlink { .. } => return Err(__WASI_ENOTSUP),
+            }
+        }
+    };
+    file.as_ref().map(|file| &**file).ok_or(__WASI_EBADF)
+}
+
 /// ### `poll_oneoff()`
 /// Concurrently poll for a set of events
 /// Inputs:
@@ -2312,7 +2594,7 @@ pub fn poll_oneoff(
 ) -> __wasi_errno_t {
     debug!("wasi::poll_oneoff");
     debug!("  => nsubscriptions = {}", nsubscriptions);
-    let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let memory = env.memory();
 
     let subscription_array = wasi_try!(in_.deref(memory, 0, nsubscriptions));
     let event_array = wasi_try!(out_.deref(memory, 0, nsubscriptions));
@@ -2322,136 +2604,107 @@ pub fn poll_oneoff(
     let mut fds = vec![];
     let mut clock_subs = vec![];
     let mut in_events = vec![];
-    let mut total_ns_slept = 0;
-
-    for sub in subscription_array.iter() {
-        let s: WasiSubscription = wasi_try!(sub.get().try_into());
-        let mut peb = PollEventBuilder::new();
-        let mut ns_to_sleep = 0;
-
-        let fd = match s.event_type {
-            EventType::Read(__wasi_subscription_fs_readwrite_t { fd }) => {
-                match fd {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                        if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_READ) {
-                            return __WASI_EACCES;
+    let mut ns_to_sleep = 0;
+
+    {
+        let state = env.state();
+        for sub in subscription_array.iter() {
+            let s: WasiSubscription = wasi_try!(sub.get().try_into());
+            let mut peb = PollEventBuilder::new();
+
+            match s.event_type {
+                EventType::Read(__wasi_subscription_fs_readwrite_t { fd }) => {
+                    match fd {
+                        __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
+                        _ => {
+                            let fd_entry = wasi_try!(state.fs.get_fd(fd));
+                            if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_READ) {
+                                return __WASI_EACCES;
+                            }
                         }
                     }
+                    in_events.push(peb.add(PollEvent::PollIn).build());
+                    fds.push(fd);
                 }
-                in_events.push(peb.add(PollEvent::PollIn).build());
-                Some(fd)
-            }
-            EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
-                match fd {
-                    __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
-                    _ => {
-                        let fd_entry = wasi_try!(state.fs.get_fd(fd));
-
-                        if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_WRITE) {
-                            return __WASI_EACCES;
+                EventType::Write(__wasi_subscription_fs_readwrite_t { fd }) => {
+                    match fd {
+                        __WASI_STDIN_FILENO | __WASI_STDOUT_FILENO | __WASI_STDERR_FILENO => (),
+                        _ => {
+                            let fd_entry = wasi_try!(state.fs.get_fd(fd));
+
+                            if !has_rights(fd_entry.rights, __WASI_RIGHT_FD_WRITE) {
+                                return __WASI_EACCES;
+                            }
                         }
                     }
+                    in_events.push(peb.add(PollEvent::PollOut).build());
+                    fds.push(fd);
                 }
-                in_events.push(peb.add(PollEvent::PollOut).build());
-                Some(fd)
-            }
-            EventType::Clock(clock_info) => {
-                if clock_info.clock_id == __WASI_CLOCK_REALTIME {
-                    // this is a hack
-                    // TODO: do this properly
-                    ns_to_sleep = clock_info.timeout;
-                    clock_subs.push(clock_info);
-                    None
-                } else {
-                    unimplemented!("Polling not implemented for clocks yet");
-                }
-            }
-        };
-
-        if let Some(fd) = fd {
-            let wasi_file_ref: &dyn WasiFile = match fd {
-                __WASI_STDERR_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stderr().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDIN_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdin().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                __WASI_STDOUT_FILENO => wasi_try!(
-                    wasi_try!(state.fs.stdout().map_err(WasiFsError::into_wasi_err)).as_ref(),
-                    __WASI_EBADF
-                )
-                .as_ref(),
-                _ => {
-                    let fd_entry = wasi_try!(state.fs.get_fd(fd));
-                    let inode = fd_entry.inode;
-                    if !has_rights(fd_entry.rights, __WASI_RIGHT_POLL_FD_READWRITE) {
-                        return __WASI_EACCES;
-                    }
-
-                    match &state.fs.inodes[inode].kind {
-                        Kind::File { handle, .. } => {
-                            if let Some(h) = handle {
-                                h.as_ref()
-                            } else {
-                                return __WASI_EBADF;
-                            }
-                        }
-                        Kind::Dir { .. }
-                        | Kind::Root { .. }
-                        | Kind::Buffer { .. }
-                        | Kind::Symlink { .. } => {
-                            unimplemented!("polling read on non-files not yet supported")
-                        }
+                EventType::Clock(clock_info) => {
+                    if clock_info.clock_id == __WASI_CLOCK_REALTIME {
+                        // this is a hack
+                        // TODO: do this properly
+                        ns_to_sleep = ns_to_sleep.max(clock_info.timeout);
+                        clock_subs.push(clock_info);
+                    } else {
+                        // Only relative timeouts on the monotonic clock are supported.
+                        return __WASI_ENOTSUP;
                     }
                 }
-            };
-            fds.push(wasi_file_ref);
-        } else {
-            let remaining_ns = ns_to_sleep as i64 - total_ns_slept as i64;
-            if remaining_ns > 0 {
-                debug!("Sleeping for {} nanoseconds", remaining_ns);
-                let duration = std::time::Duration::from_nanos(remaining_ns as u64);
-                std::thread::sleep(duration);
-                total_ns_slept += remaining_ns;
             }
         }
     }
+
+    // the state is unlocked while sleeping and waiting for the fds, so that
+    // the other threads sharing it can go on
+    if ns_to_sleep > 0 {
+        debug!("Sleeping for {} nanoseconds", ns_to_sleep);
+        std::thread::sleep(std::time::Duration::from_nanos(ns_to_sleep));
+    }
+
     let mut seen_events = vec![Default::default(); in_events.len()];
-    wasi_try!(poll(
-        fds.as_slice(),
-        in_events.as_slice(),
-        seen_events.as_mut_slice()
-    )
-    .map_err(|e| e.into_wasi_err()));
-
-    for (i, seen_event) in seen_events.into_iter().enumerate() {
-        let mut flags = 0;
-        let mut error = __WASI_EAGAIN;
-        let mut bytes_available = 0;
-        let event_iter = iterate_poll_events(seen_event);
-        for event in event_iter {
-            match event {
-                PollEvent::PollError => error = __WASI_EIO,
-                PollEvent::PollHangUp => flags = __WASI_EVENT_FD_READWRITE_HANGUP,
-                PollEvent::PollInvalid => error = __WASI_EINVAL,
-                PollEvent::PollIn => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
-                }
-                PollEvent::PollOut => {
-                    bytes_available =
-                        wasi_try!(fds[i].bytes_available().map_err(|e| e.into_wasi_err()));
-                    error = __WASI_ESUCCESS;
+    let fd_events = loop {
+        let state = env.state();
+        let files = wasi_try!(fds
+            .iter()
+            .map(|&fd| poll_file(&state.fs, fd))
+            .collect::<Result<Vec<_>, _>>());
+        let ready = wasi_try!(poll(
+            files.as_slice(),
+            in_events.as_slice(),
+            seen_events.as_mut_slice()
+        )
+        .map_err(|e| e.into_wasi_err()));
+        // without a clock subscription there's no timeout: block until an fd is ready
+        if ready > 0 || files.is_empty() || !clock_subs.is_empty() {
+            let mut fd_events = vec![];
+            for (file, &seen_event) in files.iter().zip(seen_events.iter()) {
+                let mut flags = 0;
+                let mut error = __WASI_EAGAIN;
+                let mut bytes_available = 0;
+                let event_iter = iterate_poll_events(seen_event);
+                for event in event_iter {
+                    match event {
+                        PollEvent::PollError => error = __WASI_EIO,
+                        PollEvent::PollHangUp => flags = __WASI_EVENT_FD_READWRITE_HANGUP,
+                        PollEvent::PollInvalid => error = __WASI_EINVAL,
+                        PollEvent::PollIn | PollEvent::PollOut => {
+                            bytes_available =
+                                wasi_try!(file.bytes_available().map_err(|e| e.into_wasi_err()));
+                            error = __WASI_ESUCCESS;
+                        }
+                    }
                 }
+                fd_events.push((error, flags, bytes_available));
             }
+            break fd_events;
         }
+        drop(files);
+        drop(state);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+
+    for (i, (error, flags, bytes_available)) in fd_events.into_iter().enumerate() {
         let event = __wasi_event_t {
             userdata: subscription_array[i].get().userdata,
             error,
@@ -2498,7 +2751,7 @@ pub fn proc_exit(env: &mut WasiEnv, code: __wasi_exitcode_t) {
 
 pub fn proc_raise(env: &mut WasiEnv, sig: __wasi_signal_t) -> __wasi_errno_t {
     debug!("wasi::proc_raise");
-    unimplemented!("wasi::proc_raise")
+    __WASI_ENOTSUP
 }
 
 /// ### `random_get()`
@@ -2542,7 +2795,7 @@ pub fn sock_recv(
     ro_flags: WasmPtr<__wasi_roflags_t>,
 ) -> __wasi_errno_t {
     debug!("wasi::sock_recv");
-    unimplemented!("wasi::sock_recv")
+    __WASI_ENOTSUP
 }
 pub fn sock_send(
     env: &mut WasiEnv,
@@ -2553,7 +2806,7 @@ pub fn sock_send(
     so_datalen: WasmPtr<u32>,
 ) -> __wasi_errno_t {
     debug!("wasi::sock_send");
-    unimplemented!("wasi::sock_send")
+    __WASI_ENOTSUP
 }
 pub fn sock_shutdown(
     env: &mut WasiEnv,
@@ -2561,5 +2814,1097 @@ pub fn sock_shutdown(
     how: __wasi_sdflags_t,
 ) -> __wasi_errno_t {
     debug!("wasi::sock_shutdown");
-    unimplemented!("wasi::sock_shutdown")
+    __WASI_ENOTSUP
+}
+
+/// ### `fs_watch()`
+/// Extension in the `wasmer_fs` namespace: watch a host directory for
+/// changes.  The returned fd becomes readable with `poll_oneoff` when
+/// entries under the directory are created, modified or removed, by the
+/// program or by the host.
+///
+/// Reading the fd returns whole events: a `__wasi_watch_event_kind_t`, 3
+/// bytes of padding, the length of the name as a `u32`, then the path of
+/// the entry relative to the directory.  Reading fails if the buffer is
+/// too small for the next event.
+/// Inputs:
+/// - `__wasi_fd_t fd`
+///     The base directory of `path`
+/// - `const char *path`
+///     The directory to watch, relative to `fd`
+/// - `u32 path_len`
+///     The length of `path`
+/// - `__wasi_watchflags_t flags`
+///     `__WASI_WATCH_RECURSIVE` to watch the subdirectories too
+/// Output:
+/// - `__wasi_fd_t *watch_fd`
+///     The fd to poll and read the events from
+pub fn fs_watch(
+    env: &mut WasiEnv,
+    fd: __wasi_fd_t,
+    path: WasmPtr<u8, Array>,
+    path_len: u32,
+    flags: __wasi_watchflags_t,
+    watch_fd: WasmPtr<__wasi_fd_t>,
+) -> __wasi_errno_t {
+    debug!("wasi::fs_watch");
+    let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    let base = wasi_try!(state.fs.get_fd(fd));
+    if !has_rights(base.rights, __WASI_RIGHT_FD_READDIR) {
+        return __WASI_EACCES;
+    }
+    let path_string = get_input_str!(memory, path, path_len);
+    debug!("=> fd: {}, path: {}", fd, &path_string);
+    let watch_fd_cell = wasi_try!(watch_fd.deref(memory));
+
+    let inode = wasi_try!(state.fs.get_inode_at_path(fd, &path_string, true));
+    let host_path = match &state.fs.inodes[inode].kind {
+        // only host directories can be watched
+        Kind::Dir { path, .. } if path.as_os_str().is_empty() => return __WASI_ENOTSUP,
+        Kind::Dir { path, .. } => path.clone(),
+        Kind::Root { .. } => return __WASI_ENOTSUP,
+        _ => return __WASI_ENOTDIR,
+    };
+    let kind = Kind::File {
+        handle: Some(Box::new(WatchFile::new(
+            host_path,
+            flags & __WASI_WATCH_RECURSIVE != 0,
+        ))),
+        path: std::path::PathBuf::new(),
+        fd: None,
+    };
+    let stat = __wasi_filestat_t {
+        st_filetype: __WASI_FILETYPE_UNKNOWN,
+        st_nlink: 1,
+        ..__wasi_filestat_t::default()
+    };
+    let watch_inode = state
+        .fs
+        .create_inode_with_stat(kind, false, path_string.to_string(), stat);
+    let rights =
+        __WASI_RIGHT_FD_READ | __WASI_RIGHT_FD_FILESTAT_GET | __WASI_RIGHT_POLL_FD_READWRITE;
+    let new_fd = wasi_try!(state.fs.create_fd(rights, 0, 0, Fd::READ, watch_inode));
+    watch_fd_cell.set(new_fd);
+    __WASI_ESUCCESS
+}
+
+/// ### `tty_get()`
+/// Extension in the `wasmer_tty` namespace: get the terminal settings of a
+/// stdio fd.
+/// Inputs:
+/// - `__wasi_fd_t fd`
+///     A stdio fd that is a terminal
+/// Output:
+/// - `__wasi_tty_t *tty`
+///     The terminal settings, including the window size
+pub fn tty_get(env: &mut WasiEnv, fd: __wasi_fd_t, tty: WasmPtr<__wasi_tty_t>) -> __wasi_errno_t {
+    debug!("wasi::tty_get: fd={}", fd);
+    let (memory, state) = env.get_memory_and_wasi_state(0);
+    if !state.fs.is_tty(fd) {
+        return __WASI_ENOTTY;
+    }
+    let mut settings = state.fs.tty;
+    if state.fs.tty_policy == TtyPolicy::Host {
+        if let Some((cols, rows)) = platform_tty_size(fd as i32) {
+            settings.cols = cols;
+            settings.rows = rows;
+        }
+    }
+    wasi_try!(tty.deref(memory)).set(settings);
+    __WASI_ESUCCESS
+}
+
+/// ### `tty_set()`
+/// Extension in the `wasmer_tty` namespace: set the echo and line buffering
+/// of a stdio fd.  When the fd is a host terminal and the environment allows
+/// host changes, the settings are applied to it as well, and the previous
+/// ones are restored when the environment is dropped.
+/// Inputs:
+/// - `__wasi_fd_t fd`
+///     A stdio fd that is a terminal
+/// - `const __wasi_tty_t *tty`
+///     The new settings; the window size is ignored
+pub fn tty_set(env: &mut WasiEnv, fd: __wasi_fd_t, tty: WasmPtr<__wasi_tty_t>) -> __wasi_errno_t {
+    debug!("wasi::tty_set: fd={}", fd);
+    let (memory, mut state) = env.get_memory_and_wasi_state(0);
+    if !state.fs.is_tty(fd) {
+        return __WASI_ENOTTY;
+    }
+    let new_settings = wasi_try!(tty.deref(memory)).get();
+    if state.fs.tty_policy == TtyPolicy::Host && state.fs.host_tty_changes {
+        state.fs.save_host_tty(fd);
+        wasi_try!(platform_tty_set(
+            fd as i32,
+            new_settings.echo != 0,
+            new_settings.line_buffered != 0
+        ));
+    }
+    state.fs.tty.echo = new_settings.echo;
+    state.fs.tty.line_buffered = new_settings.line_buffered;
+    __WASI_ESUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{
+        serve_remote_fs, FreshnessPolicy, HostFile, InteractiveStdin, IoLimits, ObjectStore,
+        ObjectStoreFs, RemoteFs, ThrottleMode, WasiFsImage, ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    };
+    use std::path::Path;
+    use std::sync::Arc;
+    use wasmer::{MemoryType, Store};
+
+    /// Where the paths given to the syscalls are written
+    const PATH_OFFSET: u32 = 1024;
+    /// Where the syscalls write their results
+    const OUT_OFFSET: u32 = 2048;
+    /// The buffer of the reads, writes and directory listings
+    const BUF_OFFSET: u32 = 4096;
+    const BUF_LEN: u32 = 4096;
+
+    fn with_memory(mut env: WasiEnv) -> WasiEnv {
+        let memory = Memory::new(&Store::default(), MemoryType::new(1, None, false)).unwrap();
+        env.set_memory(memory);
+        env
+    }
+
+    fn env_with_dir(dir: &Path) -> WasiEnv {
+        with_memory(
+            WasiState::new("test")
+                .preopen_dir(dir)
+                .unwrap()
+                .finalize()
+                .unwrap(),
+        )
+    }
+
+    fn env_with_image(image: WasiFsImage) -> WasiEnv {
+        with_memory(
+            WasiState::new("test")
+                .mount_image("image", Arc::new(image))
+                .finalize()
+                .unwrap(),
+        )
+    }
+
+    fn preopen_fd(env: &WasiEnv) -> __wasi_fd_t {
+        env.state().fs.preopen_fds[0]
+    }
+
+    fn write_memory(env: &WasiEnv, offset: u32, bytes: &[u8]) {
+        let view = env.memory().view::<u8>();
+        for (cell, byte) in view[offset as usize..].iter().zip(bytes) {
+            cell.set(*byte);
+        }
+    }
+
+    fn read_memory(env: &WasiEnv, offset: u32, len: usize) -> Vec<u8> {
+        let view = env.memory().view::<u8>();
+        view[offset as usize..offset as usize + len]
+            .iter()
+            .map(|cell| cell.get())
+            .collect()
+    }
+
+    fn read_u32(env: &WasiEnv, offset: u32) -> u32 {
+        let bytes = read_memory(env, offset, 4);
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn open(
+        env: &mut WasiEnv,
+        path: &str,
+        o_flags: __wasi_oflags_t,
+    ) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        let dirfd = preopen_fd(env);
+        open_at(env, dirfd, path, o_flags)
+    }
+
+    fn open_at(
+        env: &mut WasiEnv,
+        dirfd: __wasi_fd_t,
+        path: &str,
+        o_flags: __wasi_oflags_t,
+    ) -> Result<__wasi_fd_t, __wasi_errno_t> {
+        write_memory(env, PATH_OFFSET, path.as_bytes());
+        match path_open(
+            env,
+            dirfd,
+            0,
+            WasmPtr::new(PATH_OFFSET),
+            path.len() as u32,
+            o_flags,
+            ALL_RIGHTS,
+            ALL_RIGHTS,
+            0,
+            WasmPtr::new(OUT_OFFSET),
+        ) {
+            __WASI_ESUCCESS => Ok(read_u32(env, OUT_OFFSET)),
+            errno => Err(errno),
+        }
+    }
+
+    /// Reads `fd` from its current offset, up to `BUF_LEN` bytes
+    fn read_file(env: &mut WasiEnv, fd: __wasi_fd_t) -> Vec<u8> {
+        let iovs = WasmPtr::<__wasi_iovec_t, Array>::new(OUT_OFFSET + 8);
+        iovs.deref(env.memory(), 0, 1).unwrap()[0].set(__wasi_iovec_t {
+            buf: WasmPtr::new(BUF_OFFSET),
+            buf_len: BUF_LEN,
+        });
+        assert_eq!(
+            fd_read(env, fd, iovs, 1, WasmPtr::new(OUT_OFFSET)),
+            __WASI_ESUCCESS
+        );
+        let nread = read_u32(env, OUT_OFFSET);
+        read_memory(env, BUF_OFFSET, nread as usize)
+    }
+
+    fn write_file(env: &mut WasiEnv, fd: __wasi_fd_t, bytes: &[u8]) {
+        write_memory(env, BUF_OFFSET, bytes);
+        let iovs = WasmPtr::<__wasi_ciovec_t, Array>::new(OUT_OFFSET + 8);
+        iovs.deref(env.memory(), 0, 1).unwrap()[0].set(__wasi_ciovec_t {
+            buf: WasmPtr::new(BUF_OFFSET),
+            buf_len: bytes.len() as u32,
+        });
+        assert_eq!(
+            fd_write(env, fd, iovs, 1, WasmPtr::new(OUT_OFFSET)),
+            __WASI_ESUCCESS
+        );
+        assert_eq!(read_u32(env, OUT_OFFSET), bytes.len() as u32);
+    }
+
+    /// Lists the names of the entries of the directory `fd`
+    fn read_dir(env: &mut WasiEnv, fd: __wasi_fd_t) -> Vec<String> {
+        assert_eq!(
+            fd_readdir(
+                env,
+                fd,
+                WasmPtr::new(BUF_OFFSET),
+                BUF_LEN,
+                0,
+                WasmPtr::new(OUT_OFFSET)
+            ),
+            __WASI_ESUCCESS
+        );
+        let used = read_u32(env, OUT_OFFSET);
+        let dirent_len = std::mem::size_of::<__wasi_dirent_t>() as u32;
+        let mut names = vec![];
+        let mut offset = 0;
+        while offset < used {
+            let namlen = read_u32(env, BUF_OFFSET + offset + 16);
+            let name = read_memory(env, BUF_OFFSET + offset + dirent_len, namlen as usize);
+            names.push(String::from_utf8(name).unwrap());
+            offset += dirent_len + namlen;
+        }
+        names
+    }
+
+    /// Builds a tar archive with `entries`, the directories having no
+    /// contents. Their paths aren't checked, so that they can escape the
+    /// archive.
+    #[cfg(feature = "tar")]
+    fn tar_archive(entries: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+        tar_builder(entries).into_inner().unwrap()
+    }
+
+    #[cfg(feature = "tar")]
+    fn tar_builder(entries: &[(&str, Option<&[u8]>)]) -> tar::Builder<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            let contents = match contents {
+                Some(contents) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    contents
+                }
+                None => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    &[][..]
+                }
+            };
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, contents).unwrap();
+        }
+        builder
+    }
+
+    #[cfg(feature = "zip")]
+    fn zip_archive(entries: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        for (path, contents) in entries {
+            match contents {
+                Some(contents) => {
+                    writer.start_file(*path, options).unwrap();
+                    writer.write_all(contents).unwrap();
+                }
+                None => writer.add_directory(*path, options).unwrap(),
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// The entries of the archives mounted by the tests, one of them
+    /// escaping the archive
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    const ARCHIVE_ENTRIES: &[(&str, Option<&[u8]>)] = &[
+        ("dir/", None),
+        ("dir/file", Some(b"data")),
+        ("../escape", Some(b"escape")),
+    ];
+
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    fn check_mounted_archive(image: WasiFsImage) {
+        let mut env = env_with_image(image);
+        let root = preopen_fd(&env);
+        assert_eq!(read_dir(&mut env, root), vec!["dir"]);
+        let dir = open(&mut env, "dir", __WASI_O_DIRECTORY).unwrap();
+        assert_eq!(read_dir(&mut env, dir), vec!["file"]);
+        let file = open(&mut env, "dir/file", 0).unwrap();
+        assert_eq!(read_file(&mut env, file), b"data");
+    }
+
+    /// Polls the `subscriptions`, returning the events
+    fn poll_events(
+        env: &mut WasiEnv,
+        subscriptions: &[__wasi_subscription_t],
+    ) -> Result<Vec<__wasi_event_t>, __wasi_errno_t> {
+        let in_ = WasmPtr::<__wasi_subscription_t, Array>::new(PATH_OFFSET);
+        let out_ = WasmPtr::<__wasi_event_t, Array>::new(OUT_OFFSET);
+        let nevents = WasmPtr::<u32>::new(OUT_OFFSET - 4);
+        let len = subscriptions.len() as u32;
+        for (cell, subscription) in in_
+            .deref(env.memory(), 0, len)
+            .unwrap()
+            .iter()
+            .zip(subscriptions)
+        {
+            cell.set(*subscription);
+        }
+        match poll_oneoff(env, in_, out_, len, nevents) {
+            __WASI_ESUCCESS => {}
+            errno => return Err(errno),
+        }
+        let nevents = read_u32(env, nevents.offset());
+        Ok(out_
+            .deref(env.memory(), 0, nevents)
+            .unwrap()
+            .iter()
+            .map(|cell| cell.get())
+            .collect())
+    }
+
+    fn preopen_entries(env: &WasiEnv) -> Vec<String> {
+        let state = env.state();
+        let inode = state.fs.fd_map[&state.fs.preopen_fds[0]].inode;
+        match &state.fs.inodes[inode].kind {
+            Kind::Dir { entries, .. } => entries.keys().cloned().collect(),
+            _ => panic!("the preopened directory isn't a directory"),
+        }
+    }
+
+    #[test]
+    fn path_link_counts_the_links() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"data").unwrap();
+        let mut env = env_with_dir(dir.path());
+        let root = preopen_fd(&env);
+        let link = |env: &mut WasiEnv| {
+            write_memory(env, PATH_OFFSET, b"file");
+            write_memory(env, PATH_OFFSET + 256, b"link");
+            path_link(
+                env,
+                root,
+                0,
+                WasmPtr::new(PATH_OFFSET),
+                4,
+                root,
+                WasmPtr::new(PATH_OFFSET + 256),
+                4,
+            )
+        };
+        assert_eq!(link(&mut env), __WASI_ESUCCESS);
+        assert_eq!(std::fs::read(dir.path().join("link")).unwrap(), b"data");
+        assert_eq!(link(&mut env), __WASI_EEXIST);
+
+        let fd = open(&mut env, "link", 0).unwrap();
+        let stat = WasmPtr::<__wasi_filestat_t>::new(OUT_OFFSET);
+        let nlink = |env: &mut WasiEnv| {
+            assert_eq!(fd_filestat_get(env, fd, stat), __WASI_ESUCCESS);
+            stat.deref(env.memory()).unwrap().get().st_nlink
+        };
+        assert_eq!(nlink(&mut env), 2);
+
+        write_memory(&env, PATH_OFFSET, b"file");
+        assert_eq!(
+            path_unlink_file(&mut env, root, WasmPtr::new(PATH_OFFSET), 4),
+            __WASI_ESUCCESS
+        );
+        assert!(!dir.path().join("file").exists());
+        assert_eq!(nlink(&mut env), 1);
+        assert_eq!(read_file(&mut env, fd), b"data");
+    }
+
+    #[test]
+    fn open_fds_are_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = env_with_dir(dir.path());
+        {
+            let mut state = env.state();
+            let open_fds = state.fs.fd_map.len() as u32;
+            state.fs.max_open_fds = Some(open_fds + 2);
+        }
+        let first = open(&mut env, "first", __WASI_O_CREAT).unwrap();
+        open(&mut env, "second", __WASI_O_CREAT).unwrap();
+        assert_eq!(open(&mut env, "third", __WASI_O_CREAT), Err(__WASI_EMFILE));
+        assert!(!dir.path().join("third").exists());
+
+        // closing an fd frees its slot
+        assert_eq!(fd_close(&mut env, first), __WASI_ESUCCESS);
+        open(&mut env, "third", __WASI_O_CREAT).unwrap();
+        assert_eq!(open(&mut env, "first", 0), Err(__WASI_EMFILE));
+    }
+
+    #[test]
+    fn dev_tty_is_the_guest_stdio() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout_path = dir.path().join("stdout");
+        let stdout = HostFile::new(
+            std::fs::File::create(&stdout_path).unwrap(),
+            stdout_path.clone(),
+            false,
+            true,
+            false,
+        );
+        let (stdin, handle) = InteractiveStdin::new();
+        let mut env = with_memory(
+            WasiState::new("test")
+                .stdin(Box::new(stdin))
+                .stdout(Box::new(stdout))
+                .mount_devices()
+                .finalize()
+                .unwrap(),
+        );
+        let dev = *env.state().fs.preopen_fds.last().unwrap();
+        let tty = open_at(&mut env, dev, "tty", 0).unwrap();
+
+        handle.push(b"input");
+        assert_eq!(read_file(&mut env, tty), b"input");
+        write_file(&mut env, tty, b"output");
+        assert_eq!(std::fs::read(&stdout_path).unwrap(), b"output");
+    }
+
+    fn env_with_tty_policy(tty_policy: TtyPolicy) -> WasiEnv {
+        with_memory(
+            WasiState::new("test")
+                .tty_policy(tty_policy)
+                .finalize()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn tty_settings_are_emulated() {
+        let mut env = env_with_tty_policy(TtyPolicy::Always);
+        assert!(!env.state().fs.host_tty_changes);
+        let tty = WasmPtr::<__wasi_tty_t>::new(OUT_OFFSET);
+
+        assert_eq!(
+            tty_get(&mut env, __WASI_STDOUT_FILENO, tty),
+            __WASI_ESUCCESS
+        );
+        let settings = tty.deref(env.memory()).unwrap().get();
+        assert_eq!((settings.echo, settings.line_buffered), (1, 1));
+
+        tty.deref(env.memory()).unwrap().set(__wasi_tty_t {
+            echo: 0,
+            line_buffered: 0,
+            ..settings
+        });
+        assert_eq!(tty_set(&mut env, __WASI_STDIN_FILENO, tty), __WASI_ESUCCESS);
+        tty.deref(env.memory())
+            .unwrap()
+            .set(__wasi_tty_t::default());
+        assert_eq!(tty_get(&mut env, __WASI_STDIN_FILENO, tty), __WASI_ESUCCESS);
+        let settings = tty.deref(env.memory()).unwrap().get();
+        assert_eq!((settings.echo, settings.line_buffered), (0, 0));
+
+        // only stdio fds are terminals
+        assert_eq!(tty_get(&mut env, VIRTUAL_ROOT_FD, tty), __WASI_ENOTTY);
+        assert_eq!(tty_set(&mut env, VIRTUAL_ROOT_FD, tty), __WASI_ENOTTY);
+    }
+
+    #[test]
+    fn tty_policy_never() {
+        let mut env = env_with_tty_policy(TtyPolicy::Never);
+        let tty = WasmPtr::<__wasi_tty_t>::new(OUT_OFFSET);
+        for fd in 0..3 {
+            assert_eq!(tty_get(&mut env, fd, tty), __WASI_ENOTTY);
+            assert_eq!(tty_set(&mut env, fd, tty), __WASI_ENOTTY);
+        }
+        assert_eq!(env.state().fs.tty, __wasi_tty_t::default());
+    }
+
+    #[test]
+    fn fd_renumber_closes_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = env_with_dir(dir.path());
+        let from = open(&mut env, "from", __WASI_O_CREAT).unwrap();
+        let to = open(&mut env, "to", __WASI_O_CREAT).unwrap();
+        let (from_inode, to_inode) = {
+            let state = env.state();
+            (state.fs.fd_map[&from].inode, state.fs.fd_map[&to].inode)
+        };
+
+        assert_eq!(fd_renumber(&mut env, from, to), __WASI_ESUCCESS);
+
+        let state = env.state();
+        assert!(!state.fs.fd_map.contains_key(&from));
+        assert_eq!(state.fs.fd_map[&to].inode, from_inode);
+        match state.fs.inodes.get(to_inode) {
+            Some(InodeVal {
+                kind: Kind::File { handle, .. },
+                ..
+            }) => assert!(handle.is_none(), "the file of `to` is still open"),
+            _ => panic!("the inode of `to` is gone"),
+        }
+    }
+
+    #[test]
+    fn fd_renumber_of_a_closed_fd() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = env_with_dir(dir.path());
+        let from = open(&mut env, "from", __WASI_O_CREAT).unwrap();
+        assert_eq!(fd_close(&mut env, from), __WASI_ESUCCESS);
+        assert_eq!(fd_renumber(&mut env, from, from + 1), __WASI_EBADF);
+    }
+
+    #[test]
+    fn collect_garbage_forgets_closed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("dir/sub")).unwrap();
+        std::fs::write(dir.path().join("dir/sub/file"), b"data").unwrap();
+        let mut env = env_with_dir(dir.path());
+        env.state().fs.collect_garbage();
+        let inodes = env.state().fs.inodes.len();
+
+        let fd = open(&mut env, "dir/sub/file", 0).unwrap();
+        assert_eq!(preopen_entries(&env), vec!["dir".to_string()]);
+        // the open file and the directories above it are kept
+        assert_eq!(env.state().fs.collect_garbage(), 0);
+        assert_eq!(env.state().fs.inodes.len(), inodes + 3);
+
+        assert_eq!(fd_close(&mut env, fd), __WASI_ESUCCESS);
+        assert_eq!(env.state().fs.collect_garbage(), 3);
+        assert_eq!(env.state().fs.inodes.len(), inodes);
+        assert!(preopen_entries(&env).is_empty());
+
+        // they're loaded again from the host when looked up
+        open(&mut env, "dir/sub/file", 0).unwrap();
+        assert_eq!(preopen_entries(&env), vec!["dir".to_string()]);
+    }
+
+    #[test]
+    fn poll_oneoff_reads_a_virtual_file() {
+        let (stdin, handle) = InteractiveStdin::new();
+        let mut env = with_memory(
+            WasiState::new("test")
+                .stdin(Box::new(stdin))
+                .finalize()
+                .unwrap(),
+        );
+        handle.push(b"hello\n");
+
+        let events = poll_events(
+            &mut env,
+            &[__wasi_subscription_t {
+                userdata: 42,
+                type_: __WASI_EVENTTYPE_FD_READ,
+                u: __wasi_subscription_u {
+                    fd_readwrite: __wasi_subscription_fs_readwrite_t {
+                        fd: __WASI_STDIN_FILENO,
+                    },
+                },
+            }],
+        )
+        .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].userdata, 42);
+        assert_eq!(events[0].error, __WASI_ESUCCESS);
+        assert_eq!(events[0].type_, __WASI_EVENTTYPE_FD_READ);
+        assert_eq!(unsafe { events[0].u.fd_readwrite.nbytes }, 6);
+    }
+
+    #[test]
+    fn waiting_for_stdin_leaves_the_state_unlocked() {
+        let (stdin, handle) = InteractiveStdin::new();
+        let env = with_memory(
+            WasiState::new("test")
+                .stdin(Box::new(stdin))
+                .finalize()
+                .unwrap(),
+        );
+        let wait = std::time::Duration::from_millis(50);
+
+        let mut poller = env.clone();
+        let poll = std::thread::spawn(move || {
+            poll_events(
+                &mut poller,
+                &[__wasi_subscription_t {
+                    userdata: 0,
+                    type_: __WASI_EVENTTYPE_FD_READ,
+                    u: __wasi_subscription_u {
+                        fd_readwrite: __wasi_subscription_fs_readwrite_t {
+                            fd: __WASI_STDIN_FILENO,
+                        },
+                    },
+                }],
+            )
+        });
+        std::thread::sleep(wait);
+        assert!(env.state().fs.fd_map.contains_key(&__WASI_STDIN_FILENO));
+        handle.push(b"poll\n");
+        let events = poll.join().unwrap().unwrap();
+        assert_eq!(unsafe { events[0].u.fd_readwrite.nbytes }, 5);
+
+        let mut reader = env.clone();
+        let read = std::thread::spawn(move || {
+            let first = read_file(&mut reader, __WASI_STDIN_FILENO);
+            (first, read_file(&mut reader, __WASI_STDIN_FILENO))
+        });
+        std::thread::sleep(wait);
+        assert!(env.state().fs.fd_map.contains_key(&__WASI_STDIN_FILENO));
+        handle.push(b"read\n");
+        assert_eq!(
+            read.join().unwrap(),
+            (b"poll\n".to_vec(), b"read\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn poll_oneoff_with_a_timeout_only() {
+        let mut env = with_memory(WasiState::new("test").finalize().unwrap());
+        let timeout = std::time::Duration::from_millis(10);
+
+        let start = std::time::Instant::now();
+        let events = poll_events(
+            &mut env,
+            &[__wasi_subscription_t {
+                userdata: 0,
+                type_: __WASI_EVENTTYPE_CLOCK,
+                u: __wasi_subscription_u {
+                    clock: __wasi_subscription_clock_t {
+                        clock_id: __WASI_CLOCK_REALTIME,
+                        timeout: timeout.as_nanos() as u64,
+                        precision: 0,
+                        flags: 0,
+                    },
+                },
+            }],
+        )
+        .unwrap();
+        assert!(start.elapsed() >= timeout);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].error, __WASI_ESUCCESS);
+        assert_eq!(events[0].type_, __WASI_EVENTTYPE_CLOCK);
+    }
+
+    #[test]
+    fn poll_oneoff_reads_a_host_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"data").unwrap();
+        let mut env = env_with_dir(dir.path());
+        let fd = open(&mut env, "file", 0).unwrap();
+
+        let events = poll_events(
+            &mut env,
+            &[__wasi_subscription_t {
+                userdata: 0,
+                type_: __WASI_EVENTTYPE_FD_READ,
+                u: __wasi_subscription_u {
+                    fd_readwrite: __wasi_subscription_fs_readwrite_t { fd },
+                },
+            }],
+        );
+        if cfg!(unix) {
+            let events = events.unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].error, __WASI_ESUCCESS);
+            assert_eq!(unsafe { events[0].u.fd_readwrite.nbytes }, 4);
+        } else {
+            // the host files can't be polled on the other targets yet
+            assert_eq!(events.unwrap_err(), __WASI_ENOTSUP);
+        }
+    }
+
+    #[test]
+    fn mounted_image() {
+        let mut image = WasiFsImage::new();
+        image.add_file("dir/file", b"data".to_vec()).unwrap();
+        image.add_dir("empty").unwrap();
+        let mut env = env_with_image(image);
+
+        let root = preopen_fd(&env);
+        assert_eq!(read_dir(&mut env, root), vec!["dir", "empty"]);
+        let dir = open(&mut env, "dir", __WASI_O_DIRECTORY).unwrap();
+        assert_eq!(read_dir(&mut env, dir), vec!["file"]);
+        let empty = open(&mut env, "empty", __WASI_O_DIRECTORY).unwrap();
+        assert!(read_dir(&mut env, empty).is_empty());
+
+        let file = open(&mut env, "dir/file", 0).unwrap();
+        assert_eq!(read_file(&mut env, file), b"data");
+        assert_eq!(open(&mut env, "dir/missing", 0), Err(__WASI_ENOENT));
+    }
+
+    #[test]
+    fn mounted_image_files_are_copied_on_write() {
+        let mut image = WasiFsImage::new();
+        image.add_file("file", b"data".to_vec()).unwrap();
+        let image = Arc::new(image);
+        let mut envs = (0..2)
+            .map(|_| {
+                with_memory(
+                    WasiState::new("test")
+                        .mount_image("image", image.clone())
+                        .finalize()
+                        .unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let fd = open(&mut envs[0], "file", 0).unwrap();
+        write_file(&mut envs[0], fd, b"DATA");
+        let fd = open(&mut envs[0], "file", 0).unwrap();
+        assert_eq!(read_file(&mut envs[0], fd), b"DATA");
+
+        let fd = open(&mut envs[1], "file", 0).unwrap();
+        assert_eq!(read_file(&mut envs[1], fd), b"data");
+    }
+
+    #[test]
+    #[cfg(feature = "tar")]
+    fn mounted_tar_archive() {
+        let archive = tar_archive(ARCHIVE_ENTRIES);
+        check_mounted_archive(WasiFsImage::from_tar(&archive[..]).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn mounted_zip_archive() {
+        let archive = zip_archive(ARCHIVE_ENTRIES);
+        check_mounted_archive(WasiFsImage::from_zip(io::Cursor::new(archive)).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "tar")]
+    fn tar_archive_size_limit() {
+        // the escaping entry is skipped, leaving 4 bytes of files
+        let archive = tar_archive(ARCHIVE_ENTRIES);
+        check_mounted_archive(WasiFsImage::from_tar_with_limit(&archive[..], 4).unwrap());
+        let error = WasiFsImage::from_tar_with_limit(&archive[..], 3).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn zip_archive_size_limit() {
+        let archive = zip_archive(ARCHIVE_ENTRIES);
+        let image = WasiFsImage::from_zip_with_limit(io::Cursor::new(&archive), 4).unwrap();
+        check_mounted_archive(image);
+        let error = WasiFsImage::from_zip_with_limit(io::Cursor::new(&archive), 3).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "tar")]
+    fn mounted_oci_layers() {
+        let lower = tar_archive(&[
+            ("dir/", None),
+            ("dir/file", Some(b"data")),
+            ("dir/old", Some(b"old")),
+            ("opaque/", None),
+            ("opaque/hidden", Some(b"hidden")),
+        ]);
+        let mut upper = tar_builder(&[
+            ("dir/.wh.old", Some(b"")),
+            ("dir/new", Some(b"new")),
+            ("opaque/.wh..wh..opq", Some(b"")),
+            ("opaque/shown", Some(b"shown")),
+            ("../escape", Some(b"escape")),
+        ]);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_link_name("dir/file").unwrap();
+        upper.append_data(&mut header, "link", io::empty()).unwrap();
+        let upper = upper.into_inner().unwrap();
+        let image = WasiFsImage::from_oci_layers(vec![&lower[..], &upper[..]]).unwrap();
+
+        let mut env = env_with_image(image);
+        let root = preopen_fd(&env);
+        assert_eq!(read_dir(&mut env, root), vec!["dir", "link", "opaque"]);
+        let dir = open(&mut env, "dir", __WASI_O_DIRECTORY).unwrap();
+        assert_eq!(read_dir(&mut env, dir), vec!["file", "new"]);
+        let opaque = open(&mut env, "opaque", __WASI_O_DIRECTORY).unwrap();
+        assert_eq!(read_dir(&mut env, opaque), vec!["shown"]);
+        let link = open(&mut env, "link", 0).unwrap();
+        assert_eq!(read_file(&mut env, link), b"data");
+    }
+
+    #[test]
+    fn remote_dir_listed_again_after_an_error() {
+        const TOKEN: &[u8] = b"token";
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"data").unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let root = dir.path().to_path_buf();
+        std::thread::spawn(move || {
+            let mut incoming = listener.incoming();
+            // the first connection breaks right after it's authenticated
+            let mut stream = incoming.next().unwrap().unwrap();
+            let mut len = [0; 4];
+            stream.read_exact(&mut len).unwrap();
+            let mut token = vec![0; u32::from_le_bytes(len) as usize];
+            stream.read_exact(&mut token).unwrap();
+            stream.write_all(&__WASI_ESUCCESS.to_le_bytes()).unwrap();
+            drop(stream);
+            for stream in incoming {
+                let _ = serve_remote_fs(stream.unwrap(), &root, TOKEN);
+            }
+        });
+        let remote = Arc::new(RemoteFs::connect(addr, TOKEN).unwrap());
+        let mut env = with_memory(
+            WasiState::new("test")
+                .mount_remote("remote", remote)
+                .finalize()
+                .unwrap(),
+        );
+
+        let root = preopen_fd(&env);
+        assert_eq!(
+            fd_readdir(
+                &mut env,
+                root,
+                WasmPtr::new(BUF_OFFSET),
+                BUF_LEN,
+                0,
+                WasmPtr::new(OUT_OFFSET)
+            ),
+            __WASI_EIO
+        );
+        // the client connects again, and the directory is listed this time
+        assert_eq!(read_dir(&mut env, root), vec!["file"]);
+        let fd = open(&mut env, "file", 0).unwrap();
+        assert_eq!(read_file(&mut env, fd), b"data");
+    }
+
+    /// An object store with the objects in memory
+    #[derive(Debug)]
+    struct Objects(&'static [(&'static str, &'static [u8])]);
+
+    impl ObjectStore for Objects {
+        fn id(&self) -> String {
+            "objects".to_string()
+        }
+
+        fn list(&self) -> io::Result<Vec<(String, u64)>> {
+            Ok(self
+                .0
+                .iter()
+                .map(|(key, data)| (key.to_string(), data.len() as u64))
+                .collect())
+        }
+
+        fn get_range(&self, key: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+            let (_, data) = self.0.iter().find(|(k, _)| *k == key).unwrap();
+            Ok(data[offset as usize..(offset + len) as usize].to_vec())
+        }
+    }
+
+    #[test]
+    fn mounted_object_store() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let objects = Objects(&[
+            ("dir/file", b"data"),
+            ("dir/", b""),
+            ("empty/", b""),
+            ("top", b"top"),
+        ]);
+        let mut env = with_memory(
+            WasiState::new("test")
+                .mount_object_store(
+                    "objects",
+                    Arc::new(ObjectStoreFs::new(objects, cache_dir.path())),
+                )
+                .finalize()
+                .unwrap(),
+        );
+
+        let root = preopen_fd(&env);
+        assert_eq!(read_dir(&mut env, root), vec!["dir", "empty", "top"]);
+        let dir = open(&mut env, "dir", __WASI_O_DIRECTORY).unwrap();
+        assert_eq!(read_dir(&mut env, dir), vec!["file"]);
+        let empty = open(&mut env, "empty", __WASI_O_DIRECTORY).unwrap();
+        assert!(read_dir(&mut env, empty).is_empty());
+        let file = open(&mut env, "dir/file", 0).unwrap();
+        assert_eq!(read_file(&mut env, file), b"data");
+    }
+
+    #[test]
+    fn fs_watch_events() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let mut env = env_with_dir(dir.path());
+        write_memory(&env, PATH_OFFSET, b".");
+        let dirfd = preopen_fd(&env);
+        assert_eq!(
+            fs_watch(
+                &mut env,
+                dirfd,
+                WasmPtr::new(PATH_OFFSET),
+                1,
+                __WASI_WATCH_RECURSIVE,
+                WasmPtr::new(OUT_OFFSET)
+            ),
+            __WASI_ESUCCESS
+        );
+        let watch_fd = read_u32(&env, OUT_OFFSET);
+        let subscription = __wasi_subscription_t {
+            userdata: 7,
+            type_: __WASI_EVENTTYPE_FD_READ,
+            u: __wasi_subscription_u {
+                fd_readwrite: __wasi_subscription_fs_readwrite_t { fd: watch_fd },
+            },
+        };
+        // the directory is scanned again after a while
+        let scan_interval = std::time::Duration::from_millis(100);
+
+        std::fs::write(dir.path().join("sub/new"), b"data").unwrap();
+        std::thread::sleep(scan_interval);
+        let events = poll_events(&mut env, &[subscription]).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].userdata, 7);
+        assert_eq!(events[0].error, __WASI_ESUCCESS);
+        assert_eq!(unsafe { events[0].u.fd_readwrite.nbytes }, 8 + 7);
+        let mut created = vec![__WASI_WATCH_CREATED, 0, 0, 0, 7, 0, 0, 0];
+        created.extend_from_slice(b"sub/new");
+        assert_eq!(read_file(&mut env, watch_fd), created);
+
+        std::fs::remove_file(dir.path().join("sub/new")).unwrap();
+        std::thread::sleep(scan_interval);
+        let events = poll_events(&mut env, &[subscription]).unwrap();
+        assert_eq!(events.len(), 1);
+        let mut removed = vec![__WASI_WATCH_REMOVED, 0, 0, 0, 7, 0, 0, 0];
+        removed.extend_from_slice(b"sub/new");
+        assert_eq!(read_file(&mut env, watch_fd), removed);
+    }
+
+    #[test]
+    fn host_entries_expire_after_max_age() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("entry"), b"file").unwrap();
+        let max_age = std::time::Duration::from_millis(500);
+        let mut env = with_memory(
+            WasiState::new("test")
+                .preopen_dir(dir.path())
+                .unwrap()
+                .freshness_policy(FreshnessPolicy::MaxAge(max_age))
+                .finalize()
+                .unwrap(),
+        );
+        let fd = open(&mut env, "entry", 0).unwrap();
+        assert_eq!(fd_close(&mut env, fd), __WASI_ESUCCESS);
+
+        std::fs::remove_file(dir.path().join("entry")).unwrap();
+        std::fs::create_dir(dir.path().join("entry")).unwrap();
+        std::fs::write(dir.path().join("entry/file"), b"data").unwrap();
+        // the cached file is used until it expires
+        assert_eq!(open(&mut env, "entry/file", 0), Err(__WASI_ENOTDIR));
+
+        std::thread::sleep(max_age + std::time::Duration::from_millis(100));
+        let fd = open(&mut env, "entry/file", 0).unwrap();
+        assert_eq!(read_file(&mut env, fd), b"data");
+    }
+
+    fn env_with_io_limits(dir: &Path, io_limits: IoLimits) -> WasiEnv {
+        with_memory(
+            WasiState::new("test")
+                .preopen_dir(dir)
+                .unwrap()
+                .io_limits(io_limits)
+                .finalize()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn io_over_the_limits_fails_with_eagain() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = env_with_io_limits(
+            dir.path(),
+            IoLimits {
+                write_bytes_per_sec: Some(4),
+                mode: ThrottleMode::Again,
+                ..IoLimits::default()
+            },
+        );
+        let fd = open(&mut env, "file", __WASI_O_CREAT).unwrap();
+        // the write exceeding the limit goes through, the next one fails
+        write_file(&mut env, fd, b"too much");
+        let iovs = WasmPtr::<__wasi_ciovec_t, Array>::new(OUT_OFFSET + 8);
+        assert_eq!(
+            fd_write(&mut env, fd, iovs, 1, WasmPtr::new(OUT_OFFSET)),
+            __WASI_EAGAIN
+        );
+        // stdio isn't limited
+        write_memory(&env, BUF_OFFSET, b"\n");
+        iovs.deref(env.memory(), 0, 1).unwrap()[0].set(__wasi_ciovec_t {
+            buf: WasmPtr::new(BUF_OFFSET),
+            buf_len: 1,
+        });
+        assert_eq!(
+            fd_write(
+                &mut env,
+                __WASI_STDOUT_FILENO,
+                iovs,
+                1,
+                WasmPtr::new(OUT_OFFSET)
+            ),
+            __WASI_ESUCCESS
+        );
+    }
+
+    #[test]
+    fn blocked_io_unlocks_the_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = env_with_io_limits(
+            dir.path(),
+            IoLimits {
+                ops_per_sec: Some(1),
+                mode: ThrottleMode::Block,
+                ..IoLimits::default()
+            },
+        );
+        let fd = open(&mut env, "file", __WASI_O_CREAT).unwrap();
+        write_file(&mut env, fd, b"data");
+
+        let start = std::time::Instant::now();
+        let writer = {
+            let mut env = env.clone();
+            std::thread::spawn(move || write_file(&mut env, fd, b"data"))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        // the writer waits for the next second without holding the state
+        let locked = std::time::Instant::now();
+        drop(env.state());
+        assert!(locked.elapsed() < std::time::Duration::from_millis(500));
+        writer.join().unwrap();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(500));
+    }
 }
@@ -695,8 +695,45 @@ impl __wasi_subscription_t {
 
 pub type __wasi_timestamp_t = u64;
 
+/// Terminal settings of a stdio fd, read and written by the `wasmer_tty`
+/// extension functions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct __wasi_tty_t {
+    pub cols: u32,
+    pub rows: u32,
+    /// Whether input is echoed back to the terminal.
+    pub echo: u8,
+    /// Whether input is delivered a line at a time (canonical mode).
+    pub line_buffered: u8,
+}
+
+unsafe impl ValueType for __wasi_tty_t {}
+
+impl Default for __wasi_tty_t {
+    fn default() -> Self {
+        Self {
+            cols: 80,
+            rows: 25,
+            echo: 1,
+            line_buffered: 1,
+        }
+    }
+}
+
 pub type __wasi_userdata_t = u64;
 
+/// Flags of `fs_watch`, in the `wasmer_fs` extension.
+pub type __wasi_watchflags_t = u32;
+/// Watch the subdirectories of the directory too.
+pub const __WASI_WATCH_RECURSIVE: __wasi_watchflags_t = 1 << 0;
+
+/// The kind of an event read from a watch fd.
+pub type __wasi_watch_event_kind_t = u8;
+pub const __WASI_WATCH_CREATED: __wasi_watch_event_kind_t = 0;
+pub const __WASI_WATCH_MODIFIED: __wasi_watch_event_kind_t = 1;
+pub const __WASI_WATCH_REMOVED: __wasi_watch_event_kind_t = 2;
+
 pub type __wasi_whence_t = u8;
 pub const __WASI_WHENCE_SET: u8 = 0;
 pub const __WASI_WHENCE_CUR: u8 = 1;
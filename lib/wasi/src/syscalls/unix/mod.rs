@@ -63,3 +63,107 @@ pub fn platform_clock_time_get(
     // TODO: map output of clock_gettime to __wasi_errno_t
     __WASI_ESUCCESS
 }
+
+/// The window size of the host terminal `host_fd`, as `(cols, rows)`.
+pub fn platform_tty_size(host_fd: i32) -> Option<(u32, u32)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(host_fd, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return None;
+    }
+    Some((size.ws_col as u32, size.ws_row as u32))
+}
+
+/// The settings of a host terminal before the guest changed them, restored
+/// when this is dropped.
+pub struct HostTtySettings {
+    host_fd: i32,
+    termios: libc::termios,
+}
+
+impl std::fmt::Debug for HostTtySettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostTtySettings")
+            .field("host_fd", &self.host_fd)
+            .finish()
+    }
+}
+
+impl Drop for HostTtySettings {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.host_fd, libc::TCSANOW, &self.termios) };
+    }
+}
+
+/// Save the settings of the host terminal `host_fd`, to restore them later.
+pub fn platform_tty_save(host_fd: i32) -> Option<HostTtySettings> {
+    let mut termios: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(host_fd, &mut termios) } != 0 {
+        return None;
+    }
+    Some(HostTtySettings { host_fd, termios })
+}
+
+/// Turn echo and canonical mode of the host terminal `host_fd` on or off.
+pub fn platform_tty_set(
+    host_fd: i32,
+    echo: bool,
+    line_buffered: bool,
+) -> Result<(), __wasi_errno_t> {
+    let mut termios: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(host_fd, &mut termios) } != 0 {
+        return Err(__WASI_ENOTTY);
+    }
+    if echo {
+        termios.c_lflag |= libc::ECHO;
+    } else {
+        termios.c_lflag &= !libc::ECHO;
+    }
+    if line_buffered {
+        termios.c_lflag |= libc::ICANON;
+    } else {
+        termios.c_lflag &= !libc::ICANON;
+    }
+    if unsafe { libc::tcsetattr(host_fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(__WASI_EIO);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "macos")))]
+mod test {
+    use super::*;
+    use std::ptr;
+
+    fn lflag(fd: i32) -> libc::tcflag_t {
+        let mut termios: libc::termios = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut termios) }, 0);
+        termios.c_lflag & (libc::ECHO | libc::ICANON)
+    }
+
+    #[test]
+    fn host_tty_settings_are_restored() {
+        let (mut master, mut slave) = (0, 0);
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(opened, 0);
+        let before = lflag(slave);
+
+        let saved = platform_tty_save(slave).unwrap();
+        platform_tty_set(slave, false, false).unwrap();
+        assert_eq!(lflag(slave), 0);
+        drop(saved);
+        assert_eq!(lflag(slave), before);
+
+        unsafe {
+            libc::close(slave);
+            libc::close(master);
+        }
+    }
+}
@@ -43,14 +43,31 @@ pub fn platform_clock_time_get(
                 }));
             duration.as_nanos() as u64
         }
-        __WASI_CLOCK_PROCESS_CPUTIME_ID => {
-            unimplemented!("wasi::platform_clock_time_get(__WASI_CLOCK_PROCESS_CPUTIME_ID, ..)")
-        }
-        __WASI_CLOCK_THREAD_CPUTIME_ID => {
-            unimplemented!("wasi::platform_clock_time_get(__WASI_CLOCK_THREAD_CPUTIME_ID, ..)")
-        }
+        __WASI_CLOCK_PROCESS_CPUTIME_ID | __WASI_CLOCK_THREAD_CPUTIME_ID => return __WASI_EINVAL,
         _ => return __WASI_EINVAL,
     };
     time.set(nanos);
     __WASI_ESUCCESS
 }
+
+pub fn platform_tty_size(_host_fd: i32) -> Option<(u32, u32)> {
+    None
+}
+
+/// The settings of a host terminal before the guest changed them; they're
+/// only emulated on Windows, so there's nothing to restore.
+#[derive(Debug)]
+pub struct HostTtySettings;
+
+pub fn platform_tty_save(_host_fd: i32) -> Option<HostTtySettings> {
+    None
+}
+
+pub fn platform_tty_set(
+    _host_fd: i32,
+    _echo: bool,
+    _line_buffered: bool,
+) -> Result<(), __wasi_errno_t> {
+    debug!("wasi::platform_tty_set is not implemented on Windows, the settings are only emulated");
+    Ok(())
+}
@@ -69,3 +69,74 @@ pub fn get_wasi_version(module: &Module, strict: bool) -> Option<WasiVersion> {
         })
     }
 }
+
+/// How a WASI module is meant to be run, determined by its exports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WasiExecModel {
+    /// A command, exporting `_start`, run once.
+    Command,
+    /// A reactor, optionally exporting `_initialize`, initialized once
+    /// and then called through its other exports.
+    Reactor,
+}
+
+/// Detect whether a WASI module is a command or a reactor.
+///
+/// A module exporting `_start` is a command, even if it exports
+/// `_initialize` too, as they were run before reactors existed.
+///
+/// Returns `None` if `_start` or `_initialize` don't have the `() -> ()`
+/// signature.
+pub fn get_wasi_exec_model(module: &Module) -> Option<WasiExecModel> {
+    let mut start = false;
+    let mut initialize = false;
+    for export in module.exports() {
+        let found = match export.name() {
+            "_start" => &mut start,
+            "_initialize" => &mut initialize,
+            _ => continue,
+        };
+        match export.ty() {
+            ExternType::Function(ty) if ty.params().is_empty() && ty.results().is_empty() => {
+                *found = true
+            }
+            _ => return None,
+        }
+    }
+
+    match (start, initialize) {
+        (true, _) => Some(WasiExecModel::Command),
+        (false, _) => Some(WasiExecModel::Reactor),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer::Store;
+
+    fn exec_model(wat: &str) -> Option<WasiExecModel> {
+        get_wasi_exec_model(&Module::new(&Store::default(), wat).unwrap())
+    }
+
+    #[test]
+    fn exec_model_of_commands_and_reactors() {
+        assert_eq!(
+            exec_model(r#"(module (func (export "_start")))"#),
+            Some(WasiExecModel::Command)
+        );
+        assert_eq!(
+            exec_model(r#"(module (func (export "_initialize")))"#),
+            Some(WasiExecModel::Reactor)
+        );
+        assert_eq!(exec_model("(module)"), Some(WasiExecModel::Reactor));
+        assert_eq!(
+            exec_model(r#"(module (func (export "_start")) (func (export "_initialize")))"#),
+            Some(WasiExecModel::Command)
+        );
+        assert_eq!(
+            exec_model(r#"(module (func (export "_start") (param i32)))"#),
+            None
+        );
+    }
+}
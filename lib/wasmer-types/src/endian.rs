@@ -0,0 +1,191 @@
+//! Conversions of values from and to the little-endian layout of guest
+//! memory.
+//!
+//! WebAssembly memories are little-endian and lay structs out like the
+//! C ABI of `wasm32`. Casting guest bytes to host types instead gets the
+//! byte order wrong on big-endian hosts, and the field offsets wrong when
+//! the host aligns types differently (e.g. `u64` on 32-bit x86).
+
+/// A type that can be read from and written to guest memory, byte by
+/// byte, in the layout the guest uses.
+///
+/// It's implemented for the primitive numeric types and arrays of them;
+/// use [`little_endian_struct!`] to implement it for structs.
+///
+/// [`little_endian_struct!`]: crate::little_endian_struct
+pub trait LittleEndian: Sized {
+    /// The size of the value in guest memory, padding included.
+    const SIZE: usize;
+
+    /// The alignment of the value in guest memory.
+    const ALIGN: usize;
+
+    /// Reads a value from the first [`Self::SIZE`] bytes of `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is shorter than [`Self::SIZE`].
+    fn read_le(bytes: &[u8]) -> Self;
+
+    /// Writes the value to the first [`Self::SIZE`] bytes of `bytes`,
+    /// zeroing the padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is shorter than [`Self::SIZE`].
+    fn write_le(&self, bytes: &mut [u8]);
+}
+
+macro_rules! little_endian_primitive {
+    ( $($type:ty),* ) => {
+        $(
+            impl LittleEndian for $type {
+                const SIZE: usize = core::mem::size_of::<$type>();
+                const ALIGN: usize = core::mem::size_of::<$type>();
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    let mut le_bytes = [0; core::mem::size_of::<$type>()];
+                    le_bytes.copy_from_slice(&bytes[..Self::SIZE]);
+                    <$type>::from_le_bytes(le_bytes)
+                }
+
+                fn write_le(&self, bytes: &mut [u8]) {
+                    bytes[..Self::SIZE].copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+little_endian_primitive!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+macro_rules! little_endian_array {
+    ( $($len:expr),* ) => {
+        $(
+            impl<T: LittleEndian + Copy + Default> LittleEndian for [T; $len] {
+                const SIZE: usize = T::SIZE * $len;
+                const ALIGN: usize = T::ALIGN;
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    let mut array = [T::default(); $len];
+                    for (index, item) in array.iter_mut().enumerate() {
+                        *item = T::read_le(&bytes[index * T::SIZE..]);
+                    }
+                    array
+                }
+
+                fn write_le(&self, bytes: &mut [u8]) {
+                    for (index, item) in self.iter().enumerate() {
+                        item.write_le(&mut bytes[index * T::SIZE..]);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+little_endian_array!(
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 20, 24, 28, 32, 48, 64, 128, 256
+);
+
+/// Rounds `offset` up to a multiple of `align`.
+#[doc(hidden)]
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
+
+/// Returns the largest of two alignments.
+#[doc(hidden)]
+pub const fn max_align(a: usize, b: usize) -> usize {
+    [a, b][(a < b) as usize]
+}
+
+/// Implements [`LittleEndian`] for a struct, laying its fields out like
+/// the C ABI of `wasm32` does for a `#[repr(C)]` struct: in declaration
+/// order, each at a multiple of its alignment, with trailing padding.
+///
+/// The fields and their types must be listed in declaration order;
+/// forgetting a field is a compile error. The padding is zeroed when
+/// writing, so no host data leaks to the guest, and ignored when
+/// reading.
+///
+/// # Example
+///
+/// ```
+/// use wasmer_types::{little_endian_struct, LittleEndian};
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// #[repr(C)]
+/// struct Event {
+///     kind: u8,
+///     timestamp: u64,
+/// }
+///
+/// little_endian_struct!(Event { kind: u8, timestamp: u64 });
+///
+/// assert_eq!(Event::SIZE, 16);
+/// let mut bytes = [0xff; 16];
+/// let event = Event { kind: 1, timestamp: 2 };
+/// event.write_le(&mut bytes);
+/// assert_eq!(bytes, [1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+/// assert_eq!(Event::read_le(&bytes), event);
+/// ```
+#[macro_export]
+macro_rules! little_endian_struct {
+    ( $name:ident { $( $field:ident : $type:ty ),* $(,)? } ) => {
+        impl $crate::LittleEndian for $name {
+            const SIZE: usize = {
+                let offset = 0;
+                $(
+                    let offset = $crate::endian::align_up(
+                        offset,
+                        <$type as $crate::LittleEndian>::ALIGN,
+                    ) + <$type as $crate::LittleEndian>::SIZE;
+                )*
+                $crate::endian::align_up(offset, <Self as $crate::LittleEndian>::ALIGN)
+            };
+            const ALIGN: usize = {
+                let align = 1;
+                $(
+                    let align = $crate::endian::max_align(
+                        align,
+                        <$type as $crate::LittleEndian>::ALIGN,
+                    );
+                )*
+                align
+            };
+
+            #[allow(unused_assignments)]
+            fn read_le(bytes: &[u8]) -> Self {
+                let bytes = &bytes[..<Self as $crate::LittleEndian>::SIZE];
+                let mut offset = 0;
+                $(
+                    offset = $crate::endian::align_up(
+                        offset,
+                        <$type as $crate::LittleEndian>::ALIGN,
+                    );
+                    let $field = <$type as $crate::LittleEndian>::read_le(&bytes[offset..]);
+                    offset += <$type as $crate::LittleEndian>::SIZE;
+                )*
+                Self { $( $field ),* }
+            }
+
+            #[allow(unused_assignments)]
+            fn write_le(&self, bytes: &mut [u8]) {
+                let bytes = &mut bytes[..<Self as $crate::LittleEndian>::SIZE];
+                for byte in bytes.iter_mut() {
+                    *byte = 0;
+                }
+                let mut offset = 0;
+                $(
+                    offset = $crate::endian::align_up(
+                        offset,
+                        <$type as $crate::LittleEndian>::ALIGN,
+                    );
+                    $crate::LittleEndian::write_le(&self.$field, &mut bytes[offset..]);
+                    offset += <$type as $crate::LittleEndian>::SIZE;
+                )*
+            }
+        }
+    };
+}
@@ -50,6 +50,8 @@ mod lib {
     }
 }
 
+#[doc(hidden)]
+pub mod endian;
 mod features;
 mod indexes;
 mod initializers;
@@ -65,6 +67,7 @@ pub mod entity {
     pub use cranelift_entity::*;
 }
 
+pub use crate::endian::LittleEndian;
 pub use crate::features::Features;
 pub use crate::indexes::{
     CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, ImportIndex,
@@ -74,7 +77,9 @@ pub use crate::indexes::{
 pub use crate::initializers::{
     DataInitializer, DataInitializerLocation, OwnedDataInitializer, TableInitializer,
 };
-pub use crate::memory_view::{Atomically, MemoryView};
+#[cfg(feature = "std")]
+pub use crate::memory_view::WaitResult;
+pub use crate::memory_view::{AtomicValue, Atomically, MemoryView};
 pub use crate::native::{NativeWasmType, ValueType};
 pub use crate::r#ref::{ExternRef, HostInfo, HostRef};
 pub use crate::units::{Bytes, Pages, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE};
@@ -3,9 +3,11 @@ use crate::lib::std::marker::PhantomData;
 use crate::lib::std::ops::Deref;
 use crate::lib::std::slice;
 use crate::lib::std::sync::atomic::{
-    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8,
+    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering,
 };
 use crate::native::ValueType;
+#[cfg(feature = "std")]
+use std::time::Duration;
 
 pub trait Atomic {
     type Output;
@@ -92,3 +94,245 @@ impl<'a, T> Deref for MemoryView<'a, T, Atomically> {
         unsafe { slice::from_raw_parts(self.ptr as *const T, self.length) }
     }
 }
+
+/// The atomic operations available on the values of an atomic
+/// [`MemoryView`].
+pub trait AtomicValue {
+    /// The type of the value.
+    type Value: Copy;
+
+    /// Stores `new` if the current value is `current`, returning the
+    /// previous value.
+    fn compare_exchange(
+        &self,
+        current: Self::Value,
+        new: Self::Value,
+    ) -> Result<Self::Value, Self::Value>;
+
+    /// Adds `value` to the current value, wrapping around on overflow,
+    /// and returns the previous value.
+    fn fetch_add(&self, value: Self::Value) -> Self::Value;
+
+    /// Subtracts `value` from the current value, wrapping around on
+    /// overflow, and returns the previous value.
+    fn fetch_sub(&self, value: Self::Value) -> Self::Value;
+}
+
+macro_rules! atomic_value {
+    ( $($atomic:ty => $value:ty),+ ) => {
+        $(
+            impl AtomicValue for $atomic {
+                type Value = $value;
+
+                fn compare_exchange(&self, current: $value, new: $value) -> Result<$value, $value> {
+                    <$atomic>::compare_exchange(self, current, new, Ordering::SeqCst, Ordering::SeqCst)
+                }
+
+                fn fetch_add(&self, value: $value) -> $value {
+                    <$atomic>::fetch_add(self, value, Ordering::SeqCst)
+                }
+
+                fn fetch_sub(&self, value: $value) -> $value {
+                    <$atomic>::fetch_sub(self, value, Ordering::SeqCst)
+                }
+            }
+        )+
+    }
+}
+
+atomic_value!(
+    AtomicI8 => i8,
+    AtomicI16 => i16,
+    AtomicI32 => i32,
+    AtomicI64 => i64,
+    AtomicU8 => u8,
+    AtomicU16 => u16,
+    AtomicU32 => u32,
+    AtomicU64 => u64
+);
+
+/// The atomic read-modify-write operations of the WebAssembly threads
+/// proposal, with sequentially consistent ordering. They return `None`
+/// when `index` is out of bounds.
+impl<'a, T: AtomicValue> MemoryView<'a, T, Atomically> {
+    /// Stores `new` at `index` if the value there is `current`, like
+    /// the `atomic.rmw.cmpxchg` instructions.
+    ///
+    /// Returns `Ok` with the previous value if it was replaced, `Err`
+    /// with the current value otherwise.
+    pub fn compare_exchange(
+        &self,
+        index: usize,
+        current: T::Value,
+        new: T::Value,
+    ) -> Option<Result<T::Value, T::Value>> {
+        Some(self.get(index)?.compare_exchange(current, new))
+    }
+
+    /// Adds `value` to the value at `index`, like the `atomic.rmw.add`
+    /// instructions, and returns the previous value.
+    pub fn fetch_add(&self, index: usize, value: T::Value) -> Option<T::Value> {
+        Some(self.get(index)?.fetch_add(value))
+    }
+
+    /// Subtracts `value` from the value at `index`, like the
+    /// `atomic.rmw.sub` instructions, and returns the previous value.
+    pub fn fetch_sub(&self, index: usize, value: T::Value) -> Option<T::Value> {
+        Some(self.get(index)?.fetch_sub(value))
+    }
+
+    /// Wakes up to `count` threads waiting on `index` with
+    /// [`MemoryView::wait`], like `memory.atomic.notify`, and returns
+    /// how many were woken.
+    #[cfg(feature = "std")]
+    pub fn notify(&self, index: usize, count: u32) -> Option<u32> {
+        let address = self.get(index)? as *const T as usize;
+        Some(parking::notify(address, count))
+    }
+}
+
+/// The result of [`MemoryView::wait`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitResult {
+    /// The thread was woken by a notification.
+    Woken,
+    /// The value wasn't the expected one, so the thread didn't wait.
+    NotEqual,
+    /// The timeout elapsed before a notification.
+    TimedOut,
+}
+
+macro_rules! atomic_wait {
+    ( $($atomic:ty => $value:ty),+ ) => {
+        $(
+            #[cfg(feature = "std")]
+            impl<'a> MemoryView<'a, $atomic, Atomically> {
+                /// Blocks the current thread until it's woken by
+                /// [`MemoryView::notify`] on the same location, or
+                /// `timeout` elapses, like `memory.atomic.wait`.
+                ///
+                /// The thread doesn't wait if the value at `index` isn't
+                /// `expected`. Returns `None` when `index` is out of bounds.
+                pub fn wait(
+                    &self,
+                    index: usize,
+                    expected: $value,
+                    timeout: Option<Duration>,
+                ) -> Option<WaitResult> {
+                    let atomic = self.get(index)?;
+                    let address = atomic as *const $atomic as usize;
+                    Some(parking::wait(
+                        address,
+                        || atomic.load(Ordering::SeqCst) == expected,
+                        timeout,
+                    ))
+                }
+            }
+        )+
+    }
+}
+
+atomic_wait!(
+    AtomicI32 => i32,
+    AtomicI64 => i64,
+    AtomicU32 => u32,
+    AtomicU64 => u64
+);
+
+/// The queues of the threads waiting on memory locations, shared by
+/// all the memories.
+#[cfg(feature = "std")]
+mod parking {
+    use super::WaitResult;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(Default)]
+    struct Waiter {
+        woken: Mutex<bool>,
+        condvar: Condvar,
+    }
+
+    lazy_static::lazy_static! {
+        static ref QUEUES: Mutex<HashMap<usize, VecDeque<Arc<Waiter>>>> =
+            Mutex::new(HashMap::new());
+    }
+
+    pub(super) fn wait(
+        address: usize,
+        is_expected: impl FnOnce() -> bool,
+        timeout: Option<Duration>,
+    ) -> WaitResult {
+        let waiter = Arc::new(Waiter::default());
+        {
+            // Notifications take the lock too, so none can be missed
+            // between the check and the wait.
+            let mut queues = QUEUES.lock().unwrap();
+            if !is_expected() {
+                return WaitResult::NotEqual;
+            }
+            queues.entry(address).or_default().push_back(waiter.clone());
+        }
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut woken = waiter.woken.lock().unwrap();
+        while !*woken {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    woken = waiter
+                        .condvar
+                        .wait_timeout(woken, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+                None => woken = waiter.condvar.wait(woken).unwrap(),
+            }
+        }
+        if *woken {
+            return WaitResult::Woken;
+        }
+        drop(woken);
+
+        let mut queues = QUEUES.lock().unwrap();
+        if let Some(queue) = queues.get_mut(&address) {
+            queue.retain(|other| !Arc::ptr_eq(other, &waiter));
+            if queue.is_empty() {
+                queues.remove(&address);
+            }
+        }
+        // A notification may have come in before the lock was taken.
+        if *waiter.woken.lock().unwrap() {
+            WaitResult::Woken
+        } else {
+            WaitResult::TimedOut
+        }
+    }
+
+    pub(super) fn notify(address: usize, count: u32) -> u32 {
+        let mut queues = QUEUES.lock().unwrap();
+        let queue = match queues.get_mut(&address) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+        let mut notified = 0;
+        while notified < count {
+            let waiter = match queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            *waiter.woken.lock().unwrap() = true;
+            waiter.condvar.notify_one();
+            notified += 1;
+        }
+        if queue.is_empty() {
+            queues.remove(&address);
+        }
+        notified
+    }
+}
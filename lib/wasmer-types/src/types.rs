@@ -213,6 +213,17 @@ impl ExternType {
     }
 }
 
+impl fmt::Display for ExternType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Function(ty) => write!(f, "function {}", ty),
+            Self::Global(ty) => write!(f, "global {}", ty),
+            Self::Table(ty) => write!(f, "table {}", ty),
+            Self::Memory(ty) => write!(f, "memory {}", ty),
+        }
+    }
+}
+
 // TODO: `shrink_to_fit` these or change it to `Box<[Type]>` if not using
 // Cow or something else
 /// The signature of a function that is either implemented
@@ -0,0 +1,161 @@
+#![cfg(feature = "test-jit")]
+
+use crate::utils::get_compiler;
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine_jit::{CodeMemoryPages, CodeMemoryProvider, MmapCodeMemoryProvider, JIT};
+
+const WAT: &str = r#"
+    (module
+        (func (export "sum") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+"#;
+
+#[test]
+fn code_memory_is_reported() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let engine = JIT::new(&compiler_config).engine();
+    let store = Store::new(&engine);
+    assert_eq!(engine.code_memory_used(), 0);
+
+    let module = Module::new(&store, WAT)?;
+    assert!(module.code_memory_size() > 0);
+    assert_eq!(engine.code_memory_used(), module.code_memory_size());
+
+    Ok(())
+}
+
+#[test]
+fn code_memory_limit_is_enforced() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let engine = JIT::new(&compiler_config).max_code_memory(0).engine();
+    let store = Store::new(&engine);
+
+    let error = Module::new(&store, WAT).unwrap_err();
+    assert!(
+        error.to_string().contains("code memory limit exceeded"),
+        "wrong error: {}",
+        error
+    );
+    assert_eq!(engine.code_memory_used(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn purge_releases_dropped_modules() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let engine = JIT::new(&compiler_config).engine();
+    let store = Store::new(&engine);
+
+    let kept = Module::new(&store, WAT)?;
+    let dropped = Module::new(&store, WAT)?;
+    let instance = Instance::new(&dropped, &imports! {})?;
+    drop(dropped);
+
+    // The instance keeps its module alive.
+    assert_eq!(unsafe { engine.purge() }, 0);
+    assert_eq!(engine.code_memory_used(), 2 * kept.code_memory_size());
+
+    drop(instance);
+    assert_eq!(unsafe { engine.purge() }, kept.code_memory_size());
+    assert_eq!(engine.code_memory_used(), kept.code_memory_size());
+
+    drop(kept);
+    assert!(unsafe { engine.purge() } > 0);
+    assert_eq!(engine.code_memory_used(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn trampolines_are_shared_by_modules() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let engine = JIT::new(&compiler_config).engine();
+    let store = Store::new(&engine);
+    let wat = r#"
+        (module
+            (import "env" "first" (func $first (param i32) (result i32)))
+            (import "env" "second" (func $second (param i32) (result i32)))
+            (func (export "run") (param i32) (result i32)
+                (call $second (call $first (local.get 0)))))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let trampoline_memory = engine.trampoline_memory_used();
+    assert!(trampoline_memory > 0);
+    Module::new(&store, wat)?;
+    assert_eq!(engine.trampoline_memory_used(), trampoline_memory);
+
+    let double = Function::new(
+        &store,
+        &FunctionType::new(vec![ValType::I32], vec![ValType::I32]),
+        |args| Ok(vec![Value::I32(args[0].unwrap_i32() * 2)]),
+    );
+    let import_object = imports! {
+        "env" => {
+            "first" => double.clone(),
+            "second" => double,
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(3)?, 12);
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+struct CountingProvider {
+    allocated: Arc<AtomicUsize>,
+}
+
+impl CodeMemoryProvider for CountingProvider {
+    fn allocate(&self, size: usize) -> Result<Box<dyn CodeMemoryPages>, String> {
+        self.allocated.fetch_add(size, Ordering::SeqCst);
+        MmapCodeMemoryProvider.allocate(size)
+    }
+}
+
+#[test]
+fn code_memory_provider_is_used() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let provider = CountingProvider::default();
+    let engine = JIT::new(&compiler_config)
+        .code_memory_provider(provider.clone())
+        .engine();
+    let store = Store::new(&engine);
+
+    let module = Module::new(&store, WAT)?;
+    assert!(provider.allocated.load(Ordering::SeqCst) > 0);
+    let instance = Instance::new(&module, &imports! {})?;
+    let sum = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("sum")?;
+    assert_eq!(sum.call(1, 2)?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn debug_info_registration() -> Result<()> {
+    let compiler_config = get_compiler(false);
+    let engine = JIT::new(&compiler_config).debug_info(true).engine();
+    let store = Store::new(&engine);
+
+    // The modules are unregistered in any order when dropped.
+    let first = Module::new(&store, WAT)?;
+    let second = Module::new(&store, WAT)?;
+    let third = Module::new(&store, WAT)?;
+    drop(second);
+    let instance = Instance::new(&third, &imports! {})?;
+    drop(first);
+    let sum = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("sum")?;
+    assert_eq!(sum.call(1, 2)?, 3);
+
+    Ok(())
+}
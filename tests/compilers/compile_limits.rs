@@ -0,0 +1,76 @@
+use crate::utils::get_store_with;
+use anyhow::Result;
+use std::time::Duration;
+use wasmer::*;
+use wasmer_compiler::CompileError;
+
+const WAT: &str = r#"
+    (module
+        (func (export "small") (result i32)
+            (i32.const 1))
+        (func (export "large") (result i32)
+            (local i32 i32 i32 i32 i32 i32 i32 i32)
+            (local.set 0 (i32.const 1))
+            (local.set 1 (i32.add (local.get 0) (i32.const 1)))
+            (local.set 2 (i32.add (local.get 1) (i32.const 1)))
+            (local.set 3 (i32.add (local.get 2) (i32.const 1)))
+            (local.get 3)))
+"#;
+
+fn compile(limits: &CompileLimits) -> Result<Module, CompileError> {
+    let store = get_store_with(|compiler| compiler.set_limits(*limits), |engine| engine);
+    Module::new(&store, WAT)
+}
+
+#[test]
+fn modules_within_limits_compile() -> Result<()> {
+    let module = compile(
+        CompileLimits::new()
+            .max_module_size(1024)
+            .max_function_size(1024)
+            .max_locals(8)
+            .time_budget(Duration::from_secs(60)),
+    )?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let large = instance.exports.get_native_function::<(), i32>("large")?;
+    assert_eq!(large.call()?, 4);
+    Ok(())
+}
+
+#[test]
+fn max_module_size() -> Result<()> {
+    let error = compile(CompileLimits::new().max_module_size(16)).unwrap_err();
+    assert_eq!(error.code(), "resource");
+    assert!(error.to_string().contains("limit of 16 bytes"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn max_function_size() -> Result<()> {
+    let error = compile(CompileLimits::new().max_function_size(8)).unwrap_err();
+    assert_eq!(error.code(), "resource");
+    assert!(error.to_string().contains("local function 1"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn max_locals() -> Result<()> {
+    let error = compile(CompileLimits::new().max_locals(7)).unwrap_err();
+    assert_eq!(error.code(), "resource");
+    assert!(
+        error
+            .to_string()
+            .contains("has 8 locals, more than the limit of 7"),
+        "{}",
+        error
+    );
+    Ok(())
+}
+
+#[test]
+fn time_budget() -> Result<()> {
+    let error = compile(CompileLimits::new().time_budget(Duration::from_secs(0))).unwrap_err();
+    assert_eq!(error.code(), "resource");
+    assert!(error.to_string().contains("budget"), "{}", error);
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::utils::get_store;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "value" (global $value i32))
+        (memory (export "memory") 1)
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (func (export "div") (param i32 i32) (result i32)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.div_s (local.get 0) (local.get 1)))
+        (func (export "nan") (result f32)
+            (f32.div (f32.const 0) (f32.const 0)))
+        (func (export "store_value")
+            (i32.store (i32.const 16) (global.get $value))))
+"#;
+
+fn value_imports(value: i32) -> impl Fn(&Store) -> ImportObject {
+    move |store| {
+        imports! {
+            "env" => {
+                "value" => Global::new(store, Val::I32(value)),
+            },
+        }
+    }
+}
+
+#[test]
+fn differential_same_outcomes() {
+    let differential =
+        Differential::new(get_store(false), get_store(true)).imports(value_imports(42));
+    let outcomes = differential
+        .run(
+            WAT,
+            &[
+                DifferentialCall::new("div", vec![Val::I32(7), Val::I32(2)]),
+                DifferentialCall::new("div", vec![Val::I32(7), Val::I32(0)]),
+                DifferentialCall::new("nan", vec![]),
+                DifferentialCall::new("store_value", vec![]),
+            ],
+        )
+        .unwrap();
+    assert!(
+        matches!(&outcomes[0], DifferentialOutcome::Returned(values) if values[0] == Val::I32(3))
+    );
+    assert!(matches!(
+        outcomes[1],
+        DifferentialOutcome::Trapped(Some(TrapCode::IntegerDivisionByZero))
+    ));
+}
+
+#[test]
+fn differential_memory_mismatch() {
+    // The imported global has a different value with each store.
+    let next_value = Rc::new(Cell::new(0));
+    let differential =
+        Differential::new(get_store(false), get_store(false)).imports(move |store| {
+            next_value.set(next_value.get() + 1);
+            value_imports(next_value.get())(store)
+        });
+    let error = differential
+        .run(WAT, &[DifferentialCall::new("store_value", vec![])])
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        DifferentialError::Memory {
+            index: 0,
+            offset: 16,
+            ..
+        }
+    ));
+}
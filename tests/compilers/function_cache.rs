@@ -0,0 +1,97 @@
+use crate::utils::{get_store, get_store_with};
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+
+fn wat(answer: i32) -> String {
+    format!(
+        r#"(module
+            (func (export "answer") (result i32)
+                (i32.const {}))
+            (func (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (func (export "trap")
+                (unreachable)))"#,
+        answer
+    )
+}
+
+fn trap_offset(module: &Module) -> Result<usize> {
+    let instance = Instance::new(module, &imports! {})?;
+    let trap = instance.exports.get_function("trap")?;
+    let error = trap.call(&[]).unwrap_err();
+    Ok(error.trace()[0].module_offset())
+}
+
+#[test]
+fn unchanged_functions_are_reused() -> Result<()> {
+    let cache = Arc::new(FunctionCache::new());
+    let store = get_store_with(
+        |compiler| compiler.set_function_cache(cache.clone()),
+        |engine| engine,
+    );
+
+    Module::new(&store, wat(1))?;
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.hits(), 0);
+
+    // the new constant is longer, moving the functions after it
+    let module = Module::new(&store, wat(1000))?;
+    assert_eq!(cache.len(), 4);
+    assert_eq!(cache.hits(), 2);
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let answer = instance.exports.get_native_function::<(), i32>("answer")?;
+    assert_eq!(answer.call()?, 1000);
+    let double = instance.exports.get_native_function::<i32, i32>("double")?;
+    assert_eq!(double.call(21)?, 42);
+
+    let fresh = Module::new(&get_store(false), wat(1000))?;
+    assert_eq!(trap_offset(&module)?, trap_offset(&fresh)?);
+    Ok(())
+}
+
+#[test]
+fn changed_declarations_invalidate_the_functions() -> Result<()> {
+    let cache = Arc::new(FunctionCache::new());
+    let store = get_store_with(
+        |compiler| compiler.set_function_cache(cache.clone()),
+        |engine| engine,
+    );
+
+    Module::new(&store, wat(1))?;
+    // a new global changes the layout of the instances
+    let with_global = wat(1).replacen("(module", "(module (global i32 (i32.const 0))", 1);
+    Module::new(&store, with_global)?;
+    assert_eq!(cache.hits(), 0);
+    assert_eq!(cache.len(), 6);
+    Ok(())
+}
+
+#[test]
+fn changed_settings_invalidate_the_functions() -> Result<()> {
+    let cache = Arc::new(FunctionCache::new());
+    let store = get_store_with(
+        |compiler| compiler.set_function_cache(cache.clone()),
+        |engine| engine,
+    );
+    Module::new(&store, wat(1))?;
+
+    // the epoch checks change the generated code
+    let store = get_store_with(
+        |compiler| {
+            compiler.set_function_cache(cache.clone());
+            compiler.enable_epoch_interruption();
+        },
+        |engine| engine,
+    );
+    Module::new(&store, wat(1))?;
+    assert_eq!(cache.hits(), 0);
+    assert_eq!(cache.len(), 6);
+
+    // the same settings reuse the functions again
+    Module::new(&store, wat(1))?;
+    assert_eq!(cache.hits(), 3);
+    assert_eq!(cache.len(), 6);
+    Ok(())
+}
@@ -272,3 +272,58 @@ fn static_function_that_fails() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn incompatible_memory_style() -> Result<()> {
+    let store = get_store(false);
+    let module = Module::new(&store, r#"(module (import "env" "memory" (memory 1 2)))"#)?;
+
+    // a store without guard pages creates the memory as a dynamic one
+    let unguarded = Store::new_with_tunables(
+        &**store.engine(),
+        Tunables {
+            static_memory_bound: Pages(0),
+            static_memory_offset_guard_size: 0,
+            dynamic_memory_offset_guard_size: 0,
+        },
+    );
+    let memory = Memory::new(&unguarded, MemoryType::new(1, Some(2), false))?;
+    let result = Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "memory" => memory,
+            },
+        },
+    );
+    match result {
+        Err(error @ InstantiationError::Link(_)) => {
+            assert_eq!(error.code(), "import");
+            match error {
+                InstantiationError::Link(LinkError::Import(
+                    _,
+                    _,
+                    ImportError::IncompatibleMemoryStyle(_, received),
+                )) => assert_eq!(
+                    received,
+                    MemoryStyle::Dynamic {
+                        offset_guard_size: 0
+                    }
+                ),
+                error => panic!("unexpected error: {}", error),
+            }
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    let memory = Memory::new(&store, MemoryType::new(1, Some(2), false))?;
+    Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "memory" => memory,
+            },
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use crate::utils::get_store;
+use anyhow::Result;
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+
+#[derive(Debug)]
+struct CountingAllocator {
+    allocated: Arc<AtomicUsize>,
+}
+
+impl InstanceAllocator for CountingAllocator {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        self.allocated.fetch_add(layout.size(), Ordering::SeqCst);
+        SystemInstanceAllocator.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        SystemInstanceAllocator.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn instance_allocator_is_used() -> Result<()> {
+    let mut store = get_store(false);
+    let allocated = Arc::new(AtomicUsize::new(0));
+    store.set_instance_allocator(CountingAllocator {
+        allocated: allocated.clone(),
+    });
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (table 1 funcref)
+            (memory 1)
+            (global (mut i32) (i32.const 0)))
+        "#,
+    )?;
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let allocated_once = allocated.load(Ordering::SeqCst);
+    assert!(allocated_once > instance.memory_usage().vmctx);
+
+    Instance::new(&module, &imports! {})?;
+    assert_eq!(allocated.load(Ordering::SeqCst), 2 * allocated_once);
+
+    Ok(())
+}
@@ -4,11 +4,18 @@
 //! implementation, such as: singlepass, cranelift or llvm depending
 //! on what's available on the target.
 
+mod code_memory;
+mod compile_limits;
+mod differential;
+mod function_cache;
 mod imports;
+mod instance_allocator;
 mod middlewares;
 mod multi_value_imports;
 mod native_functions;
 mod serialize;
+mod signatures;
+mod threads;
 mod traps;
 mod utils;
 mod wasi;
@@ -18,6 +18,61 @@ fn test_serialize() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "test-jit")]
+fn test_serialize_compresses_data() -> Result<()> {
+    let store = get_store(false);
+    let data = "\\00".repeat(64 * 1024);
+    let wat = format!(
+        r#"
+        (module
+        (memory 1)
+        (data (i32.const 0) "{}")
+        )
+    "#,
+        data
+    );
+
+    let module = Module::new(&store, &wat)?;
+    let serialized_bytes = module.serialize()?;
+    assert!(serialized_bytes.len() < 64 * 1024);
+    let deserialized = unsafe { Module::deserialize(&store, &serialized_bytes)? };
+    assert_eq!(deserialized.hash(), module.hash());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "test-jit")]
+fn test_deserialize_rejects_mismatched_artifacts() -> Result<()> {
+    let store = get_store(false);
+    let module = Module::new(&store, "(module (func (export \"run\")))")?;
+    let serialized_bytes = module.serialize()?;
+    let header_len = b"\0wasmer-jit".len();
+
+    // A different format version.
+    let mut bytes = serialized_bytes.clone();
+    bytes[header_len] = bytes[header_len].wrapping_add(1);
+    let error = unsafe { Module::deserialize(&store, &bytes) }.unwrap_err();
+    assert!(
+        matches!(error, DeserializeError::Incompatible(_)),
+        "wrong error: {}",
+        error
+    );
+
+    // A corrupted payload.
+    let mut bytes = serialized_bytes;
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    let error = unsafe { Module::deserialize(&store, &bytes) }.unwrap_err();
+    assert!(
+        matches!(error, DeserializeError::CorruptedBinary(_)),
+        "wrong error: {}",
+        error
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_deserialize() -> Result<()> {
     let store = get_store(false);
@@ -0,0 +1,73 @@
+use crate::utils::{get_store, get_store_with};
+use anyhow::Result;
+use wasmer::*;
+
+const SECRET_KEY: [u8; 32] = [7; 32];
+const OTHER_SECRET_KEY: [u8; 32] = [8; 32];
+
+fn binary() -> Result<Vec<u8>> {
+    Ok(wat2wasm(br#"(module (func (export "one") (result i32) (i32.const 1)))"#)?.into_owned())
+}
+
+fn trusting(secret_key: &[u8; 32]) -> Result<Store> {
+    let mut trust_store = TrustStore::new();
+    trust_store.trust(&public_key(secret_key))?;
+    Ok(get_store_with(
+        |_| {},
+        |engine| engine.trust_store(trust_store),
+    ))
+}
+
+#[test]
+fn embedded_signature_is_verified() -> Result<()> {
+    let store = trusting(&SECRET_KEY)?;
+    let binary = binary()?;
+
+    let signed = sign_module(&binary, &SECRET_KEY);
+    let module = Module::from_binary(&store, &signed)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let one = instance.exports.get_native_function::<(), i32>("one")?;
+    assert_eq!(one.call()?, 1);
+
+    let error = Module::from_binary(&store, &binary).unwrap_err();
+    assert_eq!(error.code(), "signature");
+
+    let untrusted = sign_module(&binary, &OTHER_SECRET_KEY);
+    let error = Module::from_binary(&store, &untrusted).unwrap_err();
+    assert!(error.to_string().contains("untrusted key"), "{}", error);
+
+    // tampering with the signed bytes
+    let mut tampered = signed.clone();
+    tampered[binary.len() - 2] ^= 1;
+    let error = Module::from_binary(&store, &tampered).unwrap_err();
+    assert_eq!(error.code(), "signature");
+
+    Ok(())
+}
+
+#[test]
+fn detached_signature_is_verified() -> Result<()> {
+    let store = trusting(&SECRET_KEY)?;
+    let binary = binary()?;
+
+    let signature = detached_signature(&binary, &SECRET_KEY);
+    assert_eq!(signature.len(), SIGNATURE_LEN);
+    Module::from_binary_with_signature(&store, &binary, &signature)?;
+
+    let other = detached_signature(&binary, &OTHER_SECRET_KEY);
+    assert!(Module::from_binary_with_signature(&store, &binary, &other).is_err());
+    assert!(Module::from_binary_with_signature(&store, &binary, &signature[..64]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn signatures_are_optional_without_trust_store() -> Result<()> {
+    let store = get_store(false);
+    let binary = binary()?;
+
+    Module::from_binary(&store, &binary)?;
+    Module::from_binary(&store, &sign_module(&binary, &SECRET_KEY))?;
+
+    Ok(())
+}
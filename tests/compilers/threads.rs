@@ -0,0 +1,74 @@
+use crate::utils::get_store_with;
+use anyhow::Result;
+use wasmer::*;
+
+fn get_threads() -> Result<WasmThreads> {
+    let mut features = Features::new();
+    features.threads(true);
+    let store = get_store_with(|_| {}, |engine| engine.features(features));
+    let wat = r#"(module
+        (import "env" "memory" (memory 1 1 shared))
+        (import "env" "check" (func $check (param i32)))
+        (func (export "wasi_thread_start") (param $tid i32) (param $arg i32)
+            (call $check (local.get $arg))
+            (i32.store (i32.mul (local.get $tid) (i32.const 4)) (local.get $arg))))"#;
+    let module = Module::new(&store, wat)?;
+    let memory = Memory::new(&store, MemoryType::new(1, Some(1), true))?;
+    Ok(WasmThreads::new(&module, memory, |memory| {
+        let check = Function::new_native(memory.store(), |arg: i32| {
+            if arg < 0 {
+                Err(RuntimeError::new("negative argument"))
+            } else {
+                Ok(())
+            }
+        });
+        imports! {
+            "env" => {
+                "memory" => memory.clone(),
+                "check" => check,
+            }
+        }
+    })?)
+}
+
+#[test]
+fn threads_share_memory() -> Result<()> {
+    let threads = get_threads()?;
+    let spawned = (0..4).map(|i| threads.spawn(i * 10)).collect::<Vec<_>>();
+    let ids = spawned.iter().map(WasmThread::id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+    for thread in spawned {
+        thread.join()?;
+    }
+    let view = threads.memory().view::<i32>();
+    for (i, id) in ids.into_iter().enumerate() {
+        assert_eq!(view[id as usize].get(), i as i32 * 10);
+    }
+
+    // An instance on the current thread shares the memory too.
+    let instance = threads.instantiate()?;
+    let start = instance
+        .exports
+        .get_native_function::<(i32, i32), ()>(THREAD_START_EXPORT)?;
+    start.call(0, 5)?;
+    assert_eq!(view[0].get(), 5);
+    Ok(())
+}
+
+#[test]
+fn threads_report_traps() -> Result<()> {
+    let threads = get_threads()?;
+    let thread = threads.spawn(-1);
+    assert!(matches!(thread.join(), Err(ThreadError::Runtime(_))));
+    Ok(())
+}
+
+#[test]
+fn threads_need_shared_memory() -> Result<()> {
+    let store = get_store_with(|_| {}, |engine| engine.features(Features::new()));
+    let module = Module::new(&store, "(module)")?;
+    let memory = Memory::new(&store, MemoryType::new(1, Some(1), false))?;
+    let result = WasmThreads::new(&module, memory, |_| imports! {});
+    assert!(matches!(result, Err(ThreadError::NotShared)));
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::utils::get_store;
+use crate::utils::{get_store, get_store_with};
 use anyhow::Result;
 use std::panic::{self, AssertUnwindSafe};
 use wasmer::*;
@@ -288,10 +288,10 @@ fn trap_start_function_import() -> Result<()> {
     .err()
     .unwrap();
     match err {
-        InstantiationError::Link(_) => panic!("It should be a start error"),
         InstantiationError::Start(err) => {
             assert_eq!(err.message(), "user trap");
         }
+        _ => panic!("It should be a start error"),
     }
 
     Ok(())
@@ -515,3 +515,89 @@ fn present_after_module_drop() -> Result<()> {
         // assert_eq!(t.trace()[0].func_index(), 0);
     }
 }
+
+#[test]
+fn test_epoch_deadline_interrupts_loop() -> Result<()> {
+    let store = get_store_with(
+        |compiler| compiler.enable_epoch_interruption(),
+        |engine| engine,
+    );
+    let wat = r#"
+        (module
+            (func (export "spin")
+                (loop $l (br $l))))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let spin = instance.exports.get_function("spin")?;
+
+    store.set_epoch_deadline(1);
+    let ticker = {
+        let store = store.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            store.increment_epoch();
+        })
+    };
+
+    let e = spin.call(&[]).err().expect("error calling function");
+    ticker.join().unwrap();
+    assert!(
+        e.message().contains("interrupt"),
+        "wrong message: {}",
+        e.message()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_instance_shutdown() -> Result<()> {
+    let store = get_store_with(
+        |compiler| compiler.enable_epoch_interruption(),
+        |engine| engine,
+    );
+    let wat = r#"
+        (module
+            (global $stopped (export "stopped") (mut i32) (i32.const 0))
+            (func (export "_shutdown")
+                (global.set $stopped (i32.const 1))))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    assert_eq!(
+        instance.shutdown(std::time::Duration::from_secs(10))?,
+        ShutdownOutcome::Completed
+    );
+    assert_eq!(instance.exports.get_global("stopped")?.get(), Value::I32(1));
+
+    let wat = r#"
+        (module
+            (func (export "_shutdown")
+                (loop $l (br $l)))
+            (func (export "check")
+                (loop)))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    store.set_epoch_deadline(1);
+    assert_eq!(
+        instance.shutdown(std::time::Duration::from_millis(10))?,
+        ShutdownOutcome::Interrupted
+    );
+    // the deadline set before the shutdown is restored
+    let check = instance.exports.get_function("check")?;
+    check.call(&[])?;
+    store.increment_epoch();
+    assert!(check.call(&[]).is_err());
+
+    let module = Module::new(&store, "(module)")?;
+    let instance = Instance::new(&module, &imports! {})?;
+    assert_eq!(
+        instance.shutdown(std::time::Duration::from_millis(10))?,
+        ShutdownOutcome::Unsupported
+    );
+
+    Ok(())
+}
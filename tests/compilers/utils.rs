@@ -50,20 +50,40 @@ pub fn get_store(canonicalize_nans: bool) -> Store {
     Store::new(&get_engine(canonicalize_nans))
 }
 
-pub fn get_store_with_middlewares<I: Iterator<Item = Arc<dyn FunctionMiddlewareGenerator>>>(
-    middlewares: I,
+/// The builder of the engine of the tests.
+#[cfg(feature = "test-jit")]
+pub type EngineBuilder<'a> = JIT<'a>;
+#[cfg(feature = "test-native")]
+pub type EngineBuilder<'a> = Native<'a>;
+
+/// Creates a store with the compiler and the engine of the tests, after
+/// `compiler` and `engine` configured them.
+pub fn get_store_with(
+    compiler: impl FnOnce(&mut dyn CompilerConfig),
+    engine: impl FnOnce(EngineBuilder<'_>) -> EngineBuilder<'_>,
 ) -> Store {
     let mut compiler_config = get_compiler(false);
-    for x in middlewares {
-        compiler_config.push_middleware(x);
-    }
+    compiler(&mut compiler_config);
     #[cfg(feature = "test-jit")]
-    let engine = JIT::new(&compiler_config).engine();
+    let engine = engine(JIT::new(&compiler_config)).engine();
     #[cfg(feature = "test-native")]
-    let engine = Native::new(&mut compiler_config).engine();
+    let engine = engine(Native::new(&mut compiler_config)).engine();
     Store::new(&engine)
 }
 
+pub fn get_store_with_middlewares<I: Iterator<Item = Arc<dyn FunctionMiddlewareGenerator>>>(
+    middlewares: I,
+) -> Store {
+    get_store_with(
+        |compiler_config| {
+            for x in middlewares {
+                compiler_config.push_middleware(x);
+            }
+        },
+        |engine| engine,
+    )
+}
+
 #[cfg(feature = "test-jit")]
 pub fn get_headless_store() -> Store {
     Store::new(&JIT::headless().engine())
@@ -69,8 +69,10 @@ impl DummyArtifact {
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
             .module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| {
+                tunables.module_memory_style(&translation.module, index, memory_type)
+            })
             .collect();
         let table_styles: PrimaryMap<TableIndex, TableStyle> = translation
             .module
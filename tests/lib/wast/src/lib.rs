@@ -20,11 +20,15 @@
 
 mod error;
 mod spectest;
+mod suite;
+mod wasi_testsuite;
 mod wasi_wast;
 mod wast;
 
 pub use crate::error::{DirectiveError, DirectiveErrors};
 pub use crate::spectest::spectest_importobject;
+pub use crate::suite::{proposal_features, run_wast_suite, SuiteReport};
+pub use crate::wasi_testsuite::run_wasi_testsuite;
 pub use crate::wasi_wast::WasiTest;
 pub use crate::wast::Wast;
 
@@ -0,0 +1,108 @@
+use crate::wast::Wast;
+use anyhow::Result;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasmer::Features;
+
+/// The outcome of running a directory of tests, such as the WebAssembly
+/// spec test suite with [`run_wast_suite`], or the WASI test suite with
+/// [`run_wasi_testsuite`](crate::run_wasi_testsuite).
+#[derive(Debug, Default)]
+pub struct SuiteReport {
+    /// The tests that passed.
+    pub passed: Vec<PathBuf>,
+    /// The tests that failed, with their errors.
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl SuiteReport {
+    /// Whether all the tests passed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (path, error) in self.failed.iter() {
+            writeln!(f, "{}: {:?}", path.display(), error)?;
+        }
+        write!(
+            f,
+            "{} passed, {} failed",
+            self.passed.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// Runs all the `.wast` scripts under `path`, in the order of their
+/// paths, with a [`Wast`] created by `new_wast` for each of them.
+///
+/// `new_wast` gets the path of the script, e.g. to enable the features
+/// of its proposal with [`proposal_features`]. A script failing doesn't
+/// stop the run.
+pub fn run_wast_suite<F>(path: &Path, mut new_wast: F) -> Result<SuiteReport>
+where
+    F: FnMut(&Path) -> Result<Wast>,
+{
+    run_suite(path, "wast", |script| new_wast(script)?.run_file(script))
+}
+
+/// Runs `run_test` on all the files with `extension` under `path`, in
+/// the order of their paths.
+pub(crate) fn run_suite<F>(path: &Path, extension: &str, mut run_test: F) -> Result<SuiteReport>
+where
+    F: FnMut(&Path) -> Result<()>,
+{
+    let mut tests = Vec::new();
+    find_tests(path, extension, &mut tests)?;
+    tests.sort();
+
+    let mut report = SuiteReport::default();
+    for test in tests {
+        match run_test(&test) {
+            Ok(()) => report.passed.push(test),
+            Err(error) => report.failed.push((test, error)),
+        }
+    }
+    Ok(report)
+}
+
+fn find_tests(path: &Path, extension: &str, tests: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            find_tests(&entry?.path(), extension, tests)?;
+        }
+    } else if path.extension().map_or(false, |ext| ext == extension) {
+        tests.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Returns `features` with the features needed by the scripts of the
+/// proposal `path` belongs to, in the layout of the spec test suite
+/// (`proposals/<proposal>/<script>.wast`).
+pub fn proposal_features(path: &Path, mut features: Features) -> Features {
+    let in_proposal = |proposal: &str| {
+        path.components()
+            .any(|component| component.as_os_str() == proposal)
+    };
+    if in_proposal("bulk-memory-operations") {
+        features.bulk_memory(true);
+    }
+    if in_proposal("multi-value") {
+        features.multi_value(true);
+    }
+    if in_proposal("reference-types") {
+        features.reference_types(true);
+    }
+    if in_proposal("simd") {
+        features.simd(true);
+    }
+    if in_proposal("threads") {
+        features.threads(true);
+    }
+    features
+}
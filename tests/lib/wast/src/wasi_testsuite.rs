@@ -0,0 +1,92 @@
+//! Running the [WASI test suite] against the WASI implementation.
+//!
+//! [WASI test suite]: https://github.com/WebAssembly/wasi-testsuite
+
+use crate::suite::{run_suite, SuiteReport};
+use crate::wasi_wast::{get_stdout_output, OutputCapturerer};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use wasmer::{Module, Store};
+use wasmer_wasi::WasiState;
+
+/// The specification of a test, in the `.json` file next to its `.wasm`
+/// file. A test without specification runs without arguments and must
+/// exit with 0.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WasiTestSpec {
+    args: Vec<String>,
+    dirs: Vec<String>,
+    env: HashMap<String, String>,
+    exit_code: u32,
+    stdout: Option<String>,
+}
+
+/// Runs all the tests of the WASI test suite under `path` with `store`.
+///
+/// The directories preopened by a test are copied from the fixtures
+/// next to it into a temporary directory first, so the tests can change
+/// them and still be run again.
+pub fn run_wasi_testsuite(store: &Store, path: &Path) -> Result<SuiteReport> {
+    run_suite(path, "wasm", |test| run_wasi_test(store, test))
+}
+
+fn run_wasi_test(store: &Store, test: &Path) -> Result<()> {
+    let spec_path = test.with_extension("json");
+    let spec: WasiTestSpec = if spec_path.exists() {
+        serde_json::from_slice(&fs::read(&spec_path)?)
+            .with_context(|| format!("invalid test specification `{}`", spec_path.display()))?
+    } else {
+        WasiTestSpec::default()
+    };
+
+    let fixtures = test.parent().unwrap_or_else(|| Path::new("."));
+    let temp_dir = tempfile::tempdir()?;
+    let mut builder = WasiState::new(test.file_name().unwrap().to_string_lossy());
+    for dir in &spec.dirs {
+        let copy = temp_dir.path().join(dir);
+        copy_dir(&fixtures.join(dir), &copy)?;
+        builder.map_dir(dir, copy)?;
+    }
+    for (name, value) in &spec.env {
+        builder.env(name, value);
+    }
+    let mut env = builder
+        .args(&spec.args)
+        .stdout(Box::new(OutputCapturerer::new()))
+        .finalize()?;
+
+    let module = Module::new(store, fs::read(test)?)?;
+    let exit_code = env.run_command(&module)?;
+    if exit_code != spec.exit_code {
+        bail!(
+            "exited with code {} instead of {}",
+            exit_code,
+            spec.exit_code
+        );
+    }
+    if let Some(expected) = &spec.stdout {
+        let stdout = get_stdout_output(&env.state())?;
+        if &stdout != expected {
+            bail!("printed {:?} instead of {:?}", stdout, expected);
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
@@ -6,8 +6,8 @@ use std::path::PathBuf;
 use wasmer::{ImportObject, Instance, Memory, Module, Store};
 use wasmer_wasi::types::{__wasi_filesize_t, __wasi_timestamp_t};
 use wasmer_wasi::{
-    generate_import_object_from_env, get_wasi_version, WasiEnv, WasiFile, WasiFsError, WasiState,
-    WasiVersion,
+    generate_import_object_from_env, get_wasi_version, TtyPolicy, WasiEnv, WasiFile, WasiFsError,
+    WasiState, WasiVersion,
 };
 use wast::parser::{self, Parse, ParseBuffer, Parser};
 
@@ -28,7 +28,7 @@ pub struct WasiTest<'a> {
 // TODO: add `test_fs` here to sandbox better
 const BASE_TEST_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../wasi-wast/wasi/");
 
-fn get_stdout_output(wasi_state: &WasiState) -> anyhow::Result<String> {
+pub(crate) fn get_stdout_output(wasi_state: &WasiState) -> anyhow::Result<String> {
     let stdout_boxed = wasi_state.fs.stdout()?.as_ref().unwrap();
     let stdout = (&**stdout_boxed)
         .downcast_ref::<OutputCapturerer>()
@@ -149,6 +149,9 @@ impl<'a> WasiTest<'a> {
             //.env("RUST_BACKTRACE", "1")
             .stdout(Box::new(OutputCapturerer::new()))
             .stderr(Box::new(OutputCapturerer::new()))
+            // the tests expect stdio to be terminals, as the captured output
+            // stands for the terminal of the tests
+            .tty_policy(TtyPolicy::Always)
             .finalize()?;
         Ok((out, temp_dirs))
     }
@@ -433,12 +436,12 @@ mod test {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct OutputCapturerer {
+pub(crate) struct OutputCapturerer {
     output: Vec<u8>,
 }
 
 impl OutputCapturerer {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self { output: vec![] }
     }
 }